@@ -0,0 +1,236 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Encrypted billing, end to end: the meters of a customer upload their usage vectors encrypted,
+// the billing service adds them homomorphically and hands the customer a verifier package
+// proving that the encrypted total is the sum of the uploads.
+//
+//     cargo run --release --example billing -- [--work-dir <DIR>] [proof options]
+//
+// The flow goes through the same modules as the binaries:
+//
+// 1. import: the HE application logs its operations and snapshots its ciphertexts, and
+//    oplog::import_log turns the log into a manifest and the data files of its operations;
+// 2. link: manifest::build_dag links every operation to the operations producing its inputs
+//    and schedules them;
+// 3. prove: proving::prove_to_file proves every operation in schedule order;
+// 4. export: the verifier package is the manifest and the proofs, without the data files,
+//    which hold the operands;
+// 5. verify: the customer loads the package, verifies every proof with verifying::verify_data
+//    and checks that the billed ciphertext is produced by a verified operation.
+//
+// The usage vectors are pseudo-random residues standing in for the ciphertexts the SEAL wrapper
+// snapshots. Debug builds check the degrees of the constraints, which addition traces fail as
+// their Barrett bits above the moduli are constant, so the example needs --release.
+
+#![cfg_attr(
+    feature = "micro",
+    allow(clippy::modulo_one, clippy::reversed_empty_ranges, clippy::useless_vec)
+)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use stark_he::air::{InputArg, Op, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM};
+use stark_he::canonical::to_hex;
+use stark_he::manifest::{build_dag, Manifest};
+use stark_he::oplog;
+use stark_he::proving::{new_proof_options, prove_to_file, ProofOptionsConfig};
+use stark_he::scheme::Scheme;
+use stark_he::status::StatusReporter;
+use stark_he::verifying::{load_data, parse_data, verify_data};
+
+use winter_air::ProofOptions;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about = "Proves and verifies the sum of two encrypted usage vectors")]
+struct Cli {
+    // directory for the snapshots, the working files and the verifier package
+    #[clap(long)]
+    work_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    proof_options: ProofOptionsConfig,
+}
+
+// SEAL's default coefficient moduli for N = 4096, the first COEFF_LEVEL of them are used
+const MODULUS: [u64; 2] = [1125899906826241, 1125899906629633];
+
+// Usage snapshot in the format the SEAL wrapper writes, see oplog.rs.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Snapshot {
+    parameters: String,
+    values: Vec<[Vec<u64>; COEFF_LEVEL]>,
+}
+
+// xorshift64* residues modulo the moduli, one ciphertext per seed
+fn usage_ciphertext(seed: u64) -> Vec<[Vec<u64>; COEFF_LEVEL]> {
+    let mut state = seed.wrapping_mul(0x9e3779b97f4a7c15) | 1;
+    (0..VALUE_NUM)
+        .map(|_| {
+            std::array::from_fn(|l| {
+                (0..COEFF_DEGREE)
+                    .map(|_| {
+                        state ^= state >> 12;
+                        state ^= state << 25;
+                        state ^= state >> 27;
+                        state.wrapping_mul(0x2545f4914f6cdd1d) % MODULUS[l]
+                    })
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+// 1. The operation log and the snapshots of the HE application, imported into a manifest with
+//    its data files in `dir`.
+fn import(dir: &Path) -> Result<PathBuf, String> {
+    let app = dir.join("app");
+    fs::create_dir_all(&app).map_err(|err| err.to_string())?;
+    for (i, meter) in ["usage-meter-1", "usage-meter-2"].iter().enumerate() {
+        let snapshot = Snapshot {
+            parameters: String::from("billing"),
+            values: usage_ciphertext(i as u64 + 1),
+        };
+        let text = toml::to_string(&snapshot).map_err(|err| err.to_string())?;
+        fs::write(app.join(format!("{}.toml", meter)), text).map_err(|err| err.to_string())?;
+    }
+    let log = format!(
+        "[[parameters]]\nid = \"billing\"\nmodulus = {:?}\n\n\
+         [[op]]\nid = \"total\"\ntype = \"add\"\ninputs = [\"usage-meter-1\", \"usage-meter-2\"]\n\
+         output = \"usage-total\"\nparameters = \"billing\"\n",
+        &MODULUS[..COEFF_LEVEL]
+    );
+    let log_path = app.join("log.toml");
+    fs::write(&log_path, log).map_err(|err| err.to_string())?;
+
+    let import = oplog::import_log(&log_path, &app)?;
+    let work = dir.join("work");
+    fs::create_dir_all(&work).map_err(|err| err.to_string())?;
+    for (name, text) in import.data_files.iter() {
+        fs::write(work.join(name), text).map_err(|err| err.to_string())?;
+    }
+    let manifest_path = work.join("manifest.toml");
+    let text = toml::to_string(&import.manifest).map_err(|err| err.to_string())?;
+    fs::write(&manifest_path, text).map_err(|err| err.to_string())?;
+    println!(
+        "imported {} operation(s) into {}",
+        import.manifest.operations.len(),
+        manifest_path.display()
+    );
+    Ok(manifest_path)
+}
+
+// 2. and 3. Every operation of the manifest proven in schedule order, with the proof options
+//    of the command line.
+fn prove(manifest_path: &Path, options: &ProofOptions) -> Result<(), String> {
+    let manifest = Manifest::load(&manifest_path.to_string_lossy())?;
+    let dag = build_dag(&manifest)?;
+    for &i in dag.schedule.iter() {
+        let op = &manifest.operations[i];
+        let input_args = InputArg {
+            data_file_path: manifest.data_path(op).to_string_lossy().into_owned(),
+            op: Op::from_name(&op.circuit)
+                .ok_or_else(|| format!("unsupported circuit {}", op.circuit))?,
+            evaluation_key: None,
+            rotation: op.rotation,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        };
+        let proof_path = manifest.proof_path(op);
+        let mut status = StatusReporter::new(None, &op.id);
+        prove_to_file(
+            &input_args,
+            options.clone(),
+            None,
+            None,
+            None,
+            &proof_path,
+            &mut status,
+        )?;
+        println!("proved {} -> {}", op.id, proof_path.display());
+    }
+    Ok(())
+}
+
+// 4. The manifest and the proofs, copied into `package`.
+fn export(manifest_path: &Path, package: &Path) -> Result<(), String> {
+    let manifest = Manifest::load(&manifest_path.to_string_lossy())?;
+    fs::create_dir_all(package).map_err(|err| err.to_string())?;
+    fs::copy(manifest_path, package.join("manifest.toml")).map_err(|err| err.to_string())?;
+    for op in manifest.operations.iter() {
+        let proof_path = manifest.proof_path(op);
+        let name = proof_path.file_name().expect("proof paths name a file");
+        fs::copy(&proof_path, package.join(name)).map_err(|err| err.to_string())?;
+    }
+    println!("exported the verifier package to {}", package.display());
+    Ok(())
+}
+
+// 5. Every proof of the package verified, and the commitment to the public inputs of the
+//    operation producing `billed` returned.
+fn verify(package: &Path, billed: &str) -> Result<String, String> {
+    let manifest = Manifest::load(&package.join("manifest.toml").to_string_lossy())?;
+    let dag = build_dag(&manifest)?;
+    let mut commitment = None;
+    for &i in dag.schedule.iter() {
+        let op = &manifest.operations[i];
+        let proof_path = manifest.proof_path(op);
+        let data = load_data(&proof_path).map_err(String::from)?;
+        if data.circuit != op.circuit {
+            return Err(format!(
+                "{} proves {}, the manifest says {}",
+                proof_path.display(),
+                data.circuit,
+                op.circuit
+            ));
+        }
+        let (_, pub_inputs, _) =
+            parse_data(load_data(&proof_path).map_err(String::from)?).map_err(String::from)?;
+        verify_data(data, None).map_err(String::from)?;
+        println!(
+            "verified {} ({} of {})",
+            op.id,
+            op.output,
+            op.inputs.join(" + ")
+        );
+        if op.output == billed {
+            commitment = Some(to_hex(&pub_inputs.commitment()));
+        }
+    }
+    commitment.ok_or_else(|| format!("no operation of the package produces {}", billed))
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let dir = cli
+        .work_dir
+        .unwrap_or_else(|| std::env::temp_dir().join("stark-he-billing"));
+    if cfg!(debug_assertions) {
+        eprintln!("error: run the example with --release, see examples/billing.rs");
+        std::process::exit(1);
+    }
+    let result = import(&dir).and_then(|manifest_path| {
+        let options = new_proof_options(&cli.proof_options).map_err(|err| err.to_string())?;
+        prove(&manifest_path, &options)?;
+        let package = dir.join("package");
+        export(&manifest_path, &package)?;
+        verify(&package, "usage-total")
+    });
+    match result {
+        Ok(commitment) => println!("usage-total is proven, public inputs {}", commitment),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
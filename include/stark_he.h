@@ -0,0 +1,79 @@
+#ifndef STARK_HE_H
+#define STARK_HE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define STARK_HE_OK 0
+
+#define STARK_HE_INVALID_ARGUMENT 1
+
+#define STARK_HE_INVALID_INPUT 2
+
+#define STARK_HE_BAD_FORMAT 3
+
+#define STARK_HE_PARAMS_REJECTED 4
+
+#define STARK_HE_PUBLIC_INPUT_MISMATCH 5
+
+#define STARK_HE_CONSTRAINT_FAILURE 6
+
+#define STARK_HE_FRI_FAILURE 7
+
+#define STARK_HE_SIGNATURE_INVALID 8
+
+#define STARK_HE_INTERNAL_ERROR 9
+
+#define STARK_HE_PROFILE_FAST 0
+
+#define STARK_HE_PROFILE_BALANCED 1
+
+#define STARK_HE_PROFILE_PARANOID 2
+
+typedef struct StarkHeBuffer {
+  uint8_t *data;
+  uintptr_t len;
+} StarkHeBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Proves the `add` data file of `data_len` bytes at `data` with the proof options of
+// `profile_code`, a STARK_HE_PROFILE_*, and stores the proof file in `proof`.
+//
+// # Safety
+//
+// `data` points to `data_len` readable bytes; `proof` and `error`, which may be NULL, point to
+// writable buffers; a NULL `proof` fails with STARK_HE_INVALID_ARGUMENT.
+int32_t stark_he_prove(const uint8_t *data,
+                       uintptr_t data_len,
+                       uint32_t profile_code,
+                       struct StarkHeBuffer *proof,
+                       struct StarkHeBuffer *error);
+
+// Verifies the proof file of `proof_len` bytes at `proof`.
+//
+// # Safety
+//
+// `proof` points to `proof_len` readable bytes; `error`, which may be NULL, points to a
+// writable buffer.
+int32_t stark_he_verify(const uint8_t *proof, uintptr_t proof_len, struct StarkHeBuffer *error);
+
+// Releases a buffer returned by the library; empty buffers are ignored.
+//
+// # Safety
+//
+// `buffer` was returned by the library and is not used afterwards.
+void stark_he_buffer_free(struct StarkHeBuffer buffer);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* STARK_HE_H */
@@ -0,0 +1,236 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Python bindings of the library, the `stark_he` module maturin builds from pyproject.toml: the
+// CustomData of an `add` data file, the Proof of a proof file and `prove` and `verify` over them,
+// so that Python HE code (Pyfhel, TenSEAL) proves its ciphertexts in-process instead of writing
+// TOML for the command lines. Proving and verifying release the GIL.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+
+use stark_he::air::{CustomData, Data, VALUE_NUM};
+use stark_he::error::StarkHeError;
+use stark_he::params::Profile;
+use stark_he::verifying::{decode_data, verify_data, VerificationFailure};
+use stark_he::Proof;
+
+create_exception!(stark_he, Error, PyException);
+create_exception!(stark_he, VerificationError, Error);
+
+const PROFILES: [Profile; 3] = [Profile::Fast, Profile::Balanced, Profile::Paranoid];
+
+// The FRI folding factor and remainder size of `prover` by default.
+const FOLDING_FACTOR: usize = 8;
+const FRI_MAX_REMAINDER_SIZE: usize = 256;
+
+fn error(err: StarkHeError) -> PyErr {
+    match err {
+        StarkHeError::Verification(failure) => verification_error(failure),
+        err => Error::new_err(err.to_string()),
+    }
+}
+
+// Raised with two arguments, the kind of the failure (one of verifying::FAILURE_KINDS, like
+// "constraint-failure") and its message.
+fn verification_error(failure: VerificationFailure) -> PyErr {
+    VerificationError::new_err((failure.kind(), failure.to_string()))
+}
+
+// The operands of `add`, the fields of its data file: `values` holds one entry per operand, the
+// last subtracted from the sum of the others, of VALUE_NUM components with one coefficient list
+// per level.
+#[pyclass(name = "CustomData")]
+#[derive(Clone)]
+struct PyCustomData {
+    #[pyo3(get, set)]
+    modulus: Vec<u64>,
+    #[pyo3(get, set)]
+    values: Vec<Vec<Vec<Vec<u64>>>>,
+    #[pyo3(get, set)]
+    coeff_degree: Option<usize>,
+    #[pyo3(get, set)]
+    levels: Option<Vec<usize>>,
+}
+
+#[pymethods]
+impl PyCustomData {
+    #[new]
+    #[pyo3(signature = (modulus, values, coeff_degree = None, levels = None))]
+    fn new(
+        modulus: Vec<u64>,
+        values: Vec<Vec<Vec<Vec<u64>>>>,
+        coeff_degree: Option<usize>,
+        levels: Option<Vec<usize>>,
+    ) -> Self {
+        PyCustomData {
+            modulus,
+            values,
+            coeff_degree,
+            levels,
+        }
+    }
+
+    #[staticmethod]
+    fn from_toml(text: &str) -> PyResult<Self> {
+        let data: CustomData =
+            toml::from_str(text).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(PyCustomData {
+            modulus: data.modulus,
+            values: data.values.into_iter().map(Vec::from).collect(),
+            coeff_degree: data.coeff_degree,
+            levels: data.levels,
+        })
+    }
+
+    fn to_toml(&self) -> PyResult<String> {
+        toml::to_string(&self.to_custom_data()?).map_err(|err| Error::new_err(err.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CustomData(modulus={:?}, operands={}, coeff_degree={:?}, levels={:?})",
+            self.modulus,
+            self.values.len(),
+            self.coeff_degree,
+            self.levels
+        )
+    }
+}
+
+impl PyCustomData {
+    fn to_custom_data(&self) -> PyResult<CustomData> {
+        let values = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(d, operand)| {
+                <[Vec<Vec<u64>>; VALUE_NUM]>::try_from(operand.clone()).map_err(|operand| {
+                    PyValueError::new_err(format!(
+                        "operand {} has {} components, not {}",
+                        d,
+                        operand.len(),
+                        VALUE_NUM
+                    ))
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(CustomData {
+            modulus: self.modulus.clone(),
+            coeff_degree: self.coeff_degree,
+            levels: self.levels.clone(),
+            values,
+        })
+    }
+}
+
+// A proof file as `prover` writes it: the circuit, its results, the base64 proof and the
+// reproducibility manifest.
+#[pyclass(name = "Proof")]
+#[derive(Clone)]
+struct PyProof {
+    data: Data,
+}
+
+#[pymethods]
+impl PyProof {
+    #[getter]
+    fn circuit(&self) -> &str {
+        &self.data.circuit
+    }
+
+    #[getter]
+    fn format_version(&self) -> u32 {
+        self.data.format_version
+    }
+
+    #[getter]
+    fn result(&self) -> Vec<Vec<Vec<u64>>> {
+        self.data.result.clone()
+    }
+
+    #[getter]
+    fn proof(&self) -> &str {
+        &self.data.proof
+    }
+
+    #[getter]
+    fn security_bits(&self) -> Option<u32> {
+        self.data
+            .reproducibility
+            .as_ref()
+            .and_then(|manifest| manifest.security_bits)
+    }
+
+    // Proof files of every circuit read, sealed ones are rejected.
+    #[staticmethod]
+    fn from_toml(text: &str) -> PyResult<Self> {
+        let data =
+            decode_data(text.as_bytes().to_vec(), None, "proof").map_err(verification_error)?;
+        Ok(PyProof { data })
+    }
+
+    fn to_toml(&self) -> PyResult<String> {
+        toml::to_string(&self.data).map_err(|err| Error::new_err(err.to_string()))
+    }
+
+    fn verify(&self, py: Python<'_>) -> PyResult<()> {
+        verify(py, self)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Proof(circuit={:?}, format_version={}, security_bits={:?})",
+            self.data.circuit,
+            self.data.format_version,
+            self.security_bits()
+        )
+    }
+}
+
+// Proves the addition of the operands of `data` with the proof options of `profile`, one of the
+// profiles of `prover --profile`.
+#[pyfunction]
+#[pyo3(signature = (data, profile = "balanced"))]
+fn prove(py: Python<'_>, data: &PyCustomData, profile: &str) -> PyResult<PyProof> {
+    let options = PROFILES
+        .iter()
+        .find(|candidate| candidate.name() == profile)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown profile {}, expected fast, balanced or paranoid",
+                profile
+            ))
+        })?
+        .proof_options(FOLDING_FACTOR, FRI_MAX_REMAINDER_SIZE);
+    let data = data.to_custom_data()?;
+    let data = py
+        .allow_threads(|| stark_he::prove(&data, options).map(Proof::to_data))
+        .map_err(error)?;
+    Ok(PyProof { data })
+}
+
+// Verifies `proof` with the checks of `verifier` on its proof file; proofs of circuits with an
+// evaluation key are rejected, as the module cannot load one.
+#[pyfunction]
+fn verify(py: Python<'_>, proof: &PyProof) -> PyResult<()> {
+    let data = proof.data.clone();
+    py.allow_threads(|| verify_data(data, None))
+        .map_err(verification_error)
+}
+
+#[pymodule]
+#[pyo3(name = "stark_he")]
+fn stark_he_python(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyCustomData>()?;
+    m.add_class::<PyProof>()?;
+    m.add_function(wrap_pyfunction!(prove, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    m.add("Error", py.get_type::<Error>())?;
+    m.add("VerificationError", py.get_type::<VerificationError>())?;
+    m.add("VALUE_NUM", VALUE_NUM)?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+from typing import List, Optional
+
+VALUE_NUM: int
+
+class Error(Exception): ...
+
+# args: (kind, message), kind one of "bad-format", "params-rejected", "public-input-mismatch",
+# "constraint-failure", "fri-failure", "signature-invalid"
+class VerificationError(Error): ...
+
+class CustomData:
+    modulus: List[int]
+    values: List[List[List[List[int]]]]
+    coeff_degree: Optional[int]
+    levels: Optional[List[int]]
+    def __init__(
+        self,
+        modulus: List[int],
+        values: List[List[List[List[int]]]],
+        coeff_degree: Optional[int] = None,
+        levels: Optional[List[int]] = None,
+    ) -> None: ...
+    @staticmethod
+    def from_toml(text: str) -> "CustomData": ...
+    def to_toml(self) -> str: ...
+
+class Proof:
+    @property
+    def circuit(self) -> str: ...
+    @property
+    def format_version(self) -> int: ...
+    @property
+    def result(self) -> List[List[List[int]]]: ...
+    @property
+    def proof(self) -> str: ...
+    @property
+    def security_bits(self) -> Optional[int]: ...
+    @staticmethod
+    def from_toml(text: str) -> "Proof": ...
+    def to_toml(self) -> str: ...
+    def verify(self) -> None: ...
+
+def prove(data: CustomData, profile: str = "balanced") -> Proof: ...
+def verify(proof: Proof) -> None: ...
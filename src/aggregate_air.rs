@@ -0,0 +1,381 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Aggregation of many records, e.g. the ciphertexts a private statistics service receives from
+// its clients: the sum of up to AGGREGATE_MAX_OPERANDS ciphertexts and, optionally, the sum
+// multiplied by a public scalar w, e.g. the inverse of the number of records modulo the plain
+// modulus, which makes it the mean. The running sum is SumAir's, whose columns come first; the
+// records are read one at a time from a directory of ciphertext files while the trace is filled,
+// so the prover never holds more than one of them besides the trace. The last circuit row of
+// every coefficient also proves, for both components and every level,
+//
+//     s = r * w mod m
+//
+// with the sum r of the row and the scaled sum s. The sums and the scaled sums are reduced by
+// the Barrett blocks of reduction.rs against the moduli of the public inputs, the scaled sums
+// under a periodic flag of the last circuit rows, so the other rows reduce zero to zero. Without
+// a scalar, the scaled sums stay zero. The records and their number are private: the proof shows
+// that the result is the sum of some ciphertexts under the public moduli, not of which.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+use winter_prover::Trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    bind_moduli, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::scalar_mul_air::{check_scalar, SCALAR_MAX_BITS};
+use crate::sum_air::{
+    evaluate_links, link_column, link_degrees, read_sums, result_column, sum_assertions, sum_slots,
+    sum_values, RunningSum, SUM_DATA_END, SUM_MAX_MODULUS_BITS,
+};
+
+// Columns of SumAir + Scaled + Reduction
+// M0 M1 R00 .. R11 P00 .. P11 D00 .. D11 S00 S01 S10 S11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const SCALED_START: usize = SUM_DATA_END;
+const SCALED_END: usize = SCALED_START + RESULT_LEN;
+// the sums, then the scaled sums
+const REDUCTION_NUM: usize = 2 * RESULT_LEN;
+// a scaled sum is below w * m, so its quotient below the scalar
+const REDUCTION_WIDTH: usize =
+    reduction_width(SCALAR_MAX_BITS as usize, SUM_MAX_MODULUS_BITS as usize);
+// one row per sum and scaled sum, then padding
+pub const AGGREGATE_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = SCALED_END + REDUCTION_WIDTH;
+
+// Most records of one proof; the trace of the largest aggregation has COEFF_DEGREE * 2^16
+// circuit rows.
+pub const AGGREGATE_MAX_OPERANDS: usize = 1 << 16;
+// The sum and the scaled sum.
+pub const AGGREGATE_RESULT_NUM: usize = 2 * VALUE_NUM;
+
+// Trace layout constants of AggregateAir in canonical order. The trace length follows from the
+// number of records, as for SumAir.
+pub fn aggregate_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("max_operands", AGGREGATE_MAX_OPERANDS),
+        ("group", AGGREGATE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// `Records` names a directory of ciphertexts in the format of CiphertextData, one per `.toml`
+// file, taken in the order of their file names; a relative path is relative to the directory of
+// the data file. `Mean` is the optional scalar.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AggregateData {
+    pub modulus: Vec<u64>,
+    pub records: String,
+    pub mean: Option<u64>,
+}
+
+fn scaled_column(v: usize, l: usize) -> usize {
+    level_column(SCALED_START, v, l)
+}
+
+// Checks that a trace of `length` rows holds an aggregation of at most AGGREGATE_MAX_OPERANDS
+// records.
+pub fn check_aggregate_length(length: usize) -> Result<(), String> {
+    let rows = COEFF_DEGREE * AGGREGATE_GROUP;
+    let slots = length / rows;
+    if !length.is_multiple_of(rows)
+        || !slots.is_power_of_two()
+        || slots < 2
+        || slots > sum_slots(AGGREGATE_MAX_OPERANDS)
+    {
+        return Err(format!(
+            "aggregation traces have COEFF_DEGREE * {} times 2 to {} rows, not {}",
+            AGGREGATE_GROUP,
+            sum_slots(AGGREGATE_MAX_OPERANDS),
+            length
+        ));
+    }
+    Ok(())
+}
+
+// Record files of a data file, in the order they are added.
+pub fn record_paths(data_file_path: &str, records: &str) -> Result<Vec<PathBuf>, StarkHeError> {
+    let dir = Path::new(data_file_path)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(records);
+    let read_error = |err| StarkHeError::io(&dir.display().to_string(), err);
+    let entries = fs::read_dir(&dir).map_err(read_error)?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(read_error)?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    if !(2..=AGGREGATE_MAX_OPERANDS).contains(&paths.len()) {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "an aggregation has 2 to {} records, {} holds {}",
+            AGGREGATE_MAX_OPERANDS,
+            dir.display(),
+            paths.len()
+        )));
+    }
+    Ok(paths)
+}
+
+fn load_record(path: &Path) -> Result<CiphertextData, StarkHeError> {
+    load_data_file(&path.to_string_lossy())
+}
+
+// Mean scalar of a data file, which the prover records in the public inputs.
+pub fn load_mean(data_file_path: &str) -> Result<Option<u64>, StarkHeError> {
+    let data: AggregateData = load_data_file(data_file_path)?;
+    if let Some(scalar) = data.mean {
+        check_scalar(scalar).map_err(StarkHeError::InvalidInput)?;
+    }
+    Ok(data.mean)
+}
+
+// The reductions of a row by the public moduli `modulus`, with the scalar `scalar` if any: the
+// sums to their result columns, then the scaled sums to the scaled columns.
+pub fn aggregate_reductions(modulus: &[u64], scalar: Option<u64>) -> Result<Reductions, String> {
+    check_public_moduli(Op::Aggregate, modulus, SUM_MAX_MODULUS_BITS)?;
+    let w = scalar.unwrap_or(0) as u128;
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for (column, bound) in [
+        (result_column as fn(usize, usize) -> usize, 2),
+        (scaled_column, w),
+    ] {
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                results.push(column(v, l));
+                moduli.push(modulus[l]);
+                bounds.push(bound * (modulus[l] as u128 - 1));
+            }
+        }
+    }
+    // without a scalar the scaled sums are zero, and copies of them would vanish
+    let copied_end = match scalar {
+        Some(_) => SCALED_END,
+        None => SCALED_START,
+    };
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (MODULUS_NUM..copied_end).collect(),
+        SCALED_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_aggregate_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: AggregateData = load_data_file(&arg.data_file_path)?;
+    let paths = record_paths(&arg.data_file_path, &data.records)?;
+    if let Some(scalar) = data.mean {
+        check_scalar(scalar).map_err(StarkHeError::InvalidInput)?;
+    }
+    check_modulus_bits(&data.modulus, SUM_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    let reductions =
+        aggregate_reductions(&data.modulus, data.mean).map_err(StarkHeError::InvalidInput)?;
+    let slots = sum_slots(paths.len());
+
+    let mut sum = RunningSum::new(&data.modulus, slots, SCALED_END);
+    for i in 0..slots {
+        let record = paths.get(i).map(|path| load_record(path)).transpose()?;
+        if let Some(record) = &record {
+            if record.modulus != data.modulus {
+                return Err(StarkHeError::InvalidInput(format!(
+                    "record {} has other moduli than the data file",
+                    paths[i].display()
+                )));
+            }
+            check_levels(&data.modulus, COEFF_DEGREE, &record.values)?;
+        }
+        sum.add(
+            &data.modulus,
+            slots,
+            i,
+            record.as_ref().map(|record| &record.values),
+        );
+    }
+    // the scaled sums of the last circuit row of every coefficient
+    let mut scaled = vec![[0; RESULT_LEN]; sum.values.len()];
+    if let Some(w) = data.mean {
+        for k in 0..COEFF_DEGREE {
+            let row = k * slots + slots - 1;
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    let t = sum.sums[k][v][l] as u128 * w as u128;
+                    let m = data.modulus[l] as u128;
+                    scaled[row][v * COEFF_LEVEL + l] = t;
+                    sum.columns[scaled_column(v, l)][row] = element_from_u64((t % m) as u64);
+                }
+            }
+        }
+    }
+    let values = sum.values;
+    Ok(
+        reductions.build_trace(sum.columns, STATE_WIDTH, |k, j| match j {
+            j if j < RESULT_LEN => values[k][j],
+            j => scaled[k][j - RESULT_LEN],
+        }),
+    )
+}
+
+// The results are the sums of the last circuit row of every coefficient, followed with a scalar
+// by the scaled sums, and the scalar.
+pub fn get_aggregate_pub_inputs(trace: &TraceType, mean: Option<u64>) -> PublicInputs {
+    let slots = trace.length() / (COEFF_DEGREE * AGGREGATE_GROUP);
+    let mut result = read_sums(trace, slots, AGGREGATE_GROUP, result_column);
+    if mean.is_some() {
+        result.extend(read_sums(trace, slots, AGGREGATE_GROUP, scaled_column));
+    }
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: mean,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The flags of the last circuit row of every coefficient of `slots` operand slots.
+fn last_column(slots: usize) -> Vec<BaseElement> {
+    (0..slots * AGGREGATE_GROUP)
+        .map(|i| element_from_u64((i / AGGREGATE_GROUP == slots - 1) as u64))
+        .collect()
+}
+
+pub struct AggregateAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    scalar: Option<BaseElement>,
+    slots: usize,
+}
+
+impl Air for AggregateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, then the links of the running sum as in SumAir. With a
+    // scalar, the scaled sums are selected by the flag of the last circuit rows as well.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = trace_info.length() / (COEFF_DEGREE * AGGREGATE_GROUP);
+        let modulus = pub_inputs
+            .modulus
+            .expect("aggregate proofs record their moduli");
+        let reductions = aggregate_reductions(&modulus, pub_inputs.scalar)
+            .expect("aggregate moduli and scalar were checked");
+        let cycles = match pub_inputs.scalar {
+            Some(_) => vec![slots * AGGREGATE_GROUP],
+            None => Vec::new(),
+        };
+        let mut degrees = reductions.degrees(1, &cycles);
+        degrees.extend(link_degrees(slots, AGGREGATE_GROUP));
+        let num_assertions =
+            RESULT_LEN + pub_inputs.result.len() * COEFF_LEVEL + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        AggregateAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            scalar: pub_inputs.scalar.map(element_from_u64),
+            slots,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let row = frame.current();
+        let flags = &periodic_values[self.reductions.num_periodic_columns()..];
+        let (link, last) = (flags[0], flags[1]);
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        values[..RESULT_LEN].copy_from_slice(&sum_values(row));
+        if let Some(scalar) = self.scalar {
+            let w = E::from(scalar);
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    values[RESULT_LEN + v * COEFF_LEVEL + l] = last * row[result_column(v, l)] * w;
+                }
+            }
+        }
+        let (reduction, links) = result.split_at_mut(self.reductions.num_constraints());
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        evaluate_links(frame, link, links);
+    }
+
+    // The flags of the links and of the last circuit row of every coefficient.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut columns = self.reductions.periodic_columns();
+        columns.push(link_column(self.slots, AGGREGATE_GROUP));
+        columns.push(last_column(self.slots));
+        columns
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let (sums, scaled) = self.result.split_at(VALUE_NUM);
+        let mut assertions = sum_assertions(self.slots, AGGREGATE_GROUP, sums, result_column);
+        let cycle = self.slots * AGGREGATE_GROUP;
+        for (v, levels) in scaled.iter().enumerate() {
+            for l in 0..COEFF_LEVEL {
+                assertions.push(Assertion::sequence(
+                    scaled_column(v, l),
+                    (self.slots - 1) * AGGREGATE_GROUP,
+                    cycle,
+                    levels[l].clone(),
+                ));
+            }
+        }
+        assertions.extend(bind_moduli(&self.modulus, 0, cycle));
+        assertions
+    }
+}
@@ -3,360 +3,2287 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
-use winter_air::{
-    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
-    TransitionConstraintDegree,
-};
+#![allow(clippy::needless_range_loop)]
+
+use winter_air::{Assertion, ProofOptions, TraceInfo};
 use winter_math::FieldElement;
-use winter_prover::TraceTable;
-use winter_utils::{ByteWriter, Serializable};
+use winter_prover::{StarkProof, TraceTable};
+use winter_utils::{ByteReader, ByteWriter, Deserializable, DeserializationError, Serializable};
+
+use crate::aggregate_air::{aggregate_layout, aggregate_reductions, AGGREGATE_RESULT_NUM};
+use crate::barrett::{barrett_reduce, BARRETT_MODULUS_BITS};
+use crate::base_ext_air::{base_ext_layout, base_ext_reductions, BASE_EXT_RESULT_NUM};
+use crate::blind_rotate_air::{blind_rotate_layout, blind_rotate_reductions};
+use crate::canonical::{
+    commitment, from_hex, read_element, read_f64, read_option, read_sequence, read_tag, read_u64s,
+    to_hex, write_proof_options, CanonicalWriter, PARAMETERS_TAG, PUBLIC_INPUTS_EXCHANGE_TAG,
+    PUBLIC_INPUTS_TAG,
+};
+use crate::ciphertext::Ciphertext;
+use crate::ckks_encode_air::{
+    ckks_encode_layout, ckks_encode_reductions, CkksSlots, CKKS_ENCODE_RESULT_NUM,
+};
+use crate::compare_air::{compare_layout, compare_reductions, COMPARE_RESULT_NUM};
+use crate::decrypt_air::{decrypt_layout, decrypt_reductions, Decryption, DECRYPT_RESULT_NUM};
+use crate::decrypt_share_air::{
+    decrypt_share_layout, decrypt_share_reductions, DECRYPT_SHARE_RESULT_NUM,
+};
+use crate::encoding::ProofEncoding;
+use crate::encrypt_air::{encrypt_layout, encrypt_reductions, ENCRYPT_RESULT_NUM};
+use crate::error::StarkHeError;
+use crate::fma_air::{fma_layout, fma_reductions};
+use crate::he_security::HeSecurity;
+use crate::hoisted_rotate_air::{
+    hoisted_rotate_layout, hoisted_rotate_reductions, HOIST_RESULT_NUM,
+};
+use crate::horner_air::{horner_layout, horner_reductions, HORNER_RESULT_NUM};
+use crate::inner_product_air::{inner_product_layout, inner_product_reductions};
+use crate::intt_air::{intt_layout, intt_reductions};
+use crate::keyswitch_air::{keyswitch_layout, keyswitch_reductions, EvaluationKey};
+#[cfg(feature = "goldilocks")]
+use crate::limb_mul_air::limb_mul_layout;
+use crate::mat_vec_air::{mat_vec_layout, mat_vec_reductions};
+use crate::modraise_air::{modraise_layout, modraise_reductions};
+use crate::modswitch_air::{modswitch_layout, modswitch_reductions};
+use crate::mont_mul_air::{mont_mul_layout, mont_mul_reductions};
+use crate::mul_air::{mul_layout, mul_reductions, MUL_RESULT_NUM};
+use crate::multi_key_add_air::{
+    multi_key_add_layout, multi_key_add_reductions, MULTI_KEY_VALUE_NUM,
+};
+use crate::neg_air::{neg_layout, neg_reductions};
+use crate::ntt_air::{ntt_layout, ntt_reductions};
+use crate::operand_files::read_operand_files;
+use crate::pack_air::{pack_layout, pack_reductions};
+use crate::packed::read_custom_data;
+use crate::params::Params;
+use crate::pipeline_air::{pipeline_layout, pipeline_reductions};
+use crate::plain_mul_air::{plain_mul_layout, plain_mul_reductions};
+use crate::re_encrypt_air::{re_encrypt_layout, re_encrypt_reductions};
+use crate::reduction::Reductions;
+use crate::registry::{circuit_info, CircuitInfo};
+use crate::relin_air::{relin_layout, relin_reductions};
+use crate::reproducibility::ReproducibilityManifest;
+use crate::rescale_air::Scale;
+use crate::rotate_air::{rotate_layout, AutomorphismInput};
+use crate::scalar_mul_air::{scalar_mul_layout, scalar_mul_reductions};
+use crate::scheme::Scheme;
+use crate::sub_air::{sub_layout, sub_reductions};
+use crate::sum_air::{sum_layout, sum_reductions};
+use crate::unpack_air::{unpack_layout, unpack_reductions, UNPACK_RESULT_NUM};
+#[cfg(not(feature = "goldilocks"))]
+use crate::wide_mul_air::wide_mul_layout;
+use crate::wide_mul_air::wide_mul_reductions;
 
-use base64::{decode, encode};
-use clap::Args;
+use clap::{ArgEnum, Args};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-pub type BaseElement = winter_math::fields::f128::BaseElement;
+// The field, the `add` circuit and its layout are those of the verifier crate, see its lib.rs.
+pub use stark_he_verifier::fresh::{
+    check_coeff_degree, check_coeff_level, check_fresh_modulus, check_fresh_trace,
+    check_operand_num, fresh_group, fresh_operand_num, fresh_width_accepted, Boundary, Packing,
+    SchemeConfig, FRESH_MAX_OPERANDS, MAX_COEFFS_PER_ROW, MAX_COEFF_DEGREE, MAX_COEFF_LEVEL,
+    MIN_COEFF_DEGREE,
+};
+use stark_he_verifier::fresh::{fresh_width, FreshInputs, MODULUS_COLUMN};
+use stark_he_verifier::AddPublicInputs;
+pub use stark_he_verifier::{
+    check_field_extension, element_from_u128, element_from_u64, element_to_u128, element_to_u64,
+    BaseElement, COEFF_DEGREE, COEFF_LEVEL, FIELD_BITS, FIELD_MODULUS, FIELD_NAME,
+    MIN_FIELD_EXTENSION, VALUE_NUM,
+};
+
+// Homomorphic operation proven by a circuit. The short name is recorded in proof files and
+// manifests and used in proof file names.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Relin,
+    Rotate,
+    KeySwitch,
+    ModSwitch,
+    Rescale,
+    PlainMul,
+    Neg,
+    Ntt,
+    Intt,
+    Decrypt,
+    Encrypt,
+    CkksEncode,
+    ModRaise,
+    ScalarMul,
+    Fma,
+    Sum,
+    InnerProduct,
+    MatVec,
+    BaseExt,
+    MontMul,
+    WideMul,
+    BlindRotate,
+    Horner,
+    Aggregate,
+    DecryptShare,
+    Conjugate,
+    Pack,
+    Unpack,
+    MultiKeyAdd,
+    ReEncrypt,
+    HoistedRotate,
+    Compare,
+    KeyRotate,
+    KeyConjugate,
+    Pipeline,
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Sub => "sub",
+            Op::Mul => "mul",
+            Op::Relin => "relin",
+            Op::Rotate => "rotate",
+            Op::KeySwitch => "key-switch",
+            Op::ModSwitch => "mod-switch",
+            Op::Rescale => "rescale",
+            Op::PlainMul => "plain-mul",
+            Op::Neg => "neg",
+            Op::Ntt => "ntt",
+            Op::Intt => "intt",
+            Op::Decrypt => "decrypt",
+            Op::Encrypt => "encrypt",
+            Op::CkksEncode => "ckks-encode",
+            Op::ModRaise => "mod-raise",
+            Op::ScalarMul => "scalar-mul",
+            Op::Fma => "fma",
+            Op::Sum => "sum",
+            Op::InnerProduct => "inner-product",
+            Op::MatVec => "mat-vec",
+            Op::BaseExt => "base-ext",
+            Op::MontMul => "mont-mul",
+            Op::WideMul => "wide-mul",
+            Op::BlindRotate => "blind-rotate",
+            Op::Horner => "horner",
+            Op::Aggregate => "aggregate",
+            Op::DecryptShare => "decrypt-share",
+            Op::Conjugate => "conjugate",
+            Op::Pack => "pack",
+            Op::Unpack => "unpack",
+            Op::MultiKeyAdd => "multi-key-add",
+            Op::ReEncrypt => "re-encrypt",
+            Op::HoistedRotate => "hoisted-rotate",
+            Op::Compare => "compare",
+            Op::KeyRotate => "key-rotate",
+            Op::KeyConjugate => "key-conjugate",
+            Op::Pipeline => "pipeline",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Op> {
+        Op::from_str(name, false).ok()
+    }
+
+    // Whether the circuit proves over the field of this build. Every circuit does over the 128-bit
+    // field; over Goldilocks only those whose integer relations are bounded by FIELD_BITS: the
+    // additions, the multiplications reducing with reduction.rs, whose blocks check the bound for
+    // the public moduli, and wide-mul, which splits its residues into limbs. The others hold
+    // products of wide residues in single columns.
+    pub fn check_field(&self) -> Result<(), String> {
+        let supported = cfg!(not(feature = "goldilocks"))
+            || matches!(
+                self,
+                Op::Add
+                    | Op::Sub
+                    | Op::Neg
+                    | Op::Mul
+                    | Op::PlainMul
+                    | Op::ScalarMul
+                    | Op::Fma
+                    | Op::Sum
+                    | Op::WideMul
+            );
+        if !supported {
+            return Err(format!(
+                "{} proofs need the 128-bit field, this build is over {}",
+                self.name(),
+                FIELD_NAME
+            ));
+        }
+        Ok(())
+    }
+
+    // Number of ciphertext components in the result.
+    pub fn result_num(&self) -> usize {
+        match self {
+            Op::Add => VALUE_NUM,
+            Op::Sub => VALUE_NUM,
+            Op::Mul => MUL_RESULT_NUM,
+            Op::Relin => VALUE_NUM,
+            Op::Rotate => VALUE_NUM,
+            Op::KeySwitch => VALUE_NUM,
+            Op::ModSwitch => VALUE_NUM,
+            Op::Rescale => VALUE_NUM,
+            Op::PlainMul => VALUE_NUM,
+            Op::Neg => VALUE_NUM,
+            Op::Ntt => VALUE_NUM,
+            Op::Intt => VALUE_NUM,
+            Op::Decrypt => DECRYPT_RESULT_NUM,
+            Op::Encrypt => ENCRYPT_RESULT_NUM,
+            Op::CkksEncode => CKKS_ENCODE_RESULT_NUM,
+            Op::ModRaise => VALUE_NUM,
+            Op::ScalarMul => VALUE_NUM,
+            Op::Fma => MUL_RESULT_NUM,
+            Op::Sum => VALUE_NUM,
+            Op::InnerProduct => VALUE_NUM,
+            // per row of the matrix
+            Op::MatVec => VALUE_NUM,
+            // per target modulus
+            Op::BaseExt => BASE_EXT_RESULT_NUM,
+            Op::MontMul => MUL_RESULT_NUM,
+            Op::WideMul => MUL_RESULT_NUM,
+            Op::BlindRotate => VALUE_NUM,
+            // at most, one per coefficient of the polynomial
+            Op::Horner => HORNER_RESULT_NUM,
+            // at most, the sum and the scaled sum
+            Op::Aggregate => AGGREGATE_RESULT_NUM,
+            Op::DecryptShare => DECRYPT_SHARE_RESULT_NUM,
+            Op::Conjugate => VALUE_NUM,
+            Op::Pack => VALUE_NUM,
+            Op::Unpack => UNPACK_RESULT_NUM,
+            Op::MultiKeyAdd => MULTI_KEY_VALUE_NUM,
+            Op::ReEncrypt => VALUE_NUM,
+            Op::HoistedRotate => HOIST_RESULT_NUM,
+            Op::Compare => COMPARE_RESULT_NUM,
+            Op::KeyRotate => VALUE_NUM,
+            Op::KeyConjugate => VALUE_NUM,
+            Op::Pipeline => MUL_RESULT_NUM,
+        }
+    }
+
+    // The Barrett row groups of the circuits which reduce their values with reduction.rs, for
+    // the moduli in the public inputs; None for the other circuits.
+    pub fn reductions(&self, pub_inputs: &PublicInputs) -> Option<Result<Reductions, String>> {
+        let modulus = || {
+            pub_inputs
+                .modulus
+                .as_deref()
+                .ok_or_else(|| format!("{} proof does not record its moduli", self.name()))
+        };
+        match self {
+            Op::Mul => Some(modulus().and_then(mul_reductions)),
+            // over Goldilocks wide-mul checks its products limb by limb, see limb_mul_air.rs
+            Op::WideMul if cfg!(feature = "goldilocks") => None,
+            Op::WideMul => Some(modulus().and_then(wide_mul_reductions)),
+            Op::MontMul => Some(modulus().and_then(mont_mul_reductions)),
+            Op::PlainMul => Some(modulus().and_then(plain_mul_reductions)),
+            Op::Fma => Some(modulus().and_then(fma_reductions)),
+            Op::Relin => Some(modulus().and_then(relin_reductions)),
+            Op::KeySwitch => Some(modulus().and_then(keyswitch_reductions)),
+            Op::ModRaise => Some(modulus().and_then(modraise_reductions)),
+            Op::Ntt => Some(modulus().and_then(ntt_reductions)),
+            Op::Intt => Some(modulus().and_then(intt_reductions)),
+            Op::Decrypt => Some(modulus().and_then(|modulus| {
+                let plain_modulus = pub_inputs.plain_modulus.ok_or_else(|| {
+                    format!("{} proof does not record its plain modulus", self.name())
+                })?;
+                decrypt_reductions(modulus, plain_modulus)
+            })),
+            Op::DecryptShare => Some(modulus().and_then(decrypt_share_reductions)),
+            Op::Encrypt => Some(modulus().and_then(|modulus| {
+                let plain_modulus = pub_inputs.plain_modulus.ok_or_else(|| {
+                    format!("{} proof does not record its plain modulus", self.name())
+                })?;
+                encrypt_reductions(modulus, plain_modulus)
+            })),
+            Op::CkksEncode => Some(
+                pub_inputs
+                    .slots
+                    .as_ref()
+                    .ok_or_else(|| format!("{} proof does not record its slot values", self.name()))
+                    .and_then(|slots| slots.scale_bits())
+                    .and_then(ckks_encode_reductions),
+            ),
+            Op::Sub => Some(modulus().and_then(sub_reductions)),
+            Op::Neg => Some(modulus().and_then(neg_reductions)),
+            Op::MultiKeyAdd => Some(modulus().and_then(multi_key_add_reductions)),
+            Op::Sum => Some(modulus().and_then(sum_reductions)),
+            Op::BaseExt => Some(modulus().and_then(base_ext_reductions)),
+            Op::Compare => Some(modulus().and_then(compare_reductions)),
+            Op::BlindRotate => Some(modulus().and_then(blind_rotate_reductions)),
+            Op::ReEncrypt | Op::KeyRotate | Op::KeyConjugate => {
+                Some(modulus().and_then(re_encrypt_reductions))
+            }
+            Op::HoistedRotate => Some(modulus().and_then(hoisted_rotate_reductions)),
+            Op::Pack => Some(modulus().and_then(pack_reductions)),
+            Op::Unpack => Some(modulus().and_then(unpack_reductions)),
+            Op::Horner => Some(modulus().and_then(|modulus| {
+                let coefficients = pub_inputs.weights.as_deref().ok_or_else(|| {
+                    format!("{} proof does not record its coefficients", self.name())
+                })?;
+                horner_reductions(modulus, coefficients)
+            })),
+            Op::Pipeline => Some(modulus().and_then(|modulus| {
+                let steps = pub_inputs
+                    .weights
+                    .as_deref()
+                    .ok_or_else(|| format!("{} proof does not record its steps", self.name()))?;
+                pipeline_reductions(modulus, steps)
+            })),
+            Op::InnerProduct => Some(modulus().and_then(|modulus| {
+                let weights = pub_inputs
+                    .weights
+                    .as_deref()
+                    .ok_or_else(|| format!("{} proof does not record its weights", self.name()))?;
+                inner_product_reductions(modulus, weights)
+            })),
+            Op::MatVec => Some(modulus().and_then(|modulus| {
+                let weights = pub_inputs
+                    .weights
+                    .as_deref()
+                    .ok_or_else(|| format!("{} proof does not record its weights", self.name()))?;
+                mat_vec_reductions(modulus, weights)
+            })),
+            Op::Aggregate => {
+                Some(modulus().and_then(|modulus| aggregate_reductions(modulus, pub_inputs.scalar)))
+            }
+            Op::ModSwitch | Op::Rescale => {
+                Some(modulus().and_then(|modulus| modswitch_reductions(*self, modulus)))
+            }
+            Op::ScalarMul => Some(modulus().and_then(|modulus| {
+                let scalar = pub_inputs
+                    .scalar
+                    .ok_or_else(|| format!("{} proof does not record its scalar", self.name()))?;
+                scalar_mul_reductions(modulus, scalar)
+            })),
+            _ => None,
+        }
+    }
+
+    // Stable identifier and semantic version, see registry.rs.
+    pub fn info(&self) -> &'static CircuitInfo {
+        circuit_info(*self)
+    }
+
+    pub fn layout(&self) -> Vec<(&'static str, usize)> {
+        match self {
+            Op::Add => fresh_layout().to_vec(),
+            Op::Sub => sub_layout(),
+            Op::Mul => mul_layout(),
+            Op::Relin => relin_layout(),
+            Op::Rotate => rotate_layout(),
+            Op::KeySwitch => keyswitch_layout(),
+            Op::ModSwitch => modswitch_layout(),
+            Op::Rescale => modswitch_layout(),
+            Op::PlainMul => plain_mul_layout(),
+            Op::Neg => neg_layout(),
+            Op::Ntt => ntt_layout(),
+            Op::Intt => intt_layout(),
+            Op::Decrypt => decrypt_layout(),
+            Op::Encrypt => encrypt_layout(),
+            Op::CkksEncode => ckks_encode_layout(),
+            Op::ModRaise => modraise_layout(),
+            Op::ScalarMul => scalar_mul_layout(),
+            Op::Fma => fma_layout(),
+            Op::Sum => sum_layout(),
+            Op::InnerProduct => inner_product_layout(),
+            Op::MatVec => mat_vec_layout(),
+            Op::BaseExt => base_ext_layout(),
+            Op::MontMul => mont_mul_layout(),
+            #[cfg(not(feature = "goldilocks"))]
+            Op::WideMul => wide_mul_layout(),
+            #[cfg(feature = "goldilocks")]
+            Op::WideMul => limb_mul_layout(),
+            Op::BlindRotate => blind_rotate_layout(),
+            Op::Horner => horner_layout(),
+            Op::Aggregate => aggregate_layout(),
+            Op::DecryptShare => decrypt_share_layout(),
+            Op::Conjugate => rotate_layout(),
+            Op::Pack => pack_layout(),
+            Op::Unpack => unpack_layout(),
+            Op::MultiKeyAdd => multi_key_add_layout(),
+            Op::ReEncrypt => re_encrypt_layout(),
+            Op::HoistedRotate => hoisted_rotate_layout(),
+            Op::Compare => compare_layout(),
+            Op::KeyRotate => re_encrypt_layout(),
+            Op::KeyConjugate => re_encrypt_layout(),
+            Op::Pipeline => pipeline_layout(),
+        }
+    }
+}
 
 #[derive(Args, Debug)]
 #[clap(next_help_heading = "INPUT ARGUMENTS")]
 pub struct InputArg {
     #[clap(long, short, display_order = 1, default_value_t = String::from("./data.toml"))]
-    data_file_path: String,
-}
-
-// Modulus + Result + Flags + Data
-// M0 M1 R0 R1 R2 R3 F00 F01 F02 F03 F10 F11 F12 F13 D00 D01 D02 D03 D10 D11 D12 D13 D20 D21 D22 D23
-const DATA_NUM: usize = 3;
-const VALUE_NUM: usize = 2;
-const COEFF_LEVEL: usize = 2;
-const COEFF_DEGREE: usize = 4096;
-const MODULUS_NUM: usize = COEFF_LEVEL;
-const FLAG_NUM: usize = DATA_NUM - 1;
-const FLAG_LEN: usize = VALUE_NUM * COEFF_LEVEL;
-const DATA_LEN: usize = FLAG_LEN;
-const DATA_START: usize = MODULUS_NUM + DATA_LEN + FLAG_NUM * FLAG_LEN;
-const DATA_END: usize = DATA_START + DATA_NUM * DATA_LEN;
-const RESULT_START: usize = MODULUS_NUM;
-const RESULT_END: usize = RESULT_START + DATA_LEN;
-const FLAG_START: usize = RESULT_END;
-
-const STATE_WIDTH: usize = DATA_END;
-const STATE_LENGTH: usize = COEFF_DEGREE;
+    pub data_file_path: String,
+    /// Operation to prove: `add` computes a + b - c, or for other operand counts in the data
+    /// file the sum of all operands but the last minus the last, `sub` a - b, `mul` the tensor
+    /// product of two ciphertexts,
+    /// `relin` relinearizes a tensor product, `rotate` rotates the slots of a ciphertext,
+    /// `key-switch` switches a ciphertext to another secret key, `mod-switch` drops its last RNS
+    /// level, `rescale` divides a CKKS ciphertext by its last RNS prime, `plain-mul` multiplies a
+    /// ciphertext by a plaintext, `neg` negates a ciphertext, `ntt` transforms a ciphertext from
+    /// coefficient into evaluation form, `intt` back, `decrypt` decrypts a BFV ciphertext,
+    /// `encrypt` encrypts a BFV plaintext, `ckks-encode` encodes CKKS slot values, `mod-raise`
+    /// lifts a ciphertext at the first RNS level to every level, `scalar-mul` multiplies a
+    /// ciphertext by a public scalar, `fma` adds the tensor product of two ciphertexts to an
+    /// accumulator, `sum` adds any number of ciphertexts, `inner-product` multiplies them by
+    /// public weights and adds the products, `mat-vec` multiplies them by a public matrix,
+    /// `base-ext` converts a ciphertext to another RNS base, `mont-mul` is `mul` on
+    /// coefficients in Montgomery form, `wide-mul` is `mul` for moduli of up to 63 bits,
+    /// `blind-rotate` runs one CMux step of a TFHE blind rotation, `horner` evaluates a public
+    /// polynomial on a ciphertext, `aggregate` adds a directory of ciphertexts and optionally
+    /// scales the sum into a mean, `decrypt-share` computes a threshold decryption share,
+    /// `conjugate` conjugates the slots of a CKKS ciphertext, `pack` packs scalar ciphertexts
+    /// into one, `unpack` moves them back into ciphertexts of their own, `multi-key-add` adds
+    /// ciphertexts under the keys of different parties, `re-encrypt` re-encrypts a ciphertext
+    /// to the key of a delegatee, `hoisted-rotate` rotates a ciphertext by several multiples of
+    /// a step with one shared decomposition, `compare` compares two BGV ciphertexts,
+    /// `key-rotate` and `key-conjugate` apply the automorphism key of a rotation or of the
+    /// conjugation to a ciphertext and `pipeline` runs a sequence of additions, subtractions, a
+    /// multiplication and a rescaling in one trace
+    #[clap(long, arg_enum, display_order = 2, default_value_t = Op::Add)]
+    pub op: Op,
+    /// Evaluation key file, required by `--op relin` and, holding the key-switching key, by
+    /// `--op key-switch`, holding the public key, by `--op decrypt` and `--op encrypt`, holding
+    /// a public key share, by `--op decrypt-share`, holding a GGSW ciphertext of the
+    /// bootstrapping key, by `--op blind-rotate`, holding the re-encryption key, by
+    /// `--op re-encrypt` or, holding the Galois keys, by `--op hoisted-rotate`, `--op key-rotate`
+    /// and `--op key-conjugate`
+    #[clap(long, display_order = 3)]
+    pub evaluation_key: Option<String>,
+    /// Number of slots to rotate by, required by `--op rotate` and `--op key-rotate`, step of the
+    /// rotations, required by `--op hoisted-rotate`, or exponent of the monomial X^a, required by
+    /// `--op blind-rotate`
+    #[clap(long, display_order = 4, allow_hyphen_values = true)]
+    pub rotation: Option<i32>,
+    /// Comma-separated result coefficient indices, in increasing order, to expose as public
+    /// inputs instead of every coefficient; the others are only bound by the trace commitment
+    #[clap(long, display_order = 5, value_delimiter = ',')]
+    pub expose: Option<Vec<usize>>,
+    /// Encryption scheme of the operands: `bgv` is supported by `add`, `sub`, `neg`, `sum`,
+    /// `mul`, `mont-mul`, `wide-mul`, `fma` and `multi-key-add`, whose ciphertext arithmetic is
+    /// the same as BFV's
+    #[clap(long, arg_enum, display_order = 6, default_value_t = Scheme::Bfv)]
+    pub scheme: Scheme,
+    /// Ring degree N of the `add` operands, from 1024 to 32768 and padded to a power of two,
+    /// which must agree with `CoeffDegree` of the data file if both are given; without either,
+    /// the build's 4096. The other circuits take the build's degree
+    #[clap(long, display_order = 7)]
+    pub coeff_degree: Option<usize>,
+    /// SEAL parameter set of the `add` operands, which sets their ring degree, requires the data
+    /// file to have its moduli and replaces the proof options by ones of 128 bits of conjectured
+    /// security
+    #[clap(long, arg_enum, display_order = 8, conflicts_with = "coeff-degree")]
+    pub params: Option<Params>,
+    /// Coefficients of the `add` operands per trace row, a power of two up to 16, which divides
+    /// the trace length by itself: the lanes of a row are sized to the largest active modulus
+    /// and must fit into 255 trace columns. Without it, one coefficient per row
+    #[clap(long, display_order = 9)]
+    pub coeffs_per_row: Option<usize>,
+    /// HE security level in bits that the ring degree and the moduli of the `add` operands must
+    /// reach in the tables of the Homomorphic Encryption Standard, or the proof is refused;
+    /// without it, parameters below 128 bits only get a warning
+    #[clap(long, arg_enum, display_order = 10)]
+    pub he_security: Option<HeSecurity>,
+    /// Data file of `add` operands instead of the data file, repeatable: a path, or a glob with
+    /// `*` and `?` in its file name whose matches come in the order of their names. The files
+    /// have the moduli and the degree of the first, their operands follow each other
+    #[clap(long = "operand", display_order = 11, multiple_occurrences = true)]
+    pub operands: Vec<String>,
+}
 
-pub struct PublicInputs {
-    pub result: [[Vec<BaseElement>; COEFF_LEVEL]; VALUE_NUM],
+// The defaults of the command line: `add` of the operands of ./data.toml.
+impl Default for InputArg {
+    fn default() -> Self {
+        InputArg {
+            data_file_path: String::from("./data.toml"),
+            op: Op::Add,
+            evaluation_key: None,
+            rotation: None,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        }
+    }
 }
 
-impl Serializable for PublicInputs {
-    fn write_into<W: ByteWriter>(&self, target: &mut W) {
-        target.write(self.result.to_vec());
+// The layout of FreshAir, the circuit of `add`, is documented in fresh.rs of the verifier crate;
+// by default it adds DATA_NUM operands, a + b - c.
+pub const DATA_NUM: usize = 3;
+pub const MODULUS_NUM: usize = COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+
+const STATE_WIDTH: usize = fresh_width(DATA_NUM);
+pub const FRESH_TRACE_LENGTH: usize = COEFF_DEGREE * DATA_LEN;
+
+// The active level count of `operands` operands over the modulus chain `modulus`, whose primes
+// must be distinct, one per level: with `levels`, the active level count of every operand, all
+// of which must be at the same level, else the whole chain.
+pub fn check_active_levels(
+    modulus: &[u64],
+    levels: Option<&[usize]>,
+    operands: usize,
+) -> Result<usize, String> {
+    check_coeff_level(modulus.len()).map_err(|err| format!("modulus chain of {}", err))?;
+    for (l, m) in modulus.iter().enumerate() {
+        if let Some(i) = modulus[..l].iter().position(|earlier| earlier == m) {
+            return Err(format!(
+                "modulus {} ({}) repeats modulus {}, every level needs its own prime",
+                l, m, i
+            ));
+        }
+    }
+    let levels = match levels {
+        Some(levels) => levels,
+        None => return Ok(modulus.len()),
+    };
+    if levels.len() != operands {
+        return Err(format!(
+            "{} level counts for {} operands",
+            levels.len(),
+            operands
+        ));
+    }
+    if let Some(d) = levels
+        .iter()
+        .position(|&l| !(1..=modulus.len()).contains(&l))
+    {
+        return Err(format!(
+            "operand {} is at {} levels, expected 1 to the {} of the modulus chain",
+            d,
+            levels[d],
+            modulus.len()
+        ));
+    }
+    if let Some(d) = levels.iter().position(|&l| l != levels[0]) {
+        return Err(format!(
+            "operand {} is at {} levels, operand 0 at {}: operands must be at the same level, \
+             switch the others down first",
+            d, levels[d], levels[0]
+        ));
     }
+    Ok(levels[0])
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Data {
-    pub result: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
-    pub proof: String,
+// Constructors of the SchemeConfig of a prover, which reads the data file and the command line
+// the verifier crate knows nothing of.
+pub trait LoadConfig: Sized {
+    fn from_data(arg: &InputArg, data: &CustomData) -> Result<Self, StarkHeError>;
+    fn from_header(
+        arg: &InputArg,
+        data: &CustomData,
+        operands: usize,
+    ) -> Result<Self, StarkHeError>;
 }
 
-impl ::std::default::Default for Data {
-    fn default() -> Self {
-        Self {
-            result: Default::default(),
-            proof: Default::default(),
+impl LoadConfig for SchemeConfig {
+    // The configuration of `data`: the ring degree is that of `--coeff-degree` or `--params` or
+    // `CoeffDegree` of the data file, which must agree when both are given, or COEFF_DEGREE;
+    // every operand has a level per active modulus, and the modulus chain is that of `--params`,
+    // if given. Rows hold `--coeffs-per-row` coefficients, one by default; packed rows size
+    // their lanes to the largest active modulus.
+    fn from_data(arg: &InputArg, data: &CustomData) -> Result<Self, StarkHeError> {
+        let config = SchemeConfig::from_header(arg, data, data.values.len())?;
+        for (d, operand) in data.values.iter().enumerate() {
+            for (v, component) in operand.iter().enumerate() {
+                if component.len() != config.levels {
+                    return Err(StarkHeError::DimensionMismatch(format!(
+                        "operand {} component {} has {} levels, expected {}, one per active \
+                         modulus",
+                        d,
+                        v,
+                        component.len(),
+                        config.levels
+                    )));
+                }
+                for (l, coeffs) in component.iter().enumerate() {
+                    if coeffs.len() != config.degree {
+                        return Err(StarkHeError::DimensionMismatch(format!(
+                            "operand {} component {} level {} has {} coefficients, expected {}",
+                            d,
+                            v,
+                            l,
+                            coeffs.len(),
+                            config.degree
+                        )));
+                    }
+                    let m = config.modulus[l];
+                    if let Some(k) = coeffs.iter().position(|&c| c >= m) {
+                        return Err(StarkHeError::InvalidInput(format!(
+                            "coefficient {} of operand {} component {} level {} is {}, not \
+                             reduced modulo {}",
+                            k, d, v, l, coeffs[k], m
+                        )));
+                    }
+                }
+            }
         }
+        Ok(config)
+    }
+
+    // The configuration of `operands` operands with the moduli, levels and ring degree of
+    // `data`, whose values are not read, as for operands streamed to build_trace_from_rows.
+    fn from_header(
+        arg: &InputArg,
+        data: &CustomData,
+        operands: usize,
+    ) -> Result<Self, StarkHeError> {
+        if let Some(params) = arg.params {
+            params
+                .check_modulus(&data.modulus)
+                .map_err(StarkHeError::InvalidInput)?;
+        }
+        let degree = match (arg.params, arg.coeff_degree, data.coeff_degree) {
+            (Some(params), _, Some(data)) if params.coeff_degree() != data => {
+                return Err(StarkHeError::InvalidInput(format!(
+                    "--params {} of degree {} differs from CoeffDegree {} of the data file",
+                    params.name(),
+                    params.coeff_degree(),
+                    data
+                )))
+            }
+            (_, Some(arg), Some(data)) if arg != data => {
+                return Err(StarkHeError::InvalidInput(format!(
+                    "--coeff-degree {} differs from CoeffDegree {} of the data file",
+                    arg, data
+                )))
+            }
+            (params, arg, data) => params
+                .map(|params| params.coeff_degree())
+                .or(arg)
+                .or(data)
+                .unwrap_or(COEFF_DEGREE),
+        };
+        check_coeff_degree(degree).map_err(StarkHeError::InvalidInput)?;
+        check_operand_num(operands)
+            .map_err(|err| StarkHeError::DimensionMismatch(format!("data file of {}", err)))?;
+        let levels = check_active_levels(&data.modulus, data.levels.as_deref(), operands)
+            .map_err(StarkHeError::DimensionMismatch)?;
+        let modulus = data.modulus[..levels].to_vec();
+        let (coeffs_per_row, modulus_bits) = match arg.coeffs_per_row.unwrap_or(1) {
+            1 => (1, BARRETT_MODULUS_BITS),
+            coeffs_per_row => {
+                let modulus_bits = modulus
+                    .iter()
+                    .map(|m| 64 - m.leading_zeros() as usize)
+                    .max()
+                    .expect("levels were checked");
+                let packing = Packing {
+                    coeffs_per_row,
+                    modulus_bits,
+                };
+                packing.check().map_err(|err| {
+                    StarkHeError::InvalidInput(format!("--coeffs-per-row: {}", err))
+                })?;
+                (coeffs_per_row, modulus_bits)
+            }
+        };
+        check_fresh_modulus(&modulus, operands, modulus_bits)
+            .map_err(StarkHeError::InvalidInput)?;
+        let config = SchemeConfig {
+            degree,
+            levels,
+            operands,
+            coeffs_per_row,
+            modulus_bits,
+            modulus,
+        };
+        config
+            .check_rows()
+            .map_err(|err| StarkHeError::InvalidInput(format!("--coeffs-per-row: {}", err)))?;
+        if config.width() > TraceInfo::MAX_TRACE_WIDTH {
+            return Err(StarkHeError::InvalidInput(format!(
+                "{} coefficients per row of {} operands and {}-bit moduli take {} columns, \
+                 traces have at most {}",
+                coeffs_per_row,
+                config.operands,
+                modulus_bits,
+                config.width(),
+                TraceInfo::MAX_TRACE_WIDTH
+            )));
+        }
+        if config.trace_length() < TraceInfo::MIN_TRACE_LENGTH {
+            return Err(StarkHeError::InvalidInput(format!(
+                "{} coefficients per row leave {} rows, traces have at least {}",
+                coeffs_per_row,
+                config.trace_length(),
+                TraceInfo::MIN_TRACE_LENGTH
+            )));
+        }
+        Ok(config)
     }
 }
 
-pub fn from_data(data: Data) -> (PublicInputs, Vec<u8>) {
-    let mut result: [[Vec<BaseElement>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
-    for i in 0..VALUE_NUM {
-        for j in 0..COEFF_LEVEL {
-            result[i][j] = data.result[i][j]
-                .iter()
-                .map(|x| BaseElement::from(*x))
-                .collect();
+// The configuration of the add data file of `arg`.
+// The operands of `add`, from the `--operand` files if given, or else from the data file.
+pub fn load_custom_data(arg: &InputArg) -> Result<CustomData, StarkHeError> {
+    match arg.operands.is_empty() {
+        true => read_custom_data(&arg.data_file_path),
+        false => read_operand_files(&arg.operands),
+    }
+}
+
+pub fn load_scheme_config(arg: &InputArg) -> Result<SchemeConfig, StarkHeError> {
+    load_custom_data(arg).and_then(|data| SchemeConfig::from_data(arg, &data))
+}
+
+// Reads the TOML data file of a circuit at `path`; unlike `confy::load_path`, a missing file is
+// an error rather than created with default values.
+pub fn load_data_file<T: DeserializeOwned>(path: &str) -> Result<T, StarkHeError> {
+    let text = std::fs::read_to_string(path).map_err(|err| StarkHeError::io(path, err))?;
+    toml::from_str(&text).map_err(|err| StarkHeError::parse(path, err))
+}
+
+// Trace layout constants of FreshAir in canonical order.
+pub fn fresh_layout() -> [(&'static str, usize); 6] {
+    [
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Result components of the proven operation, each with one coefficient vector per level, the
+// evaluation key of a relinearization or key switch, the number of slots of a rotation, the
+// scales of a rescale, the RNS moduli of circuits whose constraints depend on them, the plain
+// modulus of a BFV decryption or encryption, the ciphertext of a decryption or a decryption
+// share, the slot values of a CKKS encoding, the scalar of a scalar multiplication or of a
+// mean, the weights of an inner product, the coefficients of a polynomial evaluation or the
+// steps of a pipeline, the digest of results which are distributed separately, the coefficient
+// indices of windowed results, which then hold only those coefficients, the scheme of proofs
+// other than BFV, the packing of additions of several coefficients per row and the input
+// ciphertext of an automorphism.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicInputs {
+    pub result: Vec<Vec<Vec<BaseElement>>>,
+    pub evaluation_key: Option<EvaluationKey>,
+    pub rotation: Option<i32>,
+    pub scale: Option<Scale>,
+    pub modulus: Option<Vec<u64>>,
+    pub plain_modulus: Option<u64>,
+    pub decryption: Option<Decryption>,
+    pub slots: Option<CkksSlots>,
+    pub scalar: Option<u64>,
+    pub weights: Option<Vec<u64>>,
+    pub result_digest: Option<[u8; 32]>,
+    pub window: Option<Vec<usize>>,
+    pub scheme: Option<Scheme>,
+    pub packing: Option<Packing>,
+    pub input: Option<AutomorphismInput>,
+}
+
+impl PublicInputs {
+    // Canonical encoding, which `commitment` hashes; it keeps only the digests of keys and does
+    // not mark absent fields, so it is never read back.
+    // Layout: the number of result components and of their levels (COEFF_LEVEL if there are none)
+    // as u32, then every result vector
+    // ordered by component index, then level index (see canonical.rs for the encoding rules), or
+    // instead the 32-byte result digest if there is one (see result_digest in
+    // inner_product_air.rs), then the 32-byte evaluation key digest if there is a key, then the
+    // rotation as u32 (two's complement) if there is one, then the prime and the scales before and
+    // after as u64 (IEEE 754 bits) if there is a rescale, then the moduli as u64 if they are
+    // public, then the plain modulus as u64 if there is one, then the ciphertext of a decryption
+    // (see Decryption::write_canonical), then the slot values of an encoding (see
+    // CkksSlots::write_canonical), then the scalar as u64 if there is one, then the weights as a
+    // sequence of u64 if there are any, then the window as a sequence of u32 indices if the results
+    // are windowed, then the scheme as u8 (see Scheme::tag) if there is one, then the coefficients
+    // per row and the modulus bits of a packed addition as u32, then the input ciphertext of an
+    // automorphism (see AutomorphismInput::write_canonical).
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut writer = CanonicalWriter::new(PUBLIC_INPUTS_TAG);
+        writer.write_u32(self.result.len() as u32);
+        writer.write_u32(self.result.first().map_or(COEFF_LEVEL, Vec::len) as u32);
+        match &self.result_digest {
+            Some(digest) => {
+                for &byte in digest.iter() {
+                    writer.write_u8(byte);
+                }
+            }
+            None => {
+                for value in self.result.iter() {
+                    for level in value.iter() {
+                        writer.write_elements(level);
+                    }
+                }
+            }
+        }
+        if let Some(key) = &self.evaluation_key {
+            for byte in key.digest() {
+                writer.write_u8(byte);
+            }
+        }
+        if let Some(rotation) = self.rotation {
+            writer.write_u32(rotation as u32);
+        }
+        if let Some(scale) = &self.scale {
+            writer.write_u64(scale.modulus);
+            writer.write_f64(scale.before);
+            writer.write_f64(scale.after);
+        }
+        if let Some(modulus) = &self.modulus {
+            for &m in modulus.iter() {
+                writer.write_u64(m);
+            }
+        }
+        if let Some(plain_modulus) = self.plain_modulus {
+            writer.write_u64(plain_modulus);
+        }
+        if let Some(decryption) = &self.decryption {
+            decryption.write_canonical(&mut writer);
+        }
+        if let Some(slots) = &self.slots {
+            slots.write_canonical(&mut writer);
+        }
+        if let Some(scalar) = self.scalar {
+            writer.write_u64(scalar);
+        }
+        if let Some(weights) = &self.weights {
+            writer.write_u32(weights.len() as u32);
+            for &w in weights.iter() {
+                writer.write_u64(w);
+            }
+        }
+        if let Some(window) = &self.window {
+            writer.write_u32(window.len() as u32);
+            for &k in window.iter() {
+                writer.write_u32(k as u32);
+            }
+        }
+        if let Some(scheme) = self.scheme {
+            writer.write_u8(scheme.tag());
         }
+        if let Some(packing) = self.packing {
+            writer.write_u32(packing.coeffs_per_row as u32);
+            writer.write_u32(packing.modulus_bits as u32);
+        }
+        if let Some(input) = &self.input {
+            input.write_canonical(&mut writer);
+        }
+        writer.into_bytes()
+    }
+
+    pub fn commitment(&self) -> [u8; 32] {
+        commitment(&self.to_canonical_bytes())
     }
-    (PublicInputs { result }, decode(data.proof).unwrap())
 }
 
-pub fn to_data(proof: Vec<u8>, public_input: PublicInputs) -> Data {
-    let mut result: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
-    for i in 0..VALUE_NUM {
-        for j in 0..COEFF_LEVEL {
-            result[i][j] = public_input.result[i][j]
-                .iter()
-                .map(|x| x.to_string().parse().unwrap())
-                .collect();
+// Exchange encoding, which `read_from` decodes, to pass public inputs next to the bytes of their
+// proof; the public coin is seeded with it, so any implementation that follows canonical.rs
+// derives the same Fiat-Shamir transcript. Unlike the canonical encoding, a commitment, it holds
+// the results and the evaluation key in full and marks which fields are present.
+// Layout: the number of result components as u32, then every component as the number of its
+// levels as u32 followed by every level as a sequence of field elements, then every other field
+// in declaration order as a presence flag followed by its value (see canonical.rs): the key as
+// in EvaluationKey::write_canonical, the rotation as u32 (two's complement), the prime and the
+// scales of a rescale as in the canonical encoding, the moduli as a sequence of u64, the plain
+// modulus as u64, the decryption and the slots as in their write_canonical, the scalar as u64,
+// the weights as a sequence of u64, the 32-byte result digest, the window as a sequence of u32,
+// the scheme tag as u8, the packing as two u32 and the input as in its write_canonical.
+impl Serializable for PublicInputs {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        let mut writer = CanonicalWriter::new(PUBLIC_INPUTS_EXCHANGE_TAG);
+        writer.write_u32(self.result.len() as u32);
+        for value in self.result.iter() {
+            writer.write_u32(value.len() as u32);
+            for level in value.iter() {
+                writer.write_elements(level);
+            }
         }
+        writer.write_option(self.evaluation_key.as_ref(), |writer, key| {
+            key.write_canonical(writer)
+        });
+        writer.write_option(self.rotation.as_ref(), |writer, &rotation| {
+            writer.write_u32(rotation as u32)
+        });
+        writer.write_option(self.scale.as_ref(), |writer, scale| {
+            writer.write_u64(scale.modulus);
+            writer.write_f64(scale.before);
+            writer.write_f64(scale.after);
+        });
+        writer.write_option(self.modulus.as_ref(), |writer, modulus| {
+            writer.write_u64s(modulus)
+        });
+        writer.write_option(self.plain_modulus.as_ref(), |writer, &plain_modulus| {
+            writer.write_u64(plain_modulus)
+        });
+        writer.write_option(self.decryption.as_ref(), |writer, decryption| {
+            decryption.write_canonical(writer)
+        });
+        writer.write_option(self.slots.as_ref(), |writer, slots| {
+            slots.write_canonical(writer)
+        });
+        writer.write_option(self.scalar.as_ref(), |writer, &scalar| {
+            writer.write_u64(scalar)
+        });
+        writer.write_option(self.weights.as_ref(), |writer, weights| {
+            writer.write_u64s(weights)
+        });
+        writer.write_option(self.result_digest.as_ref(), |writer, digest| {
+            for &byte in digest.iter() {
+                writer.write_u8(byte);
+            }
+        });
+        writer.write_option(self.window.as_ref(), |writer, window| {
+            writer.write_u32(window.len() as u32);
+            for &k in window.iter() {
+                writer.write_u32(k as u32);
+            }
+        });
+        writer.write_option(self.scheme.as_ref(), |writer, scheme| {
+            writer.write_u8(scheme.tag())
+        });
+        writer.write_option(self.packing.as_ref(), |writer, packing| {
+            writer.write_u32(packing.coeffs_per_row as u32);
+            writer.write_u32(packing.modulus_bits as u32);
+        });
+        writer.write_option(self.input.as_ref(), |writer, input| {
+            input.write_canonical(writer)
+        });
+        target.write_u8_slice(&writer.into_bytes());
+    }
+}
+
+// The exact inverse of `write_into`.
+impl Deserializable for PublicInputs {
+    fn read_from<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        read_tag(source, PUBLIC_INPUTS_EXCHANGE_TAG)?;
+        let result = read_sequence(source, |source| {
+            read_sequence(source, |source| read_sequence(source, read_element))
+        })?;
+        // the fields are read in declaration order
+        Ok(PublicInputs {
+            result,
+            evaluation_key: read_option(source, EvaluationKey::read_canonical)?,
+            rotation: read_option(source, |source| Ok(source.read_u32()? as i32))?,
+            scale: read_option(source, |source| {
+                Ok(Scale {
+                    modulus: source.read_u64()?,
+                    before: read_f64(source)?,
+                    after: read_f64(source)?,
+                })
+            })?,
+            modulus: read_option(source, read_u64s)?,
+            plain_modulus: read_option(source, |source| source.read_u64())?,
+            decryption: read_option(source, Decryption::read_canonical)?,
+            slots: read_option(source, CkksSlots::read_canonical)?,
+            scalar: read_option(source, |source| source.read_u64())?,
+            weights: read_option(source, read_u64s)?,
+            result_digest: read_option(source, |source| {
+                let mut digest = [0; 32];
+                for byte in digest.iter_mut() {
+                    *byte = source.read_u8()?;
+                }
+                Ok(digest)
+            })?,
+            window: read_option(source, |source| {
+                read_sequence(source, |source| Ok(source.read_u32()? as usize))
+            })?,
+            scheme: read_option(source, |source| {
+                let tag = source.read_u8()?;
+                Scheme::from_tag(tag).ok_or_else(|| {
+                    DeserializationError::InvalidValue(format!("unknown scheme tag {}", tag))
+                })
+            })?,
+            packing: read_option(source, |source| {
+                Ok(Packing {
+                    coeffs_per_row: source.read_u32()? as usize,
+                    modulus_bits: source.read_u32()? as usize,
+                })
+            })?,
+            input: read_option(source, AutomorphismInput::read_canonical)?,
+        })
     }
+}
+
+// Layout: trace layout constants as u32 (MODULUS_NUM, DATA_NUM, VALUE_NUM, COEFF_LEVEL,
+// COEFF_DEGREE, STATE_WIDTH), followed by the proof options.
+pub fn canonical_parameters(options: &ProofOptions) -> Vec<u8> {
+    let mut writer = CanonicalWriter::new(PARAMETERS_TAG);
+    for (_, value) in fresh_layout() {
+        writer.write_u32(value as u32);
+    }
+    write_proof_options(&mut writer, options);
+    writer.into_bytes()
+}
+
+// Version of the proof container written by this build: 2 records the version itself.
+pub const PROOF_FORMAT_VERSION: u32 = 2;
+
+fn legacy_format_version() -> u32 {
+    1
+}
+
+fn default_circuit() -> String {
+    String::from(Op::Add.name())
+}
+
+// Proof container. Files written before multiplication was supported have no circuit field and
+// are addition proofs; files written before reproducibility manifests have none. Relinearization
+// and key-switching proofs record the hex digest of their key, the key itself is distributed
+// separately;
+// rotation proofs record their rotation, rescale proofs their scales, NTT proofs their moduli
+// and base extension proofs their source and target moduli;
+// decryption proofs record the digest of the public key, their moduli, the plain modulus and the
+// ciphertext; encryption proofs the digest of the public key, their moduli and the plain modulus;
+// encoding proofs their slot values and scale; scalar multiplication proofs their scalar;
+// inner product proofs their weights and, instead of the result, its hex digest; pipeline proofs
+// the codes of their steps as weights and, if they rescale, their scales.
+// Windowed proofs record the coefficient indices their results are restricted to, BGV proofs
+// their scheme, addition proofs of several coefficients per row their packing, rotation,
+// conjugation and key-switched automorphism proofs their moduli and input ciphertext.
+//
+// Containers are versioned: `format_version` is PROOF_FORMAT_VERSION for containers written by
+// this build and missing, read as 1, in older ones. Verifiers reject containers of a newer
+// format before decoding anything else, and the reproducibility manifest of an envelope (see
+// `to_envelope`) records the field, hash function and proof options, which are checked before
+// the proof bytes are handed to Winterfell.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Data {
+    #[serde(default = "legacy_format_version")]
+    pub format_version: u32,
+    #[serde(default = "default_circuit")]
+    pub circuit: String,
+    pub result: Vec<Vec<Vec<u64>>>,
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluation_key_digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modulus: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plain_modulus: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decryption: Option<Decryption>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots: Option<CkksSlots>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scalar: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<Scheme>,
+    // a table, so it follows the plain values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Scale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packing: Option<Packing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<AutomorphismInput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reproducibility: Option<ReproducibilityManifest>,
+}
+
+// Over Goldilocks a u64 can exceed the field modulus; such results would alias smaller ones and
+// are rejected.
+pub fn from_data(data: Data) -> Result<(PublicInputs, Vec<u8>), StarkHeError> {
+    if let Some(&x) = data
+        .result
+        .iter()
+        .flatten()
+        .flatten()
+        .find(|&&x| x as u128 >= FIELD_MODULUS)
+    {
+        return Err(StarkHeError::InvalidInput(format!(
+            "result coefficient {} is not an element of {}",
+            x, FIELD_NAME
+        )));
+    }
+    let result = data
+        .result
+        .iter()
+        .map(|levels| {
+            levels
+                .iter()
+                .map(|x| x.iter().copied().map(element_from_u64).collect())
+                .collect()
+        })
+        .collect();
+    let public_input = PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: data.rotation,
+        scale: data.scale,
+        modulus: data.modulus,
+        plain_modulus: data.plain_modulus,
+        decryption: data.decryption,
+        slots: data.slots,
+        scalar: data.scalar,
+        weights: data.weights,
+        result_digest: data.result_digest.as_deref().and_then(from_hex),
+        window: data.window,
+        scheme: data.scheme,
+        packing: data.packing,
+        input: data.input,
+    };
+    let proof = ProofEncoding::default().decode(&data.proof)?;
+    Ok((public_input, proof))
+}
+
+// Results with a digest are left out, they are distributed separately.
+pub fn to_data(op: Op, proof: Vec<u8>, public_input: PublicInputs) -> Data {
+    let result = match public_input.result_digest {
+        Some(_) => Vec::new(),
+        None => public_input
+            .result
+            .into_iter()
+            .map(|levels| {
+                levels
+                    .into_iter()
+                    .map(|x| x.into_iter().map(element_to_u64).collect())
+                    .collect()
+            })
+            .collect(),
+    };
     Data {
+        format_version: PROOF_FORMAT_VERSION,
+        circuit: String::from(op.name()),
         result,
-        proof: encode(proof),
+        proof: ProofEncoding::default().encode(proof),
+        rotation: public_input.rotation,
+        scale: public_input.scale,
+        modulus: public_input.modulus,
+        plain_modulus: public_input.plain_modulus,
+        decryption: public_input.decryption,
+        slots: public_input.slots,
+        scalar: public_input.scalar,
+        weights: public_input.weights,
+        result_digest: public_input.result_digest.map(|digest| to_hex(&digest)),
+        window: public_input.window,
+        scheme: public_input.scheme,
+        packing: public_input.packing,
+        input: public_input.input,
+        evaluation_key_digest: public_input.evaluation_key.map(|key| to_hex(&key.digest())),
+        reproducibility: None,
     }
 }
 
+// The container of `proof` with a reproducibility manifest of its options and security, as the
+// prover writes it.
+pub fn to_envelope(op: Op, proof: &StarkProof, public_input: PublicInputs) -> Data {
+    let mut manifest = ReproducibilityManifest::new(op, proof.options());
+    manifest.security_bits = Some(proof.security_level(true));
+    let mut data = to_data(op, proof.to_bytes(), public_input);
+    data.reproducibility = Some(manifest);
+    data
+}
+
 pub type TraceType = TraceTable<BaseElement>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct CustomData {
     pub modulus: Vec<u64>,
-    pub values: [[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; DATA_NUM],
+    // ring degree of the operands, COEFF_DEGREE if absent; only `add` accepts other degrees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coeff_degree: Option<usize>,
+    // active level count of every operand, the first moduli of the chain being its levels, as
+    // modulus switching drops the last ones; every modulus if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub levels: Option<Vec<usize>>,
+    // the operands, the last subtracted from the sum of the others, each with one coefficient
+    // vector per active level, whose number `add` takes as its level count
+    pub values: Vec<[Vec<Vec<u64>>; VALUE_NUM]>,
 }
 
-impl ::std::default::Default for CustomData {
-    fn default() -> Self {
-        Self {
-            modulus: Default::default(),
-            values: Default::default(),
-        }
+// Same format as CustomData, with a single ciphertext.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CiphertextData {
+    pub modulus: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+impl CiphertextData {
+    // The ciphertext of COEFF_DEGREE coefficients per level, checked against the moduli.
+    pub fn into_ciphertext(self) -> Result<Ciphertext, StarkHeError> {
+        Ciphertext::from_levels(&self.modulus, COEFF_DEGREE, self.values)
     }
 }
 
-pub fn build_trace(arg: &InputArg) -> TraceType {
-    let data: CustomData = confy::load_path(&arg.data_file_path).unwrap();
-    let mut trace = TraceTable::new(STATE_WIDTH, STATE_LENGTH);
+// Layout helpers shared by the AIRs which hold one coefficient of every level per row: groups
+// of columns start with the modulus columns and store `component` major, level minor.
+pub fn level_column(start: usize, component: usize, l: usize) -> usize {
+    start + component * COEFF_LEVEL + l
+}
 
-    trace.fill(
-        |state| {
-            for i in 0..MODULUS_NUM {
-                state[i] = BaseElement::from(data.modulus[i]);
+// Result components read back from the trace, `column(component, level)` giving their columns.
+pub fn read_result(
+    trace: &TraceType,
+    num: usize,
+    column: impl Fn(usize, usize) -> usize,
+) -> Vec<Vec<Vec<BaseElement>>> {
+    (0..num)
+        .map(|r| {
+            let mut levels = vec![Vec::new(); COEFF_LEVEL];
+            for (l, level) in levels.iter_mut().enumerate() {
+                *level = trace.get_column(column(r, l)).to_vec();
             }
+            levels
+        })
+        .collect()
+}
 
-            for i in DATA_START..DATA_END {
-                let idx = i - DATA_START;
-                let d_idx = idx / DATA_LEN;
-                let v_idx = idx / COEFF_LEVEL % VALUE_NUM;
-                let l_idx = idx % COEFF_LEVEL;
-                state[i] = BaseElement::from(data.values[d_idx][v_idx][l_idx][0]);
-            }
+// Moduli of the levels, read back from the modulus columns the layouts start with.
+pub fn read_modulus(trace: &TraceType) -> Vec<u64> {
+    (0..MODULUS_NUM)
+        .map(|l| element_to_u64(trace.get(l, 0)))
+        .collect()
+}
 
-            for i in RESULT_START..RESULT_END {
-                let idx = i - RESULT_START;
-                let l_idx = idx % COEFF_LEVEL;
-                let offset = i + FLAG_NUM * FLAG_LEN + DATA_LEN;
-                let d1 = state[offset];
-                let d2 = state[offset + DATA_LEN];
-                let d3 = state[offset + 2 * DATA_LEN];
-                let m = state[l_idx];
-                let r1 = d1 + d2;
-                if r1.is_greater(&m) {
-                    state[FLAG_START + idx] = BaseElement::ONE;
-                } else {
-                    state[FLAG_START + idx] = BaseElement::ZERO;
-                }
-                if (r1 - state[FLAG_START + idx] * m).is_greater(&d3) {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ZERO;
-                } else {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ONE;
-                }
+// A result component with coefficients at the first level only, as decryptions and encodings
+// record their plaintext.
+pub fn first_level<T>(values: Vec<T>) -> Vec<Vec<T>> {
+    let mut levels: Vec<Vec<T>> = (0..COEFF_LEVEL).map(|_| Vec::new()).collect();
+    levels[0] = values;
+    levels
+}
 
-                state[i] = (r1 - state[FLAG_START + idx] * m)
-                    + state[FLAG_START + FLAG_LEN + idx] * m
-                    - d3;
-            }
+// Binds every row of each column to the given values. Sequence assertions need a stride of at
+// least 2, so even and odd rows are asserted separately.
+pub fn bind_columns(columns: Vec<(usize, Vec<BaseElement>)>) -> Vec<Assertion<BaseElement>> {
+    let mut assertions = Vec::with_capacity(2 * columns.len());
+    for (column, values) in columns {
+        for first_step in 0..2 {
+            let values = values.iter().skip(first_step).step_by(2).copied().collect();
+            assertions.push(Assertion::sequence(column, first_step, 2, values));
+        }
+    }
+    assertions
+}
 
-            for i in DATA_START..DATA_END {
-                let idx = i - DATA_START;
-                let d_idx = idx / DATA_LEN;
-                let v_idx = idx / COEFF_LEVEL % VALUE_NUM;
-                let l_idx = idx % COEFF_LEVEL;
-                state[i] = BaseElement::from(data.values[d_idx][v_idx][l_idx][1]);
-            }
+// The value FreshAir reduces for `operands` modulo `m`: the sum of all but the last operand plus
+// m minus the last, a + b - c + m for three. SchemeConfig::from_data and check_coefficient_row
+// reject unreduced operands before the trace is filled.
+fn fresh_value(operands: &[u64], m: u64) -> u128 {
+    assert!(
+        operands.iter().all(|&d| d < m),
+        "operand coefficients were checked to be reduced modulo {}",
+        m
+    );
+    let (last, rest) = operands.split_last().expect("additions have operands");
+    rest.iter().map(|&d| d as u128).sum::<u128>() + m as u128 - *last as u128
+}
 
-            for i in RESULT_START..RESULT_END {
-                let idx = i - RESULT_START;
-                let l_idx = idx % COEFF_LEVEL;
-                let offset = i + FLAG_NUM * FLAG_LEN + DATA_LEN;
-                let d1 = state[offset];
-                let d2 = state[offset + DATA_LEN];
-                let d3 = state[offset + 2 * DATA_LEN];
-                let m = state[l_idx];
-                let r1 = d1 + d2;
-                if r1.is_greater(&m) {
-                    state[FLAG_START + idx] = BaseElement::ONE;
-                } else {
-                    state[FLAG_START + idx] = BaseElement::ZERO;
-                }
-                if (r1 - state[FLAG_START + idx] * m).is_greater(&d3) {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ZERO;
-                } else {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ONE;
-                }
+// The value FreshAir constrains the result column to for `operands`: the remainder of their
+// fresh_value by m.
+pub fn fresh_result(operands: &[BaseElement], m: BaseElement) -> BaseElement {
+    let m = element_to_u64(m);
+    let operands: Vec<u64> = operands.iter().map(|&d| element_to_u64(d)).collect();
+    element_from_u64(barrett_reduce(fresh_value(&operands, m), m).1)
+}
 
-                // println!(
-                //     "fill state[{}] = {} - {} * {} + {} * {} - {} = {}",
-                //     i,
-                //     r1,
-                //     state[FLAG_START + idx],
-                //     m,
-                //     state[FLAG_START + FLAG_LEN + idx],
-                //     m,
-                //     d3,
-                //     (r1 - state[FLAG_START + idx] * m) + state[FLAG_START + FLAG_LEN + idx] * m
-                //         - d3,
-                // );
-            }
-        },
-        |last_step, state| {
-            for i in RESULT_START..RESULT_END {
-                let idx = i - RESULT_START;
-                let l_idx = idx % COEFF_LEVEL;
-                let offset = i + FLAG_NUM * FLAG_LEN + DATA_LEN;
-                let d1 = state[offset];
-                let d2 = state[offset + DATA_LEN];
-                let d3 = state[offset + 2 * DATA_LEN];
-                let m = state[l_idx];
-                let r1 = d1 + d2;
-
-                state[i] = (r1 - state[FLAG_START + idx] * m)
-                    + state[FLAG_START + FLAG_LEN + idx] * m
-                    - d3;
-
-                // println!(
-                //     "update start state[{}] = {} - {} * {} + {} * {} - {} = {}",
-                //     i,
-                //     r1,
-                //     state[FLAG_START + idx],
-                //     m,
-                //     state[FLAG_START + FLAG_LEN + idx],
-                //     m,
-                //     d3,
-                //     state[i],
-                // );
-            }
+pub fn build_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data = load_custom_data(arg)?;
+    build_trace_from_data(arg, &data).map(|(_, trace)| trace)
+}
 
-            for i in DATA_START..DATA_END {
-                let idx = i - DATA_START;
-                let d_idx = idx / DATA_LEN;
-                let v_idx = idx / COEFF_LEVEL % VALUE_NUM;
-                let l_idx = idx % COEFF_LEVEL;
-                state[i] = BaseElement::from(
-                    data.values[d_idx][v_idx][l_idx][(last_step + 2) % COEFF_DEGREE],
-                );
-            }
+// The trace of the `add` operands of `data`, held in memory, with the options of `arg` but for
+// its data file and `--operand` files, which are not read; the configuration goes to
+// FreshProver. Services embedding the crate pass `InputArg::default()`.
+pub fn build_trace_from_data(
+    arg: &InputArg,
+    data: &CustomData,
+) -> Result<(SchemeConfig, TraceType), StarkHeError> {
+    let config = SchemeConfig::from_data(arg, data)?;
+    let trace = build_fresh_trace(&config, data);
+    Ok((config, trace))
+}
 
-            for i in RESULT_START..RESULT_END {
-                let idx = i - RESULT_START;
-                let l_idx = idx % COEFF_LEVEL;
-                let offset = i + FLAG_NUM * FLAG_LEN + DATA_LEN;
-                let d1 = state[offset];
-                let d2 = state[offset + DATA_LEN];
-                let d3 = state[offset + 2 * DATA_LEN];
-                let m = state[l_idx];
-                let r1 = d1 + d2;
-                if r1.is_greater(&m) {
-                    state[FLAG_START + idx] = BaseElement::ONE;
-                } else {
-                    state[FLAG_START + idx] = BaseElement::ZERO;
-                }
-                if (r1 - state[FLAG_START + idx] * m).is_greater(&d3) {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ZERO;
-                } else {
-                    state[FLAG_START + FLAG_LEN + idx] = BaseElement::ONE;
-                }
+// The FreshAir trace of the operands of `data`, which must have the configuration `config`.
+fn build_fresh_trace(config: &SchemeConfig, data: &CustomData) -> TraceType {
+    let mut trace = TraceTable::new(config.width(), config.trace_length());
+    let coefficients = |v: usize, l: usize, k: usize| -> Vec<u64> {
+        data.values.iter().map(|operand| operand[v][l][k]).collect()
+    };
 
-                // println!(
-                //     "update end state[{}] = {} - {} * {} + {} * {} - {} = {}",
-                //     i,
-                //     r1,
-                //     state[FLAG_START + idx],
-                //     m,
-                //     state[FLAG_START + FLAG_LEN + idx],
-                //     m,
-                //     d3,
-                //     (r1 - state[FLAG_START + idx] * m) + state[FLAG_START + FLAG_LEN + idx] * m
-                //         - d3,
-                // );
-            }
-        },
+    trace.fill(
+        |state| fill_fresh_row(config, coefficients, 0, state),
+        |last_step, state| fill_fresh_row(config, coefficients, last_step + 1, state),
     );
     trace
 }
 
-pub fn get_pub_inputs(trace: &TraceType) -> PublicInputs {
-    // [[Vec<BaseElement>; COEFF_LEVEL]; VALUE_NUM]
+// Coefficient k of every operand: `row[d][v][l]` is the coefficient of operand d, component v
+// at level l.
+pub type CoefficientRow = Vec<[Vec<u64>; VALUE_NUM]>;
+
+// The trace of `operands` `add` operands with the moduli, levels and ring degree of `header`
+// (see SchemeConfig::from_header), streamed by `rows` as one CoefficientRow per coefficient,
+// coefficient 0 first. Only the coefficients of one trace row are held besides the trace, so
+// operands which do not fit into memory next to it, or arrive over a connection, are proven as
+// they are read. The first error of the stream is returned, as are streams which end early, run
+// long or yield rows of another shape.
+pub fn build_trace_from_rows<I>(
+    arg: &InputArg,
+    header: &CustomData,
+    operands: usize,
+    rows: I,
+) -> Result<(SchemeConfig, TraceType), StarkHeError>
+where
+    I: IntoIterator<Item = Result<CoefficientRow, StarkHeError>>,
+{
+    let config = SchemeConfig::from_header(arg, header, operands)?;
+    let mut trace = TraceTable::new(config.width(), config.trace_length());
+    let mut rows = rows.into_iter();
+    let mut state = vec![BaseElement::ZERO; config.width()];
+    let group = fresh_group(config.levels);
+    let mut chunk = Vec::with_capacity(config.coeffs_per_row);
+    for step in 0..config.trace_length() {
+        if step % group == 0 {
+            chunk.clear();
+            let first = step / group * config.coeffs_per_row;
+            for k in first..config.degree.min(first + config.coeffs_per_row) {
+                let row = rows.next().ok_or_else(|| {
+                    StarkHeError::DimensionMismatch(format!(
+                        "the stream ends after {} of {} coefficients",
+                        k, config.degree
+                    ))
+                })??;
+                check_coefficient_row(&config, k, &row)?;
+                chunk.push(row);
+            }
+        }
+        let coefficients = |v: usize, l: usize, k: usize| -> Vec<u64> {
+            chunk[k % config.coeffs_per_row]
+                .iter()
+                .map(|operand| operand[v][l])
+                .collect()
+        };
+        fill_fresh_row(&config, coefficients, step, &mut state);
+        trace.update_row(step, &state);
+    }
+    if rows.next().is_some() {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "the stream holds more than {} coefficients",
+            config.degree
+        )));
+    }
+    Ok((config, trace))
+}
+
+fn check_coefficient_row(
+    config: &SchemeConfig,
+    k: usize,
+    row: &CoefficientRow,
+) -> Result<(), StarkHeError> {
+    if row.len() != config.operands {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "coefficient {} has {} operands, expected {}",
+            k,
+            row.len(),
+            config.operands
+        )));
+    }
+    for (d, operand) in row.iter().enumerate() {
+        for (v, component) in operand.iter().enumerate() {
+            if component.len() != config.levels {
+                return Err(StarkHeError::DimensionMismatch(format!(
+                    "coefficient {} of operand {} component {} has {} levels, expected {}",
+                    k,
+                    d,
+                    v,
+                    component.len(),
+                    config.levels
+                )));
+            }
+            for (l, &c) in component.iter().enumerate() {
+                let m = config.modulus[l];
+                if c >= m {
+                    return Err(StarkHeError::InvalidInput(format!(
+                        "coefficient {} of operand {} component {} level {} is {}, not reduced \
+                         modulo {}",
+                        k, d, v, l, c, m
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Row `row` of a FreshAir trace, `coefficients(v, l, k)` giving coefficient k of component v at
+// level l of every operand.
+fn fill_fresh_row(
+    config: &SchemeConfig,
+    coefficients: impl Fn(usize, usize, usize) -> Vec<u64>,
+    row: usize,
+    state: &mut [BaseElement],
+) {
+    let levels = config.levels;
+    let (chunk, i) = (row / fresh_group(levels), row % fresh_group(levels));
+    let m = config.row_modulus(i);
+    state[MODULUS_COLUMN] = element_from_u64(m);
+    for j in 0..config.coeffs_per_row {
+        let k = chunk * config.coeffs_per_row + j;
+        let operands: Vec<u64> = match i < VALUE_NUM * levels && k < config.degree {
+            true => coefficients(i / levels, i % levels, k),
+            false => vec![0; config.operands],
+        };
+        let reduction = config.reduction(j);
+        for (d, &value) in operands.iter().enumerate() {
+            state[config.operand_column(j) + d] = element_from_u64(value);
+        }
+        let x = fresh_value(&operands, m);
+        state[config.lane(j)] = element_from_u64(reduction.fill(x, m, state));
+    }
+}
+
+pub fn get_pub_inputs(trace: &TraceType, config: &SchemeConfig) -> PublicInputs {
+    // VALUE_NUM x levels x degree
+    let result = (0..VALUE_NUM)
+        .map(|v| {
+            let mut levels = vec![Vec::new(); config.levels];
+            for (l, level) in levels.iter_mut().enumerate() {
+                *level = (0..config.degree)
+                    .map(|k| trace.get(config.column(k), config.row(k, v, l)))
+                    .collect();
+            }
+            levels
+        })
+        .collect();
     PublicInputs {
-        result: [
-            [
-                trace.get_column(0 + COEFF_LEVEL).to_vec(),
-                trace.get_column(1 + COEFF_LEVEL).to_vec(),
-            ],
-            [
-                trace.get_column(2 + COEFF_LEVEL).to_vec(),
-                trace.get_column(3 + COEFF_LEVEL).to_vec(),
-            ],
-        ],
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(config.modulus.clone()),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: config.packing(),
+        input: None,
+    }
+}
+
+// FreshAir over the public inputs of every circuit, which record the results, moduli and packing
+// of `add` proofs as the AddPublicInputs of the verifier crate do.
+pub type FreshAir = stark_he_verifier::fresh::FreshAir<PublicInputs>;
+
+impl FreshInputs for PublicInputs {
+    fn into_fresh(self) -> (Vec<Vec<Vec<BaseElement>>>, Vec<u64>, Option<Packing>) {
+        (
+            self.result,
+            self.modulus.expect("add proofs record their moduli"),
+            self.packing,
+        )
+    }
+}
+
+// The public inputs of an `add` proof, for verify_add of the verifier crate.
+impl From<&PublicInputs> for AddPublicInputs {
+    fn from(public_inputs: &PublicInputs) -> Self {
+        AddPublicInputs {
+            result: public_inputs.result.clone(),
+            modulus: public_inputs.modulus.clone().unwrap_or_default(),
+            scheme: public_inputs.scheme.map(|scheme| scheme.tag()),
+            packing: public_inputs.packing,
+        }
     }
 }
 
-pub struct FreshAir {
-    context: AirContext<BaseElement>,
-    result: [[Vec<BaseElement>; COEFF_LEVEL]; VALUE_NUM],
+// The reductions of row `row` of FreshAir with `config` with their operands substituted, one
+// per lane, as printed by `stark-he explain`; operands are named a, b, c, ... in data file
+// order.
+pub fn explain_fresh_row(
+    row: usize,
+    config: &SchemeConfig,
+    current: &[BaseElement],
+) -> Vec<String> {
+    let levels = config.levels;
+    let (chunk, i) = (row / fresh_group(levels), row % fresh_group(levels));
+    let sum = |terms: &[String]| {
+        let (last, rest) = terms.split_last().expect("additions have operands");
+        format!("{} - {}", rest.join(" + "), last)
+    };
+    let m = current[MODULUS_COLUMN];
+    (0..config.coeffs_per_row)
+        .map(|j| {
+            let k = chunk * config.coeffs_per_row + j;
+            let target = match i < VALUE_NUM * levels && k < config.degree {
+                true => format!("result[{}][{}] coefficient {}", i / levels, i % levels, k),
+                false => format!("padding of coefficient {}", k),
+            };
+            let reduction = config.reduction(j);
+            let (names, values): (Vec<String>, Vec<String>) = current
+                [config.operand_column(j)..config.lane(j + 1)]
+                .iter()
+                .enumerate()
+                .map(|(d, value)| (((b'a' + d as u8) as char).to_string(), value.to_string()))
+                .unzip();
+            format!(
+                "{} = {} + q - k * q: {} = {} + {} - {} * {}",
+                target,
+                sum(&names),
+                current[config.lane(j)],
+                sum(&values),
+                m,
+                reduction.quotient(current),
+                m
+            )
+        })
+        .collect()
 }
 
-impl Air for FreshAir {
-    type BaseField = BaseElement;
-    type PublicInputs = PublicInputs;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stark_he_verifier::fresh::{evaluate_fresh_lane, RESULT_COLUMN};
+    use winter_air::{Air, EvaluationFrame, FieldExtension, HashFunction};
+    use winter_prover::Trace;
+    use winter_utils::SliceReader;
+
+    // xorshift64*, enough to spread samples over the whole u64 range without extra deps
+    fn samples(count: usize) -> Vec<u64> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut values = vec![0, 1, u32::MAX as u64, 1 << 63, u64::MAX - 1, u64::MAX];
+        for _ in 0..count {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            values.push(state.wrapping_mul(0x2545_f491_4f6c_dd1d));
+        }
+        // the values of the field, every u64 but in Goldilocks
+        values.retain(|&value| (value as u128) < FIELD_MODULUS);
+        values
+    }
+
+    #[test]
+    fn u64_element_round_trip() {
+        for value in samples(100_000) {
+            assert_eq!(element_to_u64(element_from_u64(value)), value);
+            assert_eq!(element_to_u128(element_from_u64(value)), value as u128);
+        }
+    }
+
+    // every element of the 64-bit field fits
+    #[cfg(not(feature = "goldilocks"))]
+    #[test]
+    #[should_panic(expected = "does not fit into u64")]
+    fn wide_element_is_rejected() {
+        element_to_u64(BaseElement::new(u64::MAX as u128 + 1));
+    }
+
+    #[test]
+    fn data_round_trip() {
+        let values = samples(DATA_LEN * 64);
+        let mut data = Data {
+            result: vec![vec![Vec::new(); COEFF_LEVEL]; VALUE_NUM],
+            ..Default::default()
+        };
+        for (i, chunk) in values.chunks(64).take(DATA_LEN).enumerate() {
+            data.result[i / COEFF_LEVEL][i % COEFF_LEVEL] = chunk.to_vec();
+        }
+        let expected = data.result.clone();
+        let encoding = ProofEncoding::default();
+        data.proof = encoding.encode([1u8, 2, 3]);
+
+        let (public_input, proof) = from_data(data).unwrap();
+        let data = to_data(Op::Add, proof, public_input);
+        assert_eq!(data.format_version, PROOF_FORMAT_VERSION);
+        assert_eq!(data.circuit, "add");
+        assert_eq!(data.result, expected);
+        assert_eq!(encoding.decode(&data.proof).unwrap(), vec![1u8, 2, 3]);
+    }
+
+    #[cfg(feature = "goldilocks")]
+    #[test]
+    fn results_outside_the_field_are_rejected() {
+        let mut data = Data {
+            result: vec![vec![vec![0]; COEFF_LEVEL]; VALUE_NUM],
+            ..Default::default()
+        };
+        data.result[1][0][0] = FIELD_MODULUS as u64;
+        assert!(from_data(data).is_err());
+    }
+
+    // the configuration of a data file is the one verifiers derive from the shape of its trace
+    #[test]
+    fn scheme_config_round_trip() {
+        let arg = InputArg {
+            data_file_path: String::new(),
+            op: Op::Add,
+            evaluation_key: None,
+            rotation: None,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        };
+        let operand = |levels: usize| std::array::from_fn(|_| vec![vec![0; COEFF_DEGREE]; levels]);
+        for (operands, levels) in [
+            (2, 1),
+            (3, 2),
+            (5, 3),
+            (FRESH_MAX_OPERANDS, MAX_COEFF_LEVEL),
+        ] {
+            let data = CustomData {
+                modulus: (0..levels as u64).map(|l| 65537 + 65536 * l).collect(),
+                coeff_degree: None,
+                levels: None,
+                values: (0..operands).map(|_| operand(levels)).collect(),
+            };
+            let config = SchemeConfig::from_data(&arg, &data).unwrap();
+            assert_eq!(
+                (config.degree, config.levels, config.operands),
+                (COEFF_DEGREE, levels, operands)
+            );
+            let derived = SchemeConfig::from_trace(
+                config.width(),
+                config.trace_length(),
+                levels,
+                COEFF_DEGREE,
+                &config.modulus,
+                None,
+            )
+            .unwrap();
+            assert_eq!(derived, config);
+            // one modulus per level
+            assert!(SchemeConfig::from_trace(
+                config.width(),
+                config.trace_length(),
+                levels,
+                COEFF_DEGREE,
+                &config.modulus[1..],
+                None,
+            )
+            .is_err());
+        }
+
+        let data = CustomData {
+            modulus: vec![65537; 2],
+            coeff_degree: None,
+            levels: None,
+            values: vec![operand(2), operand(1)],
+        };
+        assert!(SchemeConfig::from_data(&arg, &data).is_err());
+
+        // an unreduced coefficient, and moduli the Barrett blocks cannot reduce by
+        let mut data = CustomData {
+            modulus: vec![65537],
+            coeff_degree: None,
+            levels: None,
+            values: vec![operand(1), operand(1)],
+        };
+        data.values[1][0][0][3] = 65537;
+        assert!(matches!(
+            SchemeConfig::from_data(&arg, &data),
+            Err(StarkHeError::InvalidInput(_))
+        ));
+        data.values[1][0][0][3] = 0;
+        for m in [1, 1 << 62] {
+            data.modulus = vec![m];
+            assert!(matches!(
+                SchemeConfig::from_data(&arg, &data),
+                Err(StarkHeError::InvalidInput(_))
+            ));
+        }
+        let trace = |width, length, levels, degree| {
+            SchemeConfig::from_trace(width, length, levels, degree, &vec![65537; levels], None)
+        };
+        assert!(trace(fresh_width(2) - 1, 2 * COEFF_DEGREE, 1, COEFF_DEGREE).is_err());
+        assert!(trace(fresh_width(3), 2 * COEFF_DEGREE, 0, COEFF_DEGREE).is_err());
+        assert!(trace(fresh_width(3), 4 * COEFF_DEGREE, 1, COEFF_DEGREE).is_err());
+        assert!(trace(fresh_width(3), 4 * COEFF_DEGREE, 1, COEFF_DEGREE + 1).is_ok());
+        assert!(trace(
+            fresh_width(3),
+            4 * MAX_COEFF_DEGREE,
+            1,
+            2 * MAX_COEFF_DEGREE
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn only_padding_rows_are_exempt() {
+        for levels in 1..=MAX_COEFF_LEVEL {
+            for coeffs_per_row in [1, 4] {
+                let config = SchemeConfig {
+                    degree: COEFF_DEGREE,
+                    levels,
+                    operands: 3,
+                    coeffs_per_row,
+                    modulus_bits: BARRETT_MODULUS_BITS,
+                    modulus: Vec::new(),
+                };
+                let length = config.trace_length();
+                let boundary = config.boundary();
+                let exempt = length - boundary.exemptions()..length;
+                let results = (0..COEFF_DEGREE).flat_map(|k| {
+                    let config = &config;
+                    (0..VALUE_NUM).flat_map(move |v| (0..levels).map(move |l| config.row(k, v, l)))
+                });
+                match boundary {
+                    // the exempt last row is checked as the next row of the one before
+                    Boundary::NextRow => assert_eq!(config.group_padding(), 0),
+                    Boundary::Padding(rows) => {
+                        assert_eq!(rows, 1);
+                        assert_ne!(config.group_padding(), 0);
+                        assert!(results.clone().all(|row| !exempt.contains(&row)));
+                    }
+                }
+                assert_eq!(results.max(), Some(length - 1 - config.group_padding()));
+            }
+        }
+    }
+
+    #[test]
+    fn padding_rows_hold_zeros() {
+        let (m, degree) = (65537, COEFF_DEGREE + COEFF_DEGREE / 2);
+        let values = samples(3 * VALUE_NUM * degree);
+        let mut values = values.iter().map(|value| value % m);
+        let data = CustomData {
+            modulus: vec![m],
+            coeff_degree: Some(degree),
+            levels: None,
+            values: (0..3)
+                .map(|_| {
+                    std::array::from_fn(|_| {
+                        vec![(0..degree).map(|_| values.next().unwrap()).collect()]
+                    })
+                })
+                .collect(),
+        };
+        let arg = InputArg {
+            data_file_path: String::new(),
+            op: Op::Add,
+            evaluation_key: None,
+            rotation: None,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        };
+        let config = SchemeConfig::from_data(&arg, &data).unwrap();
+        assert_eq!(config.padded_degree(), 2 * COEFF_DEGREE);
+        assert_eq!(config.trace_length(), 2 * COEFF_DEGREE * fresh_group(1));
+        let mut trace = build_fresh_trace(&config, &data);
+        let options = ProofOptions::new(
+            28,
+            8,
+            0,
+            HashFunction::Blake3_256,
+            FieldExtension::None,
+            4,
+            256,
+        );
+        let air = FreshAir::new(
+            TraceInfo::new(config.width(), config.trace_length()),
+            get_pub_inputs(&trace, &config),
+            options,
+        );
+        let periodic = air.get_periodic_column_values();
+        let violations = |trace: &TraceType| -> Vec<usize> {
+            let width = trace.main_trace_width();
+            let (mut current, mut next) = (
+                vec![BaseElement::ZERO; width],
+                vec![BaseElement::ZERO; width],
+            );
+            let mut result =
+                vec![BaseElement::ZERO; air.context().num_main_transition_constraints()];
+            (0..trace.length() - air.context().num_transition_exemptions())
+                .filter(|&row| {
+                    trace.read_row_into(row, &mut current);
+                    trace.read_row_into(row + 1, &mut next);
+                    let frame = EvaluationFrame::from_rows(current.clone(), next.clone());
+                    let values: Vec<BaseElement> =
+                        periodic.iter().map(|column| column[row]).collect();
+                    air.evaluate_transition(&frame, &values, &mut result);
+                    result.iter().any(|&value| value != BaseElement::ZERO)
+                })
+                .collect()
+        };
+        assert_eq!(violations(&trace), Vec::<usize>::new());
+        // the modulus column is pinned to the public modulus on the rows of every coefficient
+        let assertions = air.get_assertions();
+        for v in 0..VALUE_NUM {
+            let pinned = Assertion::periodic(
+                MODULUS_COLUMN,
+                config.row(0, v, 0),
+                fresh_group(1),
+                element_from_u64(m),
+            );
+            assert!(assertions.contains(&pinned));
+        }
+
+        // a padding coefficient of one, correctly reduced
+        let row = config.row(degree, 0, 0);
+        let mut state = vec![BaseElement::ZERO; config.width()];
+        trace.read_row_into(row, &mut state);
+        let reduction = config.reduction(0);
+        state[config.operand_column(0)] = BaseElement::ONE;
+        let remainder = reduction.fill(fresh_value(&[1, 0, 0], m), m, &mut state);
+        state[config.lane(0)] = element_from_u64(remainder);
+        for (column, &value) in state.iter().enumerate() {
+            trace.set(column, row, value);
+        }
+        assert_eq!(violations(&trace), vec![row - 1, row]);
+    }
 
-    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
-        let degrees = vec![TransitionConstraintDegree::new(2); DATA_LEN];
-        let num_assertions = DATA_LEN * 2;
+    #[test]
+    fn packed_rows_reduce_every_lane() {
+        let m = 65537;
+        let values = samples(3 * VALUE_NUM * COEFF_DEGREE);
+        let mut values = values.iter().map(|value| value % m);
+        let data = CustomData {
+            modulus: vec![m],
+            coeff_degree: None,
+            levels: None,
+            values: (0..3)
+                .map(|_| {
+                    std::array::from_fn(|_| {
+                        vec![(0..COEFF_DEGREE).map(|_| values.next().unwrap()).collect()]
+                    })
+                })
+                .collect(),
+        };
+        let mut arg = InputArg {
+            data_file_path: String::new(),
+            op: Op::Add,
+            evaluation_key: None,
+            rotation: None,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: Some(4),
+            he_security: None,
+            operands: Vec::new(),
+        };
+        let config = SchemeConfig::from_data(&arg, &data).unwrap();
+        assert_eq!((config.coeffs_per_row, config.modulus_bits), (4, 17));
+        assert_eq!(config.width(), RESULT_COLUMN + 4 * (1 + 2 + 2 * 17 + 3));
+        assert_eq!(config.trace_length(), COEFF_DEGREE / 4 * fresh_group(1));
+        let derived = SchemeConfig::from_trace(
+            config.width(),
+            config.trace_length(),
+            1,
+            COEFF_DEGREE,
+            &config.modulus,
+            config.packing(),
+        )
+        .unwrap();
+        assert_eq!(derived, config);
+        assert!(SchemeConfig::from_trace(
+            config.width(),
+            config.trace_length(),
+            1,
+            COEFF_DEGREE,
+            &config.modulus,
+            None
+        )
+        .is_err());
+        // a public modulus wider than the packed lanes
+        assert!(SchemeConfig::from_trace(
+            config.width(),
+            config.trace_length(),
+            1,
+            COEFF_DEGREE,
+            &[1 << 17 | 1],
+            config.packing(),
+        )
+        .is_err());
 
-        FreshAir {
-            context: AirContext::new(trace_info, degrees, num_assertions, options),
-            result: pub_inputs.result,
+        let trace = build_fresh_trace(&config, &data);
+        let public_input = get_pub_inputs(&trace, &config);
+        assert_eq!(public_input.packing, config.packing());
+        for v in 0..VALUE_NUM {
+            for k in 0..COEFF_DEGREE {
+                let operands: Vec<u64> = data.values.iter().map(|d| d[v][0][k]).collect();
+                assert_eq!(
+                    public_input.result[v][0][k],
+                    element_from_u64(barrett_reduce(fresh_value(&operands, m), m).1)
+                );
+            }
+        }
+        let mut row = vec![BaseElement::ZERO; config.width()];
+        let mut result = vec![BaseElement::ZERO; config.reduction(0).num_constraints()];
+        for r in 0..config.trace_length() {
+            trace.read_row_into(r, &mut row);
+            for j in 0..config.coeffs_per_row {
+                evaluate_fresh_lane(&config, j, &row, element_from_u64(m), &mut result);
+                assert!(result.iter().all(|&value| value == BaseElement::ZERO));
+            }
         }
+
+        arg.coeffs_per_row = Some(3);
+        assert!(SchemeConfig::from_data(&arg, &data).is_err());
+        // 16 lanes of 17-bit moduli are wider than a trace
+        arg.coeffs_per_row = Some(16);
+        assert!(SchemeConfig::from_data(&arg, &data).is_err());
+        let packing = Packing {
+            coeffs_per_row: 1,
+            modulus_bits: 17,
+        };
+        assert!(SchemeConfig::from_trace(
+            fresh_width(3),
+            2 * COEFF_DEGREE,
+            1,
+            COEFF_DEGREE,
+            &[65537],
+            Some(packing)
+        )
+        .is_err());
     }
 
-    fn context(&self) -> &AirContext<Self::BaseField> {
-        &self.context
+    #[test]
+    fn operands_must_share_a_level() {
+        let modulus = [65537, 114689, 147457];
+        assert_eq!(check_active_levels(&modulus, None, 3), Ok(3));
+        assert_eq!(check_active_levels(&modulus, Some(&[2, 2, 2]), 3), Ok(2));
+        assert!(check_active_levels(&modulus, Some(&[2, 3, 2]), 3).is_err());
+        assert!(check_active_levels(&modulus, Some(&[2, 2]), 3).is_err());
+        assert!(check_active_levels(&modulus, Some(&[0, 0, 0]), 3).is_err());
+        assert!(check_active_levels(&modulus, Some(&[4, 4, 4]), 3).is_err());
+        assert!(check_active_levels(&[65537, 114689, 65537], None, 3).is_err());
+
+        let data = CustomData {
+            modulus: modulus.to_vec(),
+            coeff_degree: None,
+            levels: Some(vec![2, 2]),
+            values: (0..2)
+                .map(|_| std::array::from_fn(|_| vec![vec![0; COEFF_DEGREE]; 2]))
+                .collect(),
+        };
+        let arg = InputArg {
+            data_file_path: String::new(),
+            op: Op::Add,
+            evaluation_key: None,
+            rotation: None,
+            expose: None,
+            scheme: Scheme::Bfv,
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        };
+        let config = SchemeConfig::from_data(&arg, &data).unwrap();
+        assert_eq!((config.levels, config.modulus), (2, modulus[..2].to_vec()));
     }
 
-    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
-        &self,
-        frame: &EvaluationFrame<E>,
-        _periodic_values: &[E],
-        result: &mut [E],
-    ) {
-        let current = frame.current();
-        let next = frame.next();
+    #[test]
+    fn traces_build_from_memory() {
+        let m = 65537;
+        let values = samples(3 * VALUE_NUM * COEFF_DEGREE);
+        let mut values = values.iter().map(|value| value % m);
+        let data = CustomData {
+            modulus: vec![m],
+            coeff_degree: None,
+            levels: None,
+            values: (0..3)
+                .map(|_| {
+                    std::array::from_fn(|_| {
+                        vec![(0..COEFF_DEGREE).map(|_| values.next().unwrap()).collect()]
+                    })
+                })
+                .collect(),
+        };
+        let dir = std::env::temp_dir().join(format!("stark-he-memory-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.toml");
+        std::fs::write(&path, toml::to_string(&data).unwrap()).unwrap();
+        let arg = InputArg {
+            data_file_path: path.to_string_lossy().into_owned(),
+            ..InputArg::default()
+        };
+        let (config, trace) = build_trace_from_data(&InputArg::default(), &data).unwrap();
+        assert_eq!(config, load_scheme_config(&arg).unwrap());
+        let columns = |trace: &TraceType| -> Vec<Vec<BaseElement>> {
+            (0..trace.main_trace_width())
+                .map(|column| trace.main_segment().get_column(column).to_vec())
+                .collect()
+        };
+        assert_eq!(columns(&trace), columns(&build_trace(&arg).unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
 
-        for i in RESULT_START..RESULT_END {
-            let idx = i - RESULT_START;
-            let l_idx = idx % COEFF_LEVEL;
-            let offset = i + FLAG_NUM * FLAG_LEN + DATA_LEN;
-            let d1 = current[offset];
-            let d2 = current[offset + DATA_LEN];
-            let d3 = current[offset + 2 * DATA_LEN];
-            let m = current[l_idx];
-            let r1 = d1 + d2;
+        // errors are returned rather than raised
+        let data = CustomData {
+            values: data.values[..1].to_vec(),
+            ..data
+        };
+        assert!(matches!(
+            build_trace_from_data(&InputArg::default(), &data),
+            Err(StarkHeError::DimensionMismatch(_))
+        ));
+    }
 
-            let ret = (r1 - current[FLAG_START + idx] * m)
-                + current[FLAG_START + FLAG_LEN + idx] * m
-                - d3;
-            result[idx] = next[i] - ret;
-            // println!(
-            //     "evaluate_transition ret:{} next[{}]:{} result[{}]:{}",
-            //     ret, i, next[i], idx, result[idx]
-            // );
+    #[test]
+    fn traces_build_from_rows() {
+        let m = 65537;
+        let values = samples(3 * VALUE_NUM * COEFF_DEGREE);
+        let mut values = values.iter().map(|value| value % m);
+        let data = CustomData {
+            modulus: vec![m],
+            coeff_degree: None,
+            levels: None,
+            values: (0..3)
+                .map(|_| {
+                    std::array::from_fn(|_| {
+                        vec![(0..COEFF_DEGREE).map(|_| values.next().unwrap()).collect()]
+                    })
+                })
+                .collect(),
+        };
+        let header = CustomData {
+            modulus: vec![m],
+            coeff_degree: None,
+            levels: None,
+            values: Vec::new(),
+        };
+        let rows = |data: &CustomData| -> Vec<Result<CoefficientRow, StarkHeError>> {
+            (0..COEFF_DEGREE)
+                .map(|k| {
+                    Ok(data
+                        .values
+                        .iter()
+                        .map(|operand| std::array::from_fn(|v| vec![operand[v][0][k]]))
+                        .collect())
+                })
+                .collect()
+        };
+        let columns = |trace: &TraceType| -> Vec<Vec<BaseElement>> {
+            (0..trace.main_trace_width())
+                .map(|column| trace.main_segment().get_column(column).to_vec())
+                .collect()
+        };
+        for coeffs_per_row in [None, Some(4)] {
+            let arg = InputArg {
+                coeffs_per_row,
+                ..InputArg::default()
+            };
+            let (config, trace) = build_trace_from_data(&arg, &data).unwrap();
+            let (streamed_config, streamed) =
+                build_trace_from_rows(&arg, &header, 3, rows(&data)).unwrap();
+            assert_eq!(streamed_config, config);
+            assert_eq!(columns(&streamed), columns(&trace));
+        }
+
+        // short, long, misshapen and failing streams
+        let arg = InputArg::default();
+        let mut short = rows(&data);
+        short.pop();
+        assert!(build_trace_from_rows(&arg, &header, 3, short).is_err());
+        let mut long = rows(&data);
+        let first = long[0].as_ref().unwrap().clone();
+        long.push(Ok(first));
+        assert!(build_trace_from_rows(&arg, &header, 3, long).is_err());
+        let mut misshapen = rows(&data);
+        misshapen[7].as_mut().unwrap().pop();
+        assert!(matches!(
+            build_trace_from_rows(&arg, &header, 3, misshapen),
+            Err(StarkHeError::DimensionMismatch(_))
+        ));
+        let mut unreduced = rows(&data);
+        unreduced[7].as_mut().unwrap()[2][1][0] = m;
+        assert!(matches!(
+            build_trace_from_rows(&arg, &header, 3, unreduced),
+            Err(StarkHeError::InvalidInput(_))
+        ));
+        let mut failing = rows(&data);
+        failing[9] = Err(StarkHeError::InvalidInput(String::from("connection reset")));
+        assert!(matches!(
+            build_trace_from_rows(&arg, &header, 3, failing),
+            Err(StarkHeError::InvalidInput(message)) if message == "connection reset"
+        ));
+    }
+
+    #[test]
+    fn data_file_errors_are_typed() {
+        let dir = std::env::temp_dir().join(format!("stark-he-errors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("missing.toml");
+        let result = load_data_file::<CiphertextData>(&missing.to_string_lossy());
+        assert!(matches!(result, Err(StarkHeError::Io { .. })));
+        assert!(!missing.exists());
+
+        let malformed = dir.join("malformed.toml");
+        std::fs::write(&malformed, "Modulus = [").unwrap();
+        let arg = InputArg {
+            data_file_path: malformed.to_string_lossy().into_owned(),
+            ..InputArg::default()
+        };
+        assert!(matches!(build_trace(&arg), Err(StarkHeError::Parse { .. })));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn full_public_inputs() -> PublicInputs {
+        let vector = |start: u64| vec![start, start + 1, u64::MAX - start];
+        PublicInputs {
+            result: vec![
+                vec![vec![element_from_u128(FIELD_MODULUS - 1)]; COEFF_LEVEL],
+                vec![vec![element_from_u64(3), element_from_u64(5)]; COEFF_LEVEL],
+            ],
+            evaluation_key: Some(EvaluationKey {
+                modulus: vec![97, 193],
+                values: vec![
+                    Default::default(),
+                    [(); VALUE_NUM].map(|_| [(); COEFF_LEVEL].map(|_| vector(7))),
+                ],
+            }),
+            rotation: Some(-3),
+            scale: Some(Scale {
+                modulus: 193,
+                before: 2f64.powi(40),
+                after: 2f64.powi(33),
+            }),
+            modulus: Some(vec![97, 193]),
+            plain_modulus: Some(17),
+            decryption: Some(Decryption {
+                ciphertext: [(); VALUE_NUM].map(|_| [(); COEFF_LEVEL].map(|_| vector(11))),
+            }),
+            slots: Some(CkksSlots {
+                scale: 2f64.powi(30),
+                values: vec![[0.5, -0.25], [-1.0, 0.0]],
+            }),
+            scalar: Some(9),
+            weights: Some(vec![1, 2, 3]),
+            result_digest: Some([0xab; 32]),
+            window: Some(vec![0, 2, 15]),
+            scheme: Some(Scheme::Bgv),
+            packing: Some(Packing {
+                coeffs_per_row: 4,
+                modulus_bits: 30,
+            }),
+            input: Some(AutomorphismInput {
+                ciphertext: [(); VALUE_NUM].map(|_| [(); COEFF_LEVEL].map(|_| vector(13))),
+            }),
         }
     }
 
-    // [[Vec<BaseElement>; COEFF_LEVEL]; VALUE_NUM],
-    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
-        let last = self.trace_length() - 1;
-        vec![
-            Assertion::single(RESULT_START, 0, self.result[0][0][0]),
-            Assertion::single(RESULT_START + 1, 0, self.result[0][1][0]),
-            Assertion::single(RESULT_START + 2, 0, self.result[1][0][0]),
-            Assertion::single(RESULT_START + 3, 0, self.result[1][1][0]),
-            Assertion::single(RESULT_START, last, self.result[0][0][last]),
-            Assertion::single(RESULT_START + 1, last, self.result[0][1][last]),
-            Assertion::single(RESULT_START + 2, last, self.result[1][0][last]),
-            Assertion::single(RESULT_START + 3, last, self.result[1][1][last]),
-        ]
+    #[test]
+    fn public_inputs_round_trip() {
+        let full = full_public_inputs();
+        let bare = PublicInputs {
+            result: vec![vec![Vec::new(); COEFF_LEVEL]],
+            evaluation_key: None,
+            rotation: None,
+            scale: None,
+            modulus: None,
+            plain_modulus: None,
+            decryption: None,
+            slots: None,
+            scalar: None,
+            weights: None,
+            result_digest: None,
+            window: None,
+            scheme: None,
+            packing: None,
+            input: None,
+        };
+        for public_inputs in [full, bare] {
+            let bytes = public_inputs.to_bytes();
+            let decoded = PublicInputs::read_from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, public_inputs);
+            assert_eq!(decoded.commitment(), public_inputs.commitment());
+            let mut reader = SliceReader::new(&bytes);
+            assert_eq!(PublicInputs::read_from(&mut reader).unwrap(), public_inputs);
+        }
+    }
+
+    // the verifier crate seeds the transcript of `add` proofs with the same bytes
+    #[test]
+    fn add_public_inputs_encode_like_public_inputs() {
+        let values = samples(VALUE_NUM * COEFF_LEVEL * 4);
+        let mut public_inputs = PublicInputs {
+            result: values
+                .chunks(COEFF_LEVEL * 4)
+                .take(VALUE_NUM)
+                .map(|component| {
+                    component
+                        .chunks(4)
+                        .map(|level| level.iter().map(|&x| element_from_u64(x)).collect())
+                        .collect()
+                })
+                .collect(),
+            evaluation_key: None,
+            rotation: None,
+            scale: None,
+            modulus: Some(vec![65537, 114689]),
+            plain_modulus: None,
+            decryption: None,
+            slots: None,
+            scalar: None,
+            weights: None,
+            result_digest: None,
+            window: None,
+            scheme: None,
+            packing: None,
+            input: None,
+        };
+        for (scheme, packing) in [
+            (None, None),
+            (
+                Some(Scheme::Bgv),
+                Some(Packing {
+                    coeffs_per_row: 4,
+                    modulus_bits: 17,
+                }),
+            ),
+        ] {
+            public_inputs.scheme = scheme;
+            public_inputs.packing = packing;
+            let add = AddPublicInputs::from(&public_inputs);
+            assert_eq!(add.to_bytes(), public_inputs.to_bytes());
+            assert_eq!(add.to_canonical_bytes(), public_inputs.to_canonical_bytes());
+        }
+    }
+
+    #[test]
+    fn public_inputs_reject_malformed_bytes() {
+        let public_inputs = full_public_inputs();
+        let bytes = public_inputs.to_bytes();
+        assert_eq!(
+            PublicInputs::read_from_bytes(&bytes).unwrap(),
+            public_inputs
+        );
+        for len in [0, 10, bytes.len() / 2, bytes.len() - 1] {
+            assert!(PublicInputs::read_from_bytes(&bytes[..len]).is_err());
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            PublicInputs::read_from_bytes(&trailing),
+            Err(DeserializationError::UnconsumedBytes)
+        ));
+        // the presence flag of the packing, followed by its two u32 and the input of
+        // VALUE_NUM * COEFF_LEVEL vectors of three u64, then a scheme tag no scheme has
+        let mut flag = bytes.clone();
+        let packing = flag.len() - 9 - (1 + VALUE_NUM * COEFF_LEVEL * (4 + 3 * 8));
+        flag[packing] = 2;
+        assert!(PublicInputs::read_from_bytes(&flag).is_err());
+        let mut scheme = bytes.clone();
+        scheme[packing - 1] = 7;
+        assert!(PublicInputs::read_from_bytes(&scheme).is_err());
+        // the first result element, set to the field modulus
+        let mut element = bytes;
+        let first = 4 + PUBLIC_INPUTS_EXCHANGE_TAG.len() + 3 * 4;
+        element[first..first + 16].copy_from_slice(&FIELD_MODULUS.to_le_bytes());
+        assert!(PublicInputs::read_from_bytes(&element).is_err());
     }
 }
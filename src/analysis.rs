@@ -0,0 +1,285 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Coefficient statistics for operands and results: a linear histogram of raw magnitudes over
+// [0, m) and a log2 histogram of centered values (coefficients lifted to (-m/2, m/2]), which is
+// how HE noise is usually reasoned about.
+
+use std::fmt::Write;
+use std::fs;
+
+use crate::air::{
+    check_active_levels, element_from_u64, element_to_u64, fresh_result, BaseElement, CustomData,
+    Data, Op, VALUE_NUM,
+};
+use crate::packed::read_custom_data;
+
+const BAR_WIDTH: usize = 48;
+
+pub struct Series {
+    pub name: String,
+    pub modulus: u64,
+    pub coeffs: Vec<u64>,
+}
+
+pub struct Histogram {
+    // (bin start, bin end, count), bins are half-open
+    pub bins: Vec<(u64, u64, usize)>,
+}
+
+pub struct SeriesStats {
+    pub name: String,
+    pub modulus: u64,
+    pub magnitude: Histogram,
+    pub centered_bits: Histogram,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub max_centered_bits: u32,
+    // log2(m / 2) - max centered bits: how much the values could still grow before wrapping
+    pub headroom_bits: i64,
+}
+
+pub fn centered(value: u64, modulus: u64) -> i128 {
+    if value > modulus / 2 {
+        value as i128 - modulus as i128
+    } else {
+        value as i128
+    }
+}
+
+fn bit_length(value: u128) -> u32 {
+    128 - value.leading_zeros()
+}
+
+pub fn analyze(series: &Series, bins: usize) -> SeriesStats {
+    let bins = bins.max(1) as u64;
+    let width = series.modulus.div_ceil(bins).max(1);
+    let mut magnitude: Vec<(u64, u64, usize)> = (0..bins)
+        .map(|i| (i * width, ((i + 1) * width).min(series.modulus), 0))
+        .collect();
+    let modulus_bits = bit_length(series.modulus as u128);
+    let mut centered_bits: Vec<(u64, u64, usize)> =
+        (0..=modulus_bits as u64).map(|b| (b, b + 1, 0)).collect();
+
+    let mut sum = 0f64;
+    let mut sum_sq = 0f64;
+    let mut max_bits = 0;
+    for &value in series.coeffs.iter() {
+        let bin = (value / width).min(bins - 1) as usize;
+        magnitude[bin].2 += 1;
+
+        let c = centered(value, series.modulus);
+        let bits = bit_length(c.unsigned_abs());
+        centered_bits[bits as usize].2 += 1;
+        max_bits = max_bits.max(bits);
+
+        sum += c as f64;
+        sum_sq += (c as f64) * (c as f64);
+    }
+    let n = series.coeffs.len().max(1) as f64;
+    let mean = sum / n;
+    let variance = (sum_sq / n - mean * mean).max(0f64);
+
+    SeriesStats {
+        name: series.name.clone(),
+        modulus: series.modulus,
+        magnitude: Histogram { bins: magnitude },
+        centered_bits: Histogram {
+            bins: centered_bits,
+        },
+        mean,
+        std_dev: variance.sqrt(),
+        max_centered_bits: max_bits,
+        headroom_bits: (modulus_bits as i64 - 1) - max_bits as i64,
+    }
+}
+
+// Operands from the data file, plus results either taken from a proof file or recomputed the
+// way the circuit computes them.
+pub fn load_series(
+    data_file_path: &str,
+    proof_file_path: Option<&str>,
+) -> Result<Vec<Series>, String> {
+    let data: CustomData = read_custom_data(data_file_path)?;
+    // the operands only hold the levels of the first moduli
+    let levels = check_active_levels(&data.modulus, data.levels.as_deref(), data.values.len())?;
+    let modulus = &data.modulus[..levels];
+    let mut series = Vec::new();
+    for (d, operand) in data.values.iter().enumerate() {
+        for (v, component) in operand.iter().enumerate() {
+            for (l, &modulus) in modulus.iter().enumerate() {
+                series.push(Series {
+                    name: format!("operand {} component {} level {}", d, v, l),
+                    modulus,
+                    coeffs: component[l].clone(),
+                });
+            }
+        }
+    }
+
+    let proof: Option<Data> = proof_file_path.map(read_toml).transpose()?;
+    if let Some(proof) = &proof {
+        if proof.circuit != Op::Add.name() {
+            return Err(format!(
+                "{} is a {} proof, only {} proofs match a data file with {} operands",
+                proof_file_path.unwrap_or_default(),
+                proof.circuit,
+                Op::Add.name(),
+                data.values.len()
+            ));
+        }
+    }
+    for v in 0..VALUE_NUM {
+        for (l, &m) in modulus.iter().enumerate() {
+            let coeffs = match &proof {
+                Some(proof) => proof.result[v][l].clone(),
+                None => (0..data.values[0][v][l].len())
+                    .map(|k| {
+                        let operands: Vec<BaseElement> = data
+                            .values
+                            .iter()
+                            .map(|operand| element_from_u64(operand[v][l][k]))
+                            .collect();
+                        element_to_u64(fresh_result(&operands, element_from_u64(m)))
+                    })
+                    .collect(),
+            };
+            series.push(Series {
+                name: format!("result component {} level {}", v, l),
+                modulus: m,
+                coeffs,
+            });
+        }
+    }
+    Ok(series)
+}
+
+fn read_toml<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))
+}
+
+pub fn render_text(stats: &SeriesStats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} (modulus {}, {} bits)\n  centered mean {:.1}, std dev {:.1} (~2^{:.1}), max |value| 2^{}, headroom {} bits",
+        stats.name,
+        stats.modulus,
+        bit_length(stats.modulus as u128),
+        stats.mean,
+        stats.std_dev,
+        stats.std_dev.max(1f64).log2(),
+        stats.max_centered_bits,
+        stats.headroom_bits
+    );
+    let _ = writeln!(out, "  magnitude:");
+    render_bars(&mut out, &stats.magnitude, |start, end| {
+        format!("[{:>20}, {:>20})", start, end)
+    });
+    let _ = writeln!(out, "  centered |value| bit length:");
+    let first = stats
+        .centered_bits
+        .bins
+        .iter()
+        .position(|bin| bin.2 > 0)
+        .unwrap_or(0);
+    let trimmed = Histogram {
+        bins: stats.centered_bits.bins[first..].to_vec(),
+    };
+    render_bars(&mut out, &trimmed, |bits, _| format!("{:>3} bits", bits));
+    out
+}
+
+fn render_bars<F: Fn(u64, u64) -> String>(out: &mut String, histogram: &Histogram, label: F) {
+    let max = histogram
+        .bins
+        .iter()
+        .map(|bin| bin.2)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for &(start, end, count) in histogram.bins.iter() {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+        let _ = writeln!(out, "    {} {:>6} {}", label(start, end), count, bar);
+    }
+}
+
+pub fn render_csv(stats: &[SeriesStats]) -> String {
+    let mut out = String::from("series,modulus,histogram,bin_start,bin_end,count\n");
+    for s in stats {
+        for (kind, histogram) in [
+            ("magnitude", &s.magnitude),
+            ("centered_bits", &s.centered_bits),
+        ] {
+            for &(start, end, count) in histogram.bins.iter() {
+                let _ = writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    s.name, s.modulus, kind, start, end, count
+                );
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(coeffs: &[u64]) -> Series {
+        Series {
+            name: String::from("a[0][0]"),
+            modulus: 17,
+            coeffs: coeffs.to_vec(),
+        }
+    }
+
+    fn counts(histogram: &Histogram) -> Vec<usize> {
+        histogram.bins.iter().map(|bin| bin.2).collect()
+    }
+
+    #[test]
+    fn empty_series_have_empty_bins() {
+        let stats = analyze(&series(&[]), 4);
+        assert_eq!(
+            stats.magnitude.bins,
+            [(0, 5, 0), (5, 10, 0), (10, 15, 0), (15, 17, 0)]
+        );
+        // bit lengths 0 to 5 of the centered values
+        assert_eq!(counts(&stats.centered_bits), [0; 6]);
+        assert_eq!((stats.mean, stats.std_dev), (0.0, 0.0));
+        assert_eq!(stats.max_centered_bits, 0);
+        assert_eq!(stats.headroom_bits, 4);
+    }
+
+    #[test]
+    fn single_values_fall_in_one_bin() {
+        // 16 is -1 centered
+        let stats = analyze(&series(&[16]), 4);
+        assert_eq!(counts(&stats.magnitude), [0, 0, 0, 1]);
+        assert_eq!(counts(&stats.centered_bits), [0, 1, 0, 0, 0, 0]);
+        assert_eq!((stats.mean, stats.std_dev), (-1.0, 0.0));
+        assert_eq!((stats.max_centered_bits, stats.headroom_bits), (1, 3));
+
+        // 8 is the largest value kept positive
+        let stats = analyze(&series(&[8]), 0);
+        assert_eq!(stats.magnitude.bins, [(0, 17, 1)]);
+        assert_eq!(counts(&stats.centered_bits), [0, 0, 0, 0, 1, 0]);
+        assert_eq!((stats.max_centered_bits, stats.headroom_bits), (4, 0));
+        assert_eq!(centered(9, 17), -8);
+    }
+
+    #[test]
+    fn values_are_binned_by_magnitude_and_centered_bits() {
+        let stats = analyze(&series(&[0, 4, 5, 9, 14, 15, 16, 1]), 4);
+        assert_eq!(counts(&stats.magnitude), [3, 2, 1, 2]);
+        // 0; 1, -1; -2, -3; 4, 5; -8
+        assert_eq!(counts(&stats.centered_bits), [1, 2, 2, 2, 1, 0]);
+        assert_eq!(stats.max_centered_bits, 4);
+        assert_eq!(stats.mean, -0.5);
+    }
+}
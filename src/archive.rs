@@ -0,0 +1,228 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Prover archives. A proving run produces the proof container, which is the verifier package:
+// the proof, the public inputs and the parameters, nothing else. With an archive directory it
+// also keeps what only the prover may see, for audits and reproduction:
+//
+//     archive.toml      index: circuit, proof path and digests, timings, archived files
+//     inputs/           copies of the data file and the evaluation key
+//     trace.bin         snapshot of the main execution trace
+//     prover.log        the log of the run
+//
+// The trace snapshot is the canonical encoding (tag TRACE_SNAPSHOT_TAG) of the trace width and
+// length as u32 followed by every column as a sequence of field elements.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use winter_prover::Matrix;
+
+use crate::air::{BaseElement, InputArg, Op};
+use crate::canonical::{commitment, to_hex, CanonicalWriter};
+use crate::operand_files::expand_operand_files;
+use crate::reproducibility::ReproducibilityManifest;
+use crate::status::unix_now;
+
+pub const ARCHIVE_INDEX: &str = "archive.toml";
+pub const TRACE_SNAPSHOT_TAG: &str = "STARK-HE/trace-snapshot/v1";
+const TRACE_FILE: &str = "trace.bin";
+const LOG_FILE: &str = "prover.log";
+const INPUT_DIR: &str = "inputs";
+
+// Log lines of the run, shared between the logger and the archive writer.
+#[derive(Clone, Default)]
+pub struct LogCapture(Arc<Mutex<String>>);
+
+impl LogCapture {
+    pub fn text(&self) -> String {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+// Forwards to env_logger and keeps a copy of every line it prints.
+struct TeeLogger {
+    inner: env_logger::Logger,
+    capture: LogCapture,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            let mut text = self.capture.0.lock().unwrap();
+            text.push_str(&record.args().to_string());
+            text.push('\n');
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// Installs the logger built by `builder`, capturing its output.
+pub fn init_logger(mut builder: env_logger::Builder) -> LogCapture {
+    let inner = builder.build();
+    let capture = LogCapture::default();
+    log::set_max_level(inner.filter());
+    log::set_boxed_logger(Box::new(TeeLogger {
+        inner,
+        capture: capture.clone(),
+    }))
+    .expect("the logger is installed once");
+    capture
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedInput {
+    // "data" or "evaluation-key"
+    pub role: String,
+    pub source: String,
+    pub file: String,
+    pub digest: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceSnapshot {
+    pub file: String,
+    pub width: usize,
+    pub length: usize,
+    pub digest: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    pub circuit: String,
+    pub proof: String,
+    pub proof_digest: String,
+    pub public_inputs_commitment: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<i32>,
+    pub created: u64,
+    pub proving_ms: u64,
+    pub log: String,
+    pub trace: TraceSnapshot,
+    pub inputs: Vec<ArchivedInput>,
+    pub reproducibility: ReproducibilityManifest,
+}
+
+// Where the prover archive goes and the log to put into it.
+pub struct ProverArchive {
+    pub dir: PathBuf,
+    pub log: LogCapture,
+}
+
+impl ProverArchive {
+    // Refuses a directory holding an earlier archive unless `force` is set, before anything is
+    // proven.
+    pub fn new(dir: &Path, log: LogCapture, force: bool) -> Result<Self, String> {
+        if dir.join(ARCHIVE_INDEX).exists() && !force {
+            return Err(format!(
+                "{} already exists, pass --force to overwrite it",
+                dir.display()
+            ));
+        }
+        Ok(ProverArchive {
+            dir: dir.to_path_buf(),
+            log,
+        })
+    }
+}
+
+pub fn encode_trace(trace: &Matrix<BaseElement>) -> Vec<u8> {
+    let mut writer = CanonicalWriter::new(TRACE_SNAPSHOT_TAG);
+    writer.write_u32(trace.num_cols() as u32);
+    writer.write_u32(trace.num_rows() as u32);
+    for column in trace.columns() {
+        writer.write_elements(column);
+    }
+    writer.into_bytes()
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::File::create(path)
+        .and_then(|mut file| file.write_all(bytes))
+        .map_err(|err| format!("cannot write {}: {}", path.display(), err))
+}
+
+fn archive_input(input_dir: &Path, role: &str, source: &str) -> Result<ArchivedInput, String> {
+    let bytes = fs::read(source).map_err(|err| format!("cannot read {}: {}", source, err))?;
+    let name = Path::new(source)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("cannot derive a file name from {}", source))?;
+    let file = format!("{}/{}-{}", INPUT_DIR, role, name);
+    write_file(&input_dir.join(format!("{}-{}", role, name)), &bytes)?;
+    Ok(ArchivedInput {
+        role: String::from(role),
+        source: String::from(source),
+        file,
+        digest: to_hex(&commitment(&bytes)),
+    })
+}
+
+// Everything a proving run knows beyond the proof container.
+pub struct RunRecord<'a> {
+    pub input_args: &'a InputArg,
+    pub proof_file_path: &'a Path,
+    pub proof_bytes: &'a [u8],
+    pub public_inputs_commitment: [u8; 32],
+    pub proving_ms: u64,
+    pub trace: &'a Matrix<BaseElement>,
+    pub reproducibility: ReproducibilityManifest,
+}
+
+pub fn write_archive(archive: &ProverArchive, record: RunRecord) -> Result<(), String> {
+    let input_dir = archive.dir.join(INPUT_DIR);
+    fs::create_dir_all(&input_dir)
+        .map_err(|err| format!("cannot create {}: {}", input_dir.display(), err))?;
+
+    let args = record.input_args;
+    let mut inputs = Vec::new();
+    if args.operands.is_empty() {
+        inputs.push(archive_input(&input_dir, "data", &args.data_file_path)?);
+    }
+    for (i, file) in expand_operand_files(&args.operands)?.iter().enumerate() {
+        let role = format!("operand-file-{}", i);
+        inputs.push(archive_input(&input_dir, &role, &file.to_string_lossy())?);
+    }
+    if let Some(key) = &args.evaluation_key {
+        inputs.push(archive_input(&input_dir, "evaluation-key", key)?);
+    }
+
+    let trace_bytes = encode_trace(record.trace);
+    write_file(&archive.dir.join(TRACE_FILE), &trace_bytes)?;
+    write_file(&archive.dir.join(LOG_FILE), archive.log.text().as_bytes())?;
+
+    let index = ArchiveIndex {
+        circuit: String::from(args.op.name()),
+        proof: record.proof_file_path.display().to_string(),
+        proof_digest: to_hex(&commitment(record.proof_bytes)),
+        public_inputs_commitment: to_hex(&record.public_inputs_commitment),
+        rotation: (args.op == Op::Rotate).then_some(args.rotation).flatten(),
+        created: unix_now(),
+        proving_ms: record.proving_ms,
+        log: String::from(LOG_FILE),
+        trace: TraceSnapshot {
+            file: String::from(TRACE_FILE),
+            width: record.trace.num_cols(),
+            length: record.trace.num_rows(),
+            digest: to_hex(&commitment(&trace_bytes)),
+        },
+        inputs,
+        reproducibility: record.reproducibility,
+    };
+    let text = toml::to_string(&index).map_err(|err| err.to_string())?;
+    write_file(&archive.dir.join(ARCHIVE_INDEX), text.as_bytes())
+}
@@ -0,0 +1,148 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Proving from async services. A proof keeps a core busy for seconds, which would stall every
+// other task of a tokio executor thread, so `prove_async` moves the trace building and the STARK
+// of `prove` onto the blocking pool of the current runtime and returns a ProveJob, a future of
+// the Proof. Its ProveStatus reports the phase, trace shape and proof statistics of the job as
+// the status files of `prover --status-dir` do, from any task and after the job is awaited.
+
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tokio::task::JoinHandle;
+use winter_air::ProofOptions;
+
+use crate::air::{build_trace_from_data, CustomData, InputArg, Op};
+use crate::error::StarkHeError;
+use crate::status::{load_all_from, JobStatus, Phase, StatusReporter};
+use crate::storage::MemoryStorage;
+use crate::{prove_add, Proof};
+
+// The status of one ProveJob, kept in a store of its own.
+#[derive(Clone)]
+pub struct ProveStatus {
+    storage: Arc<MemoryStorage>,
+}
+
+impl ProveStatus {
+    pub fn get(&self) -> JobStatus {
+        load_all_from(self.storage.as_ref())
+            .pop()
+            .unwrap_or_default()
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.get().phase
+    }
+}
+
+// A proof running on the blocking pool. Dropping the job detaches it: the proof still runs to
+// completion, and its status is still reported.
+pub struct ProveJob {
+    task: JoinHandle<Result<Proof, StarkHeError>>,
+    status: ProveStatus,
+}
+
+impl ProveJob {
+    pub fn status(&self) -> ProveStatus {
+        self.status.clone()
+    }
+}
+
+impl Future for ProveJob {
+    type Output = Result<Proof, StarkHeError>;
+
+    // A panic of the prover resumes in the task awaiting the job, as it would have in `prove`.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task)
+            .poll(cx)
+            .map(|joined| match joined {
+                Ok(result) => result,
+                Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+                Err(err) => panic!("proving task did not complete: {}", err),
+            })
+    }
+}
+
+// Proves the addition of the operands of `data` as `prove` does, on the blocking pool of the
+// tokio runtime it is called from; it panics outside of a runtime. A job whose data is rejected,
+// or whose proof fails, reports Phase::Failed with the message of the error.
+pub fn prove_async(data: CustomData, options: ProofOptions) -> ProveJob {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut status = StatusReporter::with_storage(storage.clone(), Op::Add.name());
+    status.set_phase(Phase::Queued);
+    let task = tokio::task::spawn_blocking(move || {
+        run(&data, options, &mut status).map_err(|err| {
+            status.update(|s| s.message = Some(err.to_string()));
+            status.set_phase(Phase::Failed);
+            err
+        })
+    });
+    ProveJob {
+        task,
+        status: ProveStatus { storage },
+    }
+}
+
+fn run(
+    data: &CustomData,
+    options: ProofOptions,
+    status: &mut StatusReporter,
+) -> Result<Proof, StarkHeError> {
+    status.set_phase(Phase::BuildingTrace);
+    let (config, trace) = build_trace_from_data(&InputArg::default(), data)?;
+    let now = Instant::now();
+    let proof = prove_add(config, trace, options, status)?;
+    let proving_ms = now.elapsed().as_millis() as u64;
+    status.update(|s| {
+        s.proving_ms = Some(proving_ms);
+        s.proof_size_bytes = Some(proof.proof.to_bytes().len());
+        s.security_bits = Some(proof.proof.security_level(true));
+    });
+    status.set_phase(Phase::Proved);
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::{COEFF_DEGREE, VALUE_NUM};
+    use winter_air::{FieldExtension, HashFunction};
+
+    #[tokio::test]
+    async fn rejected_data_fails_the_job() {
+        let operand = || std::array::from_fn::<_, VALUE_NUM, _>(|_| vec![vec![0; COEFF_DEGREE]]);
+        let mut data = CustomData {
+            modulus: vec![65537],
+            values: vec![operand(), operand()],
+            ..Default::default()
+        };
+        data.values[1][0][0][3] = 65537;
+        // the data is rejected before the options are used
+        let options = ProofOptions::new(
+            28,
+            8,
+            0,
+            HashFunction::Blake3_256,
+            FieldExtension::None,
+            4,
+            256,
+        );
+        let job = prove_async(data, options);
+        let status = job.status();
+        assert!(matches!(job.await, Err(StarkHeError::InvalidInput(_))));
+        let reported = status.get();
+        assert_eq!(reported.phase, Phase::Failed);
+        assert!(reported
+            .message
+            .unwrap()
+            .contains("not reduced modulo 65537"));
+    }
+}
@@ -0,0 +1,424 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// RNS base extension by fast base conversion, the step of BFV multiplication which moves the
+// operands to an auxiliary base and of CKKS key switching which lifts them to the special primes.
+// A ciphertext with residues x_i modulo the source moduli q_0, .., q_(L-1) of every level, with
+// Q = q_0 * .. * q_(L-1) and Q_i = Q / q_i, gets residues modulo the target moduli p_t:
+//
+//     y_i = x_i * (Q_i^-1 mod q_i) mod q_i,  r_t = sum_i y_i * (Q_i mod p_t) mod p_t
+//
+// As in the fast conversion of SEAL, the result represents x + u * Q for some 0 <= u < L rather
+// than x itself. The target base is separate from the levels of the ciphertext: it has
+// BASE_EXT_TARGET_NUM moduli, one more than the source base, and the result holds one component
+// per target modulus and ciphertext component, at the first level. Every circuit row reduces
+// x_i * (Q_i^-1 mod q_i) to y_i and sum_i y_i * (Q_i mod p_t) to r_t with the Barrett blocks of
+// reduction.rs, one value per row of a row group. The moduli are public inputs and, like the
+// constants derived from them, enter the constraints as constants, so the trace has no modulus
+// columns.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, first_level, level_column, load_data_file, BaseElement, InputArg,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::error::StarkHeError;
+use crate::modswitch_air::inverse_mod;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::mul_mod;
+use crate::reduction::{bit_length, reduction_group, reduction_width, Reductions};
+
+pub const BASE_EXT_TARGET_NUM: usize = COEFF_LEVEL + 1;
+// one component per target modulus and ciphertext component, target major
+pub const BASE_EXT_RESULT_NUM: usize = VALUE_NUM * BASE_EXT_TARGET_NUM;
+
+// Result + Scaled + Data + Reduction
+// R00 R01 R02 R10 R11 R12 Y00 Y01 Y10 Y11 X00 X01 X10 X11 K.. R.. S..
+const SOURCE_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = 0;
+const SCALED_START: usize = RESULT_START + BASE_EXT_RESULT_NUM;
+const DATA_START: usize = SCALED_START + SOURCE_LEN;
+const DATA_END: usize = DATA_START + SOURCE_LEN;
+// every scaled residue, then every result
+const REDUCTION_NUM: usize = SOURCE_LEN + BASE_EXT_RESULT_NUM;
+// a sum of COEFF_LEVEL products of two residues leaves a quotient below COEFF_LEVEL * 2^62
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(((COEFF_LEVEL as u128) << MUL_MAX_MODULUS_BITS) - 1),
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per scaled residue and result, then padding
+pub const BASE_EXT_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of BaseExtAir in canonical order.
+pub fn base_ext_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("target_num", BASE_EXT_TARGET_NUM),
+        ("group", BASE_EXT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// A ciphertext with residues modulo the source moduli in `Modulus` and the moduli of the target
+// base in `Target`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BaseExtData {
+    pub modulus: Vec<u64>,
+    pub target: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+fn result_column(v: usize, t: usize) -> usize {
+    RESULT_START + v * BASE_EXT_TARGET_NUM + t
+}
+
+fn scaled_column(v: usize, l: usize) -> usize {
+    level_column(SCALED_START, v, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+// Moduli a base extension can be proven with: the source moduli followed by the target moduli,
+// all of them small enough for the sums of products to stay below the field modulus, and the
+// source moduli pairwise coprime.
+pub fn check_base_ext_modulus(modulus: &[u64]) -> Result<(), String> {
+    if modulus.len() != COEFF_LEVEL + BASE_EXT_TARGET_NUM {
+        return Err(format!(
+            "base extensions need {} source and {} target moduli, found {} moduli",
+            COEFF_LEVEL,
+            BASE_EXT_TARGET_NUM,
+            modulus.len()
+        ));
+    }
+    for &m in modulus.iter() {
+        if m < 2 || 64 - m.leading_zeros() > MUL_MAX_MODULUS_BITS {
+            return Err(format!(
+                "modulus {} does not fit into {} bits",
+                m, MUL_MAX_MODULUS_BITS
+            ));
+        }
+    }
+    for i in 0..COEFF_LEVEL {
+        for j in 0..i {
+            if inverse_mod(modulus[j], modulus[i]).is_none() {
+                return Err(format!(
+                    "source moduli {} and {} are not coprime",
+                    modulus[j], modulus[i]
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Checks that `result` holds, for every ciphertext component and target modulus, the residues
+// of every coefficient modulo that target at the first level.
+pub fn check_base_ext(modulus: &[u64], result: &[Vec<Vec<u64>>]) -> Result<(), String> {
+    check_base_ext_modulus(modulus)?;
+    let target = &modulus[COEFF_LEVEL..];
+    for (r, component) in result.iter().enumerate() {
+        let p = target[r % BASE_EXT_TARGET_NUM];
+        if component[0].len() != COEFF_DEGREE
+            || component[0].iter().any(|&x| x >= p)
+            || component[1..].iter().any(|level| !level.is_empty())
+        {
+            return Err(format!(
+                "result component {} is not {} residues modulo {} at the first level",
+                r, COEFF_DEGREE, p
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Constants of the conversion: Q_i^-1 mod q_i for every source modulus and Q_i mod p_t for every
+// source and target modulus.
+pub struct BaseExtConstants {
+    pub scale: [u64; COEFF_LEVEL],
+    pub weights: [[u64; BASE_EXT_TARGET_NUM]; COEFF_LEVEL],
+}
+
+impl BaseExtConstants {
+    pub fn new(modulus: &[u64]) -> Self {
+        let (source, target) = modulus.split_at(COEFF_LEVEL);
+        let mut scale = [1; COEFF_LEVEL];
+        let mut weights = [[1; BASE_EXT_TARGET_NUM]; COEFF_LEVEL];
+        for i in 0..COEFF_LEVEL {
+            for j in (0..COEFF_LEVEL).filter(|&j| j != i) {
+                scale[i] = mul_mod(scale[i], source[j] % source[i], source[i]);
+                for t in 0..BASE_EXT_TARGET_NUM {
+                    weights[i][t] = mul_mod(weights[i][t], source[j] % target[t], target[t]);
+                }
+            }
+            scale[i] = inverse_mod(scale[i], source[i]).expect("source moduli are coprime");
+        }
+        BaseExtConstants { scale, weights }
+    }
+}
+
+// One coefficient of a component after base extension, with the scaled residues and the values
+// reduced to them and to the results.
+pub struct ExtendedCoefficient {
+    pub scaled: [u64; COEFF_LEVEL],
+    pub results: [u64; BASE_EXT_TARGET_NUM],
+    pub products: [u128; COEFF_LEVEL],
+    pub sums: [u128; BASE_EXT_TARGET_NUM],
+}
+
+// Converts the residues `x` modulo the source moduli to the target moduli.
+pub fn extend_coefficient(
+    x: [u64; COEFF_LEVEL],
+    modulus: &[u64],
+    constants: &BaseExtConstants,
+) -> ExtendedCoefficient {
+    let mut scaled = [0; COEFF_LEVEL];
+    let mut products = [0; COEFF_LEVEL];
+    for i in 0..COEFF_LEVEL {
+        products[i] = x[i] as u128 * constants.scale[i] as u128;
+        scaled[i] = (products[i] % modulus[i] as u128) as u64;
+    }
+    let mut results = [0; BASE_EXT_TARGET_NUM];
+    let mut sums = [0; BASE_EXT_TARGET_NUM];
+    for t in 0..BASE_EXT_TARGET_NUM {
+        let p = modulus[COEFF_LEVEL + t] as u128;
+        sums[t] = (0..COEFF_LEVEL)
+            .map(|i| scaled[i] as u128 * constants.weights[i][t] as u128)
+            .sum();
+        results[t] = (sums[t] % p) as u64;
+    }
+    ExtendedCoefficient {
+        scaled,
+        results,
+        products,
+        sums,
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`, the source moduli followed by the
+// target moduli: scaled residue i of component v, value v * COEFF_LEVEL + i of the row, modulo
+// q_i, then result t of component v, value SOURCE_LEN + v * BASE_EXT_TARGET_NUM + t, modulo p_t.
+// The values are bounded with the public constants, so the quotients take no bit they cannot
+// reach, e.g. none for the scaled residues of a single source modulus, whose scale is 1.
+pub fn base_ext_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_base_ext_modulus(modulus)?;
+    let (source, target) = modulus.split_at(COEFF_LEVEL);
+    let constants = BaseExtConstants::new(modulus);
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for v in 0..VALUE_NUM {
+        for (l, &q) in source.iter().enumerate() {
+            results.push(scaled_column(v, l));
+            moduli.push(q);
+            bounds.push((q as u128 - 1) * constants.scale[l] as u128);
+        }
+    }
+    for v in 0..VALUE_NUM {
+        for (t, &p) in target.iter().enumerate() {
+            results.push(result_column(v, t));
+            moduli.push(p);
+            bounds.push(
+                source
+                    .iter()
+                    .zip(constants.weights.iter())
+                    .map(|(&q, weights)| (q as u128 - 1) * weights[t] as u128)
+                    .sum(),
+            );
+        }
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Source moduli followed by the target moduli of the data file at `data_file_path`.
+pub fn load_base_ext_modulus(data_file_path: &str) -> Result<Vec<u64>, StarkHeError> {
+    let data: BaseExtData = load_data_file(data_file_path)?;
+    Ok([data.modulus, data.target].concat())
+}
+
+pub fn build_base_ext_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: BaseExtData = load_data_file(&arg.data_file_path)?;
+    let modulus = [data.modulus.as_slice(), data.target.as_slice()].concat();
+    check_base_ext_modulus(&modulus).map_err(StarkHeError::InvalidInput)?;
+    for (v, component) in data.values.iter().enumerate() {
+        for (l, level) in component.iter().enumerate() {
+            assert!(
+                level.len() == COEFF_DEGREE && level.iter().all(|&x| x < modulus[l]),
+                "component {} level {} must be {} residues modulo {}",
+                v,
+                l,
+                COEFF_DEGREE,
+                modulus[l]
+            );
+        }
+    }
+
+    let reductions = base_ext_reductions(&modulus).map_err(StarkHeError::InvalidInput)?;
+    let constants = BaseExtConstants::new(&modulus);
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    for v in 0..VALUE_NUM {
+        for k in 0..STATE_LENGTH {
+            let x = std::array::from_fn(|l| data.values[v][l][k]);
+            let extended = extend_coefficient(x, &modulus, &constants);
+            for l in 0..COEFF_LEVEL {
+                columns[data_column(v, l)][k] = element_from_u64(x[l]);
+                columns[scaled_column(v, l)][k] = element_from_u64(extended.scaled[l]);
+                values[k][v * COEFF_LEVEL + l] = extended.products[l];
+            }
+            for t in 0..BASE_EXT_TARGET_NUM {
+                columns[result_column(v, t)][k] = element_from_u64(extended.results[t]);
+                values[k][SOURCE_LEN + v * BASE_EXT_TARGET_NUM + t] = extended.sums[t];
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// The residues modulo every target, read on the first row of every group, and the moduli, which
+// the constraints use as constants.
+pub fn get_base_ext_pub_inputs(trace: &TraceType, modulus: &[u64]) -> PublicInputs {
+    let mut result = Vec::with_capacity(BASE_EXT_RESULT_NUM);
+    for v in 0..VALUE_NUM {
+        for t in 0..BASE_EXT_TARGET_NUM {
+            let column = trace.get_column(result_column(v, t));
+            result.push(first_level(
+                column.iter().step_by(BASE_EXT_GROUP).copied().collect(),
+            ));
+        }
+    }
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(modulus.to_vec()),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The values one row reduces: every scaled residue at v * COEFF_LEVEL + l, then the sum of every
+// result.
+fn row_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    scale: &[E],
+    weights: &[[E; BASE_EXT_TARGET_NUM]],
+) -> [E; REDUCTION_NUM] {
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            values[v * COEFF_LEVEL + l] = row[data_column(v, l)] * scale[l];
+        }
+        for t in 0..BASE_EXT_TARGET_NUM {
+            values[SOURCE_LEN + v * BASE_EXT_TARGET_NUM + t] = (0..COEFF_LEVEL)
+                .fold(E::ZERO, |sum, l| {
+                    sum + row[scaled_column(v, l)] * weights[l][t]
+                });
+        }
+    }
+    values
+}
+
+pub struct BaseExtAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    scale: Vec<BaseElement>,
+    weights: Vec<[BaseElement; BASE_EXT_TARGET_NUM]>,
+}
+
+impl Air for BaseExtAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The moduli and the constants derived from them are public, so the values are linear in the
+    // trace; the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("base extension proofs publish their moduli");
+        let reductions = base_ext_reductions(&modulus).expect("base extension moduli were checked");
+        let constants = BaseExtConstants::new(&modulus);
+        let degrees = reductions.degrees(1, &[]);
+        let context = AirContext::new(trace_info, degrees, BASE_EXT_RESULT_NUM, options);
+
+        BaseExtAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            result: pub_inputs.result,
+            scale: constants.scale.map(element_from_u64).to_vec(),
+            weights: constants
+                .weights
+                .map(|weights| weights.map(element_from_u64))
+                .to_vec(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let scale: Vec<E> = self.scale.iter().map(|&s| E::from(s)).collect();
+        let weights: Vec<[E; BASE_EXT_TARGET_NUM]> = self
+            .weights
+            .iter()
+            .map(|weights| weights.map(E::from))
+            .collect();
+        let values = row_values(frame.current(), &scale, &weights);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    // The results are bound on the first row of every group.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(BASE_EXT_RESULT_NUM);
+        for v in 0..VALUE_NUM {
+            for t in 0..BASE_EXT_TARGET_NUM {
+                let r = v * BASE_EXT_TARGET_NUM + t;
+                columns.push((result_column(v, t), self.result[r][0].clone()));
+            }
+        }
+        self.reductions.bind_columns(columns)
+    }
+}
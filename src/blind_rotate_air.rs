@@ -0,0 +1,447 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// One step of the blind rotation of TFHE-style programmable bootstrapping. The accumulator is an
+// RLWE ciphertext ACC = (c0, c1) of the test polynomial, and step i rotates it by the monomial
+// X^a_i, a_i being coefficient i of the mod-switched LWE ciphertext, if and only if bit s_i of
+// the LWE secret key is set. The bit is only known encrypted, as the GGSW ciphertext BSK_i of
+// the bootstrapping key, so the step is the CMux
+//
+//     ACC' = ACC + BSK_i [x] (X^a_i * ACC - ACC)
+//
+// with the external product [x] of a GGSW and an RLWE ciphertext. It uses the RNS gadget of
+// KeySwitchAir: digit (u, j) of the difference is the residue modulo q_j of its component u, and
+// BSK_i holds one RLWE row per digit,
+//
+//     d_uj = (X^a_i - 1) * c_uj mod q_j,  c_v' = c_v + sum_(u, j) d_uj * bsk_(uj)v
+//
+// Ciphertexts are in evaluation form, so the monomial is a pointwise factor: row k holds
+// evaluation k at psi^(2 bitrev(k) + 1) as NttAir outputs it, where X^a_i takes the value
+// psi^((2 bitrev(k) + 1) a_i). The factors are periodic columns derived from the public a_i and
+// the moduli of the public inputs, which the key shares. As in KeySwitchAir, the digits and the
+// sums are reduced by the Barrett blocks of reduction.rs, one per row of a row group, against
+// those moduli, and the factors are stretched over the groups.
+//
+// A blind rotation runs one step per LWE coefficient, each proven separately; the initial
+// accumulator X^-b * v is public. Bootstrapping keys of TFHE-rs live in the Fourier domain of
+// the torus modulo 2^64 and have to be brought to NTT-friendly primes to be proven here.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::Ciphertext;
+use crate::error::StarkHeError;
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::{bit_reverse, check_ntt_modulus, minimal_primitive_root, pow_mod};
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, read_group_result,
+    reduction_group, reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Digit + Key + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 D00 D01 D10 D11 K000 K001 K010 K011 K100 .. K311 K.. R..
+// S..
+// one gadget digit per component and RNS level
+pub const BLIND_ROTATE_DIGIT_NUM: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const KEY_LEN: usize = BLIND_ROTATE_DIGIT_NUM * VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DIGIT_START: usize = DATA_START + RESULT_LEN;
+const KEY_START: usize = DIGIT_START + RESULT_LEN;
+const KEY_END: usize = KEY_START + KEY_LEN;
+// a sum is below (1 + digits * max q) * m, so its quotient below one plus a digit per component
+// and level
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(BLIND_ROTATE_DIGIT_NUM as u128 * ((1 << MUL_MAX_MODULUS_BITS) - 1) + 1),
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per digit and per result coefficient of every level, then padding
+pub const BLIND_ROTATE_GROUP: usize = reduction_group(2 * RESULT_LEN);
+
+const STATE_WIDTH: usize = KEY_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of BlindRotateAir in canonical order.
+pub fn blind_rotate_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("digit_num", BLIND_ROTATE_DIGIT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", BLIND_ROTATE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn digit_column(u: usize, j: usize) -> usize {
+    level_column(DIGIT_START, u, j)
+}
+
+// Key columns of digit `i` = u * COEFF_LEVEL + j, component `v` and level `l`.
+fn key_column(i: usize, v: usize, l: usize) -> usize {
+    level_column(KEY_START + i * VALUE_NUM * COEFF_LEVEL, v, l)
+}
+
+// Exponent of the monomial X^rotation, taken modulo 2N as X^N = -1.
+pub fn monomial_exponent(rotation: i32) -> u64 {
+    (rotation as i64).rem_euclid(2 * COEFF_DEGREE as i64) as u64
+}
+
+// Values of X^rotation modulo `q` at the evaluation points, in the order of NttAir's outputs.
+pub fn monomial_evaluations(rotation: i32, q: u64) -> Vec<u64> {
+    let psi = minimal_primitive_root(q).expect("moduli are checked before use");
+    let exponent = monomial_exponent(rotation);
+    let two_n = 2 * COEFF_DEGREE as u64;
+    (0..COEFF_DEGREE)
+        .map(|k| {
+            let point = 2 * bit_reverse(k) as u64 + 1;
+            pow_mod(psi, point * exponent % two_n, q)
+        })
+        .collect()
+}
+
+// Digit of one component before its reduction, for the monomial value `monomial` and the
+// accumulator residue `c`.
+pub fn cmux_digit(monomial: u64, c: u64) -> u128 {
+    (monomial - 1) as u128 * c as u128
+}
+
+// Accumulator components of one coefficient after the CMux at a level, before their reduction;
+// `key` holds the key at this level.
+pub fn cmux(
+    c: [u64; VALUE_NUM],
+    digits: [u64; BLIND_ROTATE_DIGIT_NUM],
+    key: [[u64; VALUE_NUM]; BLIND_ROTATE_DIGIT_NUM],
+) -> [u128; VALUE_NUM] {
+    std::array::from_fn(|v| {
+        let mut sum = c[v] as u128;
+        for i in 0..BLIND_ROTATE_DIGIT_NUM {
+            sum += digits[i] as u128 * key[i][v] as u128;
+        }
+        sum
+    })
+}
+
+// The reductions of a row by the public moduli `modulus`: digit (u, j) is value u * COEFF_LEVEL
+// + j of the row, component v at level l value RESULT_LEN + v * COEFF_LEVEL + l.
+pub fn blind_rotate_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::BlindRotate, modulus, MUL_MAX_MODULUS_BITS)?;
+    check_ntt_modulus(modulus)?;
+    let digits: u128 = modulus.iter().map(|&q| q as u128 - 1).sum::<u128>() * VALUE_NUM as u128;
+    let mut results = Vec::with_capacity(2 * RESULT_LEN);
+    let mut moduli = Vec::with_capacity(2 * RESULT_LEN);
+    let mut bounds = Vec::with_capacity(2 * RESULT_LEN);
+    for u in 0..VALUE_NUM {
+        for (j, &q) in modulus.iter().enumerate() {
+            results.push(digit_column(u, j));
+            moduli.push(q);
+            bounds.push((q as u128 - 1).pow(2));
+        }
+    }
+    for v in 0..VALUE_NUM {
+        for (l, &m) in modulus.iter().enumerate() {
+            results.push(result_column(v, l));
+            moduli.push(m);
+            bounds.push((m as u128 - 1) * (1 + digits));
+        }
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (RESULT_START..KEY_END).collect(),
+        KEY_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_blind_rotate_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let rotation = arg.rotation.ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("blind rotation needs --rotation"))
+    })?;
+    assert!(
+        monomial_exponent(rotation) != 0,
+        "rotation by a multiple of {} is the identity",
+        2 * COEFF_DEGREE
+    );
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("blind rotation needs --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    assert_eq!(
+        key.modulus, data.modulus,
+        "bootstrapping key moduli differ from the data moduli"
+    );
+    assert_eq!(
+        key.values.len(),
+        BLIND_ROTATE_DIGIT_NUM,
+        "bootstrapping key needs one digit per component and level"
+    );
+    check_ntt_modulus(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let ciphertext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.values)?;
+    let keys = key
+        .values
+        .into_iter()
+        .map(|digit| Ciphertext::from_levels(&data.modulus, STATE_LENGTH, digit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reductions =
+        blind_rotate_reductions(ciphertext.modulus()).map_err(StarkHeError::InvalidInput)?;
+    let monomials: Vec<Vec<u64>> = data
+        .modulus
+        .iter()
+        .map(|&m| monomial_evaluations(rotation, m))
+        .collect();
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; KEY_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    for (i, digit) in keys.iter().enumerate() {
+        digit.write_columns(&mut columns, |v, l| key_column(i, v, l));
+    }
+    // the values of every circuit row, digits first, then the sums
+    let mut values = vec![[0; 2 * RESULT_LEN]; STATE_LENGTH];
+    for l in 0..COEFF_LEVEL {
+        columns[l] = vec![element_from_u64(data.modulus[l]); STATE_LENGTH];
+    }
+    for k in 0..STATE_LENGTH {
+        let mut digits = [0; BLIND_ROTATE_DIGIT_NUM];
+        for u in 0..VALUE_NUM {
+            for j in 0..COEFF_LEVEL {
+                let product = cmux_digit(monomials[j][k], ciphertext.coeff(u, j, k));
+                digits[u * COEFF_LEVEL + j] = (product % data.modulus[j] as u128) as u64;
+                values[k][u * COEFF_LEVEL + j] = product;
+                columns[digit_column(u, j)][k] = element_from_u64(digits[u * COEFF_LEVEL + j]);
+            }
+        }
+        for l in 0..COEFF_LEVEL {
+            let m = data.modulus[l];
+            let level_key =
+                std::array::from_fn(|i| std::array::from_fn(|v| keys[i].coeff(v, l, k)));
+            let c = std::array::from_fn(|v| ciphertext.coeff(v, l, k));
+            for (v, sum) in cmux(c, digits, level_key).into_iter().enumerate() {
+                values[k][RESULT_LEN + v * COEFF_LEVEL + l] = sum;
+                columns[result_column(v, l)][k] = element_from_u64((sum % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// Results, the rotation and the bootstrapping key, read back from the first row of every group.
+pub fn get_blind_rotate_pub_inputs(trace: &TraceType, rotation: i32) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, BLIND_ROTATE_GROUP, result_column),
+        evaluation_key: Some(EvaluationKey::from_trace(
+            trace,
+            BLIND_ROTATE_DIGIT_NUM,
+            BLIND_ROTATE_GROUP,
+            key_column,
+        )),
+        rotation: Some(rotation),
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The values of one row with the monomial values `monomial` of its evaluation point, in the
+// order of blind_rotate_reductions.
+fn row_values<E: FieldElement>(row: &[E], monomial: &[E]) -> [E; 2 * RESULT_LEN] {
+    let mut values = [E::ZERO; 2 * RESULT_LEN];
+    for u in 0..VALUE_NUM {
+        for j in 0..COEFF_LEVEL {
+            values[u * COEFF_LEVEL + j] = (monomial[j] - E::ONE) * row[data_column(u, j)];
+        }
+    }
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let mut sum = row[data_column(v, l)];
+            for u in 0..VALUE_NUM {
+                for j in 0..COEFF_LEVEL {
+                    sum += row[digit_column(u, j)] * row[key_column(u * COEFF_LEVEL + j, v, l)];
+                }
+            }
+            values[RESULT_LEN + v * COEFF_LEVEL + l] = sum;
+        }
+    }
+    values
+}
+
+pub struct BlindRotateAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    rotation: i32,
+    key: EvaluationKey,
+}
+
+impl Air for BlindRotateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The sums are quadratic; a digit is linear with a monomial factor whose stretched column
+    // spans the trace, which adds the same degree. The moduli were checked by parse_data and
+    // against the key by verify_proof.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("blind rotation proofs record their moduli");
+        let reductions =
+            blind_rotate_reductions(&modulus).expect("blind rotation moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + KEY_LEN
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        BlindRotateAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            rotation: pub_inputs
+                .rotation
+                .expect("blind rotation proofs need the rotation"),
+            key: pub_inputs
+                .evaluation_key
+                .expect("blind rotation needs the bootstrapping key"),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    // Periodic values are those of the reductions, then the monomial at every level.
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let monomial = &periodic_values[self.reductions.num_periodic_columns()..];
+        let values = row_values(frame.current(), monomial);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut columns = self.reductions.periodic_columns();
+        for &m in self.modulus.iter() {
+            let values: Vec<BaseElement> = monomial_evaluations(self.rotation, m)
+                .into_iter()
+                .map(element_from_u64)
+                .collect();
+            columns.push(self.reductions.stretch(&values));
+        }
+        columns
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, key
+    // columns on the first row of every group, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(self.reductions.bind_columns(self.key.columns(key_column)));
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntt_air::{forward_transform, mul_mod};
+
+    const MODULUS: u64 = 65537;
+
+    // The periodic monomial values multiply evaluations like X^a multiplies coefficients,
+    // negacyclically.
+    #[test]
+    fn monomial_rotates_negacyclically() {
+        let coefficients: Vec<u64> = (0..COEFF_DEGREE as u64)
+            .map(|k| (k * k + 1) % MODULUS)
+            .collect();
+        for rotation in [1, 5, -3, COEFF_DEGREE as i32 + 2] {
+            let exponent = monomial_exponent(rotation) as usize;
+            let mut rotated = vec![0; COEFF_DEGREE];
+            for k in 0..COEFF_DEGREE {
+                let position = (k + exponent) % (2 * COEFF_DEGREE);
+                if position < COEFF_DEGREE {
+                    rotated[position] = coefficients[k];
+                } else {
+                    rotated[position - COEFF_DEGREE] = MODULUS - coefficients[k];
+                }
+            }
+            let monomial = monomial_evaluations(rotation, MODULUS);
+            let evaluations = forward_transform(coefficients.clone(), MODULUS);
+            let products: Vec<u64> = (0..COEFF_DEGREE)
+                .map(|k| mul_mod(monomial[k], evaluations[k], MODULUS))
+                .collect();
+            assert_eq!(products, forward_transform(rotated, MODULUS));
+        }
+    }
+
+    #[test]
+    fn cmux_selects_by_the_key() {
+        let m = MODULUS;
+        let (monomial, c) = (12345, [678, 9012]);
+        let mut digits = [0; BLIND_ROTATE_DIGIT_NUM];
+        for u in 0..VALUE_NUM {
+            digits[u * COEFF_LEVEL] = (cmux_digit(monomial, c[u]) % m as u128) as u64;
+        }
+        // a noiseless encryption of 1 under the zero key: row u of the gadget adds digit u to
+        // component u at the first level
+        let mut key = [[0; VALUE_NUM]; BLIND_ROTATE_DIGIT_NUM];
+        for u in 0..VALUE_NUM {
+            key[u * COEFF_LEVEL][u] = 1;
+        }
+        let results = cmux(c, digits, key);
+        for v in 0..VALUE_NUM {
+            assert_eq!((results[v] % m as u128) as u64, mul_mod(monomial, c[v], m));
+        }
+        let results = cmux(c, digits, [[0; VALUE_NUM]; BLIND_ROTATE_DIGIT_NUM]);
+        assert_eq!(results, c.map(u128::from));
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Canonical byte encoding used for every hash, signature and transcript input.
+//
+// The rules are deliberately simple so that other implementations (e.g. a Python verifier)
+// can reproduce the exact same bytes:
+// - every encoding starts with a length-prefixed ASCII domain tag;
+// - integers are written little-endian with a fixed width (u8, u32 or u64);
+// - field elements are written as their canonical integer value in 16 little-endian bytes;
+// - strings are written as their length as u32 followed by their UTF-8 bytes;
+// - sequences are prefixed with their length as u32 and written in index order.
+//
+// Encodings which are decoded again, like the exchange encoding of public inputs, also write
+// optional values as a presence flag, 0 or 1 as u8, followed by the value if there is one.
+
+use winter_air::ProofOptions;
+use winter_prover::crypto::{hashers::Blake3_256, Digest, Hasher};
+use winter_utils::{ByteReader, DeserializationError};
+
+use crate::air::{element_from_u128, BaseElement, FIELD_MODULUS};
+
+// The writer and the tags of public inputs are those of the verifier crate, so that its
+// AddPublicInputs encode like PublicInputs.
+pub use stark_he_verifier::canonical::{
+    CanonicalWriter, PUBLIC_INPUTS_EXCHANGE_TAG, PUBLIC_INPUTS_TAG,
+};
+pub const PARAMETERS_TAG: &str = "STARK-HE/parameters/v1";
+pub const CIRCUIT_TAG: &str = "STARK-HE/circuit/v1";
+pub const EVALUATION_KEY_TAG: &str = "STARK-HE/evaluation-key/v1";
+pub const RESULT_TAG: &str = "STARK-HE/result/v1";
+
+// Reading counterparts of the writer, for the encodings which are decoded again.
+
+pub fn read_tag<R: ByteReader>(source: &mut R, tag: &str) -> Result<(), DeserializationError> {
+    let len = source.read_u32()? as usize;
+    if len != tag.len() || source.read_u8_vec(len)? != tag.as_bytes() {
+        return Err(DeserializationError::InvalidValue(format!(
+            "not an encoding tagged {}",
+            tag
+        )));
+    }
+    Ok(())
+}
+
+pub fn read_f64<R: ByteReader>(source: &mut R) -> Result<f64, DeserializationError> {
+    Ok(f64::from_bits(source.read_u64()?))
+}
+
+// Field elements must be canonical, below the modulus.
+pub fn read_element<R: ByteReader>(source: &mut R) -> Result<BaseElement, DeserializationError> {
+    let mut bytes = [0; 16];
+    for byte in bytes.iter_mut() {
+        *byte = source.read_u8()?;
+    }
+    let value = u128::from_le_bytes(bytes);
+    if value >= FIELD_MODULUS {
+        return Err(DeserializationError::InvalidValue(format!(
+            "{} is not a canonical field element",
+            value
+        )));
+    }
+    Ok(element_from_u128(value))
+}
+
+// A sequence of values read by `read`; the length is not trusted for allocations, a sequence
+// longer than the input fails once the input is exhausted.
+pub fn read_sequence<R: ByteReader, T>(
+    source: &mut R,
+    mut read: impl FnMut(&mut R) -> Result<T, DeserializationError>,
+) -> Result<Vec<T>, DeserializationError> {
+    let len = source.read_u32()?;
+    (0..len).map(|_| read(source)).collect()
+}
+
+pub fn read_u64s<R: ByteReader>(source: &mut R) -> Result<Vec<u64>, DeserializationError> {
+    read_sequence(source, |source| source.read_u64())
+}
+
+pub fn read_option<R: ByteReader, T>(
+    source: &mut R,
+    read: impl FnOnce(&mut R) -> Result<T, DeserializationError>,
+) -> Result<Option<T>, DeserializationError> {
+    match source.read_u8()? {
+        0 => Ok(None),
+        1 => read(source).map(Some),
+        flag => Err(DeserializationError::InvalidValue(format!(
+            "invalid presence flag {}",
+            flag
+        ))),
+    }
+}
+
+// Writes the STARK protocol parameters; enum values follow their Winterfell discriminants.
+pub fn write_proof_options(writer: &mut CanonicalWriter, options: &ProofOptions) {
+    writer.write_u32(options.num_queries() as u32);
+    writer.write_u32(options.blowup_factor() as u32);
+    writer.write_u32(options.grinding_factor());
+    writer.write_u8(options.hash_fn() as u8);
+    writer.write_u8(options.field_extension() as u8);
+    let fri_options = options.to_fri_options();
+    writer.write_u32(fri_options.folding_factor() as u32);
+    writer.write_u32(fri_options.max_remainder_size() as u32);
+}
+
+// BLAKE3-256 digest of a canonical encoding.
+pub fn commitment(bytes: &[u8]) -> [u8; 32] {
+    Blake3_256::<BaseElement>::hash(bytes).as_bytes()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Inverse of to_hex for 32-byte digests; None unless `hex` has 64 hex digits.
+pub fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
@@ -0,0 +1,300 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Typed ciphertexts. Data files hold ciphertexts as nested arrays indexed by component, level and
+// coefficient, in which nothing ties a level to its modulus or checks its length. An RnsPoly
+// carries its moduli and ring degree and is only built from levels of `degree` coefficients
+// reduced by their modulus; a Ciphertext is a list of such polynomials over the same moduli and
+// degree, two components for fresh ciphertexts, three for tensor products. Trace builders convert
+// the arrays of their data file once, with typed errors, and then index by (component, level,
+// coefficient) without panicking on short or unreduced input.
+
+use crate::air::{element_from_u64, BaseElement, COEFF_LEVEL};
+use crate::error::StarkHeError;
+
+// A polynomial in RNS form: one coefficient vector per level, the level `l` reduced by
+// `modulus[l]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RnsPoly {
+    modulus: Vec<u64>,
+    degree: usize,
+    levels: Vec<Vec<u64>>,
+}
+
+impl RnsPoly {
+    pub fn new(
+        modulus: &[u64],
+        degree: usize,
+        levels: Vec<Vec<u64>>,
+    ) -> Result<Self, StarkHeError> {
+        if levels.len() != modulus.len() {
+            return Err(StarkHeError::DimensionMismatch(format!(
+                "a polynomial of {} levels needs as many moduli, not {}",
+                levels.len(),
+                modulus.len()
+            )));
+        }
+        for (l, (level, &m)) in levels.iter().zip(modulus).enumerate() {
+            check_level(l, level, m, degree)?;
+        }
+        Ok(RnsPoly {
+            modulus: modulus.to_vec(),
+            degree,
+            levels,
+        })
+    }
+
+    pub fn modulus(&self) -> &[u64] {
+        &self.modulus
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn level(&self, l: usize) -> &[u64] {
+        &self.levels[l]
+    }
+
+    // One trace column per level, holding its coefficients as field elements.
+    pub fn to_columns(&self) -> Vec<Vec<BaseElement>> {
+        self.levels
+            .iter()
+            .map(|level| level.iter().copied().map(element_from_u64).collect())
+            .collect()
+    }
+
+    pub fn into_levels(self) -> Vec<Vec<u64>> {
+        self.levels
+    }
+}
+
+// Moduli of a data file a circuit reduces by: above 1, of at most `bits` bits.
+pub fn check_modulus_bits(modulus: &[u64], bits: u32) -> Result<(), String> {
+    for &m in modulus.iter() {
+        if m < 2 || 64 - m.leading_zeros() > bits {
+            return Err(format!("modulus {} does not fit into {} bits", m, bits));
+        }
+    }
+    Ok(())
+}
+
+// Level `l` of a polynomial: `degree` coefficients reduced by its modulus `m`.
+pub fn check_level(l: usize, level: &[u64], m: u64, degree: usize) -> Result<(), StarkHeError> {
+    if level.len() != degree {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "level {} holds {} coefficients, the ring degree is {}",
+            l,
+            level.len(),
+            degree
+        )));
+    }
+    if m == 0 {
+        return Err(StarkHeError::InvalidInput(format!(
+            "modulus of level {} is 0",
+            l
+        )));
+    }
+    if let Some(k) = level.iter().position(|&c| c >= m) {
+        return Err(StarkHeError::InvalidInput(format!(
+            "coefficient {} of level {} is {}, not reduced modulo {}",
+            k, l, level[k], m
+        )));
+    }
+    Ok(())
+}
+
+// The checks of `Ciphertext::from_levels` for trace builders which index the components of their
+// data file directly instead of converting them.
+pub fn check_levels(
+    modulus: &[u64],
+    degree: usize,
+    values: &[[Vec<u64>; COEFF_LEVEL]],
+) -> Result<(), StarkHeError> {
+    let modulus = checked_modulus(modulus)?;
+    for levels in values {
+        for (l, (level, &m)) in levels.iter().zip(modulus).enumerate() {
+            check_level(l, level, m, degree)?;
+        }
+    }
+    Ok(())
+}
+
+fn checked_modulus(modulus: &[u64]) -> Result<&[u64], StarkHeError> {
+    modulus.get(..COEFF_LEVEL).ok_or_else(|| {
+        StarkHeError::DimensionMismatch(format!(
+            "a ciphertext of {} levels needs as many moduli, not {}",
+            COEFF_LEVEL,
+            modulus.len()
+        ))
+    })
+}
+
+// Components of the same moduli and ring degree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ciphertext {
+    components: Vec<RnsPoly>,
+}
+
+impl Ciphertext {
+    pub fn new(components: Vec<RnsPoly>) -> Result<Self, StarkHeError> {
+        let first = components.first().ok_or_else(|| {
+            StarkHeError::DimensionMismatch(String::from("a ciphertext has at least one component"))
+        })?;
+        for (v, component) in components.iter().enumerate().skip(1) {
+            if component.modulus != first.modulus || component.degree != first.degree {
+                return Err(StarkHeError::DimensionMismatch(format!(
+                    "component {} has other moduli or another ring degree than component 0",
+                    v
+                )));
+            }
+        }
+        Ok(Ciphertext { components })
+    }
+
+    // The `N` components of a data file, each with COEFF_LEVEL levels of `degree` coefficients
+    // over the first COEFF_LEVEL moduli of `modulus`.
+    pub fn from_levels<const N: usize>(
+        modulus: &[u64],
+        degree: usize,
+        values: [[Vec<u64>; COEFF_LEVEL]; N],
+    ) -> Result<Self, StarkHeError> {
+        let modulus = checked_modulus(modulus)?;
+        let components = values
+            .into_iter()
+            .map(|levels| RnsPoly::new(modulus, degree, Vec::from(levels)))
+            .collect::<Result<_, _>>()?;
+        Ciphertext::new(components)
+    }
+
+    // The operands of a data file, which share its moduli.
+    pub fn from_operands<const N: usize, const D: usize>(
+        modulus: &[u64],
+        degree: usize,
+        values: [[[Vec<u64>; COEFF_LEVEL]; N]; D],
+    ) -> Result<Vec<Self>, StarkHeError> {
+        values
+            .into_iter()
+            .map(|operand| Ciphertext::from_levels(modulus, degree, operand))
+            .collect()
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn component(&self, v: usize) -> &RnsPoly {
+        &self.components[v]
+    }
+
+    pub fn modulus(&self) -> &[u64] {
+        self.components[0].modulus()
+    }
+
+    pub fn degree(&self) -> usize {
+        self.components[0].degree()
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.components[0].level_count()
+    }
+
+    pub fn coeff(&self, v: usize, l: usize, k: usize) -> u64 {
+        self.components[v].levels[l][k]
+    }
+
+    // Writes every level of every component into the first rows of the trace column
+    // `column(component, level)`, the layout `read_result` reads results back with.
+    pub fn write_columns(
+        &self,
+        columns: &mut [Vec<BaseElement>],
+        column: impl Fn(usize, usize) -> usize,
+    ) {
+        for (v, component) in self.components.iter().enumerate() {
+            for (l, values) in component.to_columns().into_iter().enumerate() {
+                columns[column(v, l)][..values.len()].copy_from_slice(&values);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULUS: [u64; 3] = [17, 97, 193];
+
+    fn levels(start: u64, degree: usize) -> [Vec<u64>; COEFF_LEVEL] {
+        [(); COEFF_LEVEL].map(|_| (start..start + degree as u64).collect())
+    }
+
+    #[test]
+    fn ciphertexts_are_validated() {
+        let ciphertext =
+            Ciphertext::from_levels(&MODULUS, 4, [levels(0, 4), levels(5, 4)]).unwrap();
+        assert_eq!(ciphertext.component_count(), 2);
+        assert_eq!(ciphertext.level_count(), COEFF_LEVEL);
+        assert_eq!(ciphertext.modulus(), &MODULUS[..COEFF_LEVEL]);
+        assert_eq!(ciphertext.degree(), 4);
+        assert_eq!(ciphertext.coeff(1, COEFF_LEVEL - 1, 2), 7);
+
+        // a short level, an unreduced coefficient and too few moduli
+        let mut short = levels(0, 4);
+        short[0].pop();
+        assert!(matches!(
+            Ciphertext::from_levels(&MODULUS, 4, [levels(0, 4), short]),
+            Err(StarkHeError::DimensionMismatch(_))
+        ));
+        assert!(matches!(
+            Ciphertext::from_levels(&MODULUS, 4, [levels(0, 4), levels(14, 4)]),
+            Err(StarkHeError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Ciphertext::from_levels(&[], 4, [levels(0, 4)]),
+            Err(StarkHeError::DimensionMismatch(_))
+        ));
+        assert!(check_levels(&MODULUS, 4, &[levels(0, 4), levels(5, 4)]).is_ok());
+        assert!(check_modulus_bits(&MODULUS, 8).is_ok());
+        assert!(check_modulus_bits(&MODULUS, 7).is_err());
+        assert!(check_modulus_bits(&[1], 8).is_err());
+        assert!(matches!(
+            check_levels(&MODULUS, 4, &[levels(14, 4)]),
+            Err(StarkHeError::InvalidInput(_))
+        ));
+
+        // components over other moduli
+        let a = RnsPoly::new(&[17], 2, vec![vec![1, 2]]).unwrap();
+        let b = RnsPoly::new(&[97], 2, vec![vec![1, 2]]).unwrap();
+        assert!(Ciphertext::new(vec![a.clone(), b]).is_err());
+        assert!(Ciphertext::new(Vec::new()).is_err());
+        assert_eq!(
+            Ciphertext::new(vec![a.clone(), a])
+                .unwrap()
+                .component_count(),
+            2
+        );
+    }
+
+    #[test]
+    fn ciphertexts_fill_trace_columns() {
+        let ciphertext =
+            Ciphertext::from_levels(&MODULUS, 2, [levels(1, 2), levels(3, 2)]).unwrap();
+        let column = |v: usize, l: usize| 1 + v * COEFF_LEVEL + l;
+        let mut columns = vec![vec![BaseElement::new(0); 4]; 1 + 2 * COEFF_LEVEL];
+        ciphertext.write_columns(&mut columns, column);
+        for l in 0..COEFF_LEVEL {
+            assert_eq!(
+                columns[column(1, l)][..2],
+                [element_from_u64(3), element_from_u64(4)]
+            );
+            assert_eq!(columns[column(0, l)][2..], [BaseElement::new(0); 2]);
+        }
+        assert!(columns[0].iter().all(|&x| x == BaseElement::new(0)));
+    }
+}
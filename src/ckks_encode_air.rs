@@ -0,0 +1,736 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// CKKS encoding of N/2 complex slot values z at a power-of-two scale Delta into an integer
+// plaintext polynomial m, the result. Decoding evaluates m at the primitive 2N-th roots of
+// unity zeta^(5^i) (slot i) and zeta^(-5^i) (its conjugate), which is NttAir's forward network
+// over the complex numbers with psi = exp(i pi / N). The proof runs that network in fixed point
+// on m and shows that every evaluation lies within Delta * 2^-20 of Delta * z (resp. its
+// conjugate), i.e. that m decodes to z to the precision of the values. The embedding scales
+// Euclidean norms by sqrt(N), so up to the error of the fixed-point transform every coefficient
+// of such a plaintext is within sqrt(2) times that distance of the unrounded encoding.
+//
+// Twiddles are rounded to TWIDDLE_BITS fractional bits, and a butterfly with inputs A, B and a
+// twiddle w proves
+//
+//     P * 2^40 + R = w * B,  X = A + P,  Y = A - P,  R in 0..2^40
+//
+// for the real and the imaginary part of the product. The circuit rows, the padding, the row
+// groups and the wiring are those of NttAir, with one running product for the real and one for
+// the imaginary parts, and the rows of a group reduce w * B + 2^PRODUCT_BITS to R modulo 2^40,
+// which range checks R and leaves P the integer quotient. The outputs of butterfly b move to
+// circuit rows 16b + 12 and 16b + 13, on the last row of a group, where C = E + Delta * 2^-20
+// for the error E of the real (imaginary) part of evaluation 2b and 2b + 1 is reduced to itself
+// modulo Delta * 2^-19, which range checks it. Values are fixed-point numbers with
+// VALUE_FRAC_BITS fractional bits; the verifier checks them, the scale and the bound on the
+// coefficients of m, which keeps every intermediate value far from the field modulus. The moduli
+// of the reductions follow from TWIDDLE_BITS and the public scale.
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+use winter_utils::{ByteReader, DeserializationError};
+
+use crate::air::{
+    element_from_u128, element_from_u64, first_level, load_data_file, BaseElement, InputArg,
+    PublicInputs, COEFF_DEGREE,
+};
+use crate::canonical::{read_f64, read_sequence, CanonicalWriter};
+use crate::decrypt_air::{first_row, signed_element};
+use crate::error::StarkHeError;
+use crate::intt_air::LAST_STAGE;
+use crate::ntt_air::{
+    bit_reverse, butterfly, evaluate_wiring, wiring_assertions, wiring_degrees,
+    wiring_periodic_columns, NttTrace, BUTTERFLY_NUM, NTT_STAGE_NUM, NTT_TRACE_LENGTH, STAGE_SLOTS,
+    WIRING_PERIODIC_NUM,
+};
+use crate::reduction::{bit_length, reduction_group, reduction_width, Reductions};
+use crate::rotate_air::GALOIS_GENERATOR;
+
+// Inputs + Outputs + Products + Remainders + Checks, real part first, + Reduction
+// A0 A1 B0 B1 X0 X1 Y0 Y1 P0 P1 R0 R1 C0 C1 K.. R.. S..
+const PART_NUM: usize = 2;
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + PART_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + PART_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + PART_NUM;
+const PRODUCT_START: usize = OUTPUT_Y_START + PART_NUM;
+const REMAINDER_START: usize = PRODUCT_START + PART_NUM;
+const CHECK_START: usize = REMAINDER_START + PART_NUM;
+const CHECK_END: usize = CHECK_START + PART_NUM;
+
+pub const SLOT_NUM: usize = COEFF_DEGREE / 2;
+pub const TWIDDLE_BITS: usize = 40;
+pub const VALUE_FRAC_BITS: u32 = 20;
+// slot values below 2^VALUE_BITS in magnitude
+pub const VALUE_BITS: u32 = 20;
+// plaintext coefficients below 2^COEFF_BITS in magnitude, recorded with an offset of
+// 2^COEFF_BITS as TOML integers are signed 64-bit numbers
+pub const COEFF_BITS: u32 = 60;
+const COEFF_OFFSET: u64 = 1 << COEFF_BITS;
+// the bits of E + Delta * 2^-20 fit into the remainder bits of one part
+pub const MAX_SCALE_BITS: u32 = VALUE_FRAC_BITS + TWIDDLE_BITS as u32 - 1;
+// rows 16b + 12 and 16b + 13 check evaluations 2b and 2b + 1
+const CHECK_STAGE: usize = NTT_STAGE_NUM;
+// network values stay below 2^NETWORK_BITS in magnitude, the coefficients doubling at most on
+// every stage, and the products w * B below 2^PRODUCT_BITS
+const NETWORK_BITS: usize = COEFF_BITS as usize + NTT_STAGE_NUM + 1;
+const PRODUCT_BITS: usize = TWIDDLE_BITS + NETWORK_BITS + 1;
+const PRODUCT_OFFSET: u128 = 1 << PRODUCT_BITS;
+// the remainders of both parts, then the checks of both parts
+const REDUCTION_NUM: usize = 2 * PART_NUM;
+// quotients of products offset into 0..2^(PRODUCT_BITS+1) by moduli of up to 2^TWIDDLE_BITS
+const REDUCTION_WIDTH: usize = reduction_width(
+    PRODUCT_BITS + 2 - TWIDDLE_BITS,
+    bit_length(1 << TWIDDLE_BITS),
+);
+// one row per reduction, then padding
+pub const CKKS_ENCODE_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = CHECK_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = NTT_TRACE_LENGTH;
+pub const CKKS_ENCODE_AUX_WIDTH: usize = PART_NUM;
+const AUX_WIDTH: usize = CKKS_ENCODE_AUX_WIDTH;
+pub const CKKS_ENCODE_RESULT_NUM: usize = 1;
+
+// Periodic columns after the wiring ones: twiddles and targets run over the whole trace, the
+// selectors of the butterflies, the last stage, the first check row and both check rows have a
+// period of one circuit row per stage slot, all stretched over the groups. Those of the
+// reductions follow.
+const TWIDDLE_START: usize = WIRING_PERIODIC_NUM;
+const BUTTERFLY_SELECTOR: usize = TWIDDLE_START + PART_NUM;
+const LAST_SELECTOR: usize = BUTTERFLY_SELECTOR + 1;
+const SHIFT_SELECTOR: usize = LAST_SELECTOR + 1;
+const CHECK_SELECTOR: usize = SHIFT_SELECTOR + 1;
+const TARGET_START: usize = CHECK_SELECTOR + 1;
+const REDUCTION_PERIODIC_START: usize = TARGET_START + PART_NUM;
+
+// Trace layout constants of CkksEncodeAir in canonical order.
+pub fn ckks_encode_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("twiddle_bits", TWIDDLE_BITS),
+        ("value_frac_bits", VALUE_FRAC_BITS as usize),
+        ("group", CKKS_ENCODE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// The encoded slot values as real and imaginary part, and the scale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CkksSlots {
+    pub scale: f64,
+    pub values: Vec<[f64; 2]>,
+}
+
+impl CkksSlots {
+    // Layout: the scale as u64 (IEEE 754 bits), the number of values as u32, then the real and
+    // the imaginary part of every value as u64 (IEEE 754 bits).
+    pub fn write_canonical(&self, writer: &mut CanonicalWriter) {
+        writer.write_f64(self.scale);
+        writer.write_u32(self.values.len() as u32);
+        for value in self.values.iter() {
+            writer.write_f64(value[0]);
+            writer.write_f64(value[1]);
+        }
+    }
+
+    pub fn read_canonical<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let scale = read_f64(source)?;
+        let values = read_sequence(source, |source| Ok([read_f64(source)?, read_f64(source)?]))?;
+        Ok(CkksSlots { scale, values })
+    }
+
+    // log2 of the scale, which must be a power of two between 2^VALUE_FRAC_BITS and
+    // 2^MAX_SCALE_BITS.
+    pub fn scale_bits(&self) -> Result<u32, String> {
+        let bits = self.scale.log2();
+        if !self.scale.is_finite()
+            || bits.fract() != 0.0
+            || !(VALUE_FRAC_BITS as f64..=MAX_SCALE_BITS as f64).contains(&bits)
+        {
+            return Err(format!(
+                "scale {} is not a power of two between 2^{} and 2^{}",
+                self.scale, VALUE_FRAC_BITS, MAX_SCALE_BITS
+            ));
+        }
+        Ok(bits as u32)
+    }
+
+    // Every value as fixed-point numbers, real and imaginary part times 2^VALUE_FRAC_BITS.
+    pub fn fixed_values(&self) -> Result<Vec<[i64; 2]>, String> {
+        if self.values.len() != SLOT_NUM {
+            return Err(format!(
+                "{} slot values, expected {}",
+                self.values.len(),
+                SLOT_NUM
+            ));
+        }
+        let unit = (1u64 << VALUE_FRAC_BITS) as f64;
+        let bound = (1u64 << VALUE_BITS) as f64;
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let fixed = value.map(|part| part * unit);
+                if value.iter().all(|part| part.abs() < bound)
+                    && fixed.iter().all(|part| part.fract() == 0.0)
+                {
+                    Ok(fixed.map(|part| part as i64))
+                } else {
+                    Err(format!(
+                        "slot {} value {} + {}i is not a multiple of 2^-{} below 2^{}",
+                        i, value[0], value[1], VALUE_FRAC_BITS, VALUE_BITS
+                    ))
+                }
+            })
+            .collect()
+    }
+
+    // The values and the scale must be valid and `result` must hold a plaintext with N
+    // coefficients below 2^COEFF_BITS in magnitude, offset by 2^COEFF_BITS, at the first level.
+    pub fn check(&self, result: &[Vec<Vec<u64>>]) -> Result<(), String> {
+        self.scale_bits()?;
+        self.fixed_values()?;
+        match &result.iter().map(Vec::as_slice).collect::<Vec<_>>()[..] {
+            [[plaintext, dropped @ ..]]
+                if plaintext.len() == COEFF_DEGREE
+                    && dropped.iter().all(Vec::is_empty)
+                    && plaintext.iter().all(|&c| c < 2 * COEFF_OFFSET) => {}
+            _ => {
+                return Err(format!(
+                    "plaintext is not {} coefficients below 2^{}",
+                    COEFF_DEGREE, COEFF_BITS
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+// Slot values as real and imaginary part and a power-of-two scale.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CkksEncodeData {
+    pub scale: f64,
+    pub values: Vec<[f64; 2]>,
+}
+
+// Slot values of a data file, rounded to VALUE_FRAC_BITS fractional bits, which the prover
+// records in the public inputs.
+pub fn load_slots(data_file_path: &str) -> Result<CkksSlots, StarkHeError> {
+    let data: CkksEncodeData = load_data_file(data_file_path)?;
+    let unit = (1u64 << VALUE_FRAC_BITS) as f64;
+    let slots = CkksSlots {
+        scale: data.scale,
+        values: data
+            .values
+            .iter()
+            .map(|value| value.map(|part| (part * unit).round() / unit))
+            .collect(),
+    };
+    slots.scale_bits().map_err(StarkHeError::InvalidInput)?;
+    slots.fixed_values().map_err(StarkHeError::InvalidInput)?;
+    Ok(slots)
+}
+
+fn input_a_column(p: usize) -> usize {
+    INPUT_A_START + p
+}
+
+fn input_b_column(p: usize) -> usize {
+    INPUT_B_START + p
+}
+
+fn output_x_column(p: usize) -> usize {
+    OUTPUT_X_START + p
+}
+
+fn output_y_column(p: usize) -> usize {
+    OUTPUT_Y_START + p
+}
+
+fn product_column(p: usize) -> usize {
+    PRODUCT_START + p
+}
+
+fn remainder_column(p: usize) -> usize {
+    REMAINDER_START + p
+}
+
+fn check_column(p: usize) -> usize {
+    CHECK_START + p
+}
+
+// The reductions of a row at the scale 2^scale_bits: the offset product w * B of part p is value
+// p, reduced to R modulo 2^TWIDDLE_BITS, and C of part p value PART_NUM + p, reduced to itself
+// modulo Delta * 2^-19.
+pub fn ckks_encode_reductions(scale_bits: u32) -> Result<Reductions, String> {
+    let check = 1 << (scale_bits - VALUE_FRAC_BITS + 1);
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for p in 0..PART_NUM {
+        results.push(remainder_column(p));
+        moduli.push(1 << TWIDDLE_BITS);
+        bounds.push(2 * PRODUCT_OFFSET - 1);
+    }
+    for p in 0..PART_NUM {
+        results.push(check_column(p));
+        moduli.push(check);
+        bounds.push(check as u128 - 1);
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (0..CHECK_END).collect(),
+        CHECK_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Wiring columns of CkksEncodeAir, real part first.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    (0..PART_NUM)
+        .map(|p| {
+            [
+                input_a_column(p),
+                input_b_column(p),
+                output_x_column(p),
+                output_y_column(p),
+            ]
+        })
+        .collect()
+}
+
+// cos and sin of pi * a / N for a in 0..N, reduced to the first octant and summed as Taylor
+// series with basic IEEE 754 operations only, so every platform derives the same twiddles.
+fn unit_root(a: usize) -> (f64, f64) {
+    if a > COEFF_DEGREE / 2 {
+        let (cos, sin) = unit_root(COEFF_DEGREE - a);
+        return (-cos, sin);
+    }
+    if a > COEFF_DEGREE / 4 {
+        let (cos, sin) = unit_root(COEFF_DEGREE / 2 - a);
+        return (sin, cos);
+    }
+    let x = std::f64::consts::PI * a as f64 / COEFF_DEGREE as f64;
+    let (mut cos, mut sin, mut term) = (0.0, 0.0, 1.0);
+    for k in 0..24 {
+        match k % 4 {
+            0 => cos += term,
+            1 => sin += term,
+            2 => cos -= term,
+            _ => sin -= term,
+        }
+        term = term * x / (k + 1) as f64;
+    }
+    (cos, sin)
+}
+
+// psi^bitrev(k) for k = 0..N with psi = exp(i pi / N), indexed by m + i in the butterfly
+// network like the NTT twiddles.
+fn roots() -> Vec<(f64, f64)> {
+    (0..COEFF_DEGREE)
+        .map(|k| unit_root(bit_reverse(k)))
+        .collect()
+}
+
+// The roots rounded to TWIDDLE_BITS fractional bits.
+fn twiddles() -> Vec<[i64; 2]> {
+    let unit = (1u64 << TWIDDLE_BITS) as f64;
+    roots()
+        .into_iter()
+        .map(|(cos, sin)| [(cos * unit).round() as i64, (sin * unit).round() as i64])
+        .collect()
+}
+
+// Delta * z for every output of the network: output k is the evaluation at
+// zeta^(2 bitrev(k) + 1), which is zeta^(5^i) for slot i and zeta^(-5^i) for its conjugate.
+fn targets(values: &[[i64; 2]], scale_bits: u32) -> Vec<[i128; 2]> {
+    let two_n = 2 * COEFF_DEGREE;
+    let shift = scale_bits - VALUE_FRAC_BITS;
+    let output = |exponent: usize| bit_reverse((exponent - 1) / 2);
+    let mut targets = vec![[0; 2]; COEFF_DEGREE];
+    let mut exponent = 1;
+    for value in values.iter() {
+        let [re, im] = value.map(|part| (part as i128) << shift);
+        targets[output(exponent)] = [re, im];
+        targets[output(two_n - exponent)] = [re, -im];
+        exponent = exponent * GALOIS_GENERATOR as usize % two_n;
+    }
+    targets
+}
+
+fn wide_element(value: i128) -> BaseElement {
+    let magnitude = BaseElement::from(value.unsigned_abs());
+    if value < 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+// Coefficients of round(Delta * z) under the inverse canonical embedding, undoing the forward
+// network butterfly by butterfly in floating point.
+fn encode(targets: &[[i128; 2]]) -> Vec<i64> {
+    let roots = roots();
+    let mut a: Vec<[f64; 2]> = targets
+        .iter()
+        .map(|target| target.map(|part| part as f64))
+        .collect();
+    for s in (0..NTT_STAGE_NUM).rev() {
+        for b in 0..BUTTERFLY_NUM {
+            let (_, j, t, w) = butterfly(b * STAGE_SLOTS + s, false).unwrap();
+            let (cos, sin) = roots[w];
+            let (x, y) = (a[j], a[j + t]);
+            let sum = [(x[0] + y[0]) / 2.0, (x[1] + y[1]) / 2.0];
+            let difference = [(x[0] - y[0]) / 2.0, (x[1] - y[1]) / 2.0];
+            a[j] = sum;
+            a[j + t] = [
+                difference[0] * cos + difference[1] * sin,
+                difference[1] * cos - difference[0] * sin,
+            ];
+        }
+    }
+    a.iter().map(|value| value[0].round() as i64).collect()
+}
+
+pub fn build_ckks_encode_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let slots = load_slots(&arg.data_file_path)?;
+    let scale_bits = slots.scale_bits().map_err(StarkHeError::InvalidInput)?;
+    let fixed_values = slots.fixed_values().map_err(StarkHeError::InvalidInput)?;
+    let targets = targets(&fixed_values, scale_bits);
+    let plaintext = encode(&targets);
+    assert!(
+        plaintext.iter().all(|c| c.unsigned_abs() < 1 << COEFF_BITS),
+        "the encoding has coefficients beyond 2^{}",
+        COEFF_BITS
+    );
+    let reductions = ckks_encode_reductions(scale_bits).map_err(StarkHeError::InvalidInput)?;
+    let twiddles = twiddles();
+    let unit = 1i128 << TWIDDLE_BITS;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; CHECK_END];
+    let mut values = vec![[PRODUCT_OFFSET, PRODUCT_OFFSET, 0, 0]; STATE_LENGTH];
+    let mut a: Vec<[i128; 2]> = plaintext.iter().map(|&c| [c as i128, 0]).collect();
+    for s in 0..NTT_STAGE_NUM {
+        for b in 0..BUTTERFLY_NUM {
+            let row = b * STAGE_SLOTS + s;
+            let (_, j, t, w) = butterfly(row, false).unwrap();
+            let [w0, w1] = twiddles[w].map(|part| part as i128);
+            let (x, y) = (a[j], a[j + t]);
+            let products = [w0 * y[0] - w1 * y[1], w0 * y[1] + w1 * y[0]];
+            for p in 0..PART_NUM {
+                let product = products[p].div_euclid(unit);
+                let remainder = products[p].rem_euclid(unit);
+                columns[input_a_column(p)][row] = wide_element(x[p]);
+                columns[input_b_column(p)][row] = wide_element(y[p]);
+                columns[product_column(p)][row] = wide_element(product);
+                columns[output_x_column(p)][row] = wide_element(x[p] + product);
+                columns[output_y_column(p)][row] = wide_element(x[p] - product);
+                columns[remainder_column(p)][row] = wide_element(remainder);
+                values[row][p] = (PRODUCT_OFFSET as i128 + products[p]) as u128;
+                a[j][p] = x[p] + product;
+                a[j + t][p] = x[p] - product;
+            }
+        }
+    }
+
+    let tolerance = 1i128 << (scale_bits - VALUE_FRAC_BITS);
+    for b in 0..BUTTERFLY_NUM {
+        let row = first_row(b) + CHECK_STAGE;
+        for p in 0..PART_NUM {
+            columns[input_a_column(p)][row] = wide_element(a[2 * b][p]);
+            columns[input_b_column(p)][row] = wide_element(a[2 * b + 1][p]);
+            columns[input_a_column(p)][row + 1] = wide_element(a[2 * b + 1][p]);
+        }
+        for h in 0..2 {
+            let k = 2 * b + h;
+            for p in 0..PART_NUM {
+                let shifted = a[k][p] - targets[k][p] + tolerance;
+                assert!(
+                    (0..2 * tolerance).contains(&shifted),
+                    "evaluation {} is not within 2^-{} of the slot values at scale 2^{}",
+                    k,
+                    VALUE_FRAC_BITS,
+                    scale_bits
+                );
+                columns[check_column(p)][row + h] = wide_element(shifted);
+                values[row + h][PART_NUM + p] = shifted as u128;
+            }
+        }
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        Vec::new(),
+        wiring_columns(),
+        false,
+        CKKS_ENCODE_GROUP,
+    ))
+}
+
+pub fn get_ckks_encode_pub_inputs(trace: &NttTrace, slots: CkksSlots) -> PublicInputs {
+    let main = trace.main_segment();
+    let offset = element_from_u64(COEFF_OFFSET);
+    let plaintext = (0..COEFF_DEGREE)
+        .map(|k| {
+            let column = [input_a_column(0), input_b_column(0)][k / BUTTERFLY_NUM];
+            main.get(column, first_row(k % BUTTERFLY_NUM) * CKKS_ENCODE_GROUP) + offset
+        })
+        .collect();
+    PublicInputs {
+        result: vec![first_level(plaintext)],
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: None,
+        plain_modulus: None,
+        decryption: None,
+        slots: Some(slots),
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct CkksEncodeAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    plaintext: Vec<BaseElement>,
+    slots: CkksSlots,
+    scale_bits: u32,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl Air for CkksEncodeAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The butterflies, the moves of the outputs to the check rows and the checks sit under
+    // selectors of period STAGE_SLOTS * G, the products and the values of the remainders also
+    // multiply a twiddle. The moves refer to the next row and apply on the last row of a group,
+    // so the last row, which is padding, is never checked.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = pub_inputs
+            .slots
+            .expect("encoding proofs publish their slot values");
+        let scale_bits = slots.scale_bits().expect("scales are checked before use");
+        let reductions = ckks_encode_reductions(scale_bits).expect("encoding scales were checked");
+        let group = reductions.group();
+        let stage = STAGE_SLOTS * group;
+        let length = STATE_LENGTH * group;
+        let selected = TransitionConstraintDegree::with_cycles(1, vec![stage]);
+        let moved = TransitionConstraintDegree::with_cycles(1, vec![stage, group]);
+        let mut degrees = reductions.degrees(1, &[length]);
+        for _ in 0..PART_NUM {
+            degrees.push(TransitionConstraintDegree::with_cycles(
+                1,
+                vec![stage, length],
+            ));
+            degrees.push(selected.clone());
+            degrees.push(selected.clone());
+        }
+        for _ in 0..PART_NUM {
+            degrees.push(moved.clone());
+            degrees.push(moved.clone());
+            degrees.push(selected.clone());
+        }
+        let aux_degrees = wiring_degrees(AUX_WIDTH, group);
+        let num_assertions = 2 * PART_NUM;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let offset = element_from_u64(COEFF_OFFSET);
+        let plaintext = pub_inputs.result[0][0]
+            .iter()
+            .map(|&c| c - offset)
+            .collect();
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        CkksEncodeAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            plaintext,
+            slots,
+            scale_bits,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (row, next) = (frame.current(), frame.next());
+        let selector = periodic_values[BUTTERFLY_SELECTOR];
+        let last = periodic_values[LAST_SELECTOR];
+        let shift = periodic_values[SHIFT_SELECTOR];
+        let check = periodic_values[CHECK_SELECTOR];
+        let reduction_periodic = &periodic_values[REDUCTION_PERIODIC_START..];
+        let group_last = self.reductions.last(reduction_periodic);
+
+        // w * B + 2^PRODUCT_BITS reduced to R, C to itself
+        let (w0, w1) = (
+            periodic_values[TWIDDLE_START],
+            periodic_values[TWIDDLE_START + 1],
+        );
+        let (b0, b1) = (row[input_b_column(0)], row[input_b_column(1)]);
+        let products = [w0 * b0 - w1 * b1, w0 * b1 + w1 * b0];
+        let offset = E::from(element_from_u128(PRODUCT_OFFSET));
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        for p in 0..PART_NUM {
+            values[p] = products[p] + offset;
+            values[PART_NUM + p] = row[check_column(p)];
+        }
+        let (reduction, result) = result.split_at_mut(self.reductions.num_constraints());
+        self.reductions
+            .evaluate(frame, reduction_periodic, &values, reduction);
+
+        // P * 2^40 + R = w * B, X = A + P, Y = A - P for both parts
+        let unit = E::from(BaseElement::from(1u128 << TWIDDLE_BITS));
+        let mut i = 0;
+        for p in 0..PART_NUM {
+            let (a, product) = (row[input_a_column(p)], row[product_column(p)]);
+            result[i] = selector * (product * unit + row[remainder_column(p)] - products[p]);
+            result[i + 1] = selector * (row[output_x_column(p)] - a - product);
+            result[i + 2] = selector * (row[output_y_column(p)] - a + product);
+            i += 3;
+        }
+
+        // X, Y of the last stage move to A, B of the first check row, B to A of the second,
+        // where A + Delta * 2^-20 - Delta * z is C
+        let tolerance = E::from(BaseElement::from(
+            1u128 << (self.scale_bits - VALUE_FRAC_BITS),
+        ));
+        for p in 0..PART_NUM {
+            let a = next[input_a_column(p)];
+            result[i] = group_last
+                * (last * (a - row[output_x_column(p)]) + shift * (a - row[input_b_column(p)]));
+            result[i + 1] = group_last * last * (next[input_b_column(p)] - row[output_y_column(p)]);
+            result[i + 2] = check * (row[input_a_column(p)] + tolerance - row[check_column(p)])
+                - periodic_values[TARGET_START + p];
+            i += 3;
+        }
+        debug_assert_eq!(i, result.len());
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The plaintext as the real inputs of the first stage, whose imaginary inputs are zero, on
+    // the first rows of the groups.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let stride = STAGE_SLOTS * self.reductions.group();
+        let (low, high) = self.plaintext.split_at(BUTTERFLY_NUM);
+        vec![
+            Assertion::sequence(input_a_column(0), 0, stride, low.to_vec()),
+            Assertion::sequence(input_b_column(0), 0, stride, high.to_vec()),
+            Assertion::sequence(
+                input_a_column(1),
+                0,
+                stride,
+                vec![BaseElement::ZERO; BUTTERFLY_NUM],
+            ),
+            Assertion::sequence(
+                input_b_column(1),
+                0,
+                stride,
+                vec![BaseElement::ZERO; BUTTERFLY_NUM],
+            ),
+        ]
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    // Twiddles on the butterfly rows and targets on the check rows, zero elsewhere.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let twiddles = twiddles();
+        let values = self
+            .slots
+            .fixed_values()
+            .expect("slot values are checked before use");
+        let targets = targets(&values, self.scale_bits);
+        let mut twiddle_columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; PART_NUM];
+        let mut target_columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; PART_NUM];
+        for row in 0..STATE_LENGTH {
+            if let Some((_, _, _, w)) = butterfly(row, false) {
+                for p in 0..PART_NUM {
+                    twiddle_columns[p][row] = signed_element(twiddles[w][p]);
+                }
+            }
+        }
+        for b in 0..BUTTERFLY_NUM {
+            for h in 0..2 {
+                for p in 0..PART_NUM {
+                    target_columns[p][first_row(b) + CHECK_STAGE + h] =
+                        wide_element(targets[2 * b + h][p]);
+                }
+            }
+        }
+        let selector = |stages: std::ops::Range<usize>| -> Vec<BaseElement> {
+            (0..STAGE_SLOTS)
+                .map(|s| BaseElement::from(stages.contains(&s) as u64))
+                .collect()
+        };
+        let stretch = |column: &[BaseElement]| self.reductions.stretch(column);
+        let mut columns = wiring_periodic_columns(false, self.reductions.group());
+        columns.extend(twiddle_columns.iter().map(|column| stretch(column)));
+        columns.push(stretch(&selector(0..NTT_STAGE_NUM)));
+        columns.push(stretch(&selector(LAST_STAGE..LAST_STAGE + 1)));
+        columns.push(stretch(&selector(CHECK_STAGE..CHECK_STAGE + 1)));
+        columns.push(stretch(&selector(CHECK_STAGE..CHECK_STAGE + 2)));
+        columns.extend(target_columns.iter().map(|column| stretch(column)));
+        columns.extend(self.reductions.periodic_columns());
+        columns
+    }
+}
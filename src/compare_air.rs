@@ -0,0 +1,321 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Less-than comparison of two BGV ciphertexts a and b with an odd prime plain modulus t: the
+// result encrypts 1 if a < b and 0 otherwise, read as centred residues with |a - b| <= (t-1)/2,
+// as the sign S(a - b) of their difference (see src/sign.rs). a > b is b < a with the operands
+// swapped. Every circuit row holds one coefficient of every level of both operands in evaluation
+// form, and takes, for every component v and level,
+//
+//     x_v = a_v - b_v + c_v * m,  c_v in {0, 1}
+//
+// then the Horner evaluation of the sign polynomial on x with the sign gadget, whose right-hand
+// sides are reduced to the partials by the Barrett blocks of reduction.rs, one per row of a row
+// group, against the moduli of the public inputs. The borrow only picks the representative of
+// a - b modulo m, which the reduced partials do not depend on. The result, the d + 1 = t
+// components of S(x), the moduli and the plain modulus, from which the verifier derives the
+// coefficients, are public. Plain moduli below COMPARE_MAX_PLAIN_MODULUS pad the polynomial with
+// zero coefficients.
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+use crate::sign::{
+    partial_index, sign_coefficients, sign_elements, sign_partial_num, sign_partials, sign_values,
+};
+
+// Modulus + Partial + Borrow + Data + Reduction
+// M0 M1 P0_0..P0_26 P1_0..P1_26 C00 C01 C10 C11 A00 .. A11 B00 .. B11 K.. R.. S..
+pub const COMPARE_MAX_PLAIN_MODULUS: u64 = 7;
+const MAX_DEGREE: usize = COMPARE_MAX_PLAIN_MODULUS as usize - 1;
+pub const COMPARE_RESULT_NUM: usize = MAX_DEGREE + 1;
+const PARTIAL_NUM: usize = sign_partial_num(MAX_DEGREE);
+const PARTIAL_LEN: usize = PARTIAL_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const PARTIAL_START: usize = MODULUS_NUM;
+const BORROW_START: usize = PARTIAL_START + PARTIAL_LEN;
+const DATA_START: usize = BORROW_START + DATA_LEN;
+const DATA_END: usize = DATA_START + 2 * DATA_LEN;
+// a right-hand side stays below 2 m^2 + m, so its quotient takes a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per partial of every level, then padding
+pub const COMPARE_GROUP: usize = reduction_group(PARTIAL_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of CompareAir in canonical order.
+pub fn compare_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("max_plain_modulus", COMPARE_MAX_PLAIN_MODULUS as usize),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", COMPARE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Two BGV ciphertexts in evaluation form, a in Values[0] and b in Values[1], and the plain
+// modulus.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CompareData {
+    pub modulus: Vec<u64>,
+    pub plain_modulus: u64,
+    pub values: [[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; 2],
+}
+
+fn partial_column(i: usize, l: usize) -> usize {
+    PARTIAL_START + l * PARTIAL_NUM + i
+}
+
+// Component `c` of the sign at level `l`, the last Horner step.
+fn result_column(c: usize, l: usize) -> usize {
+    partial_column(partial_index(MAX_DEGREE, c), l)
+}
+
+fn borrow_column(v: usize, l: usize) -> usize {
+    level_column(BORROW_START, v, l)
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+// Coefficients of the sign polynomial for the plain modulus `t`, padded to the maximal degree.
+pub fn compare_coefficients(t: u64) -> Vec<u64> {
+    let mut coefficients = sign_coefficients(t);
+    coefficients.resize(MAX_DEGREE + 1, 0);
+    coefficients
+}
+
+// The plain modulus must be an odd prime no larger than COMPARE_MAX_PLAIN_MODULUS.
+pub fn check_plain_modulus(t: u64) -> Result<(), String> {
+    if (3..=COMPARE_MAX_PLAIN_MODULUS).contains(&t) && (2..t).all(|p| !t.is_multiple_of(p)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "plain modulus {} is not an odd prime up to {}",
+            t, COMPARE_MAX_PLAIN_MODULUS
+        ))
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`, partial i of level l being value
+// l * PARTIAL_NUM + i of the row: a right-hand side adds two products of residues and a
+// coefficient below the plain modulus, itself below every modulus.
+pub fn compare_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Compare, modulus, MUL_MAX_MODULUS_BITS)?;
+    let mut results = Vec::with_capacity(PARTIAL_LEN);
+    let mut moduli = Vec::with_capacity(PARTIAL_LEN);
+    let mut bounds = Vec::with_capacity(PARTIAL_LEN);
+    for (l, &m) in modulus.iter().enumerate() {
+        for i in 0..PARTIAL_NUM {
+            results.push(partial_column(i, l));
+            moduli.push(m);
+            bounds.push(2 * (m as u128 - 1).pow(2) + m as u128 - 1);
+        }
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (PARTIAL_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_compare_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: CompareData = load_data_file(&arg.data_file_path)?;
+    check_plain_modulus(data.plain_modulus).map_err(StarkHeError::InvalidInput)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+    if let Some(&m) = data.modulus.iter().find(|&&m| m <= data.plain_modulus) {
+        return Err(StarkHeError::InvalidInput(format!(
+            "plain modulus {} must be below modulus {}",
+            data.plain_modulus, m
+        )));
+    }
+    let reductions = compare_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let coefficients = compare_coefficients(data.plain_modulus);
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    let mut values = vec![[0; PARTIAL_LEN]; STATE_LENGTH];
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            let mut x = [0; VALUE_NUM];
+            for v in 0..VALUE_NUM {
+                let (a, b) = (data.values[0][v][l][k], data.values[1][v][l][k]);
+                let borrow = (a < b) as u64;
+                x[v] = a + borrow * m - b;
+                columns[data_column(0, v, l)][k] = element_from_u64(a);
+                columns[data_column(1, v, l)][k] = element_from_u64(b);
+                columns[borrow_column(v, l)][k] = element_from_u64(borrow);
+            }
+            let (partials, sums) = sign_partials(x, &coefficients, m);
+            for i in 0..PARTIAL_NUM {
+                columns[partial_column(i, l)][k] = element_from_u64(partials[i]);
+                values[k][l * PARTIAL_NUM + i] = sums[i];
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+pub fn get_compare_pub_inputs(trace: &TraceType, plain_modulus: u64) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, COMPARE_RESULT_NUM, COMPARE_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: Some(plain_modulus),
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The right-hand sides of one row with the public moduli `modulus` and the sign coefficients
+// `coefficients`, partial i of level l at l * PARTIAL_NUM + i, and the borrow constraints, zero
+// when the borrows are binary.
+fn evaluate_row<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    modulus: &[E],
+    coefficients: &[E],
+    borrows: &mut [E],
+) -> Vec<E> {
+    let mut values = Vec::with_capacity(PARTIAL_LEN);
+    for l in 0..COEFF_LEVEL {
+        let x: [E; VALUE_NUM] = std::array::from_fn(|v| {
+            let c = row[borrow_column(v, l)];
+            borrows[v * COEFF_LEVEL + l] = c * c - c;
+            row[data_column(0, v, l)] - row[data_column(1, v, l)] + c * modulus[l]
+        });
+        let partials = &row[partial_column(0, l)..partial_column(PARTIAL_NUM, l)];
+        values.extend(sign_values(x, coefficients, partials));
+    }
+    values
+}
+
+pub struct CompareAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    coefficients: Vec<BaseElement>,
+}
+
+impl Air for CompareAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The right-hand sides are quadratic in the trace, with the public moduli as constants; the
+    // borrows are binary on every row.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("comparison proofs record their moduli");
+        let reductions = compare_reductions(&modulus).expect("comparison moduli were checked");
+        let mut degrees = reductions.degrees(2, &[]);
+        degrees.extend(vec![TransitionConstraintDegree::new(2); DATA_LEN]);
+        let result_len = COMPARE_RESULT_NUM * COEFF_LEVEL;
+        let num_assertions = grouped_result_assertion_num(result_len, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let plain_modulus = pub_inputs
+            .plain_modulus
+            .expect("comparison proofs need the plain modulus");
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        CompareAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            coefficients: sign_elements(&compare_coefficients(plain_modulus)),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let modulus: Vec<E> = self
+            .modulus
+            .iter()
+            .map(|&m| E::from(element_from_u64(m)))
+            .collect();
+        let coefficients: Vec<E> = self.coefficients.iter().map(|&s| E::from(s)).collect();
+        let (reduction, borrows) = result.split_at_mut(self.reductions.num_constraints());
+        let values = evaluate_row(frame.current(), &modulus, &coefficients, borrows);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+    }
+
+    // The results are bound on the first row of every group, the modulus columns to the public
+    // moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(COMPARE_RESULT_NUM * COEFF_LEVEL);
+        for c in 0..COMPARE_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(c, l), self.result[c][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Layered configuration of the prover. Every option comes from, in decreasing precedence:
+//
+//     command line   --num-queries 30
+//     environment    STARK_HE_NUM_QUERIES=30, the option in upper case with underscores
+//     config file    num_queries = 30, the option with underscores
+//     default        that of the option, if any
+//
+// The config file is TOML, the one of `--config`, or else of STARK_HE_CONFIG, or else
+// ./stark-he.toml if it exists. Repeatable and comma separated options take arrays in the file
+// and comma separated values in the environment, flags take booleans. The lower layers reach
+// clap as if they had been given on the command line, so they are parsed and validated the same
+// way, and options which conflict on the command line also conflict across layers.
+// `stark-he config show` prints where every option comes from.
+
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use clap::{ArgMatches, Command, ErrorKind, Parser, ValueSource};
+
+use crate::air::InputArg;
+use crate::output::OutputArg;
+use crate::proving::ProofOptionsConfig;
+
+pub const CONFIG_ENV: &str = "STARK_HE_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &str = "./stark-he.toml";
+const ENV_PREFIX: &str = "STARK_HE_";
+
+#[derive(Parser)]
+#[clap(name = "prover", author, version, about, long_about = None)]
+pub struct ProverCli {
+    #[clap(flatten)]
+    pub output_args: OutputArg,
+    #[clap(flatten)]
+    pub proof_options: ProofOptionsConfig,
+    #[clap(flatten)]
+    pub input_args: InputArg,
+    /// Directory receiving the job status file read by `stark-he tui`
+    #[clap(long)]
+    pub status_dir: Option<String>,
+    /// Seal the proof container to the X25519 public key in this file (see `stark-he keygen`)
+    #[clap(long)]
+    pub encrypt_to: Option<String>,
+    /// Also write a prover archive (inputs, trace snapshot, log) to this directory; it is for
+    /// internal retention, only the proof file goes to verifiers
+    #[clap(long)]
+    pub archive_dir: Option<String>,
+    /// TOML file of default options, below the environment and the command line
+    /// [default: $STARK_HE_CONFIG, or ./stark-he.toml if it exists]
+    #[clap(long)]
+    pub config: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Env(String),
+    File(String),
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "command line"),
+            Source::Env(name) => write!(f, "environment {}", name),
+            Source::File(path) => write!(f, "config file {}", path),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+// An option with a value, under its config file key.
+#[derive(Debug)]
+pub struct Setting {
+    pub key: String,
+    pub values: Vec<String>,
+    pub source: Source,
+}
+
+// The command line with the lower layers merged in, and where every option comes from.
+pub struct Layered {
+    pub args: Vec<OsString>,
+    pub settings: Vec<Setting>,
+}
+
+impl Layered {
+    pub fn parse(&self) -> Result<ProverCli, clap::Error> {
+        ProverCli::try_parse_from(&self.args)
+    }
+}
+
+// Parses the prover's command line `args` with the environment `env` and the config file.
+pub fn parse_prover_cli<I>(args: I, env: impl Fn(&str) -> Option<String>) -> ProverCli
+where
+    I: IntoIterator<Item = OsString>,
+{
+    layer(&<ProverCli as clap::CommandFactory>::command(), args, env)
+        .and_then(|layered| layered.parse())
+        .unwrap_or_else(|err| err.exit())
+}
+
+// Merges the environment `env` and the config file into the command line `args` of `command`.
+pub fn layer<I>(
+    command: &Command,
+    args: I,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Layered, clap::Error>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let args: Vec<OsString> = args.into_iter().collect();
+    let matches = command.clone().try_get_matches_from(&args)?;
+    let error = |message: String| clap::Error::raw(ErrorKind::InvalidValue, message + "\n");
+    let (path, file) = read_config_file(&matches, &env).map_err(error)?;
+
+    let options: Vec<_> = command
+        .get_arguments()
+        .filter_map(|arg| arg.get_long().map(|long| (arg, long)))
+        .filter(|(_, long)| !["help", "version", "config"].contains(long))
+        .collect();
+    let key = |long: &str| long.replace('-', "_");
+    if let Some(unknown) = file
+        .keys()
+        .find(|name| !options.iter().any(|(_, long)| key(long) == **name))
+    {
+        return Err(error(format!("unknown option {} in {}", unknown, path)));
+    }
+
+    let mut layered = Layered {
+        args,
+        settings: Vec::new(),
+    };
+    for (arg, long) in options {
+        let key = key(long);
+        let env_name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+        let repeatable = arg.is_multiple_occurrences_set() || arg.get_value_delimiter().is_some();
+        let raw = |values: &mut dyn Iterator<Item = &OsStr>| -> Vec<String> {
+            values
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect()
+        };
+        if matches.value_source(arg.get_id()) == Some(ValueSource::CommandLine) {
+            let values = match arg.is_takes_value_set() {
+                true => raw(&mut matches.get_raw(arg.get_id()).into_iter().flatten()),
+                false => vec![String::from("true")],
+            };
+            layered.settings.push(Setting {
+                key,
+                values,
+                source: Source::CommandLine,
+            });
+            continue;
+        }
+        let (values, source) = if let Some(value) = env(&env_name) {
+            let values = match repeatable {
+                true => value.split(',').map(String::from).collect(),
+                false => vec![value],
+            };
+            (values, Source::Env(env_name))
+        } else if let Some(value) = file.get(&key) {
+            let values =
+                toml_values(value).map_err(|err| error(format!("{} of {}: {}", key, path, err)))?;
+            (values, Source::File(path.clone()))
+        } else {
+            let values = raw(&mut arg.get_default_values().iter().copied());
+            if !values.is_empty() {
+                layered.settings.push(Setting {
+                    key,
+                    values,
+                    source: Source::Default,
+                });
+            }
+            continue;
+        };
+
+        if !arg.is_takes_value_set() {
+            match values.as_slice() {
+                [value] if value == "true" => layered.args.push(format!("--{}", long).into()),
+                [value] if value == "false" => (),
+                _ => {
+                    return Err(error(format!(
+                        "{} ({}) is a flag, true or false",
+                        key, source
+                    )))
+                }
+            }
+        } else if arg.is_multiple_occurrences_set() {
+            for value in &values {
+                layered.args.push(format!("--{}={}", long, value).into());
+            }
+        } else if let Some(delimiter) = arg.get_value_delimiter() {
+            let value = values.join(&delimiter.to_string());
+            layered.args.push(format!("--{}={}", long, value).into());
+        } else if let [value] = values.as_slice() {
+            layered.args.push(format!("--{}={}", long, value).into());
+        } else {
+            return Err(error(format!("{} ({}) takes a single value", key, source)));
+        }
+        layered.settings.push(Setting {
+            key,
+            values,
+            source,
+        });
+    }
+    Ok(layered)
+}
+
+// The path and the options of the config file, none if there is no such file.
+fn read_config_file(
+    matches: &ArgMatches,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(String, toml::value::Table), String> {
+    let path = match matches
+        .value_of("config")
+        .map(String::from)
+        .or_else(|| env(CONFIG_ENV))
+    {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => String::from(DEFAULT_CONFIG_PATH),
+        None => return Ok((String::new(), toml::value::Table::new())),
+    };
+    let text = fs::read_to_string(&path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    let table = toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))?;
+    Ok((path, table))
+}
+
+fn toml_values(value: &toml::Value) -> Result<Vec<String>, String> {
+    match value {
+        toml::Value::Array(values) => values.iter().map(toml_value).collect(),
+        value => toml_value(value).map(|value| vec![value]),
+    }
+}
+
+fn toml_value(value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(value) => Ok(value.clone()),
+        toml::Value::Integer(value) => Ok(value.to_string()),
+        toml::Value::Float(value) => Ok(value.to_string()),
+        toml::Value::Boolean(value) => Ok(value.to_string()),
+        value => Err(format!("{} is not a string, a number or a boolean", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        std::iter::once("prover")
+            .chain(args.iter().copied())
+            .map(OsString::from)
+            .collect()
+    }
+
+    fn source<'a>(layered: &'a Layered, key: &str) -> (&'a [String], &'a Source) {
+        let setting = layered
+            .settings
+            .iter()
+            .find(|setting| setting.key == key)
+            .unwrap();
+        (&setting.values, &setting.source)
+    }
+
+    #[test]
+    fn layers_take_precedence_in_order() {
+        let dir = std::env::temp_dir().join(format!("stark-he-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stark-he.toml");
+        fs::write(
+            &path,
+            "num_queries = 30\ngrinding_factor = 8\noperand = [\"a.toml\", \"b.toml\"]\n\
+             expose = [1, 2]\nforce = true\n",
+        )
+        .unwrap();
+        let config = path.to_string_lossy().into_owned();
+        let env = |name: &str| match name {
+            "STARK_HE_GRINDING_FACTOR" => Some(String::from("12")),
+            "STARK_HE_FOLDING_FACTOR" => Some(String::from("16")),
+            _ => None,
+        };
+        let command = ProverCli::command();
+        let layered = layer(
+            &command,
+            args(&["--config", &config, "--num-queries", "20"]),
+            env,
+        )
+        .unwrap();
+        let cli = layered.parse().unwrap();
+        assert_eq!(cli.proof_options.num_queries, 20);
+        assert_eq!(cli.proof_options.grinding_factor, 12);
+        assert_eq!(cli.proof_options.folding_factor, 16);
+        assert_eq!(cli.proof_options.blowup_factor, 4);
+        assert_eq!(cli.input_args.operands, ["a.toml", "b.toml"]);
+        assert_eq!(cli.input_args.expose, Some(vec![1, 2]));
+        assert!(cli.output_args.force);
+
+        assert_eq!(
+            source(&layered, "num_queries"),
+            (&[String::from("20")][..], &Source::CommandLine)
+        );
+        let env = Source::Env(String::from("STARK_HE_GRINDING_FACTOR"));
+        assert_eq!(source(&layered, "grinding_factor").1, &env);
+        assert_eq!(source(&layered, "operand").1, &Source::File(config.clone()));
+        assert_eq!(source(&layered, "blowup_factor").1, &Source::Default);
+
+        fs::write(&path, "num_querie = 30\n").unwrap();
+        assert!(layer(&command, args(&["--config", &config]), |_| None).is_err());
+        fs::write(&path, "num_queries = [30, 31]\n").unwrap();
+        assert!(layer(&command, args(&["--config", &config]), |_| None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
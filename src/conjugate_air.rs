@@ -0,0 +1,115 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Complex conjugation of the slots of a CKKS ciphertext, e.g. to extract real parts as
+// (z + conj(z)) / 2. It is the Galois automorphism a(X) -> a(X^-1), g = 2N - 1, which maps the
+// evaluation at zeta^(5^i) (slot i) to the one at zeta^(-5^i) (its conjugate) and is outside
+// the subgroup generated by 5, so no rotation reaches it. g is its own inverse: result
+// coefficient k is the source coefficient N - k, negated, for k > 0, and coefficient 0 stays.
+//
+// The trace and constraints are RotateAir's with the index column walking by 2N - 1. The
+// Galois element is fixed, so the public inputs hold only the results, the input ciphertext and
+// its moduli.
+
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+};
+use winter_math::{ExtensionOf, FieldElement};
+
+use crate::air::{
+    load_data_file, BaseElement, CiphertextData, InputArg, PublicInputs, COEFF_DEGREE,
+};
+use crate::error::StarkHeError;
+use crate::rotate_air::{
+    automorphism_coefficients, build_automorphism_trace, get_automorphism_pub_inputs,
+    AutomorphismTrace, RotateAir,
+};
+
+// Galois element of the conjugation, its own inverse modulo 2N.
+pub const CONJUGATION_ELEMENT: u64 = 2 * COEFF_DEGREE as u64 - 1;
+
+// Reference implementation of the conjugation of one coefficient vector modulo `m`.
+pub fn conjugate_coefficients(values: &[u64], m: u64) -> Vec<u64> {
+    automorphism_coefficients(values, CONJUGATION_ELEMENT, m)
+}
+
+pub fn build_conjugate_trace(arg: &InputArg) -> Result<AutomorphismTrace, StarkHeError> {
+    // a single ciphertext in coefficient form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    build_automorphism_trace(&data, CONJUGATION_ELEMENT)
+}
+
+pub fn get_conjugate_pub_inputs(trace: &AutomorphismTrace) -> PublicInputs {
+    get_automorphism_pub_inputs(trace)
+}
+
+pub struct ConjugateAir {
+    automorphism: RotateAir,
+}
+
+impl Air for ConjugateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        ConjugateAir {
+            automorphism: RotateAir::with_step(
+                trace_info,
+                pub_inputs,
+                options,
+                CONJUGATION_ELEMENT,
+            ),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        self.automorphism.context()
+    }
+
+    fn evaluate_transition<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.automorphism
+            .evaluate_transition(frame, periodic_values, result)
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        self.automorphism.evaluate_aux_transition(
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        )
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        self.automorphism.get_assertions()
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        self.automorphism.get_aux_assertions(aux_rand_elements)
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.automorphism.get_periodic_column_values()
+    }
+}
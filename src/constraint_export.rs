@@ -0,0 +1,510 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Constraint specification export for `stark-he export-constraints`: every main transition
+// constraint of a circuit as a polynomial over the trace values of the current and the next row
+// and the periodic values, plus the shape of the boundary assertions, for analysis outside Rust.
+//
+// The AIRs only describe their constraints as code evaluating them, so the polynomials are
+// recovered from evaluations. At a pseudo-random point every variable is perturbed in turn to
+// find the variables a constraint depends on, and the constraint is restricted to a random line
+// to find its total degree d. It is then evaluated on the grid of points with small
+// non-negative coordinates summing to at most d over those variables, which determines it:
+// the forward differences at the origin are its coefficients in the falling factorial basis,
+// which Stirling numbers turn into monomials. Every polynomial is checked against the
+// constraint at another random point. Constraints of the auxiliary segment (the wiring
+// arguments) depend on the verifier's random elements and are only counted, as in explain.rs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use clap::ArgEnum;
+use winter_air::{Air, EvaluationFrame};
+use winter_math::{FieldElement, StarkField};
+use winter_prover::{Prover, Trace};
+
+use crate::air::{element_from_u128, BaseElement, InputArg, Op, PublicInputs};
+use crate::error::StarkHeError;
+use crate::explain::signed;
+use crate::proving::CircuitVisitor;
+
+// Highest total degree searched for, above the degree of every circuit.
+const MAX_DEGREE: usize = 8;
+// Largest number of evaluations interpolating a single constraint.
+const MAX_GRID_POINTS: usize = 200_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+// Builds the trace of a circuit, whose public inputs instantiate its AIR, and exports the
+// constraints of that AIR.
+pub struct ConstraintExporter {
+    pub format: ExportFormat,
+}
+
+impl CircuitVisitor for ConstraintExporter {
+    type Output = Result<String, String>;
+
+    fn visit<P>(
+        self,
+        prover: Result<P, StarkHeError>,
+        build: fn(&InputArg) -> Result<P::Trace, StarkHeError>,
+        input_args: &InputArg,
+    ) -> Self::Output
+    where
+        P: Prover<BaseField = BaseElement>,
+        P::Air: Air<PublicInputs = PublicInputs>,
+    {
+        let prover = prover?;
+        let trace = build(input_args)?;
+        let width = trace.main_segment().num_cols();
+        let air = P::Air::new(
+            trace.get_info(),
+            prover.get_pub_inputs(&trace),
+            prover.options().clone(),
+        );
+        let spec = extract(input_args.op, &air, width)?;
+        Ok(match self.format {
+            ExportFormat::Json => spec.to_json(),
+            ExportFormat::Csv => spec.to_csv(),
+        })
+    }
+}
+
+// A monomial as (variable, power) pairs in increasing variable order; variables are the
+// current row, then the next row, then the periodic values.
+type Monomial = Vec<(usize, usize)>;
+
+pub struct ConstraintPolynomial {
+    pub degree: usize,
+    pub terms: Vec<(BaseElement, Monomial)>,
+}
+
+pub struct AssertionShape {
+    pub column: usize,
+    pub first_step: usize,
+    pub stride: usize,
+    pub values: usize,
+}
+
+pub struct ConstraintSpec {
+    pub op: Op,
+    pub width: usize,
+    pub length: usize,
+    pub exemptions: usize,
+    pub periods: Vec<usize>,
+    pub constraints: Vec<ConstraintPolynomial>,
+    pub aux_constraints: usize,
+    pub assertions: Vec<AssertionShape>,
+}
+
+// splitmix64, so that the export is reproducible
+struct Points(u64);
+
+impl Points {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn element(&mut self) -> BaseElement {
+        element_from_u128(((self.next_u64() as u128) << 64) | self.next_u64() as u128)
+    }
+
+    fn point(&mut self, len: usize) -> Vec<BaseElement> {
+        (0..len).map(|_| self.element()).collect()
+    }
+}
+
+// Signed Stirling numbers of the first kind: x (x - 1) .. (x - k + 1) = sum s(k, j) x^j.
+fn stirling(max: usize) -> Vec<Vec<BaseElement>> {
+    let mut s = vec![vec![BaseElement::ZERO; max + 1]; max + 1];
+    s[0][0] = BaseElement::ONE;
+    for k in 0..max {
+        for j in 1..=k + 1 {
+            s[k + 1][j] = s[k][j - 1] - BaseElement::from(k as u64) * s[k][j];
+        }
+    }
+    s
+}
+
+// Exponent vectors over `n` variables with total degree at most `degree`.
+fn grid(n: usize, degree: usize) -> Vec<Vec<usize>> {
+    let mut points = vec![Vec::with_capacity(n)];
+    for _ in 0..n {
+        points = points
+            .into_iter()
+            .flat_map(|point: Vec<usize>| {
+                let used: usize = point.iter().sum();
+                (0..=degree - used).map(move |e| {
+                    let mut next = point.clone();
+                    next.push(e);
+                    next
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+fn binomial(n: usize, k: usize) -> u64 {
+    (0..k).fold(1u64, |acc, i| acc * (n - i) as u64 / (i + 1) as u64)
+}
+
+fn factorial(n: usize) -> u64 {
+    (1..=n as u64).product()
+}
+
+fn extract<A: Air<BaseField = BaseElement>>(
+    op: Op,
+    air: &A,
+    width: usize,
+) -> Result<ConstraintSpec, String> {
+    let context = air.context();
+    let periods: Vec<usize> = air
+        .get_periodic_column_values()
+        .iter()
+        .map(Vec::len)
+        .collect();
+    let num_vars = 2 * width + periods.len();
+    let num_constraints = context.num_main_transition_constraints();
+    let evaluate = |point: &[BaseElement]| {
+        let frame =
+            EvaluationFrame::from_rows(point[..width].to_vec(), point[width..2 * width].to_vec());
+        let mut result = vec![BaseElement::ZERO; num_constraints];
+        air.evaluate_transition(&frame, &point[2 * width..], &mut result);
+        result
+    };
+    let mut points = Points(op as u64);
+
+    // variables every constraint depends on
+    let base = points.point(num_vars);
+    let at_base = evaluate(&base);
+    let mut support = vec![Vec::new(); num_constraints];
+    for v in 0..num_vars {
+        let mut perturbed = base.clone();
+        perturbed[v] += points.element();
+        for (c, value) in evaluate(&perturbed).iter().enumerate() {
+            if *value != at_base[c] {
+                support[c].push(v);
+            }
+        }
+    }
+
+    // total degrees, from the forward differences along a random line
+    let direction = points.point(num_vars);
+    let line: Vec<Vec<BaseElement>> = (0..=MAX_DEGREE + 1)
+        .map(|t| {
+            let t = BaseElement::from(t as u64);
+            let point: Vec<_> = base
+                .iter()
+                .zip(direction.iter())
+                .map(|(&b, &d)| b + t * d)
+                .collect();
+            evaluate(&point)
+        })
+        .collect();
+    let mut degrees = Vec::with_capacity(num_constraints);
+    for c in 0..num_constraints {
+        let mut differences: Vec<BaseElement> = line.iter().map(|values| values[c]).collect();
+        let mut degree = 0;
+        for k in 1..=MAX_DEGREE + 1 {
+            differences = differences.windows(2).map(|w| w[1] - w[0]).collect();
+            if differences[0] != BaseElement::ZERO {
+                degree = k;
+            }
+        }
+        if degree > MAX_DEGREE {
+            return Err(format!(
+                "constraint {} of {} has a degree above {}",
+                c,
+                op.name(),
+                MAX_DEGREE
+            ));
+        }
+        degrees.push(degree);
+    }
+
+    // interpolation on the grid of every constraint; points are shared between constraints
+    let stirling = stirling(MAX_DEGREE);
+    let mut cache: HashMap<Vec<(usize, usize)>, Vec<BaseElement>> = HashMap::new();
+    let mut constraints = Vec::with_capacity(num_constraints);
+    for c in 0..num_constraints {
+        let (vars, degree) = (&support[c], degrees[c]);
+        let size = binomial(vars.len() + degree, degree);
+        if size > MAX_GRID_POINTS as u64 {
+            return Err(format!(
+                "constraint {} of {} depends on {} variables with degree {}, too many to \
+                 interpolate",
+                c,
+                op.name(),
+                vars.len(),
+                degree
+            ));
+        }
+        let exponents = grid(vars.len(), degree);
+        let mut value_at = |alpha: &[usize]| -> BaseElement {
+            let key: Vec<(usize, usize)> = vars
+                .iter()
+                .zip(alpha)
+                .filter(|(_, &e)| e > 0)
+                .map(|(&v, &e)| (v, e))
+                .collect();
+            let values = cache.entry(key).or_insert_with_key(|key| {
+                let mut point = vec![BaseElement::ZERO; num_vars];
+                for &(v, e) in key.iter() {
+                    point[v] = BaseElement::from(e as u64);
+                }
+                evaluate(&point)
+            });
+            values[c]
+        };
+
+        let mut terms: BTreeMap<Monomial, BaseElement> = BTreeMap::new();
+        for alpha in exponents.iter() {
+            // forward difference at the origin over the points below alpha
+            let mut difference = BaseElement::ZERO;
+            for beta in grid_below(alpha) {
+                let mut weight = 1u64;
+                for (&a, &b) in alpha.iter().zip(beta.iter()) {
+                    weight *= binomial(a, b);
+                }
+                let weight = BaseElement::from(weight);
+                let parity = alpha.iter().sum::<usize>() - beta.iter().sum::<usize>();
+                let value = value_at(&beta) * weight;
+                if parity % 2 == 0 {
+                    difference += value;
+                } else {
+                    difference -= value;
+                }
+            }
+            if difference == BaseElement::ZERO {
+                continue;
+            }
+            let scale: u64 = alpha.iter().map(|&a| factorial(a)).product();
+            let newton = difference / BaseElement::from(scale);
+
+            // expand the product of falling factorials into monomials
+            let mut expansion: Vec<(BaseElement, Monomial)> = vec![(newton, Vec::new())];
+            for (&v, &a) in vars.iter().zip(alpha.iter()).filter(|(_, &a)| a > 0) {
+                let mut next = Vec::new();
+                for (coefficient, monomial) in expansion.iter() {
+                    for (j, &s) in stirling[a].iter().enumerate().skip(1) {
+                        if s == BaseElement::ZERO {
+                            continue;
+                        }
+                        let mut monomial = monomial.clone();
+                        monomial.push((v, j));
+                        next.push((*coefficient * s, monomial));
+                    }
+                }
+                expansion = next;
+            }
+            for (coefficient, monomial) in expansion {
+                *terms.entry(monomial).or_insert(BaseElement::ZERO) += coefficient;
+            }
+        }
+        let terms: Vec<(BaseElement, Monomial)> = terms
+            .into_iter()
+            .filter(|(_, coefficient)| *coefficient != BaseElement::ZERO)
+            .map(|(monomial, coefficient)| (coefficient, monomial))
+            .collect();
+        constraints.push(ConstraintPolynomial { degree, terms });
+    }
+
+    // the polynomials must agree with the constraints away from the grid
+    let check = points.point(num_vars);
+    let at_check = evaluate(&check);
+    for (c, polynomial) in constraints.iter().enumerate() {
+        let value =
+            polynomial
+                .terms
+                .iter()
+                .fold(BaseElement::ZERO, |acc, (coefficient, monomial)| {
+                    acc + monomial.iter().fold(*coefficient, |product, &(v, e)| {
+                        product * check[v].exp((e as u32).into())
+                    })
+                });
+        if value != at_check[c] {
+            return Err(format!(
+                "constraint {} of {} is not a polynomial of its trace values",
+                c,
+                op.name()
+            ));
+        }
+    }
+
+    let length = context.trace_len();
+    let assertions = air
+        .get_assertions()
+        .iter()
+        .map(|assertion| AssertionShape {
+            column: assertion.column(),
+            first_step: assertion.first_step(),
+            stride: assertion.stride(),
+            values: assertion.values().len(),
+        })
+        .collect();
+    Ok(ConstraintSpec {
+        op,
+        width,
+        length,
+        exemptions: context.num_transition_exemptions(),
+        periods,
+        constraints,
+        aux_constraints: context.num_aux_transition_constraints(),
+        assertions,
+    })
+}
+
+// Exponent vectors componentwise at most `alpha`.
+fn grid_below(alpha: &[usize]) -> Vec<Vec<usize>> {
+    let mut points = vec![Vec::with_capacity(alpha.len())];
+    for &a in alpha {
+        points = points
+            .into_iter()
+            .flat_map(|point: Vec<usize>| {
+                (0..=a).map(move |e| {
+                    let mut next = point.clone();
+                    next.push(e);
+                    next
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+impl ConstraintSpec {
+    // (row, column) of variable `v`, the row being `current`, `next` or `periodic`.
+    fn variable(&self, v: usize) -> (&'static str, usize) {
+        if v < self.width {
+            ("current", v)
+        } else if v < 2 * self.width {
+            ("next", v - self.width)
+        } else {
+            ("periodic", v - 2 * self.width)
+        }
+    }
+
+    fn monomial_text(&self, monomial: &Monomial) -> String {
+        if monomial.is_empty() {
+            return String::from("1");
+        }
+        let factors: Vec<String> = monomial
+            .iter()
+            .map(|&(v, e)| {
+                let (row, column) = self.variable(v);
+                match e {
+                    1 => format!("{}[{}]", row, column),
+                    _ => format!("{}[{}]^{}", row, column, e),
+                }
+            })
+            .collect();
+        factors.join("*")
+    }
+
+    // One line per term: constraint index, its degree, the signed coefficient and the monomial
+    // as a product of `current[c]`, `next[c]` and `periodic[c]` powers.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("constraint,degree,coefficient,monomial\n");
+        for (c, constraint) in self.constraints.iter().enumerate() {
+            for (coefficient, monomial) in constraint.terms.iter() {
+                let _ = writeln!(
+                    out,
+                    "{},{},{},{}",
+                    c,
+                    constraint.degree,
+                    signed(*coefficient),
+                    self.monomial_text(monomial)
+                );
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let info = self.op.info();
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"circuit\":\"{}\",\"version\":\"{}\",\"field_modulus\":\"{}\",\
+             \"trace_width\":{},\"trace_length\":{},\"transition_exemptions\":{},\"layout\":{{",
+            info.id,
+            info.version,
+            BaseElement::MODULUS,
+            self.width,
+            self.length,
+            self.exemptions
+        );
+        let layout: Vec<String> = self
+            .op
+            .layout()
+            .iter()
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect();
+        out.push_str(&layout.join(","));
+        out.push_str("},\"periodic_columns\":[");
+        let periods: Vec<String> = self.periods.iter().map(usize::to_string).collect();
+        out.push_str(&periods.join(","));
+        out.push_str("],\"transition_constraints\":[");
+        for (c, constraint) in self.constraints.iter().enumerate() {
+            if c > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"index\":{},\"degree\":{},\"terms\":[",
+                c, constraint.degree
+            );
+            for (t, (coefficient, monomial)) in constraint.terms.iter().enumerate() {
+                if t > 0 {
+                    out.push(',');
+                }
+                let factors: Vec<String> = monomial
+                    .iter()
+                    .map(|&(v, e)| {
+                        let (row, column) = self.variable(v);
+                        format!(
+                            "{{\"row\":\"{}\",\"column\":{},\"power\":{}}}",
+                            row, column, e
+                        )
+                    })
+                    .collect();
+                let _ = write!(
+                    out,
+                    "{{\"coefficient\":\"{}\",\"factors\":[{}]}}",
+                    signed(*coefficient),
+                    factors.join(",")
+                );
+            }
+            out.push_str("]}");
+        }
+        let _ = write!(
+            out,
+            "],\"auxiliary_constraints\":{},\"assertions\":[",
+            self.aux_constraints
+        );
+        let assertions: Vec<String> = self
+            .assertions
+            .iter()
+            .map(|a| {
+                format!(
+                    "{{\"column\":{},\"first_step\":{},\"stride\":{},\"values\":{}}}",
+                    a.column, a.first_step, a.stride, a.values
+                )
+            })
+            .collect();
+        out.push_str(&assertions.join(","));
+        out.push_str("]}\n");
+        out
+    }
+}
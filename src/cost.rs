@@ -0,0 +1,264 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Dry-run cost model of proving. Estimates follow from the trace shape of a circuit and the proof
+// options: the work and memory of the prover are dominated by the low-degree extension of the
+// trace and composition columns, the proof by the queried rows and their Merkle paths. The
+// constants were calibrated against a single-threaded release build and are meant for comparing
+// schedules, not as exact predictions.
+
+use winter_air::ProofOptions;
+
+use crate::air::{Op, COEFF_DEGREE, FRESH_TRACE_LENGTH};
+use crate::ckks_encode_air::CKKS_ENCODE_AUX_WIDTH;
+use crate::decrypt_air::DECRYPT_AUX_WIDTH;
+use crate::decrypt_share_air::DECRYPT_SHARE_AUX_WIDTH;
+use crate::encrypt_air::ENCRYPT_AUX_WIDTH;
+use crate::intt_air::INTT_AUX_WIDTH;
+use crate::mat_vec_air::{min_mat_vec_length, MAT_VEC_AUX_WIDTH};
+use crate::ntt_air::{NTT_AUX_WIDTH, NTT_TRACE_LENGTH};
+use crate::rotate_air::AUTOMORPHISM_AUX_WIDTH;
+use crate::sum_air::sum_slots;
+
+// Proving time per cell of the extended trace and composition columns.
+const NS_PER_LDE_CELL: f64 = 280.0;
+// Data files, keys and the binary itself.
+const BASE_MEMORY: usize = 8 << 20;
+const ELEMENT_BYTES: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceShape {
+    pub width: usize,
+    pub length: usize,
+    // highest transition constraint degree
+    pub max_degree: usize,
+}
+
+impl TraceShape {
+    pub fn of(op: Op) -> TraceShape {
+        let layout = op.layout();
+        let constant = |constant: &str| {
+            layout
+                .iter()
+                .find(|(name, _)| *name == constant)
+                .map(|(_, value)| *value)
+        };
+        let width = constant("state_width").expect("every layout records its state width");
+        // circuits reducing with reduction.rs repeat every row over a row group
+        let group = constant("group").unwrap_or(1);
+        // degrees declared by the AIRs: range checks of the rotation and conjugation digits are
+        // cubic, the NTT wiring arguments are cubic under a periodic selector, the matrix-vector
+        // wiring argument and the reductions of quadratic products quadratic under one, the
+        // scaled sums of aggregations and the weighted sums of inner products linear under two,
+        // everything else is at most quadratic
+        let max_degree = match op {
+            Op::Ntt | Op::Intt | Op::Decrypt | Op::DecryptShare | Op::Encrypt | Op::CkksEncode => 4,
+            Op::Rotate
+            | Op::Conjugate
+            | Op::MatVec
+            | Op::Mul
+            | Op::WideMul
+            | Op::MontMul
+            | Op::PlainMul
+            | Op::Fma
+            | Op::Relin
+            | Op::KeySwitch
+            | Op::Horner
+            | Op::Aggregate
+            | Op::InnerProduct
+            | Op::Pipeline
+            | Op::Compare
+            | Op::BlindRotate
+            | Op::Pack
+            | Op::Unpack
+            | Op::ReEncrypt
+            | Op::HoistedRotate
+            | Op::KeyRotate
+            | Op::KeyConjugate => 3,
+            Op::Add
+            | Op::Sub
+            | Op::Neg
+            | Op::ModSwitch
+            | Op::ModRaise
+            | Op::BaseExt
+            | Op::MultiKeyAdd
+            | Op::ScalarMul
+            | Op::Sum
+            | Op::Rescale => 2,
+        };
+        // the NTTs, decryption, encryption and encoding run one butterfly per row and commit to
+        // their wiring argument separately, as matrix-vector products, rotations and
+        // conjugations do; additions reduce one result coefficient per row
+        match op {
+            Op::Rotate | Op::Conjugate => TraceShape {
+                width: width + AUTOMORPHISM_AUX_WIDTH,
+                length: COEFF_DEGREE,
+                max_degree,
+            },
+            Op::Ntt => TraceShape {
+                width: width + NTT_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::Intt => TraceShape {
+                width: width + INTT_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::Decrypt => TraceShape {
+                width: width + DECRYPT_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::DecryptShare => TraceShape {
+                width: width + DECRYPT_SHARE_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::Encrypt => TraceShape {
+                width: width + ENCRYPT_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::CkksEncode => TraceShape {
+                width: width + CKKS_ENCODE_AUX_WIDTH,
+                length: NTT_TRACE_LENGTH * group,
+                max_degree,
+            },
+            Op::Add => TraceShape {
+                width,
+                length: FRESH_TRACE_LENGTH,
+                max_degree,
+            },
+            Op::Sum | Op::InnerProduct | Op::Horner | Op::Pipeline | Op::Aggregate => TraceShape {
+                width,
+                length: COEFF_DEGREE * sum_slots(2) * group,
+                max_degree,
+            },
+            Op::MatVec => TraceShape {
+                width: width + MAT_VEC_AUX_WIDTH,
+                length: min_mat_vec_length(),
+                max_degree,
+            },
+            _ => TraceShape {
+                width,
+                length: COEFF_DEGREE * group,
+                max_degree,
+            },
+        }
+    }
+
+    // Shape of a sum or inner product `op` of `operands` ciphertexts; TraceShape::of gives the
+    // shortest one.
+    pub fn sum(op: Op, operands: usize) -> TraceShape {
+        let shape = TraceShape::of(op);
+        TraceShape {
+            length: shape.length / sum_slots(2) * sum_slots(operands),
+            ..shape
+        }
+    }
+
+    // One trace holding all `shapes` side by side, as a single proof of several operations
+    // would. None if the traces differ in length.
+    pub fn compose(shapes: &[TraceShape]) -> Option<TraceShape> {
+        let length = shapes.first()?.length;
+        if shapes.iter().any(|shape| shape.length != length) {
+            return None;
+        }
+        Some(TraceShape {
+            width: shapes.iter().map(|shape| shape.width).sum(),
+            length,
+            max_degree: shapes.iter().map(|shape| shape.max_degree).max()?,
+        })
+    }
+
+    // Columns of the constraint composition polynomial, see AirContext::ce_blowup_factor.
+    pub fn composition_width(&self) -> usize {
+        (self.max_degree - 1).next_power_of_two().max(2)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CostEstimate {
+    pub lde_rows: usize,
+    pub seconds: f64,
+    pub memory_bytes: usize,
+    pub proof_bytes: usize,
+}
+
+impl CostEstimate {
+    // Cost of proving one after another: times and proof sizes add up, memory is reused.
+    pub fn chain(estimates: &[CostEstimate]) -> CostEstimate {
+        CostEstimate {
+            lde_rows: estimates.iter().map(|e| e.lde_rows).max().unwrap_or(0),
+            seconds: estimates.iter().map(|e| e.seconds).sum(),
+            memory_bytes: estimates.iter().map(|e| e.memory_bytes).max().unwrap_or(0),
+            proof_bytes: estimates.iter().map(|e| e.proof_bytes).sum(),
+        }
+    }
+}
+
+// Bytes of the authentication paths of `queries` leaves of a tree with `leaves` leaves. Paths
+// are batched, so the levels above the queried leaves are shared.
+fn merkle_paths(queries: usize, leaves: usize, digest_bytes: usize) -> usize {
+    let depth = leaves.trailing_zeros() as usize;
+    let shared = queries.min(leaves).next_power_of_two().trailing_zeros() as usize;
+    queries * depth.saturating_sub(shared) * digest_bytes
+}
+
+pub fn estimate(shape: &TraceShape, options: &ProofOptions) -> CostEstimate {
+    let extension = options.field_extension().degree() as usize;
+    let digest_bytes = options.hash_fn().collision_resistance() as usize / 4;
+    let queries = options.num_queries();
+    let lde_rows = shape.length * options.blowup_factor();
+    let composition_width = shape.composition_width() * extension;
+
+    let lde_cells = (shape.width + composition_width) * lde_rows;
+    let seconds = lde_cells as f64 * NS_PER_LDE_CELL * 1e-9;
+
+    // trace table, its polynomials, both extensions and two Merkle trees
+    let memory_bytes = BASE_MEMORY
+        + 2 * shape.width * shape.length * ELEMENT_BYTES
+        + lde_cells * ELEMENT_BYTES
+        + 2 * 2 * lde_rows * digest_bytes;
+
+    // queried trace and composition rows with their paths, then the FRI layers and remainder
+    let fri = options.to_fri_options();
+    let folding = fri.folding_factor();
+    let mut proof_bytes = queries * (shape.width + composition_width) * ELEMENT_BYTES
+        + 2 * merkle_paths(queries, lde_rows, digest_bytes);
+    let mut domain = lde_rows;
+    for _ in 0..fri.num_fri_layers(lde_rows) {
+        domain /= folding;
+        proof_bytes += queries * folding * extension * ELEMENT_BYTES
+            + merkle_paths(queries, domain, digest_bytes);
+    }
+    proof_bytes += fri.fri_remainder_size(lde_rows) * extension * ELEMENT_BYTES;
+    // out-of-domain frame of two rows and the composition columns
+    proof_bytes += (2 * shape.width + composition_width) * extension * ELEMENT_BYTES;
+
+    CostEstimate {
+        lde_rows,
+        seconds,
+        memory_bytes,
+        proof_bytes,
+    }
+}
+
+// Human-readable size, e.g. "12.5 MiB".
+pub fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
@@ -0,0 +1,911 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// BFV decryption of a ciphertext under a secret key the verifier never sees. The key is pinned
+// by the matching public key (b, a) = (-(a * s + e), a), distributed like an evaluation key with
+// a single digit, and the proof shows that for a ternary s and an error e with |e| <= 32
+//
+//     b + a * s + e = 0 mod q,  v = c0 + c1 * s mod q,  m = round(t * [v]_Q / Q) mod t
+//
+// for every RNS level, with Q = q0 * q1 * .. and the plain modulus t. The ciphertext, the
+// public key and the secret key are in NTT form, so both products are pointwise.
+//
+// The trace holds three networks of InttAir per level, for the evaluations of s, of e =
+// -(b + a * s) and of v, on the circuit rows and row groups of InttAir. Their circuit rows 16b
+// hold evaluations 2b and 2b + 1, where the key relation and the phase are checked against the
+// public key and the ciphertext; their last-stage circuit rows 16b + 11 hold coefficients b and
+// b + N/2, where s is checked to be ternary, e to be the six-bit value E - 32 at every level, and
+// the phase, rebuilt in mixed radix from its residues wl as w = w0 + q0 * u1 + q0 * q1 * u2 + ..,
+// to round to the plaintext:
+//
+//     (w0 + .. + q0 * .. * q(l-1) * ul) mod ql = wl,  t * w + floor(Q / 2) = P * Q + r
+//
+// where P is the plaintext m, or t if the rounding wraps around, and r = r0 + q0 * r1 + .. has
+// the digits rl < ql. Besides the butterflies, the rows of a group reduce q^2 - b - a * s to the
+// error and c0 + c1 * s to the phase on the first stage, the mixed-radix sums to the phase and P
+// to m modulo t on the last, and reduce the digits ul and rl to themselves, all against the public
+// moduli and t. A reduction of a relation reduces its result to itself on the other circuit rows,
+// where that result is a residue too. The plaintext is bound by sequence assertions.
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+use winter_utils::{ByteReader, DeserializationError};
+
+use crate::air::{
+    element_from_u128, element_from_u64, element_to_u64, first_level, level_column, load_data_file,
+    BaseElement, InputArg, PublicInputs, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::canonical::{read_u64s, CanonicalWriter};
+use crate::error::StarkHeError;
+use crate::intt_air::{
+    butterfly_values, fill_inverse_network, intt_periodic_columns, INTT_PERIODIC_NUM, LAST_STAGE,
+    SCALE_START, TWIDDLE_START,
+};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::{
+    check_ntt_modulus, evaluate_wiring, wiring_assertions, wiring_degrees, NttTrace, BUTTERFLY_NUM,
+    NTT_STAGE_NUM, NTT_TRACE_LENGTH, STAGE_SLOTS,
+};
+use crate::reduction::{reduction_group, reduction_width, Reductions};
+
+// Networks of the secret key, the key error and the phase, one per level each, + Reduction
+// Network columns: A.. B.. X.. Y.., network major, level minor
+// Rows 16b, half h holding evaluation 2b + h: PB.. PA.. C0.. C1.., half major
+// Rows 16b + 11, half h holding coefficient b + hN/2: S.. E.. U.. R.. P.. M..
+pub const PUBLIC_KEY_DIGIT_NUM: usize = 1;
+const KEY_NETWORK: usize = 0;
+const ERROR_NETWORK: usize = 1;
+const PHASE_NETWORK: usize = 2;
+const NETWORK_NUM: usize = 3;
+const POLY_NUM: usize = NETWORK_NUM * COEFF_LEVEL;
+const HALF_NUM: usize = 2;
+const HALF_LEN: usize = HALF_NUM * COEFF_LEVEL;
+// the key error is E - 32 with E in 0..64
+pub const ERROR_BITS: usize = 6;
+pub const ERROR_OFFSET: u64 = 1 << (ERROR_BITS - 1);
+// mixed-radix digits of the phase beyond its residue at the first level
+const CRT_DIGIT_NUM: usize = COEFF_LEVEL - 1;
+
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + POLY_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + POLY_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + POLY_NUM;
+const KEY_B_START: usize = OUTPUT_Y_START + POLY_NUM;
+const KEY_A_START: usize = KEY_B_START + HALF_LEN;
+const CIPHERTEXT_START: usize = KEY_A_START + HALF_LEN;
+const SECRET_START: usize = CIPHERTEXT_START + VALUE_NUM * HALF_LEN;
+const ERROR_BIT_START: usize = SECRET_START + HALF_NUM;
+const CRT_DIGIT_START: usize = ERROR_BIT_START + HALF_NUM * ERROR_BITS;
+const REMAINDER_START: usize = CRT_DIGIT_START + HALF_NUM * CRT_DIGIT_NUM;
+const ROUNDED_START: usize = REMAINDER_START + HALF_LEN;
+const PLAINTEXT_START: usize = ROUNDED_START + HALF_NUM;
+const PLAINTEXT_END: usize = PLAINTEXT_START + HALF_NUM;
+// butterflies, key relations, phases, mixed-radix sums and digits, remainder digits, plaintexts
+const REDUCTION_NUM: usize =
+    2 * POLY_NUM + 2 * HALF_LEN + 2 * HALF_NUM * CRT_DIGIT_NUM + HALF_LEN + HALF_NUM;
+// the butterflies reach 2 * q^2, so their quotients take a bit more than the modulus; t takes at
+// most the bits of the moduli
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per reduction, then padding
+pub const DECRYPT_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = PLAINTEXT_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = NTT_TRACE_LENGTH;
+pub const DECRYPT_AUX_WIDTH: usize = POLY_NUM;
+const AUX_WIDTH: usize = DECRYPT_AUX_WIDTH;
+pub const DECRYPT_RESULT_NUM: usize = 1;
+
+// Periodic columns after those of InttAir: selectors of the first and the last stage, then
+// those of the reductions.
+const FIRST_SELECTOR: usize = INTT_PERIODIC_NUM;
+const LAST_SELECTOR: usize = FIRST_SELECTOR + 1;
+const REDUCTION_PERIODIC_START: usize = LAST_SELECTOR + 1;
+
+// Trace layout constants of DecryptAir in canonical order.
+pub fn decrypt_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("error_bits", ERROR_BITS),
+        ("group", DECRYPT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// The decrypted ciphertext, in NTT form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decryption {
+    pub ciphertext: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+impl Decryption {
+    // Layout: every ciphertext vector as its length (u32) and u64 coefficients, ordered by
+    // component and level.
+    pub fn write_canonical(&self, writer: &mut CanonicalWriter) {
+        for component in self.ciphertext.iter() {
+            for level in component.iter() {
+                writer.write_u64s(level);
+            }
+        }
+    }
+
+    pub fn read_canonical<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let mut ciphertext: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
+        for level in ciphertext.iter_mut().flatten() {
+            *level = read_u64s(source)?;
+        }
+        Ok(Decryption { ciphertext })
+    }
+
+    // The ciphertext must be reduced modulo `modulus`, the plaintext in `result` modulo
+    // `plain_modulus`, and the rounding must fit into the field.
+    pub fn check(
+        &self,
+        modulus: &[u64],
+        plain_modulus: u64,
+        result: &[Vec<Vec<u64>>],
+    ) -> Result<(), String> {
+        check_decrypt_parameters(modulus, plain_modulus)?;
+        match &result.iter().map(Vec::as_slice).collect::<Vec<_>>()[..] {
+            [[plaintext, dropped @ ..]]
+                if plaintext.len() == COEFF_DEGREE
+                    && dropped.iter().all(Vec::is_empty)
+                    && plaintext.iter().all(|&c| c < plain_modulus) => {}
+            _ => {
+                return Err(format!(
+                    "plaintext is not {} residues modulo {}",
+                    COEFF_DEGREE, plain_modulus
+                ))
+            }
+        }
+        for (v, component) in self.ciphertext.iter().enumerate() {
+            for (l, level) in component.iter().enumerate() {
+                if level.len() != COEFF_DEGREE || level.iter().any(|&c| c >= modulus[l]) {
+                    return Err(format!(
+                        "ciphertext component {} level {} is not {} residues modulo {}",
+                        v, l, COEFF_DEGREE, modulus[l]
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// NTT-friendly moduli, t * Q below 2^126 so that the rounding relation does not wrap, and t
+// within the bits of the reduction blocks.
+pub fn check_decrypt_parameters(modulus: &[u64], plain_modulus: u64) -> Result<(), String> {
+    check_ntt_modulus(modulus)?;
+    let bound = modulus
+        .iter()
+        .try_fold(plain_modulus as u128, |acc, &q| acc.checked_mul(q as u128));
+    if plain_modulus < 2 || !matches!(bound, Some(bound) if bound < 1 << 126) {
+        return Err(format!(
+            "plain modulus {} times the product of the moduli must be below 2^126",
+            plain_modulus
+        ));
+    }
+    if 64 - plain_modulus.leading_zeros() > MUL_MAX_MODULUS_BITS {
+        return Err(format!(
+            "plain modulus {} does not fit into {} bits",
+            plain_modulus, MUL_MAX_MODULUS_BITS
+        ));
+    }
+    Ok(())
+}
+
+// Same format as CiphertextData, with the plain modulus and the secret key in NTT form.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DecryptData {
+    pub modulus: Vec<u64>,
+    pub plain_modulus: u64,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+    pub secret_key: [Vec<u64>; COEFF_LEVEL],
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlainModulusData {
+    plain_modulus: u64,
+}
+
+// Plain modulus of a decryption or encryption data file, which the prover records in the public
+// inputs.
+pub fn load_plain_modulus(data_file_path: &str) -> Result<u64, StarkHeError> {
+    let data: PlainModulusData = load_data_file(data_file_path)?;
+    Ok(data.plain_modulus)
+}
+
+fn network_columns(n: usize, l: usize) -> [usize; 4] {
+    [INPUT_A_START, INPUT_B_START, OUTPUT_X_START, OUTPUT_Y_START]
+        .map(|start| level_column(start, n, l))
+}
+
+// Input or output column of half `h` of a network: A, B on rows 16b, X, Y on rows 16b + 11.
+fn input_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([INPUT_A_START, INPUT_B_START][h], n, l)
+}
+
+fn output_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([OUTPUT_X_START, OUTPUT_Y_START][h], n, l)
+}
+
+fn key_column(v: usize, h: usize, l: usize) -> usize {
+    level_column([KEY_B_START, KEY_A_START][v], h, l)
+}
+
+fn ciphertext_column(v: usize, h: usize, l: usize) -> usize {
+    level_column(CIPHERTEXT_START + v * HALF_LEN, h, l)
+}
+
+fn error_bit_column(h: usize, i: usize) -> usize {
+    ERROR_BIT_START + h * ERROR_BITS + i
+}
+
+// Digit of level l >= 1 in the mixed-radix reconstruction of the phase.
+fn crt_digit_column(h: usize, l: usize) -> usize {
+    CRT_DIGIT_START + h * CRT_DIGIT_NUM + l - 1
+}
+
+// Digit of level l in the mixed-radix remainder of the rounding.
+fn remainder_column(h: usize, l: usize) -> usize {
+    level_column(REMAINDER_START, h, l)
+}
+
+// Reductions of a row: the values reduced to X and Y (half h) of every network, ...
+fn network_reduction(n: usize, h: usize, l: usize) -> usize {
+    HALF_NUM * (n * COEFF_LEVEL + l) + h
+}
+
+// .. the key relation (r = 0) and the phase (r = 1), reduced to the inputs of the error and the
+// phase networks, ..
+fn relation_reduction(r: usize, h: usize, l: usize) -> usize {
+    2 * POLY_NUM + r * HALF_LEN + level_column(0, h, l)
+}
+
+// .. the mixed-radix sum up to level l >= 1 (d = 0), reduced to the phase, and its digit
+// (d = 1), ..
+fn crt_reduction(h: usize, l: usize, d: usize) -> usize {
+    2 * POLY_NUM + 2 * HALF_LEN + 2 * (h * CRT_DIGIT_NUM + l - 1) + d
+}
+
+// .. the digits of the remainder ..
+fn remainder_reduction(h: usize, l: usize) -> usize {
+    2 * POLY_NUM + 2 * HALF_LEN + 2 * HALF_NUM * CRT_DIGIT_NUM + level_column(0, h, l)
+}
+
+// .. and P, reduced to the plaintext.
+fn plaintext_reduction(h: usize) -> usize {
+    REDUCTION_NUM - HALF_NUM + h
+}
+
+// The reductions of a row by the public moduli `modulus` and the plain modulus, every value
+// bounded as build_decrypt_trace computes it.
+pub fn decrypt_reductions(modulus: &[u64], plain_modulus: u64) -> Result<Reductions, String> {
+    check_decrypt_parameters(modulus, plain_modulus)?;
+    let mut results = vec![0; REDUCTION_NUM];
+    let mut moduli = vec![0; REDUCTION_NUM];
+    let mut bounds = vec![0; REDUCTION_NUM];
+    let mut set = |j: usize, result: usize, m: u64, bound: u128| {
+        results[j] = result;
+        moduli[j] = m;
+        bounds[j] = bound;
+    };
+    for h in 0..HALF_NUM {
+        let mut radix = modulus[0] as u128;
+        for l in 0..COEFF_LEVEL {
+            let q = modulus[l] as u128;
+            for n in 0..NETWORK_NUM {
+                set(
+                    network_reduction(n, h, l),
+                    output_column(n, h, l),
+                    modulus[l],
+                    2 * q * q,
+                );
+            }
+            for (r, n) in [ERROR_NETWORK, PHASE_NETWORK].into_iter().enumerate() {
+                set(
+                    relation_reduction(r, h, l),
+                    input_column(n, h, l),
+                    modulus[l],
+                    q * q,
+                );
+            }
+            if l > 0 {
+                radix *= q;
+                let phase = output_column(PHASE_NETWORK, h, l);
+                set(crt_reduction(h, l, 0), phase, modulus[l], radix);
+                set(
+                    crt_reduction(h, l, 1),
+                    crt_digit_column(h, l),
+                    modulus[l],
+                    q,
+                );
+            }
+            set(
+                remainder_reduction(h, l),
+                remainder_column(h, l),
+                modulus[l],
+                q,
+            );
+        }
+        set(
+            plaintext_reduction(h),
+            PLAINTEXT_START + h,
+            plain_modulus,
+            plain_modulus as u128,
+        );
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (0..PLAINTEXT_END).collect(),
+        PLAINTEXT_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Wiring columns of DecryptAir, in polynomial order.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    let mut wiring = Vec::with_capacity(POLY_NUM);
+    for n in 0..NETWORK_NUM {
+        for l in 0..COEFF_LEVEL {
+            wiring.push(network_columns(n, l));
+        }
+    }
+    wiring
+}
+
+// Circuit rows holding evaluations 2b and 2b + 1, and coefficients b and b + N/2.
+pub fn first_row(b: usize) -> usize {
+    b * STAGE_SLOTS
+}
+
+pub fn last_row(b: usize) -> usize {
+    b * STAGE_SLOTS + LAST_STAGE
+}
+
+pub fn signed(value: u64, q: u64) -> i64 {
+    if value > q / 2 {
+        -((q - value) as i64)
+    } else {
+        value as i64
+    }
+}
+
+pub fn build_decrypt_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let data: DecryptData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_ref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from(
+            "decryption needs --evaluation-key holding the public key",
+        ))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    let decryption = Decryption {
+        ciphertext: data.values,
+    };
+    let reductions = decrypt_reductions(&data.modulus, data.plain_modulus)
+        .map_err(StarkHeError::InvalidInput)?;
+    let modulus = data.modulus;
+    assert!(
+        key.modulus == modulus && key.values.len() == PUBLIC_KEY_DIGIT_NUM,
+        "the public key must be a single digit over the moduli of the ciphertext"
+    );
+    let [key_b, key_a] = &key.values[0];
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; PLAINTEXT_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    let mut secret = vec![[0; COEFF_LEVEL]; COEFF_DEGREE];
+    let mut error = vec![[0; COEFF_LEVEL]; COEFF_DEGREE];
+    let mut phase = vec![[0; COEFF_LEVEL]; COEFF_DEGREE];
+    for l in 0..COEFF_LEVEL {
+        let q = modulus[l];
+        let s = &data.secret_key[l];
+        assert!(
+            [
+                s,
+                &key_b[l],
+                &key_a[l],
+                &decryption.ciphertext[0][l],
+                &decryption.ciphertext[1][l]
+            ]
+            .iter()
+            .all(|values| values.len() == COEFF_DEGREE && values.iter().all(|&c| c < q)),
+            "keys and ciphertext must be {} residues modulo {}",
+            COEFF_DEGREE,
+            q
+        );
+        let mut e = vec![0; COEFF_DEGREE];
+        let mut v = vec![0; COEFF_DEGREE];
+        for k in 0..COEFF_DEGREE {
+            let (row, h) = (first_row(k / 2), k % 2);
+            let key_sum = key_b[l][k] as u128 + key_a[l][k] as u128 * s[k] as u128;
+            e[k] = (q - (key_sum % q as u128) as u64) % q;
+            let phase_sum = decryption.ciphertext[0][l][k] as u128
+                + decryption.ciphertext[1][l][k] as u128 * s[k] as u128;
+            v[k] = (phase_sum % q as u128) as u64;
+
+            columns[key_column(0, h, l)][row] = element_from_u64(key_b[l][k]);
+            columns[key_column(1, h, l)][row] = element_from_u64(key_a[l][k]);
+            for c in 0..VALUE_NUM {
+                columns[ciphertext_column(c, h, l)][row] =
+                    element_from_u64(decryption.ciphertext[c][l][k]);
+            }
+        }
+        let mut coefficients = Vec::with_capacity(NETWORK_NUM);
+        for (n, a) in [s.clone(), e, v].into_iter().enumerate() {
+            let (a, network_values) =
+                fill_inverse_network(&mut columns, network_columns(n, l), a, q);
+            for (row, pair) in network_values.into_iter().enumerate() {
+                for h in 0..HALF_NUM {
+                    values[row][network_reduction(n, h, l)] = pair[h];
+                }
+            }
+            coefficients.push(a);
+        }
+        for k in 0..COEFF_DEGREE {
+            secret[k][l] = signed(coefficients[0][k], q);
+            error[k][l] = signed(coefficients[1][k], q);
+            phase[k][l] = coefficients[2][k];
+        }
+    }
+
+    let t = data.plain_modulus as u128;
+    let product: u128 = modulus.iter().map(|&q| q as u128).product();
+    for k in 0..COEFF_DEGREE {
+        let (row, h) = (last_row(k % BUTTERFLY_NUM), k / BUTTERFLY_NUM);
+        let s = secret[k][0];
+        assert!(
+            secret[k].iter().all(|&c| c == s) && s.abs() <= 1,
+            "secret key coefficient {} is not ternary",
+            k
+        );
+        let e = error[k][0];
+        let offset = ERROR_OFFSET as i64;
+        assert!(
+            error[k].iter().all(|&c| c == e) && (-offset..offset).contains(&e),
+            "public key error coefficient {} is out of range, the keys do not match",
+            k
+        );
+        columns[SECRET_START + h][row] = signed_element(s);
+        let bits = (e + offset) as u64;
+        for i in 0..ERROR_BITS {
+            columns[error_bit_column(h, i)][row] = BaseElement::from((bits >> i) & 1);
+        }
+
+        let (mut w, mut radix) = (phase[k][0] as u128, modulus[0] as u128);
+        for l in 1..COEFF_LEVEL {
+            let (residue, q) = (phase[k][l] as u128, modulus[l] as u128);
+            let u = (residue + q - w % q) % q * inverse_mod(radix % q, q) % q;
+            w += radix * u;
+            radix *= q;
+            columns[crt_digit_column(h, l)][row] = element_from_u128(u);
+        }
+        let rounded = t * w + product / 2;
+        let mut remainder = rounded % product;
+        for l in 0..COEFF_LEVEL {
+            let q = modulus[l] as u128;
+            columns[remainder_column(h, l)][row] = element_from_u128(remainder % q);
+            remainder /= q;
+        }
+        let plaintext = rounded / product;
+        columns[ROUNDED_START + h][row] = element_from_u128(plaintext);
+        columns[PLAINTEXT_START + h][row] = element_from_u128(plaintext % t);
+    }
+
+    // the relations on the rows of their stage, their results elsewhere, as row_values
+    let column = |c: usize, k: usize| element_to_u64(columns[c][k]) as u128;
+    for k in 0..STATE_LENGTH {
+        let (first, last) = (k % STAGE_SLOTS == 0, k % STAGE_SLOTS == LAST_STAGE);
+        let values = &mut values[k];
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                let q = modulus[l] as u128;
+                let s = column(input_column(KEY_NETWORK, h, l), k);
+                let key =
+                    q * q - column(key_column(0, h, l), k) - column(key_column(1, h, l), k) * s;
+                let phase = column(ciphertext_column(0, h, l), k)
+                    + column(ciphertext_column(1, h, l), k) * s;
+                for (r, (relation, n)) in [(key, ERROR_NETWORK), (phase, PHASE_NETWORK)]
+                    .into_iter()
+                    .enumerate()
+                {
+                    values[relation_reduction(r, h, l)] = if first {
+                        relation
+                    } else {
+                        column(input_column(n, h, l), k)
+                    };
+                }
+                values[remainder_reduction(h, l)] = column(remainder_column(h, l), k);
+            }
+            let (mut w, mut radix) = (
+                column(output_column(PHASE_NETWORK, h, 0), k),
+                modulus[0] as u128,
+            );
+            for l in 1..COEFF_LEVEL {
+                let u = column(crt_digit_column(h, l), k);
+                w += radix * u;
+                radix *= modulus[l] as u128;
+                values[crt_reduction(h, l, 0)] = if last {
+                    w
+                } else {
+                    column(output_column(PHASE_NETWORK, h, l), k)
+                };
+                values[crt_reduction(h, l, 1)] = u;
+            }
+            values[plaintext_reduction(h)] = column(ROUNDED_START + h, k);
+        }
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        modulus,
+        wiring_columns(),
+        true,
+        DECRYPT_GROUP,
+    ))
+}
+
+pub fn signed_element(value: i64) -> BaseElement {
+    let magnitude = BaseElement::from(value.unsigned_abs());
+    if value < 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+// x^-1 mod m for coprime x and m.
+fn inverse_mod(x: u128, m: u128) -> u128 {
+    let (mut a, mut b, mut u, mut v) = (x as i128 % m as i128, m as i128, 1i128, 0i128);
+    while b != 0 {
+        let q = a / b;
+        (a, b) = (b, a - q * b);
+        (u, v) = (v, u - q * v);
+    }
+    u.rem_euclid(m as i128) as u128
+}
+
+pub fn get_decrypt_pub_inputs(trace: &NttTrace, plain_modulus: u64) -> PublicInputs {
+    let main = trace.main_segment();
+    let evaluations = |column: &dyn Fn(usize) -> usize| -> Vec<u64> {
+        (0..COEFF_DEGREE)
+            .map(|k| element_to_u64(main.get(column(k % 2), first_row(k / 2) * DECRYPT_GROUP)))
+            .collect()
+    };
+    let ciphertext = std::array::from_fn(|v| {
+        std::array::from_fn(|l| evaluations(&|h| ciphertext_column(v, h, l)))
+    });
+    let key =
+        std::array::from_fn(|v| std::array::from_fn(|l| evaluations(&|h| key_column(v, h, l))));
+    let plaintext = (0..COEFF_DEGREE)
+        .map(|k| {
+            main.get(
+                PLAINTEXT_START + k / BUTTERFLY_NUM,
+                last_row(k % BUTTERFLY_NUM) * DECRYPT_GROUP,
+            )
+        })
+        .collect();
+    PublicInputs {
+        result: vec![first_level(plaintext)],
+        evaluation_key: Some(EvaluationKey {
+            modulus: trace.modulus().to_vec(),
+            values: vec![key],
+        }),
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus().to_vec()),
+        plain_modulus: Some(plain_modulus),
+        decryption: Some(Decryption { ciphertext }),
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct DecryptAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    plaintext: Vec<BaseElement>,
+    key: EvaluationKey,
+    decryption: Decryption,
+    modulus: Vec<u64>,
+    plain_modulus: u64,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl DecryptAir {
+    // The values of the reductions of a row, as build_decrypt_trace computes them: a relation
+    // under the selector of its stage, its result elsewhere.
+    fn row_values<E: FieldElement + From<BaseElement>>(
+        &self,
+        row: &[E],
+        periodic_values: &[E],
+    ) -> [E; REDUCTION_NUM] {
+        let first = periodic_values[FIRST_SELECTOR];
+        let last = periodic_values[LAST_SELECTOR];
+        let q = |l: usize| E::from(element_from_u64(self.modulus[l]));
+        let select = |selector: E, relation: E, result: E| result + selector * (relation - result);
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        for n in 0..NETWORK_NUM {
+            for l in 0..COEFF_LEVEL {
+                let pair = butterfly_values(
+                    row,
+                    network_columns(n, l),
+                    periodic_values[SCALE_START + l],
+                    periodic_values[TWIDDLE_START + l],
+                    q(l),
+                );
+                for h in 0..HALF_NUM {
+                    values[network_reduction(n, h, l)] = pair[h];
+                }
+            }
+        }
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                let s = row[input_column(KEY_NETWORK, h, l)];
+                let key = q(l) * q(l) - row[key_column(0, h, l)] - row[key_column(1, h, l)] * s;
+                let phase = row[ciphertext_column(0, h, l)] + row[ciphertext_column(1, h, l)] * s;
+                values[relation_reduction(0, h, l)] =
+                    select(first, key, row[input_column(ERROR_NETWORK, h, l)]);
+                values[relation_reduction(1, h, l)] =
+                    select(first, phase, row[input_column(PHASE_NETWORK, h, l)]);
+                values[remainder_reduction(h, l)] = row[remainder_column(h, l)];
+            }
+            let mut w = row[output_column(PHASE_NETWORK, h, 0)];
+            let mut radix = q(0);
+            for l in 1..COEFF_LEVEL {
+                let u = row[crt_digit_column(h, l)];
+                w += radix * u;
+                radix *= q(l);
+                values[crt_reduction(h, l, 0)] =
+                    select(last, w, row[output_column(PHASE_NETWORK, h, l)]);
+                values[crt_reduction(h, l, 1)] = u;
+            }
+            values[plaintext_reduction(h)] = row[ROUNDED_START + h];
+        }
+        values
+    }
+}
+
+impl Air for DecryptAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // Only the current row is checked. The key relation is quadratic under the selector of the
+    // first stage, the largest value a reduction selects; the checks of the last stage sit under
+    // its selector, the ternary check of s is cubic and the bit checks are quadratic on every
+    // row, where the prover keeps those columns zero off the last stage.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("decryption proofs publish their moduli");
+        let plain_modulus = pub_inputs
+            .plain_modulus
+            .expect("decryption proofs publish the plain modulus");
+        let reductions =
+            decrypt_reductions(&modulus, plain_modulus).expect("decryption moduli were checked");
+        let stage = STAGE_SLOTS * reductions.group();
+        let selected = |degree| TransitionConstraintDegree::with_cycles(degree, vec![stage]);
+        let mut degrees = reductions.degrees(2, &[stage]);
+        for _ in 0..HALF_NUM {
+            degrees.push(TransitionConstraintDegree::new(3));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(2)));
+            degrees.extend((0..ERROR_BITS).map(|_| TransitionConstraintDegree::new(2)));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(1)));
+            degrees.push(selected(1));
+        }
+        let aux_degrees = wiring_degrees(AUX_WIDTH, reductions.group());
+        let num_assertions = 2 * VALUE_NUM * HALF_LEN + HALF_NUM;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let decryption = pub_inputs
+            .decryption
+            .expect("decryption proofs publish the ciphertext");
+        let key = pub_inputs
+            .evaluation_key
+            .expect("decryption proofs are verified against a public key");
+        let plaintext = pub_inputs
+            .result
+            .into_iter()
+            .next()
+            .and_then(|levels| levels.into_iter().next())
+            .expect("decryption proofs publish the plaintext");
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        DecryptAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            plaintext,
+            key,
+            decryption,
+            modulus,
+            plain_modulus,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let row = frame.current();
+        let values = self.row_values(row, periodic_values);
+        let (reduction, result) = result.split_at_mut(self.reductions.num_constraints());
+        self.reductions.evaluate(
+            frame,
+            &periodic_values[REDUCTION_PERIODIC_START..],
+            &values,
+            reduction,
+        );
+
+        let last = periodic_values[LAST_SELECTOR];
+        let q: Vec<E> = self
+            .modulus
+            .iter()
+            .map(|&q| E::from(element_from_u64(q)))
+            .collect();
+        let t = E::from(BaseElement::from(self.plain_modulus));
+        let modulus_product: u128 = self.modulus.iter().map(|&q| q as u128).product();
+        let product = E::from(element_from_u128(modulus_product));
+        let half = E::from(element_from_u128(modulus_product / 2));
+        let two = E::from(BaseElement::from(2u64));
+        let offset = E::from(BaseElement::from(ERROR_OFFSET));
+        let mut i = 0;
+        for h in 0..HALF_NUM {
+            // s in {-1, 0, 1}, residue s + q * s(s - 1)/2 at both levels
+            let s = row[SECRET_START + h];
+            result[i] = s * (s - E::ONE) * (s + E::ONE);
+            i += 1;
+            for l in 0..COEFF_LEVEL {
+                result[i] = last
+                    * (row[output_column(KEY_NETWORK, h, l)] - s - q[l] * s * (s - E::ONE) / two);
+                i += 1;
+            }
+
+            // e = E - 32 with E in 0..64, residue e + q * (1 - bit 5) at both levels
+            let mut bits = E::ZERO;
+            for b in (0..ERROR_BITS).rev() {
+                let bit = row[error_bit_column(h, b)];
+                result[i] = bit * (bit - E::ONE);
+                bits = bits * two + bit;
+                i += 1;
+            }
+            let sign = row[error_bit_column(h, ERROR_BITS - 1)];
+            for l in 0..COEFF_LEVEL {
+                result[i] = last
+                    * (row[output_column(ERROR_NETWORK, h, l)] - bits + offset
+                        - q[l] * (E::ONE - sign));
+                i += 1;
+            }
+
+            // w = w0 + q0 * u1 + q0 * q1 * u2 + .. rounded to P with the remainder
+            // r = r0 + q0 * r1 + .., both of digits the reductions range check
+            let mut w = row[output_column(PHASE_NETWORK, h, 0)];
+            let mut remainder = row[remainder_column(h, 0)];
+            let mut radix = q[0];
+            for l in 1..COEFF_LEVEL {
+                w += radix * row[crt_digit_column(h, l)];
+                remainder += radix * row[remainder_column(h, l)];
+                radix *= q[l];
+            }
+            result[i] = last * (t * w + half - row[ROUNDED_START + h] * product - remainder);
+            i += 1;
+        }
+        debug_assert_eq!(i, result.len());
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The public key and the ciphertext on the first rows of the groups of the first stage, the
+    // plaintext on those of the last.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let group = self.reductions.group();
+        let mut assertions = Vec::new();
+        let halves = |values: &[u64], h: usize| -> Vec<BaseElement> {
+            values
+                .iter()
+                .skip(h)
+                .step_by(2)
+                .map(|&value| element_from_u64(value))
+                .collect()
+        };
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                for v in 0..VALUE_NUM {
+                    assertions.push(Assertion::sequence(
+                        key_column(v, h, l),
+                        0,
+                        STAGE_SLOTS * group,
+                        halves(&self.key.values[0][v][l], h),
+                    ));
+                    assertions.push(Assertion::sequence(
+                        ciphertext_column(v, h, l),
+                        0,
+                        STAGE_SLOTS * group,
+                        halves(&self.decryption.ciphertext[v][l], h),
+                    ));
+                }
+            }
+            assertions.push(Assertion::sequence(
+                PLAINTEXT_START + h,
+                LAST_STAGE * group,
+                STAGE_SLOTS * group,
+                self.plaintext[h * BUTTERFLY_NUM..(h + 1) * BUTTERFLY_NUM].to_vec(),
+            ));
+        }
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut first = vec![BaseElement::ZERO; STAGE_SLOTS];
+        let mut last = vec![BaseElement::ZERO; STAGE_SLOTS];
+        first[0] = BaseElement::ONE;
+        last[LAST_STAGE] = BaseElement::ONE;
+        let mut columns = intt_periodic_columns(&self.modulus, self.reductions.group());
+        columns.push(self.reductions.stretch(&first));
+        columns.push(self.reductions.stretch(&last));
+        columns.extend(self.reductions.periodic_columns());
+        columns
+    }
+}
@@ -0,0 +1,746 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Partial decryption share of a threshold BFV or CKKS decryption, where every party holds an
+// additive share s of the secret key and publishes d = c1 * s + n for a ciphertext (c0, c1) and
+// a smudging noise n that hides its share; the parties' shares and c0 add up to the phase. The
+// share s is committed to by the matching public key share (b, a) = (-(a * s + e), a),
+// distributed like an evaluation key with a single digit as for DecryptAir, and the proof shows
+// that for a ternary s, an error e with |e| <= 32 and a noise n with |n| <= 2^(SMUDGING_BITS-1)
+//
+//     b + a * s + e = 0 mod q,  d = c1 * s + n mod q
+//
+// for every RNS level. The ciphertext, the public key, the secret key share and the result are
+// in NTT form, so both products are pointwise.
+//
+// The trace holds three networks of InttAir per level, for the evaluations of s, of e =
+// -(b + a * s) and of n, on the circuit rows and row groups of InttAir. Their circuit rows 16b
+// hold evaluations 2b and 2b + 1, where both relations are checked against the public key, the
+// ciphertext and the share; their last-stage circuit rows 16b + 11 hold coefficients b and
+// b + N/2, where s is checked to be ternary, e to be the six-bit value E - 32 and n to be
+// N - 2^(SMUDGING_BITS-1), all equal at every level. Besides the butterflies, the rows of a group
+// reduce q^2 - b - a * s to the error and c1 * s + n to the share on the first stage, and
+// N + q - 2^(SMUDGING_BITS-1) to the noise on the last, against the public moduli, and reduce N
+// to itself modulo 2^SMUDGING_BITS, which range checks it. A reduction of a relation reduces its
+// result to itself on the other circuit rows, where that result is a residue too.
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+
+use crate::air::{
+    element_from_u128, element_from_u64, element_to_u64, level_column, load_data_file, BaseElement,
+    InputArg, PublicInputs, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::decrypt_air::{
+    first_row, last_row, signed, signed_element, Decryption, ERROR_BITS, ERROR_OFFSET,
+    PUBLIC_KEY_DIGIT_NUM,
+};
+use crate::error::StarkHeError;
+use crate::intt_air::{
+    butterfly_values, fill_inverse_network, intt_periodic_columns, INTT_PERIODIC_NUM, LAST_STAGE,
+    SCALE_START, TWIDDLE_START,
+};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::{
+    check_ntt_modulus, evaluate_wiring, forward_transform, wiring_assertions, wiring_degrees,
+    NttTrace, BUTTERFLY_NUM, NTT_STAGE_NUM, NTT_TRACE_LENGTH, STAGE_SLOTS,
+};
+use crate::reduction::{reduction_group, reduction_width, Reductions};
+
+// Networks of the secret key share, the key error and the smudging noise, one per level each,
+// + Reduction
+// Network columns: A.. B.. X.. Y.., network major, level minor
+// Rows 16b, half h holding evaluation 2b + h: PB.. PA.. C0.. C1.. D.., half major
+// Rows 16b + 11, half h holding coefficient b + hN/2: S.. E bits.. N..
+const KEY_NETWORK: usize = 0;
+const ERROR_NETWORK: usize = 1;
+const NOISE_NETWORK: usize = 2;
+const NETWORK_NUM: usize = 3;
+const POLY_NUM: usize = NETWORK_NUM * COEFF_LEVEL;
+const HALF_NUM: usize = 2;
+const HALF_LEN: usize = HALF_NUM * COEFF_LEVEL;
+// the smudging noise is N - 2^(SMUDGING_BITS-1) with N below 2^SMUDGING_BITS, which every
+// modulus must exceed
+#[cfg(not(feature = "micro"))]
+pub const SMUDGING_BITS: usize = 32;
+#[cfg(feature = "micro")]
+pub const SMUDGING_BITS: usize = 16;
+pub const SMUDGING_OFFSET: u64 = 1 << (SMUDGING_BITS - 1);
+
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + POLY_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + POLY_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + POLY_NUM;
+const KEY_B_START: usize = OUTPUT_Y_START + POLY_NUM;
+const KEY_A_START: usize = KEY_B_START + HALF_LEN;
+const CIPHERTEXT_START: usize = KEY_A_START + HALF_LEN;
+const SHARE_START: usize = CIPHERTEXT_START + VALUE_NUM * HALF_LEN;
+const SECRET_START: usize = SHARE_START + HALF_LEN;
+const ERROR_BIT_START: usize = SECRET_START + HALF_NUM;
+const NOISE_START: usize = ERROR_BIT_START + HALF_NUM * ERROR_BITS;
+const NOISE_END: usize = NOISE_START + HALF_NUM;
+// butterflies, key relations, shares, noise residues, noises
+const REDUCTION_NUM: usize = 2 * POLY_NUM + 2 * HALF_LEN + HALF_LEN + HALF_NUM;
+// the butterflies reach 2 * q^2, so their quotients take a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per reduction, then padding
+pub const DECRYPT_SHARE_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = NOISE_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = NTT_TRACE_LENGTH;
+pub const DECRYPT_SHARE_AUX_WIDTH: usize = POLY_NUM;
+const AUX_WIDTH: usize = DECRYPT_SHARE_AUX_WIDTH;
+pub const DECRYPT_SHARE_RESULT_NUM: usize = 1;
+
+// Periodic columns after those of InttAir: selectors of the first and the last stage, then
+// those of the reductions.
+const FIRST_SELECTOR: usize = INTT_PERIODIC_NUM;
+const LAST_SELECTOR: usize = FIRST_SELECTOR + 1;
+const REDUCTION_PERIODIC_START: usize = LAST_SELECTOR + 1;
+
+// Trace layout constants of DecryptShareAir in canonical order.
+pub fn decrypt_share_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("error_bits", ERROR_BITS),
+        ("smudging_bits", SMUDGING_BITS),
+        ("group", DECRYPT_SHARE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// NTT-friendly moduli above 2^SMUDGING_BITS, so that the noise has a unique residue.
+pub fn check_share_modulus(modulus: &[u64]) -> Result<(), String> {
+    check_ntt_modulus(modulus)?;
+    if let Some(q) = modulus.iter().find(|&&q| q <= 1 << SMUDGING_BITS) {
+        return Err(format!(
+            "modulus {} must exceed the 2^{} bound of the smudging noise",
+            q, SMUDGING_BITS
+        ));
+    }
+    Ok(())
+}
+
+// The moduli must be valid and the ciphertext and the share in `result` reduced modulo them.
+pub fn check_share(
+    modulus: &[u64],
+    decryption: &Decryption,
+    result: &[Vec<Vec<u64>>],
+) -> Result<(), String> {
+    check_share_modulus(modulus)?;
+    let mut polys: Vec<(String, &Vec<u64>, usize)> = Vec::new();
+    for (v, component) in decryption.ciphertext.iter().enumerate() {
+        for (l, level) in component.iter().enumerate() {
+            polys.push((format!("ciphertext component {}", v), level, l));
+        }
+    }
+    match result {
+        [share] => {
+            for (l, level) in share.iter().enumerate() {
+                polys.push((String::from("share"), level, l));
+            }
+        }
+        _ => return Err(format!("share has {} components, expected 1", result.len())),
+    }
+    for (name, level, l) in polys {
+        if level.len() != COEFF_DEGREE || level.iter().any(|&c| c >= modulus[l]) {
+            return Err(format!(
+                "{} level {} is not {} residues modulo {}",
+                name, l, COEFF_DEGREE, modulus[l]
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Same format as CiphertextData, with the secret key share in NTT form and the smudging noise
+// in coefficient form.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DecryptShareData {
+    pub modulus: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+    pub secret_key: [Vec<u64>; COEFF_LEVEL],
+    pub noise: Vec<i64>,
+}
+
+fn network_columns(n: usize, l: usize) -> [usize; 4] {
+    [INPUT_A_START, INPUT_B_START, OUTPUT_X_START, OUTPUT_Y_START]
+        .map(|start| level_column(start, n, l))
+}
+
+// Input or output column of half `h` of a network: A, B on rows 16b, X, Y on rows 16b + 11.
+fn input_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([INPUT_A_START, INPUT_B_START][h], n, l)
+}
+
+fn output_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([OUTPUT_X_START, OUTPUT_Y_START][h], n, l)
+}
+
+fn key_column(v: usize, h: usize, l: usize) -> usize {
+    level_column([KEY_B_START, KEY_A_START][v], h, l)
+}
+
+fn ciphertext_column(v: usize, h: usize, l: usize) -> usize {
+    level_column(CIPHERTEXT_START + v * HALF_LEN, h, l)
+}
+
+fn share_column(h: usize, l: usize) -> usize {
+    level_column(SHARE_START, h, l)
+}
+
+fn error_bit_column(h: usize, i: usize) -> usize {
+    ERROR_BIT_START + h * ERROR_BITS + i
+}
+
+// Reductions of a row: the values reduced to X and Y (half h) of every network, ...
+fn network_reduction(n: usize, h: usize, l: usize) -> usize {
+    HALF_NUM * (n * COEFF_LEVEL + l) + h
+}
+
+// .. the key relation (r = 0), reduced to the input of the error network, and the share (r = 1),
+// ..
+fn relation_reduction(r: usize, h: usize, l: usize) -> usize {
+    2 * POLY_NUM + r * HALF_LEN + level_column(0, h, l)
+}
+
+// .. the offset noise, reduced to the output of the noise network, ..
+fn residue_reduction(h: usize, l: usize) -> usize {
+    2 * POLY_NUM + 2 * HALF_LEN + level_column(0, h, l)
+}
+
+// .. and the noise N, reduced to itself.
+fn noise_reduction(h: usize) -> usize {
+    REDUCTION_NUM - HALF_NUM + h
+}
+
+// The reductions of a row by the public moduli `modulus` and 2^SMUDGING_BITS, every value bounded
+// as build_decrypt_share_trace computes it.
+pub fn decrypt_share_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_share_modulus(modulus)?;
+    let mut results = vec![0; REDUCTION_NUM];
+    let mut moduli = vec![0; REDUCTION_NUM];
+    let mut bounds = vec![0; REDUCTION_NUM];
+    let mut set = |j: usize, result: usize, m: u64, bound: u128| {
+        results[j] = result;
+        moduli[j] = m;
+        bounds[j] = bound;
+    };
+    let noise_bound = 1u128 << SMUDGING_BITS;
+    for h in 0..HALF_NUM {
+        for l in 0..COEFF_LEVEL {
+            let q = modulus[l] as u128;
+            for n in 0..NETWORK_NUM {
+                set(
+                    network_reduction(n, h, l),
+                    output_column(n, h, l),
+                    modulus[l],
+                    2 * q * q,
+                );
+            }
+            let relations = [input_column(ERROR_NETWORK, h, l), share_column(h, l)];
+            for (r, result) in relations.into_iter().enumerate() {
+                set(relation_reduction(r, h, l), result, modulus[l], q * q);
+            }
+            set(
+                residue_reduction(h, l),
+                output_column(NOISE_NETWORK, h, l),
+                modulus[l],
+                q + noise_bound,
+            );
+        }
+        set(
+            noise_reduction(h),
+            NOISE_START + h,
+            1 << SMUDGING_BITS,
+            noise_bound,
+        );
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (0..NOISE_END).collect(),
+        NOISE_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Wiring columns of DecryptShareAir, in polynomial order.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    let mut wiring = Vec::with_capacity(POLY_NUM);
+    for n in 0..NETWORK_NUM {
+        for l in 0..COEFF_LEVEL {
+            wiring.push(network_columns(n, l));
+        }
+    }
+    wiring
+}
+
+pub fn build_decrypt_share_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let data: DecryptShareData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_ref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from(
+            "a decryption share needs --evaluation-key holding the public key share",
+        ))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    let reductions = decrypt_share_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let modulus = data.modulus;
+    assert!(
+        key.modulus == modulus && key.values.len() == PUBLIC_KEY_DIGIT_NUM,
+        "the public key share must be a single digit over the moduli of the ciphertext"
+    );
+    let offset = SMUDGING_OFFSET as i64;
+    assert!(
+        data.noise.len() == COEFF_DEGREE
+            && data.noise.iter().all(|c| (-offset..offset).contains(c)),
+        "smudging noise must be {} coefficients in {}..{}",
+        COEFF_DEGREE,
+        -offset,
+        offset
+    );
+    let [key_b, key_a] = &key.values[0];
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; NOISE_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    let mut secret = vec![[0; COEFF_LEVEL]; COEFF_DEGREE];
+    let mut error = vec![[0; COEFF_LEVEL]; COEFF_DEGREE];
+    for l in 0..COEFF_LEVEL {
+        let q = modulus[l];
+        let s = &data.secret_key[l];
+        assert!(
+            [
+                s,
+                &key_b[l],
+                &key_a[l],
+                &data.values[0][l],
+                &data.values[1][l]
+            ]
+            .iter()
+            .all(|values| values.len() == COEFF_DEGREE && values.iter().all(|&c| c < q)),
+            "keys and ciphertext must be {} residues modulo {}",
+            COEFF_DEGREE,
+            q
+        );
+        let noise: Vec<u64> = data
+            .noise
+            .iter()
+            .map(|&c| c.rem_euclid(q as i64) as u64)
+            .collect();
+        let noise = forward_transform(noise, q);
+        let mut e = vec![0; COEFF_DEGREE];
+        for k in 0..COEFF_DEGREE {
+            let (row, h) = (first_row(k / 2), k % 2);
+            let key_sum = key_b[l][k] as u128 + key_a[l][k] as u128 * s[k] as u128;
+            e[k] = (q - (key_sum % q as u128) as u64) % q;
+            let share_sum = data.values[1][l][k] as u128 * s[k] as u128 + noise[k] as u128;
+
+            columns[key_column(0, h, l)][row] = element_from_u64(key_b[l][k]);
+            columns[key_column(1, h, l)][row] = element_from_u64(key_a[l][k]);
+            for c in 0..VALUE_NUM {
+                columns[ciphertext_column(c, h, l)][row] = element_from_u64(data.values[c][l][k]);
+            }
+            columns[share_column(h, l)][row] = element_from_u128(share_sum % q as u128);
+        }
+        let mut coefficients = Vec::with_capacity(NETWORK_NUM);
+        for (n, a) in [s.clone(), e, noise].into_iter().enumerate() {
+            let (a, network_values) =
+                fill_inverse_network(&mut columns, network_columns(n, l), a, q);
+            for (row, pair) in network_values.into_iter().enumerate() {
+                for h in 0..HALF_NUM {
+                    values[row][network_reduction(n, h, l)] = pair[h];
+                }
+            }
+            coefficients.push(a);
+        }
+        for k in 0..COEFF_DEGREE {
+            secret[k][l] = signed(coefficients[0][k], q);
+            error[k][l] = signed(coefficients[1][k], q);
+        }
+    }
+
+    for k in 0..COEFF_DEGREE {
+        let (row, h) = (last_row(k % BUTTERFLY_NUM), k / BUTTERFLY_NUM);
+        let s = secret[k][0];
+        assert!(
+            secret[k].iter().all(|&c| c == s) && s.abs() <= 1,
+            "secret key share coefficient {} is not ternary",
+            k
+        );
+        let e = error[k][0];
+        let error_offset = ERROR_OFFSET as i64;
+        assert!(
+            error[k].iter().all(|&c| c == e) && (-error_offset..error_offset).contains(&e),
+            "public key error coefficient {} is out of range, the keys do not match",
+            k
+        );
+        columns[SECRET_START + h][row] = signed_element(s);
+        let bits = (e + error_offset) as u64;
+        for i in 0..ERROR_BITS {
+            columns[error_bit_column(h, i)][row] = BaseElement::from((bits >> i) & 1);
+        }
+        columns[NOISE_START + h][row] = BaseElement::from((data.noise[k] + offset) as u64);
+    }
+
+    // the relations on the rows of their stage, their results elsewhere, as row_values
+    let column = |c: usize, k: usize| element_to_u64(columns[c][k]) as u128;
+    for k in 0..STATE_LENGTH {
+        let (first, last) = (k % STAGE_SLOTS == 0, k % STAGE_SLOTS == LAST_STAGE);
+        let values = &mut values[k];
+        for h in 0..HALF_NUM {
+            let noise = column(NOISE_START + h, k);
+            for l in 0..COEFF_LEVEL {
+                let q = modulus[l] as u128;
+                let s = column(input_column(KEY_NETWORK, h, l), k);
+                let key =
+                    q * q - column(key_column(0, h, l), k) - column(key_column(1, h, l), k) * s;
+                let share = column(ciphertext_column(1, h, l), k) * s
+                    + column(input_column(NOISE_NETWORK, h, l), k);
+                let relations = [
+                    (key, input_column(ERROR_NETWORK, h, l)),
+                    (share, share_column(h, l)),
+                ];
+                for (r, (relation, result)) in relations.into_iter().enumerate() {
+                    values[relation_reduction(r, h, l)] =
+                        if first { relation } else { column(result, k) };
+                }
+                values[residue_reduction(h, l)] = if last {
+                    noise + q - SMUDGING_OFFSET as u128
+                } else {
+                    column(output_column(NOISE_NETWORK, h, l), k)
+                };
+            }
+            values[noise_reduction(h)] = noise;
+        }
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        modulus,
+        wiring_columns(),
+        true,
+        DECRYPT_SHARE_GROUP,
+    ))
+}
+
+pub fn get_decrypt_share_pub_inputs(trace: &NttTrace) -> PublicInputs {
+    let main = trace.main_segment();
+    let evaluations = |column: &dyn Fn(usize) -> usize| -> Vec<u64> {
+        (0..COEFF_DEGREE)
+            .map(|k| {
+                element_to_u64(main.get(column(k % 2), first_row(k / 2) * DECRYPT_SHARE_GROUP))
+            })
+            .collect()
+    };
+    let ciphertext = std::array::from_fn(|v| {
+        std::array::from_fn(|l| evaluations(&|h| ciphertext_column(v, h, l)))
+    });
+    let key =
+        std::array::from_fn(|v| std::array::from_fn(|l| evaluations(&|h| key_column(v, h, l))));
+    let share = (0..COEFF_LEVEL)
+        .map(|l| {
+            (0..COEFF_DEGREE)
+                .map(|k| {
+                    main.get(
+                        share_column(k % 2, l),
+                        first_row(k / 2) * DECRYPT_SHARE_GROUP,
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    PublicInputs {
+        result: vec![share],
+        evaluation_key: Some(EvaluationKey {
+            modulus: trace.modulus().to_vec(),
+            values: vec![key],
+        }),
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus().to_vec()),
+        plain_modulus: None,
+        decryption: Some(Decryption { ciphertext }),
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct DecryptShareAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    share: Vec<Vec<BaseElement>>,
+    key: EvaluationKey,
+    decryption: Decryption,
+    modulus: Vec<u64>,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl DecryptShareAir {
+    // The values of the reductions of a row, as build_decrypt_share_trace computes them: a
+    // relation under the selector of its stage, its result elsewhere.
+    fn row_values<E: FieldElement + From<BaseElement>>(
+        &self,
+        row: &[E],
+        periodic_values: &[E],
+    ) -> [E; REDUCTION_NUM] {
+        let first = periodic_values[FIRST_SELECTOR];
+        let last = periodic_values[LAST_SELECTOR];
+        let q = |l: usize| E::from(element_from_u64(self.modulus[l]));
+        let select = |selector: E, relation: E, result: E| result + selector * (relation - result);
+        let offset = E::from(BaseElement::from(SMUDGING_OFFSET));
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        for n in 0..NETWORK_NUM {
+            for l in 0..COEFF_LEVEL {
+                let pair = butterfly_values(
+                    row,
+                    network_columns(n, l),
+                    periodic_values[SCALE_START + l],
+                    periodic_values[TWIDDLE_START + l],
+                    q(l),
+                );
+                for h in 0..HALF_NUM {
+                    values[network_reduction(n, h, l)] = pair[h];
+                }
+            }
+        }
+        for h in 0..HALF_NUM {
+            let noise = row[NOISE_START + h];
+            for l in 0..COEFF_LEVEL {
+                let s = row[input_column(KEY_NETWORK, h, l)];
+                let key = q(l) * q(l) - row[key_column(0, h, l)] - row[key_column(1, h, l)] * s;
+                let share =
+                    row[ciphertext_column(1, h, l)] * s + row[input_column(NOISE_NETWORK, h, l)];
+                values[relation_reduction(0, h, l)] =
+                    select(first, key, row[input_column(ERROR_NETWORK, h, l)]);
+                values[relation_reduction(1, h, l)] = select(first, share, row[share_column(h, l)]);
+                values[residue_reduction(h, l)] = select(
+                    last,
+                    noise + q(l) - offset,
+                    row[output_column(NOISE_NETWORK, h, l)],
+                );
+            }
+            values[noise_reduction(h)] = noise;
+        }
+        values
+    }
+}
+
+impl Air for DecryptShareAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // Only the current row is checked, as in DecryptAir: the relations reduced on the first stage
+    // are quadratic under its selector, the checks of the last stage sit under its selector, the
+    // ternary check of s is cubic and the bit checks are quadratic on every row.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("decryption share proofs publish their moduli");
+        let reductions =
+            decrypt_share_reductions(&modulus).expect("decryption share moduli were checked");
+        let stage = STAGE_SLOTS * reductions.group();
+        let selected = |degree| TransitionConstraintDegree::with_cycles(degree, vec![stage]);
+        let mut degrees = reductions.degrees(2, &[stage]);
+        for _ in 0..HALF_NUM {
+            degrees.push(TransitionConstraintDegree::new(3));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(2)));
+            degrees.extend((0..ERROR_BITS).map(|_| TransitionConstraintDegree::new(2)));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(1)));
+        }
+        let aux_degrees = wiring_degrees(AUX_WIDTH, reductions.group());
+        let num_assertions = (2 + VALUE_NUM + 1) * HALF_LEN;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let decryption = pub_inputs
+            .decryption
+            .expect("decryption share proofs publish the ciphertext");
+        let key = pub_inputs
+            .evaluation_key
+            .expect("decryption share proofs are verified against a public key share");
+        let share = pub_inputs
+            .result
+            .into_iter()
+            .next()
+            .expect("decryption share proofs publish the share");
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        DecryptShareAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            share,
+            key,
+            decryption,
+            modulus,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let row = frame.current();
+        let values = self.row_values(row, periodic_values);
+        let (reduction, result) = result.split_at_mut(self.reductions.num_constraints());
+        self.reductions.evaluate(
+            frame,
+            &periodic_values[REDUCTION_PERIODIC_START..],
+            &values,
+            reduction,
+        );
+
+        let last = periodic_values[LAST_SELECTOR];
+        let q: Vec<E> = self
+            .modulus
+            .iter()
+            .map(|&q| E::from(element_from_u64(q)))
+            .collect();
+        let two = E::from(BaseElement::from(2u64));
+        let offset = E::from(BaseElement::from(ERROR_OFFSET));
+        let mut i = 0;
+        for h in 0..HALF_NUM {
+            // s in {-1, 0, 1}, residue s + q * s(s - 1)/2 at every level
+            let s = row[SECRET_START + h];
+            result[i] = s * (s - E::ONE) * (s + E::ONE);
+            i += 1;
+            for l in 0..COEFF_LEVEL {
+                result[i] = last
+                    * (row[output_column(KEY_NETWORK, h, l)] - s - q[l] * s * (s - E::ONE) / two);
+                i += 1;
+            }
+
+            // e = E - 32 with E in 0..64, residue e + q * (1 - bit 5) at every level
+            let mut bits = E::ZERO;
+            for b in (0..ERROR_BITS).rev() {
+                let bit = row[error_bit_column(h, b)];
+                result[i] = bit * (bit - E::ONE);
+                bits = bits * two + bit;
+                i += 1;
+            }
+            let sign = row[error_bit_column(h, ERROR_BITS - 1)];
+            for l in 0..COEFF_LEVEL {
+                result[i] = last
+                    * (row[output_column(ERROR_NETWORK, h, l)] - bits + offset
+                        - q[l] * (E::ONE - sign));
+                i += 1;
+            }
+        }
+        debug_assert_eq!(i, result.len());
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The public key share, the ciphertext and the decryption share on the first rows of the
+    // groups of the first stage.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let stride = STAGE_SLOTS * self.reductions.group();
+        let mut assertions = Vec::new();
+        let halves = |values: &[u64], h: usize| -> Vec<BaseElement> {
+            values
+                .iter()
+                .skip(h)
+                .step_by(2)
+                .map(|&value| element_from_u64(value))
+                .collect()
+        };
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                for v in 0..VALUE_NUM {
+                    assertions.push(Assertion::sequence(
+                        key_column(v, h, l),
+                        0,
+                        stride,
+                        halves(&self.key.values[0][v][l], h),
+                    ));
+                    assertions.push(Assertion::sequence(
+                        ciphertext_column(v, h, l),
+                        0,
+                        stride,
+                        halves(&self.decryption.ciphertext[v][l], h),
+                    ));
+                }
+                assertions.push(Assertion::sequence(
+                    share_column(h, l),
+                    0,
+                    stride,
+                    self.share[l].iter().skip(h).step_by(2).copied().collect(),
+                ));
+            }
+        }
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut first = vec![BaseElement::ZERO; STAGE_SLOTS];
+        let mut last = vec![BaseElement::ZERO; STAGE_SLOTS];
+        first[0] = BaseElement::ONE;
+        last[LAST_STAGE] = BaseElement::ONE;
+        let mut columns = intt_periodic_columns(&self.modulus, self.reductions.group());
+        columns.push(self.reductions.stretch(&first));
+        columns.push(self.reductions.stretch(&last));
+        columns.extend(self.reductions.periodic_columns());
+        columns
+    }
+}
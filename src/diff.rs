@@ -0,0 +1,406 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Structural comparison of two proof files. Sections are compared in the order in which the
+// prover produces them (header, parameters, public inputs, commitments), so the first reported
+// section is where two runs of a supposedly identical computation started to diverge.
+
+use std::fmt;
+
+use winter_air::{proof::StarkProof, HashFunction};
+use winter_prover::crypto::{
+    hashers::{Blake3_192, Blake3_256, Sha3_256},
+    Digest, Hasher,
+};
+
+use crate::air::{BaseElement, Data, PublicInputs};
+use crate::canonical::to_hex;
+use crate::reproducibility::ReproducibilityManifest;
+use crate::verifying::parse_data;
+
+pub struct Difference {
+    pub section: &'static str,
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}:\n  < {}\n  > {}",
+            self.section, self.field, self.left, self.right
+        )
+    }
+}
+
+struct Differences(Vec<Difference>);
+
+impl Differences {
+    fn compare<T: PartialEq + fmt::Debug>(
+        &mut self,
+        section: &'static str,
+        field: &str,
+        a: T,
+        b: T,
+    ) {
+        if a != b {
+            self.compare_text(section, field, format!("{:?}", a), format!("{:?}", b));
+        }
+    }
+
+    fn compare_text(&mut self, section: &'static str, field: &str, a: String, b: String) {
+        if a != b {
+            self.0.push(Difference {
+                section,
+                field: field.to_string(),
+                left: a,
+                right: b,
+            });
+        }
+    }
+}
+
+// Proofs from different builds are compared as well: the reproducibility manifests are only
+// diffed, not checked against this build.
+pub fn diff_proofs(mut a: Data, mut b: Data) -> Result<Vec<Difference>, String> {
+    let build_a = build_info(a.reproducibility.take());
+    let build_b = build_info(b.reproducibility.take());
+    let (op_a, inputs_a, proof_a) = parse_data(a)?;
+    let (op_b, inputs_b, proof_b) = parse_data(b)?;
+    let mut diffs = Differences(Vec::new());
+
+    diffs.compare_text(
+        "header",
+        "circuit",
+        op_a.name().to_string(),
+        op_b.name().to_string(),
+    );
+    diffs.compare_text("header", "crate version", build_a.0, build_b.0);
+    diffs.compare_text("header", "circuit semver", build_a.1, build_b.1);
+    diffs.compare_text("header", "circuit version", build_a.2, build_b.2);
+    compare_header(&mut diffs, &proof_a, &proof_b);
+    compare_parameters(&mut diffs, &proof_a, &proof_b);
+    compare_public_inputs(&mut diffs, &inputs_a, &inputs_b);
+    compare_commitments(&mut diffs, &proof_a, &proof_b)?;
+    Ok(diffs.0)
+}
+
+// Crate version, circuit semantic version and circuit version from the reproducibility
+// manifest, when the proof has one.
+fn build_info(manifest: Option<ReproducibilityManifest>) -> (String, String, String) {
+    let unknown = || String::from("unknown");
+    match manifest {
+        Some(manifest) => (
+            manifest.crate_version,
+            if manifest.circuit_semver.is_empty() {
+                unknown()
+            } else {
+                manifest.circuit_semver
+            },
+            manifest.circuit_version,
+        ),
+        None => (unknown(), unknown(), unknown()),
+    }
+}
+
+fn compare_header(diffs: &mut Differences, a: &StarkProof, b: &StarkProof) {
+    const SECTION: &str = "header";
+    diffs.compare(
+        SECTION,
+        "trace width",
+        a.trace_layout().main_trace_width(),
+        b.trace_layout().main_trace_width(),
+    );
+    diffs.compare(SECTION, "trace length", a.trace_length(), b.trace_length());
+    diffs.compare_text(
+        SECTION,
+        "trace meta",
+        to_hex(a.get_trace_info().meta()),
+        to_hex(b.get_trace_info().meta()),
+    );
+    diffs.compare_text(
+        SECTION,
+        "field modulus",
+        to_hex(a.context.field_modulus_bytes()),
+        to_hex(b.context.field_modulus_bytes()),
+    );
+}
+
+fn compare_parameters(diffs: &mut Differences, a: &StarkProof, b: &StarkProof) {
+    const SECTION: &str = "parameters";
+    let (a, b) = (a.options(), b.options());
+    diffs.compare(SECTION, "num queries", a.num_queries(), b.num_queries());
+    diffs.compare(
+        SECTION,
+        "blowup factor",
+        a.blowup_factor(),
+        b.blowup_factor(),
+    );
+    diffs.compare(
+        SECTION,
+        "grinding factor",
+        a.grinding_factor(),
+        b.grinding_factor(),
+    );
+    diffs.compare(SECTION, "hash function", a.hash_fn(), b.hash_fn());
+    diffs.compare(
+        SECTION,
+        "field extension",
+        a.field_extension(),
+        b.field_extension(),
+    );
+    let (fri_a, fri_b) = (a.to_fri_options(), b.to_fri_options());
+    diffs.compare(
+        SECTION,
+        "FRI folding factor",
+        fri_a.folding_factor(),
+        fri_b.folding_factor(),
+    );
+    diffs.compare(
+        SECTION,
+        "FRI max remainder size",
+        fri_a.max_remainder_size(),
+        fri_b.max_remainder_size(),
+    );
+}
+
+fn compare_public_inputs(diffs: &mut Differences, a: &PublicInputs, b: &PublicInputs) {
+    const SECTION: &str = "public inputs";
+    diffs.compare_text(
+        SECTION,
+        "commitment",
+        to_hex(&a.commitment()),
+        to_hex(&b.commitment()),
+    );
+    diffs.compare(SECTION, "result components", a.result.len(), b.result.len());
+    diffs.compare(SECTION, "rotation", a.rotation, b.rotation);
+    diffs.compare(SECTION, "scale", a.scale, b.scale);
+    diffs.compare(SECTION, "modulus", a.modulus.clone(), b.modulus.clone());
+    diffs.compare(SECTION, "plain modulus", a.plain_modulus, b.plain_modulus);
+    diffs.compare(SECTION, "scalar", a.scalar, b.scalar);
+    diffs.compare(SECTION, "weights", a.weights.clone(), b.weights.clone());
+    diffs.compare(
+        SECTION,
+        "result digest",
+        a.result_digest.map(|digest| to_hex(&digest)),
+        b.result_digest.map(|digest| to_hex(&digest)),
+    );
+    diffs.compare(SECTION, "window", a.window.clone(), b.window.clone());
+    diffs.compare(
+        SECTION,
+        "encoding scale",
+        a.slots.as_ref().map(|slots| slots.scale),
+        b.slots.as_ref().map(|slots| slots.scale),
+    );
+    for (v, (levels_a, levels_b)) in a.result.iter().zip(b.result.iter()).enumerate() {
+        for (l, (coeffs_a, coeffs_b)) in levels_a.iter().zip(levels_b.iter()).enumerate() {
+            let field = format!("result component {} level {}", v, l);
+            if coeffs_a.len() != coeffs_b.len() {
+                diffs.compare(
+                    SECTION,
+                    &format!("{} length", field),
+                    coeffs_a.len(),
+                    coeffs_b.len(),
+                );
+                continue;
+            }
+            let differing: Vec<usize> = (0..coeffs_a.len())
+                .filter(|&k| coeffs_a[k] != coeffs_b[k])
+                .collect();
+            if let Some(&k) = differing.first() {
+                diffs.0.push(Difference {
+                    section: SECTION,
+                    field: format!(
+                        "{}: {} coefficients differ, first at index {}",
+                        field,
+                        differing.len(),
+                        k
+                    ),
+                    left: coeffs_a[k].to_string(),
+                    right: coeffs_b[k].to_string(),
+                });
+            }
+        }
+    }
+}
+
+type ParsedCommitments = (Vec<String>, String, Vec<String>);
+
+fn parse_commitments(proof: &StarkProof) -> Result<ParsedCommitments, String> {
+    let num_trace_segments = proof.trace_layout().num_segments();
+    let num_fri_layers = proof
+        .options()
+        .to_fri_options()
+        .num_fri_layers(proof.lde_domain_size());
+    match proof.options().hash_fn() {
+        HashFunction::Blake3_192 => {
+            parse_with::<Blake3_192<BaseElement>>(proof, num_trace_segments, num_fri_layers, 24)
+        }
+        HashFunction::Blake3_256 => {
+            parse_with::<Blake3_256<BaseElement>>(proof, num_trace_segments, num_fri_layers, 32)
+        }
+        HashFunction::Sha3_256 => {
+            parse_with::<Sha3_256<BaseElement>>(proof, num_trace_segments, num_fri_layers, 32)
+        }
+    }
+}
+
+fn parse_with<H: Hasher>(
+    proof: &StarkProof,
+    num_trace_segments: usize,
+    num_fri_layers: usize,
+    digest_len: usize,
+) -> Result<ParsedCommitments, String> {
+    let hex = |digest: &H::Digest| to_hex(&digest.as_bytes()[..digest_len]);
+    let (trace, constraint, fri) = proof
+        .commitments
+        .clone()
+        .parse::<H>(num_trace_segments, num_fri_layers)
+        .map_err(|err| format!("cannot parse commitments: {}", err))?;
+    Ok((
+        trace.iter().map(hex).collect(),
+        hex(&constraint),
+        fri.iter().map(hex).collect(),
+    ))
+}
+
+fn compare_commitments(
+    diffs: &mut Differences,
+    a: &StarkProof,
+    b: &StarkProof,
+) -> Result<(), String> {
+    const SECTION: &str = "commitments";
+    if a.options().hash_fn() != b.options().hash_fn() {
+        // digests of different hash functions are not comparable
+        return Ok(());
+    }
+    let (trace_a, constraint_a, fri_a) = parse_commitments(a)?;
+    let (trace_b, constraint_b, fri_b) = parse_commitments(b)?;
+    for (i, (root_a, root_b)) in trace_a.iter().zip(trace_b.iter()).enumerate() {
+        diffs.compare_text(
+            SECTION,
+            &format!("trace segment {} root", i),
+            root_a.clone(),
+            root_b.clone(),
+        );
+    }
+    diffs.compare_text(SECTION, "constraint root", constraint_a, constraint_b);
+    diffs.compare(SECTION, "FRI layer count", fri_a.len(), fri_b.len());
+    for (i, (root_a, root_b)) in fri_a.iter().zip(fri_b.iter()).enumerate() {
+        diffs.compare_text(
+            SECTION,
+            &format!("FRI layer {} root", i),
+            root_a.clone(),
+            root_b.clone(),
+        );
+    }
+    diffs.compare(SECTION, "proof-of-work nonce", a.pow_nonce, b.pow_nonce);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::{CustomData, COEFF_DEGREE, MIN_FIELD_EXTENSION, VALUE_NUM};
+    use winter_air::ProofOptions;
+
+    const MODULUS: u64 = 114689;
+
+    // Hashes of the coefficient indices shifted by `shift`, so that no column of the trace is
+    // constant.
+    fn operand(seed: u64, shift: u64) -> [Vec<Vec<u64>>; VALUE_NUM] {
+        std::array::from_fn(|v| {
+            let hash = |k: u64| {
+                let x = (k + 1000 * seed + 100 * v as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                (x ^ x >> 31).wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 47
+            };
+            vec![(0..COEFF_DEGREE as u64)
+                .map(|k| (hash(k) + shift) % MODULUS)
+                .collect()]
+        })
+    }
+
+    fn prove(value: u64, num_queries: usize) -> Data {
+        let data = CustomData {
+            modulus: vec![MODULUS],
+            values: vec![operand(0, value), operand(1, 0)],
+            ..Default::default()
+        };
+        let options = ProofOptions::new(
+            num_queries,
+            8,
+            0,
+            HashFunction::Blake3_256,
+            MIN_FIELD_EXTENSION,
+            4,
+            256,
+        );
+        crate::prove(&data, options).unwrap().to_data()
+    }
+
+    fn sections(diffs: &[Difference]) -> Vec<&'static str> {
+        let mut sections: Vec<&str> = diffs.iter().map(|diff| diff.section).collect();
+        sections.dedup();
+        sections
+    }
+
+    #[test]
+    fn equal_containers_have_no_differences() {
+        let proof = prove(2, 28);
+        assert!(diff_proofs(proof.clone(), proof).unwrap().is_empty());
+    }
+
+    #[test]
+    fn differences_are_reported_by_section() {
+        let proof = prove(2, 28);
+        let mut tampered = proof.clone();
+        let coefficient = proof.result[1][0][5];
+        tampered.result[1][0][5] = coefficient + 1;
+        let diffs = diff_proofs(proof.clone(), tampered).unwrap();
+        assert_eq!(sections(&diffs), ["public inputs"]);
+        assert_eq!(diffs[0].field, "commitment");
+        let result = diffs.last().unwrap();
+        assert_eq!(
+            result.field,
+            "result component 1 level 0: 1 coefficients differ, first at index 5"
+        );
+        let (left, right) = (coefficient.to_string(), (coefficient + 1).to_string());
+        assert_eq!(
+            (result.left.as_str(), result.right.as_str()),
+            (&*left, &*right)
+        );
+        assert_eq!(
+            result.to_string(),
+            format!(
+                "[public inputs] {}:\n  < {}\n  > {}",
+                result.field, left, right
+            )
+        );
+
+        let other = prove(3, 32);
+        let diffs = diff_proofs(proof.clone(), other).unwrap();
+        assert_eq!(
+            sections(&diffs),
+            ["parameters", "public inputs", "commitments"]
+        );
+        assert_eq!(diffs[0].field, "num queries");
+        assert_eq!(
+            (diffs[0].left.as_str(), diffs[0].right.as_str()),
+            ("28", "32")
+        );
+        assert!(diffs.iter().any(|diff| diff.field == "constraint root"));
+        let differing = format!(
+            "result component 0 level 0: {} coefficients differ, first at index 0",
+            COEFF_DEGREE
+        );
+        assert!(diffs.iter().any(|diff| diff.field == differing));
+
+        let mut malformed = proof.clone();
+        malformed.proof = String::from("AAAA");
+        assert!(diff_proofs(proof, malformed).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Base64 encoding of the proof bytes stored in proof containers. Proofs are decoded through a
+// streaming reader with a size limit, so an oversized or corrupted `proof` field fails with a
+// typed error instead of a panic or an unbounded allocation.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::read::DecoderReader;
+use base64::Engine;
+
+// Proofs of the current circuits are well below 1 MiB.
+pub const MAX_PROOF_BYTES: usize = 16 << 20;
+
+const CHUNK_SIZE: usize = 64 << 10;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Empty,
+    TooLarge { limit: usize },
+    Invalid(base64::DecodeError),
+    Io(io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "proof is empty"),
+            DecodeError::TooLarge { limit } => {
+                write!(f, "proof is larger than the limit of {} bytes", limit)
+            }
+            DecodeError::Invalid(err) => write!(f, "invalid base64: {}", err),
+            DecodeError::Io(err) => write!(f, "cannot read proof: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        // the decoder reports malformed input as an io::Error wrapping the base64 error
+        let invalid = err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<base64::DecodeError>())
+            .cloned();
+        match invalid {
+            Some(invalid) => DecodeError::Invalid(invalid),
+            None => DecodeError::Io(err),
+        }
+    }
+}
+
+pub struct ProofEncoding {
+    pub engine: GeneralPurpose,
+    pub max_bytes: usize,
+}
+
+impl Default for ProofEncoding {
+    // Standard alphabet, padded on encode; decoding also accepts unpadded input.
+    fn default() -> Self {
+        let config = GeneralPurposeConfig::new()
+            .with_encode_padding(true)
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        ProofEncoding {
+            engine: GeneralPurpose::new(&alphabet::STANDARD, config),
+            max_bytes: MAX_PROOF_BYTES,
+        }
+    }
+}
+
+impl ProofEncoding {
+    pub fn encode<T: AsRef<[u8]>>(&self, bytes: T) -> String {
+        self.engine.encode(bytes)
+    }
+
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>, DecodeError> {
+        self.decode_from(text.trim().as_bytes())
+    }
+
+    // Decodes base64 read from `reader` in chunks, failing as soon as the decoded size exceeds
+    // the limit.
+    pub fn decode_from<R: Read>(&self, mut reader: R) -> Result<Vec<u8>, DecodeError> {
+        let mut decoder = DecoderReader::new(&mut reader, &self.engine);
+        let mut bytes = Vec::new();
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        loop {
+            let read = decoder.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            if bytes.len() + read > self.max_bytes {
+                return Err(DecodeError::TooLarge {
+                    limit: self.max_bytes,
+                });
+            }
+            bytes.extend_from_slice(&chunk[..read]);
+        }
+        if bytes.is_empty() {
+            return Err(DecodeError::Empty);
+        }
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,685 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// BFV encryption of a plaintext the verifier never sees under a public key (b, a), distributed
+// like an evaluation key with a single digit. The ciphertext is the result and commits to the
+// plaintext; the proof shows that for a ternary u, errors e1, e2 with |e| <= 32 and a plaintext
+// m with coefficients below t
+//
+//     c0 = b * u + e1 + floor(Q / t) * m mod q,  c1 = a * u + e2 mod q
+//
+// for both RNS levels, with Q = q0 * q1 and the plain modulus t. The ciphertext and the public
+// key are in NTT form, so both products are pointwise.
+//
+// The trace holds eight networks of InttAir, one per level for the evaluations of u, e1, e2 and
+// m, on the circuit rows and row groups of InttAir. Their circuit rows 16b hold evaluations 2b
+// and 2b + 1, where both relations are checked against the public key and the ciphertext; their
+// last-stage circuit rows 16b + 11 hold coefficients b and b + N/2, where u is checked to be
+// ternary, e1 and e2 to be values E - 32 with E in 0..64 and m to be a value M below t, all equal
+// at both levels. Besides the butterflies, the rows of a group reduce both relations to the
+// ciphertext on the first stage against the public moduli, and reduce E and M to themselves
+// modulo 64 and t, which range checks them. A reduction of a relation reduces the ciphertext to
+// itself on the other circuit rows, where it is zero.
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+
+use crate::air::{
+    element_from_u128, element_from_u64, element_to_u64, level_column, load_data_file, BaseElement,
+    InputArg, PublicInputs, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::decrypt_air::{
+    first_row, last_row, signed_element, ERROR_BITS, ERROR_OFFSET, PUBLIC_KEY_DIGIT_NUM,
+};
+use crate::error::StarkHeError;
+use crate::intt_air::{
+    butterfly_values, fill_inverse_network, intt_periodic_columns, INTT_PERIODIC_NUM, LAST_STAGE,
+    SCALE_START, TWIDDLE_START,
+};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::{
+    check_ntt_modulus, evaluate_wiring, forward_transform, wiring_assertions, wiring_degrees,
+    NttTrace, BUTTERFLY_NUM, NTT_STAGE_NUM, NTT_TRACE_LENGTH, STAGE_SLOTS,
+};
+use crate::reduction::{reduction_group, reduction_width, Reductions};
+
+// Networks of the randomness, both errors and the plaintext, one per level each, + Reduction
+// Network columns: A.. B.. X.. Y.., network major, level minor
+// Rows 16b, half h holding evaluation 2b + h: PB.. PA.. C0.. C1.., half major
+// Rows 16b + 11, half h holding coefficient b + hN/2: U.. E1.. E2.. M..
+const RANDOMNESS_NETWORK: usize = 0;
+const ERROR_NETWORK: usize = 1;
+const PLAINTEXT_NETWORK: usize = ERROR_NETWORK + VALUE_NUM;
+const NETWORK_NUM: usize = PLAINTEXT_NETWORK + 1;
+const POLY_NUM: usize = NETWORK_NUM * COEFF_LEVEL;
+const HALF_NUM: usize = 2;
+const HALF_LEN: usize = HALF_NUM * COEFF_LEVEL;
+pub const PLAIN_BITS: usize = 20;
+
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + POLY_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + POLY_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + POLY_NUM;
+const KEY_B_START: usize = OUTPUT_Y_START + POLY_NUM;
+const KEY_A_START: usize = KEY_B_START + HALF_LEN;
+const CIPHERTEXT_START: usize = KEY_A_START + HALF_LEN;
+const RANDOMNESS_START: usize = CIPHERTEXT_START + VALUE_NUM * HALF_LEN;
+const ERROR_START: usize = RANDOMNESS_START + HALF_NUM;
+const PLAINTEXT_START: usize = ERROR_START + VALUE_NUM * HALF_NUM;
+const PLAINTEXT_END: usize = PLAINTEXT_START + HALF_NUM;
+// butterflies, ciphertext components, errors, plaintexts
+const REDUCTION_NUM: usize = 2 * POLY_NUM + VALUE_NUM * HALF_LEN + VALUE_NUM * HALF_NUM + HALF_NUM;
+// the butterflies reach 2 * q^2, so their quotients take a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per reduction, then padding
+pub const ENCRYPT_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = PLAINTEXT_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = NTT_TRACE_LENGTH;
+pub const ENCRYPT_AUX_WIDTH: usize = POLY_NUM;
+const AUX_WIDTH: usize = ENCRYPT_AUX_WIDTH;
+pub const ENCRYPT_RESULT_NUM: usize = VALUE_NUM;
+
+// Periodic columns after those of InttAir: selectors of the first and the last stage, then
+// those of the reductions.
+const FIRST_SELECTOR: usize = INTT_PERIODIC_NUM;
+const LAST_SELECTOR: usize = FIRST_SELECTOR + 1;
+const REDUCTION_PERIODIC_START: usize = LAST_SELECTOR + 1;
+
+// Trace layout constants of EncryptAir in canonical order.
+pub fn encrypt_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("error_bits", ERROR_BITS),
+        ("plain_bits", PLAIN_BITS),
+        ("group", ENCRYPT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// NTT-friendly moduli and a plain modulus of at most PLAIN_BITS bits.
+pub fn check_encrypt_parameters(modulus: &[u64], plain_modulus: u64) -> Result<(), String> {
+    check_ntt_modulus(modulus)?;
+    if !(2..=1 << PLAIN_BITS).contains(&plain_modulus) {
+        return Err(format!(
+            "plain modulus {} must be in 2..=2^{}",
+            plain_modulus, PLAIN_BITS
+        ));
+    }
+    Ok(())
+}
+
+// The parameters must be valid and the ciphertext in `result` reduced modulo `modulus`.
+pub fn check_encryption(
+    modulus: &[u64],
+    plain_modulus: u64,
+    result: &[Vec<Vec<u64>>],
+) -> Result<(), String> {
+    check_encrypt_parameters(modulus, plain_modulus)?;
+    if result.len() != VALUE_NUM {
+        return Err(format!(
+            "ciphertext has {} components, expected {}",
+            result.len(),
+            VALUE_NUM
+        ));
+    }
+    for (v, component) in result.iter().enumerate() {
+        for (l, level) in component.iter().enumerate() {
+            if level.len() != COEFF_DEGREE || level.iter().any(|&c| c >= modulus[l]) {
+                return Err(format!(
+                    "ciphertext component {} level {} is not {} residues modulo {}",
+                    v, l, COEFF_DEGREE, modulus[l]
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Plaintext coefficients below the plain modulus, a ternary randomness and the errors e1 and
+// e2, all in coefficient form.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EncryptData {
+    pub modulus: Vec<u64>,
+    pub plain_modulus: u64,
+    pub plaintext: Vec<u64>,
+    pub randomness: Vec<i64>,
+    pub errors: [Vec<i64>; VALUE_NUM],
+}
+
+fn network_columns(n: usize, l: usize) -> [usize; 4] {
+    [INPUT_A_START, INPUT_B_START, OUTPUT_X_START, OUTPUT_Y_START]
+        .map(|start| level_column(start, n, l))
+}
+
+// Input or output column of half `h` of a network: A, B on rows 16b, X, Y on rows 16b + 11.
+fn input_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([INPUT_A_START, INPUT_B_START][h], n, l)
+}
+
+fn output_column(n: usize, h: usize, l: usize) -> usize {
+    level_column([OUTPUT_X_START, OUTPUT_Y_START][h], n, l)
+}
+
+fn key_column(v: usize, h: usize, l: usize) -> usize {
+    level_column([KEY_B_START, KEY_A_START][v], h, l)
+}
+
+fn ciphertext_column(v: usize, h: usize, l: usize) -> usize {
+    level_column(CIPHERTEXT_START + v * HALF_LEN, h, l)
+}
+
+fn error_column(v: usize, h: usize) -> usize {
+    ERROR_START + v * HALF_NUM + h
+}
+
+// Reductions of a row: the values reduced to X and Y (half h) of every network, ...
+fn network_reduction(n: usize, h: usize, l: usize) -> usize {
+    HALF_NUM * (n * COEFF_LEVEL + l) + h
+}
+
+// .. the relation of every ciphertext component, reduced to it, ..
+fn ciphertext_reduction(v: usize, h: usize, l: usize) -> usize {
+    2 * POLY_NUM + level_column(v * HALF_LEN, h, l)
+}
+
+// .. the errors E ..
+fn error_reduction(v: usize, h: usize) -> usize {
+    2 * POLY_NUM + VALUE_NUM * HALF_LEN + v * HALF_NUM + h
+}
+
+// .. and the plaintext M, both reduced to themselves.
+fn plaintext_reduction(h: usize) -> usize {
+    REDUCTION_NUM - HALF_NUM + h
+}
+
+// The reductions of a row by the public moduli `modulus`, 64 and the plain modulus, every value
+// bounded as build_encrypt_trace computes it.
+pub fn encrypt_reductions(modulus: &[u64], plain_modulus: u64) -> Result<Reductions, String> {
+    check_encrypt_parameters(modulus, plain_modulus)?;
+    let mut results = vec![0; REDUCTION_NUM];
+    let mut moduli = vec![0; REDUCTION_NUM];
+    let mut bounds = vec![0; REDUCTION_NUM];
+    let mut set = |j: usize, result: usize, m: u64, bound: u128| {
+        results[j] = result;
+        moduli[j] = m;
+        bounds[j] = bound;
+    };
+    for h in 0..HALF_NUM {
+        for l in 0..COEFF_LEVEL {
+            let q = modulus[l] as u128;
+            for n in 0..NETWORK_NUM {
+                set(
+                    network_reduction(n, h, l),
+                    output_column(n, h, l),
+                    modulus[l],
+                    2 * q * q,
+                );
+            }
+            for v in 0..VALUE_NUM {
+                set(
+                    ciphertext_reduction(v, h, l),
+                    ciphertext_column(v, h, l),
+                    modulus[l],
+                    q * q,
+                );
+            }
+        }
+        for v in 0..VALUE_NUM {
+            let error = error_column(v, h);
+            set(
+                error_reduction(v, h),
+                error,
+                2 * ERROR_OFFSET,
+                2 * ERROR_OFFSET as u128,
+            );
+        }
+        set(
+            plaintext_reduction(h),
+            PLAINTEXT_START + h,
+            plain_modulus,
+            plain_modulus as u128 - 1,
+        );
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (0..PLAINTEXT_END).collect(),
+        PLAINTEXT_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Wiring columns of EncryptAir, in polynomial order.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    let mut wiring = Vec::with_capacity(POLY_NUM);
+    for n in 0..NETWORK_NUM {
+        for l in 0..COEFF_LEVEL {
+            wiring.push(network_columns(n, l));
+        }
+    }
+    wiring
+}
+
+// floor(Q / t) modulo every modulus.
+fn plaintext_scales(modulus: &[u64], plain_modulus: u64) -> Vec<u64> {
+    let delta = modulus.iter().map(|&q| q as u128).product::<u128>() / plain_modulus as u128;
+    modulus
+        .iter()
+        .map(|&q| (delta % q as u128) as u64)
+        .collect()
+}
+
+pub fn build_encrypt_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let data: EncryptData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_ref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from(
+            "encryption needs --evaluation-key holding the public key",
+        ))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    let reductions = encrypt_reductions(&data.modulus, data.plain_modulus)
+        .map_err(StarkHeError::InvalidInput)?;
+    let modulus = data.modulus;
+    assert!(
+        key.modulus == modulus && key.values.len() == PUBLIC_KEY_DIGIT_NUM,
+        "the public key must be a single digit over the moduli of the ciphertext"
+    );
+    let offset = ERROR_OFFSET as i64;
+    assert!(
+        data.plaintext.len() == COEFF_DEGREE
+            && data.plaintext.iter().all(|&c| c < data.plain_modulus),
+        "plaintext must be {} residues modulo {}",
+        COEFF_DEGREE,
+        data.plain_modulus
+    );
+    assert!(
+        data.randomness.len() == COEFF_DEGREE && data.randomness.iter().all(|c| c.abs() <= 1),
+        "randomness must be {} ternary coefficients",
+        COEFF_DEGREE
+    );
+    assert!(
+        data.errors
+            .iter()
+            .all(|e| e.len() == COEFF_DEGREE && e.iter().all(|c| (-offset..offset).contains(c))),
+        "errors must be {} coefficients in {}..{}",
+        COEFF_DEGREE,
+        -offset,
+        offset
+    );
+    let key = &key.values[0];
+    let scales = plaintext_scales(&modulus, data.plain_modulus);
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; PLAINTEXT_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    for l in 0..COEFF_LEVEL {
+        let q = modulus[l];
+        assert!(
+            key.iter()
+                .all(|k| k[l].len() == COEFF_DEGREE && k[l].iter().all(|&c| c < q)),
+            "public key must be {} residues modulo {}",
+            COEFF_DEGREE,
+            q
+        );
+        let residues = |values: &[i64]| -> Vec<u64> {
+            values.iter().map(|&c| (c + q as i64) as u64 % q).collect()
+        };
+        let mut polys = vec![residues(&data.randomness)];
+        polys.extend(data.errors.iter().map(|e| residues(e)));
+        polys.push(data.plaintext.clone());
+        let evaluations: Vec<Vec<u64>> = polys
+            .into_iter()
+            .map(|poly| forward_transform(poly, q))
+            .collect();
+        for k in 0..COEFF_DEGREE {
+            let (row, h) = (first_row(k / 2), k % 2);
+            let u = evaluations[RANDOMNESS_NETWORK][k] as u128;
+            let message = scales[l] as u128 * evaluations[PLAINTEXT_NETWORK][k] as u128;
+            for v in 0..VALUE_NUM {
+                let mut sum = key[v][l][k] as u128 * u + evaluations[ERROR_NETWORK + v][k] as u128;
+                if v == 0 {
+                    sum += message;
+                }
+                columns[key_column(v, h, l)][row] = element_from_u64(key[v][l][k]);
+                columns[ciphertext_column(v, h, l)][row] = element_from_u128(sum % q as u128);
+                values[row][ciphertext_reduction(v, h, l)] = sum;
+            }
+        }
+        for (n, poly) in evaluations.into_iter().enumerate() {
+            let (_, network_values) =
+                fill_inverse_network(&mut columns, network_columns(n, l), poly, q);
+            for (row, pair) in network_values.into_iter().enumerate() {
+                for h in 0..HALF_NUM {
+                    values[row][network_reduction(n, h, l)] = pair[h];
+                }
+            }
+        }
+    }
+
+    for k in 0..COEFF_DEGREE {
+        let (row, h) = (last_row(k % BUTTERFLY_NUM), k / BUTTERFLY_NUM);
+        columns[RANDOMNESS_START + h][row] = signed_element(data.randomness[k]);
+        for v in 0..VALUE_NUM {
+            let error = (data.errors[v][k] + offset) as u64;
+            columns[error_column(v, h)][row] = BaseElement::from(error);
+            values[row][error_reduction(v, h)] = error as u128;
+        }
+        columns[PLAINTEXT_START + h][row] = BaseElement::from(data.plaintext[k]);
+        values[row][plaintext_reduction(h)] = data.plaintext[k] as u128;
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        modulus,
+        wiring_columns(),
+        true,
+        ENCRYPT_GROUP,
+    ))
+}
+
+pub fn get_encrypt_pub_inputs(trace: &NttTrace, plain_modulus: u64) -> PublicInputs {
+    let main = trace.main_segment();
+    let evaluations = |column: &dyn Fn(usize) -> usize| -> Vec<BaseElement> {
+        (0..COEFF_DEGREE)
+            .map(|k| main.get(column(k % 2), first_row(k / 2) * ENCRYPT_GROUP))
+            .collect()
+    };
+    let result = (0..VALUE_NUM)
+        .map(|v| {
+            (0..COEFF_LEVEL)
+                .map(|l| evaluations(&|h| ciphertext_column(v, h, l)))
+                .collect()
+        })
+        .collect();
+    let key = std::array::from_fn(|v| {
+        std::array::from_fn(|l| {
+            evaluations(&|h| key_column(v, h, l))
+                .into_iter()
+                .map(element_to_u64)
+                .collect()
+        })
+    });
+    PublicInputs {
+        result,
+        evaluation_key: Some(EvaluationKey {
+            modulus: trace.modulus().to_vec(),
+            values: vec![key],
+        }),
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus().to_vec()),
+        plain_modulus: Some(plain_modulus),
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct EncryptAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    ciphertext: Vec<Vec<Vec<BaseElement>>>,
+    key: EvaluationKey,
+    modulus: Vec<u64>,
+    plaintext_scales: Vec<u64>,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl EncryptAir {
+    // The values of the reductions of a row, as build_encrypt_trace computes them: the relations
+    // under the selector of the first stage, the ciphertext elsewhere.
+    fn row_values<E: FieldElement + From<BaseElement>>(
+        &self,
+        row: &[E],
+        periodic_values: &[E],
+    ) -> [E; REDUCTION_NUM] {
+        let first = periodic_values[FIRST_SELECTOR];
+        let q = |l: usize| E::from(element_from_u64(self.modulus[l]));
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        for n in 0..NETWORK_NUM {
+            for l in 0..COEFF_LEVEL {
+                let pair = butterfly_values(
+                    row,
+                    network_columns(n, l),
+                    periodic_values[SCALE_START + l],
+                    periodic_values[TWIDDLE_START + l],
+                    q(l),
+                );
+                for h in 0..HALF_NUM {
+                    values[network_reduction(n, h, l)] = pair[h];
+                }
+            }
+        }
+        // b * u + e1 + floor(Q / t) * m and a * u + e2 on the evaluations
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                for v in 0..VALUE_NUM {
+                    let mut sum = row[key_column(v, h, l)]
+                        * row[input_column(RANDOMNESS_NETWORK, h, l)]
+                        + row[input_column(ERROR_NETWORK + v, h, l)];
+                    if v == 0 {
+                        let scale = E::from(element_from_u64(self.plaintext_scales[l]));
+                        sum += scale * row[input_column(PLAINTEXT_NETWORK, h, l)];
+                    }
+                    let c = row[ciphertext_column(v, h, l)];
+                    values[ciphertext_reduction(v, h, l)] = c + first * (sum - c);
+                }
+            }
+            for v in 0..VALUE_NUM {
+                values[error_reduction(v, h)] = row[error_column(v, h)];
+            }
+            values[plaintext_reduction(h)] = row[PLAINTEXT_START + h];
+        }
+        values
+    }
+}
+
+impl Air for EncryptAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // Only the current row is checked. The relations are quadratic under the selector of the
+    // first stage, the residue checks sit under the selector of the last; the ternary check of u
+    // is cubic on every row, where the prover keeps u zero off the last stage.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("encryption proofs publish their moduli");
+        let plain_modulus = pub_inputs
+            .plain_modulus
+            .expect("encryption proofs publish the plain modulus");
+        let reductions =
+            encrypt_reductions(&modulus, plain_modulus).expect("encryption moduli were checked");
+        let stage = STAGE_SLOTS * reductions.group();
+        let selected = |degree| TransitionConstraintDegree::with_cycles(degree, vec![stage]);
+        let mut degrees = reductions.degrees(2, &[stage]);
+        for _ in 0..HALF_NUM {
+            degrees.push(TransitionConstraintDegree::new(3));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(2)));
+            degrees.extend((0..VALUE_NUM * COEFF_LEVEL).map(|_| selected(2)));
+            degrees.extend((0..COEFF_LEVEL).map(|_| selected(1)));
+        }
+        let aux_degrees = wiring_degrees(AUX_WIDTH, reductions.group());
+        let num_assertions = 2 * VALUE_NUM * HALF_LEN;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let key = pub_inputs
+            .evaluation_key
+            .expect("encryption proofs are verified against a public key");
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        EncryptAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            ciphertext: pub_inputs.result,
+            key,
+            plaintext_scales: plaintext_scales(&modulus, plain_modulus),
+            modulus,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let row = frame.current();
+        let values = self.row_values(row, periodic_values);
+        let (reduction, result) = result.split_at_mut(self.reductions.num_constraints());
+        self.reductions.evaluate(
+            frame,
+            &periodic_values[REDUCTION_PERIODIC_START..],
+            &values,
+            reduction,
+        );
+
+        let last = periodic_values[LAST_SELECTOR];
+        let q: Vec<E> = self
+            .modulus
+            .iter()
+            .map(|&q| E::from(element_from_u64(q)))
+            .collect();
+        let two = E::from(BaseElement::from(2u64));
+        let offset = E::from(BaseElement::from(ERROR_OFFSET));
+        let mut i = 0;
+        for h in 0..HALF_NUM {
+            // u in {-1, 0, 1}, residue u + q * u(u - 1)/2 at both levels
+            let u = row[RANDOMNESS_START + h];
+            result[i] = u * (u - E::ONE) * (u + E::ONE);
+            i += 1;
+            for l in 0..COEFF_LEVEL {
+                result[i] = last
+                    * (row[output_column(RANDOMNESS_NETWORK, h, l)]
+                        - u
+                        - q[l] * u * (u - E::ONE) / two);
+                i += 1;
+            }
+
+            // e = E - 32 with E in 0..64, residue e or e + q at both levels, the one below q
+            for v in 0..VALUE_NUM {
+                let e = row[error_column(v, h)] - offset;
+                for l in 0..COEFF_LEVEL {
+                    let residue = row[output_column(ERROR_NETWORK + v, h, l)];
+                    result[i] = last * (residue - e) * (residue - e - q[l]);
+                    i += 1;
+                }
+            }
+
+            // m in 0..t, the same residue at both levels
+            for l in 0..COEFF_LEVEL {
+                result[i] =
+                    last * (row[output_column(PLAINTEXT_NETWORK, h, l)] - row[PLAINTEXT_START + h]);
+                i += 1;
+            }
+        }
+        debug_assert_eq!(i, result.len());
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The public key and the ciphertext on the first rows of the groups of the first stage.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let stride = STAGE_SLOTS * self.reductions.group();
+        let mut assertions = Vec::new();
+        let halves = |values: Vec<BaseElement>, h: usize| -> Vec<BaseElement> {
+            values.into_iter().skip(h).step_by(2).collect()
+        };
+        for h in 0..HALF_NUM {
+            for l in 0..COEFF_LEVEL {
+                for v in 0..VALUE_NUM {
+                    let key = self.key.values[0][v][l]
+                        .iter()
+                        .map(|&value| element_from_u64(value))
+                        .collect();
+                    assertions.push(Assertion::sequence(
+                        key_column(v, h, l),
+                        0,
+                        stride,
+                        halves(key, h),
+                    ));
+                    assertions.push(Assertion::sequence(
+                        ciphertext_column(v, h, l),
+                        0,
+                        stride,
+                        halves(self.ciphertext[v][l].clone(), h),
+                    ));
+                }
+            }
+        }
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut first = vec![BaseElement::ZERO; STAGE_SLOTS];
+        let mut last = vec![BaseElement::ZERO; STAGE_SLOTS];
+        first[0] = BaseElement::ONE;
+        last[LAST_STAGE] = BaseElement::ONE;
+        let mut columns = intt_periodic_columns(&self.modulus, self.reductions.group());
+        columns.push(self.reductions.stretch(&first));
+        columns.push(self.reductions.stretch(&last));
+        columns.extend(self.reductions.periodic_columns());
+        columns
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Errors of the library entry points: loading data files, building traces and decoding proofs.
+// Callers embedding the crate match on the kind; the command lines print the message.
+
+use std::fmt;
+use std::io;
+
+use crate::encoding::DecodeError;
+use crate::verifying::VerificationFailure;
+
+#[derive(Debug)]
+pub enum StarkHeError {
+    // a data, key or result file cannot be read
+    Io { path: String, source: io::Error },
+    // a file is not TOML or packed data of the expected fields
+    Parse { path: String, message: String },
+    // operands, levels or coefficients whose counts do not fit each other or the circuit
+    DimensionMismatch(String),
+    // parameters the circuit rejects, like moduli, degrees or a missing command line argument
+    InvalidInput(String),
+    // proof bytes or a proof container which cannot be decoded
+    ProofDeserialization(String),
+    // a decoded proof which does not verify
+    Verification(VerificationFailure),
+    // a proof which the prover fails to generate, store or archive
+    Proving(String),
+}
+
+impl StarkHeError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        StarkHeError::Io {
+            path: String::from(path),
+            source,
+        }
+    }
+
+    pub fn parse<E: fmt::Display>(path: &str, err: E) -> Self {
+        StarkHeError::Parse {
+            path: String::from(path),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StarkHeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StarkHeError::Io { path, source } => write!(f, "cannot read {}: {}", path, source),
+            StarkHeError::Parse { path, message } => {
+                write!(f, "cannot parse {}: {}", path, message)
+            }
+            StarkHeError::DimensionMismatch(message)
+            | StarkHeError::InvalidInput(message)
+            | StarkHeError::ProofDeserialization(message)
+            | StarkHeError::Proving(message) => write!(f, "{}", message),
+            StarkHeError::Verification(failure) => write!(f, "{}", failure),
+        }
+    }
+}
+
+impl std::error::Error for StarkHeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StarkHeError::Io { source, .. } => Some(source),
+            StarkHeError::Verification(failure) => Some(failure),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for StarkHeError {
+    fn from(err: DecodeError) -> Self {
+        StarkHeError::ProofDeserialization(err.to_string())
+    }
+}
+
+impl From<VerificationFailure> for StarkHeError {
+    fn from(failure: VerificationFailure) -> Self {
+        StarkHeError::Verification(failure)
+    }
+}
+
+// Callers that only report the reason keep working with strings.
+impl From<StarkHeError> for String {
+    fn from(err: StarkHeError) -> Self {
+        err.to_string()
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Step-through of a single trace row for `stark-he explain`: the main trace values of the row
+// and of the next one, the periodic values and every main transition constraint evaluated on
+// them, followed by the constraints with their operands substituted for the circuits which
+// describe them. Constraints of the auxiliary segment (the wiring arguments) are only counted,
+// their values depend on the verifier's random elements.
+
+use std::fmt::Write;
+
+use winter_air::{Air, EvaluationFrame};
+use winter_math::{FieldElement, StarkField};
+use winter_prover::{Matrix, Prover, Trace};
+
+use crate::air::{
+    explain_fresh_row, load_scheme_config, BaseElement, InputArg, Op, PublicInputs, SchemeConfig,
+};
+use crate::error::StarkHeError;
+use crate::proving::CircuitVisitor;
+
+// Builds the trace of a circuit and explains row `row` of it.
+pub struct RowExplainer {
+    pub row: usize,
+}
+
+impl CircuitVisitor for RowExplainer {
+    type Output = Result<String, String>;
+
+    fn visit<P>(
+        self,
+        prover: Result<P, StarkHeError>,
+        build: fn(&InputArg) -> Result<P::Trace, StarkHeError>,
+        input_args: &InputArg,
+    ) -> Self::Output
+    where
+        P: Prover<BaseField = BaseElement>,
+        P::Air: Air<PublicInputs = PublicInputs>,
+    {
+        let prover = prover?;
+        let trace = build(input_args)?;
+        let pub_inputs = prover.get_pub_inputs(&trace);
+        // additions are laid out by the configuration of their data file
+        let config = (input_args.op == Op::Add)
+            .then(|| load_scheme_config(input_args))
+            .transpose()?;
+        let air = P::Air::new(trace.get_info(), pub_inputs, prover.options().clone());
+        explain_row(
+            input_args.op,
+            config.as_ref(),
+            &air,
+            trace.main_segment(),
+            self.row,
+        )
+    }
+}
+
+fn explain_row<A: Air<BaseField = BaseElement>>(
+    op: Op,
+    config: Option<&SchemeConfig>,
+    air: &A,
+    main: &Matrix<BaseElement>,
+    row: usize,
+) -> Result<String, String> {
+    let length = main.num_rows();
+    if row >= length {
+        return Err(format!(
+            "row {} is outside the {} trace of {} rows",
+            row,
+            op.name(),
+            length
+        ));
+    }
+    let next_row = (row + 1) % length;
+    let read = |r| {
+        let mut values = vec![BaseElement::ZERO; main.num_cols()];
+        main.read_row_into(r, &mut values);
+        values
+    };
+    let frame = EvaluationFrame::from_rows(read(row), read(next_row));
+    let periodic: Vec<BaseElement> = air
+        .get_periodic_column_values()
+        .iter()
+        .map(|column| column[row % column.len()])
+        .collect();
+    let context = air.context();
+    let mut result = vec![BaseElement::ZERO; context.num_main_transition_constraints()];
+    air.evaluate_transition(&frame, &periodic, &mut result);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} row {} of {}, next row {}",
+        op.name(),
+        row,
+        length,
+        next_row
+    )
+    .unwrap();
+    if row >= length - context.num_transition_exemptions() {
+        writeln!(
+            out,
+            "transition constraints are not enforced on this row, violations are expected"
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\ncolumn  current -> next").unwrap();
+    for (c, (current, next)) in frame.current().iter().zip(frame.next()).enumerate() {
+        writeln!(out, "{:>6}  {} -> {}", c, signed(*current), signed(*next)).unwrap();
+    }
+    if !periodic.is_empty() {
+        writeln!(out, "\nperiodic column  value").unwrap();
+        for (c, value) in periodic.iter().enumerate() {
+            writeln!(out, "{:>15}  {}", c, signed(*value)).unwrap();
+        }
+    }
+
+    writeln!(out, "\nconstraint  value").unwrap();
+    for (i, value) in result.iter().enumerate() {
+        let verdict = if *value == BaseElement::ZERO {
+            ""
+        } else {
+            "  violated"
+        };
+        writeln!(out, "{:>10}  {}{}", i, signed(*value), verdict).unwrap();
+    }
+    let violated = result.iter().filter(|&&v| v != BaseElement::ZERO).count();
+    writeln!(
+        out,
+        "{} of {} main transition constraints violated, {} auxiliary constraints not evaluated",
+        violated,
+        result.len(),
+        context.num_aux_transition_constraints()
+    )
+    .unwrap();
+
+    let details = match op {
+        Op::Add => explain_fresh_row(
+            row,
+            config.expect("additions have a configuration"),
+            frame.current(),
+        ),
+        _ => Vec::new(),
+    };
+    if !details.is_empty() {
+        writeln!(out).unwrap();
+        for line in details {
+            writeln!(out, "{}", line).unwrap();
+        }
+    }
+    Ok(out)
+}
+
+// Elements above p / 2 as negative numbers, so that signed values such as errors, borrows and
+// violated constraints read naturally.
+pub fn signed(value: BaseElement) -> String {
+    let value = value.as_int();
+    if value > BaseElement::MODULUS / 2 {
+        format!("-{}", BaseElement::MODULUS - value)
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// C API of the library, for C and C++ HE stacks (SEAL-based services, say) which prove and verify
+// in-process. Both sides exchange the files of the command lines as bytes: `stark_he_prove` takes
+// a data file of `add` operands, as `prover --op add` reads it, and returns the proof file;
+// `stark_he_verify` takes a proof file of any circuit without an evaluation key, as `verifier`
+// reads it. Both return one of the STARK_HE_* codes below and, unless the caller passes NULL,
+// the message of a failure in an `error` buffer.
+//
+// Buffers returned by the library are owned by the caller, who releases them with
+// `stark_he_buffer_free`. include/stark_he.h declares this API; regenerate it with
+// `cbindgen --config cbindgen.toml --output include/stark_he.h` after changing it.
+
+use std::{ptr, slice};
+
+use crate::air::CustomData;
+use crate::error::StarkHeError;
+use crate::params::Profile;
+use crate::verifying::{decode_data, verify_data, VerificationFailure};
+
+pub const STARK_HE_OK: i32 = 0;
+// a pointer argument is NULL, or the profile is unknown
+pub const STARK_HE_INVALID_ARGUMENT: i32 = 1;
+// the data file cannot be parsed or its operands do not fit the circuit
+pub const STARK_HE_INVALID_INPUT: i32 = 2;
+// the proof failed, VerificationFailure::kind_index plus STARK_HE_BAD_FORMAT
+pub const STARK_HE_BAD_FORMAT: i32 = 3;
+pub const STARK_HE_PARAMS_REJECTED: i32 = 4;
+pub const STARK_HE_PUBLIC_INPUT_MISMATCH: i32 = 5;
+pub const STARK_HE_CONSTRAINT_FAILURE: i32 = 6;
+pub const STARK_HE_FRI_FAILURE: i32 = 7;
+pub const STARK_HE_SIGNATURE_INVALID: i32 = 8;
+// the prover failed to generate the proof, see StarkHeError::Proving
+pub const STARK_HE_INTERNAL_ERROR: i32 = 9;
+
+// The proof options of `stark_he_prove`, those of the profiles of `prover --profile` with the
+// default FRI folding factor and remainder.
+pub const STARK_HE_PROFILE_FAST: u32 = 0;
+pub const STARK_HE_PROFILE_BALANCED: u32 = 1;
+pub const STARK_HE_PROFILE_PARANOID: u32 = 2;
+
+const FOLDING_FACTOR: usize = 8;
+const FRI_MAX_REMAINDER_SIZE: usize = 256;
+
+// Bytes allocated by the library, an empty buffer has a NULL `data`.
+#[repr(C)]
+pub struct StarkHeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl StarkHeBuffer {
+    fn empty() -> Self {
+        StarkHeBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return StarkHeBuffer::empty();
+        }
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        StarkHeBuffer { data, len }
+    }
+}
+
+struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Failure {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<StarkHeError> for Failure {
+    fn from(err: StarkHeError) -> Self {
+        match err {
+            StarkHeError::Verification(failure) => Failure::from(failure),
+            StarkHeError::ProofDeserialization(message) => {
+                Failure::new(STARK_HE_BAD_FORMAT, message)
+            }
+            StarkHeError::Proving(message) => Failure::new(STARK_HE_INTERNAL_ERROR, message),
+            err => Failure::new(STARK_HE_INVALID_INPUT, err.to_string()),
+        }
+    }
+}
+
+impl From<VerificationFailure> for Failure {
+    fn from(failure: VerificationFailure) -> Self {
+        Failure::new(
+            STARK_HE_BAD_FORMAT + failure.kind_index() as i32,
+            failure.to_string(),
+        )
+    }
+}
+
+fn profile(code: u32) -> Option<Profile> {
+    match code {
+        STARK_HE_PROFILE_FAST => Some(Profile::Fast),
+        STARK_HE_PROFILE_BALANCED => Some(Profile::Balanced),
+        STARK_HE_PROFILE_PARANOID => Some(Profile::Paranoid),
+        _ => None,
+    }
+}
+
+// The `len` bytes at `data`, which may be NULL when `len` is 0.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Failure> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(Failure::new(STARK_HE_INVALID_ARGUMENT, "input is NULL")),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+// Runs `f` and hands the message of a failure to `error` unless it is NULL.
+fn call<F: FnOnce() -> Result<(), Failure>>(error: *mut StarkHeBuffer, f: F) -> i32 {
+    match f() {
+        Ok(()) => STARK_HE_OK,
+        Err(failure) => {
+            if !error.is_null() {
+                unsafe { *error = StarkHeBuffer::from_vec(failure.message.into_bytes()) };
+            }
+            failure.code
+        }
+    }
+}
+
+/// Proves the `add` data file of `data_len` bytes at `data` with the proof options of
+/// `profile_code`, a STARK_HE_PROFILE_*, and stores the proof file in `proof`.
+///
+/// # Safety
+///
+/// `data` points to `data_len` readable bytes; `proof` and `error`, which may be NULL, point to
+/// writable buffers; a NULL `proof` fails with STARK_HE_INVALID_ARGUMENT.
+#[no_mangle]
+pub unsafe extern "C" fn stark_he_prove(
+    data: *const u8,
+    data_len: usize,
+    profile_code: u32,
+    proof: *mut StarkHeBuffer,
+    error: *mut StarkHeBuffer,
+) -> i32 {
+    call(error, || {
+        if proof.is_null() {
+            return Err(Failure::new(STARK_HE_INVALID_ARGUMENT, "proof is NULL"));
+        }
+        let options = profile(profile_code)
+            .ok_or_else(|| {
+                Failure::new(
+                    STARK_HE_INVALID_ARGUMENT,
+                    format!("unknown profile {}", profile_code),
+                )
+            })?
+            .proof_options(FOLDING_FACTOR, FRI_MAX_REMAINDER_SIZE);
+        let text = std::str::from_utf8(input(data, data_len)?)
+            .map_err(|err| StarkHeError::parse("data", err))?;
+        let custom: CustomData =
+            toml::from_str(text).map_err(|err| StarkHeError::parse("data", err))?;
+        let data = crate::prove(&custom, options)?.to_data();
+        let text = toml::to_string(&data).expect("proof files serialize to TOML");
+        *proof = StarkHeBuffer::from_vec(text.into_bytes());
+        Ok(())
+    })
+}
+
+/// Verifies the proof file of `proof_len` bytes at `proof`.
+///
+/// # Safety
+///
+/// `proof` points to `proof_len` readable bytes; `error`, which may be NULL, points to a
+/// writable buffer.
+#[no_mangle]
+pub unsafe extern "C" fn stark_he_verify(
+    proof: *const u8,
+    proof_len: usize,
+    error: *mut StarkHeBuffer,
+) -> i32 {
+    call(error, || {
+        let bytes = input(proof, proof_len)?.to_vec();
+        verify_data(decode_data(bytes, None, "proof")?, None)?;
+        Ok(())
+    })
+}
+
+/// Releases a buffer returned by the library; empty buffers are ignored.
+///
+/// # Safety
+///
+/// `buffer` was returned by the library and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn stark_he_buffer_free(buffer: StarkHeBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(buffer: StarkHeBuffer) -> String {
+        let text =
+            unsafe { String::from_utf8_lossy(slice::from_raw_parts(buffer.data, buffer.len)) }
+                .into_owned();
+        unsafe { stark_he_buffer_free(buffer) };
+        text
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported() {
+        let mut error = StarkHeBuffer::empty();
+        let mut proof = StarkHeBuffer::empty();
+        let code = unsafe { stark_he_prove(ptr::null(), 3, 0, &mut proof, &mut error) };
+        assert_eq!(code, STARK_HE_INVALID_ARGUMENT);
+        assert_eq!(message(error), "input is NULL");
+        assert!(proof.data.is_null());
+
+        let mut error = StarkHeBuffer::empty();
+        let code = unsafe { stark_he_prove(b"".as_ptr(), 0, 3, &mut proof, &mut error) };
+        assert_eq!(code, STARK_HE_INVALID_ARGUMENT);
+        assert_eq!(message(error), "unknown profile 3");
+
+        let data = b"Modulus = \"not a list\"";
+        let code = unsafe {
+            stark_he_prove(
+                data.as_ptr(),
+                data.len(),
+                STARK_HE_PROFILE_FAST,
+                &mut proof,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(code, STARK_HE_INVALID_INPUT);
+    }
+
+    #[test]
+    fn malformed_proofs_are_rejected() {
+        let mut error = StarkHeBuffer::empty();
+        let proof = b"circuit = 42";
+        let code = unsafe { stark_he_verify(proof.as_ptr(), proof.len(), &mut error) };
+        assert_eq!(code, STARK_HE_BAD_FORMAT);
+        assert!(message(error).starts_with("cannot parse proof"));
+        assert_eq!(
+            unsafe { stark_he_verify(ptr::null(), 0, ptr::null_mut()) },
+            STARK_HE_BAD_FORMAT
+        );
+        unsafe { stark_he_buffer_free(StarkHeBuffer::empty()) };
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Fused multiply-add, one step of a dot product: the tensor product of ciphertexts a and b, as
+// in MulAir, added to an accumulator c of three components,
+//
+//     r0 = a0 * b0 + c0,  r1 = a0 * b1 + a1 * b0 + c1,  r2 = a1 * b1 + c2
+//
+// so a multiplication and the following addition need a single proof. Operands and accumulator
+// are in NTT form and every circuit row holds one evaluation point of every RNS level; as in
+// MulAir, the sums are reduced by the Barrett blocks of reduction.rs, one per row of a row group,
+// against the moduli of the public inputs. With 62-bit moduli the unreduced sums stay below
+// 2^125.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::mul_air::{tensor_terms, MUL_DATA_NUM, MUL_MAX_MODULUS_BITS, MUL_RESULT_NUM};
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Accumulator + Reduction
+// M0 M1 R00 R01 R10 R11 R20 R21 A00 A01 A10 A11 B00 B01 B10 B11 C00 C01 C10 C11 C20 C21
+// K.. R.. S..
+const RESULT_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const ACCUMULATOR_START: usize = DATA_START + MUL_DATA_NUM * DATA_LEN;
+const ACCUMULATOR_END: usize = ACCUMULATOR_START + RESULT_LEN;
+// as in MulAir, the middle sum reaches 2 (m - 1)^2 + m - 1
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per sum and level, then padding
+pub const FMA_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = ACCUMULATOR_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of FmaAir in canonical order.
+pub fn fma_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("result_num", MUL_RESULT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", FMA_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as MulData, with the three accumulator components in `Accumulator`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FmaData {
+    pub modulus: Vec<u64>,
+    pub values: [[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; MUL_DATA_NUM],
+    pub accumulator: [[Vec<u64>; COEFF_LEVEL]; MUL_RESULT_NUM],
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+fn accumulator_column(r: usize, l: usize) -> usize {
+    level_column(ACCUMULATOR_START, r, l)
+}
+
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+// Tensor product terms of one evaluation point plus the accumulator `c`, before their reduction.
+pub fn fma_terms(
+    a: [u64; VALUE_NUM],
+    b: [u64; VALUE_NUM],
+    c: [u64; MUL_RESULT_NUM],
+) -> [u128; MUL_RESULT_NUM] {
+    let terms = tensor_terms(a, b);
+    std::array::from_fn(|r| terms[r] + c[r] as u128)
+}
+
+// The reductions of a row by the public moduli `modulus`, sum r at level l being value
+// r * COEFF_LEVEL + l of the row: the bounds of MulAir plus a residue of the accumulator.
+pub fn fma_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Fma, modulus, MUL_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        MUL_RESULT_NUM,
+        result_column,
+        |r, l| {
+            let m = modulus[l] as u128;
+            (1 + (r == 1) as u128) * (m - 1) * (m - 1) + m - 1
+        },
+        (RESULT_START..ACCUMULATOR_END).collect(),
+        ACCUMULATOR_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_fma_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: FmaData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+
+    let operands = Ciphertext::from_operands(&data.modulus, STATE_LENGTH, data.values)?;
+    let accumulator = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.accumulator)?;
+    let reductions = fma_reductions(operands[0].modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; ACCUMULATOR_END];
+    for (d, operand) in operands.iter().enumerate() {
+        operand.write_columns(&mut columns, |v, l| data_column(d, v, l));
+    }
+    accumulator.write_columns(&mut columns, accumulator_column);
+    let terms = |k: usize, l: usize| {
+        let [a, b] = [0, 1].map(|d| [0, 1].map(|v| operands[d].coeff(v, l, k)));
+        let c = [0, 1, 2].map(|r| accumulator.coeff(r, l, k));
+        fma_terms(a, b, c)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = operands[0].modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for (r, term) in terms(k, l).into_iter().enumerate() {
+                columns[result_column(r, l)][k] = element_from_u64((term % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        terms(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    }))
+}
+
+pub fn get_fma_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MUL_RESULT_NUM, FMA_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The sums of one row, sum r at level l at r * COEFF_LEVEL + l.
+fn row_sums<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    for l in 0..COEFF_LEVEL {
+        let a = [row[data_column(0, 0, l)], row[data_column(0, 1, l)]];
+        let b = [row[data_column(1, 0, l)], row[data_column(1, 1, l)]];
+        let terms = [a[0] * b[0], a[0] * b[1] + a[1] * b[0], a[1] * b[1]];
+        for r in 0..MUL_RESULT_NUM {
+            sums[r * COEFF_LEVEL + l] = terms[r] + row[accumulator_column(r, l)];
+        }
+    }
+    sums
+}
+
+pub struct FmaAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for FmaAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in MulAir, the sums are quadratic in the operands and selected by a periodic column;
+    // the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("fma proofs record their moduli");
+        let reductions = fma_reductions(&modulus).expect("fma moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        FmaAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let sums = row_sums(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..MUL_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(r, l), self.result[r][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Gadget decomposition of a coefficient c into N digits of base w = 2^digit_bits, least
+// significant first, as key switching, relinearization and bootstrapping multiply the digits
+// of a ciphertext component with the rows of a key:
+//
+//     c = sum_i d_i * w^i,  0 <= d_i < w
+//
+// Every digit is held in the trace as its bits, bit j of digit i in column i * digit_bits + j
+// of the decomposition, which are constrained to be binary. The digits are linear combinations
+// of their bits, so they are range checked for free and can enter the constraints of the
+// circuit directly. A decomposition of N digits takes N * digit_bits columns and as many
+// constraints of degree 2 for the bits, followed by one linear constraint recomposing c. The
+// field has 128 bits, so c must have at most N * digit_bits < 128 bits, which the trace
+// builders check.
+
+use winter_air::TransitionConstraintDegree;
+use winter_math::FieldElement;
+
+use crate::air::{element_from_u64, BaseElement};
+
+// Base-w digits of `value`, least significant first.
+pub fn decompose<const N: usize>(value: u64, digit_bits: usize) -> [u64; N] {
+    assert!(
+        N * digit_bits >= 64 || value >> (N * digit_bits) == 0,
+        "{} does not fit into {} digits of {} bits",
+        value,
+        N,
+        digit_bits
+    );
+    std::array::from_fn(|i| {
+        let shift = i * digit_bits;
+        if shift >= 64 {
+            0
+        } else {
+            (value >> shift) & ((1 << digit_bits) - 1)
+        }
+    })
+}
+
+// Values of the bit columns of `value` decomposed into `bit_num` bits.
+pub fn bit_elements(value: u64, bit_num: usize) -> Vec<BaseElement> {
+    (0..bit_num)
+        .map(|b| element_from_u64(if b < 64 { (value >> b) & 1 } else { 0 }))
+        .collect()
+}
+
+// Degrees of the constraints of a decomposition into `digit_num` digits of `digit_bits` bits.
+pub fn decomposition_degrees(
+    digit_bits: usize,
+    digit_num: usize,
+) -> Vec<TransitionConstraintDegree> {
+    let mut degrees = vec![TransitionConstraintDegree::new(2); digit_bits * digit_num];
+    degrees.push(TransitionConstraintDegree::new(1));
+    degrees
+}
+
+// Constraint values of the decomposition of `value` into the bit columns `bits`, zero when the
+// bits are binary and recompose `value`; one per bit, then the recomposition. The digits are
+// written to `digits`.
+pub fn evaluate_decomposition<E: FieldElement + From<BaseElement>>(
+    bits: &[E],
+    value: E,
+    digit_bits: usize,
+    digits: &mut [E],
+    result: &mut [E],
+) {
+    let two = E::from(element_from_u64(2));
+    let base = E::from(element_from_u64(1 << digit_bits));
+    let mut recomposed = E::ZERO;
+    for i in (0..digits.len()).rev() {
+        digits[i] = E::ZERO;
+        for j in (0..digit_bits).rev() {
+            let b = i * digit_bits + j;
+            result[b] = bits[b] * bits[b] - bits[b];
+            digits[i] = digits[i] * two + bits[b];
+        }
+        recomposed = recomposed * base + digits[i];
+    }
+    result[digits.len() * digit_bits] = value - recomposed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits_recompose() {
+        for value in [0, 1, 1023, 1024, (1 << 50) - 1, 0x2_5a5a_5a5a_5a5a] {
+            let digits: [u64; 5] = decompose(value, 10);
+            assert!(digits.iter().all(|&d| d < 1 << 10));
+            let recomposed = digits.iter().rev().fold(0, |c, &d| (c << 10) + d);
+            assert_eq!(recomposed, value);
+
+            let mut bit_digits = [BaseElement::ZERO; 5];
+            let mut result = vec![BaseElement::ONE; 51];
+            evaluate_decomposition(
+                &bit_elements(value, 50),
+                element_from_u64(value),
+                10,
+                &mut bit_digits,
+                &mut result,
+            );
+            assert_eq!(bit_digits, digits.map(element_from_u64));
+            assert!(result.iter().all(|&r| r == BaseElement::ZERO));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit")]
+    fn decompose_rejects_wide_values() {
+        decompose::<5>(1 << 50, 10);
+    }
+
+    // A digit of w = 4 with the bits 0 and 2 recomposes 4, but the second bit is not binary.
+    #[test]
+    fn bits_are_range_checked() {
+        let bits = [BaseElement::ZERO, element_from_u64(2)];
+        let mut digits = [BaseElement::ZERO; 1];
+        let mut result = vec![BaseElement::ZERO; 3];
+        evaluate_decomposition(&bits, element_from_u64(4), 2, &mut digits, &mut result);
+        assert_eq!(result[2], BaseElement::ZERO);
+        assert_ne!(result[1], BaseElement::ZERO);
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Security of the HE parameters of `add`, checked against the tables of the Homomorphic
+// Encryption Standard (homomorphicencryption.org, 2018) for ternary secrets and classical
+// attackers, the ones SEAL enforces: a ring degree N admits a modulus chain of at most so many
+// bits at 128, 192 or 256 bits of security. The bits of a chain are the sum of the bit lengths
+// of its moduli, as SEAL counts them. Degrees between two powers of two only get the security
+// of the smaller one, and degrees below 1024 have none.
+//
+// The STARK proves the arithmetic of the ciphertexts whatever their parameters, so the prover
+// only warns about parameters below 128 bits, unless `--he-security` asks for a level, below
+// which it refuses to prove.
+
+use clap::ArgEnum;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+pub enum HeSecurity {
+    #[clap(name = "128")]
+    Bits128,
+    #[clap(name = "192")]
+    Bits192,
+    #[clap(name = "256")]
+    Bits256,
+}
+
+// Largest modulus bits at 128, 192 and 256 bits of security, per ring degree.
+const HE_STANDARD: [(usize, [u32; 3]); 6] = [
+    (1024, [27, 19, 14]),
+    (2048, [54, 37, 29]),
+    (4096, [109, 75, 58]),
+    (8192, [218, 152, 118]),
+    (16384, [438, 305, 237]),
+    (32768, [881, 611, 476]),
+];
+
+impl HeSecurity {
+    pub fn bits(&self) -> u32 {
+        match self {
+            HeSecurity::Bits128 => 128,
+            HeSecurity::Bits192 => 192,
+            HeSecurity::Bits256 => 256,
+        }
+    }
+
+    // Largest modulus bits at this level for ring degree `degree`, none below 1024.
+    pub fn max_modulus_bits(&self, degree: usize) -> Option<u32> {
+        HE_STANDARD
+            .iter()
+            .rev()
+            .find(|(n, _)| *n <= degree)
+            .map(|(_, bits)| bits[*self as usize])
+    }
+}
+
+pub fn modulus_bits(modulus: &[u64]) -> u32 {
+    modulus.iter().map(|m| 64 - m.leading_zeros()).sum()
+}
+
+// Checks that ring degree `degree` with the moduli `modulus` reaches `level`.
+pub fn check_he_security(degree: usize, modulus: &[u64], level: HeSecurity) -> Result<(), String> {
+    let bits = modulus_bits(modulus);
+    match level.max_modulus_bits(degree) {
+        None => Err(format!(
+            "ring degree {} has no {}-bit parameters in the HE standard, which starts at {}",
+            degree,
+            level.bits(),
+            HE_STANDARD[0].0
+        )),
+        Some(max) if bits > max => Err(format!(
+            "a modulus chain of {} bits at ring degree {} is below {} bits of HE security, \
+             which allows at most {} bits",
+            bits,
+            degree,
+            level.bits(),
+            max
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::Params;
+
+    #[test]
+    fn presets_reach_128_bits() {
+        for params in Params::value_variants() {
+            let (degree, modulus) = (params.coeff_degree(), params.modulus());
+            assert!(
+                check_he_security(degree, modulus, HeSecurity::Bits128).is_ok(),
+                "{}",
+                params.name()
+            );
+            assert!(check_he_security(degree, modulus, HeSecurity::Bits192).is_err());
+        }
+    }
+
+    #[test]
+    fn levels_follow_the_tables() {
+        // 2 * 30 bits
+        let modulus = [0x3fffc001, 0x3fff4001];
+        assert_eq!(modulus_bits(&modulus), 60);
+        assert!(check_he_security(2048, &modulus, HeSecurity::Bits128).is_err());
+        assert!(check_he_security(4096, &modulus, HeSecurity::Bits192).is_ok());
+        assert!(check_he_security(4096, &modulus, HeSecurity::Bits256).is_err());
+        // between two powers of two, the smaller one counts
+        assert!(check_he_security(6144, &modulus, HeSecurity::Bits256).is_err());
+        assert!(check_he_security(8192, &modulus, HeSecurity::Bits256).is_ok());
+        assert!(check_he_security(16, &modulus[..1], HeSecurity::Bits128).is_err());
+    }
+}
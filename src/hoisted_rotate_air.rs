@@ -0,0 +1,442 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Hoisted rotations. A key-switched rotation of (c0, c1) with Galois element g and a Galois key
+// (gk0_i, gk1_i) is
+//
+//     c0' = phi(c0) + sum_i phi(d_i) * gk0_i,  c1' = sum_i phi(d_i) * gk1_i
+//
+// for the automorphism phi: X -> X^g and the gadget digits d_i of c1. phi commutes with the
+// decomposition, so a batch of rotations of one ciphertext can share it: this circuit proves
+// HOIST_NUM rotations by j * k slots, j = 1..HOIST_NUM, for the step k given by --rotation,
+// with a single decomposition of c1 instead of one per rotation.
+//
+// Ciphertexts and keys are in evaluation form, where phi permutes the evaluation points: row k
+// evaluates at psi^p, p = 2 * rev(k) + 1, and phi(a) takes its value from the row of psi^(p * g).
+// As phi is a ring automorphism, the rotated ciphertext is phi(t) for the unrotated key switch
+//
+//     t0 = c0 + sum_i d_i * phi^-1(gk0_i),  t1 = sum_i d_i * phi^-1(gk1_i)
+//
+// which is row-local: a circuit row holds the bits of c1 and t of every rotation, as in
+// ReEncryptAir, whose decomposition and key format the rotations share. As there, t is reduced
+// by the Barrett blocks of reduction.rs against the moduli of the public inputs, one component
+// of one rotation and level per row of a row group. The keys of all rotations would not fit
+// into the trace next to the block, so every row holds the key digits of the component it
+// reduces only, which are asserted on that row; the sums of the other components a row computes
+// with them are not selected. The permutations only apply to public values: the t columns are
+// asserted against the results and the key columns against the keys, each permuted by phi^-1 of
+// its rotation, so the results are the rotated ciphertexts themselves, and the data columns
+// against the public input ciphertext, unpermuted. KeyRotateAir proves a single key-switched
+// automorphism the same way.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, element_to_u64, level_column, load_data_file, read_modulus, BaseElement,
+    CiphertextData, InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM,
+    VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::gadget::{bit_elements, decomposition_degrees, evaluate_decomposition};
+use crate::key_rotate_air::{automorphism_rows, permute, permute_key};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::re_encrypt_air::{
+    re_encrypt, re_encrypt_bound, RE_ENCRYPT_DIGIT_BITS, RE_ENCRYPT_DIGIT_NUM,
+    RE_ENCRYPT_MAX_MODULUS_BITS, RE_ENCRYPT_REDUCTION_WIDTH,
+};
+use crate::reduction::{
+    bind_moduli, check_public_moduli, read_group_result, reduction_group, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::rotate_air::{galois_element, galois_inverse, AutomorphismInput, ROTATION_NUM};
+
+// Modulus + Result + Data + Bits + Key + Reduction
+// M0 M1 R000 R001 R010 R011 R100 .. R311 C00 C01 C10 C11
+// B0_0..B0_49 B1_0..B1_49 K0..K4 K.. R.. S..
+pub const HOIST_NUM: usize = 4;
+pub const HOIST_RESULT_NUM: usize = HOIST_NUM * VALUE_NUM;
+// digits of the Galois keys of all rotations, those of rotation j from j * RE_ENCRYPT_DIGIT_NUM
+pub const HOIST_KEY_DIGIT_NUM: usize = HOIST_NUM * RE_ENCRYPT_DIGIT_NUM;
+const BIT_NUM: usize = RE_ENCRYPT_DIGIT_BITS * RE_ENCRYPT_DIGIT_NUM;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_LEN: usize = HOIST_NUM * DATA_LEN;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const BIT_START: usize = DATA_START + DATA_LEN;
+// the key digits of the component a row reduces
+const KEY_START: usize = BIT_START + BIT_NUM * COEFF_LEVEL;
+const KEY_END: usize = KEY_START + RE_ENCRYPT_DIGIT_NUM;
+// one row per result coefficient and level, then padding
+pub const HOIST_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = KEY_END + RE_ENCRYPT_REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+// binary bits and c1 recomposition, ahead of the reductions
+const DECOMPOSITION_CONSTRAINTS: usize = BIT_NUM * COEFF_LEVEL + COEFF_LEVEL;
+
+// Trace layout constants of HoistedRotateAir in canonical order.
+pub fn hoisted_rotate_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("hoist_num", HOIST_NUM),
+        ("value_num", VALUE_NUM),
+        ("digit_bits", RE_ENCRYPT_DIGIT_BITS),
+        ("digit_num", RE_ENCRYPT_DIGIT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", HOIST_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Column of component `v` of rotation `j` at level `l`; result component r = j * VALUE_NUM + v.
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn bit_column(l: usize, b: usize) -> usize {
+    BIT_START + l * BIT_NUM + b
+}
+
+fn key_column(i: usize) -> usize {
+    KEY_START + i
+}
+
+// Row of a group reducing result component `r` at level `l`, which holds the key digits of
+// rotation r / VALUE_NUM for the component and level.
+fn reduction_row(r: usize, l: usize) -> usize {
+    r * COEFF_LEVEL + l
+}
+
+// Rotation (j + 1) * step of result `j`, taken modulo the rotation group.
+pub fn hoisted_rotation(step: i32, j: usize) -> i32 {
+    ((step as i64).rem_euclid(ROTATION_NUM as i64) * (j as i64 + 1) % ROTATION_NUM as i64) as i32
+}
+
+// Rows of the rotations of `step`, phi for `inverse` false and phi^-1 otherwise.
+fn rotation_rows(step: i32, inverse: bool) -> Vec<Vec<usize>> {
+    (0..HOIST_NUM)
+        .map(|j| {
+            let rotation = hoisted_rotation(step, j);
+            automorphism_rows(if inverse {
+                galois_inverse(rotation)
+            } else {
+                galois_element(rotation)
+            })
+        })
+        .collect()
+}
+
+// The reductions of a row by the public moduli `modulus`, result component r at level l being
+// value r * COEFF_LEVEL + l of the row.
+pub fn hoisted_rotate_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::HoistedRotate, modulus, RE_ENCRYPT_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        HOIST_RESULT_NUM,
+        result_column,
+        |_, l| re_encrypt_bound(modulus[l]),
+        (RESULT_START..BIT_START).collect(),
+        KEY_END,
+        RE_ENCRYPT_REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_hoisted_rotate_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in evaluation form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let step = arg.rotation.ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("hoisted rotations need --rotation"))
+    })?;
+    assert!(
+        (step as i64).rem_euclid(ROTATION_NUM as i64) != 0,
+        "rotation by a multiple of {} slots is the identity",
+        ROTATION_NUM
+    );
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("hoisted rotations need --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    assert_eq!(
+        key.modulus, data.modulus,
+        "Galois key moduli differ from the data moduli"
+    );
+    assert_eq!(
+        key.values.len(),
+        HOIST_KEY_DIGIT_NUM,
+        "Galois keys need {} digits",
+        HOIST_KEY_DIGIT_NUM
+    );
+    check_modulus_bits(&data.modulus, RE_ENCRYPT_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, STATE_LENGTH, &data.values)?;
+    for digit in key.values.iter() {
+        check_levels(&key.modulus, STATE_LENGTH, digit)?;
+    }
+    let reductions =
+        hoisted_rotate_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let inverse_rows = rotation_rows(step, true);
+
+    // the key of rotation j at level l of row k, permuted by phi^-1 of the rotation
+    let row_key = |j: usize, l: usize, k: usize| -> [[u64; VALUE_NUM]; RE_ENCRYPT_DIGIT_NUM] {
+        std::array::from_fn(|i| {
+            std::array::from_fn(|v| {
+                key.values[j * RE_ENCRYPT_DIGIT_NUM + i][v][l][inverse_rows[j][k]]
+            })
+        })
+    };
+    let sums = |k: usize, l: usize| -> [u128; HOIST_RESULT_NUM] {
+        let c = [data.values[0][l][k], data.values[1][l][k]];
+        let sums: Vec<u128> = (0..HOIST_NUM)
+            .flat_map(|j| re_encrypt(c, row_key(j, l, k)))
+            .collect();
+        sums.try_into()
+            .expect("every rotation has VALUE_NUM components")
+    };
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; KEY_START];
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            let c = [data.values[0][l][k], data.values[1][l][k]];
+            for v in 0..VALUE_NUM {
+                columns[data_column(v, l)][k] = element_from_u64(c[v]);
+            }
+            for (b, bit) in bit_elements(c[1], BIT_NUM).into_iter().enumerate() {
+                columns[bit_column(l, b)][k] = bit;
+            }
+            for (r, sum) in sums(k, l).into_iter().enumerate() {
+                columns[result_column(r, l)][k] = element_from_u64((sum % m as u128) as u64);
+            }
+        }
+    }
+    // every row of a group holds the key digits of the component it reduces
+    let mut trace = reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        sums(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    });
+    for k in 0..STATE_LENGTH {
+        for r in 0..HOIST_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                let digits = row_key(r / VALUE_NUM, l, k);
+                let row = k * HOIST_GROUP + reduction_row(r, l);
+                for i in 0..RE_ENCRYPT_DIGIT_NUM {
+                    let value = element_from_u64(digits[i][r % VALUE_NUM]);
+                    trace.set(key_column(i), row, value);
+                }
+            }
+        }
+    }
+    Ok(trace)
+}
+
+// Digit `i` of the key of result component `r` at level `l` as the trace holds it, permuted by
+// phi^-1 of its rotation, read back from the rows reducing it.
+fn read_key_digit(trace: &TraceType, i: usize, r: usize, l: usize) -> Vec<u64> {
+    trace.get_column(key_column(i))[reduction_row(r, l)..]
+        .iter()
+        .step_by(HOIST_GROUP)
+        .map(|x| element_to_u64(*x))
+        .collect()
+}
+
+// The rotated ciphertexts and the Galois keys, read back from the trace and permuted by the
+// automorphisms of their rotations, and the input ciphertext.
+pub fn get_hoisted_rotate_pub_inputs(trace: &TraceType, step: i32) -> PublicInputs {
+    let rows = rotation_rows(step, false);
+    let mut result = read_group_result(trace, HOIST_RESULT_NUM, HOIST_GROUP, result_column);
+    for (r, levels) in result.iter_mut().enumerate() {
+        for values in levels.iter_mut() {
+            *values = permute(values, &rows[r / VALUE_NUM]);
+        }
+    }
+    let mut key = EvaluationKey {
+        modulus: read_modulus(trace),
+        values: vec![Default::default(); HOIST_KEY_DIGIT_NUM],
+    };
+    for (d, digit) in key.values.iter_mut().enumerate() {
+        let (j, i) = (d / RE_ENCRYPT_DIGIT_NUM, d % RE_ENCRYPT_DIGIT_NUM);
+        for (v, component) in digit.iter_mut().enumerate() {
+            for (l, values) in component.iter_mut().enumerate() {
+                *values = read_key_digit(trace, i, j * VALUE_NUM + v, l);
+            }
+        }
+    }
+    for (j, rows) in rows.iter().enumerate() {
+        let digits = j * RE_ENCRYPT_DIGIT_NUM..(j + 1) * RE_ENCRYPT_DIGIT_NUM;
+        permute_key(&mut key, digits, rows);
+    }
+    PublicInputs {
+        result,
+        evaluation_key: Some(key),
+        rotation: Some(step),
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: Some(AutomorphismInput::from_elements(read_group_result(
+            trace,
+            VALUE_NUM,
+            HOIST_GROUP,
+            data_column,
+        ))),
+    }
+}
+
+// Decomposition constraints of one row into `result` and the unrotated key switches with the
+// key digits of the row, result component r at level l at r * COEFF_LEVEL + l.
+fn evaluate_row<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    result: &mut [E],
+) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    let mut idx = 0;
+    for l in 0..COEFF_LEVEL {
+        let mut digits = [E::ZERO; RE_ENCRYPT_DIGIT_NUM];
+        evaluate_decomposition(
+            &row[bit_column(l, 0)..bit_column(l, BIT_NUM)],
+            row[data_column(1, l)],
+            RE_ENCRYPT_DIGIT_BITS,
+            &mut digits,
+            &mut result[idx..idx + BIT_NUM + 1],
+        );
+        idx += BIT_NUM + 1;
+
+        // the same for every rotation, whose key only the selected row holds
+        for v in 0..VALUE_NUM {
+            let mut sum = if v == 0 {
+                row[data_column(0, l)]
+            } else {
+                E::ZERO
+            };
+            for i in 0..RE_ENCRYPT_DIGIT_NUM {
+                sum += digits[i] * row[key_column(i)];
+            }
+            for j in 0..HOIST_NUM {
+                sums[reduction_row(j * VALUE_NUM + v, l)] = sum;
+            }
+        }
+    }
+    sums
+}
+
+pub struct HoistedRotateAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    galois_keys: EvaluationKey,
+    input: AutomorphismInput,
+    // phi^-1 of every rotation
+    inverse_rows: Vec<Vec<usize>>,
+}
+
+impl Air for HoistedRotateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The decomposition holds on every row; the sums are quadratic and selected by a periodic
+    // column.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("hoisted rotation proofs record their moduli");
+        let reductions =
+            hoisted_rotate_reductions(&modulus).expect("hoisted rotation moduli were checked");
+        let mut degrees = Vec::with_capacity(DECOMPOSITION_CONSTRAINTS);
+        for _ in 0..COEFF_LEVEL {
+            degrees.extend(decomposition_degrees(
+                RE_ENCRYPT_DIGIT_BITS,
+                RE_ENCRYPT_DIGIT_NUM,
+            ));
+        }
+        degrees.extend(reductions.degrees(2, &[]));
+        let step = pub_inputs
+            .rotation
+            .expect("hoisted rotation proofs need the rotation");
+        let num_assertions =
+            RESULT_LEN + RESULT_LEN * RE_ENCRYPT_DIGIT_NUM + MODULUS_ASSERTION_NUM + DATA_LEN;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        HoistedRotateAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            galois_keys: pub_inputs
+                .evaluation_key
+                .expect("hoisted rotations need the Galois keys"),
+            input: pub_inputs
+                .input
+                .expect("hoisted rotation proofs record their input ciphertext"),
+            inverse_rows: rotation_rows(step, true),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (decomposition, reduction) = result.split_at_mut(DECOMPOSITION_CONSTRAINTS);
+        let sums = evaluate_row(frame.current(), decomposition);
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, reduction);
+    }
+
+    // Results are bound on the first row of every group, the key digits of a component on the
+    // rows reducing it, each permuted back by phi^-1 of its rotation, the data columns to the
+    // input on the first row of every group and the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..HOIST_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                let values = permute(&self.result[r][l], &self.inverse_rows[r / VALUE_NUM]);
+                columns.push((result_column(r, l), values));
+            }
+        }
+        let mut assertions = self.reductions.bind_columns(columns);
+        for (d, digit) in self.galois_keys.values.iter().enumerate() {
+            let (j, i) = (d / RE_ENCRYPT_DIGIT_NUM, d % RE_ENCRYPT_DIGIT_NUM);
+            for (v, component) in digit.iter().enumerate() {
+                for (l, values) in component.iter().enumerate() {
+                    let values = permute(values, &self.inverse_rows[j]);
+                    let values = values.into_iter().map(element_from_u64).collect();
+                    let row = reduction_row(j * VALUE_NUM + v, l);
+                    assertions.push(Assertion::sequence(key_column(i), row, HOIST_GROUP, values));
+                }
+            }
+        }
+        assertions.extend(
+            self.reductions
+                .bind_columns(self.input.columns(data_column)),
+        );
+        assertions.extend(bind_moduli(&self.modulus, 0, self.reductions.group()));
+        assertions
+    }
+}
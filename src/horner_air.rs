@@ -0,0 +1,447 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Evaluation of a public polynomial a_0 + a_1 x + ... + a_d x^d on a ciphertext x by Horner's
+// rule, e.g. a polynomial approximation of an activation function in encrypted inference:
+//
+//     p_0 = a_d,  p_(j+1) = p_j * x + a_(d-j-1)
+//
+// Every step multiplies the accumulator by x with the tensor product of MulAir, so p_j has j + 1
+// components and the result d + 1, without relinearization; the coefficients are added to the
+// first component as given, so they must already be scaled as plaintexts, e.g. by Delta in BFV.
+// As in SumAir the steps run one per circuit row, coefficient k of step j in circuit row
+// k * P + P - d - 1 + j for the power of two P > d, and the rows before the first step hold
+// zeros, which leave the accumulator at zero. Every circuit row proves, for every component t
+// and level,
+//
+//     r_t = (p_t * x_0 + p_(t-1) * x_1 + [t = 0] a) mod m
+//
+// with the accumulator p of the previous circuit row and the coefficient a of the step, a
+// periodic column, reduced by the Barrett blocks of reduction.rs against the moduli of the
+// public inputs as in MulAir. The accumulator of the next circuit row is r unless the row is
+// the last of its coefficient, and x is the same in all circuit rows of a coefficient. The
+// accumulator has one component fewer than the result, as its last component only appears in
+// the last step. A polynomial of a lower degree d leaves the components above d of the result
+// and from d on of the accumulator zero: the steps do not read them, and they are neither
+// copied nor linked.
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+use winter_prover::Trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::inner_product_air::WEIGHT_MAX_BITS;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{
+    bind_moduli, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::sum_air::{link_column, sum_slots};
+
+// Modulus + Result + Partial + Data + Reduction
+// M0 M1 R00 R01 .. R71 P00 .. P61 X00 X01 X10 X11 K.. R.. S..
+pub const HORNER_MAX_DEGREE: usize = 7;
+pub const HORNER_RESULT_NUM: usize = HORNER_MAX_DEGREE + 1;
+const RESULT_LEN: usize = HORNER_RESULT_NUM * COEFF_LEVEL;
+const PARTIAL_LEN: usize = HORNER_MAX_DEGREE * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const PARTIAL_START: usize = RESULT_START + RESULT_LEN;
+const DATA_START: usize = PARTIAL_START + PARTIAL_LEN;
+const DATA_END: usize = DATA_START + DATA_LEN;
+// a middle component plus a coefficient reaches 2 (m - 1)^2 + a, so its quotient takes two bits
+// more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 2,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per component and level, then padding
+pub const HORNER_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+
+// Trace layout constants of HornerAir in canonical order. The trace length follows from the
+// degree, as for SumAir.
+pub fn horner_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("max_degree", HORNER_MAX_DEGREE),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", HORNER_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as CiphertextData, with the coefficients a_0..a_d of the polynomial in
+// `Coefficients`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HornerData {
+    pub modulus: Vec<u64>,
+    pub coefficients: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+fn result_column(t: usize, l: usize) -> usize {
+    level_column(RESULT_START, t, l)
+}
+
+fn partial_column(t: usize, l: usize) -> usize {
+    level_column(PARTIAL_START, t, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+// A polynomial of degree 1 to HORNER_MAX_DEGREE with coefficients of at most WEIGHT_MAX_BITS
+// bits; with `trace_length`, the trace must have the rows of its steps.
+pub fn check_coefficients(coefficients: &[u64], trace_length: Option<usize>) -> Result<(), String> {
+    if !(2..=HORNER_RESULT_NUM).contains(&coefficients.len()) {
+        return Err(format!(
+            "a polynomial evaluation has 2 to {} coefficients, not {}",
+            HORNER_RESULT_NUM,
+            coefficients.len()
+        ));
+    }
+    if let Some(&a) = coefficients
+        .iter()
+        .find(|a| 64 - a.leading_zeros() > WEIGHT_MAX_BITS)
+    {
+        return Err(format!(
+            "coefficient {} has more than {} bits",
+            a, WEIGHT_MAX_BITS
+        ));
+    }
+    let expected = COEFF_DEGREE * sum_slots(coefficients.len()) * HORNER_GROUP;
+    match trace_length {
+        Some(length) if length != expected => Err(format!(
+            "polynomials of {} coefficients are evaluated in {} rows, not {}",
+            coefficients.len(),
+            expected,
+            length
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Coefficients of a data file, which the prover records in the public inputs.
+pub fn load_coefficients(data_file_path: &str) -> Result<Vec<u64>, StarkHeError> {
+    let data: HornerData = load_data_file(data_file_path)?;
+    check_coefficients(&data.coefficients, None).map_err(StarkHeError::InvalidInput)?;
+    Ok(data.coefficients)
+}
+
+// Coefficient added in each row of a coefficient of the ciphertext, zero before the first step.
+fn step_coefficients(coefficients: &[u64], slots: usize) -> Vec<u64> {
+    let mut steps = vec![0; slots];
+    for (j, &a) in coefficients.iter().rev().enumerate() {
+        steps[slots - coefficients.len() + j] = a;
+    }
+    steps
+}
+
+// One Horner step at a level with modulus `m`: the components of p * x + a reduced by `m` and
+// before the reduction, for an accumulator `p` with one component fewer than the result.
+pub fn horner_step(
+    p: &[u64; HORNER_MAX_DEGREE],
+    x: [u64; VALUE_NUM],
+    a: u64,
+    m: u64,
+) -> ([u64; HORNER_RESULT_NUM], [u128; HORNER_RESULT_NUM]) {
+    let mut results = [0; HORNER_RESULT_NUM];
+    let mut sums = [0; HORNER_RESULT_NUM];
+    for t in 0..HORNER_RESULT_NUM {
+        let mut sum = if t == 0 { a as u128 } else { 0 };
+        if t < HORNER_MAX_DEGREE {
+            sum += p[t] as u128 * x[0] as u128;
+        }
+        if t > 0 {
+            sum += p[t - 1] as u128 * x[1] as u128;
+        }
+        results[t] = (sum % m as u128) as u64;
+        sums[t] = sum;
+    }
+    (results, sums)
+}
+
+// The reductions of a circuit row by the public moduli `modulus`, for the coefficients
+// `coefficients`, component t at level l being value t * COEFF_LEVEL + l of the row.
+pub fn horner_reductions(modulus: &[u64], coefficients: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Horner, modulus, MUL_MAX_MODULUS_BITS)?;
+    check_coefficients(coefficients, None)?;
+    let a = coefficients.iter().copied().max().unwrap_or(0) as u128;
+    // the d + 1 components of the result and the first d of the accumulator, then x
+    let components = coefficients.len();
+    let mut copied: Vec<usize> = (0..components)
+        .flat_map(|t| (0..COEFF_LEVEL).map(move |l| result_column(t, l)))
+        .collect();
+    copied.extend(
+        (0..components - 1).flat_map(|t| (0..COEFF_LEVEL).map(move |l| partial_column(t, l))),
+    );
+    copied.extend(DATA_START..DATA_END);
+    Reductions::of_components(
+        modulus,
+        HORNER_RESULT_NUM,
+        result_column,
+        |_, l| 2 * (modulus[l] as u128 - 1).pow(2) + a,
+        copied,
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_horner_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: HornerData = load_data_file(&arg.data_file_path)?;
+    check_coefficients(&data.coefficients, None).map_err(StarkHeError::InvalidInput)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, COEFF_DEGREE, &data.values)?;
+    let reductions =
+        horner_reductions(&data.modulus, &data.coefficients).map_err(StarkHeError::InvalidInput)?;
+    let slots = sum_slots(data.coefficients.len());
+    let steps = step_coefficients(&data.coefficients, slots);
+    let length = slots * COEFF_DEGREE;
+
+    let mut columns = vec![vec![BaseElement::ZERO; length]; DATA_END];
+    let mut values = vec![[0; RESULT_LEN]; length];
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); length];
+        for k in 0..COEFF_DEGREE {
+            let x = std::array::from_fn(|v| data.values[v][l][k]);
+            let mut p = [0; HORNER_MAX_DEGREE];
+            for i in 0..slots {
+                let row = k * slots + i;
+                let (results, sums) = horner_step(&p, x, steps[i], m);
+                for t in 0..HORNER_RESULT_NUM {
+                    columns[result_column(t, l)][row] = element_from_u64(results[t]);
+                    values[row][t * COEFF_LEVEL + l] = sums[t];
+                }
+                for t in 0..HORNER_MAX_DEGREE {
+                    columns[partial_column(t, l)][row] = element_from_u64(p[t]);
+                }
+                for v in 0..VALUE_NUM {
+                    columns[data_column(v, l)][row] = element_from_u64(x[v]);
+                }
+                p.copy_from_slice(&results[..HORNER_MAX_DEGREE]);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// The results are the d + 1 components of the last circuit row of every coefficient; the public
+// inputs also hold the coefficients and the moduli.
+pub fn get_horner_pub_inputs(trace: &TraceType, coefficients: Vec<u64>) -> PublicInputs {
+    let slots = trace.length() / (COEFF_DEGREE * HORNER_GROUP);
+    let result = (0..coefficients.len())
+        .map(|t| {
+            (0..COEFF_LEVEL)
+                .map(|l| {
+                    (0..COEFF_DEGREE)
+                        .map(|k| {
+                            trace.get(result_column(t, l), (k * slots + slots - 1) * HORNER_GROUP)
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: Some(coefficients),
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The components p * x + a of one row with coefficient `a` for a polynomial of `components`
+// coefficients, component t at level l at t * COEFF_LEVEL + l; those above its degree are zero.
+fn step_values<E: FieldElement>(row: &[E], a: E, components: usize) -> [E; RESULT_LEN] {
+    let mut values = [E::ZERO; RESULT_LEN];
+    for l in 0..COEFF_LEVEL {
+        let x = [row[data_column(0, l)], row[data_column(1, l)]];
+        for t in 0..components {
+            let mut sum = if t == 0 { a } else { E::ZERO };
+            if t + 1 < components {
+                sum += row[partial_column(t, l)] * x[0];
+            }
+            if t > 0 {
+                sum += row[partial_column(t - 1, l)] * x[1];
+            }
+            values[t * COEFF_LEVEL + l] = sum;
+        }
+    }
+    values
+}
+
+pub struct HornerAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    coefficients: Vec<u64>,
+    slots: usize,
+}
+
+impl Air for HornerAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, then the links of the accumulator and of x as in SumAir.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = trace_info.length() / (COEFF_DEGREE * HORNER_GROUP);
+        let modulus = pub_inputs
+            .modulus
+            .expect("horner proofs record their moduli");
+        let coefficients = pub_inputs.weights.unwrap_or_default();
+        let reductions = horner_reductions(&modulus, &coefficients)
+            .expect("horner moduli and coefficients were checked");
+        let mut degrees = reductions.degrees(2, &[]);
+        let link = TransitionConstraintDegree::with_cycles(1, vec![slots * HORNER_GROUP]);
+        let partials = (coefficients.len() - 1) * COEFF_LEVEL;
+        degrees.extend(vec![link; partials + DATA_LEN]);
+        let num_assertions = partials + coefficients.len() * COEFF_LEVEL + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        HornerAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            coefficients,
+            slots,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let flags = &periodic_values[self.reductions.num_periodic_columns()..];
+        let (a, link) = (flags[0], flags[1]);
+        let (reduction, links) = result.split_at_mut(self.reductions.num_constraints());
+        let components = self.coefficients.len();
+        let values = step_values(frame.current(), a, components);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        let (current, next) = (frame.current(), frame.next());
+        let (partial_links, data_links) = links.split_at_mut((components - 1) * COEFF_LEVEL);
+        for t in 0..components - 1 {
+            for l in 0..COEFF_LEVEL {
+                partial_links[t * COEFF_LEVEL + l] =
+                    link * (next[partial_column(t, l)] - current[result_column(t, l)]);
+            }
+        }
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                data_links[v * COEFF_LEVEL + l] =
+                    link * (next[data_column(v, l)] - current[data_column(v, l)]);
+            }
+        }
+    }
+
+    // The coefficients of the circuit rows of one coefficient over their groups, then the flags
+    // of the links.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let steps: Vec<BaseElement> = step_coefficients(&self.coefficients, self.slots)
+            .into_iter()
+            .map(element_from_u64)
+            .collect();
+        let mut columns = self.reductions.periodic_columns();
+        columns.push(self.reductions.stretch(&steps));
+        columns.push(link_column(self.slots, HORNER_GROUP));
+        columns
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let cycle = self.slots * HORNER_GROUP;
+        let mut assertions = Vec::with_capacity(self.context.num_assertions());
+        for t in 0..self.coefficients.len() - 1 {
+            for l in 0..COEFF_LEVEL {
+                assertions.push(Assertion::periodic(
+                    partial_column(t, l),
+                    0,
+                    cycle,
+                    BaseElement::ZERO,
+                ));
+            }
+        }
+        for (t, levels) in self.result.iter().enumerate() {
+            for l in 0..COEFF_LEVEL {
+                assertions.push(Assertion::sequence(
+                    result_column(t, l),
+                    (self.slots - 1) * HORNER_GROUP,
+                    cycle,
+                    levels[l].clone(),
+                ));
+            }
+        }
+        assertions.extend(bind_moduli(&self.modulus, 0, cycle));
+        assertions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULUS: u64 = 65537;
+
+    // (x0 + x1 s) evaluated by 3 + 2X + X^2 expands to the components of p(x) in powers of s
+    #[test]
+    fn steps_expand_the_polynomial() {
+        let (x, coefficients) = ([5, 7], [3, 2, 1]);
+        let slots = sum_slots(coefficients.len());
+        let mut p = [0; HORNER_MAX_DEGREE];
+        for &a in step_coefficients(&coefficients, slots).iter() {
+            let (results, _) = horner_step(&p, x, a, MODULUS);
+            p.copy_from_slice(&results[..HORNER_MAX_DEGREE]);
+        }
+        // 3 + 2 (x0 + x1 s) + (x0 + x1 s)^2
+        assert_eq!(&p[..4], &[3 + 10 + 25, 14 + 70, 49, 0]);
+    }
+
+    #[test]
+    fn coefficients_are_checked() {
+        assert!(check_coefficients(&[1], None).is_err());
+        assert!(check_coefficients(&[1; HORNER_RESULT_NUM + 1], None).is_err());
+        assert!(check_coefficients(&[1, 1 << 62], None).is_err());
+        assert!(check_coefficients(&[1, 2, 3], Some(COEFF_DEGREE * 4 * HORNER_GROUP)).is_ok());
+        assert!(check_coefficients(&[1, 2, 3], Some(COEFF_DEGREE * 8 * HORNER_GROUP)).is_err());
+        assert!(check_coefficients(&[1, 2, 3], Some(COEFF_DEGREE * 4)).is_err());
+    }
+}
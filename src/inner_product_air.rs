@@ -0,0 +1,354 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Inner product w_0 * d_0 + ... + w_(N-1) * d_(N-1) of N ciphertexts with a public plaintext
+// vector of integer weights, e.g. a neuron of an encrypted inference. As in SumAir the operands
+// are added one per circuit row in a running sum, coefficient k of operand i in circuit row
+// k * P + i for the
+// power of two P >= N, with zero weights in the rows of the missing operands. Every circuit row
+// proves, for both components and every level,
+//
+//     r = (p + w * d) mod m
+//
+// with the partial sum p of the previous products, reduced by the Barrett blocks of
+// reduction.rs against the moduli of the public inputs as in SumAir. The weights are periodic
+// columns, so they are public without a trace column. Weights and moduli have at most 62 bits,
+// so p + w * d stays far below the field modulus.
+//
+// Only the BLAKE3 digest of the result is a public input: the verifier gets the ciphertext itself
+// from a separate file, as the evaluation keys of relinearization proofs, and checks it against
+// the digest before its coefficients enter the assertions.
+
+use std::fs;
+use std::path::Path;
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+use winter_prover::Trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, element_to_u64, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::canonical::{commitment, CanonicalWriter, RESULT_TAG};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{
+    bind_moduli, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::sum_air::{
+    evaluate_links, link_column, link_degrees, read_sums, result_column, sum_assertions, sum_slots,
+    weighted_sum_values, RunningSum, SUM_DATA_END, SUM_MAX_OPERANDS,
+};
+
+// Columns of SumAir + Reduction
+// M0 M1 R00 R01 R10 R11 P00 P01 P10 P11 D00 D01 D10 D11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+
+// Largest weight, as for the moduli.
+pub const WEIGHT_MAX_BITS: u32 = MUL_MAX_MODULUS_BITS;
+
+// p + w * d is below (w + 1) * m, so its quotient at most the weight
+const REDUCTION_WIDTH: usize =
+    reduction_width(WEIGHT_MAX_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per sum and level, then padding
+pub const INNER_PRODUCT_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = SUM_DATA_END + REDUCTION_WIDTH;
+
+// Trace layout constants of InnerProductAir in canonical order. The trace length follows from
+// the number of weights, as for SumAir.
+pub fn inner_product_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("max_operands", SUM_MAX_OPERANDS),
+        ("group", INNER_PRODUCT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as SumData, with one weight per operand in `Weights`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InnerProductData {
+    pub modulus: Vec<u64>,
+    pub weights: Vec<u64>,
+    pub values: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+// 2 to SUM_MAX_OPERANDS weights of at most WEIGHT_MAX_BITS bits; with `trace_length`, the
+// trace must have the rows of that many operands.
+pub fn check_weights(weights: &[u64], trace_length: Option<usize>) -> Result<(), String> {
+    if !(2..=SUM_MAX_OPERANDS).contains(&weights.len()) {
+        return Err(format!(
+            "an inner product has 2 to {} weights, not {}",
+            SUM_MAX_OPERANDS,
+            weights.len()
+        ));
+    }
+    if let Some(&w) = weights
+        .iter()
+        .find(|w| 64 - w.leading_zeros() > WEIGHT_MAX_BITS)
+    {
+        return Err(format!(
+            "weight {} has more than {} bits",
+            w, WEIGHT_MAX_BITS
+        ));
+    }
+    let expected = COEFF_DEGREE * sum_slots(weights.len()) * INNER_PRODUCT_GROUP;
+    match trace_length {
+        Some(length) if length != expected => Err(format!(
+            "inner products of {} weights have {} rows, not {}",
+            weights.len(),
+            expected,
+            length
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Weights of a data file, which the prover records in the public inputs.
+pub fn load_weights(data_file_path: &str) -> Result<Vec<u64>, StarkHeError> {
+    let data: InnerProductData = load_data_file(data_file_path)?;
+    check_weights(&data.weights, None).map_err(StarkHeError::InvalidInput)?;
+    Ok(data.weights)
+}
+
+// Layout: the number of result components and COEFF_LEVEL as u32, then every result vector
+// ordered by component index, then level index, as in the public inputs.
+pub fn result_digest(result: &[Vec<Vec<BaseElement>>]) -> [u8; 32] {
+    let mut writer = CanonicalWriter::new(RESULT_TAG);
+    writer.write_u32(result.len() as u32);
+    writer.write_u32(COEFF_LEVEL as u32);
+    for value in result.iter() {
+        for level in value.iter() {
+            writer.write_elements(level);
+        }
+    }
+    commitment(&writer.into_bytes())
+}
+
+// Storage key of the result ciphertext of the proof stored under `proof_key`, e.g.
+// `x.result.toml` next to `x.proof`.
+pub fn result_key(proof_key: &str) -> String {
+    Path::new(proof_key)
+        .with_extension("result.toml")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Result of an inner product proven from `data_file_path` with the moduli of the proof, in the
+// format of a data file, so that it can be the operand of the next operation.
+pub fn result_text(
+    data_file_path: &str,
+    pub_inputs: &PublicInputs,
+) -> Result<String, StarkHeError> {
+    let modulus = match &pub_inputs.modulus {
+        Some(modulus) => modulus.clone(),
+        None => load_data_file::<InnerProductData>(data_file_path)?.modulus,
+    };
+    let mut values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
+    for (v, levels) in pub_inputs.result.iter().enumerate() {
+        values[v] =
+            std::array::from_fn(|l| levels[l].iter().copied().map(element_to_u64).collect());
+    }
+    let result = CiphertextData { modulus, values };
+    Ok(toml::to_string(&result).expect("ciphertexts serialize to TOML"))
+}
+
+// The moduli and the coefficients of a result ciphertext.
+type ResultCiphertext = (Vec<u64>, Vec<Vec<Vec<BaseElement>>>);
+
+// Reads a result ciphertext written from result_text, its moduli and its coefficients; the
+// verifier checks the moduli against those of the proof.
+pub fn load_result(path: &str) -> Result<ResultCiphertext, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    let data: CiphertextData =
+        toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))?;
+    for (v, levels) in data.values.iter().enumerate() {
+        for (l, values) in levels.iter().enumerate() {
+            if values.len() != COEFF_DEGREE {
+                return Err(format!(
+                    "{}: component {} level {} has {} coefficients, expected {}",
+                    path,
+                    v,
+                    l,
+                    values.len(),
+                    COEFF_DEGREE
+                ));
+            }
+        }
+    }
+    let values = data
+        .values
+        .into_iter()
+        .map(|levels| {
+            levels
+                .into_iter()
+                .map(|x| x.into_iter().map(element_from_u64).collect())
+                .collect()
+        })
+        .collect();
+    Ok((data.modulus, values))
+}
+
+// The reductions of a row by the public moduli `modulus`, for products with the weights
+// `weights`, component v at level l being value v * COEFF_LEVEL + l of the row.
+pub fn inner_product_reductions(modulus: &[u64], weights: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::InnerProduct, modulus, MUL_MAX_MODULUS_BITS)?;
+    let w = weights.iter().copied().max().unwrap_or(0) as u128;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| (w + 1) * (modulus[l] as u128 - 1),
+        (MODULUS_NUM..SUM_DATA_END).collect(),
+        SUM_DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_inner_product_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: InnerProductData = load_data_file(&arg.data_file_path)?;
+    check_weights(&data.weights, None).map_err(StarkHeError::InvalidInput)?;
+    if data.values.len() != data.weights.len() {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "an inner product needs one weight per operand, found {} weights for {} operands",
+            data.weights.len(),
+            data.values.len()
+        )));
+    }
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, COEFF_DEGREE, operand)?;
+    }
+    let reductions = inner_product_reductions(&data.modulus, &data.weights)
+        .map_err(StarkHeError::InvalidInput)?;
+    let slots = sum_slots(data.values.len());
+
+    let mut sum = RunningSum::new(&data.modulus, slots, SUM_DATA_END);
+    for i in 0..slots {
+        let w = data.weights.get(i).copied().unwrap_or(0);
+        sum.add_weighted(&data.modulus, slots, i, w, data.values.get(i));
+    }
+    let values = sum.values;
+    Ok(reductions.build_trace(sum.columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// The results are the running sums of the last circuit row of every coefficient; the public
+// inputs commit to their digest, to the weights and to the moduli.
+pub fn get_inner_product_pub_inputs(trace: &TraceType, weights: Vec<u64>) -> PublicInputs {
+    let slots = trace.length() / (COEFF_DEGREE * INNER_PRODUCT_GROUP);
+    let result = read_sums(trace, slots, INNER_PRODUCT_GROUP, result_column);
+    let digest = result_digest(&result);
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: Some(weights),
+        result_digest: Some(digest),
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct InnerProductAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    weights: Vec<u64>,
+    slots: usize,
+}
+
+impl Air for InnerProductAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, whose values carry the weight column of a coefficient, then
+    // the links of the running sum as in SumAir.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = trace_info.length() / (COEFF_DEGREE * INNER_PRODUCT_GROUP);
+        let modulus = pub_inputs
+            .modulus
+            .expect("inner product proofs record their moduli");
+        let weights = pub_inputs.weights.unwrap_or_default();
+        let reductions = inner_product_reductions(&modulus, &weights)
+            .expect("inner product moduli and weights were checked");
+        let mut degrees = reductions.degrees(1, &[slots * INNER_PRODUCT_GROUP]);
+        degrees.extend(link_degrees(slots, INNER_PRODUCT_GROUP));
+        let num_assertions = 2 * RESULT_LEN + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        InnerProductAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            weights,
+            slots,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let flags = &periodic_values[self.reductions.num_periodic_columns()..];
+        let (weight, link) = (flags[0], flags[1]);
+        let (reduction, links) = result.split_at_mut(self.reductions.num_constraints());
+        let values = weighted_sum_values(frame.current(), weight);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        evaluate_links(frame, link, links);
+    }
+
+    // The weights of the circuit rows of one coefficient over their groups, then the flags of
+    // the links.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut weights = vec![BaseElement::ZERO; self.slots];
+        for (i, &w) in self.weights.iter().take(self.slots).enumerate() {
+            weights[i] = element_from_u64(w);
+        }
+        let mut columns = self.reductions.periodic_columns();
+        columns.push(self.reductions.stretch(&weights));
+        columns.push(link_column(self.slots, INNER_PRODUCT_GROUP));
+        columns
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut assertions =
+            sum_assertions(self.slots, INNER_PRODUCT_GROUP, &self.result, result_column);
+        assertions.extend(bind_moduli(
+            &self.modulus,
+            0,
+            self.slots * INNER_PRODUCT_GROUP,
+        ));
+        assertions
+    }
+}
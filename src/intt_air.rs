@@ -0,0 +1,453 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Inverse negacyclic NTT of both components of a ciphertext, from evaluation form back into
+// coefficient form, so that products computed by MulAir or PlainMulAir can be brought back for
+// decryption. The transform is SEAL's in-place Gentleman-Sande network, the mirror image of the
+// forward one in ntt_air.rs: stage s = 0..log2(N) - 1 with t = 2^s and m = N / 2t runs
+//
+//     (a[j], a[j + t]) <- (u * (a[j] + a[j + t]), u * w * (a[j] - a[j + t])) mod q
+//
+// for j = 2it..2it + t - 1 and w = psi^-bitrev(m + i). The scaling by N^-1 mod q is folded into
+// the last stage: u is N^-1 there and 1 on every other stage.
+//
+// The circuit rows, the padding, the row groups and the wiring argument are those of NttAir. A
+// butterfly proves
+//
+//     X = U * (A + B) mod q,  Y = V * (A - B + q) mod q
+//
+// with the periodic columns U = u and V = u * w, both values reduced by the Barrett blocks of
+// reduction.rs against the public moduli. They stay below 2 * q^2, so their quotients take one
+// bit more than the modulus. The outputs of the last stage, circuit rows 16b + 11, are a[b] and
+// a[b + N/2] and are bound to the result by sequence assertions.
+
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, BaseElement, CiphertextData, InputArg,
+    PublicInputs, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::{
+    bit_reverse, butterfly, check_ntt_modulus, evaluate_wiring, minimal_primitive_root, mul_mod,
+    pow_mod, wiring_assertions, wiring_degrees, wiring_periodic_columns, NttTrace, BUTTERFLY_NUM,
+    NTT_STAGE_NUM, NTT_TRACE_LENGTH, STAGE_SLOTS, WIRING_PERIODIC_NUM,
+};
+use crate::reduction::{reduction_group, reduction_width, Reductions};
+
+// Inputs + Outputs, one column per polynomial each, + Reduction
+// A00 A01 A10 A11 B.. X.. Y.. K.. R.. S..
+const POLY_NUM: usize = VALUE_NUM * COEFF_LEVEL;
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + POLY_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + POLY_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + POLY_NUM;
+const OUTPUT_END: usize = OUTPUT_Y_START + POLY_NUM;
+// the sum and the difference of every polynomial
+const REDUCTION_NUM: usize = 2 * POLY_NUM;
+// both values stay below 2 * q^2, so their quotients take a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per value, then padding
+pub const INTT_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = OUTPUT_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = NTT_TRACE_LENGTH;
+pub const INTT_AUX_WIDTH: usize = POLY_NUM;
+const AUX_WIDTH: usize = INTT_AUX_WIDTH;
+pub const LAST_STAGE: usize = NTT_STAGE_NUM - 1;
+
+// Periodic columns after the wiring ones: the stage scale u of every level, with a period of
+// one circuit row per stage slot, and the twiddles u * w of every level over the whole trace.
+// Those of the reductions follow.
+pub const SCALE_START: usize = WIRING_PERIODIC_NUM;
+pub const TWIDDLE_START: usize = SCALE_START + COEFF_LEVEL;
+pub const INTT_PERIODIC_NUM: usize = TWIDDLE_START + COEFF_LEVEL;
+
+// Trace layout constants of InttAir in canonical order.
+pub fn intt_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("group", INTT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn input_a_column(v: usize, l: usize) -> usize {
+    level_column(INPUT_A_START, v, l)
+}
+
+fn input_b_column(v: usize, l: usize) -> usize {
+    level_column(INPUT_B_START, v, l)
+}
+
+fn output_x_column(v: usize, l: usize) -> usize {
+    level_column(OUTPUT_X_START, v, l)
+}
+
+fn output_y_column(v: usize, l: usize) -> usize {
+    level_column(OUTPUT_Y_START, v, l)
+}
+
+// Output X (h = 0) or Y (h = 1) of component v at level l.
+fn output_column(h: usize, v: usize, l: usize) -> usize {
+    level_column([OUTPUT_X_START, OUTPUT_Y_START][h], v, l)
+}
+
+// Stage scales u: 1, and N^-1 mod q on the last stage.
+fn stage_scales(q: u64) -> [u64; STAGE_SLOTS] {
+    let mut scales = [0; STAGE_SLOTS];
+    scales[..NTT_STAGE_NUM].fill(1);
+    scales[LAST_STAGE] = pow_mod(COEFF_DEGREE as u64, q - 2, q);
+    scales
+}
+
+// psi^-bitrev(k) for k = 0..N, indexed by m + i in the butterfly network.
+fn inverse_twiddles(q: u64) -> Vec<u64> {
+    let psi = minimal_primitive_root(q).expect("moduli are checked before use");
+    let psi_inverse = pow_mod(psi, 2 * COEFF_DEGREE as u64 - 1, q);
+    let mut powers = vec![1; COEFF_DEGREE];
+    for k in 1..COEFF_DEGREE {
+        powers[k] = mul_mod(powers[k - 1], psi_inverse, q);
+    }
+    (0..COEFF_DEGREE).map(|k| powers[bit_reverse(k)]).collect()
+}
+
+// Columns A, B, X, Y of the network of component `v` at level `l`.
+fn network_columns(v: usize, l: usize) -> [usize; 4] {
+    [
+        input_a_column(v, l),
+        input_b_column(v, l),
+        output_x_column(v, l),
+        output_y_column(v, l),
+    ]
+}
+
+// Wiring columns of InttAir, in polynomial order.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    let mut wiring = Vec::with_capacity(POLY_NUM);
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            wiring.push(network_columns(v, l));
+        }
+    }
+    wiring
+}
+
+// The reductions of a butterfly by the public moduli `modulus`: the sum of component v at level
+// l is value v * COEFF_LEVEL + l of a row, reduced to X, its difference value
+// POLY_NUM + v * COEFF_LEVEL + l, reduced to Y.
+pub fn intt_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_ntt_modulus(modulus)?;
+    Reductions::of_components(
+        modulus,
+        2 * VALUE_NUM,
+        |c, l| output_column(c / VALUE_NUM, c % VALUE_NUM, l),
+        |_, l| 2 * (modulus[l] as u128).pow(2),
+        (INPUT_A_START..OUTPUT_END).collect(),
+        OUTPUT_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Runs the network on the evaluations `a` modulo `q`, filling its rows of the `network` columns
+// A, B, X, Y, and returns the coefficients with the values every row reduces to X and Y, zero on
+// the padding rows.
+pub fn fill_inverse_network(
+    columns: &mut [Vec<BaseElement>],
+    network: [usize; 4],
+    mut a: Vec<u64>,
+    q: u64,
+) -> (Vec<u64>, Vec<[u128; 2]>) {
+    let [a_column, b_column, x_column, y_column] = network;
+    let scales = stage_scales(q);
+    let twiddles = inverse_twiddles(q);
+    let mut values = vec![[0; 2]; STATE_LENGTH];
+    for s in 0..NTT_STAGE_NUM {
+        let u = scales[s];
+        for b in 0..BUTTERFLY_NUM {
+            let row = b * STAGE_SLOTS + s;
+            let (_, j, t, w) = butterfly(row, true).unwrap();
+            let (x, y) = (a[j], a[j + t]);
+            let sum = u as u128 * (x + y) as u128;
+            let difference = mul_mod(twiddles[w], u, q) as u128 * (x + q - y) as u128;
+            a[j] = (sum % q as u128) as u64;
+            a[j + t] = (difference % q as u128) as u64;
+
+            columns[a_column][row] = element_from_u64(x);
+            columns[b_column][row] = element_from_u64(y);
+            columns[x_column][row] = element_from_u64(a[j]);
+            columns[y_column][row] = element_from_u64(a[j + t]);
+            values[row] = [sum, difference];
+        }
+    }
+    (a, values)
+}
+
+pub fn build_intt_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let reductions = intt_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; OUTPUT_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    for l in 0..COEFF_LEVEL {
+        let q = data.modulus[l];
+        for v in 0..VALUE_NUM {
+            let a = data.values[v][l].clone();
+            assert!(
+                a.len() == COEFF_DEGREE && a.iter().all(|&c| c < q),
+                "operand coefficients must be {} residues modulo {}",
+                COEFF_DEGREE,
+                q
+            );
+            let (_, network_values) =
+                fill_inverse_network(&mut columns, network_columns(v, l), a, q);
+            for (row, [sum, difference]) in network_values.into_iter().enumerate() {
+                values[row][v * COEFF_LEVEL + l] = sum;
+                values[row][POLY_NUM + v * COEFF_LEVEL + l] = difference;
+            }
+        }
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        data.modulus,
+        wiring_columns(),
+        true,
+        INTT_GROUP,
+    ))
+}
+
+pub fn get_intt_pub_inputs(trace: &NttTrace) -> PublicInputs {
+    let main = trace.main_segment();
+    let mut result = vec![vec![Vec::new(); COEFF_LEVEL]; VALUE_NUM];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let rows = (0..BUTTERFLY_NUM).map(|b| (b * STAGE_SLOTS + LAST_STAGE) * INTT_GROUP);
+            let mut values: Vec<BaseElement> = rows
+                .clone()
+                .map(|row| main.get(output_x_column(v, l), row))
+                .collect();
+            values.extend(rows.map(|row| main.get(output_y_column(v, l), row)));
+            result[v][l] = values;
+        }
+    }
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus().to_vec()),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The wiring columns, the stage scales and the twiddles of every level, of a network whose
+// circuit rows are repeated over groups of `group` rows.
+pub fn intt_periodic_columns(modulus: &[u64], group: usize) -> Vec<Vec<BaseElement>> {
+    let mut scale_columns = vec![vec![BaseElement::ZERO; STAGE_SLOTS]; COEFF_LEVEL];
+    let mut twiddle_columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; COEFF_LEVEL];
+    for l in 0..COEFF_LEVEL {
+        let q = modulus[l];
+        let scales = stage_scales(q);
+        let twiddles = inverse_twiddles(q);
+        for s in 0..STAGE_SLOTS {
+            scale_columns[l][s] = element_from_u64(scales[s]);
+        }
+        for row in 0..STATE_LENGTH {
+            if let Some((s, _, _, w)) = butterfly(row, true) {
+                twiddle_columns[l][row] = element_from_u64(mul_mod(twiddles[w], scales[s], q));
+            }
+        }
+    }
+    let stretch = |column: Vec<BaseElement>| -> Vec<BaseElement> {
+        column
+            .into_iter()
+            .flat_map(|value| vec![value; group])
+            .collect()
+    };
+    let mut columns = wiring_periodic_columns(true, group);
+    columns.extend(scale_columns.into_iter().map(stretch));
+    columns.extend(twiddle_columns.into_iter().map(stretch));
+    columns
+}
+
+// The values U * (A + B) and V * (A - B + q) the butterfly in the `network` columns reduces to X
+// and Y, with the stage scale U and the twiddle V of its level.
+pub fn butterfly_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    network: [usize; 4],
+    scale: E,
+    twiddle: E,
+    q: E,
+) -> [E; 2] {
+    let [a, b, _, _] = network.map(|column| row[column]);
+    [scale * (a + b), twiddle * (a - b + q)]
+}
+
+// The values a row reduces, in the order of intt_reductions.
+fn row_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    scales: &[E],
+    twiddles: &[E],
+    modulus: &[u64],
+) -> [E; REDUCTION_NUM] {
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for l in 0..COEFF_LEVEL {
+        let q = E::from(element_from_u64(modulus[l]));
+        for v in 0..VALUE_NUM {
+            let [sum, difference] =
+                butterfly_values(row, network_columns(v, l), scales[l], twiddles[l], q);
+            values[v * COEFF_LEVEL + l] = sum;
+            values[POLY_NUM + v * COEFF_LEVEL + l] = difference;
+        }
+    }
+    values
+}
+
+pub struct InttAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    modulus: Vec<u64>,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl Air for InttAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in NttAir only the current row is checked; the values are linear in the trace under the
+    // twiddles, which run over the whole trace.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("inverse NTT proofs publish their moduli");
+        let reductions = intt_reductions(&modulus).expect("inverse NTT moduli were checked");
+        let degrees = reductions.degrees(1, &[trace_info.length()]);
+        let aux_degrees = wiring_degrees(AUX_WIDTH, reductions.group());
+        let num_assertions = 2 * POLY_NUM;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        InttAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            result: pub_inputs.result,
+            modulus,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let values = row_values(
+            frame.current(),
+            &periodic_values[SCALE_START..TWIDDLE_START],
+            &periodic_values[TWIDDLE_START..INTT_PERIODIC_NUM],
+            &self.modulus,
+        );
+        self.reductions.evaluate(
+            frame,
+            &periodic_values[INTT_PERIODIC_NUM..],
+            &values,
+            result,
+        );
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The last stage is bound on the first row of its groups.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let group = self.reductions.group();
+        let mut assertions = Vec::with_capacity(2 * POLY_NUM);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let (low, high) = self.result[v][l].split_at(BUTTERFLY_NUM);
+                assertions.push(Assertion::sequence(
+                    output_x_column(v, l),
+                    LAST_STAGE * group,
+                    STAGE_SLOTS * group,
+                    low.to_vec(),
+                ));
+                assertions.push(Assertion::sequence(
+                    output_y_column(v, l),
+                    LAST_STAGE * group,
+                    STAGE_SLOTS * group,
+                    high.to_vec(),
+                ));
+            }
+        }
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut columns = intt_periodic_columns(&self.modulus, self.reductions.group());
+        columns.extend(self.reductions.periodic_columns());
+        columns
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Key-switched conjugation of the slots of a CKKS ciphertext in evaluation form: the
+// conjugation of ConjugateAir, g = 2N - 1, followed by the key switch with its automorphism
+// key, so that the result decrypts under the original secret. The trace and constraints are
+// KeyRotateAir's with the Galois element of the conjugation, which is fixed, so the public
+// inputs hold only the results and the key.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{load_data_file, BaseElement, CiphertextData, InputArg, PublicInputs, TraceType};
+use crate::conjugate_air::CONJUGATION_ELEMENT;
+use crate::error::StarkHeError;
+use crate::key_rotate_air::{
+    build_automorphism_key_trace, get_automorphism_key_pub_inputs, KeyRotateAir,
+};
+use crate::keyswitch_air::load_evaluation_key;
+
+pub fn build_key_conjugate_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in evaluation form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from(
+            "key-switched conjugation needs --evaluation-key",
+        ))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    build_automorphism_key_trace(&data, key, CONJUGATION_ELEMENT)
+}
+
+pub fn get_key_conjugate_pub_inputs(trace: &TraceType) -> PublicInputs {
+    get_automorphism_key_pub_inputs(trace, CONJUGATION_ELEMENT)
+}
+
+pub struct KeyConjugateAir {
+    automorphism: KeyRotateAir,
+}
+
+impl Air for KeyConjugateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        KeyConjugateAir {
+            automorphism: KeyRotateAir::with_element(
+                trace_info,
+                pub_inputs,
+                options,
+                CONJUGATION_ELEMENT,
+            ),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        self.automorphism.context()
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.automorphism.get_periodic_column_values()
+    }
+
+    fn evaluate_transition<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.automorphism
+            .evaluate_transition(frame, periodic_values, result)
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        self.automorphism.get_assertions()
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Key-switched rotation, the application of an automorphism key. A key-switched automorphism of
+// (c0, c1) with Galois element g and an automorphism key (gk0_i, gk1_i) for g is
+//
+//     c0' = phi(c0) + sum_i phi(d_i) * gk0_i,  c1' = sum_i phi(d_i) * gk1_i
+//
+// for the automorphism phi: X -> X^g and the gadget digits d_i of c1. Ciphertexts and keys are
+// in evaluation form, where phi permutes the evaluation points: row k evaluates at psi^p,
+// p = 2 * rev(k) + 1, and phi(a) takes its value from the row of psi^(p * g). As phi is a ring
+// automorphism, c' = phi(t) for the unrotated key switch
+//
+//     t0 = c0 + sum_i d_i * phi^-1(gk0_i),  t1 = sum_i d_i * phi^-1(gk1_i)
+//
+// which is the re-encryption of ReEncryptAir with the key permuted by phi^-1. So the trace and
+// the constraints are ReEncryptAir's, and only public values are permuted: the results and the
+// key are published as c' and the key itself, and asserted permuted back by phi^-1. The input c
+// is public as well and asserted unpermuted against the data columns, so unlike RotateAir no
+// permutation argument is needed: the verifier applies phi to public values only. Rotating by
+// k applies the key of g = 5^k mod 2N; the Galois element is a parameter of the AIR, as the step
+// of RotateAir, so that KeyConjugateAir applies the key of the conjugation with the same trace
+// and constraints, and HoistedRotateAir shares the permutations.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    load_data_file, BaseElement, CiphertextData, InputArg, PublicInputs, TraceType, COEFF_DEGREE,
+};
+use crate::error::StarkHeError;
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::ntt_air::bit_reverse;
+use crate::re_encrypt_air::{
+    get_re_encrypt_pub_inputs, re_encrypt_trace, read_re_encrypt_input, ReEncryptAir,
+};
+use crate::rotate_air::{galois_element, ROTATION_NUM};
+
+// Rows the automorphism with Galois element `g` takes the values of the rows from, in the order
+// of NttAir's outputs: phi(a) has the value of row `rows[k]` of a in row k.
+pub fn automorphism_rows(g: u64) -> Vec<usize> {
+    let two_n = 2 * COEFF_DEGREE as u64;
+    (0..COEFF_DEGREE)
+        .map(|k| {
+            let point = (2 * bit_reverse(k) as u64 + 1) * g % two_n;
+            bit_reverse(((point - 1) / 2) as usize)
+        })
+        .collect()
+}
+
+// Rows of the inverse automorphism, phi^-1 for the rows of phi.
+pub fn inverse_rows(rows: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; rows.len()];
+    for (k, &u) in rows.iter().enumerate() {
+        inverse[u] = k;
+    }
+    inverse
+}
+
+// Values in row k of a permuted by `rows`.
+pub fn permute<T: Copy>(values: &[T], rows: &[usize]) -> Vec<T> {
+    rows.iter().map(|&k| values[k]).collect()
+}
+
+// Every key vector of the digits `digits` of `key` permuted by `rows`.
+pub fn permute_key(key: &mut EvaluationKey, digits: std::ops::Range<usize>, rows: &[usize]) {
+    for digit in key.values[digits].iter_mut() {
+        for values in digit.iter_mut().flatten() {
+            *values = permute(values, rows);
+        }
+    }
+}
+
+// Trace of the key-switched automorphism with Galois element `g` of `data` with `key`, the
+// unrotated key switch.
+pub fn build_automorphism_key_trace(
+    data: &CiphertextData,
+    mut key: EvaluationKey,
+    g: u64,
+) -> Result<TraceType, StarkHeError> {
+    let digits = 0..key.values.len();
+    permute_key(&mut key, digits, &inverse_rows(&automorphism_rows(g)));
+    re_encrypt_trace(data, &key)
+}
+
+// The key-switched ciphertext and the automorphism key, read back from the trace and permuted
+// by phi, and the input ciphertext.
+pub fn get_automorphism_key_pub_inputs(trace: &TraceType, g: u64) -> PublicInputs {
+    let rows = automorphism_rows(g);
+    let mut pub_inputs = PublicInputs {
+        input: Some(read_re_encrypt_input(trace)),
+        ..get_re_encrypt_pub_inputs(trace)
+    };
+    for values in pub_inputs.result.iter_mut().flatten() {
+        *values = permute(values, &rows);
+    }
+    if let Some(key) = pub_inputs.evaluation_key.as_mut() {
+        let digits = 0..key.values.len();
+        permute_key(key, digits, &rows);
+    }
+    pub_inputs
+}
+
+pub fn build_key_rotate_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in evaluation form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let rotation = arg.rotation.ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("key-switched rotation needs --rotation"))
+    })?;
+    assert!(
+        (rotation as i64).rem_euclid(ROTATION_NUM as i64) != 0,
+        "rotation by a multiple of {} slots is the identity",
+        ROTATION_NUM
+    );
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("key-switched rotation needs --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    build_automorphism_key_trace(&data, key, galois_element(rotation))
+}
+
+pub fn get_key_rotate_pub_inputs(trace: &TraceType, rotation: i32) -> PublicInputs {
+    PublicInputs {
+        rotation: Some(rotation),
+        ..get_automorphism_key_pub_inputs(trace, galois_element(rotation))
+    }
+}
+
+pub struct KeyRotateAir {
+    key_switch: ReEncryptAir,
+}
+
+impl Air for KeyRotateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let rotation = pub_inputs
+            .rotation
+            .expect("key-switched rotation proofs need the rotation");
+        KeyRotateAir::with_element(trace_info, pub_inputs, options, galois_element(rotation))
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        self.key_switch.context()
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.key_switch.get_periodic_column_values()
+    }
+
+    fn evaluate_transition<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.key_switch
+            .evaluate_transition(frame, periodic_values, result)
+    }
+
+    // Results and key columns are bound on the first row of every group, permuted back by
+    // phi^-1, the data columns to the input, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        self.key_switch.get_assertions()
+    }
+}
+
+impl KeyRotateAir {
+    // The AIR of the key-switched automorphism with Galois element `g`, which asserts the
+    // results and the key of `pub_inputs` permuted back by phi^-1.
+    pub fn with_element(
+        trace_info: TraceInfo,
+        mut pub_inputs: PublicInputs,
+        options: ProofOptions,
+        g: u64,
+    ) -> Self {
+        let rows = inverse_rows(&automorphism_rows(g));
+        for values in pub_inputs.result.iter_mut().flatten() {
+            *values = permute(values, &rows);
+        }
+        if let Some(key) = pub_inputs.evaluation_key.as_mut() {
+            let digits = 0..key.values.len();
+            permute_key(key, digits, &rows);
+        }
+        KeyRotateAir {
+            key_switch: ReEncryptAir::new(trace_info, pub_inputs, options),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Key switching of a ciphertext (c0, c1) under secret s to a secret s' with a key-switching key
+// (ksk0_j, ksk1_j) for the RNS gadget: digit j of c1 is its residue modulo q_j, lifted to every
+// level, and
+//
+//     c0' = c0 + sum_j [c1]_qj * ksk0_j,  c1' = sum_j [c1]_qj * ksk1_j
+//
+// The digits are the c1 columns themselves, so the decomposition needs no extra columns: every
+// circuit row holds one coefficient of every level and uses the residues of all levels. As in
+// MulAir, the sums are reduced by the Barrett blocks of reduction.rs, one per row of a row group,
+// against the moduli of the public inputs; the rows after them reduce every digit to itself
+// modulo its prime, which range checks it. Relinearization and rotation are key switches of c2
+// and of the rotated c1 respectively.
+//
+// The key columns are asserted against the key as in RelinAir, whose evaluation key shares the
+// format and digest defined here.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+use winter_utils::{ByteReader, DeserializationError};
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, element_to_u64, level_column, load_data_file, read_modulus, BaseElement,
+    CiphertextData, InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM,
+    VALUE_NUM,
+};
+use crate::canonical::{commitment, read_u64s, CanonicalWriter, EVALUATION_KEY_TAG};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, read_group_result,
+    reduction_group, reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Key + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 K000 K001 K010 K011 K100 .. K111 K.. R.. S..
+// one gadget digit per RNS level
+pub const KEYSWITCH_DIGIT_NUM: usize = COEFF_LEVEL;
+// (1 + digits) * m^2 must stay below the field modulus
+pub const KEYSWITCH_MAX_MODULUS_BITS: u32 = 62;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const KEY_LEN: usize = KEYSWITCH_DIGIT_NUM * VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const KEY_START: usize = DATA_START + RESULT_LEN;
+const KEY_END: usize = KEY_START + KEY_LEN;
+// a sum is below (1 + sum_j (q_j - 1)) * m, so its quotient below one plus a digit per level
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(KEYSWITCH_DIGIT_NUM as u128 * ((1 << KEYSWITCH_MAX_MODULUS_BITS) - 1) + 1),
+    KEYSWITCH_MAX_MODULUS_BITS as usize,
+);
+// one row per result coefficient and level, one per digit, then padding
+const REDUCTION_NUM: usize = RESULT_LEN + KEYSWITCH_DIGIT_NUM;
+pub const KEYSWITCH_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = KEY_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of KeySwitchAir in canonical order.
+pub fn keyswitch_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("digit_num", KEYSWITCH_DIGIT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", KEYSWITCH_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Values holds, for every gadget digit, the key components multiplied into c0 and c1:
+// RELIN_DIGIT_NUM base-w digits for relinearization, one RNS digit per level for key switching.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EvaluationKey {
+    pub modulus: Vec<u64>,
+    pub values: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+impl EvaluationKey {
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut writer = CanonicalWriter::new(EVALUATION_KEY_TAG);
+        self.write_canonical(&mut writer);
+        writer.into_bytes()
+    }
+
+    // Layout: the moduli, the number of digits, VALUE_NUM and COEFF_LEVEL as u32, then every key
+    // vector as u64 sequences ordered by digit, component and level.
+    pub fn write_canonical(&self, writer: &mut CanonicalWriter) {
+        writer.write_u64s(&self.modulus);
+        writer.write_u32(self.values.len() as u32);
+        writer.write_u32(VALUE_NUM as u32);
+        writer.write_u32(COEFF_LEVEL as u32);
+        for digit in self.values.iter() {
+            for component in digit.iter() {
+                for level in component.iter() {
+                    writer.write_u64s(level);
+                }
+            }
+        }
+    }
+
+    // Inverse of write_canonical, for keys of this build's VALUE_NUM and COEFF_LEVEL.
+    pub fn read_canonical<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let modulus = read_u64s(source)?;
+        let digits = source.read_u32()?;
+        if source.read_u32()? as usize != VALUE_NUM || source.read_u32()? as usize != COEFF_LEVEL {
+            return Err(DeserializationError::InvalidValue(String::from(
+                "evaluation key has another number of components or levels",
+            )));
+        }
+        let values = (0..digits)
+            .map(|_| {
+                let mut digit: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
+                for level in digit.iter_mut().flatten() {
+                    *level = read_u64s(source)?;
+                }
+                Ok(digit)
+            })
+            .collect::<Result<_, DeserializationError>>()?;
+        Ok(EvaluationKey { modulus, values })
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        commitment(&self.to_canonical_bytes())
+    }
+
+    // Key read back from the trace, `column(digit, component, level)` giving its columns, with
+    // one coefficient per row group of `group` rows.
+    pub fn from_trace(
+        trace: &TraceType,
+        digits: usize,
+        group: usize,
+        column: impl Fn(usize, usize, usize) -> usize,
+    ) -> EvaluationKey {
+        let mut key = EvaluationKey {
+            modulus: (0..MODULUS_NUM)
+                .map(|l| element_to_u64(trace.get(l, 0)))
+                .collect(),
+            values: vec![Default::default(); digits],
+        };
+        for i in 0..digits {
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    key.values[i][v][l] = trace
+                        .get_column(column(i, v, l))
+                        .iter()
+                        .step_by(group)
+                        .map(|x| element_to_u64(*x))
+                        .collect();
+                }
+            }
+        }
+        key
+    }
+
+    // Key columns paired with their values, for `bind_columns`.
+    pub fn columns(
+        &self,
+        column: impl Fn(usize, usize, usize) -> usize,
+    ) -> Vec<(usize, Vec<BaseElement>)> {
+        let mut columns = Vec::with_capacity(self.values.len() * VALUE_NUM * COEFF_LEVEL);
+        for (i, digit) in self.values.iter().enumerate() {
+            for (v, component) in digit.iter().enumerate() {
+                for (l, level) in component.iter().enumerate() {
+                    let values = level.iter().map(|x| element_from_u64(*x)).collect();
+                    columns.push((column(i, v, l), values));
+                }
+            }
+        }
+        columns
+    }
+}
+
+pub fn load_evaluation_key(path: &str) -> Result<EvaluationKey, StarkHeError> {
+    load_data_file(path)
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn key_column(j: usize, v: usize, l: usize) -> usize {
+    level_column(KEY_START + j * VALUE_NUM * COEFF_LEVEL, v, l)
+}
+
+// Switched components of one coefficient at a level, before their reduction; `c1` holds the
+// residues of every level, `key` the key at this level.
+pub fn switch_key(
+    c0: u64,
+    c1: [u64; KEYSWITCH_DIGIT_NUM],
+    key: [[u64; VALUE_NUM]; KEYSWITCH_DIGIT_NUM],
+) -> [u128; VALUE_NUM] {
+    std::array::from_fn(|v| {
+        let mut sum = if v == 0 { c0 as u128 } else { 0 };
+        for j in 0..KEYSWITCH_DIGIT_NUM {
+            sum += c1[j] as u128 * key[j][v] as u128;
+        }
+        sum
+    })
+}
+
+// The reductions of a row by the public moduli `modulus`, component v at level l being value
+// v * COEFF_LEVEL + l of the row and digit j value RESULT_LEN + j, reduced to itself modulo q_j.
+pub fn keyswitch_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::KeySwitch, modulus, KEYSWITCH_MAX_MODULUS_BITS)?;
+    let digits: u128 = modulus.iter().map(|&q| q as u128 - 1).sum();
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            results.push(result_column(v, l));
+            moduli.push(modulus[l]);
+            bounds.push((modulus[l] as u128 - 1) * (1 + digits));
+        }
+    }
+    for j in 0..KEYSWITCH_DIGIT_NUM {
+        results.push(data_column(1, j));
+        moduli.push(modulus[j]);
+        bounds.push(modulus[j] as u128 - 1);
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (RESULT_START..KEY_END).collect(),
+        KEY_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_keyswitch_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("key switching needs --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    assert_eq!(
+        key.modulus, data.modulus,
+        "key-switching key moduli differ from the data moduli"
+    );
+    assert_eq!(
+        key.values.len(),
+        KEYSWITCH_DIGIT_NUM,
+        "key-switching key needs one digit per level"
+    );
+    check_modulus_bits(&data.modulus, KEYSWITCH_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+
+    let ciphertext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.values)?;
+    let keys = key
+        .values
+        .into_iter()
+        .map(|digit| Ciphertext::from_levels(&data.modulus, STATE_LENGTH, digit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reductions =
+        keyswitch_reductions(ciphertext.modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; KEY_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    for (j, digit) in keys.iter().enumerate() {
+        digit.write_columns(&mut columns, |v, l| key_column(j, v, l));
+    }
+    let sums = |k: usize, l: usize| {
+        let c1 = std::array::from_fn(|j| ciphertext.coeff(1, j, k));
+        let level_key = std::array::from_fn(|j| std::array::from_fn(|v| keys[j].coeff(v, l, k)));
+        switch_key(ciphertext.coeff(0, l, k), c1, level_key)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = ciphertext.modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for (v, sum) in sums(k, l).into_iter().enumerate() {
+                columns[result_column(v, l)][k] = element_from_u64((sum % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        if j < RESULT_LEN {
+            sums(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+        } else {
+            ciphertext.coeff(1, j - RESULT_LEN, k) as u128
+        }
+    }))
+}
+
+// Results and the key-switching key, read back from the first row of every group.
+pub fn get_keyswitch_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, KEYSWITCH_GROUP, result_column),
+        evaluation_key: Some(EvaluationKey::from_trace(
+            trace,
+            KEYSWITCH_DIGIT_NUM,
+            KEYSWITCH_GROUP,
+            key_column,
+        )),
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The key-switched sums of one row, component v at level l at v * COEFF_LEVEL + l, then the
+// digits.
+fn row_values<E: FieldElement>(row: &[E]) -> [E; REDUCTION_NUM] {
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let mut sum = if v == 0 {
+                row[data_column(0, l)]
+            } else {
+                E::ZERO
+            };
+            for j in 0..KEYSWITCH_DIGIT_NUM {
+                sum += row[data_column(1, j)] * row[key_column(j, v, l)];
+            }
+            values[v * COEFF_LEVEL + l] = sum;
+        }
+    }
+    for j in 0..KEYSWITCH_DIGIT_NUM {
+        values[RESULT_LEN + j] = row[data_column(1, j)];
+    }
+    values
+}
+
+pub struct KeySwitchAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    key: EvaluationKey,
+}
+
+impl Air for KeySwitchAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The sums are quadratic and selected by a periodic column; the moduli were checked by
+    // parse_data and against the key by attach_evaluation_key.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("key-switch proofs record their moduli");
+        let reductions = keyswitch_reductions(&modulus).expect("key-switch moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + KEY_LEN
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        KeySwitchAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            key: pub_inputs
+                .evaluation_key
+                .expect("key switching needs the key-switching key"),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let values = row_values(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, key
+    // columns on the first row of every group, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(self.reductions.bind_columns(self.key.columns(key_column)));
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
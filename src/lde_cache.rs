@@ -0,0 +1,205 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Cache of the low-degree extensions of trace columns, shared by the proofs of a batch. Columns
+// are looked up by the digest of their values and the size of the LDE domain, so an operand
+// ciphertext that several proofs of a batch consume in the same row order is interpolated and
+// extended only once. Every proof still hashes its own extended rows into its trace commitment,
+// so proofs are identical with and without the cache.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::debug;
+use winter_air::Air;
+use winter_math::{log2, FieldElement};
+use winter_prover::crypto::{ElementHasher, MerkleTree};
+use winter_prover::{Matrix, ProofOptions, Prover, StarkDomain};
+
+use crate::air::BaseElement;
+use crate::canonical::commitment;
+
+// digest of the column values and LDE domain size
+type ColumnKey = ([u8; 32], usize);
+
+struct CachedColumn {
+    poly: Vec<BaseElement>,
+    lde: Vec<BaseElement>,
+}
+
+impl CachedColumn {
+    fn size_bytes(&self) -> usize {
+        (self.poly.len() + self.lde.len()) * BaseElement::ELEMENT_BYTES
+    }
+}
+
+// Columns are evicted oldest first once the cache holds more than `max_bytes`.
+pub struct LdeCache {
+    max_bytes: usize,
+    used_bytes: usize,
+    columns: HashMap<ColumnKey, CachedColumn>,
+    order: VecDeque<ColumnKey>,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+pub type SharedLdeCache = Arc<Mutex<LdeCache>>;
+
+impl LdeCache {
+    pub fn new(max_bytes: usize) -> Self {
+        LdeCache {
+            max_bytes,
+            used_bytes: 0,
+            columns: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn shared(max_bytes: usize) -> SharedLdeCache {
+        Arc::new(Mutex::new(LdeCache::new(max_bytes)))
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    fn insert(&mut self, key: ColumnKey, column: CachedColumn) {
+        let size = column.size_bytes();
+        if size > self.max_bytes || self.columns.contains_key(&key) {
+            return;
+        }
+        while self.used_bytes + size > self.max_bytes {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.columns.remove(&oldest) {
+                self.used_bytes -= evicted.size_bytes();
+            }
+        }
+        self.used_bytes += size;
+        self.order.push_back(key);
+        self.columns.insert(key, column);
+    }
+}
+
+// Trace polynomials and their extension over `domain` with the commitment to the extended rows,
+// as Prover::build_trace_commitment computes them, taking cached columns from `cache`. Only
+// base field segments are cached.
+pub fn build_trace_commitment<E, H>(
+    cache: Option<&SharedLdeCache>,
+    trace: &Matrix<E>,
+    domain: &StarkDomain<BaseElement>,
+) -> (Matrix<E>, MerkleTree<H>, Matrix<E>)
+where
+    E: FieldElement<BaseField = BaseElement>,
+    H: ElementHasher<BaseField = BaseElement>,
+{
+    let cache = match cache {
+        Some(cache) if E::ELEMENT_BYTES == BaseElement::ELEMENT_BYTES => cache,
+        _ => {
+            let trace_polys = trace.interpolate_columns();
+            let trace_lde = trace_polys.evaluate_columns_over(domain);
+            let trace_tree = trace_lde.commit_to_rows();
+            return (trace_lde, trace_tree, trace_polys);
+        }
+    };
+
+    let now = Instant::now();
+    let lde_size = domain.lde_domain_size();
+    let keys: Vec<ColumnKey> = trace
+        .columns()
+        .map(|column| (commitment(E::elements_as_bytes(column)), lde_size))
+        .collect();
+    let mut polys: Vec<Option<Vec<E>>> = vec![None; keys.len()];
+    let mut ldes: Vec<Option<Vec<E>>> = vec![None; keys.len()];
+    {
+        let cache = cache.lock().unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            if let Some(column) = cache.columns.get(key) {
+                polys[i] = Some(column.poly.iter().map(|&x| E::from(x)).collect());
+                ldes[i] = Some(column.lde.iter().map(|&x| E::from(x)).collect());
+            }
+        }
+    }
+
+    let missing: Vec<usize> = (0..keys.len()).filter(|&i| polys[i].is_none()).collect();
+    if !missing.is_empty() {
+        let columns = missing
+            .iter()
+            .map(|&i| trace.get_column(i).to_vec())
+            .collect();
+        let missing_polys = Matrix::new(columns).interpolate_columns();
+        let missing_ldes = missing_polys.evaluate_columns_over(domain);
+        let mut cache = cache.lock().unwrap();
+        for (j, &i) in missing.iter().enumerate() {
+            let poly = missing_polys.get_column(j);
+            let lde = missing_ldes.get_column(j);
+            cache.insert(
+                keys[i],
+                CachedColumn {
+                    poly: E::as_base_elements(poly).to_vec(),
+                    lde: E::as_base_elements(lde).to_vec(),
+                },
+            );
+            polys[i] = Some(poly.to_vec());
+            ldes[i] = Some(lde.to_vec());
+        }
+    }
+    {
+        let mut cache = cache.lock().unwrap();
+        cache.hits += keys.len() - missing.len();
+        cache.misses += missing.len();
+    }
+    debug!(
+        "Extended execution trace of {} columns to 2^{} steps in {} ms, {} columns from the LDE cache",
+        keys.len(),
+        log2(lde_size),
+        now.elapsed().as_millis(),
+        keys.len() - missing.len()
+    );
+
+    let trace_polys = Matrix::new(polys.into_iter().map(Option::unwrap).collect());
+    let trace_lde = Matrix::new(ldes.into_iter().map(Option::unwrap).collect());
+    let trace_tree = trace_lde.commit_to_rows();
+    (trace_lde, trace_tree, trace_polys)
+}
+
+// A prover whose trace commitment goes through an LDE cache, otherwise identical to `inner`.
+pub struct CachingProver<P> {
+    pub inner: P,
+    pub cache: Option<SharedLdeCache>,
+}
+
+impl<P> Prover for CachingProver<P>
+where
+    P: Prover<BaseField = BaseElement>,
+{
+    type BaseField = BaseElement;
+    type Air = P::Air;
+    type Trace = P::Trace;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> <Self::Air as Air>::PublicInputs {
+        self.inner.get_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        self.inner.options()
+    }
+
+    fn build_trace_commitment<E, H>(
+        &self,
+        trace: &Matrix<E>,
+        domain: &StarkDomain<Self::BaseField>,
+    ) -> (Matrix<E>, MerkleTree<H>, Matrix<E>)
+    where
+        E: FieldElement<BaseField = Self::BaseField>,
+        H: ElementHasher<BaseField = Self::BaseField>,
+    {
+        build_trace_commitment(self.cache.as_ref(), trace, domain)
+    }
+}
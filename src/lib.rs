@@ -0,0 +1,175 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// STARK proofs of homomorphic encryption operations. The circuits, their trace builders, the
+// prover and the verifier live here; the `prover`, `verifier` and `stark-he` binaries are thin
+// command lines over them, which read and write data and proof files. Services embedding the
+// crate call `prove` and `verify` below with the operands and the proof in memory.
+
+// With a single level, loops over the higher levels are empty and level indices constant, and
+// the small polynomials would fit into arrays.
+#![cfg_attr(
+    feature = "micro",
+    allow(clippy::modulo_one, clippy::reversed_empty_ranges, clippy::useless_vec)
+)]
+
+pub mod aggregate_air;
+pub mod air;
+pub mod analysis;
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod async_prover;
+pub mod base_ext_air;
+pub mod blind_rotate_air;
+pub mod canonical;
+pub mod ciphertext;
+pub mod ckks_encode_air;
+pub mod compare_air;
+pub mod config;
+pub mod conjugate_air;
+pub mod constraint_export;
+pub mod cost;
+pub mod decrypt_air;
+pub mod decrypt_share_air;
+pub mod diff;
+pub mod encoding;
+pub mod encrypt_air;
+pub mod error;
+pub mod explain;
+pub mod ffi;
+pub mod fma_air;
+pub mod gadget;
+pub mod he_security;
+pub mod hoisted_rotate_air;
+pub mod horner_air;
+pub mod inner_product_air;
+pub mod intt_air;
+pub mod key_conjugate_air;
+pub mod key_rotate_air;
+pub mod keyswitch_air;
+pub mod lde_cache;
+#[cfg(feature = "goldilocks")]
+pub mod limb_mul_air;
+pub mod limbs;
+pub mod lint;
+pub mod manifest;
+pub mod mat_vec_air;
+pub mod modraise_air;
+pub mod modswitch_air;
+pub mod mont_mul_air;
+pub mod montgomery;
+pub mod mul_air;
+pub mod multi_key_add_air;
+pub mod neg_air;
+pub mod ntt_air;
+pub mod operand_files;
+pub mod operation;
+pub mod oplog;
+pub mod output;
+pub mod pack_air;
+pub mod packed;
+pub mod params;
+pub mod pipeline_air;
+pub mod plain_mul_air;
+pub mod preflight;
+pub mod proving;
+pub mod re_encrypt_air;
+pub mod reduction;
+pub mod registry;
+pub mod relin_air;
+pub mod reproducibility;
+pub mod rescale_air;
+pub mod rotate_air;
+pub mod scalar_mul_air;
+pub mod scheme;
+pub mod sealed;
+pub mod session;
+pub mod sign;
+pub mod status;
+pub mod storage;
+pub mod sub_air;
+pub mod sum_air;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod unpack_air;
+#[cfg(not(feature = "offline"))]
+pub mod verify_server;
+pub mod verifying;
+pub mod wide_mul_air;
+pub mod window;
+
+// The Barrett gadget is shared with FreshAir in the verifier crate.
+pub use stark_he_verifier::barrett;
+
+use winter_air::ProofOptions;
+use winter_prover::StarkProof;
+
+use air::{
+    build_trace_from_data, build_trace_from_rows, to_envelope, CoefficientRow, CustomData, Data,
+    InputArg, Op, PublicInputs, SchemeConfig, TraceType,
+};
+use error::StarkHeError;
+use proving::{prove_trace, FreshProver};
+use status::StatusReporter;
+use verifying::verify_data;
+
+// An `add` proof with the public inputs it was generated for, the results of the addition.
+pub struct Proof {
+    pub proof: StarkProof,
+    pub public_inputs: PublicInputs,
+}
+
+impl Proof {
+    // The proof file container of the proof, an envelope as written by the prover.
+    pub fn to_data(self) -> Data {
+        to_envelope(Op::Add, &self.proof, self.public_inputs)
+    }
+}
+
+// Proves the addition of the operands of `data`, the sum of all but the last minus the last,
+// as `prover --op add` does for the data file holding them.
+pub fn prove(data: &CustomData, options: ProofOptions) -> Result<Proof, StarkHeError> {
+    let (config, trace) = build_trace_from_data(&InputArg::default(), data)?;
+    let mut status = StatusReporter::new(None, Op::Add.name());
+    prove_add(config, trace, options, &mut status)
+}
+
+// Proves the addition of `operands` operands streamed by `rows`, one CoefficientRow per
+// coefficient, with the moduli, levels and ring degree of `header`, whose values are not read;
+// see air::build_trace_from_rows.
+pub fn prove_rows<I>(
+    header: &CustomData,
+    operands: usize,
+    rows: I,
+    options: ProofOptions,
+) -> Result<Proof, StarkHeError>
+where
+    I: IntoIterator<Item = Result<CoefficientRow, StarkHeError>>,
+{
+    let (config, trace) = build_trace_from_rows(&InputArg::default(), header, operands, rows)?;
+    let mut status = StatusReporter::new(None, Op::Add.name());
+    prove_add(config, trace, options, &mut status)
+}
+
+fn prove_add(
+    config: SchemeConfig,
+    trace: TraceType,
+    options: ProofOptions,
+    status: &mut StatusReporter,
+) -> Result<Proof, StarkHeError> {
+    let prover = FreshProver { options, config };
+    let output = prove_trace(Op::Add, prover, trace, None, false, status)?;
+    Ok(Proof {
+        proof: output.proof,
+        public_inputs: output.public_input,
+    })
+}
+
+// Verifies an `add` proof against `public_inputs`, with the checks of `verifier` on its proof
+// file; a rejected proof is a StarkHeError::Verification with the VerificationFailure.
+pub fn verify(proof: StarkProof, public_inputs: PublicInputs) -> Result<(), StarkHeError> {
+    verify_data(to_envelope(Op::Add, &proof, public_inputs), None)?;
+    Ok(())
+}
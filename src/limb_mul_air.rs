@@ -0,0 +1,602 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// wide-mul over the Goldilocks field, whose 63 bits hold neither the terms of the tensor product,
+// up to 2 * (m - 1)^2, nor q * m + r: the operands are held as the limbs of limbs.rs, and every
+// term a * b = q * m + r is checked limb by limb with signed carries. As in WideMulAir, every
+// circuit row holds one evaluation point of every RNS level and is repeated over a row group,
+// whose rows share a block of binary columns:
+//
+//     term rows     two per term and level: the bits of q, of r and of the slack m - 1 - r, then
+//                   the bits of the carries of the limb identity, checked on the first of the two
+//     operand rows  one per operand residue: its bits and those of its slack, whose slices of
+//                   LIMB_BITS bits are the limbs of the operand columns
+//     padding       the rest, at least one row, so that the last row of the trace, which
+//                   winterfell exempts from the transition constraints, holds no check
+//
+// so every limb and carry is range checked and every residue is below its modulus. The limbs of
+// q and r are slices of their bits, those of m periodic columns of the public moduli. A carry c
+// of B bits is held as c + 2^(B-1). The operand limbs and the results are copied from row to row
+// within a group and the results bound on its first row. As the Barrett blocks of reduction.rs,
+// the block, the limbs and the carries are sized to the bits of the largest public modulus, the
+// reserved columns they leave staying zero and unconstrained, so the layout does not depend on
+// the moduli. Moduli take at most BARRETT_MODULUS_BITS bits, so that the remainder and slack of
+// a row stay below 2^FIELD_BITS.
+//
+// Modulus + Result + Limbs + Block, the limbs of A00 first
+// M0 M1 R00 R01 R10 R11 R20 R21 A00.. A01.. A10.. A11.. B00.. B01.. B10.. B11.. bits
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+use winter_prover::TraceTable;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, Boundary, InputArg,
+    Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, FIELD_BITS, FIELD_NAME, MODULUS_NUM,
+    VALUE_NUM,
+};
+use crate::barrett::{compose, write_bits, BARRETT_MODULUS_BITS};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::limbs::{
+    add_limb_product, add_limb_product_int, carries, evaluate_carries, split_limbs, COEFF_LIMB_NUM,
+    LIMB_BITS,
+};
+use crate::mul_air::{tensor_terms, MulData, MUL_DATA_NUM, MUL_RESULT_NUM};
+use crate::reduction::{
+    bind_moduli, bit_length, check_public_moduli, read_group_result, MODULUS_ASSERTION_NUM,
+};
+
+pub const LIMB_MUL_MAX_MODULUS_BITS: u32 = BARRETT_MODULUS_BITS as u32;
+
+const RESULT_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const OPERAND_LEN: usize = MUL_DATA_NUM * VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const LIMB_START: usize = RESULT_START + RESULT_LEN;
+const BLOCK_START: usize = LIMB_START + OPERAND_LEN * COEFF_LIMB_NUM;
+// the quotient of the middle term takes a bit more than the modulus, the carries fewer columns
+const BLOCK_WIDTH: usize = 3 * LIMB_MUL_MAX_MODULUS_BITS as usize + 1;
+const TERM_ROWS: usize = 2 * RESULT_LEN;
+pub const LIMB_MUL_GROUP: usize = (TERM_ROWS + OPERAND_LEN + 1).next_power_of_two();
+
+const STATE_WIDTH: usize = BLOCK_START + BLOCK_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of LimbMulAir in canonical order.
+pub fn limb_mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("result_num", MUL_RESULT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("limb_bits", LIMB_BITS as usize),
+        ("group", LIMB_MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Operand v of data d at level l, in the order of the operand rows.
+fn operand_index(d: usize, v: usize, l: usize) -> usize {
+    (d * VALUE_NUM + v) * COEFF_LEVEL + l
+}
+
+fn limb_column(operand: usize, i: usize) -> usize {
+    LIMB_START + operand * COEFF_LIMB_NUM + i
+}
+
+// Term r at level l is term r * COEFF_LEVEL + l of a row, as in WideMulAir.
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+// The blocks of a proof, sized to its public moduli: the bits of the quotients, remainders and
+// slacks, and those of every carry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimbMul {
+    modulus: Vec<u64>,
+    modulus_bits: usize,
+    quotient_bits: usize,
+    carry_bits: Vec<usize>,
+}
+
+impl LimbMul {
+    pub fn new(modulus: &[u64]) -> Result<Self, String> {
+        check_public_moduli(Op::WideMul, modulus, LIMB_MUL_MAX_MODULUS_BITS)?;
+        let modulus_bits = modulus
+            .iter()
+            .map(|&m| bit_length(m as u128))
+            .max()
+            .expect("moduli were checked");
+        let quotient = modulus
+            .iter()
+            .map(|&m| 2 * (m as u128 - 1).pow(2) / m as u128)
+            .max()
+            .expect("moduli were checked");
+        let quotient_bits = bit_length(quotient);
+        let mut block = LimbMul {
+            modulus: modulus.to_vec(),
+            modulus_bits,
+            quotient_bits,
+            carry_bits: Vec::new(),
+        };
+        block.carry_bits = block.size_carries()?;
+        assert!(
+            block.width() <= BLOCK_WIDTH,
+            "limb blocks fit their columns"
+        );
+        Ok(block)
+    }
+
+    fn residue_limbs(&self) -> usize {
+        self.modulus_bits.div_ceil(LIMB_BITS as usize)
+    }
+
+    fn quotient_limbs(&self) -> usize {
+        self.quotient_bits.div_ceil(LIMB_BITS as usize)
+    }
+
+    // Coefficients of the limb identity, one carry fewer.
+    fn coefficients(&self) -> usize {
+        (2 * self.residue_limbs()).max(self.quotient_limbs() + self.residue_limbs()) - 1
+    }
+
+    // Bits of every carry, from the largest limbs the bits allow: the coefficient of X^k of the
+    // difference lies between -(q * m)_k - r_k and the middle term (a0 * b1 + a1 * b0)_k. A carry
+    // of B bits takes the values -2^(B-1) to 2^(B-1) - 1; with any of them every equation has to
+    // stay below 2^FIELD_BITS, so that it cannot wrap around the field modulus.
+    fn size_carries(&self) -> Result<Vec<usize>, String> {
+        let limb_max = |bits: usize, limbs: usize| -> Vec<i128> {
+            (0..limbs)
+                .map(|i| (1i128 << (bits - LIMB_BITS as usize * i).min(LIMB_BITS as usize)) - 1)
+                .collect()
+        };
+        let residue = limb_max(self.modulus_bits, self.residue_limbs());
+        let quotient = limb_max(self.quotient_bits, self.quotient_limbs());
+        let mut high = vec![0i128; self.coefficients()];
+        add_limb_product_int_signed(&residue, &residue, &mut high, 2);
+        let mut low = vec![0i128; self.coefficients()];
+        add_limb_product_int_signed(&quotient, &residue, &mut low, -1);
+        for (k, &r) in residue.iter().enumerate() {
+            low[k] -= r;
+        }
+        let base = 1i128 << LIMB_BITS;
+        let (mut carry_low, mut carry_high) = (0, 0);
+        let mut bits = Vec::with_capacity(self.coefficients() - 1);
+        for k in 0..self.coefficients() - 1 {
+            carry_low = (low[k] + carry_low).div_euclid(base);
+            carry_high = (high[k] + carry_high).div_euclid(base);
+            bits.push(bit_length(carry_high.max(-carry_low - 1) as u128) + 1);
+        }
+        // d_k + c_(k-1) - c_k * X at the extremes of the carries
+        let carry = |k: usize| bits.get(k).map_or(0, |&b| 1i128 << (b - 1));
+        for k in 0..self.coefficients() {
+            let reach = high[k].max(-low[k]) + k.checked_sub(1).map_or(0, carry) + carry(k) * base;
+            if bit_length(reach as u128) > FIELD_BITS {
+                return Err(format!(
+                    "limb identities of {}-bit moduli exceed the {} bits of {}",
+                    self.modulus_bits, FIELD_BITS, FIELD_NAME
+                ));
+            }
+        }
+        Ok(bits)
+    }
+
+    // Columns of the block on the rows which hold the most bits.
+    fn width(&self) -> usize {
+        (self.quotient_bits + 2 * self.modulus_bits).max(self.carry_bits.iter().sum())
+    }
+
+    fn remainder_start(&self) -> usize {
+        BLOCK_START + self.quotient_bits
+    }
+
+    fn slack_start(&self) -> usize {
+        self.remainder_start() + self.modulus_bits
+    }
+
+    fn carry_start(&self, k: usize) -> usize {
+        BLOCK_START + self.carry_bits[..k].iter().sum::<usize>()
+    }
+
+    // The results and the operand limbs the residues take.
+    fn copied(&self) -> Vec<usize> {
+        let mut copied: Vec<usize> = (RESULT_START..LIMB_START).collect();
+        for operand in 0..OPERAND_LEN {
+            copied.extend((0..self.residue_limbs()).map(|i| limb_column(operand, i)));
+        }
+        copied
+    }
+
+    // Selectors of the first row of every term and of every operand row, the flag of the last
+    // row of a group, m - 1 on the rows checking a residue and the limbs of m on the first rows
+    // of the terms, zero elsewhere.
+    pub fn periodic_columns(&self) -> Vec<Vec<BaseElement>> {
+        let flag = |row: usize| {
+            (0..LIMB_MUL_GROUP)
+                .map(|i| element_from_u64((i == row) as u64))
+                .collect::<Vec<_>>()
+        };
+        let mut columns: Vec<Vec<BaseElement>> = (0..RESULT_LEN).map(|j| flag(2 * j)).collect();
+        columns.extend((0..OPERAND_LEN).map(|o| flag(TERM_ROWS + o)));
+        columns.push(flag(LIMB_MUL_GROUP - 1));
+        let level = |i: usize| match i {
+            i if i < TERM_ROWS && i % 2 == 0 => Some((i / 2) % COEFF_LEVEL),
+            i if (TERM_ROWS..TERM_ROWS + OPERAND_LEN).contains(&i) => {
+                Some((i - TERM_ROWS) % COEFF_LEVEL)
+            }
+            _ => None,
+        };
+        columns.push(
+            (0..LIMB_MUL_GROUP)
+                .map(|i| {
+                    level(i).map_or(BaseElement::ZERO, |l| element_from_u64(self.modulus[l] - 1))
+                })
+                .collect(),
+        );
+        for limb in 0..self.residue_limbs() {
+            columns.push(
+                (0..LIMB_MUL_GROUP)
+                    .map(|i| match level(i) {
+                        Some(l) if i < TERM_ROWS => element_from_u64(
+                            split_limbs::<COEFF_LIMB_NUM>(self.modulus[l] as u128)[limb],
+                        ),
+                        _ => BaseElement::ZERO,
+                    })
+                    .collect(),
+            );
+        }
+        columns
+    }
+
+    // Copies, the slack and result equations, one per operand limb, one per coefficient of the
+    // limb identity, then one per bit.
+    pub fn degrees(&self) -> Vec<TransitionConstraintDegree> {
+        let grouped =
+            |degree| TransitionConstraintDegree::with_cycles(degree, vec![LIMB_MUL_GROUP]);
+        let mut degrees = vec![grouped(1); self.copied().len() + 2 + self.residue_limbs()];
+        // the coefficients above the operand products only hold q * m
+        degrees.extend(
+            (0..self.coefficients())
+                .map(|k| grouped(1 + (k < 2 * self.residue_limbs() - 1) as usize)),
+        );
+        degrees.extend(vec![TransitionConstraintDegree::new(2); self.width()]);
+        degrees
+    }
+
+    // The limbs of the `limbs` slices of LIMB_BITS bits of the `bits` bits starting at `start`.
+    fn slices<E: FieldElement + From<BaseElement>>(
+        row: &[E],
+        start: usize,
+        bits: usize,
+        limbs: usize,
+    ) -> Vec<E> {
+        (0..limbs)
+            .map(|i| {
+                let first = start + LIMB_BITS as usize * i;
+                compose(&row[first..(first + LIMB_BITS as usize).min(start + bits)])
+            })
+            .collect()
+    }
+
+    pub fn evaluate<E: FieldElement + From<BaseElement>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (current, next) = (frame.current(), frame.next());
+        let (terms, rest) = periodic_values.split_at(RESULT_LEN);
+        let (operands, rest) = rest.split_at(OPERAND_LEN);
+        let (last, bound, modulus_limbs) = (rest[0], rest[1], &rest[2..]);
+        let term = terms.iter().fold(E::ZERO, |sum, &selector| sum + selector);
+        let operand = operands
+            .iter()
+            .fold(E::ZERO, |sum, &selector| sum + selector);
+        let (residue_limbs, quotient_limbs) = (self.residue_limbs(), self.quotient_limbs());
+        let remainder = compose(&current[self.remainder_start()..self.slack_start()]);
+        let slack = compose(&current[self.slack_start()..self.slack_start() + self.modulus_bits]);
+        let remainder_limbs = Self::slices(
+            current,
+            self.remainder_start(),
+            self.modulus_bits,
+            residue_limbs,
+        );
+
+        let copied = self.copied();
+        let (copies, result) = result.split_at_mut(copied.len());
+        for (constraint, &c) in copies.iter_mut().zip(copied.iter()) {
+            *constraint = (E::ONE - last) * (next[c] - current[c]);
+        }
+        result[0] = (term + operand) * (remainder + slack) - bound;
+        result[1] = (0..RESULT_LEN)
+            .fold(E::ZERO, |sum, j| sum + terms[j] * current[RESULT_START + j])
+            - term * remainder;
+        for i in 0..residue_limbs {
+            result[2 + i] = (0..OPERAND_LEN).fold(E::ZERO, |sum, o| {
+                sum + operands[o] * current[limb_column(o, i)]
+            }) - operand * remainder_limbs[i];
+        }
+
+        // the difference of the selected term and q * m + r, limb by limb
+        let result = &mut result[2 + residue_limbs..];
+        let coefficients = self.coefficients();
+        let mut differences = vec![E::ZERO; coefficients];
+        let limbs = |d: usize, v: usize, l: usize| {
+            let o = operand_index(d, v, l);
+            (0..residue_limbs)
+                .map(|i| current[limb_column(o, i)])
+                .collect::<Vec<_>>()
+        };
+        for l in 0..COEFF_LEVEL {
+            let (a, b) = (
+                [limbs(0, 0, l), limbs(0, 1, l)],
+                [limbs(1, 0, l), limbs(1, 1, l)],
+            );
+            let mut products = vec![vec![E::ZERO; coefficients]; MUL_RESULT_NUM];
+            add_limb_product(&a[0], &b[0], &mut products[0]);
+            add_limb_product(&a[0], &b[1], &mut products[1]);
+            add_limb_product(&a[1], &b[0], &mut products[1]);
+            add_limb_product(&a[1], &b[1], &mut products[2]);
+            for (r, product) in products.iter().enumerate() {
+                let selector = terms[r * COEFF_LEVEL + l];
+                for (d, &p) in differences.iter_mut().zip(product) {
+                    *d += selector * p;
+                }
+            }
+        }
+        let quotient = Self::slices(current, BLOCK_START, self.quotient_bits, quotient_limbs);
+        let mut product = vec![E::ZERO; coefficients];
+        add_limb_product(&quotient, modulus_limbs, &mut product);
+        for (k, d) in differences.iter_mut().enumerate() {
+            *d -= product[k] + term * remainder_limbs.get(k).copied().unwrap_or(E::ZERO);
+        }
+        let carries: Vec<E> = self
+            .carry_bits
+            .iter()
+            .enumerate()
+            .map(|(k, &bits)| {
+                let start = self.carry_start(k);
+                let offset = E::from(element_from_u64(1 << (bits - 1)));
+                term * (compose(&next[start..start + bits]) - offset)
+            })
+            .collect();
+        evaluate_carries(&differences, &carries, &mut result[..coefficients]);
+
+        for (i, &bit) in current[BLOCK_START..BLOCK_START + self.width()]
+            .iter()
+            .enumerate()
+        {
+            result[coefficients + i] = bit * (bit - E::ONE);
+        }
+    }
+
+    // Writes the bits of the term `x` at level `l` to `row` and those of the carries of its limb
+    // identity to `carry_row`, and returns its remainder; `products` are the coefficients of the
+    // term as a limb product.
+    fn fill_term(
+        &self,
+        x: u128,
+        l: usize,
+        products: &[i128],
+        row: &mut [BaseElement],
+        carry_row: &mut [BaseElement],
+    ) -> u64 {
+        let m = self.modulus[l];
+        let (quotient, remainder) = (x / m as u128, (x % m as u128) as u64);
+        write_bits(quotient, &mut row[BLOCK_START..self.remainder_start()]);
+        self.fill_residue(remainder, m, row);
+        let (residue_limbs, quotient_limbs) = (self.residue_limbs(), self.quotient_limbs());
+        let mut differences = products.to_vec();
+        let q = split_limbs::<COEFF_LIMB_NUM>(quotient);
+        let m_limbs = split_limbs::<COEFF_LIMB_NUM>(m as u128);
+        let mut qm = vec![0i128; self.coefficients()];
+        add_limb_product_int(&q[..quotient_limbs], &m_limbs[..residue_limbs], &mut qm);
+        let r = split_limbs::<COEFF_LIMB_NUM>(remainder as u128);
+        for (k, d) in differences.iter_mut().enumerate() {
+            *d -= qm[k] + if k < residue_limbs { r[k] as i128 } else { 0 };
+        }
+        for (k, carry) in carries(&differences).into_iter().enumerate() {
+            let (start, bits) = (self.carry_start(k), self.carry_bits[k]);
+            write_bits(
+                (carry + (1 << (bits - 1))) as u128,
+                &mut carry_row[start..start + bits],
+            );
+        }
+        remainder
+    }
+
+    // Writes the bits of the residue `value` modulo `m` and of its slack to `row`.
+    fn fill_residue(&self, value: u64, m: u64, row: &mut [BaseElement]) {
+        write_bits(
+            value as u128,
+            &mut row[self.remainder_start()..self.slack_start()],
+        );
+        write_bits(
+            (m - 1 - value) as u128,
+            &mut row[self.slack_start()..self.slack_start() + self.modulus_bits],
+        );
+    }
+}
+
+// Same as add_limb_product_int for the signed bounds of LimbMul::size_carries, every product
+// scaled by `scale`.
+fn add_limb_product_int_signed(a: &[i128], b: &[i128], out: &mut [i128], scale: i128) {
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            out[i + j] += scale * x * y;
+        }
+    }
+}
+
+// The limbs of the moduli and the length of the trace of a wide-mul proof, which parse_data
+// checks before the STARK.
+pub fn check_limb_mul_trace(modulus: &[u64], trace_length: usize) -> Result<(), String> {
+    LimbMul::new(modulus)?;
+    if trace_length != STATE_LENGTH * LIMB_MUL_GROUP {
+        return Err(format!(
+            "{} proof has a trace of {} rows, expected {} coefficient rows of {} rows",
+            Op::WideMul.name(),
+            trace_length,
+            STATE_LENGTH,
+            LIMB_MUL_GROUP
+        ));
+    }
+    Ok(())
+}
+
+pub fn build_limb_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: MulData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, LIMB_MUL_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+    let block = LimbMul::new(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let residue_limbs = block.residue_limbs();
+
+    let mut trace = vec![vec![BaseElement::ZERO; STATE_LENGTH * LIMB_MUL_GROUP]; STATE_WIDTH];
+    let mut rows = vec![vec![BaseElement::ZERO; STATE_WIDTH]; LIMB_MUL_GROUP];
+    for k in 0..STATE_LENGTH {
+        for row in rows.iter_mut() {
+            row.fill(BaseElement::ZERO);
+        }
+        let value = |d: usize, v: usize, l: usize| data.values[d][v][l][k];
+        for l in 0..COEFF_LEVEL {
+            rows[0][l] = element_from_u64(data.modulus[l]);
+            for d in 0..MUL_DATA_NUM {
+                for v in 0..VALUE_NUM {
+                    let limbs = split_limbs::<COEFF_LIMB_NUM>(value(d, v, l) as u128);
+                    for i in 0..residue_limbs {
+                        rows[0][limb_column(operand_index(d, v, l), i)] =
+                            element_from_u64(limbs[i]);
+                    }
+                    let row = &mut rows[TERM_ROWS + operand_index(d, v, l)];
+                    block.fill_residue(value(d, v, l), data.modulus[l], row);
+                }
+            }
+            let limbs = |d: usize, v: usize| {
+                split_limbs::<COEFF_LIMB_NUM>(value(d, v, l) as u128)[..residue_limbs].to_vec()
+            };
+            let (a, b) = ([limbs(0, 0), limbs(0, 1)], [limbs(1, 0), limbs(1, 1)]);
+            let mut products = vec![vec![0i128; block.coefficients()]; MUL_RESULT_NUM];
+            add_limb_product_int(&a[0], &b[0], &mut products[0]);
+            add_limb_product_int(&a[0], &b[1], &mut products[1]);
+            add_limb_product_int(&a[1], &b[0], &mut products[1]);
+            add_limb_product_int(&a[1], &b[1], &mut products[2]);
+            let terms = tensor_terms(
+                [value(0, 0, l), value(0, 1, l)],
+                [value(1, 0, l), value(1, 1, l)],
+            );
+            for (r, (&x, product)) in terms.iter().zip(products.iter()).enumerate() {
+                let j = r * COEFF_LEVEL + l;
+                let (head, tail) = rows.split_at_mut(2 * j + 1);
+                let remainder = block.fill_term(x, l, product, &mut head[2 * j], &mut tail[0]);
+                rows[0][result_column(r, l)] = element_from_u64(remainder);
+            }
+        }
+        // the modulus, result and limb columns of the first row over the whole group
+        let (first, others) = rows.split_at_mut(1);
+        for row in others.iter_mut() {
+            row[..BLOCK_START].copy_from_slice(&first[0][..BLOCK_START]);
+        }
+        for (i, row) in rows.iter().enumerate() {
+            for (c, &value) in row.iter().enumerate() {
+                trace[c][k * LIMB_MUL_GROUP + i] = value;
+            }
+        }
+    }
+    Ok(TraceTable::init(trace))
+}
+
+pub fn get_limb_mul_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MUL_RESULT_NUM, LIMB_MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct LimbMulAir {
+    context: AirContext<BaseElement>,
+    block: LimbMul,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+}
+
+impl Air for LimbMulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("wide-mul proofs record their moduli");
+        let block = LimbMul::new(&modulus).expect("wide-mul moduli were checked");
+        let num_assertions = RESULT_LEN + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, block.degrees(), num_assertions, options);
+
+        LimbMulAir {
+            context: Boundary::Padding(1).apply(context),
+            block,
+            modulus,
+            result: pub_inputs.result,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.block.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.block.evaluate(frame, periodic_values, result);
+    }
+
+    // Every result coefficient is bound on the first row of its group, the modulus columns to the
+    // public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut assertions = Vec::with_capacity(RESULT_LEN + MODULUS_ASSERTION_NUM);
+        for r in 0..MUL_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                assertions.push(Assertion::sequence(
+                    result_column(r, l),
+                    0,
+                    LIMB_MUL_GROUP,
+                    self.result[r][l].clone(),
+                ));
+            }
+        }
+        assertions.extend(bind_moduli(&self.modulus, 0, LIMB_MUL_GROUP));
+        assertions
+    }
+}
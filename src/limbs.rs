@@ -0,0 +1,170 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Multi-limb integers: a value is held in several trace columns as limbs of LIMB_BITS bits,
+// least significant first, so that an identity between products of values too large for the
+// field, e.g. a * b = q * m + r for 64-bit residues, can be checked limb by limb. Both sides are
+// polynomials in X = 2^LIMB_BITS; with d_k the coefficient of X^k of their difference, the
+// identity holds over the integers if
+//
+//     d_0 = c_0 * X,  d_k + c_(k-1) = c_k * X,  d_n + c_(n-1) = 0
+//
+// for carries c_k, which are signed. Every d_k is a sum of a few products of limbs, below
+// 2^(2 * LIMB_BITS + 4) for the limbs of 64-bit values, and the carries below 2^(LIMB_BITS + 4),
+// so none of these equations wraps around the field modulus: limbs take 32 bits in the 128-bit
+// field and 16 bits in the 63 of Goldilocks. The bounds only hold for limbs and carries within
+// their ranges, which the circuits holding them check with bits, see limb_mul_air.rs.
+
+use winter_math::FieldElement;
+
+use crate::air::{element_from_u64, BaseElement};
+use crate::decrypt_air::signed_element;
+
+#[cfg(not(feature = "goldilocks"))]
+pub const LIMB_BITS: u32 = 32;
+#[cfg(feature = "goldilocks")]
+pub const LIMB_BITS: u32 = 16;
+// limbs of a u64 coefficient or modulus
+pub const COEFF_LIMB_NUM: usize = (u64::BITS / LIMB_BITS) as usize;
+
+pub fn split_limbs<const N: usize>(value: u128) -> [u64; N] {
+    std::array::from_fn(|i| (value >> (LIMB_BITS as usize * i)) as u64 & ((1 << LIMB_BITS) - 1))
+}
+
+pub fn limb_elements<const N: usize>(value: u128) -> [BaseElement; N] {
+    split_limbs::<N>(value).map(element_from_u64)
+}
+
+// Value of the limbs, as a field element.
+pub fn compose_limbs<E: FieldElement + From<BaseElement>>(limbs: &[E]) -> E {
+    let base = E::from(element_from_u64(1 << LIMB_BITS));
+    limbs
+        .iter()
+        .rev()
+        .fold(E::ZERO, |value, &limb| value * base + limb)
+}
+
+// Adds the limb products of `a` and `b` to the coefficients of `out`.
+pub fn add_limb_product<E: FieldElement>(a: &[E], b: &[E], out: &mut [E]) {
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            out[i + j] += x * y;
+        }
+    }
+}
+
+// Same as add_limb_product on integers, for the trace builders.
+pub fn add_limb_product_int(a: &[u64], b: &[u64], out: &mut [i128]) {
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            out[i + j] += x as i128 * y as i128;
+        }
+    }
+}
+
+// Carries c_0..c_(n-1) of the coefficients d_0..d_n of a difference which is zero as an integer.
+pub fn carries(differences: &[i128]) -> Vec<i128> {
+    let mut carry = 0;
+    let carries = differences[..differences.len() - 1]
+        .iter()
+        .map(|&d| {
+            let total = d + carry;
+            assert!(
+                total % (1 << LIMB_BITS) == 0,
+                "limb difference {} is not a multiple of 2^{}",
+                total,
+                LIMB_BITS
+            );
+            carry = total >> LIMB_BITS;
+            carry
+        })
+        .collect();
+    assert_eq!(
+        differences[differences.len() - 1] + carry,
+        0,
+        "limb difference is not zero"
+    );
+    carries
+}
+
+pub fn carry_elements(differences: &[i128]) -> Vec<BaseElement> {
+    carries(differences)
+        .into_iter()
+        .map(|carry| signed_element(i64::try_from(carry).expect("carries fit into i64")))
+        .collect()
+}
+
+// Values of the carry equations for the coefficients `differences` and the carries `carries`,
+// one fewer; zero when the difference is zero as an integer.
+pub fn evaluate_carries<E: FieldElement + From<BaseElement>>(
+    differences: &[E],
+    carries: &[E],
+    result: &mut [E],
+) {
+    let base = E::from(element_from_u64(1 << LIMB_BITS));
+    let mut carry = E::ZERO;
+    for (k, &d) in differences.iter().enumerate() {
+        let next = carries.get(k).copied().unwrap_or(E::ZERO);
+        result[k] = d + carry - next * base;
+        carry = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::element_from_u128;
+
+    #[test]
+    fn limbs_round_trip() {
+        let limb = 1u128 << LIMB_BITS;
+        for value in [0, 1, limb - 1, limb, limb.pow(3) - 1] {
+            let limbs: [BaseElement; 3] = limb_elements(value);
+            assert_eq!(compose_limbs(&limbs), element_from_u128(value));
+        }
+    }
+
+    // (2^64 - 1)^2 does not fit into the field, but its limb identity with q = 2^64 - 2,
+    // m = 2^64 and r = 1 holds
+    #[test]
+    fn carries_check_wide_products() {
+        const N: usize = COEFF_LIMB_NUM + 1;
+        let a = split_limbs::<COEFF_LIMB_NUM>(u64::MAX as u128);
+        let q = split_limbs::<N>(u64::MAX as u128 - 1);
+        let m: [u64; N] = std::array::from_fn(|i| (i == COEFF_LIMB_NUM) as u64);
+        let mut differences = vec![0i128; 2 * N - 1];
+        add_limb_product_int(&a, &a, &mut differences);
+        let mut product = vec![0i128; 2 * N - 1];
+        add_limb_product_int(&q, &m, &mut product);
+        for (d, p) in differences.iter_mut().zip(product) {
+            *d -= p;
+        }
+        differences[0] -= 1;
+
+        let element = |limbs: &[u64]| {
+            limbs
+                .iter()
+                .map(|&x| element_from_u64(x))
+                .collect::<Vec<_>>()
+        };
+        let mut elements = vec![BaseElement::ZERO; 2 * N - 1];
+        add_limb_product(&element(&a), &element(&a), &mut elements);
+        let mut product = vec![BaseElement::ZERO; 2 * N - 1];
+        add_limb_product(&element(&q), &element(&m), &mut product);
+        for (d, p) in elements.iter_mut().zip(product) {
+            *d -= p;
+        }
+        elements[0] -= BaseElement::ONE;
+
+        let mut result = vec![BaseElement::ONE; 2 * N - 1];
+        evaluate_carries(&elements, &carry_elements(&differences), &mut result);
+        assert!(result.iter().all(|&value| value == BaseElement::ZERO));
+
+        let mut wrong = carry_elements(&differences);
+        wrong[1] += BaseElement::ONE;
+        evaluate_carries(&elements, &wrong, &mut result);
+        assert!(result.iter().any(|&value| value != BaseElement::ZERO));
+    }
+}
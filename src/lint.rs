@@ -0,0 +1,540 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::fmt;
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::air::{
+    check_active_levels, check_coeff_degree, check_operand_num, element_from_u64, element_to_u64,
+    fresh_result, BaseElement, Data, Op, COEFF_DEGREE, VALUE_NUM,
+};
+use crate::barrett::BARRETT_MODULUS_BITS;
+use crate::he_security::{check_he_security, HeSecurity};
+use crate::packed::{decode_custom_data, is_packed};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+// Same shape as CustomData but without fixed array sizes, so that a malformed file can still
+// be inspected instead of failing deserialization as a whole.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LooseData {
+    #[serde(default)]
+    modulus: Vec<u64>,
+    coeff_degree: Option<usize>,
+    levels: Option<Vec<usize>>,
+    #[serde(default)]
+    values: Vec<Vec<Vec<Vec<u64>>>>,
+}
+
+// Packed files have the shape of CustomData by construction.
+fn parse_loose(bytes: &[u8]) -> Result<LooseData, String> {
+    if is_packed(bytes) {
+        let data = decode_custom_data(bytes).map_err(|err| err.to_string())?;
+        return Ok(LooseData {
+            modulus: data.modulus,
+            coeff_degree: data.coeff_degree,
+            levels: data.levels,
+            values: data
+                .values
+                .into_iter()
+                .map(|operand| operand.into_iter().collect())
+                .collect(),
+        });
+    }
+    let text = std::str::from_utf8(bytes).map_err(|err| err.to_string())?;
+    toml::from_str(text).map_err(|err| err.to_string())
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn warn(&mut self, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+}
+
+// Checks a data file (and optionally the proof file produced from it) without proving.
+pub fn lint_data(data_file_path: &str, proof_file_path: Option<&str>) -> Report {
+    let mut report = Report::default();
+
+    let mut data: LooseData = match fs::read(data_file_path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| parse_loose(&bytes))
+    {
+        Ok(data) => data,
+        Err(err) => {
+            report.error(format!("cannot read {}: {}", data_file_path, err));
+            return report;
+        }
+    };
+
+    check_modulus(&data.modulus, &mut report);
+    let degree = data.coeff_degree.unwrap_or(COEFF_DEGREE);
+    if let Err(err) = check_he_security(degree, &data.modulus, HeSecurity::Bits128) {
+        report.warn(err);
+    }
+    let levels = match check_active_levels(&data.modulus, data.levels.as_deref(), data.values.len())
+    {
+        Ok(levels) => levels,
+        Err(err) => {
+            report.error(err);
+            return report;
+        }
+    };
+    // the operands only hold their active levels
+    data.modulus.truncate(levels);
+    if !check_shape(&data, &mut report) {
+        return report;
+    }
+    check_ranges(&data, &mut report);
+    if report.has_errors() {
+        return report;
+    }
+
+    let expected = expected_results(&data);
+    if let Some(path) = proof_file_path {
+        check_proof_results(path, &expected, &mut report);
+    }
+    report
+}
+
+// One modulus per RNS level; their number and repeated moduli are checked by
+// check_active_levels.
+fn check_modulus(modulus: &[u64], report: &mut Report) {
+    for (i, m) in modulus.iter().enumerate() {
+        if *m < 2 {
+            report.error(format!("modulus {} is {}, expected a prime", i, m));
+        } else if 64 - m.leading_zeros() as usize > BARRETT_MODULUS_BITS {
+            report.error(format!(
+                "modulus {} ({}) has more than {} bits",
+                i, m, BARRETT_MODULUS_BITS
+            ));
+        } else if !is_prime(*m) {
+            report.warn(format!("modulus {} ({}) is not prime", i, m));
+        }
+    }
+}
+
+fn check_shape(data: &LooseData, report: &mut Report) -> bool {
+    let mut ok = true;
+    let degree = data.coeff_degree.unwrap_or(COEFF_DEGREE);
+    if let Err(err) = check_coeff_degree(degree) {
+        report.error(err);
+        ok = false;
+    }
+    if let Err(err) = check_operand_num(data.values.len()) {
+        report.error(err);
+        ok = false;
+    }
+    for (d, operand) in data.values.iter().enumerate() {
+        if operand.len() != VALUE_NUM {
+            report.error(format!(
+                "operand {} has {} components, expected {}",
+                d,
+                operand.len(),
+                VALUE_NUM
+            ));
+            ok = false;
+        }
+        for (v, component) in operand.iter().enumerate() {
+            if component.len() != data.modulus.len() {
+                report.error(format!(
+                    "operand {} component {} has {} levels, expected one per active modulus, {}",
+                    d,
+                    v,
+                    component.len(),
+                    data.modulus.len()
+                ));
+                ok = false;
+            }
+            for (l, coeffs) in component.iter().enumerate() {
+                if coeffs.len() != degree {
+                    report.warn(format!(
+                        "operand {} component {} level {} has {} coefficients, expected {}",
+                        d,
+                        v,
+                        l,
+                        coeffs.len(),
+                        degree
+                    ));
+                    ok = false;
+                }
+            }
+        }
+    }
+    ok
+}
+
+fn check_ranges(data: &LooseData, report: &mut Report) {
+    for (d, operand) in data.values.iter().enumerate() {
+        for (v, component) in operand.iter().enumerate() {
+            for (l, coeffs) in component.iter().enumerate() {
+                let m = data.modulus[l];
+                let out_of_range: Vec<usize> =
+                    (0..coeffs.len()).filter(|&k| coeffs[k] >= m).collect();
+                if let Some(first) = out_of_range.first() {
+                    report.error(format!(
+                        "operand {} component {} level {} has {} coefficients >= modulus {} (first at index {})",
+                        d,
+                        v,
+                        l,
+                        out_of_range.len(),
+                        m,
+                        first
+                    ));
+                }
+            }
+        }
+    }
+}
+
+// Results as the circuit will compute them, the sum of all operands but the last minus the last,
+// the canonical `a + b - c mod m` for three.
+fn expected_results(data: &LooseData) -> Vec<Vec<Vec<u64>>> {
+    (0..VALUE_NUM)
+        .map(|v| {
+            data.modulus
+                .iter()
+                .enumerate()
+                .map(|(l, &m)| {
+                    (0..data.values[0][v][l].len())
+                        .map(|k| {
+                            let operands: Vec<BaseElement> = data
+                                .values
+                                .iter()
+                                .map(|operand| element_from_u64(operand[v][l][k]))
+                                .collect();
+                            element_to_u64(fresh_result(&operands, element_from_u64(m)))
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn check_proof_results(path: &str, expected: &[Vec<Vec<u64>>], report: &mut Report) {
+    let proof: Data = match fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| toml::from_str(&text).map_err(|err| err.to_string()))
+    {
+        Ok(proof) => proof,
+        Err(err) => {
+            report.error(format!("cannot read {}: {}", path, err));
+            return;
+        }
+    };
+    if proof.circuit != Op::Add.name() || proof.result.len() != expected.len() {
+        report.error(format!(
+            "{} is a {} proof with {} result components, expected an {} proof with {}",
+            path,
+            proof.circuit,
+            proof.result.len(),
+            Op::Add.name(),
+            expected.len()
+        ));
+        return;
+    }
+    for (v, levels) in expected.iter().enumerate() {
+        for (l, expected) in levels.iter().enumerate() {
+            let claimed = &proof.result[v][l];
+            if claimed.len() != expected.len() {
+                report.error(format!(
+                    "proof component {} level {} has {} result coefficients, expected {}",
+                    v,
+                    l,
+                    claimed.len(),
+                    expected.len()
+                ));
+            } else if let Some(k) = (0..claimed.len()).find(|&k| claimed[k] != expected[k]) {
+                report.error(format!(
+                    "proof component {} level {} result differs from the data file at index {}",
+                    v, l, k
+                ));
+            }
+        }
+    }
+}
+
+// Deterministic Miller-Rabin for the whole u64 range.
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let mul = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let pow = |mut base: u64, mut exp: u64| {
+        let mut acc = 1u64;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = mul(acc, base);
+            }
+            base = mul(base, base);
+            exp >>= 1;
+        }
+        acc
+    };
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'witness: for a in [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        let mut x = pow(a, d);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul(x, x);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::Params;
+    use clap::ArgEnum;
+
+    const MODULUS: u64 = 65537;
+
+    fn loose(operands: usize) -> LooseData {
+        LooseData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: vec![vec![vec![vec![3; COEFF_DEGREE]]; VALUE_NUM]; operands],
+        }
+    }
+
+    fn messages(report: &Report) -> Vec<(Severity, &str)> {
+        report
+            .findings
+            .iter()
+            .map(|finding| (finding.severity, finding.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn moduli_are_checked() {
+        let mut report = Report::default();
+        check_modulus(&[1, 1 << 62, 65535, MODULUS], &mut report);
+        let too_wide = format!(
+            "modulus 1 ({}) has more than {} bits",
+            1u64 << 62,
+            BARRETT_MODULUS_BITS
+        );
+        assert_eq!(
+            messages(&report),
+            [
+                (Severity::Error, "modulus 0 is 1, expected a prime"),
+                (Severity::Error, too_wide.as_str()),
+                (Severity::Warning, "modulus 2 (65535) is not prime"),
+            ]
+        );
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn shapes_and_ranges_are_checked() {
+        let mut report = Report::default();
+        let data = loose(3);
+        assert!(check_shape(&data, &mut report));
+        check_ranges(&data, &mut report);
+        assert!(report.findings.is_empty());
+
+        let mut data = loose(3);
+        data.values[1].pop();
+        data.values[0][1].push(vec![3; COEFF_DEGREE]);
+        data.values[2][0][0].truncate(3);
+        assert!(!check_shape(&data, &mut report));
+        let expected = [
+            (
+                Severity::Error,
+                String::from(
+                    "operand 0 component 1 has 2 levels, expected one per active modulus, 1",
+                ),
+            ),
+            (
+                Severity::Error,
+                format!("operand 1 has 1 components, expected {}", VALUE_NUM),
+            ),
+            (
+                Severity::Warning,
+                format!(
+                    "operand 2 component 0 level 0 has 3 coefficients, expected {}",
+                    COEFF_DEGREE
+                ),
+            ),
+        ];
+        let found = messages(&report);
+        for (severity, message) in expected.iter() {
+            assert!(
+                found.contains(&(*severity, message.as_str())),
+                "{}",
+                message
+            );
+        }
+
+        let mut report = Report::default();
+        assert!(!check_shape(&loose(1), &mut report));
+        assert!(report.has_errors());
+
+        let mut report = Report::default();
+        let mut data = loose(2);
+        data.values[1][0][0][5] = MODULUS;
+        data.values[1][0][0][9] = u64::MAX;
+        check_ranges(&data, &mut report);
+        assert_eq!(
+            messages(&report),
+            [(
+                Severity::Error,
+                "operand 1 component 0 level 0 has 2 coefficients >= modulus 65537 (first at index 5)"
+            )]
+        );
+    }
+
+    #[test]
+    fn proof_results_are_checked() {
+        let dir = std::env::temp_dir().join(format!("stark-he-lint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proof.toml");
+        let path_text = path.to_string_lossy().into_owned();
+        let expected = expected_results(&loose(3));
+        // 3 + 3 - 3
+        assert!(expected.iter().flatten().flatten().all(|&c| c == 3));
+        let write = |circuit: &str, result: &[Vec<Vec<u64>>]| {
+            let data = Data {
+                circuit: String::from(circuit),
+                result: result.to_vec(),
+                ..Default::default()
+            };
+            fs::write(&path, toml::to_string(&data).unwrap()).unwrap();
+        };
+
+        write(Op::Add.name(), &expected);
+        let mut report = Report::default();
+        check_proof_results(&path_text, &expected, &mut report);
+        assert!(report.findings.is_empty());
+
+        let mut differing = expected.clone();
+        differing[1][0][7] = 4;
+        write(Op::Add.name(), &differing);
+        check_proof_results(&path_text, &expected, &mut report);
+        write(Op::Sub.name(), &expected);
+        check_proof_results(&path_text, &expected, &mut report);
+        fs::remove_dir_all(&dir).unwrap();
+        check_proof_results(&path_text, &expected, &mut report);
+        let found = messages(&report);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            found[0].1,
+            "proof component 1 level 0 result differs from the data file at index 7"
+        );
+        assert!(found[1].1.ends_with(&format!(
+            "is a sub proof with {} result components, expected an add proof with {}",
+            VALUE_NUM, VALUE_NUM
+        )));
+        assert!(found[2].1.starts_with("cannot read"));
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn missing_data_files_are_reported() {
+        let report = lint_data("/nonexistent/stark-he-data.toml", None);
+        assert!(report.has_errors());
+        assert!(report.findings[0]
+            .to_string()
+            .starts_with("error: cannot read"));
+    }
+
+    #[test]
+    fn miller_rabin() {
+        let primes = [
+            2,
+            3,
+            37,
+            41,
+            MODULUS,
+            (1 << 61) - 1,
+            0xffff_ffff_0000_0001,
+            18_446_744_073_709_551_557,
+        ];
+        for n in primes {
+            assert!(is_prime(n), "{}", n);
+        }
+        // Carmichael numbers, and strong pseudoprimes to the first bases
+        let composites = [
+            0,
+            1,
+            4,
+            1763,
+            561,
+            1105,
+            1729,
+            2465,
+            2821,
+            6601,
+            8911,
+            3_215_031_751,
+            3_825_123_056_546_413_051,
+            1 << 62,
+            u64::MAX,
+        ];
+        for n in composites {
+            assert!(!is_prime(n), "{}", n);
+        }
+        for params in Params::value_variants() {
+            for &q in params.modulus() {
+                assert!(is_prime(q), "{} of {}", q, params.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Operation manifests describe an HE computation as a list of operations, each consuming and
+// producing named ciphertexts:
+//
+//     [[operation]]
+//     id = "sum-1"
+//     circuit = "add"    # or "mul" (two inputs), "relin", "rotate" or "key-switch" (one input)
+//     data = "sum-1.toml"
+//     inputs = ["a", "b", "c"]
+//     output = "s1"
+//
+// Relinearizations and key switches also name their `evaluation_key` file, rotations their
+// `rotation`. Any operation may `expose` only some result coefficients, see window.rs. Relative
+// paths are resolved against the manifest's directory. An operation depends on every operation
+// producing one of its inputs; ciphertexts nobody produces are external inputs. A top-level
+// `hash_fn = "sha3-256"` pins the hash function of all its proofs for the verifiers downstream,
+// over the prover's `--hash-fn`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::Op;
+use crate::proving::EnumHashFunction;
+use crate::scheme::Scheme;
+
+fn default_circuit() -> String {
+    String::from(Op::Add.name())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    #[serde(default = "default_circuit")]
+    pub circuit: String,
+    pub data: String,
+    pub inputs: Vec<String>,
+    pub output: String,
+    // defaults to `<id>.<circuit>.proof` next to the manifest
+    pub proof: Option<String>,
+    pub evaluation_key: Option<String>,
+    pub rotation: Option<i32>,
+    #[serde(default)]
+    pub expose: Option<Vec<usize>>,
+    // defaults to BFV
+    #[serde(default)]
+    pub scheme: Option<Scheme>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_fn: Option<EnumHashFunction>,
+    #[serde(rename = "operation", default)]
+    pub operations: Vec<Operation>,
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl Manifest {
+    pub fn load(path: &str) -> Result<Manifest, String> {
+        let text =
+            fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+        let mut manifest: Manifest =
+            toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))?;
+        manifest.base_dir = Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(manifest)
+    }
+
+    pub fn data_path(&self, op: &Operation) -> PathBuf {
+        self.base_dir.join(&op.data)
+    }
+
+    pub fn evaluation_key_path(&self, op: &Operation) -> Option<PathBuf> {
+        op.evaluation_key
+            .as_ref()
+            .map(|key| self.base_dir.join(key))
+    }
+
+    pub fn proof_path(&self, op: &Operation) -> PathBuf {
+        match &op.proof {
+            Some(proof) => self.base_dir.join(proof),
+            None => self
+                .base_dir
+                .join(format!("{}.{}.proof", op.id, op.circuit)),
+        }
+    }
+}
+
+pub struct Dag {
+    // for every operation, the operations producing its inputs
+    pub dependencies: Vec<Vec<usize>>,
+    // operations in an order where every operation follows its dependencies
+    pub schedule: Vec<usize>,
+}
+
+pub fn build_dag(manifest: &Manifest) -> Result<Dag, String> {
+    let ops = &manifest.operations;
+    let mut ids = HashMap::new();
+    let mut producers = HashMap::new();
+    for (i, op) in ops.iter().enumerate() {
+        if ids.insert(op.id.as_str(), i).is_some() {
+            return Err(format!("duplicate operation id {}", op.id));
+        }
+        if let Some(other) = producers.insert(op.output.as_str(), i) {
+            return Err(format!(
+                "ciphertext {} is produced by both {} and {}",
+                op.output, ops[other].id, op.id
+            ));
+        }
+    }
+
+    let dependencies: Vec<Vec<usize>> = ops
+        .iter()
+        .map(|op| {
+            let mut deps: Vec<usize> = op
+                .inputs
+                .iter()
+                .filter_map(|input| producers.get(input.as_str()).copied())
+                .collect();
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect();
+
+    // Kahn's algorithm, always taking the earliest ready operation so that the schedule is
+    // stable with respect to manifest order.
+    let mut pending: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+    let mut consumers = vec![Vec::new(); ops.len()];
+    for (i, deps) in dependencies.iter().enumerate() {
+        for &dep in deps {
+            consumers[dep].push(i);
+        }
+    }
+    let mut ready: Vec<usize> = (0..ops.len()).filter(|&i| pending[i] == 0).collect();
+    let mut schedule = Vec::with_capacity(ops.len());
+    while !ready.is_empty() {
+        ready.sort_unstable_by(|a, b| b.cmp(a));
+        let next = ready.pop().unwrap();
+        schedule.push(next);
+        for &consumer in consumers[next].iter() {
+            pending[consumer] -= 1;
+            if pending[consumer] == 0 {
+                ready.push(consumer);
+            }
+        }
+    }
+    if schedule.len() != ops.len() {
+        let cyclic: Vec<&str> = (0..ops.len())
+            .filter(|&i| pending[i] > 0)
+            .map(|i| ops[i].id.as_str())
+            .collect();
+        return Err(format!(
+            "operations form a dependency cycle: {}",
+            cyclic.join(", ")
+        ));
+    }
+
+    Ok(Dag {
+        dependencies,
+        schedule,
+    })
+}
+
+// Graphviz rendering: ciphertexts are ellipses, operations are boxes, external inputs are grey.
+pub fn to_dot(manifest: &Manifest) -> String {
+    let produced: Vec<&str> = manifest
+        .operations
+        .iter()
+        .map(|op| op.output.as_str())
+        .collect();
+    let mut out = String::from("digraph manifest {\n    rankdir=LR;\n");
+    let mut external: Vec<&str> = manifest
+        .operations
+        .iter()
+        .flat_map(|op| op.inputs.iter().map(String::as_str))
+        .filter(|input| !produced.contains(input))
+        .collect();
+    external.sort_unstable();
+    external.dedup();
+    for input in external {
+        let _ = writeln!(
+            out,
+            "    \"ct:{}\" [label=\"{}\", shape=ellipse, style=filled, fillcolor=lightgrey];",
+            input, input
+        );
+    }
+    for op in manifest.operations.iter() {
+        let _ = writeln!(
+            out,
+            "    \"op:{}\" [label=\"{}\\n{}\", shape=box];",
+            op.id, op.id, op.circuit
+        );
+        let _ = writeln!(
+            out,
+            "    \"ct:{}\" [label=\"{}\", shape=ellipse];",
+            op.output, op.output
+        );
+        for input in op.inputs.iter() {
+            let _ = writeln!(out, "    \"ct:{}\" -> \"op:{}\";", input, op.id);
+        }
+        let _ = writeln!(out, "    \"op:{}\" -> \"ct:{}\";", op.id, op.output);
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> Manifest {
+        toml::from_str(text).unwrap()
+    }
+
+    // Operations given as (id, inputs, output), in manifest order.
+    fn operations(ops: &[(&str, &[&str], &str)]) -> Manifest {
+        let mut text = String::new();
+        for (id, inputs, output) in ops {
+            let _ = writeln!(
+                text,
+                "[[operation]]\nid = \"{}\"\ndata = \"{}.toml\"\ninputs = {:?}\noutput = \"{}\"",
+                id, id, inputs, output
+            );
+        }
+        manifest(&text)
+    }
+
+    #[test]
+    fn schedules_follow_dependencies_then_manifest_order() {
+        let ops = operations(&[
+            ("square", &["s1"], "s2"),
+            ("sum-1", &["a", "b"], "s1"),
+            ("sum-2", &["c", "d"], "t1"),
+            ("total", &["s2", "t1", "s1"], "u"),
+        ]);
+        let dag = build_dag(&ops).unwrap();
+        assert_eq!(dag.dependencies, [vec![1], vec![], vec![], vec![0, 1, 2]]);
+        assert_eq!(dag.schedule, [1, 0, 2, 3]);
+        assert!(build_dag(&Manifest::default()).unwrap().schedule.is_empty());
+    }
+
+    #[test]
+    fn cycles_and_duplicates_are_rejected() {
+        let cyclic = operations(&[
+            ("sum-1", &["a"], "s1"),
+            ("sum-2", &["s1", "s3"], "s2"),
+            ("sum-3", &["s2"], "s3"),
+        ]);
+        assert_eq!(
+            build_dag(&cyclic).err().unwrap(),
+            "operations form a dependency cycle: sum-2, sum-3"
+        );
+        let own_input = operations(&[("sum-1", &["a", "s1"], "s1")]);
+        assert_eq!(
+            build_dag(&own_input).err().unwrap(),
+            "operations form a dependency cycle: sum-1"
+        );
+
+        let duplicate_id = operations(&[("sum-1", &["a"], "s1"), ("sum-1", &["b"], "s2")]);
+        assert_eq!(
+            build_dag(&duplicate_id).err().unwrap(),
+            "duplicate operation id sum-1"
+        );
+        let duplicate_output = operations(&[("sum-1", &["a"], "s1"), ("sum-2", &["b"], "s1")]);
+        assert_eq!(
+            build_dag(&duplicate_output).err().unwrap(),
+            "ciphertext s1 is produced by both sum-1 and sum-2"
+        );
+    }
+
+    #[test]
+    fn missing_manifests_and_fields_are_reported() {
+        let err = Manifest::load("/nonexistent/stark-he-manifest.toml").unwrap_err();
+        assert!(err.starts_with("cannot read /nonexistent/stark-he-manifest.toml"));
+        assert!(toml::from_str::<Manifest>("[[operation]]\nid = \"sum-1\"\n").is_err());
+
+        let ops = manifest(
+            "[[operation]]\nid = \"rot\"\ncircuit = \"rotate\"\ndata = \"rot.toml\"\n\
+             inputs = [\"a\"]\noutput = \"b\"\nevaluation_key = \"keys/rot.key\"\n\
+             proof = \"out/rot.proof\"\n\
+             [[operation]]\nid = \"sum\"\ndata = \"sum.toml\"\ninputs = [\"a\", \"b\"]\n\
+             output = \"c\"\n",
+        );
+        let (rot, sum) = (&ops.operations[0], &ops.operations[1]);
+        assert_eq!(sum.circuit, "add");
+        assert_eq!(
+            ops.evaluation_key_path(rot),
+            Some(PathBuf::from("keys/rot.key"))
+        );
+        assert_eq!(ops.evaluation_key_path(sum), None);
+        assert_eq!(ops.proof_path(rot), PathBuf::from("out/rot.proof"));
+        assert_eq!(ops.proof_path(sum), PathBuf::from("sum.add.proof"));
+        // inputs nobody produces are external
+        assert_eq!(build_dag(&ops).unwrap().dependencies, [vec![], vec![0]]);
+    }
+
+    #[test]
+    fn dot_export() {
+        let ops = operations(&[("sum-1", &["b", "a"], "s1"), ("sq", &["s1", "a"], "s2")]);
+        assert_eq!(
+            to_dot(&ops),
+            "digraph manifest {\n    rankdir=LR;\n\
+             \x20   \"ct:a\" [label=\"a\", shape=ellipse, style=filled, fillcolor=lightgrey];\n\
+             \x20   \"ct:b\" [label=\"b\", shape=ellipse, style=filled, fillcolor=lightgrey];\n\
+             \x20   \"op:sum-1\" [label=\"sum-1\\nadd\", shape=box];\n\
+             \x20   \"ct:s1\" [label=\"s1\", shape=ellipse];\n\
+             \x20   \"ct:b\" -> \"op:sum-1\";\n\
+             \x20   \"ct:a\" -> \"op:sum-1\";\n\
+             \x20   \"op:sum-1\" -> \"ct:s1\";\n\
+             \x20   \"op:sq\" [label=\"sq\\nadd\", shape=box];\n\
+             \x20   \"ct:s2\" [label=\"s2\", shape=ellipse];\n\
+             \x20   \"ct:s1\" -> \"op:sq\";\n\
+             \x20   \"ct:a\" -> \"op:sq\";\n\
+             \x20   \"op:sq\" -> \"ct:s2\";\n\
+             }\n"
+        );
+    }
+}
@@ -0,0 +1,547 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Product W * d of a public plaintext matrix of integer weights with a vector of C ciphertexts,
+// e.g. a full linear layer of an encrypted inference in one proof: result j is the inner
+// product sum_i W[j][i] * d_i of row j with the vector. The trace holds one InnerProductAir per
+// row of the matrix, interleaved per coefficient: coefficient k of operand i for row j is in
+// circuit row (k * R + j) * P + i, with R the number of matrix rows and P the number of columns
+// plus one, both rounded up to powers of two. Row and column index of a circuit row are thus
+// periodic, and the weights are periodic columns of period R * P. Every circuit row proves
+//
+//     r = (p + w * d) mod m
+//
+// as in InnerProductAir, reduced by the Barrett blocks of reduction.rs against the moduli of the
+// public inputs over a row group of G rows, and the running sums restart with every matrix row.
+// The last slot of every matrix row is padding, so that the last trace row holds no operand.
+//
+// The operands of different matrix rows must be the same vector. Operand i of matrix row j is
+// wired to operand i of matrix row j + 1 by a permutation argument as in NttAir, with one
+// auxiliary column per polynomial: with random alpha and gamma, the operand d in the first row t
+// of its group is the factor gamma - d - alpha * t entering its matrix row and
+// gamma - d - alpha * (t + P * G) leaving it, and the running product of leaving over entering
+// factors must return to 1. The copies of the reductions carry the operand over its group.
+
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TraceLayout, TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, element_to_u64, load_data_file, BaseElement, InputArg, Op, PublicInputs,
+    COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::inner_product_air::WEIGHT_MAX_BITS;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::wiring_assertions;
+use crate::reduction::{
+    bind_moduli, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::sum_air::{
+    data_column, evaluate_links, link_column, link_degrees, partial_column, result_column,
+    weighted_sum_values, SUM_DATA_END, SUM_MAX_OPERANDS,
+};
+
+// Columns of SumAir + Reduction
+// M0 M1 R00 R01 R10 R11 P00 P01 P10 P11 D00 D01 D10 D11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+// p + w * d is below (w + 1) * m, so its quotient at most the weight
+const REDUCTION_WIDTH: usize =
+    reduction_width(WEIGHT_MAX_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per sum and level, then padding
+pub const MAT_VEC_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = SUM_DATA_END + REDUCTION_WIDTH;
+// one running product per polynomial, driven by alpha and gamma
+pub const MAT_VEC_AUX_WIDTH: usize = RESULT_LEN;
+const AUX_WIDTH: usize = MAT_VEC_AUX_WIDTH;
+const AUX_RAND_NUM: usize = 2;
+
+// Most circuit rows per coefficient, as for the largest sum: the rounded numbers of matrix rows
+// and columns plus one multiply to at most 256.
+pub const MAT_VEC_MAX_SLOTS: usize = SUM_MAX_OPERANDS;
+
+// Periodic columns after those of the reductions: the weights, the flags of the links of the
+// running sums, the selectors of operands entering and leaving their matrix row and the trace
+// row; the wiring takes the last three.
+const WEIGHT: usize = 0;
+const LINK: usize = 1;
+const WIRING: usize = 2;
+const INPUT_SELECTOR: usize = 0;
+const OUTPUT_SELECTOR: usize = 1;
+const POSITION: usize = 2;
+
+// Trace layout constants of MatVecAir in canonical order. The trace length follows from the
+// shape of the matrix, see check_matrix.
+pub fn mat_vec_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("max_slots", MAT_VEC_MAX_SLOTS),
+        ("group", MAT_VEC_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as InnerProductData, with one row of weights per result in `Weights`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MatVecData {
+    pub modulus: Vec<u64>,
+    pub weights: Vec<Vec<u64>>,
+    pub values: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+// Rows per matrix row of a matrix with `columns` columns, leaving at least one padding slot.
+pub fn mat_vec_slots(columns: usize) -> usize {
+    (columns + 1).next_power_of_two()
+}
+
+// Shape of a trace: matrix rows and columns, circuit rows per matrix row and per coefficient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Shape {
+    rows: usize,
+    columns: usize,
+    slots: usize,
+    block: usize,
+}
+
+impl Shape {
+    fn new(rows: usize, columns: usize) -> Self {
+        let slots = mat_vec_slots(columns);
+        Shape {
+            rows,
+            columns,
+            slots,
+            block: rows.next_power_of_two() * slots,
+        }
+    }
+
+    // Matrix row and column of a circuit row.
+    fn index(&self, row: usize) -> (usize, usize) {
+        ((row % self.block) / self.slots, row % self.slots)
+    }
+
+    // Operands of the circuit row `row` which are wired into and out of it.
+    fn wired(&self, row: usize) -> (bool, bool) {
+        let (j, i) = self.index(row);
+        let operand = i < self.columns;
+        (operand && j > 0, operand && j + 1 < self.block / self.slots)
+    }
+}
+
+// A matrix of `rows` rows stored row by row in `weights`, with 2 or more rows, at least one
+// column, weights of at most WEIGHT_MAX_BITS bits and at most MAT_VEC_MAX_SLOTS circuit rows per
+// coefficient; with `trace_length`, the trace must have the row groups of that many.
+pub fn check_matrix(
+    weights: &[u64],
+    rows: usize,
+    trace_length: Option<usize>,
+) -> Result<(), String> {
+    if rows < 2 || weights.is_empty() || !weights.len().is_multiple_of(rows) {
+        return Err(format!(
+            "{} weights do not form a matrix of {} rows, at least 2 are needed",
+            weights.len(),
+            rows
+        ));
+    }
+    let shape = Shape::new(rows, weights.len() / rows);
+    if shape.block > MAT_VEC_MAX_SLOTS {
+        return Err(format!(
+            "a {} x {} matrix needs {} circuit rows per coefficient, at most {} are supported",
+            rows, shape.columns, shape.block, MAT_VEC_MAX_SLOTS
+        ));
+    }
+    if let Some(&w) = weights
+        .iter()
+        .find(|w| 64 - w.leading_zeros() > WEIGHT_MAX_BITS)
+    {
+        return Err(format!(
+            "weight {} has more than {} bits",
+            w, WEIGHT_MAX_BITS
+        ));
+    }
+    let expected = COEFF_DEGREE * shape.block * MAT_VEC_GROUP;
+    match trace_length {
+        Some(length) if length != expected => Err(format!(
+            "products of a {} x {} matrix have {} rows, not {}",
+            rows, shape.columns, expected, length
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Execution trace with the wiring argument as its auxiliary segment, and the matrix it was
+// built for.
+pub struct MatVecTrace {
+    layout: TraceLayout,
+    main: Matrix<BaseElement>,
+    weights: Vec<u64>,
+    shape: Shape,
+}
+
+// The reductions of a row by the public moduli `modulus`, for products with the weights
+// `weights`, component v at level l being value v * COEFF_LEVEL + l of the row.
+pub fn mat_vec_reductions(modulus: &[u64], weights: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::MatVec, modulus, MUL_MAX_MODULUS_BITS)?;
+    let w = weights.iter().copied().max().unwrap_or(0) as u128;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| (w + 1) * (modulus[l] as u128 - 1),
+        (MODULUS_NUM..SUM_DATA_END).collect(),
+        SUM_DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_mat_vec_trace(arg: &InputArg) -> Result<MatVecTrace, StarkHeError> {
+    let data: MatVecData = load_data_file(&arg.data_file_path)?;
+    let columns = data.values.len();
+    assert!(
+        data.weights.iter().all(|row| row.len() == columns),
+        "every row of the matrix needs one weight per operand"
+    );
+    let weights: Vec<u64> = data.weights.concat();
+    check_matrix(&weights, data.weights.len(), None).map_err(StarkHeError::InvalidInput)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, COEFF_DEGREE, operand)?;
+    }
+    let reductions =
+        mat_vec_reductions(&data.modulus, &weights).map_err(StarkHeError::InvalidInput)?;
+    let shape = Shape::new(data.weights.len(), columns);
+    let length = shape.block * COEFF_DEGREE;
+
+    let mut trace = vec![vec![BaseElement::ZERO; length]; SUM_DATA_END];
+    let mut values = vec![[0; RESULT_LEN]; length];
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        trace[l] = vec![element_from_u64(m); length];
+        for v in 0..VALUE_NUM {
+            for k in 0..COEFF_DEGREE {
+                let mut p = 0;
+                for row in k * shape.block..(k + 1) * shape.block {
+                    let (j, i) = shape.index(row);
+                    if i == 0 {
+                        p = 0;
+                    }
+                    // every block of the coefficient holds the operands, the weights of the
+                    // padding rows are zero
+                    let d = data.values.get(i).map_or(0, |operand| operand[v][l][k]);
+                    let w = match data.weights.get(j) {
+                        Some(weights) if i < columns => weights[i],
+                        _ => 0,
+                    };
+                    let t = p as u128 + w as u128 * d as u128;
+                    let r = (t % m as u128) as u64;
+                    trace[data_column(v, l)][row] = element_from_u64(d);
+                    trace[partial_column(v, l)][row] = element_from_u64(p);
+                    trace[result_column(v, l)][row] = element_from_u64(r);
+                    values[row][v * COEFF_LEVEL + l] = t;
+                    p = r;
+                }
+            }
+        }
+    }
+    let main = reductions.build_columns(trace, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(MatVecTrace {
+        layout: TraceLayout::new(STATE_WIDTH, [AUX_WIDTH], [AUX_RAND_NUM]),
+        main: Matrix::new(main),
+        weights,
+        shape,
+    })
+}
+
+// One result of VALUE_NUM components per matrix row, read from the last slot of the row; the
+// weights and the moduli are public as well.
+pub fn get_mat_vec_pub_inputs(trace: &MatVecTrace) -> PublicInputs {
+    let shape = trace.shape;
+    let mut result = Vec::with_capacity(shape.rows * VALUE_NUM);
+    for j in 0..shape.rows {
+        for v in 0..VALUE_NUM {
+            let mut levels = vec![Vec::new(); COEFF_LEVEL];
+            for (l, level) in levels.iter_mut().enumerate() {
+                *level = (0..COEFF_DEGREE)
+                    .map(|k| {
+                        let row = k * shape.block + j * shape.slots + shape.slots - 1;
+                        trace.main.get(result_column(v, l), row * MAT_VEC_GROUP)
+                    })
+                    .collect();
+            }
+            result.push(levels);
+        }
+    }
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(
+            (0..MODULUS_NUM)
+                .map(|l| element_to_u64(trace.main.get(l, 0)))
+                .collect(),
+        ),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: Some(trace.weights.clone()),
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// Factors of the operand `d` entering and leaving its matrix row under the wiring columns
+// `wiring`, 1 where a selector excludes them.
+fn row_factors<F, E>(d: F, wiring: &[F], slots: usize, alpha: E, gamma: E) -> (E, E)
+where
+    F: FieldElement<BaseField = BaseElement>,
+    E: FieldElement<BaseField = BaseElement> + ExtensionOf<F>,
+{
+    let (d, position) = (E::from(d), E::from(wiring[POSITION]));
+    let next = E::from(BaseElement::from((slots * MAT_VEC_GROUP) as u64));
+    let input = E::from(wiring[INPUT_SELECTOR]);
+    let output = E::from(wiring[OUTPUT_SELECTOR]);
+    (
+        input * (gamma - d - alpha * position - E::ONE) + E::ONE,
+        output * (gamma - d - alpha * (position + next) - E::ONE) + E::ONE,
+    )
+}
+
+impl Trace for MatVecTrace {
+    type BaseField = BaseElement;
+
+    fn layout(&self) -> &TraceLayout {
+        &self.layout
+    }
+
+    fn length(&self) -> usize {
+        self.main.num_rows()
+    }
+
+    fn meta(&self) -> &[u8] {
+        &[]
+    }
+
+    fn main_segment(&self) -> &Matrix<BaseElement> {
+        &self.main
+    }
+
+    // The running products z_(r+1) = z_r * leaving_r / entering_r, starting from 1.
+    fn build_aux_segment<E: FieldElement<BaseField = BaseElement>>(
+        &mut self,
+        aux_segments: &[Matrix<E>],
+        rand_elements: &[E],
+    ) -> Option<Matrix<E>> {
+        if !aux_segments.is_empty() {
+            return None;
+        }
+        let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+        let periodic = wiring_columns(self.shape, self.length());
+        let mut periodic_values = vec![BaseElement::ZERO; periodic.len()];
+        let mut columns = vec![vec![E::ONE; self.length()]; AUX_WIDTH];
+        for r in 0..self.length() - 1 {
+            for (value, column) in periodic_values.iter_mut().zip(periodic.iter()) {
+                *value = column[r % column.len()];
+            }
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    let p = v * COEFF_LEVEL + l;
+                    let d = self.main.get(data_column(v, l), r);
+                    let (input, output) =
+                        row_factors(d, &periodic_values, self.shape.slots, alpha, gamma);
+                    columns[p][r + 1] = columns[p][r] * output * input.inv();
+                }
+            }
+        }
+        Some(Matrix::new(columns))
+    }
+
+    fn read_main_frame(&self, row_idx: usize, frame: &mut EvaluationFrame<BaseElement>) {
+        let next_row_idx = (row_idx + 1) % self.length();
+        self.main.read_row_into(row_idx, frame.current_mut());
+        self.main.read_row_into(next_row_idx, frame.next_mut());
+    }
+}
+
+// Wiring columns of a matrix of `shape` in a trace of `length` rows, in the order of the indices
+// above: the selectors apply on the first row of every group only.
+fn wiring_columns(shape: Shape, length: usize) -> Vec<Vec<BaseElement>> {
+    let mut input = vec![BaseElement::ZERO; shape.block * MAT_VEC_GROUP];
+    let mut output = vec![BaseElement::ZERO; shape.block * MAT_VEC_GROUP];
+    for row in 0..shape.block {
+        let (entering, leaving) = shape.wired(row);
+        input[row * MAT_VEC_GROUP] = BaseElement::from(entering as u64);
+        output[row * MAT_VEC_GROUP] = BaseElement::from(leaving as u64);
+    }
+    let position = (0..length as u64).map(BaseElement::from).collect();
+    vec![input, output, position]
+}
+
+pub struct MatVecAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    weights: Vec<u64>,
+    shape: Shape,
+}
+
+impl Air for MatVecAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, whose values carry the weight column of period R * P, then
+    // the links of the running sums, as in InnerProductAir. The wiring constraint multiplies the
+    // next running product with an operand under a selector of the same period.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let rows = pub_inputs.result.len() / VALUE_NUM;
+        let modulus = pub_inputs
+            .modulus
+            .expect("mat-vec proofs record their moduli");
+        let weights = pub_inputs.weights.unwrap_or_default();
+        let shape = Shape::new(rows, weights.len() / rows.max(1));
+        let reductions = mat_vec_reductions(&modulus, &weights)
+            .expect("mat-vec moduli and weights were checked");
+        let cycle = shape.block * MAT_VEC_GROUP;
+        let mut degrees = reductions.degrees(1, &[cycle]);
+        degrees.extend(link_degrees(shape.slots, MAT_VEC_GROUP));
+        let aux_degrees = vec![TransitionConstraintDegree::with_cycles(2, vec![cycle]); AUX_WIDTH];
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            (rows + 1) * RESULT_LEN + MODULUS_ASSERTION_NUM,
+            2 * AUX_WIDTH,
+            options,
+        );
+
+        MatVecAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            weights,
+            shape,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let flags = &periodic_values[self.reductions.num_periodic_columns()..];
+        let (reduction, links) = result.split_at_mut(self.reductions.num_constraints());
+        let values = weighted_sum_values(frame.current(), flags[WEIGHT]);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        evaluate_links(frame, flags[LINK], links);
+    }
+
+    // Wiring constraints z_next * entering - z * leaving of every polynomial.
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        let rand_elements = aux_rand_elements.get_segment_elements(0);
+        let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+        let wiring = &periodic_values[self.reductions.num_periodic_columns() + WIRING..];
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let p = v * COEFF_LEVEL + l;
+                let d = main_frame.current()[data_column(v, l)];
+                let (input, output) = row_factors(d, wiring, self.shape.slots, alpha, gamma);
+                result[p] = aux_frame.next()[p] * input - aux_frame.current()[p] * output;
+            }
+        }
+    }
+
+    // The weights of the circuit rows of one coefficient over their groups, the flags of the
+    // links, then the wiring columns.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let shape = self.shape;
+        let mut weights = vec![BaseElement::ZERO; shape.block];
+        for (row, weight) in weights.iter_mut().enumerate() {
+            let (j, i) = shape.index(row);
+            if j < shape.rows && i < shape.columns {
+                *weight = element_from_u64(self.weights[j * shape.columns + i]);
+            }
+        }
+        let mut columns = self.reductions.periodic_columns();
+        columns.push(self.reductions.stretch(&weights));
+        columns.push(link_column(shape.slots, MAT_VEC_GROUP));
+        columns.extend(wiring_columns(shape, self.trace_length()));
+        columns
+    }
+
+    // Partial sums start at zero in every matrix row, result j of a coefficient is the last
+    // running sum of matrix row j, on the first row of its group; the modulus columns hold the
+    // public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let shape = self.shape;
+        let mut assertions = Vec::with_capacity((shape.rows + 1) * RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                assertions.push(Assertion::periodic(
+                    partial_column(v, l),
+                    0,
+                    shape.slots * MAT_VEC_GROUP,
+                    BaseElement::ZERO,
+                ));
+                for j in 0..shape.rows {
+                    assertions.push(Assertion::sequence(
+                        result_column(v, l),
+                        (j * shape.slots + shape.slots - 1) * MAT_VEC_GROUP,
+                        shape.block * MAT_VEC_GROUP,
+                        self.result[j * VALUE_NUM + v][l].clone(),
+                    ));
+                }
+            }
+        }
+        assertions.extend(bind_moduli(&self.modulus, 0, shape.slots * MAT_VEC_GROUP));
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+}
+
+// Smallest trace of a matrix-vector product, for trace shapes: two rows, one column.
+pub fn min_mat_vec_length() -> usize {
+    COEFF_DEGREE * Shape::new(2, 1).block * MAT_VEC_GROUP
+}
@@ -0,0 +1,323 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Modulus raising, the first step of bootstrapping: a ciphertext at the first RNS level, modulo
+// q_0 only, is lifted to every level of Q = q_0 * .. * q_L. Every coefficient c is taken as its
+// centered representative x in [-h, q_0 - h) with h = floor(q_0 / 2) and reduced modulo every
+// modulus:
+//
+//     e = (c + h) mod q_0,  x = e - h,  r_l = x mod q_l   for every level l
+//
+// The trace holds e as the remainder of c + h by q_0, as ModSwitchAir holds its rounding, and
+// r_l as the remainder by q_l of e - h + o_l, where the offset o_l, the least multiple of q_l not
+// below h, keeps the value positive. h and o_l follow from the public moduli. As in ModSwitchAir
+// every circuit row holds one coefficient, and the values are reduced by the Barrett blocks of
+// reduction.rs, one per row of a row group, so e and every r_l are range checked. The residue at
+// the first level is c again. Inputs are in coefficient form with empty higher levels; the
+// result holds every level.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::check_modulus_bits;
+use crate::error::StarkHeError;
+use crate::modswitch_air::MODSWITCH_MAX_MODULUS_BITS;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Shifted + Data + Reduction
+// M0 M1 R00 R01 R10 R11 E0 E1 C0 C1 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const SHIFTED_START: usize = RESULT_START + RESULT_LEN;
+const DATA_START: usize = SHIFTED_START + VALUE_NUM;
+const DATA_END: usize = DATA_START + VALUE_NUM;
+// the centering of every component, then its reduction at every level
+const REDUCTION_NUM: usize = VALUE_NUM * (1 + COEFF_LEVEL);
+// a centered value is below q_0 + q_l, so its quotient takes a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MODSWITCH_MAX_MODULUS_BITS as usize + 1,
+    MODSWITCH_MAX_MODULUS_BITS as usize,
+);
+// one row per centering and reduction, then padding
+pub const MODRAISE_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of ModRaiseAir in canonical order.
+pub fn modraise_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", MODRAISE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn shifted_column(v: usize) -> usize {
+    SHIFTED_START + v
+}
+
+fn data_column(v: usize) -> usize {
+    DATA_START + v
+}
+
+// One coefficient with all its residues after modulus raising, and the shifted value e of the
+// centering.
+pub struct RaisedCoefficient {
+    pub results: [u64; COEFF_LEVEL],
+    pub shifted: u64,
+}
+
+// Lifts the residue `c` modulo the first modulus to every modulus.
+pub fn raise_coefficient(c: u64, modulus: &[u64]) -> RaisedCoefficient {
+    let first = modulus[0] as u128;
+    let half = first / 2;
+    let shifted = c as u128 + half;
+    let wrap = shifted >= first;
+    let shifted = shifted - wrap as u128 * first;
+    let centered = shifted as i128 - half as i128;
+
+    RaisedCoefficient {
+        results: std::array::from_fn(|l| centered.rem_euclid(modulus[l] as i128) as u64),
+        shifted: shifted as u64,
+    }
+}
+
+// h and the offset o_l at every level.
+pub fn centering(modulus: &[u64]) -> (u64, [u64; COEFF_LEVEL]) {
+    let half = modulus[0] / 2;
+    (
+        half,
+        std::array::from_fn(|l| half.next_multiple_of(modulus[l])),
+    )
+}
+
+// Value j of a row of the component with residue `c` shifted to `shifted`: its centering for
+// j = 0, its reduction at level j - 1 otherwise.
+fn centered_value(c: u64, shifted: u64, modulus: &[u64], j: usize) -> u128 {
+    let (half, offsets) = centering(modulus);
+    match j {
+        0 => c as u128 + half as u128,
+        _ => shifted as u128 + offsets[j - 1] as u128 - half as u128,
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`: the centering of every component to
+// its shifted column, then its reduction at every level to its result column.
+pub fn modraise_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::ModRaise, modulus, MODSWITCH_MAX_MODULUS_BITS)?;
+    let (half, offsets) = centering(modulus);
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for v in 0..VALUE_NUM {
+        results.push(shifted_column(v));
+        moduli.push(modulus[0]);
+        bounds.push(modulus[0] as u128 - 1 + half as u128);
+        for l in 0..COEFF_LEVEL {
+            results.push(result_column(v, l));
+            moduli.push(modulus[l]);
+            bounds.push(modulus[0] as u128 - 1 - half as u128 + offsets[l] as u128);
+        }
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Trace of modulus raising `data`, whose ciphertext is at the first level.
+pub fn modraise_trace(data: &CiphertextData) -> Result<TraceType, StarkHeError> {
+    if data.modulus.len() != COEFF_LEVEL {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "modulus raising needs {} moduli, found {}",
+            COEFF_LEVEL,
+            data.modulus.len()
+        )));
+    }
+    check_modulus_bits(&data.modulus, MODSWITCH_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    for (v, component) in data.values.iter().enumerate() {
+        if !(component[0].len() == COEFF_DEGREE
+            && component[0].iter().all(|&c| c < data.modulus[0])
+            && component[1..].iter().all(Vec::is_empty))
+        {
+            return Err(StarkHeError::InvalidInput(format!(
+                "component {} must be {} residues modulo {} at the first level only",
+                v, COEFF_DEGREE, data.modulus[0]
+            )));
+        }
+    }
+    let reductions = modraise_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for l in 0..COEFF_LEVEL {
+        columns[l] = vec![element_from_u64(data.modulus[l]); STATE_LENGTH];
+    }
+    let mut shifted = vec![[0; VALUE_NUM]; STATE_LENGTH];
+    for v in 0..VALUE_NUM {
+        for k in 0..STATE_LENGTH {
+            let c = data.values[v][0][k];
+            columns[data_column(v)][k] = element_from_u64(c);
+            let raised = raise_coefficient(c, &data.modulus);
+            shifted[k][v] = raised.shifted;
+            columns[shifted_column(v)][k] = element_from_u64(raised.shifted);
+            for l in 0..COEFF_LEVEL {
+                columns[result_column(v, l)][k] = element_from_u64(raised.results[l]);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let v = j / (1 + COEFF_LEVEL);
+        let c = data.values[v][0][k];
+        centered_value(c, shifted[k][v], &data.modulus, j % (1 + COEFF_LEVEL))
+    }))
+}
+
+pub fn build_modraise_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in coefficient form at the first level
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    modraise_trace(&data)
+}
+
+pub fn get_modraise_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, MODRAISE_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The centered values of one row, in the order of modraise_reductions.
+fn row_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    half: E,
+    offsets: &[E; COEFF_LEVEL],
+) -> [E; REDUCTION_NUM] {
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for v in 0..VALUE_NUM {
+        let j = v * (1 + COEFF_LEVEL);
+        values[j] = row[data_column(v)] + half;
+        for l in 0..COEFF_LEVEL {
+            values[j + 1 + l] = row[shifted_column(v)] - half + offsets[l];
+        }
+    }
+    values
+}
+
+pub struct ModRaiseAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    half: BaseElement,
+    offsets: [BaseElement; COEFF_LEVEL],
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for ModRaiseAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The values are linear and selected by a periodic column.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("mod-raise proofs record their moduli");
+        let reductions = modraise_reductions(&modulus).expect("mod-raise moduli were checked");
+        let (half, offsets) = centering(&modulus);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(
+            trace_info,
+            reductions.degrees(1, &[]),
+            num_assertions,
+            options,
+        );
+        ModRaiseAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            half: element_from_u64(half),
+            offsets: offsets.map(element_from_u64),
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let values = row_values(
+            frame.current(),
+            E::from(self.half),
+            &self.offsets.map(E::from),
+        );
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    // Results of every level are bound on the first row of every group or of the groups of the
+    // window, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Modulus switching of a BFV ciphertext from Q = q_0 * .. * q_L to Q' = Q / q_L, dropping the
+// last RNS level. Every coefficient c is scaled and rounded, c' = round(c / q_L), which in RNS
+// form only needs the residues of c:
+//
+//     e = (c mod q_L + h) mod q_L,  h = floor(q_L / 2)
+//     c' mod q_l = (c mod q_l + h - e) * q_L^-1 mod q_l   for every kept level l
+//
+// The trace holds e as the remainder of c_L + h by q_L, and r as the remainder by q_l of
+//
+//     (c_l + h - e + o_l) * q_L^-1 mod q_l
+//
+// where the offset o_l, the least multiple of q_l not below q_L - 1 - h, keeps the value
+// positive. h, o_l and q_L^-1 follow from the public moduli. As in MulAir every circuit row
+// holds one coefficient of every level, and the values are reduced by the Barrett blocks of
+// reduction.rs, one per row of a row group; the remainder bits range check e and every r, so no
+// rounding but the nearest one satisfies the constraints. Inputs are in coefficient form; the
+// dropped level of the result is left empty. The trace, constraints and result bindings are
+// shared with RescaleAir.
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, load_data_file, read_modulus, BaseElement, CiphertextData, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Rounded + Data + Reduction
+// M0 M1 R00 R10 E0 E1 X00 X01 X10 X11 K.. R.. S..
+pub const DROPPED_LEVEL: usize = COEFF_LEVEL - 1;
+pub const KEPT_LEVEL_NUM: usize = COEFF_LEVEL - 1;
+// the scaled values must stay below 2^127 with their moduli
+pub const MODSWITCH_MAX_MODULUS_BITS: u32 = 62;
+const RESULT_LEN: usize = VALUE_NUM * KEPT_LEVEL_NUM;
+const RESULT_START: usize = MODULUS_NUM;
+const ROUNDED_START: usize = RESULT_START + RESULT_LEN;
+const DATA_START: usize = ROUNDED_START + VALUE_NUM;
+const DATA_END: usize = DATA_START + VALUE_NUM * COEFF_LEVEL;
+// the rounding of every component, then its scaling at every kept level
+const REDUCTION_NUM: usize = VALUE_NUM * (1 + KEPT_LEVEL_NUM);
+// a scaled value is below (2 q_l + q_L) * q_l, so its quotient below 3 * 2^62
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(3 << MODSWITCH_MAX_MODULUS_BITS),
+    MODSWITCH_MAX_MODULUS_BITS as usize,
+);
+// one row per rounding and scaling, then padding
+pub const MODSWITCH_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of ModSwitchAir in canonical order.
+pub fn modswitch_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("kept_level_num", KEPT_LEVEL_NUM),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", MODSWITCH_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    RESULT_START + v * KEPT_LEVEL_NUM + l
+}
+
+fn rounded_column(v: usize) -> usize {
+    ROUNDED_START + v
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    DATA_START + v * COEFF_LEVEL + l
+}
+
+// a^-1 mod m, None if they are not coprime.
+pub fn inverse_mod(a: u64, m: u64) -> Option<u64> {
+    let (mut r0, mut r1) = (m as i128, (a % m) as i128);
+    let (mut t0, mut t1) = (0i128, 1i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    (r0 == 1).then(|| t0.rem_euclid(m as i128) as u64)
+}
+
+// One coefficient with all its residues after modulus switching, and the rounded residue e.
+pub struct SwitchedCoefficient {
+    pub results: [u64; KEPT_LEVEL_NUM],
+    pub rounded: u64,
+}
+
+// Scales and rounds the coefficient with residues `x` by the last modulus. `inverses[l]` is
+// q_L^-1 mod q_l.
+pub fn switch_coefficient(
+    x: [u64; COEFF_LEVEL],
+    modulus: &[u64],
+    inverses: &[u64; KEPT_LEVEL_NUM],
+) -> SwitchedCoefficient {
+    let dropped = modulus[DROPPED_LEVEL] as u128;
+    let half = dropped / 2;
+    let shifted = x[DROPPED_LEVEL] as u128 + half;
+    let rounded = shifted % dropped;
+
+    let mut results = [0; KEPT_LEVEL_NUM];
+    for l in 0..KEPT_LEVEL_NUM {
+        let m = modulus[l] as i128;
+        let difference = (x[l] as i128 + half as i128 - rounded as i128).rem_euclid(m);
+        results[l] = (difference as u128 * inverses[l] as u128 % m as u128) as u64;
+    }
+    SwitchedCoefficient {
+        results,
+        rounded: rounded as u64,
+    }
+}
+
+// q_L^-1 modulo every kept modulus.
+pub fn level_inverses(modulus: &[u64]) -> Result<[u64; KEPT_LEVEL_NUM], String> {
+    let mut inverses = [0; KEPT_LEVEL_NUM];
+    for l in 0..KEPT_LEVEL_NUM {
+        inverses[l] = inverse_mod(modulus[DROPPED_LEVEL], modulus[l]).ok_or_else(|| {
+            format!(
+                "modulus {} is not coprime to modulus {}",
+                modulus[DROPPED_LEVEL], modulus[l]
+            )
+        })?;
+    }
+    Ok(inverses)
+}
+
+// The constants of the scaling: h, and q_L^-1 and the offset o_l at every kept level.
+pub struct Scaling {
+    pub half: u64,
+    pub inverses: [u64; KEPT_LEVEL_NUM],
+    pub offsets: [u64; KEPT_LEVEL_NUM],
+}
+
+impl Scaling {
+    pub fn new(modulus: &[u64]) -> Result<Self, String> {
+        let dropped = modulus[DROPPED_LEVEL];
+        let half = dropped / 2;
+        Ok(Scaling {
+            half,
+            inverses: level_inverses(modulus)?,
+            offsets: std::array::from_fn(|l| (dropped - 1 - half).next_multiple_of(modulus[l])),
+        })
+    }
+
+    // Value j of a row of the component with residues `x` rounded to `rounded`: its rounding
+    // for j = 0, its scaling at level j - 1 otherwise.
+    pub fn value(&self, x: [u64; COEFF_LEVEL], rounded: u64, j: usize) -> u128 {
+        match j {
+            0 => x[DROPPED_LEVEL] as u128 + self.half as u128,
+            _ => {
+                let l = j - 1;
+                (x[l] as u128 + self.half as u128 - rounded as u128 + self.offsets[l] as u128)
+                    * self.inverses[l] as u128
+            }
+        }
+    }
+}
+
+// The reductions of a row of `op`, modulus switching or rescaling, by the public moduli
+// `modulus`: the rounding of every component to its rounded column, then its scaling at every
+// kept level to its result column.
+pub fn modswitch_reductions(op: Op, modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(op, modulus, MODSWITCH_MAX_MODULUS_BITS)?;
+    let scaling = Scaling::new(modulus)?;
+    let dropped = modulus[DROPPED_LEVEL];
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for v in 0..VALUE_NUM {
+        results.push(rounded_column(v));
+        moduli.push(dropped);
+        bounds.push(dropped as u128 - 1 + scaling.half as u128);
+        for l in 0..KEPT_LEVEL_NUM {
+            results.push(result_column(v, l));
+            moduli.push(modulus[l]);
+            bounds.push(
+                (modulus[l] as u128 - 1 + scaling.half as u128 + scaling.offsets[l] as u128)
+                    * scaling.inverses[l] as u128,
+            );
+        }
+    }
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Trace of modulus switching `data` for `op`, modulus switching or rescaling.
+pub fn modswitch_trace(op: Op, data: &CiphertextData) -> Result<TraceType, StarkHeError> {
+    if data.modulus.len() < 2 {
+        return Err(StarkHeError::DimensionMismatch(String::from(
+            "modulus switching needs a second RNS level",
+        )));
+    }
+    check_modulus_bits(&data.modulus, MODSWITCH_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, STATE_LENGTH, &data.values)?;
+    let inverses = level_inverses(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let scaling = Scaling::new(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let reductions = modswitch_reductions(op, &data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for l in 0..COEFF_LEVEL {
+        columns[l] = vec![element_from_u64(data.modulus[l]); STATE_LENGTH];
+    }
+    let residues = |v: usize, k: usize| std::array::from_fn(|l| data.values[v][l][k]);
+    let mut rounded = vec![[0; VALUE_NUM]; STATE_LENGTH];
+    for v in 0..VALUE_NUM {
+        for k in 0..STATE_LENGTH {
+            let x = residues(v, k);
+            for l in 0..COEFF_LEVEL {
+                columns[data_column(v, l)][k] = element_from_u64(x[l]);
+            }
+            let switched = switch_coefficient(x, &data.modulus, &inverses);
+            rounded[k][v] = switched.rounded;
+            columns[rounded_column(v)][k] = element_from_u64(switched.rounded);
+            for l in 0..KEPT_LEVEL_NUM {
+                columns[result_column(v, l)][k] = element_from_u64(switched.results[l]);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let v = j / (1 + KEPT_LEVEL_NUM);
+        scaling.value(residues(v, k), rounded[k][v], j % (1 + KEPT_LEVEL_NUM))
+    }))
+}
+
+pub fn build_modswitch_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in coefficient form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    modswitch_trace(Op::ModSwitch, &data)
+}
+
+// Results of the kept levels, read back from the first row of every group; the dropped level
+// is empty.
+pub fn read_modswitch_result(trace: &TraceType) -> Vec<Vec<Vec<BaseElement>>> {
+    (0..VALUE_NUM)
+        .map(|v| {
+            let mut levels = vec![Vec::new(); COEFF_LEVEL];
+            for l in 0..KEPT_LEVEL_NUM {
+                levels[l] = trace
+                    .get_column(result_column(v, l))
+                    .iter()
+                    .step_by(MODSWITCH_GROUP)
+                    .copied()
+                    .collect();
+            }
+            levels
+        })
+        .collect()
+}
+
+pub fn get_modswitch_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_modswitch_result(trace),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The constraints of modulus switching and rescaling: the reductions of a row, with the
+// constants of the scaling as field elements.
+pub struct ModSwitchConstraints {
+    reductions: Reductions,
+    half: BaseElement,
+    inverses: [BaseElement; KEPT_LEVEL_NUM],
+    offsets: [BaseElement; KEPT_LEVEL_NUM],
+}
+
+impl ModSwitchConstraints {
+    // Constraints of `op` for the public moduli `modulus`, which parse_data checked.
+    pub fn new(op: Op, modulus: &[u64]) -> Self {
+        let reductions =
+            modswitch_reductions(op, modulus).expect("modulus switching moduli were checked");
+        let scaling = Scaling::new(modulus).expect("modulus switching moduli were checked");
+        ModSwitchConstraints {
+            reductions,
+            half: element_from_u64(scaling.half),
+            inverses: scaling.inverses.map(element_from_u64),
+            offsets: scaling.offsets.map(element_from_u64),
+        }
+    }
+
+    pub fn reductions(&self) -> &Reductions {
+        &self.reductions
+    }
+
+    // The values are linear in the trace columns and selected by a periodic column.
+    pub fn degrees(&self) -> Vec<TransitionConstraintDegree> {
+        self.reductions.degrees(1, &[])
+    }
+
+    pub fn evaluate<E: FieldElement + From<BaseElement>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let row = frame.current();
+        let half = E::from(self.half);
+        let mut values = [E::ZERO; REDUCTION_NUM];
+        for v in 0..VALUE_NUM {
+            let j = v * (1 + KEPT_LEVEL_NUM);
+            values[j] = row[data_column(v, DROPPED_LEVEL)] + half;
+            for l in 0..KEPT_LEVEL_NUM {
+                values[j + 1 + l] = (row[data_column(v, l)] + half - row[rounded_column(v)]
+                    + E::from(self.offsets[l]))
+                    * E::from(self.inverses[l]);
+            }
+        }
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    // Results of the kept levels are bound on the first row of every group or of the groups
+    // of the window, the modulus columns to the public moduli.
+    pub fn assertions(
+        &self,
+        result: &[Vec<Vec<BaseElement>>],
+        modulus: &[u64],
+        window: Option<&[usize]>,
+    ) -> Vec<Assertion<BaseElement>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..KEPT_LEVEL_NUM {
+                columns.push((result_column(v, l), result[v][l].clone()));
+            }
+        }
+        let mut assertions = self.reductions.bind_results(columns, window);
+        assertions.extend(self.reductions.bind_result_moduli(modulus, window));
+        assertions
+    }
+}
+
+// Number of assertions ModSwitchConstraints::assertions makes.
+pub fn modswitch_assertion_num(window: Option<&[usize]>) -> usize {
+    grouped_result_assertion_num(RESULT_LEN, window)
+        + grouped_result_assertion_num(MODULUS_NUM, window)
+}
+
+pub struct ModSwitchAir {
+    context: AirContext<BaseElement>,
+    constraints: ModSwitchConstraints,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for ModSwitchAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("mod-switch proofs record their moduli");
+        let constraints = ModSwitchConstraints::new(Op::ModSwitch, &modulus);
+        let num_assertions = modswitch_assertion_num(pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, constraints.degrees(), num_assertions, options);
+        ModSwitchAir {
+            context: constraints.reductions().boundary().apply(context),
+            constraints,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.constraints.reductions().periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.constraints.evaluate(frame, periodic_values, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        self.constraints
+            .assertions(&self.result, &self.modulus, self.window.as_deref())
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Ciphertext-ciphertext multiplication in Montgomery form: the BFV tensor product of MulAir on
+// operands whose NTT-form coefficients are held as a * R mod m (see montgomery.rs), with every
+// term reduced by REDC instead of by a quotient, so the results are in Montgomery form as well.
+// As in MulAir every circuit row is repeated over a row group, and row j of a group proves the
+// REDC of term j with a Barrett block against the moduli of the public inputs, which range checks
+// the result and the REDC multiple. Data files have MulAir's format with the coefficients in
+// Montgomery form; every modulus must be odd.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::check_levels;
+use crate::error::StarkHeError;
+use crate::montgomery::{
+    evaluate_redc_value, redc, redc_bound, redc_value, MONTGOMERY_MAX_MODULUS_BITS,
+    MONTGOMERY_QUOTIENT_BITS, MONTGOMERY_R_BITS,
+};
+use crate::mul_air::{tensor_terms, MulData, MUL_DATA_NUM, MUL_RESULT_NUM};
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 R20 R21 A00 A01 A10 A11 B00 B01 B10 B11 K.. R.. S..
+const RESULT_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + MUL_DATA_NUM * DATA_LEN;
+const REDUCTION_WIDTH: usize = reduction_width(
+    MONTGOMERY_QUOTIENT_BITS,
+    MONTGOMERY_MAX_MODULUS_BITS as usize,
+);
+// one row per term and level, then padding
+pub const MONT_MUL_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of MontMulAir in canonical order.
+pub fn mont_mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("result_num", MUL_RESULT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("montgomery_r_bits", MONTGOMERY_R_BITS as usize),
+        ("group", MONT_MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, which must be odd, term r at level l
+// being value r * COEFF_LEVEL + l of the row.
+pub fn mont_mul_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::MontMul, modulus, MONTGOMERY_MAX_MODULUS_BITS)?;
+    if let Some(m) = modulus.iter().find(|&&m| m % 2 == 0) {
+        return Err(format!("Montgomery modulus {} is not odd", m));
+    }
+    Reductions::of_components(
+        modulus,
+        MUL_RESULT_NUM,
+        result_column,
+        |_, l| redc_bound(modulus[l]),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_mont_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: MulData = load_data_file(&arg.data_file_path)?;
+    let reductions = mont_mul_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    let terms = |k: usize, l: usize| {
+        let [a, b] = [0, 1].map(|d| [0, 1].map(|v| data.values[d][v][l][k]));
+        tensor_terms(a, b)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for d in 0..MUL_DATA_NUM {
+                for v in 0..VALUE_NUM {
+                    columns[data_column(d, v, l)][k] = element_from_u64(data.values[d][v][l][k]);
+                }
+            }
+            for (r, term) in terms(k, l).into_iter().enumerate() {
+                columns[result_column(r, l)][k] = element_from_u64(redc(term, m).0);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let (r, l) = (j / COEFF_LEVEL, j % COEFF_LEVEL);
+        let m = data.modulus[l];
+        let term = terms(k, l)[r];
+        redc_value(redc(term, m).0, term, m)
+    }))
+}
+
+pub fn get_mont_mul_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MUL_RESULT_NUM, MONT_MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The values reducing to the terms of one row by the public moduli `modulus`, term r at level l
+// at r * COEFF_LEVEL + l.
+fn row_values<E: FieldElement + From<BaseElement>>(row: &[E], modulus: &[u64]) -> [E; RESULT_LEN] {
+    let mut values = [E::ZERO; RESULT_LEN];
+    for l in 0..COEFF_LEVEL {
+        let m = E::from(element_from_u64(modulus[l]));
+        let a = [row[data_column(0, 0, l)], row[data_column(0, 1, l)]];
+        let b = [row[data_column(1, 0, l)], row[data_column(1, 1, l)]];
+        let terms = [a[0] * b[0], a[0] * b[1] + a[1] * b[0], a[1] * b[1]];
+        for r in 0..MUL_RESULT_NUM {
+            values[r * COEFF_LEVEL + l] =
+                evaluate_redc_value(row[result_column(r, l)], terms[r], m);
+        }
+    }
+    values
+}
+
+pub struct MontMulAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for MontMulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in MulAir the values are quadratic in the operands and selected by a periodic column;
+    // the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("mont-mul proofs record their moduli");
+        let reductions = mont_mul_reductions(&modulus).expect("mont-mul moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        MontMulAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let values = row_values(frame.current(), &self.modulus);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, result);
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, the
+    // modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..MUL_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(r, l), self.result[r][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Montgomery-form arithmetic: a residue a modulo an odd m is held as a * R mod m with
+// R = 2^MONTGOMERY_R_BITS, and a product x of two such residues is reduced by REDC to
+// x * R^-1 mod m, which is again in Montgomery form. The REDC constraint
+//
+//     c * R = x + t * m
+//
+// proves the reduction with the REDC multiple t, which lies in -R..R. Circuits prove it with a
+// Barrett block of reduction.rs, which reduces c * (R + 1) - x + R * m = c + (t + R) * m to c:
+// the block range checks c below m and the quotient t + R below 2R, so the multiple is range
+// checked as well. Libraries keeping their NTT-form coefficients in Montgomery form, as Lattigo does, can
+// prove products without converting out of it and back.
+
+use winter_math::FieldElement;
+
+use crate::air::{element_from_u128, element_from_u64, BaseElement};
+
+pub const MONTGOMERY_R_BITS: u32 = 64;
+// c + (t + R) * m stays below 2^127 for products of two residues and the sum of two such
+// products
+pub const MONTGOMERY_MAX_MODULUS_BITS: u32 = 62;
+// bits of the quotient t + R of the value a Barrett block reduces
+pub const MONTGOMERY_QUOTIENT_BITS: usize = MONTGOMERY_R_BITS as usize + 1;
+
+// R as a field element.
+pub fn montgomery_r<E: FieldElement + From<BaseElement>>() -> E {
+    E::from(element_from_u128(1 << MONTGOMERY_R_BITS))
+}
+
+// -m^-1 mod R for an odd `m`, by Newton's iteration, each step doubling the correct low bits.
+pub fn neg_inverse(m: u64) -> u64 {
+    assert!(m % 2 == 1, "Montgomery moduli must be odd, not {}", m);
+    let mut inverse = m;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(m.wrapping_mul(inverse)));
+    }
+    inverse.wrapping_neg()
+}
+
+// x * R^-1 mod m and the multiple t with c * R = x + t * m, for x < m * R.
+pub fn redc(x: u128, m: u64) -> (u64, BaseElement) {
+    debug_assert!(x < (m as u128) << MONTGOMERY_R_BITS);
+    let t = (x as u64).wrapping_mul(neg_inverse(m));
+    let u = ((x + t as u128 * m as u128) >> MONTGOMERY_R_BITS) as u64;
+    if u >= m {
+        // c * R = x + (t - R) * m
+        (u - m, element_from_u64(t) - montgomery_r())
+    } else {
+        (u, element_from_u64(t))
+    }
+}
+
+pub fn to_montgomery(a: u64, m: u64) -> u64 {
+    (((a as u128) << MONTGOMERY_R_BITS) % m as u128) as u64
+}
+
+pub fn from_montgomery(a: u64, m: u64) -> u64 {
+    redc(a as u128, m).0
+}
+
+// The value c * (R + 1) - x + R * m a Barrett block reduces to the REDC `c` of `x` modulo `m`,
+// at most redc_bound(m).
+pub fn redc_value(c: u64, x: u128, m: u64) -> u128 {
+    ((c as u128 + m as u128) << MONTGOMERY_R_BITS) + c as u128 - x
+}
+
+pub fn redc_bound(m: u64) -> u128 {
+    ((m as u128) << (MONTGOMERY_R_BITS + 1)) - 1
+}
+
+// redc_value over the field, which the block reduces to `c` with a quotient below 2R exactly when
+// `c` is x * R^-1 mod m.
+pub fn evaluate_redc_value<E: FieldElement + From<BaseElement>>(c: E, x: E, m: E) -> E {
+    (c + m) * montgomery_r() + c - x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULI: [u64; 3] = [65537, 1125899906826241, (1 << 62) - 57];
+
+    #[test]
+    fn redc_multiplies_in_montgomery_form() {
+        for m in MODULI {
+            for (a, b) in [(0, 5), (1, 1), (m - 1, m - 1), (12345 % m, m / 3)] {
+                let (a_mont, b_mont) = (to_montgomery(a, m), to_montgomery(b, m));
+                let x = a_mont as u128 * b_mont as u128;
+                let (c, t) = redc(x, m);
+                assert!(c < m);
+                assert_eq!(
+                    from_montgomery(c, m) as u128,
+                    a as u128 * b as u128 % m as u128
+                );
+                let value = redc_value(c, x, m);
+                assert!(value <= redc_bound(m));
+                assert_eq!(value % m as u128, c as u128);
+                assert_eq!(
+                    element_from_u64(c) + (t + montgomery_r()) * element_from_u64(m),
+                    element_from_u128(value)
+                );
+                assert_eq!(
+                    evaluate_redc_value(
+                        element_from_u64(c),
+                        element_from_u128(x),
+                        element_from_u64(m)
+                    ),
+                    element_from_u128(value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "must be odd")]
+    fn even_modulus_is_rejected() {
+        neg_inverse(1 << 20);
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Ciphertext-ciphertext multiplication. For ciphertexts a = (a0, a1) and b = (b0, b1) the BFV
+// tensor product is
+//
+//     c0 = a0 * b0,  c1 = a0 * b1 + a1 * b0,  c2 = a1 * b1
+//
+// Operands are expected in NTT (evaluation) form, where the polynomial products are
+// coefficient-wise, so every circuit row holds the tensor product of one evaluation point for
+// every RNS level. The terms are reduced to the results by the Barrett blocks of reduction.rs,
+// one term per row of a row group, against the moduli of the public inputs. The following
+// scale-and-round by t/q and the relinearization of c2 are separate steps.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 R20 R21 A00 A01 A10 A11 B00 B01 B10 B11 K.. R.. S..
+pub const MUL_DATA_NUM: usize = 2;
+pub const MUL_RESULT_NUM: usize = 3;
+// 2 * m^2 must stay below the field modulus for the middle term not to wrap
+pub const MUL_MAX_MODULUS_BITS: u32 = 62;
+const RESULT_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + MUL_DATA_NUM * DATA_LEN;
+// the middle term reaches 2 (m - 1)^2, so its quotient takes a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    MUL_MAX_MODULUS_BITS as usize + 1,
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per term and level, then padding
+pub const MUL_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of MulAir in canonical order.
+pub fn mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("result_num", MUL_RESULT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as CustomData, with two operands.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MulData {
+    pub modulus: Vec<u64>,
+    pub values: [[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; MUL_DATA_NUM],
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+// Tensor product terms of one evaluation point, before their reduction.
+pub fn tensor_terms(a: [u64; VALUE_NUM], b: [u64; VALUE_NUM]) -> [u128; MUL_RESULT_NUM] {
+    let (a, b) = (a.map(u128::from), b.map(u128::from));
+    [a[0] * b[0], a[0] * b[1] + a[1] * b[0], a[1] * b[1]]
+}
+
+// Tensor product terms of one evaluation point and the quotients by `m` removed from them.
+pub fn tensor_product(
+    a: [u64; VALUE_NUM],
+    b: [u64; VALUE_NUM],
+    m: u64,
+) -> ([u64; MUL_RESULT_NUM], [u64; MUL_RESULT_NUM]) {
+    let (terms, m) = (tensor_terms(a, b), m as u128);
+    (terms.map(|t| (t % m) as u64), terms.map(|t| (t / m) as u64))
+}
+
+// The reductions of a row by the public moduli `modulus`, term r at level l being value
+// r * COEFF_LEVEL + l of the row: the terms of residues are below (m - 1)^2, twice that for the
+// middle one.
+pub fn mul_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Mul, modulus, MUL_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        MUL_RESULT_NUM,
+        result_column,
+        |r, l| (1 + (r == 1) as u128) * (modulus[l] as u128 - 1).pow(2),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: MulData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+
+    let operands = Ciphertext::from_operands(&data.modulus, STATE_LENGTH, data.values)?;
+    let reductions = mul_reductions(operands[0].modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for (d, operand) in operands.iter().enumerate() {
+        operand.write_columns(&mut columns, |v, l| data_column(d, v, l));
+    }
+    let terms = |k: usize, l: usize| {
+        let [a, b] = [0, 1].map(|d| [0, 1].map(|v| operands[d].coeff(v, l, k)));
+        tensor_terms(a, b)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = operands[0].modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for (r, term) in terms(k, l).into_iter().enumerate() {
+                columns[result_column(r, l)][k] = element_from_u64((term % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        terms(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    }))
+}
+
+pub fn get_mul_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MUL_RESULT_NUM, MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The terms of one row, term r at level l at r * COEFF_LEVEL + l.
+fn row_terms<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    let mut terms = [E::ZERO; RESULT_LEN];
+    for l in 0..COEFF_LEVEL {
+        let a = [row[data_column(0, 0, l)], row[data_column(0, 1, l)]];
+        let b = [row[data_column(1, 0, l)], row[data_column(1, 1, l)]];
+        let products = [a[0] * b[0], a[0] * b[1] + a[1] * b[0], a[1] * b[1]];
+        for (r, product) in products.into_iter().enumerate() {
+            terms[r * COEFF_LEVEL + l] = product;
+        }
+    }
+    terms
+}
+
+pub struct MulAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for MulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The terms are quadratic in the operands and selected by a periodic column; the moduli were
+    // checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("mul proofs record their moduli");
+        let reductions = mul_reductions(&modulus).expect("mul moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        MulAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let terms = row_terms(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &terms, result);
+    }
+
+    // Unlike FreshAir, every result coefficient is bound to the public inputs, on the first row
+    // of its group; the modulus columns are bound to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..MUL_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(r, l), self.result[r][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
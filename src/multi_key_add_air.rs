@@ -0,0 +1,281 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Multi-key addition. A multi-key BFV ciphertext of parties P decrypts under the concatenated
+// secret keys (1, s_p for p in P): its components are c_0 and one component c_p per party, so
+// the ciphertexts of different parties are added by extending both to the union of their
+// parties, with zero components for the missing ones, and adding componentwise,
+//
+//     (a_0 + b_0, a_1 + b_1, ..., a_n + b_n)
+//
+// e.g. to aggregate inputs of several parties without a joint key. Parties are numbered
+// 0..MULTI_KEY_PARTY_NUM and component p + 1 always belongs to party p, so that the layout and
+// the number of results are fixed. As in SubAir every circuit row proves, for every component
+// and level,
+//
+//     r = (a + b) mod m
+//
+// with the Barrett blocks of reduction.rs, one sum per row of a row group, against the moduli of
+// the public inputs; the quotient is a single bit.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 .. R41 A00 .. A41 B00 .. B41 K.. R.. S..
+pub const MULTI_KEY_PARTY_NUM: usize = 4;
+pub const MULTI_KEY_VALUE_NUM: usize = MULTI_KEY_PARTY_NUM + 1;
+const DATA_NUM: usize = 2;
+const RESULT_LEN: usize = MULTI_KEY_VALUE_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = RESULT_LEN;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + DATA_NUM * DATA_LEN;
+pub const MULTI_KEY_MAX_MODULUS_BITS: u32 = 62;
+// a sum of two residues is below 2m, so its quotient is a single bit
+const REDUCTION_WIDTH: usize = reduction_width(1, MULTI_KEY_MAX_MODULUS_BITS as usize);
+// one row per sum, then padding
+pub const MULTI_KEY_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of MultiKeyAddAir in canonical order.
+pub fn multi_key_add_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", DATA_NUM),
+        ("party_num", MULTI_KEY_PARTY_NUM),
+        ("value_num", MULTI_KEY_VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", MULTI_KEY_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// A ciphertext of `Parties`: Values holds c_0, then the component of every party in the order
+// of `Parties`. A fresh single-key ciphertext (c0, c1) of party p has Parties = [p].
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MultiKeyCiphertext {
+    pub parties: Vec<usize>,
+    pub values: Vec<[Vec<u64>; COEFF_LEVEL]>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MultiKeyAddData {
+    pub modulus: Vec<u64>,
+    pub operands: [MultiKeyCiphertext; DATA_NUM],
+}
+
+impl MultiKeyCiphertext {
+    // Components extended to every party, zero for the parties the ciphertext is not under.
+    pub fn extend(&self) -> Result<Vec<[Vec<u64>; COEFF_LEVEL]>, String> {
+        if self.values.len() != self.parties.len() + 1 {
+            return Err(format!(
+                "a ciphertext of {} parties needs {} components, found {}",
+                self.parties.len(),
+                self.parties.len() + 1,
+                self.values.len()
+            ));
+        }
+        let mut values = vec![std::array::from_fn(|_| vec![0; COEFF_DEGREE]); MULTI_KEY_VALUE_NUM];
+        values[0] = self.values[0].clone();
+        for (i, &p) in self.parties.iter().enumerate() {
+            if p >= MULTI_KEY_PARTY_NUM {
+                return Err(format!("party {} is not below {}", p, MULTI_KEY_PARTY_NUM));
+            }
+            if self.parties[..i].contains(&p) {
+                return Err(format!("party {} is listed twice", p));
+            }
+            values[p + 1] = self.values[i + 1].clone();
+        }
+        Ok(values)
+    }
+}
+
+fn result_column(u: usize, l: usize) -> usize {
+    level_column(RESULT_START, u, l)
+}
+
+fn data_column(d: usize, u: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, u, l)
+}
+
+// a + b mod m for residues a, b < m.
+pub fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`, the sum of component u at level l
+// being value u * COEFF_LEVEL + l of the row.
+pub fn multi_key_add_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::MultiKeyAdd, modulus, MULTI_KEY_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        MULTI_KEY_VALUE_NUM,
+        result_column,
+        |_, l| 2 * (modulus[l] as u128 - 1),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_multi_key_add_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: MultiKeyAddData = load_data_file(&arg.data_file_path)?;
+    let operands = data
+        .operands
+        .iter()
+        .map(|operand| operand.extend().map_err(StarkHeError::InvalidInput))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_modulus_bits(&data.modulus, MULTI_KEY_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    for operand in operands.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+    let reductions = multi_key_add_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for u in 0..MULTI_KEY_VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                let (a, b) = (operands[0][u][l][k], operands[1][u][l][k]);
+                columns[data_column(0, u, l)][k] = element_from_u64(a);
+                columns[data_column(1, u, l)][k] = element_from_u64(b);
+                columns[result_column(u, l)][k] = element_from_u64(add_mod(a, b, m));
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let (u, l) = (j / COEFF_LEVEL, j % COEFF_LEVEL);
+        operands[0][u][l][k] as u128 + operands[1][u][l][k] as u128
+    }))
+}
+
+pub fn get_multi_key_add_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MULTI_KEY_VALUE_NUM, MULTI_KEY_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The sums of one row, component u at level l at u * COEFF_LEVEL + l.
+fn row_sums<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    for u in 0..MULTI_KEY_VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            sums[u * COEFF_LEVEL + l] = row[data_column(0, u, l)] + row[data_column(1, u, l)];
+        }
+    }
+    sums
+}
+
+pub struct MultiKeyAddAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for MultiKeyAddAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The sums are linear and selected by a periodic column, as in SubAir; the moduli were
+    // checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("multi-key-add proofs record their moduli");
+        let reductions =
+            multi_key_add_reductions(&modulus).expect("multi-key-add moduli were checked");
+        let degrees = reductions.degrees(1, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        MultiKeyAddAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let sums = row_sums(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for u in 0..MULTI_KEY_VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(u, l), self.result[u][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Ciphertext negation, coefficient-wise r = (m - c) mod m for both components and every RNS
+// level. A residue c lies in [0, m), so m - c lies in (0, m] and every circuit row proves
+//
+//     r = (m - c) mod m
+//
+// with the Barrett blocks of reduction.rs, one coefficient per row of a row group as in MulAir,
+// against the moduli of the public inputs: the quotient is a single bit, set for c = 0 only,
+// which keeps zero coefficients zero.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::check_modulus_bits;
+use crate::error::StarkHeError;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + RESULT_LEN;
+pub const NEG_MAX_MODULUS_BITS: u32 = 62;
+// m - c is at most m, so its quotient is a single bit
+const REDUCTION_WIDTH: usize = reduction_width(1, NEG_MAX_MODULUS_BITS as usize);
+// one row per coefficient and level, then padding
+pub const NEG_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of NegAir in canonical order.
+pub fn neg_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", NEG_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+// (m - c) mod m for a residue c < m.
+pub fn neg_mod(c: u64, m: u64) -> u64 {
+    if c == 0 {
+        0
+    } else {
+        m - c
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`, the negation of component v at level l
+// being value v * COEFF_LEVEL + l of the row.
+pub fn neg_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Neg, modulus, NEG_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| modulus[l] as u128,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_neg_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, NEG_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    let ciphertext = data.into_ciphertext()?;
+    let reductions = neg_reductions(ciphertext.modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    for l in 0..COEFF_LEVEL {
+        let m = ciphertext.modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                let c = ciphertext.coeff(v, l, k);
+                columns[result_column(v, l)][k] = element_from_u64(neg_mod(c, m));
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let (v, l) = (j / COEFF_LEVEL, j % COEFF_LEVEL);
+        (ciphertext.modulus()[l] - ciphertext.coeff(v, l, k)) as u128
+    }))
+}
+
+pub fn get_neg_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, NEG_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The moduli `modulus` minus the coefficients of one row, component v at level l at
+// v * COEFF_LEVEL + l.
+fn row_negations<E: FieldElement>(row: &[E], modulus: &[E]) -> [E; RESULT_LEN] {
+    let mut negations = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            negations[v * COEFF_LEVEL + l] = modulus[l] - row[data_column(v, l)];
+        }
+    }
+    negations
+}
+
+pub struct NegAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for NegAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The moduli are public constants, so the negations are linear and selected by a periodic
+    // column; the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("neg proofs record their moduli");
+        let reductions = neg_reductions(&modulus).expect("neg moduli were checked");
+        let degrees = reductions.degrees(1, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        NegAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let modulus: [E; COEFF_LEVEL] =
+            std::array::from_fn(|l| E::from(element_from_u64(self.modulus[l])));
+        let negations = row_negations(frame.current(), &modulus);
+        self.reductions
+            .evaluate(frame, periodic_values, &negations, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
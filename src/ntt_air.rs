@@ -0,0 +1,696 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Forward negacyclic NTT of both components of a ciphertext, from coefficient form into the
+// evaluation form the other circuits expect. The transform is SEAL's in-place Cooley-Tukey
+// network: stage s = 0..log2(N) - 1 with m = 2^s and t = N / 2m runs the butterflies
+//
+//     (a[j], a[j + t]) <- (a[j] + w * a[j + t], a[j] - w * a[j + t]) mod q
+//
+// for j = 2it..2it + t - 1 and w = psi^bitrev(m + i), psi being the smallest primitive 2N-th
+// root of unity modulo q. Result k is the evaluation at psi^(2 bitrev(k) + 1).
+//
+// Every circuit row is one butterfly of every polynomial: circuit row 16b + s holds butterfly b
+// of stage s, so a stage is a strided set of circuit rows and the last stage is bound to the
+// result by sequence assertions. Stages 12..15 are all-zero padding. The twiddles, the positions
+// and the stage selectors are periodic columns derived from the public moduli. A butterfly proves
+//
+//     X = (A + w * B) mod q,  Y = (A + q * (q - 1) - w * B) mod q
+//
+// with inputs A, B and outputs X, Y, q * (q - 1) keeping the second value nonnegative. Every
+// circuit row is repeated over a row group of reduction.rs, whose rows reduce the two values of
+// every polynomial against the public moduli, so the outputs are range-checked residues. Outputs
+// of stage s are wired to the inputs of stage s + 1 by a permutation argument in an auxiliary
+// column per polynomial: with random alpha and gamma, every value v at position j entering stage
+// s is the factor gamma - v - alpha * (sN + j) on the first row of its group, and the running
+// product z of output factors over input factors must return to 1. The inputs of stage 0 are the
+// private ciphertext.
+
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TraceLayout, TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+
+use crate::air::{
+    element_from_u128, element_from_u64, level_column, load_data_file, BaseElement, CiphertextData,
+    InputArg, PublicInputs, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{reduction_group, reduction_width, Reductions};
+
+// Inputs + Outputs, one column per polynomial each, + Reduction
+// A00 A01 A10 A11 B.. X.. Y.. K.. R.. S..
+pub const NTT_STAGE_NUM: usize = COEFF_DEGREE.trailing_zeros() as usize;
+// rows per butterfly, leaving at least two padding rows after the last stage for the checks of
+// CkksEncodeAir
+pub const STAGE_SLOTS: usize = (NTT_STAGE_NUM + 2).next_power_of_two();
+pub const BUTTERFLY_NUM: usize = COEFF_DEGREE / 2;
+const POLY_NUM: usize = VALUE_NUM * COEFF_LEVEL;
+const INPUT_A_START: usize = 0;
+const INPUT_B_START: usize = INPUT_A_START + POLY_NUM;
+const OUTPUT_X_START: usize = INPUT_B_START + POLY_NUM;
+const OUTPUT_Y_START: usize = OUTPUT_X_START + POLY_NUM;
+const OUTPUT_END: usize = OUTPUT_Y_START + POLY_NUM;
+// the sum and the difference of every polynomial
+const REDUCTION_NUM: usize = 2 * POLY_NUM;
+// both values stay below q^2, so their quotients take the bits of the modulus
+const REDUCTION_WIDTH: usize =
+    reduction_width(MUL_MAX_MODULUS_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per value, then padding
+pub const NTT_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = OUTPUT_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = STAGE_SLOTS * BUTTERFLY_NUM;
+// circuit rows of a network
+pub const NTT_TRACE_LENGTH: usize = STATE_LENGTH;
+// one running product per polynomial, driven by alpha and gamma
+pub const NTT_AUX_WIDTH: usize = POLY_NUM;
+const AUX_WIDTH: usize = NTT_AUX_WIDTH;
+const AUX_RAND_NUM: usize = 2;
+
+// Periodic columns: stage selectors and the stride have a period of one group per stage slot,
+// positions and twiddles run over the whole trace. The first WIRING_PERIODIC_NUM are shared
+// with InttAir; those of the reductions follow, then the twiddles.
+const INPUT_SELECTOR: usize = 0;
+const OUTPUT_SELECTOR: usize = 1;
+const STRIDE: usize = 2;
+const POSITION: usize = 3;
+pub const WIRING_PERIODIC_NUM: usize = 4;
+
+// Trace layout constants of NttAir in canonical order.
+pub fn ntt_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("stage_num", NTT_STAGE_NUM),
+        ("group", NTT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn input_a_column(v: usize, l: usize) -> usize {
+    level_column(INPUT_A_START, v, l)
+}
+
+fn input_b_column(v: usize, l: usize) -> usize {
+    level_column(INPUT_B_START, v, l)
+}
+
+fn output_x_column(v: usize, l: usize) -> usize {
+    level_column(OUTPUT_X_START, v, l)
+}
+
+fn output_y_column(v: usize, l: usize) -> usize {
+    level_column(OUTPUT_Y_START, v, l)
+}
+
+// Output X (h = 0) or Y (h = 1) of component v at level l.
+fn output_column(h: usize, v: usize, l: usize) -> usize {
+    level_column([OUTPUT_X_START, OUTPUT_Y_START][h], v, l)
+}
+
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+pub fn pow_mod(base: u64, exponent: u64, m: u64) -> u64 {
+    let (mut result, mut base, mut exponent) = (1, base % m, exponent);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+pub fn bit_reverse(k: usize) -> usize {
+    k.reverse_bits() >> (usize::BITS as usize - NTT_STAGE_NUM)
+}
+
+// Smallest primitive 2N-th root of unity modulo q, the root SEAL uses. Half of all residues
+// lift to a primitive root when q is a prime with q = 1 mod 2N, so a few candidates suffice.
+pub fn minimal_primitive_root(q: u64) -> Option<u64> {
+    let two_n = 2 * COEFF_DEGREE as u64;
+    if q < 3 || !(q - 1).is_multiple_of(two_n) {
+        return None;
+    }
+    let root = (2..66)
+        .map(|x| pow_mod(x, (q - 1) / two_n, q))
+        .find(|&root| pow_mod(root, COEFF_DEGREE as u64, q) == q - 1)?;
+    let (mut power, square) = (root, mul_mod(root, root, q));
+    let mut minimal = root;
+    for _ in 0..COEFF_DEGREE {
+        minimal = minimal.min(power);
+        power = mul_mod(power, square, q);
+    }
+    Some(minimal)
+}
+
+// Moduli an NTT proof can be checked against: one NTT-friendly prime per level, small enough
+// for w * B to stay below the field modulus.
+pub fn check_ntt_modulus(modulus: &[u64]) -> Result<(), String> {
+    if modulus.len() != COEFF_LEVEL {
+        return Err(format!(
+            "NTT proofs need {} moduli, found {}",
+            COEFF_LEVEL,
+            modulus.len()
+        ));
+    }
+    for &q in modulus.iter() {
+        if q == 0 || 64 - q.leading_zeros() > MUL_MAX_MODULUS_BITS {
+            return Err(format!(
+                "modulus {} does not fit into {} bits",
+                q, MUL_MAX_MODULUS_BITS
+            ));
+        }
+        if minimal_primitive_root(q).is_none() {
+            return Err(format!(
+                "modulus {} has no primitive {}-th root of unity",
+                q,
+                2 * COEFF_DEGREE
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The reductions of a butterfly by the public moduli `modulus`: the sum of component v at level
+// l is value v * COEFF_LEVEL + l of a row, reduced to X, its difference value
+// POLY_NUM + v * COEFF_LEVEL + l, reduced to Y.
+pub fn ntt_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_ntt_modulus(modulus)?;
+    Reductions::of_components(
+        modulus,
+        2 * VALUE_NUM,
+        |c, l| output_column(c / VALUE_NUM, c % VALUE_NUM, l),
+        |_, l| (modulus[l] as u128).pow(2),
+        (INPUT_A_START..OUTPUT_END).collect(),
+        OUTPUT_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// psi^bitrev(k) for k = 0..N, indexed by m + i in the butterfly network.
+fn twiddles(q: u64) -> Vec<u64> {
+    let psi = minimal_primitive_root(q).expect("moduli are checked before use");
+    let mut powers = vec![1; COEFF_DEGREE];
+    for k in 1..COEFF_DEGREE {
+        powers[k] = mul_mod(powers[k - 1], psi, q);
+    }
+    (0..COEFF_DEGREE).map(|k| powers[bit_reverse(k)]).collect()
+}
+
+// Evaluations of the coefficients `a` modulo `q` in the order NttAir outputs them, computed
+// without a trace.
+pub fn forward_transform(mut a: Vec<u64>, q: u64) -> Vec<u64> {
+    let twiddles = twiddles(q);
+    for s in 0..NTT_STAGE_NUM {
+        for b in 0..BUTTERFLY_NUM {
+            let (_, j, t, w) = butterfly(b * STAGE_SLOTS + s, false).unwrap();
+            let (x, product) = (a[j], mul_mod(a[j + t], twiddles[w], q));
+            a[j] = (x + product) % q;
+            a[j + t] = (x + q - product) % q;
+        }
+    }
+    a
+}
+
+// Stride t of stage s: N / 2^(s+1) in the forward network, 2^s in the inverse one.
+fn stride(s: usize, inverse: bool) -> usize {
+    if inverse {
+        1 << s
+    } else {
+        COEFF_DEGREE >> (s + 1)
+    }
+}
+
+// Butterfly of a row: stage, position j of the first input, stride t and twiddle index m + i
+// with m = N / 2t; None on padding rows.
+pub fn butterfly(row: usize, inverse: bool) -> Option<(usize, usize, usize, usize)> {
+    let (s, b) = (row % STAGE_SLOTS, row / STAGE_SLOTS);
+    if s >= NTT_STAGE_NUM {
+        return None;
+    }
+    let t = stride(s, inverse);
+    let (m, i) = (COEFF_DEGREE / (2 * t), b / t);
+    Some((s, 2 * i * t + b % t, t, m + i))
+}
+
+// Execution trace with the wiring argument as its auxiliary segment. `wiring` holds the
+// columns A, B, X, Y of every polynomial.
+pub struct NttTrace {
+    layout: TraceLayout,
+    main: Matrix<BaseElement>,
+    modulus: Vec<u64>,
+    wiring: Vec<[usize; 4]>,
+    inverse: bool,
+    group: usize,
+}
+
+impl NttTrace {
+    // `group` is the number of rows every circuit row of `main` is repeated over.
+    pub fn new(
+        main: Matrix<BaseElement>,
+        modulus: Vec<u64>,
+        wiring: Vec<[usize; 4]>,
+        inverse: bool,
+        group: usize,
+    ) -> Self {
+        NttTrace {
+            layout: TraceLayout::new(main.num_cols(), [wiring.len()], [AUX_RAND_NUM]),
+            main,
+            modulus,
+            wiring,
+            inverse,
+            group,
+        }
+    }
+
+    pub fn modulus(&self) -> &[u64] {
+        &self.modulus
+    }
+}
+
+// Wiring columns of NttAir, in polynomial order.
+fn wiring_columns() -> Vec<[usize; 4]> {
+    let mut wiring = Vec::with_capacity(POLY_NUM);
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            wiring.push([
+                input_a_column(v, l),
+                input_b_column(v, l),
+                output_x_column(v, l),
+                output_y_column(v, l),
+            ]);
+        }
+    }
+    wiring
+}
+
+pub fn build_ntt_trace(arg: &InputArg) -> Result<NttTrace, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let reductions = ntt_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; OUTPUT_END];
+    let mut values = vec![[0; REDUCTION_NUM]; STATE_LENGTH];
+    for l in 0..COEFF_LEVEL {
+        let q = data.modulus[l];
+        let offset = q as u128 * (q as u128 - 1);
+        let twiddles = twiddles(q);
+        for v in 0..VALUE_NUM {
+            let mut a = data.values[v][l].clone();
+            assert!(
+                a.len() == COEFF_DEGREE && a.iter().all(|&c| c < q),
+                "operand coefficients must be {} residues modulo {}",
+                COEFF_DEGREE,
+                q
+            );
+            // the padding rows reduce the offset of the difference alone
+            for row in values.iter_mut() {
+                row[POLY_NUM + v * COEFF_LEVEL + l] = offset;
+            }
+            // rows are visited stage by stage, so every butterfly reads the outputs of the
+            // previous stage
+            for s in 0..NTT_STAGE_NUM {
+                for b in 0..BUTTERFLY_NUM {
+                    let row = b * STAGE_SLOTS + s;
+                    let (_, j, t, w) = butterfly(row, false).unwrap();
+                    let (x, y) = (a[j], a[j + t]);
+                    let product = y as u128 * twiddles[w] as u128;
+                    let sum = x as u128 + product;
+                    let difference = x as u128 + offset - product;
+                    a[j] = (sum % q as u128) as u64;
+                    a[j + t] = (difference % q as u128) as u64;
+
+                    columns[input_a_column(v, l)][row] = element_from_u64(x);
+                    columns[input_b_column(v, l)][row] = element_from_u64(y);
+                    columns[output_x_column(v, l)][row] = element_from_u64(a[j]);
+                    columns[output_y_column(v, l)][row] = element_from_u64(a[j + t]);
+                    values[row][v * COEFF_LEVEL + l] = sum;
+                    values[row][POLY_NUM + v * COEFF_LEVEL + l] = difference;
+                }
+            }
+        }
+    }
+    let main = reductions.build_columns(columns, STATE_WIDTH, |k, j| values[k][j]);
+    Ok(NttTrace::new(
+        Matrix::new(main),
+        data.modulus,
+        wiring_columns(),
+        false,
+        NTT_GROUP,
+    ))
+}
+
+pub fn get_ntt_pub_inputs(trace: &NttTrace) -> PublicInputs {
+    let last = NTT_STAGE_NUM - 1;
+    let mut result = vec![vec![Vec::new(); COEFF_LEVEL]; VALUE_NUM];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let values = &mut result[v][l];
+            for b in 0..BUTTERFLY_NUM {
+                let row = (b * STAGE_SLOTS + last) * trace.group;
+                values.push(trace.main.get(output_x_column(v, l), row));
+                values.push(trace.main.get(output_y_column(v, l), row));
+            }
+        }
+    }
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus.clone()),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// Permutation factor of the value `value` at `position`: gamma - value - alpha * position.
+fn wire<E: FieldElement>(value: E, position: E, alpha: E, gamma: E) -> E {
+    gamma - value - alpha * position
+}
+
+// Factors of the inputs and the outputs of a row in the wiring columns A, B, X, Y, 1 where a
+// selector excludes them.
+fn row_factors<F, E>(
+    row: &[F],
+    periodic_values: &[F],
+    [a, b, x, y]: [usize; 4],
+    alpha: E,
+    gamma: E,
+) -> (E, E)
+where
+    F: FieldElement<BaseField = BaseElement>,
+    E: FieldElement<BaseField = BaseElement> + ExtensionOf<F>,
+{
+    let position = E::from(periodic_values[POSITION]);
+    let stride = E::from(periodic_values[STRIDE]);
+    let next_stage = E::from(BaseElement::from(COEFF_DEGREE as u64));
+    let a = wire(E::from(row[a]), position, alpha, gamma);
+    let b = wire(E::from(row[b]), position + stride, alpha, gamma);
+    let x = wire(E::from(row[x]), position + next_stage, alpha, gamma);
+    let y = wire(
+        E::from(row[y]),
+        position + next_stage + stride,
+        alpha,
+        gamma,
+    );
+    let input = E::from(periodic_values[INPUT_SELECTOR]);
+    let output = E::from(periodic_values[OUTPUT_SELECTOR]);
+    (
+        input * (a * b - E::ONE) + E::ONE,
+        output * (x * y - E::ONE) + E::ONE,
+    )
+}
+
+impl Trace for NttTrace {
+    type BaseField = BaseElement;
+
+    fn layout(&self) -> &TraceLayout {
+        &self.layout
+    }
+
+    fn length(&self) -> usize {
+        self.main.num_rows()
+    }
+
+    fn meta(&self) -> &[u8] {
+        &[]
+    }
+
+    fn main_segment(&self) -> &Matrix<BaseElement> {
+        &self.main
+    }
+
+    // The running products z_(r+1) = z_r * outputs_r / inputs_r, starting from 1.
+    fn build_aux_segment<E: FieldElement<BaseField = BaseElement>>(
+        &mut self,
+        aux_segments: &[Matrix<E>],
+        rand_elements: &[E],
+    ) -> Option<Matrix<E>> {
+        if !aux_segments.is_empty() {
+            return None;
+        }
+        let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+        let periodic = wiring_periodic_columns(self.inverse, self.group);
+        let mut row = vec![BaseElement::ZERO; self.main.num_cols()];
+        let mut periodic_values = vec![BaseElement::ZERO; periodic.len()];
+        let mut columns = vec![vec![E::ONE; self.length()]; self.wiring.len()];
+        for r in 0..self.length() - 1 {
+            self.main.read_row_into(r, &mut row);
+            for (value, column) in periodic_values.iter_mut().zip(periodic.iter()) {
+                *value = column[r % column.len()];
+            }
+            for (p, &wiring) in self.wiring.iter().enumerate() {
+                let (inputs, outputs) = row_factors(&row, &periodic_values, wiring, alpha, gamma);
+                columns[p][r + 1] = columns[p][r] * outputs * inputs.inv();
+            }
+        }
+        Some(Matrix::new(columns))
+    }
+
+    fn read_main_frame(&self, row_idx: usize, frame: &mut EvaluationFrame<BaseElement>) {
+        let next_row_idx = (row_idx + 1) % self.length();
+        self.main.read_row_into(row_idx, frame.current_mut());
+        self.main.read_row_into(next_row_idx, frame.next_mut());
+    }
+}
+
+// Stage selectors, stride and position of the forward or the inverse network whose circuit rows
+// are repeated over groups of `group` rows; all of them are zero but on the first row of a group,
+// so every circuit row enters the wiring once.
+pub fn wiring_periodic_columns(inverse: bool, group: usize) -> Vec<Vec<BaseElement>> {
+    let mut input = vec![BaseElement::ZERO; STAGE_SLOTS * group];
+    let mut output = vec![BaseElement::ZERO; STAGE_SLOTS * group];
+    let mut strides = vec![BaseElement::ZERO; STAGE_SLOTS * group];
+    for s in 0..NTT_STAGE_NUM {
+        input[s * group] = BaseElement::from((s > 0) as u64);
+        output[s * group] = BaseElement::from((s + 1 < NTT_STAGE_NUM) as u64);
+        strides[s * group] = BaseElement::from(stride(s, inverse) as u64);
+    }
+    let mut position = vec![BaseElement::ZERO; STATE_LENGTH * group];
+    for row in 0..STATE_LENGTH {
+        if let Some((s, j, _, _)) = butterfly(row, inverse) {
+            position[row * group] = BaseElement::from((s * COEFF_DEGREE + j) as u64);
+        }
+    }
+    vec![input, output, strides, position]
+}
+
+// The wiring columns, those of the reductions, then one twiddle column per level.
+fn periodic_columns(modulus: &[u64], reductions: &Reductions) -> Vec<Vec<BaseElement>> {
+    let mut twiddle_columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; COEFF_LEVEL];
+    let tables: Vec<Vec<u64>> = modulus.iter().map(|&q| twiddles(q)).collect();
+    for row in 0..STATE_LENGTH {
+        if let Some((_, _, _, w)) = butterfly(row, false) {
+            for l in 0..COEFF_LEVEL {
+                twiddle_columns[l][row] = element_from_u64(tables[l][w]);
+            }
+        }
+    }
+    let mut columns = wiring_periodic_columns(false, reductions.group());
+    columns.extend(reductions.periodic_columns());
+    columns.extend(
+        twiddle_columns
+            .iter()
+            .map(|column| reductions.stretch(column)),
+    );
+    columns
+}
+
+// Degrees of the wiring constraints: the next running product times two input factors under
+// a selector of period STAGE_SLOTS circuit rows of `group` rows.
+pub fn wiring_degrees(poly_num: usize, group: usize) -> Vec<TransitionConstraintDegree> {
+    vec![TransitionConstraintDegree::with_cycles(3, vec![STAGE_SLOTS * group]); poly_num]
+}
+
+// Wiring constraints z_next * inputs - z * outputs of every polynomial.
+pub fn evaluate_wiring<F, E>(
+    wiring: &[[usize; 4]],
+    main_frame: &EvaluationFrame<F>,
+    aux_frame: &EvaluationFrame<E>,
+    periodic_values: &[F],
+    aux_rand_elements: &AuxTraceRandElements<E>,
+    result: &mut [E],
+) where
+    F: FieldElement<BaseField = BaseElement>,
+    E: FieldElement<BaseField = BaseElement> + ExtensionOf<F>,
+{
+    let rand_elements = aux_rand_elements.get_segment_elements(0);
+    let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+    for (p, &columns) in wiring.iter().enumerate() {
+        let (inputs, outputs) =
+            row_factors(main_frame.current(), periodic_values, columns, alpha, gamma);
+        result[p] = aux_frame.next()[p] * inputs - aux_frame.current()[p] * outputs;
+    }
+}
+
+// Every running product starts and ends at 1.
+pub fn wiring_assertions<E: FieldElement<BaseField = BaseElement>>(
+    poly_num: usize,
+    trace_length: usize,
+) -> Vec<Assertion<E>> {
+    let mut assertions = Vec::with_capacity(2 * poly_num);
+    for p in 0..poly_num {
+        assertions.push(Assertion::single(p, 0, E::ONE));
+        assertions.push(Assertion::single(p, trace_length - 1, E::ONE));
+    }
+    assertions
+}
+
+// The values a butterfly reduces, in the order of ntt_reductions.
+fn butterfly_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    twiddles: &[E],
+    modulus: &[u64],
+) -> [E; REDUCTION_NUM] {
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for l in 0..COEFF_LEVEL {
+        let q = modulus[l] as u128;
+        let offset = E::from(element_from_u128(q * (q - 1)));
+        for v in 0..VALUE_NUM {
+            let a = row[input_a_column(v, l)];
+            let product = twiddles[l] * row[input_b_column(v, l)];
+            values[v * COEFF_LEVEL + l] = a + product;
+            values[POLY_NUM + v * COEFF_LEVEL + l] = a + offset - product;
+        }
+    }
+    values
+}
+
+pub struct NttAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    modulus: Vec<u64>,
+    wiring: Vec<[usize; 4]>,
+}
+
+impl Air for NttAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // Only the current row is checked: the last row, exempt from transition constraints, is
+    // padding. The values are linear in the trace under the twiddles, which run over the whole
+    // trace; the wiring constraint multiplies the next running product with two input factors
+    // under a selector of period STAGE_SLOTS circuit rows.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("NTT proofs publish their moduli");
+        let reductions = ntt_reductions(&modulus).expect("NTT moduli were checked");
+        let degrees = reductions.degrees(1, &[trace_info.length()]);
+        let aux_degrees = wiring_degrees(AUX_WIDTH, reductions.group());
+        let num_assertions = 2 * POLY_NUM;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+        let context = AirContext::new_multi_segment(
+            trace_info,
+            degrees,
+            aux_degrees,
+            num_assertions,
+            num_aux_assertions,
+            options,
+        );
+
+        NttAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            result: pub_inputs.result,
+            modulus,
+            wiring: wiring_columns(),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (reduction_values, twiddles) =
+            periodic_values[WIRING_PERIODIC_NUM..].split_at(self.reductions.num_periodic_columns());
+        let values = butterfly_values(frame.current(), twiddles, &self.modulus);
+        self.reductions
+            .evaluate(frame, reduction_values, &values, result);
+    }
+
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        evaluate_wiring(
+            &self.wiring,
+            main_frame,
+            aux_frame,
+            periodic_values,
+            aux_rand_elements,
+            result,
+        );
+    }
+
+    // The last stage is bound on the first row of its groups.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let group = self.reductions.group();
+        let last = NTT_STAGE_NUM - 1;
+        let mut assertions = Vec::with_capacity(2 * POLY_NUM);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let values = &self.result[v][l];
+                let even = values.iter().step_by(2).copied().collect();
+                let odd = values.iter().skip(1).step_by(2).copied().collect();
+                assertions.push(Assertion::sequence(
+                    output_x_column(v, l),
+                    last * group,
+                    STAGE_SLOTS * group,
+                    even,
+                ));
+                assertions.push(Assertion::sequence(
+                    output_y_column(v, l),
+                    last * group,
+                    STAGE_SLOTS * group,
+                    odd,
+                ));
+            }
+        }
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        periodic_columns(&self.modulus, &self.reductions)
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Operands of `add` from several data files, `prover --operand PATH`, for pipelines in which
+// different services produce the ciphertexts. Every file is a data file of its own, TOML or
+// packed, with whole operands of the same moduli and degree and their own `Levels`; the operands
+// follow each other in the order of the arguments. An argument may be a glob with `*` and `?` in its file
+// name, whose matches come in the order of their names.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::air::CustomData;
+use crate::error::StarkHeError;
+use crate::packed::read_custom_data;
+
+// The files of `patterns`, globs expanded.
+pub fn expand_operand_files(patterns: &[String]) -> Result<Vec<PathBuf>, StarkHeError> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if !name.contains(['*', '?']) {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let list_error = |err| StarkHeError::io(&dir.display().to_string(), err);
+        let entries = fs::read_dir(dir).map_err(list_error)?;
+        let mut matches = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(list_error)?;
+            let matched = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file| glob_match(name.as_bytes(), file.as_bytes()));
+            if matched && entry.path().is_file() {
+                matches.push(entry.path());
+            }
+        }
+        if matches.is_empty() {
+            return Err(StarkHeError::InvalidInput(format!(
+                "no operand files match {}",
+                pattern
+            )));
+        }
+        matches.sort();
+        files.extend(matches);
+    }
+    Ok(files)
+}
+
+// Whether `name` matches `pattern`, in which `*` stands for any bytes and `?` for one.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some((&p, rest)) => name
+            .split_first()
+            .is_some_and(|(&c, name)| (p == b'?' || p == c) && glob_match(rest, name)),
+    }
+}
+
+// The operands of the files of `patterns` as a single CustomData; the active levels of files
+// without `Levels` are every modulus.
+pub fn read_operand_files(patterns: &[String]) -> Result<CustomData, StarkHeError> {
+    let mut files = Vec::new();
+    for file in expand_operand_files(patterns)? {
+        let data = read_custom_data(&file.to_string_lossy())?;
+        files.push((file, data));
+    }
+    let (first, data) = files
+        .first()
+        .ok_or_else(|| StarkHeError::InvalidInput(String::from("no operand files")))?;
+    for (file, other) in &files[1..] {
+        let field = if other.modulus != data.modulus {
+            "Modulus"
+        } else if other.coeff_degree != data.coeff_degree {
+            "CoeffDegree"
+        } else {
+            continue;
+        };
+        return Err(StarkHeError::InvalidInput(format!(
+            "operand file {} differs from {} in {}",
+            file.display(),
+            first.display(),
+            field
+        )));
+    }
+    let levels = files
+        .iter()
+        .any(|(_, data)| data.levels.is_some())
+        .then(|| {
+            files
+                .iter()
+                .flat_map(|(_, data)| {
+                    data.levels
+                        .clone()
+                        .unwrap_or_else(|| vec![data.modulus.len(); data.values.len()])
+                })
+                .collect()
+        });
+    Ok(CustomData {
+        modulus: data.modulus.clone(),
+        coeff_degree: data.coeff_degree,
+        levels,
+        values: files
+            .into_iter()
+            .flat_map(|(_, data)| data.values)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_file_names() {
+        assert!(glob_match(b"ct-*.toml", b"ct-0.toml"));
+        assert!(glob_match(b"ct-*.toml", b"ct-.toml"));
+        assert!(glob_match(b"ct-?.toml", b"ct-1.toml"));
+        assert!(!glob_match(b"ct-?.toml", b"ct-10.toml"));
+        assert!(!glob_match(b"ct-*.toml", b"ct-0.shep"));
+        assert!(glob_match(b"*", b"data.toml"));
+    }
+
+    #[test]
+    fn operand_files_are_concatenated() {
+        let dir = std::env::temp_dir().join(format!("stark-he-operands-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let operand = |x: u64| CustomData {
+            modulus: vec![65537],
+            coeff_degree: Some(2),
+            levels: None,
+            values: vec![[vec![vec![x, x]], vec![vec![x + 1, x + 1]]]],
+        };
+        for (i, x) in [(0, 1), (1, 2), (2, 3)] {
+            let path = dir.join(format!("ct-{}.toml", i));
+            fs::write(path, toml::to_string(&operand(x)).unwrap()).unwrap();
+        }
+        let pattern = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        // explicit paths keep their order, globs sort their matches
+        assert!(read_operand_files(&[pattern("ct-9*")]).is_err());
+        let data = read_operand_files(&[pattern("ct-2.toml"), pattern("ct-?.toml")]).unwrap();
+        let firsts: Vec<u64> = data.values.iter().map(|d| d[0][0][0]).collect();
+        assert_eq!(firsts, [3, 1, 2, 3]);
+        assert_eq!(data.modulus, [65537]);
+        assert_eq!(data.levels, None);
+
+        let mut other = operand(4);
+        other.modulus = vec![97];
+        fs::write(dir.join("other.toml"), toml::to_string(&other).unwrap()).unwrap();
+        let err = read_operand_files(&[pattern("ct-0.toml"), pattern("other.toml")]).unwrap_err();
+        assert!(err.to_string().contains("Modulus"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
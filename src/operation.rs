@@ -0,0 +1,899 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// The homomorphic operations and their circuits. An operation is its AIR, its prover, which
+// derives the public inputs from the trace, and its trace builder, the three behind HeOperation;
+// proving, verification and the tools reach them through `visit_operation`, the registry of the
+// operations. A new operation is a module with its AIR and its trace builder, a prover, a
+// variant of Op with its name and layout, an implementation of HeOperation and an arm of
+// `visit_operation`. All operations share the public inputs of proof files, PublicInputs, each
+// using its own fields of them.
+
+use winter_air::{Air, ProofOptions};
+use winter_prover::Prover;
+
+use crate::aggregate_air::{build_aggregate_trace, load_mean, AggregateAir};
+use crate::air::{
+    build_trace, load_scheme_config, BaseElement, FreshAir, InputArg, Op, PublicInputs,
+};
+use crate::base_ext_air::{build_base_ext_trace, load_base_ext_modulus, BaseExtAir};
+use crate::blind_rotate_air::{build_blind_rotate_trace, BlindRotateAir};
+use crate::ckks_encode_air::{build_ckks_encode_trace, load_slots, CkksEncodeAir};
+use crate::compare_air::{build_compare_trace, CompareAir};
+use crate::conjugate_air::{build_conjugate_trace, ConjugateAir};
+use crate::decrypt_air::{build_decrypt_trace, load_plain_modulus, DecryptAir};
+use crate::decrypt_share_air::{build_decrypt_share_trace, DecryptShareAir};
+use crate::encrypt_air::{build_encrypt_trace, EncryptAir};
+use crate::error::StarkHeError;
+use crate::fma_air::{build_fma_trace, FmaAir};
+use crate::hoisted_rotate_air::{build_hoisted_rotate_trace, HoistedRotateAir};
+use crate::horner_air::{build_horner_trace, load_coefficients, HornerAir};
+use crate::inner_product_air::{build_inner_product_trace, load_weights, InnerProductAir};
+use crate::intt_air::{build_intt_trace, InttAir};
+use crate::key_conjugate_air::{build_key_conjugate_trace, KeyConjugateAir};
+use crate::key_rotate_air::{build_key_rotate_trace, KeyRotateAir};
+use crate::keyswitch_air::{build_keyswitch_trace, KeySwitchAir};
+#[cfg(feature = "goldilocks")]
+use crate::limb_mul_air::{build_limb_mul_trace, LimbMulAir};
+use crate::mat_vec_air::{build_mat_vec_trace, MatVecAir};
+use crate::modraise_air::{build_modraise_trace, ModRaiseAir};
+use crate::modswitch_air::{build_modswitch_trace, ModSwitchAir};
+use crate::mont_mul_air::{build_mont_mul_trace, MontMulAir};
+use crate::mul_air::{build_mul_trace, MulAir};
+use crate::multi_key_add_air::{build_multi_key_add_trace, MultiKeyAddAir};
+use crate::neg_air::{build_neg_trace, NegAir};
+use crate::ntt_air::{build_ntt_trace, NttAir};
+use crate::pack_air::{build_pack_trace, PackAir};
+use crate::pipeline_air::{build_pipeline_trace, load_program, PipelineAir};
+use crate::plain_mul_air::{build_plain_mul_trace, PlainMulAir};
+use crate::proving::{
+    AggregateProver, BaseExtProver, BlindRotateProver, CkksEncodeProver, CompareProver,
+    ConjugateProver, DecryptProver, DecryptShareProver, EncryptProver, FmaProver, FreshProver,
+    HoistedRotateProver, HornerProver, InnerProductProver, InttProver, KeyConjugateProver,
+    KeyRotateProver, KeySwitchProver, MatVecProver, ModRaiseProver, ModSwitchProver, MontMulProver,
+    MulProver, MultiKeyAddProver, NegProver, NttProver, PackProver, PipelineProver, PlainMulProver,
+    ReEncryptProver, RelinProver, RescaleProver, RotateProver, ScalarMulProver, SubProver,
+    SumProver, UnpackProver, WideMulProver,
+};
+use crate::re_encrypt_air::{build_re_encrypt_trace, ReEncryptAir};
+use crate::relin_air::{build_relin_trace, RelinAir};
+use crate::rescale_air::{build_rescale_trace, load_scale, RescaleAir};
+use crate::rotate_air::{build_rotate_trace, RotateAir};
+use crate::scalar_mul_air::{build_scalar_mul_trace, load_scalar, ScalarMulAir};
+use crate::sub_air::{build_sub_trace, SubAir};
+use crate::sum_air::{build_sum_trace, SumAir};
+use crate::unpack_air::{build_unpack_trace, UnpackAir};
+#[cfg(not(feature = "goldilocks"))]
+use crate::wide_mul_air::{build_wide_mul_trace, WideMulAir};
+
+pub trait HeOperation {
+    const OP: Op;
+    type Air: Air<BaseField = BaseElement, PublicInputs = PublicInputs>;
+    type Prover: Prover<BaseField = BaseElement, Air = Self::Air>;
+
+    // The prover of `input_args`, with the parameters of the data file which are not operands.
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<Self::Prover, StarkHeError>;
+
+    // The trace of the operands of `input_args`.
+    fn build_trace(input_args: &InputArg) -> Result<<Self::Prover as Prover>::Trace, StarkHeError>;
+}
+
+// Something done with an operation, see `visit_operation`.
+pub trait OperationVisitor {
+    type Output;
+
+    fn visit<O: HeOperation>(self) -> Self::Output;
+}
+
+// Hands the operation of `op` to `visitor`.
+pub fn visit_operation<V: OperationVisitor>(op: Op, visitor: V) -> V::Output {
+    match op {
+        Op::Add => visitor.visit::<AddOperation>(),
+        Op::Sub => visitor.visit::<SubOperation>(),
+        Op::Mul => visitor.visit::<MulOperation>(),
+        Op::Relin => visitor.visit::<RelinOperation>(),
+        Op::Rotate => visitor.visit::<RotateOperation>(),
+        Op::KeySwitch => visitor.visit::<KeySwitchOperation>(),
+        Op::ModSwitch => visitor.visit::<ModSwitchOperation>(),
+        Op::Rescale => visitor.visit::<RescaleOperation>(),
+        Op::PlainMul => visitor.visit::<PlainMulOperation>(),
+        Op::Neg => visitor.visit::<NegOperation>(),
+        Op::Ntt => visitor.visit::<NttOperation>(),
+        Op::Intt => visitor.visit::<InttOperation>(),
+        Op::Decrypt => visitor.visit::<DecryptOperation>(),
+        Op::Encrypt => visitor.visit::<EncryptOperation>(),
+        Op::CkksEncode => visitor.visit::<CkksEncodeOperation>(),
+        Op::ModRaise => visitor.visit::<ModRaiseOperation>(),
+        Op::ScalarMul => visitor.visit::<ScalarMulOperation>(),
+        Op::Fma => visitor.visit::<FmaOperation>(),
+        Op::Sum => visitor.visit::<SumOperation>(),
+        Op::InnerProduct => visitor.visit::<InnerProductOperation>(),
+        Op::MatVec => visitor.visit::<MatVecOperation>(),
+        Op::BaseExt => visitor.visit::<BaseExtOperation>(),
+        Op::MontMul => visitor.visit::<MontMulOperation>(),
+        Op::WideMul => visitor.visit::<WideMulOperation>(),
+        Op::BlindRotate => visitor.visit::<BlindRotateOperation>(),
+        Op::Horner => visitor.visit::<HornerOperation>(),
+        Op::Aggregate => visitor.visit::<AggregateOperation>(),
+        Op::DecryptShare => visitor.visit::<DecryptShareOperation>(),
+        Op::Conjugate => visitor.visit::<ConjugateOperation>(),
+        Op::Pack => visitor.visit::<PackOperation>(),
+        Op::Unpack => visitor.visit::<UnpackOperation>(),
+        Op::MultiKeyAdd => visitor.visit::<MultiKeyAddOperation>(),
+        Op::ReEncrypt => visitor.visit::<ReEncryptOperation>(),
+        Op::HoistedRotate => visitor.visit::<HoistedRotateOperation>(),
+        Op::Compare => visitor.visit::<CompareOperation>(),
+        Op::KeyRotate => visitor.visit::<KeyRotateOperation>(),
+        Op::KeyConjugate => visitor.visit::<KeyConjugateOperation>(),
+        Op::Pipeline => visitor.visit::<PipelineOperation>(),
+    }
+}
+
+pub struct AddOperation;
+
+impl HeOperation for AddOperation {
+    const OP: Op = Op::Add;
+    type Air = FreshAir;
+    type Prover = FreshProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<FreshProver, StarkHeError> {
+        Ok(FreshProver {
+            options,
+            config: load_scheme_config(input_args)?,
+        })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<FreshProver as Prover>::Trace, StarkHeError> {
+        build_trace(input_args)
+    }
+}
+
+pub struct SubOperation;
+
+impl HeOperation for SubOperation {
+    const OP: Op = Op::Sub;
+    type Air = SubAir;
+    type Prover = SubProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<SubProver, StarkHeError> {
+        Ok(SubProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<SubProver as Prover>::Trace, StarkHeError> {
+        build_sub_trace(input_args)
+    }
+}
+
+pub struct MulOperation;
+
+impl HeOperation for MulOperation {
+    const OP: Op = Op::Mul;
+    type Air = MulAir;
+    type Prover = MulProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<MulProver, StarkHeError> {
+        Ok(MulProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<MulProver as Prover>::Trace, StarkHeError> {
+        build_mul_trace(input_args)
+    }
+}
+
+pub struct RelinOperation;
+
+impl HeOperation for RelinOperation {
+    const OP: Op = Op::Relin;
+    type Air = RelinAir;
+    type Prover = RelinProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<RelinProver, StarkHeError> {
+        Ok(RelinProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<RelinProver as Prover>::Trace, StarkHeError> {
+        build_relin_trace(input_args)
+    }
+}
+
+pub struct RotateOperation;
+
+impl HeOperation for RotateOperation {
+    const OP: Op = Op::Rotate;
+    type Air = RotateAir;
+    type Prover = RotateProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<RotateProver, StarkHeError> {
+        Ok(RotateProver {
+            options,
+            rotation: input_args.rotation.ok_or_else(|| {
+                StarkHeError::InvalidInput(String::from("rotation needs --rotation"))
+            })?,
+        })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<RotateProver as Prover>::Trace, StarkHeError> {
+        build_rotate_trace(input_args)
+    }
+}
+
+pub struct KeySwitchOperation;
+
+impl HeOperation for KeySwitchOperation {
+    const OP: Op = Op::KeySwitch;
+    type Air = KeySwitchAir;
+    type Prover = KeySwitchProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<KeySwitchProver, StarkHeError> {
+        Ok(KeySwitchProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<KeySwitchProver as Prover>::Trace, StarkHeError> {
+        build_keyswitch_trace(input_args)
+    }
+}
+
+pub struct ModSwitchOperation;
+
+impl HeOperation for ModSwitchOperation {
+    const OP: Op = Op::ModSwitch;
+    type Air = ModSwitchAir;
+    type Prover = ModSwitchProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<ModSwitchProver, StarkHeError> {
+        Ok(ModSwitchProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<ModSwitchProver as Prover>::Trace, StarkHeError> {
+        build_modswitch_trace(input_args)
+    }
+}
+
+pub struct RescaleOperation;
+
+impl HeOperation for RescaleOperation {
+    const OP: Op = Op::Rescale;
+    type Air = RescaleAir;
+    type Prover = RescaleProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<RescaleProver, StarkHeError> {
+        Ok(RescaleProver {
+            options,
+            scale: load_scale(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<RescaleProver as Prover>::Trace, StarkHeError> {
+        build_rescale_trace(input_args)
+    }
+}
+
+pub struct PlainMulOperation;
+
+impl HeOperation for PlainMulOperation {
+    const OP: Op = Op::PlainMul;
+    type Air = PlainMulAir;
+    type Prover = PlainMulProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<PlainMulProver, StarkHeError> {
+        Ok(PlainMulProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<PlainMulProver as Prover>::Trace, StarkHeError> {
+        build_plain_mul_trace(input_args)
+    }
+}
+
+pub struct NegOperation;
+
+impl HeOperation for NegOperation {
+    const OP: Op = Op::Neg;
+    type Air = NegAir;
+    type Prover = NegProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<NegProver, StarkHeError> {
+        Ok(NegProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<NegProver as Prover>::Trace, StarkHeError> {
+        build_neg_trace(input_args)
+    }
+}
+
+pub struct NttOperation;
+
+impl HeOperation for NttOperation {
+    const OP: Op = Op::Ntt;
+    type Air = NttAir;
+    type Prover = NttProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<NttProver, StarkHeError> {
+        Ok(NttProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<NttProver as Prover>::Trace, StarkHeError> {
+        build_ntt_trace(input_args)
+    }
+}
+
+pub struct InttOperation;
+
+impl HeOperation for InttOperation {
+    const OP: Op = Op::Intt;
+    type Air = InttAir;
+    type Prover = InttProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<InttProver, StarkHeError> {
+        Ok(InttProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<InttProver as Prover>::Trace, StarkHeError> {
+        build_intt_trace(input_args)
+    }
+}
+
+pub struct DecryptOperation;
+
+impl HeOperation for DecryptOperation {
+    const OP: Op = Op::Decrypt;
+    type Air = DecryptAir;
+    type Prover = DecryptProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<DecryptProver, StarkHeError> {
+        Ok(DecryptProver {
+            options,
+            plain_modulus: load_plain_modulus(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<DecryptProver as Prover>::Trace, StarkHeError> {
+        build_decrypt_trace(input_args)
+    }
+}
+
+pub struct EncryptOperation;
+
+impl HeOperation for EncryptOperation {
+    const OP: Op = Op::Encrypt;
+    type Air = EncryptAir;
+    type Prover = EncryptProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<EncryptProver, StarkHeError> {
+        Ok(EncryptProver {
+            options,
+            plain_modulus: load_plain_modulus(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<EncryptProver as Prover>::Trace, StarkHeError> {
+        build_encrypt_trace(input_args)
+    }
+}
+
+pub struct CkksEncodeOperation;
+
+impl HeOperation for CkksEncodeOperation {
+    const OP: Op = Op::CkksEncode;
+    type Air = CkksEncodeAir;
+    type Prover = CkksEncodeProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<CkksEncodeProver, StarkHeError> {
+        Ok(CkksEncodeProver {
+            options,
+            slots: load_slots(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<CkksEncodeProver as Prover>::Trace, StarkHeError> {
+        build_ckks_encode_trace(input_args)
+    }
+}
+
+pub struct ModRaiseOperation;
+
+impl HeOperation for ModRaiseOperation {
+    const OP: Op = Op::ModRaise;
+    type Air = ModRaiseAir;
+    type Prover = ModRaiseProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<ModRaiseProver, StarkHeError> {
+        Ok(ModRaiseProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<ModRaiseProver as Prover>::Trace, StarkHeError> {
+        build_modraise_trace(input_args)
+    }
+}
+
+pub struct ScalarMulOperation;
+
+impl HeOperation for ScalarMulOperation {
+    const OP: Op = Op::ScalarMul;
+    type Air = ScalarMulAir;
+    type Prover = ScalarMulProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<ScalarMulProver, StarkHeError> {
+        Ok(ScalarMulProver {
+            options,
+            scalar: load_scalar(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<ScalarMulProver as Prover>::Trace, StarkHeError> {
+        build_scalar_mul_trace(input_args)
+    }
+}
+
+pub struct FmaOperation;
+
+impl HeOperation for FmaOperation {
+    const OP: Op = Op::Fma;
+    type Air = FmaAir;
+    type Prover = FmaProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<FmaProver, StarkHeError> {
+        Ok(FmaProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<FmaProver as Prover>::Trace, StarkHeError> {
+        build_fma_trace(input_args)
+    }
+}
+
+pub struct SumOperation;
+
+impl HeOperation for SumOperation {
+    const OP: Op = Op::Sum;
+    type Air = SumAir;
+    type Prover = SumProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<SumProver, StarkHeError> {
+        Ok(SumProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<SumProver as Prover>::Trace, StarkHeError> {
+        build_sum_trace(input_args)
+    }
+}
+
+pub struct InnerProductOperation;
+
+impl HeOperation for InnerProductOperation {
+    const OP: Op = Op::InnerProduct;
+    type Air = InnerProductAir;
+    type Prover = InnerProductProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<InnerProductProver, StarkHeError> {
+        Ok(InnerProductProver {
+            options,
+            weights: load_weights(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<InnerProductProver as Prover>::Trace, StarkHeError> {
+        build_inner_product_trace(input_args)
+    }
+}
+
+pub struct MatVecOperation;
+
+impl HeOperation for MatVecOperation {
+    const OP: Op = Op::MatVec;
+    type Air = MatVecAir;
+    type Prover = MatVecProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<MatVecProver, StarkHeError> {
+        Ok(MatVecProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<MatVecProver as Prover>::Trace, StarkHeError> {
+        build_mat_vec_trace(input_args)
+    }
+}
+
+pub struct BaseExtOperation;
+
+impl HeOperation for BaseExtOperation {
+    const OP: Op = Op::BaseExt;
+    type Air = BaseExtAir;
+    type Prover = BaseExtProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<BaseExtProver, StarkHeError> {
+        Ok(BaseExtProver {
+            options,
+            modulus: load_base_ext_modulus(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<BaseExtProver as Prover>::Trace, StarkHeError> {
+        build_base_ext_trace(input_args)
+    }
+}
+
+pub struct MontMulOperation;
+
+impl HeOperation for MontMulOperation {
+    const OP: Op = Op::MontMul;
+    type Air = MontMulAir;
+    type Prover = MontMulProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<MontMulProver, StarkHeError> {
+        Ok(MontMulProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<MontMulProver as Prover>::Trace, StarkHeError> {
+        build_mont_mul_trace(input_args)
+    }
+}
+
+pub struct WideMulOperation;
+
+impl HeOperation for WideMulOperation {
+    const OP: Op = Op::WideMul;
+    #[cfg(not(feature = "goldilocks"))]
+    type Air = WideMulAir;
+    #[cfg(feature = "goldilocks")]
+    type Air = LimbMulAir;
+    type Prover = WideMulProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<WideMulProver, StarkHeError> {
+        Ok(WideMulProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<WideMulProver as Prover>::Trace, StarkHeError> {
+        #[cfg(not(feature = "goldilocks"))]
+        return build_wide_mul_trace(input_args);
+        #[cfg(feature = "goldilocks")]
+        return build_limb_mul_trace(input_args);
+    }
+}
+
+pub struct BlindRotateOperation;
+
+impl HeOperation for BlindRotateOperation {
+    const OP: Op = Op::BlindRotate;
+    type Air = BlindRotateAir;
+    type Prover = BlindRotateProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<BlindRotateProver, StarkHeError> {
+        Ok(BlindRotateProver {
+            options,
+            rotation: input_args.rotation.ok_or_else(|| {
+                StarkHeError::InvalidInput(String::from("blind rotation needs --rotation"))
+            })?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<BlindRotateProver as Prover>::Trace, StarkHeError> {
+        build_blind_rotate_trace(input_args)
+    }
+}
+
+pub struct HornerOperation;
+
+impl HeOperation for HornerOperation {
+    const OP: Op = Op::Horner;
+    type Air = HornerAir;
+    type Prover = HornerProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<HornerProver, StarkHeError> {
+        Ok(HornerProver {
+            options,
+            coefficients: load_coefficients(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<HornerProver as Prover>::Trace, StarkHeError> {
+        build_horner_trace(input_args)
+    }
+}
+
+pub struct AggregateOperation;
+
+impl HeOperation for AggregateOperation {
+    const OP: Op = Op::Aggregate;
+    type Air = AggregateAir;
+    type Prover = AggregateProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<AggregateProver, StarkHeError> {
+        Ok(AggregateProver {
+            options,
+            mean: load_mean(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<AggregateProver as Prover>::Trace, StarkHeError> {
+        build_aggregate_trace(input_args)
+    }
+}
+
+pub struct DecryptShareOperation;
+
+impl HeOperation for DecryptShareOperation {
+    const OP: Op = Op::DecryptShare;
+    type Air = DecryptShareAir;
+    type Prover = DecryptShareProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<DecryptShareProver, StarkHeError> {
+        Ok(DecryptShareProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<DecryptShareProver as Prover>::Trace, StarkHeError> {
+        build_decrypt_share_trace(input_args)
+    }
+}
+
+pub struct ConjugateOperation;
+
+impl HeOperation for ConjugateOperation {
+    const OP: Op = Op::Conjugate;
+    type Air = ConjugateAir;
+    type Prover = ConjugateProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<ConjugateProver, StarkHeError> {
+        Ok(ConjugateProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<ConjugateProver as Prover>::Trace, StarkHeError> {
+        build_conjugate_trace(input_args)
+    }
+}
+
+pub struct PackOperation;
+
+impl HeOperation for PackOperation {
+    const OP: Op = Op::Pack;
+    type Air = PackAir;
+    type Prover = PackProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<PackProver, StarkHeError> {
+        Ok(PackProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<PackProver as Prover>::Trace, StarkHeError> {
+        build_pack_trace(input_args)
+    }
+}
+
+pub struct UnpackOperation;
+
+impl HeOperation for UnpackOperation {
+    const OP: Op = Op::Unpack;
+    type Air = UnpackAir;
+    type Prover = UnpackProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<UnpackProver, StarkHeError> {
+        Ok(UnpackProver { options })
+    }
+
+    fn build_trace(input_args: &InputArg) -> Result<<UnpackProver as Prover>::Trace, StarkHeError> {
+        build_unpack_trace(input_args)
+    }
+}
+
+pub struct MultiKeyAddOperation;
+
+impl HeOperation for MultiKeyAddOperation {
+    const OP: Op = Op::MultiKeyAdd;
+    type Air = MultiKeyAddAir;
+    type Prover = MultiKeyAddProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<MultiKeyAddProver, StarkHeError> {
+        Ok(MultiKeyAddProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<MultiKeyAddProver as Prover>::Trace, StarkHeError> {
+        build_multi_key_add_trace(input_args)
+    }
+}
+
+pub struct ReEncryptOperation;
+
+impl HeOperation for ReEncryptOperation {
+    const OP: Op = Op::ReEncrypt;
+    type Air = ReEncryptAir;
+    type Prover = ReEncryptProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<ReEncryptProver, StarkHeError> {
+        Ok(ReEncryptProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<ReEncryptProver as Prover>::Trace, StarkHeError> {
+        build_re_encrypt_trace(input_args)
+    }
+}
+
+pub struct HoistedRotateOperation;
+
+impl HeOperation for HoistedRotateOperation {
+    const OP: Op = Op::HoistedRotate;
+    type Air = HoistedRotateAir;
+    type Prover = HoistedRotateProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<HoistedRotateProver, StarkHeError> {
+        Ok(HoistedRotateProver {
+            options,
+            rotation: input_args.rotation.ok_or_else(|| {
+                StarkHeError::InvalidInput(String::from("hoisted rotations need --rotation"))
+            })?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<HoistedRotateProver as Prover>::Trace, StarkHeError> {
+        build_hoisted_rotate_trace(input_args)
+    }
+}
+
+pub struct CompareOperation;
+
+impl HeOperation for CompareOperation {
+    const OP: Op = Op::Compare;
+    type Air = CompareAir;
+    type Prover = CompareProver;
+
+    fn prover(input_args: &InputArg, options: ProofOptions) -> Result<CompareProver, StarkHeError> {
+        Ok(CompareProver {
+            options,
+            plain_modulus: load_plain_modulus(&input_args.data_file_path)?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<CompareProver as Prover>::Trace, StarkHeError> {
+        build_compare_trace(input_args)
+    }
+}
+
+pub struct KeyRotateOperation;
+
+impl HeOperation for KeyRotateOperation {
+    const OP: Op = Op::KeyRotate;
+    type Air = KeyRotateAir;
+    type Prover = KeyRotateProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<KeyRotateProver, StarkHeError> {
+        Ok(KeyRotateProver {
+            options,
+            rotation: input_args.rotation.ok_or_else(|| {
+                StarkHeError::InvalidInput(String::from("key-switched rotation needs --rotation"))
+            })?,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<KeyRotateProver as Prover>::Trace, StarkHeError> {
+        build_key_rotate_trace(input_args)
+    }
+}
+
+pub struct KeyConjugateOperation;
+
+impl HeOperation for KeyConjugateOperation {
+    const OP: Op = Op::KeyConjugate;
+    type Air = KeyConjugateAir;
+    type Prover = KeyConjugateProver;
+
+    fn prover(_: &InputArg, options: ProofOptions) -> Result<KeyConjugateProver, StarkHeError> {
+        Ok(KeyConjugateProver { options })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<KeyConjugateProver as Prover>::Trace, StarkHeError> {
+        build_key_conjugate_trace(input_args)
+    }
+}
+
+pub struct PipelineOperation;
+
+impl HeOperation for PipelineOperation {
+    const OP: Op = Op::Pipeline;
+    type Air = PipelineAir;
+    type Prover = PipelineProver;
+
+    fn prover(
+        input_args: &InputArg,
+        options: ProofOptions,
+    ) -> Result<PipelineProver, StarkHeError> {
+        let (steps, scale) = load_program(&input_args.data_file_path)?;
+        Ok(PipelineProver {
+            options,
+            steps,
+            scale,
+        })
+    }
+
+    fn build_trace(
+        input_args: &InputArg,
+    ) -> Result<<PipelineProver as Prover>::Trace, StarkHeError> {
+        build_pipeline_trace(input_args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ArgEnum;
+
+    struct OpOf;
+
+    impl OperationVisitor for OpOf {
+        type Output = Op;
+
+        fn visit<O: HeOperation>(self) -> Op {
+            O::OP
+        }
+    }
+
+    #[test]
+    fn operations_are_registered_under_their_op() {
+        for &op in Op::value_variants() {
+            assert_eq!(visit_operation(op, OpOf), op, "{}", op.name());
+        }
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Operation logs emitted by the SEAL and OpenFHE wrappers of the HE application, turned into a
+// manifest and the data files of its operations:
+//
+//     [[parameters]]
+//     id = "p0"
+//     modulus = [1125899906826241, 1125899906629633]
+//
+//     [[op]]
+//     type = "multiply"      # or EvalMult, add, sub, negate, multiply_plain, relinearize,
+//                            # rotate_rows, switch_key, mod_switch_to_next, rescale_to_next,
+//                            # transform_to_ntt, ...
+//     inputs = ["ct1", "ct2"]
+//     output = "ct3"
+//     parameters = "p0"
+//
+// Relinearizations and key switches name their `evaluation_key` file, rotations their
+// `rotation`. Every ciphertext or plaintext an operation consumes has a snapshot `<id>.toml`
+// holding its components in `Values` (one vector per level, a single component for plaintexts) and optionally the `Parameters` id and the
+// `Form` ("ntt" or "coefficient") it was written in; rescaled ciphertexts record their `Scale`.
+// Additions of two ciphertexts become `a + b - c` with a zero operand.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::air::{CiphertextData, CustomData, Op, COEFF_DEGREE, COEFF_LEVEL, VALUE_NUM};
+use crate::manifest::{build_dag, Manifest, Operation};
+use crate::mul_air::{MulData, MUL_RESULT_NUM};
+use crate::plain_mul_air::PlainMulData;
+use crate::relin_air::RelinData;
+use crate::rescale_air::RescaleData;
+use crate::sub_air::SubData;
+
+#[derive(Debug, Deserialize)]
+pub struct ParameterSet {
+    pub id: String,
+    pub modulus: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogEntry {
+    #[serde(rename = "type")]
+    pub op_type: String,
+    pub id: Option<String>,
+    pub inputs: Vec<String>,
+    pub output: String,
+    pub parameters: String,
+    pub evaluation_key: Option<String>,
+    pub rotation: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OperationLog {
+    #[serde(rename = "parameters", default)]
+    pub parameter_sets: Vec<ParameterSet>,
+    #[serde(rename = "op", default)]
+    pub entries: Vec<LogEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Snapshot {
+    pub parameters: Option<String>,
+    pub form: Option<String>,
+    pub scale: Option<f64>,
+    pub values: Vec<[Vec<u64>; COEFF_LEVEL]>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LogOp {
+    Add,
+    Sub,
+    Negate,
+    Mul,
+    MulPlain,
+    Relin,
+    Rotate,
+    Conjugate,
+    KeySwitch,
+    ModSwitch,
+    Rescale,
+    ToNtt,
+    FromNtt,
+}
+
+// SEAL evaluator and OpenFHE method names, compared in lower case without underscores.
+const OP_NAMES: [(&str, LogOp); 27] = [
+    ("add", LogOp::Add),
+    ("evaladd", LogOp::Add),
+    ("sub", LogOp::Sub),
+    ("evalsub", LogOp::Sub),
+    ("negate", LogOp::Negate),
+    ("evalnegate", LogOp::Negate),
+    ("multiply", LogOp::Mul),
+    ("evalmult", LogOp::Mul),
+    ("multiplyplain", LogOp::MulPlain),
+    ("evalmultplain", LogOp::MulPlain),
+    ("relinearize", LogOp::Relin),
+    ("relin", LogOp::Relin),
+    ("rotaterows", LogOp::Rotate),
+    ("evalrotate", LogOp::Rotate),
+    ("complexconjugate", LogOp::Conjugate),
+    ("complexconjugateinplace", LogOp::Conjugate),
+    ("switchkey", LogOp::KeySwitch),
+    ("keyswitch", LogOp::KeySwitch),
+    ("evalkeyswitch", LogOp::KeySwitch),
+    ("modswitchtonext", LogOp::ModSwitch),
+    ("modswitch", LogOp::ModSwitch),
+    ("rescaletonext", LogOp::Rescale),
+    ("rescale", LogOp::Rescale),
+    ("transformtontt", LogOp::ToNtt),
+    ("transformtonttinplace", LogOp::ToNtt),
+    ("transformfromntt", LogOp::FromNtt),
+    ("transformfromnttinplace", LogOp::FromNtt),
+];
+
+impl LogOp {
+    fn parse(op_type: &str) -> Option<LogOp> {
+        let name = op_type.to_lowercase().replace('_', "");
+        OP_NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, op)| *op)
+    }
+
+    fn circuit(&self) -> Op {
+        match self {
+            LogOp::Add => Op::Add,
+            LogOp::Sub => Op::Sub,
+            LogOp::Negate => Op::Neg,
+            LogOp::Mul => Op::Mul,
+            LogOp::MulPlain => Op::PlainMul,
+            LogOp::Relin => Op::Relin,
+            LogOp::Rotate => Op::Rotate,
+            LogOp::Conjugate => Op::Conjugate,
+            LogOp::KeySwitch => Op::KeySwitch,
+            LogOp::ModSwitch => Op::ModSwitch,
+            LogOp::Rescale => Op::Rescale,
+            LogOp::ToNtt => Op::Ntt,
+            LogOp::FromNtt => Op::Intt,
+        }
+    }
+
+    fn input_num(&self) -> usize {
+        match self {
+            LogOp::Add | LogOp::Sub | LogOp::Mul | LogOp::MulPlain => 2,
+            LogOp::Negate
+            | LogOp::Relin
+            | LogOp::Rotate
+            | LogOp::Conjugate
+            | LogOp::KeySwitch
+            | LogOp::ModSwitch
+            | LogOp::Rescale
+            | LogOp::ToNtt
+            | LogOp::FromNtt => 1,
+        }
+    }
+
+    // Components of the `input`-th input; the plaintext of MulPlain comes second.
+    fn component_num(&self, input: usize) -> usize {
+        match (self, input) {
+            (LogOp::Relin, _) => MUL_RESULT_NUM,
+            (LogOp::MulPlain, 1) => 1,
+            _ => VALUE_NUM,
+        }
+    }
+
+    // Representation the circuit expects its inputs in, if it matters.
+    fn form(&self) -> Option<&'static str> {
+        match self {
+            LogOp::Mul | LogOp::MulPlain | LogOp::FromNtt => Some("ntt"),
+            LogOp::Rotate | LogOp::Conjugate | LogOp::ModSwitch | LogOp::Rescale | LogOp::ToNtt => {
+                Some("coefficient")
+            }
+            _ => None,
+        }
+    }
+}
+
+// Manifest and data files derived from a log, not yet written.
+pub struct Import {
+    pub manifest: Manifest,
+    // file name relative to the manifest and its TOML text
+    pub data_files: Vec<(String, String)>,
+}
+
+fn load_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path.display(), err))
+}
+
+fn load_snapshot(
+    snapshot_dir: &Path,
+    ciphertext: &str,
+    parameters: &str,
+    op: LogOp,
+    input: usize,
+) -> Result<Snapshot, String> {
+    let path = snapshot_dir.join(format!("{}.toml", ciphertext));
+    let snapshot: Snapshot = load_toml(&path)?;
+    let problem = if snapshot.values.len() != op.component_num(input) {
+        Some(format!(
+            "has {} components, expected {}",
+            snapshot.values.len(),
+            op.component_num(input)
+        ))
+    } else if snapshot
+        .values
+        .iter()
+        .flatten()
+        .any(|level| level.len() != COEFF_DEGREE)
+    {
+        Some(format!(
+            "does not have {} coefficients per level",
+            COEFF_DEGREE
+        ))
+    } else if matches!(&snapshot.parameters, Some(p) if p != parameters) {
+        Some(format!(
+            "was written with parameters {}, the operation uses {}",
+            snapshot.parameters.as_deref().unwrap_or_default(),
+            parameters
+        ))
+    } else if op == LogOp::Rescale && snapshot.scale.is_none() {
+        Some(String::from("does not record its Scale"))
+    } else if let (Some(form), Some(expected)) = (&snapshot.form, op.form()) {
+        (form.to_lowercase() != expected)
+            .then(|| format!("is in {} form, expected {}", form, expected))
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(format!("ciphertext {} {}", ciphertext, problem)),
+        None => Ok(snapshot),
+    }
+}
+
+fn to_values<const N: usize>(values: Vec<[Vec<u64>; COEFF_LEVEL]>) -> [[Vec<u64>; COEFF_LEVEL]; N] {
+    values
+        .try_into()
+        .unwrap_or_else(|_| panic!("snapshot component count was checked"))
+}
+
+// Data file contents of one operation.
+fn data_text(
+    op: LogOp,
+    modulus: Vec<u64>,
+    mut inputs: Vec<Snapshot>,
+) -> Result<String, toml::ser::Error> {
+    let scale = inputs.first().and_then(|snapshot| snapshot.scale);
+    let mut operands = inputs.drain(..).map(|snapshot| snapshot.values);
+    let mut next = || operands.next().expect("input count was checked");
+    match op {
+        LogOp::Add => {
+            let zero = std::array::from_fn(|_| vec![vec![0; COEFF_DEGREE]; COEFF_LEVEL]);
+            let [a, b] = [to_values(next()), to_values(next())]
+                .map(|operand: [_; VALUE_NUM]| operand.map(Vec::from));
+            let values = vec![a, b, zero];
+            toml::to_string(&CustomData {
+                modulus,
+                coeff_degree: None,
+                levels: None,
+                values,
+            })
+        }
+        LogOp::Sub => toml::to_string(&SubData {
+            modulus,
+            values: [to_values(next()), to_values(next())],
+        }),
+        LogOp::Mul => toml::to_string(&MulData {
+            modulus,
+            values: [to_values(next()), to_values(next())],
+        }),
+        LogOp::MulPlain => {
+            let values = to_values(next());
+            let [plaintext] = to_values(next());
+            toml::to_string(&PlainMulData {
+                modulus,
+                values,
+                plaintext,
+            })
+        }
+        LogOp::Relin => toml::to_string(&RelinData {
+            modulus,
+            values: to_values(next()),
+        }),
+        LogOp::Rescale => toml::to_string(&RescaleData {
+            modulus,
+            scale: scale.expect("snapshot scale was checked"),
+            values: to_values(next()),
+        }),
+        LogOp::Negate
+        | LogOp::Rotate
+        | LogOp::Conjugate
+        | LogOp::KeySwitch
+        | LogOp::ModSwitch
+        | LogOp::ToNtt
+        | LogOp::FromNtt => toml::to_string(&CiphertextData {
+            modulus,
+            values: to_values(next()),
+        }),
+    }
+}
+
+// Builds the manifest of the log at `log_path`, reading snapshots from `snapshot_dir`. Evaluation
+// key paths in the log are relative to the log and are made absolute.
+pub fn import_log(log_path: &Path, snapshot_dir: &Path) -> Result<Import, String> {
+    let log: OperationLog = load_toml(log_path)?;
+    let log_dir = log_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut manifest = Manifest::default();
+    let mut data_files = Vec::with_capacity(log.entries.len());
+
+    for (index, entry) in log.entries.iter().enumerate() {
+        let id = entry
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("op{}", index + 1));
+        let context = |err: String| format!("operation {} ({}): {}", id, entry.op_type, err);
+        let op = LogOp::parse(&entry.op_type)
+            .ok_or_else(|| context(String::from("unsupported operation type")))?;
+        if entry.inputs.len() != op.input_num() {
+            return Err(context(format!(
+                "has {} inputs, expected {}",
+                entry.inputs.len(),
+                op.input_num()
+            )));
+        }
+        let parameters = log
+            .parameter_sets
+            .iter()
+            .find(|p| p.id == entry.parameters)
+            .ok_or_else(|| context(format!("unknown parameters {}", entry.parameters)))?;
+        if parameters.modulus.len() != COEFF_LEVEL {
+            return Err(context(format!(
+                "parameters {} have {} moduli, expected {}",
+                parameters.id,
+                parameters.modulus.len(),
+                COEFF_LEVEL
+            )));
+        }
+        let evaluation_key = match (&entry.evaluation_key, op) {
+            (Some(key), _) => {
+                let path = log_dir.join(key);
+                let path = fs::canonicalize(&path)
+                    .map_err(|err| context(format!("cannot find {}: {}", path.display(), err)))?;
+                Some(path.to_string_lossy().into_owned())
+            }
+            (None, LogOp::Relin | LogOp::KeySwitch) => {
+                return Err(context(String::from("needs an evaluation_key")))
+            }
+            (None, _) => None,
+        };
+        if op == LogOp::Rotate && entry.rotation.is_none() {
+            return Err(context(String::from("needs a rotation")));
+        }
+
+        let inputs = entry
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| load_snapshot(snapshot_dir, input, &parameters.id, op, i))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(context)?;
+        let data = format!("{}.toml", id);
+        let text = data_text(op, parameters.modulus.clone(), inputs)
+            .map_err(|err| context(err.to_string()))?;
+        data_files.push((data.clone(), text));
+        manifest.operations.push(Operation {
+            id,
+            circuit: String::from(op.circuit().name()),
+            data,
+            inputs: entry.inputs.clone(),
+            output: entry.output.clone(),
+            proof: None,
+            evaluation_key,
+            rotation: entry.rotation,
+            expose: None,
+            scheme: None,
+        });
+    }
+
+    build_dag(&manifest)?;
+    Ok(Import {
+        manifest,
+        data_files,
+    })
+}
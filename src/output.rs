@@ -0,0 +1,83 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+const DEFAULT_PROOF_FILE_PATH: &str = "./stark.toml";
+
+#[derive(Args, Debug)]
+#[clap(next_help_heading = "OUTPUT ARGUMENTS")]
+pub struct OutputArg {
+    /// Exact path of the proof file [default: ./stark.toml]
+    #[clap(
+        long,
+        short = 'o',
+        short_alias = 'p',
+        alias = "proof-file-path",
+        display_order = 1,
+        conflicts_with = "output-dir"
+    )]
+    pub output: Option<String>,
+    /// Directory receiving proofs named after --name-template
+    #[clap(long, display_order = 2)]
+    pub output_dir: Option<String>,
+    /// File name template, placeholders: {input_stem}, {circuit}
+    #[clap(long, display_order = 3, default_value_t = String::from("{input_stem}.{circuit}.proof"))]
+    pub name_template: String,
+    /// Overwrite the proof file if it already exists
+    #[clap(long, display_order = 4)]
+    pub force: bool,
+}
+
+impl OutputArg {
+    // Resolves where the proof for `input_path` proven with `circuit` goes, refusing to clobber
+    // an existing file unless --force was given.
+    pub fn resolve(&self, input_path: &str, circuit: &str) -> Result<PathBuf, String> {
+        let path = match (&self.output, &self.output_dir) {
+            (Some(output), _) => PathBuf::from(output),
+            (None, Some(dir)) => {
+                let name = render_template(&self.name_template, input_path, circuit)?;
+                fs::create_dir_all(dir)
+                    .map_err(|err| format!("cannot create output directory {}: {}", dir, err))?;
+                Path::new(dir).join(name)
+            }
+            (None, None) => PathBuf::from(DEFAULT_PROOF_FILE_PATH),
+        };
+        if path.exists() && !self.force {
+            return Err(format!(
+                "{} already exists, pass --force to overwrite it",
+                path.display()
+            ));
+        }
+        Ok(path)
+    }
+}
+
+pub fn render_template(template: &str, input_path: &str, circuit: &str) -> Result<String, String> {
+    let input_stem = Path::new(input_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| format!("cannot derive a file stem from {}", input_path))?;
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated placeholder in template {}", template))?;
+        match &rest[start + 1..start + end] {
+            "input_stem" => name.push_str(input_stem),
+            "circuit" => name.push_str(circuit),
+            other => return Err(format!("unknown placeholder {{{}}} in template", other)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
@@ -0,0 +1,293 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Packing of PACK_NUM scalar ciphertexts, each encrypting a value in its constant coefficient,
+// into one batched ciphertext whose coefficient j * N / PACK_NUM holds value j:
+//
+//     c_v' = sum_j X^(e_j) * c_jv,  e_j = j * N / PACK_NUM
+//
+// Ciphertexts are in evaluation form, so every monomial is a pointwise factor as in
+// BlindRotateAir: circuit row k holds evaluation k of every operand and of the result, and
+//
+//     r_v = sum_j w_jl * c_jv mod q_l
+//
+// w_jl being X^(e_j) at the evaluation point of row k modulo q_l, is proven by the Barrett
+// blocks of reduction.rs, one per row of a row group, against the moduli of the public inputs.
+// The factors are periodic columns derived from those moduli, stretched over the groups.
+// Missing operands are zero ciphertexts. The sum stays below PACK_NUM * 2^124 for moduli of 62
+// bits, far below the field modulus.
+//
+// UnpackAir proves the reverse, moving every value back into the constant coefficient of a
+// ciphertext of its own.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::blind_rotate_air::monomial_evaluations;
+use crate::ciphertext::check_levels;
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::check_ntt_modulus;
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, read_group_result,
+    reduction_group, reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 C000 C001 C010 C011 C100 .. C711 K.. R.. S..
+pub const PACK_NUM: usize = 8;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = PACK_NUM * RESULT_LEN;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + DATA_LEN;
+// a sum is below PACK_NUM * m^2, so its quotient below PACK_NUM * m
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(PACK_NUM as u128 * ((1 << MUL_MAX_MODULUS_BITS) - 1)),
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per result coefficient and level, then padding
+pub const PACK_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of PackAir in canonical order.
+pub fn pack_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("pack_num", PACK_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", PACK_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// At most PACK_NUM ciphertexts, in evaluation form; operand j goes to coefficient
+// j * N / PACK_NUM.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PackData {
+    pub modulus: Vec<u64>,
+    pub values: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+// Exponent e_j of the monomial operand `j` is multiplied by.
+pub fn pack_exponent(j: usize) -> i32 {
+    (j * (COEFF_DEGREE / PACK_NUM)) as i32
+}
+
+// Factors of every operand at every level, in the order of NttAir's outputs, for the monomials
+// X^(sign * e_j).
+pub fn pack_factors(modulus: &[u64], sign: i32) -> Vec<[Vec<u64>; COEFF_LEVEL]> {
+    (0..PACK_NUM)
+        .map(|j| std::array::from_fn(|l| monomial_evaluations(sign * pack_exponent(j), modulus[l])))
+        .collect()
+}
+
+// Periodic columns of the reductions, then of `factors` for every operand and level, stretched
+// over the groups.
+pub fn factor_columns(
+    reductions: &Reductions,
+    factors: &[[Vec<u64>; COEFF_LEVEL]],
+) -> Vec<Vec<BaseElement>> {
+    let mut columns = reductions.periodic_columns();
+    for levels in factors.iter() {
+        for values in levels.iter() {
+            let column: Vec<BaseElement> = values.iter().map(|&x| element_from_u64(x)).collect();
+            columns.push(reductions.stretch(&column));
+        }
+    }
+    columns
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn data_column(j: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + j * RESULT_LEN, v, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, component v at level l being value
+// v * COEFF_LEVEL + l of the row.
+pub fn pack_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Pack, modulus, MUL_MAX_MODULUS_BITS)?;
+    check_ntt_modulus(modulus)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| PACK_NUM as u128 * (modulus[l] as u128 - 1).pow(2),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_pack_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: PackData = load_data_file(&arg.data_file_path)?;
+    check_ntt_modulus(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    if data.values.is_empty() || data.values.len() > PACK_NUM {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "packing needs between 1 and {} ciphertexts, found {}",
+            PACK_NUM,
+            data.values.len()
+        )));
+    }
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+    let reductions = pack_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let factors = pack_factors(&data.modulus, 1);
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for (j, values) in data.values.iter().enumerate() {
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns[data_column(j, v, l)] =
+                    values[v][l].iter().map(|&c| element_from_u64(c)).collect();
+            }
+        }
+    }
+    let sum = |k: usize, v: usize, l: usize| {
+        data.values
+            .iter()
+            .enumerate()
+            .map(|(j, values)| factors[j][l][k] as u128 * values[v][l][k] as u128)
+            .sum::<u128>()
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                columns[result_column(v, l)][k] =
+                    element_from_u64((sum(k, v, l) % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        sum(k, j / COEFF_LEVEL, j % COEFF_LEVEL)
+    }))
+}
+
+pub fn get_pack_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, PACK_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The sums of one row with the factors `factors` of its evaluation point, component v at level l
+// at v * COEFF_LEVEL + l.
+fn row_sums<E: FieldElement>(row: &[E], factors: &[E]) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let mut sum = E::ZERO;
+            for j in 0..PACK_NUM {
+                sum += factors[j * COEFF_LEVEL + l] * row[data_column(j, v, l)];
+            }
+            sums[v * COEFF_LEVEL + l] = sum;
+        }
+    }
+    sums
+}
+
+pub struct PackAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    modulus: Vec<u64>,
+}
+
+impl Air for PackAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The sums are linear in the operands with factors whose stretched columns span the trace;
+    // the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("packing proofs need the moduli");
+        let reductions = pack_reductions(&modulus).expect("packing moduli were checked");
+        let degrees = reductions.degrees(1, &[STATE_LENGTH * reductions.group()]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        PackAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            modulus,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    // Periodic values are those of the reductions, then the factors for every operand and level.
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let factors = &periodic_values[self.reductions.num_periodic_columns()..];
+        let sums = row_sums(frame.current(), factors);
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, result);
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        factor_columns(&self.reductions, &pack_factors(&self.modulus, 1))
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, the
+    // modulus columns to the public moduli, from which the factors are derived.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Binary input format of the add circuit, an alternative to TOML data files for large ciphertext
+// dumps. Integers are little endian:
+//
+//     magic "SHEP", version u8
+//     modulus count u8, moduli u64 each
+//     operand count u8
+//     every coefficient vector of CustomData::values, operand major then component then level:
+//         encoding u8, length u32, payload
+//
+// Raw vectors hold their coefficients as u64. Delta vectors hold the first coefficient as u64
+// and the zigzag encoded differences of consecutive coefficients in blocks of BLOCK_LEN, every
+// block a bit width u8 followed by its differences packed LSB first at that width. Centered
+// delta vectors do the same after lifting the residues to (-q/2, q/2] with the modulus q of
+// their level, so that small negative coefficients stored as q - x stay small. Coefficients of
+// fresh or low-noise ciphertexts in coefficient form compress 2-4x this way; the encoder picks
+// the smallest encoding of every vector and falls back to raw vectors.
+//
+// Version 1 files have no operand count and hold three operands.
+//
+// Data files starting with the magic are decoded transparently wherever a CustomData file is
+// read.
+
+use std::fmt;
+use std::fs;
+
+use crate::air::CustomData;
+use crate::error::StarkHeError;
+
+pub const MAGIC: &[u8; 4] = b"SHEP";
+pub const VERSION: u8 = 2;
+// Operands of version 1 files.
+const V1_OPERAND_NUM: usize = 3;
+
+const RAW: u8 = 0;
+const DELTA: u8 = 1;
+const CENTERED_DELTA: u8 = 2;
+const BLOCK_LEN: usize = 128;
+// Bounds the allocation of a corrupted length field.
+const MAX_VECTOR_LEN: usize = 1 << 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PackedError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownEncoding(u8),
+    BadBitWidth(u8),
+    MissingModulus,
+    TooLong(usize),
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for PackedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackedError::Truncated => write!(f, "packed data is truncated"),
+            PackedError::BadMagic => write!(f, "not a packed data file"),
+            PackedError::UnsupportedVersion(version) => {
+                write!(f, "unsupported packed data version {}", version)
+            }
+            PackedError::UnknownEncoding(encoding) => {
+                write!(f, "unknown coefficient encoding {}", encoding)
+            }
+            PackedError::BadBitWidth(width) => write!(f, "invalid bit width {}", width),
+            PackedError::MissingModulus => {
+                write!(f, "centered vector of a level without a modulus")
+            }
+            PackedError::TooLong(len) => {
+                write!(
+                    f,
+                    "coefficient vector of {} values exceeds the limit of {}",
+                    len, MAX_VECTOR_LEN
+                )
+            }
+            PackedError::TrailingBytes(len) => {
+                write!(f, "{} unexpected bytes after the last vector", len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackedError {}
+
+pub fn is_packed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn zigzag(delta: i64) -> u64 {
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn bit_width(value: u64) -> u8 {
+    (64 - value.leading_zeros()) as u8
+}
+
+// Residue modulo `modulus` as a signed value in (-q/2, q/2], in two's complement.
+fn lift(value: u64, modulus: u64) -> u64 {
+    if value > modulus / 2 {
+        value.wrapping_sub(modulus)
+    } else {
+        value
+    }
+}
+
+fn unlift(value: u64, modulus: u64) -> u64 {
+    if (value as i64) < 0 {
+        value.wrapping_add(modulus)
+    } else {
+        value
+    }
+}
+
+// Differences wrap around 2^64, so every u64 vector round-trips.
+fn deltas(values: &[u64]) -> impl Iterator<Item = u64> + '_ {
+    values
+        .windows(2)
+        .map(|pair| zigzag(pair[1].wrapping_sub(pair[0]) as i64))
+}
+
+fn pack_delta(values: &[u64], out: &mut Vec<u8>) {
+    out.extend_from_slice(&values[0].to_le_bytes());
+    let deltas: Vec<u64> = deltas(values).collect();
+    for block in deltas.chunks(BLOCK_LEN) {
+        let width = block.iter().map(|&d| bit_width(d)).max().unwrap_or(0);
+        out.push(width);
+        let mut acc: u128 = 0;
+        let mut bits = 0;
+        for &delta in block {
+            acc |= (delta as u128) << bits;
+            bits += width as usize;
+            while bits >= 8 {
+                out.push(acc as u8);
+                acc >>= 8;
+                bits -= 8;
+            }
+        }
+        if bits > 0 {
+            out.push(acc as u8);
+        }
+    }
+}
+
+// Size of the delta payload, to choose between the encodings without packing twice.
+fn delta_size(values: &[u64]) -> usize {
+    let deltas: Vec<u64> = deltas(values).collect();
+    8 + deltas
+        .chunks(BLOCK_LEN)
+        .map(|block| {
+            let width = block.iter().map(|&d| bit_width(d)).max().unwrap_or(0) as usize;
+            1 + (block.len() * width).div_ceil(8)
+        })
+        .sum::<usize>()
+}
+
+fn pack_vector(values: &[u64], modulus: Option<u64>, out: &mut Vec<u8>) {
+    let mut best = (RAW, 8 * values.len(), None);
+    if !values.is_empty() {
+        let size = delta_size(values);
+        if size < best.1 {
+            best = (DELTA, size, Some(values.to_vec()));
+        }
+    }
+    // lifting round-trips residues only
+    if let Some(q) = modulus.filter(|&q| q > 0 && q <= i64::MAX as u64) {
+        if !values.is_empty() && values.iter().all(|&v| v < q) {
+            let lifted: Vec<u64> = values.iter().map(|&v| lift(v, q)).collect();
+            let size = delta_size(&lifted);
+            if size < best.1 {
+                best = (CENTERED_DELTA, size, Some(lifted));
+            }
+        }
+    }
+
+    let (encoding, _, deltas) = best;
+    out.push(encoding);
+    out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    match deltas {
+        Some(deltas) => pack_delta(&deltas, out),
+        None => {
+            for value in values {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+pub fn encode_custom_data(data: &CustomData) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(data.modulus.len() as u8);
+    for modulus in data.modulus.iter() {
+        out.extend_from_slice(&modulus.to_le_bytes());
+    }
+    out.push(data.values.len() as u8);
+    for component in data.values.iter().flatten() {
+        for (l, values) in component.iter().enumerate() {
+            pack_vector(values, data.modulus.get(l).copied(), &mut out);
+        }
+    }
+    out
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PackedError> {
+        if self.bytes.len() < len {
+            return Err(PackedError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, PackedError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, PackedError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, PackedError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn vector(&mut self, modulus: Option<u64>) -> Result<Vec<u64>, PackedError> {
+        let encoding = self.u8()?;
+        let len = self.u32()? as usize;
+        if len > MAX_VECTOR_LEN {
+            return Err(PackedError::TooLong(len));
+        }
+        let mut values = Vec::with_capacity(len);
+        match encoding {
+            RAW => {
+                for _ in 0..len {
+                    values.push(self.u64()?);
+                }
+            }
+            DELTA | CENTERED_DELTA if len == 0 => return Err(PackedError::Truncated),
+            DELTA | CENTERED_DELTA => {
+                let mut value = self.u64()?;
+                values.push(value);
+                while values.len() < len {
+                    let width = self.u8()?;
+                    if width > 64 {
+                        return Err(PackedError::BadBitWidth(width));
+                    }
+                    let count = BLOCK_LEN.min(len - values.len());
+                    let block = self.take((count * width as usize).div_ceil(8))?;
+                    let mask = if width == 64 {
+                        u64::MAX
+                    } else {
+                        (1u64 << width) - 1
+                    };
+                    for i in 0..count {
+                        let bit = i * width as usize;
+                        // a value spans at most 9 bytes
+                        let mut acc: u128 = 0;
+                        for (j, &byte) in block[bit / 8..].iter().take(9).enumerate() {
+                            acc |= (byte as u128) << (8 * j);
+                        }
+                        let delta = ((acc >> (bit % 8)) as u64) & mask;
+                        value = value.wrapping_add(unzigzag(delta) as u64);
+                        values.push(value);
+                    }
+                }
+            }
+            encoding => return Err(PackedError::UnknownEncoding(encoding)),
+        }
+        if encoding == CENTERED_DELTA {
+            let q = modulus.ok_or(PackedError::MissingModulus)?;
+            for value in values.iter_mut() {
+                *value = unlift(*value, q);
+            }
+        }
+        Ok(values)
+    }
+}
+
+pub fn decode_custom_data(bytes: &[u8]) -> Result<CustomData, PackedError> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(PackedError::BadMagic);
+    }
+    let version = reader.u8()?;
+    if version != 1 && version != VERSION {
+        return Err(PackedError::UnsupportedVersion(version));
+    }
+    let modulus_num = reader.u8()? as usize;
+    let mut data = CustomData {
+        modulus: Vec::with_capacity(modulus_num),
+        ..Default::default()
+    };
+    for _ in 0..modulus_num {
+        data.modulus.push(reader.u64()?);
+    }
+    let operand_num = match version {
+        1 => V1_OPERAND_NUM,
+        _ => reader.u8()? as usize,
+    };
+    data.values.resize_with(operand_num, Default::default);
+    // one level per modulus
+    for component in data.values.iter_mut().flatten() {
+        for l in 0..modulus_num {
+            component.push(reader.vector(data.modulus.get(l).copied())?);
+        }
+    }
+    if !reader.bytes.is_empty() {
+        return Err(PackedError::TrailingBytes(reader.bytes.len()));
+    }
+    Ok(data)
+}
+
+// Reads a CustomData file in either the TOML or the packed format.
+pub fn read_custom_data(path: &str) -> Result<CustomData, StarkHeError> {
+    let bytes = fs::read(path).map_err(|err| StarkHeError::io(path, err))?;
+    if is_packed(&bytes) {
+        return decode_custom_data(&bytes).map_err(|err| StarkHeError::parse(path, err));
+    }
+    let text = String::from_utf8(bytes).map_err(|err| StarkHeError::parse(path, err))?;
+    toml::from_str(&text).map_err(|err| StarkHeError::parse(path, err))
+}
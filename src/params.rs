@@ -0,0 +1,322 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Named parameter sets after Microsoft SEAL, selected by `prover --params`. The BFV presets are
+// SEAL's 128-bit default coefficient moduli of their degree (CoeffModulus::BFVDefault), the CKKS
+// preset is the 60, 40, 40, 60 bit modulus chain SEAL's CKKS examples create for degree 8192
+// (CoeffModulus::Create), with the primes SEAL picks. A preset sets the ring degree of `add`,
+// requires the moduli of the data file to be its own, and replaces the proof options by those of
+// `--security-level 128`, to match the security of the ciphertexts. The other circuits are of
+// the build's degree and level count, which no preset has.
+//
+// Security levels derive the proof options which reach a conjectured security, about
+// num_queries * log2(blowup_factor) + grinding_factor bits capped by the field and the hash
+// function, for every trace of at most 2^28 rows: 96 bits are reached by the default 42 queries
+// at a blowup factor of 4 and 16 bits of grinding, in the smallest field of the build (see
+// MIN_FIELD_EXTENSION); 128 bits also need a quadratic extension of the 128-bit field, as the
+// base field leaves less than 128 bits over a large LDE domain, and 38 queries
+// at a blowup factor of 8. The hash function is the caller's, as long as its collision
+// resistance reaches the level: BLAKE3-192 only reaches 96 bits.
+
+use clap::ArgEnum;
+use winter_air::{FieldExtension, HashFunction, ProofOptions};
+
+use crate::air::{Op, MIN_FIELD_EXTENSION};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Params {
+    #[clap(name = "bfv_4096_128bit")]
+    Bfv4096,
+    #[clap(name = "bfv_8192_128bit")]
+    Bfv8192,
+    #[clap(name = "bfv_16384_128bit")]
+    Bfv16384,
+    #[clap(name = "ckks_8192")]
+    Ckks8192,
+}
+
+const BFV_4096_MODULUS: [u64; 3] = [0xffffee001, 0xffffc4001, 0x1ffffe0001];
+const BFV_8192_MODULUS: [u64; 5] = [
+    0x7fffffd8001,
+    0x7fffffc8001,
+    0xfffffffc001,
+    0xffffff6c001,
+    0xfffffebc001,
+];
+const BFV_16384_MODULUS: [u64; 9] = [
+    0xfffffffd8001,
+    0xfffffffa0001,
+    0xfffffff00001,
+    0x1fffffff68001,
+    0x1fffffff50001,
+    0x1ffffffee8001,
+    0x1ffffffea0001,
+    0x1ffffffe88001,
+    0x1ffffffe48001,
+];
+const CKKS_8192_MODULUS: [u64; 4] = [
+    0xffffffffffe8001,
+    0xfffff4c001,
+    0xfffffdc001,
+    0xfffffffffffc001,
+];
+
+impl Params {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Params::Bfv4096 => "bfv_4096_128bit",
+            Params::Bfv8192 => "bfv_8192_128bit",
+            Params::Bfv16384 => "bfv_16384_128bit",
+            Params::Ckks8192 => "ckks_8192",
+        }
+    }
+
+    pub fn coeff_degree(&self) -> usize {
+        match self {
+            Params::Bfv4096 => 4096,
+            Params::Bfv8192 | Params::Ckks8192 => 8192,
+            Params::Bfv16384 => 16384,
+        }
+    }
+
+    // RNS moduli, one per level, in SEAL's order.
+    pub fn modulus(&self) -> &'static [u64] {
+        match self {
+            Params::Bfv4096 => &BFV_4096_MODULUS,
+            Params::Bfv8192 => &BFV_8192_MODULUS,
+            Params::Bfv16384 => &BFV_16384_MODULUS,
+            Params::Ckks8192 => &CKKS_8192_MODULUS,
+        }
+    }
+
+    pub fn proof_options(&self) -> ProofOptions {
+        SecurityLevel::Bits128
+            .proof_options(HashFunction::Blake3_256, 8, 256)
+            .expect("BLAKE3-256 reaches 128 bits")
+    }
+
+    // The moduli of a data file must be those of the preset, in its order.
+    pub fn check_modulus(&self, modulus: &[u64]) -> Result<(), String> {
+        if modulus != self.modulus() {
+            return Err(format!(
+                "data file moduli {:?} are not those of {}, {:?}",
+                modulus,
+                self.name(),
+                self.modulus()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum SecurityLevel {
+    #[clap(name = "96")]
+    Bits96,
+    #[clap(name = "128")]
+    Bits128,
+}
+
+impl SecurityLevel {
+    pub fn bits(&self) -> u32 {
+        match self {
+            SecurityLevel::Bits96 => 96,
+            SecurityLevel::Bits128 => 128,
+        }
+    }
+
+    // Proof options of this level with the hash function, FRI folding factor and largest
+    // remainder left to the caller; hash functions of less collision resistance than the level
+    // are rejected.
+    pub fn proof_options(
+        &self,
+        hash_fn: HashFunction,
+        folding_factor: usize,
+        fri_max_remainder_size: usize,
+    ) -> Result<ProofOptions, String> {
+        if hash_fn.collision_resistance() < self.bits() {
+            return Err(format!(
+                "{:?} has {} bits of collision resistance, below --security-level {}",
+                hash_fn,
+                hash_fn.collision_resistance(),
+                self.bits()
+            ));
+        }
+        let (num_queries, blowup_factor, field_extension) = match self {
+            SecurityLevel::Bits96 => (42, 4, MIN_FIELD_EXTENSION),
+            SecurityLevel::Bits128 => (38, 8, FieldExtension::Quadratic),
+        };
+        Ok(ProofOptions::new(
+            num_queries,
+            blowup_factor,
+            16,
+            hash_fn,
+            field_extension,
+            folding_factor,
+            fri_max_remainder_size,
+        ))
+    }
+}
+
+// Named proof options for `prover --profile`, trading proving time against security: `fast`
+// for development, about 90 bits with the 96-bit BLAKE3-192; `balanced`, the cap of the base
+// field at a smaller proof than the defaults; `paranoid`, the 128 bits of SHA3-256 with a
+// wide margin of queries, grinding and a quadratic extension. Unlike the security levels, a
+// profile also sets the hash function; the FRI folding factor and remainder size stay the
+// caller's. None of them has the options of the defaults or of a security level, so a proof
+// has the options of at most one profile, which its reproducibility manifest records.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Profile {
+    Fast,
+    Balanced,
+    Paranoid,
+}
+
+impl Profile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Fast => "fast",
+            Profile::Balanced => "balanced",
+            Profile::Paranoid => "paranoid",
+        }
+    }
+
+    // Queries, blowup factor, grinding bits, hash function and field extension.
+    fn settings(&self) -> (usize, usize, u32, HashFunction, FieldExtension) {
+        match self {
+            Profile::Fast => (24, 8, 20, HashFunction::Blake3_192, MIN_FIELD_EXTENSION),
+            Profile::Balanced => (36, 8, 16, HashFunction::Blake3_256, MIN_FIELD_EXTENSION),
+            Profile::Paranoid => (
+                48,
+                16,
+                24,
+                HashFunction::Sha3_256,
+                FieldExtension::Quadratic,
+            ),
+        }
+    }
+
+    pub fn proof_options(
+        &self,
+        folding_factor: usize,
+        fri_max_remainder_size: usize,
+    ) -> ProofOptions {
+        let (num_queries, blowup_factor, grinding_factor, hash_fn, field_extension) =
+            self.settings();
+        ProofOptions::new(
+            num_queries,
+            blowup_factor,
+            grinding_factor,
+            hash_fn,
+            field_extension,
+            folding_factor,
+            fri_max_remainder_size,
+        )
+    }
+
+    // The profile whose options `options` has, whatever its FRI folding factor and remainder.
+    pub fn of(options: &ProofOptions) -> Option<Profile> {
+        let settings = (
+            options.num_queries(),
+            options.blowup_factor(),
+            options.grinding_factor(),
+            options.hash_fn(),
+            options.field_extension(),
+        );
+        Profile::value_variants()
+            .iter()
+            .copied()
+            .find(|profile| profile.settings() == settings)
+    }
+}
+
+// Presets configure the runtime degree and moduli of `add`; the other circuits are of the
+// build's.
+pub fn check_params(op: Op, params: Option<Params>) -> Result<(), String> {
+    match params {
+        Some(params) if op != Op::Add => Err(format!(
+            "--params {} applies to {} proofs, {} proofs are of the build's degree and moduli",
+            params.name(),
+            Op::Add.name(),
+            op.name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::{check_coeff_degree, check_coeff_level};
+    use crate::barrett::BARRETT_MODULUS_BITS;
+    use crate::ntt_air::pow_mod;
+
+    #[test]
+    fn presets_are_ntt_friendly() {
+        for params in Params::value_variants() {
+            assert_eq!(Params::from_str(params.name(), false), Ok(*params));
+            let n = params.coeff_degree() as u64;
+            assert!(check_coeff_degree(params.coeff_degree()).is_ok());
+            assert!(check_coeff_level(params.modulus().len()).is_ok());
+            for &q in params.modulus() {
+                assert_eq!(q % (2 * n), 1, "{} of {}", q, params.name());
+                assert_eq!(pow_mod(3, q - 1, q), 1, "{} of {}", q, params.name());
+                assert!(64 - q.leading_zeros() as usize <= BARRETT_MODULUS_BITS);
+            }
+        }
+    }
+
+    #[test]
+    fn moduli_and_circuits_are_checked() {
+        let params = Params::Bfv4096;
+        assert!(params.check_modulus(&BFV_4096_MODULUS).is_ok());
+        assert!(params.check_modulus(&BFV_4096_MODULUS[..2]).is_err());
+        assert!(params
+            .check_modulus(&[0x1ffffe0001, 0xffffc4001, 0xffffee001])
+            .is_err());
+        assert!(check_params(Op::Add, Some(params)).is_ok());
+        assert!(check_params(Op::Mul, Some(params)).is_err());
+        assert!(check_params(Op::Mul, None).is_ok());
+    }
+
+    #[test]
+    fn levels_take_the_hash_function() {
+        let options = SecurityLevel::Bits128
+            .proof_options(HashFunction::Sha3_256, 8, 256)
+            .unwrap();
+        assert_eq!(options.hash_fn(), HashFunction::Sha3_256);
+        assert_eq!(options.field_extension(), FieldExtension::Quadratic);
+        let options = SecurityLevel::Bits96
+            .proof_options(HashFunction::Blake3_192, 4, 32)
+            .unwrap();
+        assert_eq!(options.hash_fn(), HashFunction::Blake3_192);
+        assert_eq!(options.to_fri_options().folding_factor(), 4);
+    }
+
+    #[test]
+    fn profiles_are_recognized() {
+        for profile in Profile::value_variants() {
+            assert_eq!(Profile::from_str(profile.name(), false), Ok(*profile));
+            let options = profile.proof_options(4, 32);
+            assert_eq!(Profile::of(&options), Some(*profile));
+        }
+        let defaults = SecurityLevel::Bits96
+            .proof_options(HashFunction::Blake3_256, 8, 256)
+            .unwrap();
+        assert_eq!(Profile::of(&defaults), None);
+        assert_eq!(Profile::of(&Params::Bfv4096.proof_options()), None);
+    }
+
+    #[test]
+    fn levels_reject_weak_hash_functions() {
+        let err = SecurityLevel::Bits128
+            .proof_options(HashFunction::Blake3_192, 8, 256)
+            .unwrap_err();
+        assert!(err.ends_with("below --security-level 128"));
+        assert!(SecurityLevel::Bits96
+            .proof_options(HashFunction::Blake3_192, 8, 256)
+            .is_ok());
+    }
+}
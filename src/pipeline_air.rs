@@ -0,0 +1,793 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// A small encrypted program, a sequence of steps such as add -> mul -> rescale, proven in a
+// single trace instead of one proof per operation. A register x holds the running ciphertext,
+// and every step combines it with an operand b of its own:
+//
+//     add: x + b,  sub: x - b,  mul: x (x) b as in MulAir,  rescale: round(x / q_L) as in
+//     RescaleAir
+//
+// As in HornerAir the steps run one per circuit row, coefficient k of step j in circuit row
+// k * P + P - s + j for s steps and the power of two P >= s, and the rows before the first step
+// pass the register on unchanged. Which step a row runs is given by selector columns, one
+// periodic column per kind of step, so every circuit row proves, for every register component t
+// and level,
+//
+//     r_t = (pass * x_t + add * (x_t + b_t) + sub * (x_t + m - b_t) + mul * (x (x) b)_t) mod m
+//
+// with the selectors of the row and pass = 1 - add - sub - mul - rescale, plus, under the
+// rescale selector, the rounding and scaling of ModSwitchAir on the kept levels, which leave
+// zero at the dropped level. The values are reduced by the Barrett blocks of reduction.rs
+// against the moduli of the public inputs, one per row of a row group, the roundings by the
+// dropped modulus. The register of the next circuit row is r unless the row is the last of its
+// coefficient. The register has the three components of a tensor product, the last one zero
+// before the multiplication, so a program multiplies at most once; rescaling drops the last
+// level and ends the program. Like the circuits they replace the steps act on every row alone,
+// so additions and subtractions work in either form, a multiplication needs evaluation form and
+// a rescaling coefficient form. The steps are public, their operands private.
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+use winter_prover::Trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::modswitch_air::{Scaling, DROPPED_LEVEL, KEPT_LEVEL_NUM};
+use crate::mul_air::{MUL_MAX_MODULUS_BITS, MUL_RESULT_NUM};
+use crate::reduction::{
+    bind_moduli, bit_length, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+use crate::rescale_air::Scale;
+use crate::sum_air::{link_column, sum_slots};
+
+// Modulus + Result + Rounded + Register + Operand + Reduction
+// M0 M1 R00 R01 R10 R11 R20 R21 E0 E1 E2 X00 .. X21 B00 B01 B10 B11 K.. R.. S..
+pub const PIPELINE_MAX_STEPS: usize = 8;
+const REGISTER_NUM: usize = MUL_RESULT_NUM;
+const RESULT_LEN: usize = REGISTER_NUM * COEFF_LEVEL;
+const OPERAND_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const ROUNDED_START: usize = RESULT_START + RESULT_LEN;
+const REGISTER_START: usize = ROUNDED_START + REGISTER_NUM;
+const OPERAND_START: usize = REGISTER_START + RESULT_LEN;
+const OPERAND_END: usize = OPERAND_START + OPERAND_LEN;
+// every register component at every level, then the rounding of every component
+const REDUCTION_NUM: usize = RESULT_LEN + REGISTER_NUM;
+// a scaled value is below 3 * 2^62 times its modulus, as in ModSwitchAir, and a product below
+// 2^63 times it
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(3 << MUL_MAX_MODULUS_BITS),
+    MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per register component, level and rounding, then padding
+pub const PIPELINE_GROUP: usize = reduction_group(REDUCTION_NUM);
+
+const STATE_WIDTH: usize = OPERAND_END + REDUCTION_WIDTH;
+// selector columns of a row, one per kind of step
+const SELECTOR_NUM: usize = 4;
+
+// Trace layout constants of PipelineAir in canonical order. The trace length follows from the
+// number of steps, as for HornerAir.
+pub fn pipeline_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("register_num", REGISTER_NUM),
+        ("max_steps", PIPELINE_MAX_STEPS),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", PIPELINE_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Kinds of steps, recorded in the public inputs by their codes; 0 passes the register on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipelineStep {
+    Add,
+    Sub,
+    Mul,
+    Rescale,
+}
+
+impl PipelineStep {
+    pub fn code(&self) -> u64 {
+        match self {
+            PipelineStep::Add => 1,
+            PipelineStep::Sub => 2,
+            PipelineStep::Mul => 3,
+            PipelineStep::Rescale => 4,
+        }
+    }
+
+    pub fn from_code(code: u64) -> Option<PipelineStep> {
+        match code {
+            1 => Some(PipelineStep::Add),
+            2 => Some(PipelineStep::Sub),
+            3 => Some(PipelineStep::Mul),
+            4 => Some(PipelineStep::Rescale),
+            _ => None,
+        }
+    }
+
+    // Index of the selector column of the step.
+    fn selector(&self) -> usize {
+        self.code() as usize - 1
+    }
+
+    fn has_operand(&self) -> bool {
+        *self != PipelineStep::Rescale
+    }
+}
+
+// Same format as CiphertextData, with the steps in `Steps`, e.g. ["add", "mul", "rescale"], one
+// ciphertext in `Operands` for every step but the rescaling, in order, and the scale of the
+// register before the rescaling in `Scale` if there is one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PipelineData {
+    pub modulus: Vec<u64>,
+    pub steps: Vec<PipelineStep>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+    pub operands: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+fn result_column(t: usize, l: usize) -> usize {
+    level_column(RESULT_START, t, l)
+}
+
+fn rounded_column(t: usize) -> usize {
+    ROUNDED_START + t
+}
+
+fn register_column(t: usize, l: usize) -> usize {
+    level_column(REGISTER_START, t, l)
+}
+
+fn operand_column(v: usize, l: usize) -> usize {
+    level_column(OPERAND_START, v, l)
+}
+
+// Whether the program ends with a rescaling, which leaves the last level of the result empty.
+pub fn rescales(steps: &[u64]) -> bool {
+    steps.last() == Some(&PipelineStep::Rescale.code())
+}
+
+// A program of 1 to PIPELINE_MAX_STEPS steps which multiplies at most once and rescales at most
+// at the end; with `trace_length`, the trace must have the rows of its steps.
+pub fn check_program(steps: &[u64], trace_length: Option<usize>) -> Result<(), String> {
+    if !(1..=PIPELINE_MAX_STEPS).contains(&steps.len()) {
+        return Err(format!(
+            "a pipeline has 1 to {} steps, not {}",
+            PIPELINE_MAX_STEPS,
+            steps.len()
+        ));
+    }
+    let mut kinds = Vec::with_capacity(steps.len());
+    for &code in steps.iter() {
+        kinds.push(PipelineStep::from_code(code).ok_or_else(|| format!("unknown step {}", code))?);
+    }
+    let multiplications = kinds.iter().filter(|&&step| step == PipelineStep::Mul);
+    if multiplications.count() > 1 {
+        return Err(String::from(
+            "a pipeline multiplies at most once, its register has three components",
+        ));
+    }
+    if kinds[..kinds.len() - 1].contains(&PipelineStep::Rescale) {
+        return Err(String::from(
+            "rescaling drops the last RNS level, so it must be the last step",
+        ));
+    }
+    if rescales(steps) && KEPT_LEVEL_NUM == 0 {
+        return Err(String::from("rescaling needs a second RNS level"));
+    }
+    let expected = COEFF_DEGREE * sum_slots(steps.len()) * PIPELINE_GROUP;
+    match trace_length {
+        Some(length) if length != expected => Err(format!(
+            "pipelines of {} steps are proven in {} rows, not {}",
+            steps.len(),
+            expected,
+            length
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Programs ending with a rescaling record valid scales, the others none.
+pub fn check_pipeline_scale(steps: &[u64], scale: Option<Scale>) -> Result<(), String> {
+    match (rescales(steps), scale) {
+        (true, Some(scale)) => scale.check(),
+        (true, None) => Err(String::from(
+            "pipeline proof does not record the scales of its rescaling",
+        )),
+        (false, Some(_)) => Err(String::from(
+            "pipelines without a rescaling take no scales, but some are recorded",
+        )),
+        (false, None) => Ok(()),
+    }
+}
+
+// Step codes and scale of a data file, which the prover records in the public inputs.
+pub fn load_program(data_file_path: &str) -> Result<(Vec<u64>, Option<f64>), StarkHeError> {
+    let data: PipelineData = load_data_file(data_file_path)?;
+    let steps: Vec<u64> = data.steps.iter().map(PipelineStep::code).collect();
+    check_program(&steps, None).map_err(StarkHeError::InvalidInput)?;
+    if let Some(scale) = data.scale {
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(StarkHeError::InvalidInput(format!(
+                "invalid scale {}",
+                scale
+            )));
+        }
+    }
+    if data.scale.is_some() != rescales(&steps) {
+        return Err(StarkHeError::InvalidInput(String::from(
+            "pipelines need a scale exactly if they rescale",
+        )));
+    }
+    Ok((steps, data.scale))
+}
+
+// Step code of each row of a coefficient, 0 before the first step.
+fn step_codes(steps: &[u64], slots: usize) -> Vec<u64> {
+    let mut codes = vec![0; slots];
+    codes[slots - steps.len()..].copy_from_slice(steps);
+    codes
+}
+
+// One step at one evaluation point: the register components after `step`, for the register `x`
+// and the operand `b` at every level, the values reduced to them and for a rescaling with
+// `scaling` the rounded residues. The last level of a rescaled register is zero.
+pub struct StepRow {
+    pub results: [[u64; COEFF_LEVEL]; REGISTER_NUM],
+    pub rounded: [u64; REGISTER_NUM],
+    pub values: [u128; REDUCTION_NUM],
+}
+
+pub fn pipeline_step(
+    step: Option<PipelineStep>,
+    x: &[[u64; COEFF_LEVEL]; REGISTER_NUM],
+    b: &[[u64; COEFF_LEVEL]; VALUE_NUM],
+    modulus: &[u64],
+    scaling: Option<&Scaling>,
+) -> StepRow {
+    let mut row = StepRow {
+        results: [[0; COEFF_LEVEL]; REGISTER_NUM],
+        rounded: [0; REGISTER_NUM],
+        values: [0; REDUCTION_NUM],
+    };
+    if step == Some(PipelineStep::Rescale) {
+        let scaling = scaling.expect("rescaling steps have the constants of their scaling");
+        let dropped = modulus[DROPPED_LEVEL] as u128;
+        for t in 0..REGISTER_NUM {
+            let rounding = scaling.value(x[t], 0, 0);
+            row.rounded[t] = (rounding % dropped) as u64;
+            row.values[RESULT_LEN + t] = rounding;
+            for l in 0..KEPT_LEVEL_NUM {
+                let value = scaling.value(x[t], row.rounded[t], 1 + l);
+                row.results[t][l] = (value % modulus[l] as u128) as u64;
+                row.values[t * COEFF_LEVEL + l] = value;
+            }
+        }
+        return row;
+    }
+    for l in 0..COEFF_LEVEL {
+        let m = modulus[l] as u128;
+        let (x, b) = (x.map(|x| x[l] as u128), b.map(|b| b[l] as u128));
+        let terms = match step {
+            None => [x[0], x[1], x[2]],
+            Some(PipelineStep::Add) => [x[0] + b[0], x[1] + b[1], x[2]],
+            Some(PipelineStep::Sub) => [x[0] + m - b[0], x[1] + m - b[1], x[2]],
+            _ => [x[0] * b[0], x[0] * b[1] + x[1] * b[0], x[1] * b[1]],
+        };
+        for t in 0..REGISTER_NUM {
+            row.results[t][l] = (terms[t] % m) as u64;
+            row.values[t * COEFF_LEVEL + l] = terms[t];
+        }
+    }
+    row
+}
+
+// The scaling of a program `steps` over the moduli `modulus` if it rescales.
+fn pipeline_scaling(steps: &[u64], modulus: &[u64]) -> Result<Option<Scaling>, String> {
+    rescales(steps).then(|| Scaling::new(modulus)).transpose()
+}
+
+// The reductions of a circuit row of the program `steps` by the public moduli `modulus`: every
+// register component at every level to its result column, then the rounding of every component
+// to its rounded column. The rounded columns are only read under the rescale selector, so a
+// program which does not rescale leaves them zero and does not copy them over the row group.
+pub fn pipeline_reductions(modulus: &[u64], steps: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Pipeline, modulus, MUL_MAX_MODULUS_BITS)?;
+    let scaling = pipeline_scaling(steps, modulus)?;
+    let multiplies = steps.contains(&PipelineStep::Mul.code());
+    let dropped = modulus[DROPPED_LEVEL];
+    let mut results = Vec::with_capacity(REDUCTION_NUM);
+    let mut moduli = Vec::with_capacity(REDUCTION_NUM);
+    let mut bounds = Vec::with_capacity(REDUCTION_NUM);
+    for t in 0..REGISTER_NUM {
+        for l in 0..COEFF_LEVEL {
+            let m = modulus[l] as u128;
+            // a subtraction reaches 2m - 1, a multiplication 2 (m - 1)^2
+            let mut bound = 2 * m - 1;
+            if multiplies {
+                bound = bound.max(2 * (m - 1).pow(2));
+            }
+            if let Some(scaling) = scaling.as_ref().filter(|_| l != DROPPED_LEVEL) {
+                bound = bound.max(
+                    (m - 1 + scaling.half as u128 + scaling.offsets[l] as u128)
+                        * scaling.inverses[l] as u128,
+                );
+            }
+            results.push(result_column(t, l));
+            moduli.push(modulus[l]);
+            bounds.push(bound);
+        }
+    }
+    for t in 0..REGISTER_NUM {
+        results.push(rounded_column(t));
+        moduli.push(dropped);
+        bounds.push(
+            scaling
+                .as_ref()
+                .map_or(0, |scaling| dropped as u128 - 1 + scaling.half as u128),
+        );
+    }
+    let copied = (RESULT_START..OPERAND_END)
+        .filter(|&column| scaling.is_some() || !(ROUNDED_START..REGISTER_START).contains(&column))
+        .collect();
+    Reductions::new(
+        results,
+        moduli,
+        &bounds,
+        copied,
+        OPERAND_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_pipeline_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: PipelineData = load_data_file(&arg.data_file_path)?;
+    let steps: Vec<u64> = data.steps.iter().map(PipelineStep::code).collect();
+    check_program(&steps, None).map_err(StarkHeError::InvalidInput)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    let operand_num = data.steps.iter().filter(|step| step.has_operand()).count();
+    if data.operands.len() != operand_num {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "the steps of the pipeline need {} operands, found {}",
+            operand_num,
+            data.operands.len()
+        )));
+    }
+    for ciphertext in std::iter::once(&data.values).chain(data.operands.iter()) {
+        check_levels(&data.modulus, COEFF_DEGREE, ciphertext)?;
+    }
+    let reductions =
+        pipeline_reductions(&data.modulus, &steps).map_err(StarkHeError::InvalidInput)?;
+    let scaling = pipeline_scaling(&steps, &data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let slots = sum_slots(steps.len());
+    let codes = step_codes(&steps, slots);
+    let length = slots * COEFF_DEGREE;
+
+    let mut columns = vec![vec![BaseElement::ZERO; length]; OPERAND_END];
+    let mut values = vec![[0; REDUCTION_NUM]; length];
+    for l in 0..COEFF_LEVEL {
+        columns[l] = vec![element_from_u64(data.modulus[l]); length];
+    }
+    for k in 0..COEFF_DEGREE {
+        let mut x = [[0; COEFF_LEVEL]; REGISTER_NUM];
+        for v in 0..VALUE_NUM {
+            x[v] = std::array::from_fn(|l| data.values[v][l][k]);
+        }
+        let mut operands = data.operands.iter();
+        for i in 0..slots {
+            let row = k * slots + i;
+            let step = PipelineStep::from_code(codes[i]);
+            let mut b = [[0; COEFF_LEVEL]; VALUE_NUM];
+            if step.is_some_and(|step| step.has_operand()) {
+                let values = operands.next().unwrap();
+                b = std::array::from_fn(|v| std::array::from_fn(|l| values[v][l][k]));
+            }
+            let witness = pipeline_step(step, &x, &b, &data.modulus, scaling.as_ref());
+            for t in 0..REGISTER_NUM {
+                columns[rounded_column(t)][row] = element_from_u64(witness.rounded[t]);
+                for l in 0..COEFF_LEVEL {
+                    columns[register_column(t, l)][row] = element_from_u64(x[t][l]);
+                    columns[result_column(t, l)][row] = element_from_u64(witness.results[t][l]);
+                }
+            }
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    columns[operand_column(v, l)][row] = element_from_u64(b[v][l]);
+                }
+            }
+            values[row] = witness.values;
+            x = witness.results;
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// The results are the register components of the last circuit row of every coefficient,
+// without the dropped level if the program rescales; the public inputs also hold the step
+// codes, the moduli and the scales of the rescaling.
+pub fn get_pipeline_pub_inputs(
+    trace: &TraceType,
+    steps: Vec<u64>,
+    scale: Option<f64>,
+) -> PublicInputs {
+    let slots = trace.length() / (COEFF_DEGREE * PIPELINE_GROUP);
+    let levels = if rescales(&steps) {
+        KEPT_LEVEL_NUM
+    } else {
+        COEFF_LEVEL
+    };
+    let result = (0..REGISTER_NUM)
+        .map(|t| {
+            let mut values = vec![Vec::new(); COEFF_LEVEL];
+            for l in 0..levels {
+                values[l] = (0..COEFF_DEGREE)
+                    .map(|k| {
+                        trace.get(
+                            result_column(t, l),
+                            (k * slots + slots - 1) * PIPELINE_GROUP,
+                        )
+                    })
+                    .collect();
+            }
+            values
+        })
+        .collect();
+    let modulus = read_modulus(trace);
+    let scale = scale.map(|before| Scale::new(before, modulus[DROPPED_LEVEL]));
+    PublicInputs {
+        result,
+        evaluation_key: None,
+        rotation: None,
+        scale,
+        modulus: Some(modulus),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: Some(steps),
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The public moduli and the constants of the scaling as field elements, zero for programs
+// which do not rescale.
+struct StepConstants {
+    moduli: [BaseElement; COEFF_LEVEL],
+    half: BaseElement,
+    inverses: [BaseElement; KEPT_LEVEL_NUM],
+    offsets: [BaseElement; KEPT_LEVEL_NUM],
+}
+
+impl StepConstants {
+    fn new(modulus: &[u64], scaling: Option<&Scaling>) -> Self {
+        StepConstants {
+            moduli: std::array::from_fn(|l| element_from_u64(modulus[l])),
+            half: element_from_u64(scaling.map_or(0, |scaling| scaling.half)),
+            inverses: std::array::from_fn(|l| {
+                element_from_u64(scaling.map_or(0, |scaling| scaling.inverses[l]))
+            }),
+            offsets: std::array::from_fn(|l| {
+                element_from_u64(scaling.map_or(0, |scaling| scaling.offsets[l]))
+            }),
+        }
+    }
+}
+
+// The values one circuit row with the selectors `s` of its step reduces: every register
+// component at every level, at t * COEFF_LEVEL + l, then the rounding of every component.
+fn step_values<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    s: &[E],
+    constants: &StepConstants,
+) -> [E; REDUCTION_NUM] {
+    let (add, sub, mul, rescale) = (s[0], s[1], s[2], s[3]);
+    let pass = E::ONE - add - sub - mul - rescale;
+    let half = E::from(constants.half);
+    let mut values = [E::ZERO; REDUCTION_NUM];
+    for l in 0..COEFF_LEVEL {
+        let m = E::from(constants.moduli[l]);
+        let x: [E; REGISTER_NUM] = std::array::from_fn(|t| row[register_column(t, l)]);
+        let b = [row[operand_column(0, l)], row[operand_column(1, l)]];
+        let tensor = [x[0] * b[0], x[0] * b[1] + x[1] * b[0], x[1] * b[1]];
+        for t in 0..REGISTER_NUM {
+            let (plus, minus) = if t < VALUE_NUM {
+                (b[t], m - b[t])
+            } else {
+                (E::ZERO, E::ZERO)
+            };
+            let mut value =
+                pass * x[t] + add * (x[t] + plus) + sub * (x[t] + minus) + mul * tensor[t];
+            if l != DROPPED_LEVEL {
+                value += rescale
+                    * (x[t] + half - row[rounded_column(t)] + E::from(constants.offsets[l]))
+                    * E::from(constants.inverses[l]);
+            }
+            values[t * COEFF_LEVEL + l] = value;
+        }
+    }
+    for t in 0..REGISTER_NUM {
+        values[RESULT_LEN + t] = rescale * (row[register_column(t, DROPPED_LEVEL)] + half);
+    }
+    values
+}
+
+// Degree of the values of a circuit row of the program `steps` in `slots` operand slots: the
+// tensor product is quadratic, the other steps linear, with a periodic factor unless every
+// slot runs the same linear combination of the register and the operand.
+fn step_value_degree(steps: &[u64], slots: usize) -> (usize, Vec<usize>) {
+    let codes = step_codes(steps, slots);
+    let operand_sign = |code: u64| match PipelineStep::from_code(code) {
+        Some(PipelineStep::Add) => 1,
+        Some(PipelineStep::Sub) => -1,
+        _ => 0,
+    };
+    let periodic = steps.contains(&PipelineStep::Mul.code())
+        || rescales(steps)
+        || codes
+            .iter()
+            .any(|&code| operand_sign(code) != operand_sign(codes[0]));
+    let degree = 1 + steps.contains(&PipelineStep::Mul.code()) as usize;
+    let cycles = match periodic {
+        true => vec![slots * PIPELINE_GROUP],
+        false => Vec::new(),
+    };
+    (degree, cycles)
+}
+
+pub struct PipelineAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    constants: StepConstants,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    steps: Vec<u64>,
+    slots: usize,
+}
+
+impl PipelineAir {
+    fn multiplies(&self) -> bool {
+        self.steps.contains(&PipelineStep::Mul.code())
+    }
+}
+
+impl Air for PipelineAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, the links of the register as in HornerAir and, for a program
+    // which multiplies, the empty last register component of the multiplication.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = trace_info.length() / (COEFF_DEGREE * PIPELINE_GROUP);
+        let modulus = pub_inputs
+            .modulus
+            .expect("pipeline proofs record their moduli");
+        let steps = pub_inputs.weights.unwrap_or_default();
+        let reductions =
+            pipeline_reductions(&modulus, &steps).expect("pipeline moduli and steps were checked");
+        let scaling =
+            pipeline_scaling(&steps, &modulus).expect("pipeline moduli and steps were checked");
+        let (degree, cycles) = step_value_degree(&steps, slots);
+        let mut degrees = reductions.degrees(degree, &cycles);
+        let linked = TransitionConstraintDegree::with_cycles(1, vec![slots * PIPELINE_GROUP]);
+        degrees.extend(vec![linked.clone(); RESULT_LEN]);
+        if steps.contains(&PipelineStep::Mul.code()) {
+            degrees.extend(vec![linked; COEFF_LEVEL]);
+        }
+        let levels = if rescales(&steps) {
+            KEPT_LEVEL_NUM
+        } else {
+            COEFF_LEVEL
+        };
+        let num_assertions = COEFF_LEVEL + REGISTER_NUM * levels + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        PipelineAir {
+            context: reductions.boundary().apply(context),
+            constants: StepConstants::new(&modulus, scaling.as_ref()),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            steps,
+            slots,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let flags = &periodic_values[self.reductions.num_periodic_columns()..];
+        let (selectors, link) = (&flags[..SELECTOR_NUM], flags[SELECTOR_NUM]);
+        let (reduction, rest) = result.split_at_mut(self.reductions.num_constraints());
+        let values = step_values(frame.current(), selectors, &self.constants);
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        let (current, next) = (frame.current(), frame.next());
+        let (links, empty) = rest.split_at_mut(RESULT_LEN);
+        for t in 0..REGISTER_NUM {
+            for l in 0..COEFF_LEVEL {
+                links[t * COEFF_LEVEL + l] =
+                    link * (next[register_column(t, l)] - current[result_column(t, l)]);
+            }
+        }
+        if self.multiplies() {
+            let mul = selectors[PipelineStep::Mul.selector()];
+            for l in 0..COEFF_LEVEL {
+                empty[l] = mul * current[register_column(REGISTER_NUM - 1, l)];
+            }
+        }
+    }
+
+    // The selectors of the circuit rows of one coefficient over their groups, then the flags of
+    // the links.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let codes = step_codes(&self.steps, self.slots);
+        let mut selectors = vec![vec![BaseElement::ZERO; self.slots]; SELECTOR_NUM];
+        for (i, &code) in codes.iter().enumerate() {
+            if let Some(step) = PipelineStep::from_code(code) {
+                selectors[step.selector()][i] = BaseElement::ONE;
+            }
+        }
+        let mut columns = self.reductions.periodic_columns();
+        columns.extend(
+            selectors
+                .iter()
+                .map(|column| self.reductions.stretch(column)),
+        );
+        columns.push(link_column(self.slots, PIPELINE_GROUP));
+        columns
+    }
+
+    // The last register component is zero in the first row of every coefficient, the results
+    // are bound in the last, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let cycle = self.slots * PIPELINE_GROUP;
+        let mut assertions = Vec::with_capacity(self.context.num_assertions());
+        for l in 0..COEFF_LEVEL {
+            assertions.push(Assertion::periodic(
+                register_column(REGISTER_NUM - 1, l),
+                0,
+                cycle,
+                BaseElement::ZERO,
+            ));
+        }
+        let levels = if rescales(&self.steps) {
+            KEPT_LEVEL_NUM
+        } else {
+            COEFF_LEVEL
+        };
+        for (t, values) in self.result.iter().enumerate() {
+            for l in 0..levels {
+                assertions.push(Assertion::sequence(
+                    result_column(t, l),
+                    (self.slots - 1) * PIPELINE_GROUP,
+                    cycle,
+                    values[l].clone(),
+                ));
+            }
+        }
+        assertions.extend(bind_moduli(&self.modulus, 0, cycle));
+        assertions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::element_from_u128;
+
+    const MODULUS: u64 = 65537;
+
+    #[test]
+    fn programs_are_checked() {
+        let (add, mul, rescale) = (1, 3, 4);
+        assert!(check_program(&[], None).is_err());
+        assert!(check_program(&[add; PIPELINE_MAX_STEPS + 1], None).is_err());
+        assert!(check_program(&[add, 5], None).is_err());
+        assert!(check_program(&[mul, add, mul], None).is_err());
+        assert!(check_program(&[rescale, add], None).is_err());
+        assert!(check_program(&[add, mul, add], Some(COEFF_DEGREE * 4 * PIPELINE_GROUP)).is_ok());
+        assert!(check_program(&[add, mul, add], Some(COEFF_DEGREE * 8 * PIPELINE_GROUP)).is_err());
+        assert!(check_program(&[add, mul, add], Some(COEFF_DEGREE * 4)).is_err());
+        assert_eq!(
+            check_program(&[add, mul, rescale], None).is_ok(),
+            KEPT_LEVEL_NUM != 0
+        );
+    }
+
+    // every kind of step reduces the values of its row under its own selector only
+    #[test]
+    fn steps_satisfy_their_row() {
+        let modulus: Vec<u64> = (0..COEFF_LEVEL).map(|l| [MODULUS, 40961][l]).collect();
+        let mut x = [[0; COEFF_LEVEL]; REGISTER_NUM];
+        for t in 0..VALUE_NUM {
+            x[t] = std::array::from_fn(|l| (12345 + 777 * t as u64) % modulus[l]);
+        }
+        let b: [[u64; COEFF_LEVEL]; VALUE_NUM] =
+            std::array::from_fn(|v| std::array::from_fn(|l| (54321 + 999 * v as u64) % modulus[l]));
+        let mut kinds = vec![
+            None,
+            Some(PipelineStep::Add),
+            Some(PipelineStep::Sub),
+            Some(PipelineStep::Mul),
+        ];
+        let scaling = (KEPT_LEVEL_NUM != 0).then(|| Scaling::new(&modulus).unwrap());
+        if scaling.is_some() {
+            kinds.push(Some(PipelineStep::Rescale));
+        }
+        let constants = StepConstants::new(&modulus, scaling.as_ref());
+        for step in kinds {
+            let witness = pipeline_step(step, &x, &b, &modulus, scaling.as_ref());
+            let mut row = vec![BaseElement::ZERO; OPERAND_END];
+            for l in 0..COEFF_LEVEL {
+                row[l] = element_from_u64(modulus[l]);
+            }
+            for t in 0..REGISTER_NUM {
+                row[rounded_column(t)] = element_from_u64(witness.rounded[t]);
+                for l in 0..COEFF_LEVEL {
+                    row[register_column(t, l)] = element_from_u64(x[t][l]);
+                    row[result_column(t, l)] = element_from_u64(witness.results[t][l]);
+                }
+            }
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    row[operand_column(v, l)] = element_from_u64(b[v][l]);
+                }
+            }
+            let mut selectors = [BaseElement::ZERO; SELECTOR_NUM];
+            if let Some(step) = step {
+                selectors[step.selector()] = BaseElement::ONE;
+            }
+            let values = step_values(&row, &selectors, &constants);
+            for (j, &value) in witness.values.iter().enumerate() {
+                assert_eq!(
+                    values[j],
+                    element_from_u128(value),
+                    "{:?} value {}",
+                    step,
+                    j
+                );
+            }
+            for t in 0..REGISTER_NUM {
+                for l in 0..COEFF_LEVEL {
+                    let value = witness.values[t * COEFF_LEVEL + l];
+                    assert_eq!(value % modulus[l] as u128, witness.results[t][l] as u128);
+                }
+            }
+        }
+    }
+}
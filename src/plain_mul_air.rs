@@ -0,0 +1,229 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Plaintext-ciphertext multiplication, e.g. plaintext weights times an encrypted input. For a
+// ciphertext c = (c0, c1) and a plaintext polynomial p the product is (c0 * p, c1 * p); with
+// both in NTT (evaluation) form the products are coefficient-wise, so every circuit row proves
+//
+//     r_v = c_v * p mod q_l
+//
+// for both components and every RNS level, with the Barrett blocks of reduction.rs, one product
+// per row of a row group, against the moduli of the public inputs. The plaintext stays in the
+// trace and is not part of the public inputs; unlike the tensor product, the result has two
+// components and needs no relinearization.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Ciphertext + Plaintext + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 P0 P1 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const PLAINTEXT_START: usize = DATA_START + VALUE_NUM * COEFF_LEVEL;
+const PLAINTEXT_END: usize = PLAINTEXT_START + COEFF_LEVEL;
+// products of residues are below m^2, so their quotients are below m
+const REDUCTION_WIDTH: usize =
+    reduction_width(MUL_MAX_MODULUS_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per product, then padding
+pub const PLAIN_MUL_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = PLAINTEXT_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of PlainMulAir in canonical order.
+pub fn plain_mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", PLAIN_MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as CiphertextData, with the plaintext operand in `Plaintext`, one vector per level.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlainMulData {
+    pub modulus: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+    pub plaintext: [Vec<u64>; COEFF_LEVEL],
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn plaintext_column(l: usize) -> usize {
+    PLAINTEXT_START + l
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, the product of component v at level l
+// being value v * COEFF_LEVEL + l of the row.
+pub fn plain_mul_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::PlainMul, modulus, MUL_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| (modulus[l] as u128 - 1).pow(2),
+        (RESULT_START..PLAINTEXT_END).collect(),
+        PLAINTEXT_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_plain_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: PlainMulData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+
+    let ciphertext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.values)?;
+    let plaintext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, [data.plaintext])?;
+    let reductions =
+        plain_mul_reductions(ciphertext.modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; PLAINTEXT_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    plaintext.write_columns(&mut columns, |_, l| plaintext_column(l));
+    let product = |k: usize, v: usize, l: usize| {
+        ciphertext.coeff(v, l, k) as u128 * plaintext.coeff(0, l, k) as u128
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = ciphertext.modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                columns[result_column(v, l)][k] =
+                    element_from_u64((product(k, v, l) % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        product(k, j / COEFF_LEVEL, j % COEFF_LEVEL)
+    }))
+}
+
+pub fn get_plain_mul_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, PLAIN_MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The products of one row, component v at level l at v * COEFF_LEVEL + l.
+fn row_products<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    let mut products = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            products[v * COEFF_LEVEL + l] = row[data_column(v, l)] * row[plaintext_column(l)];
+        }
+    }
+    products
+}
+
+pub struct PlainMulAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for PlainMulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in MulAir, the products are quadratic and selected by a periodic column; the moduli
+    // were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("plain-mul proofs record their moduli");
+        let reductions = plain_mul_reductions(&modulus).expect("plain-mul moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        PlainMulAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let products = row_products(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &products, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
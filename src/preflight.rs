@@ -0,0 +1,243 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Preflight: the checks of a proof container that need no cryptographic verification, for
+// ingestion services which reject malformed or unwanted proofs right away and leave the STARK
+// verification to asynchronous workers. In order:
+//
+//     size        container size against the policy limit
+//     container   sealed header (magic, version), TOML structure
+//     header      known circuit, supported circuit version and recorded options, result shape,
+//                 the fields the circuit records (rotation, key digest, scales, moduli, scalar)
+//     signature   the proof header: field modulus and trace shape of the circuit
+//     policy      accepted circuits and minimum conjectured security
+//
+// A container passing preflight can still fail verification; one failing it would never pass.
+// Failures of the size, signature and policy checks are ParamsRejected, malformed containers
+// BadFormat, see VerificationFailure.
+
+use serde::Deserialize;
+use winter_math::StarkField;
+use winter_prover::StarkProof;
+use x25519_dalek::StaticSecret;
+
+use crate::air::{
+    check_coeff_degree, check_coeff_level, fresh_width_accepted, BaseElement, Data, Op,
+    PublicInputs, COEFF_DEGREE, COEFF_LEVEL,
+};
+use crate::cost::TraceShape;
+use crate::registry;
+use crate::verifying::{decode_data, evaluation_key_digits, parse_data, VerificationFailure};
+
+// Which proofs a verifier accepts, read from a TOML file.
+#[derive(Debug, Default, Deserialize)]
+pub struct VerifyPolicy {
+    // circuit identifiers or `--op` names; every registered circuit when missing
+    #[serde(default)]
+    pub circuits: Option<Vec<String>>,
+    // minimum conjectured security of the proof
+    #[serde(default)]
+    pub min_security_bits: Option<u32>,
+    // largest accepted container, sealed or not
+    #[serde(default)]
+    pub max_container_kb: Option<usize>,
+}
+
+impl VerifyPolicy {
+    pub fn load(path: &str) -> Result<VerifyPolicy, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {}", path, err))?;
+        let policy: VerifyPolicy =
+            toml::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))?;
+        for circuit in policy.circuits.iter().flatten() {
+            if registry::lookup(circuit).is_none() && Op::from_name(circuit).is_none() {
+                return Err(format!("{}: unknown circuit {}", path, circuit));
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn check_size(&self, bytes: usize) -> Result<(), String> {
+        match self.max_container_kb {
+            Some(limit) if bytes > limit * 1024 => Err(format!(
+                "container has {} bytes, the policy allows {} KiB",
+                bytes, limit
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check(&self, op: Op, proof: &StarkProof) -> Result<(), String> {
+        self.check_circuit(op)?;
+        self.check_security(proof)
+    }
+
+    pub fn check_circuit(&self, op: Op) -> Result<(), String> {
+        if let Some(circuits) = &self.circuits {
+            let info = op.info();
+            if !circuits.iter().any(|c| c == info.id || c == op.name()) {
+                return Err(format!("circuit {} is not accepted by the policy", info.id));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_security(&self, proof: &StarkProof) -> Result<(), String> {
+        if let Some(min_bits) = self.min_security_bits {
+            let bits = proof.security_level(true);
+            if bits < min_bits {
+                return Err(format!(
+                    "proof has {} bits of security, the policy requires {}",
+                    bits, min_bits
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A container which passed preflight, decoded and ready for verification.
+pub struct Preflighted {
+    pub op: Op,
+    pub pub_inputs: PublicInputs,
+    pub proof: StarkProof,
+    pub evaluation_key_digest: Option<String>,
+    pub container_bytes: usize,
+}
+
+// Mod-switch and rescale results have no coefficients at the dropped level, decryptions and
+// encodings hold the plaintext at the first, base extensions their residues modulo the targets;
+// additions may be of any accepted level count and degree, which parse_data checks against the
+// trace length.
+pub fn check_result_shape(op: Op, data: &Data) -> Result<(), String> {
+    let dropped = matches!(
+        op,
+        Op::ModSwitch | Op::Rescale | Op::Decrypt | Op::CkksEncode | Op::BaseExt
+    )
+    .then_some(COEFF_LEVEL - 1);
+    let (level_num, degree) = match data.result.first() {
+        Some(levels) if op == Op::Add => {
+            check_coeff_level(levels.len())?;
+            check_coeff_degree(levels[0].len())?;
+            (levels.len(), levels[0].len())
+        }
+        _ => (COEFF_LEVEL, COEFF_DEGREE),
+    };
+    for (v, levels) in data.result.iter().enumerate() {
+        if levels.len() != level_num {
+            return Err(format!(
+                "result component {} has {} levels, expected {}",
+                v,
+                levels.len(),
+                level_num
+            ));
+        }
+        for (l, values) in levels.iter().enumerate() {
+            let expected = match (Some(l) == dropped, &data.window) {
+                (true, _) => 0,
+                (false, Some(window)) => window.len(),
+                (false, None) => degree,
+            };
+            if values.len() != expected {
+                return Err(format!(
+                    "result component {} level {} has {} coefficients, expected {}",
+                    v,
+                    l,
+                    values.len(),
+                    expected
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_key_digest(op: Op, digest: Option<&str>) -> Result<(), String> {
+    match (evaluation_key_digits(op), digest) {
+        (Some(_), Some(digest))
+            if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            Ok(())
+        }
+        (Some(_), Some(digest)) => Err(format!("malformed evaluation key digest {}", digest)),
+        (Some(_), None) => Err(String::from(
+            "proof does not record the digest of its evaluation key",
+        )),
+        (None, Some(_)) => Err(format!(
+            "{} proofs take no evaluation key, but one is recorded",
+            op.name()
+        )),
+        (None, None) => Ok(()),
+    }
+}
+
+// The proof header must describe a proof of `op`, whose traces have `shape`, over this build's
+// field.
+pub fn check_signature(op: Op, shape: &TraceShape, proof: &StarkProof) -> Result<(), String> {
+    let modulus = BaseElement::MODULUS.to_le_bytes();
+    if proof.context.field_modulus_bytes() != modulus {
+        return Err(String::from("proof is over a different field"));
+    }
+    let layout = proof.trace_layout();
+    let width = layout.main_trace_width() + layout.aux_trace_width();
+    // sum, inner product, matrix-vector and aggregation traces grow with the number of
+    // operands, polynomial evaluations with the degree, pipelines with the number of steps and
+    // additions with the ring degree, their length is checked by parse_data; addition traces
+    // also widen with the number of operands and the coefficients per row
+    let width_fits = match op {
+        Op::Add => fresh_width_accepted(width),
+        _ => width == shape.width,
+    };
+    let length_fits = matches!(
+        op,
+        Op::Add
+            | Op::Sum
+            | Op::InnerProduct
+            | Op::MatVec
+            | Op::Horner
+            | Op::Pipeline
+            | Op::Aggregate
+    ) || proof.trace_length() == shape.length;
+    if !width_fits || !length_fits {
+        return Err(format!(
+            "proof trace is {} x {}, {} traces are {} x {}",
+            width,
+            proof.trace_length(),
+            op.info().id,
+            shape.width,
+            shape.length
+        ));
+    }
+    Ok(())
+}
+
+// Runs every preflight check on the container `bytes`, named `name` in error messages; sealed
+// containers are opened with `key`.
+pub fn preflight(
+    bytes: Vec<u8>,
+    key: Option<&StaticSecret>,
+    policy: &VerifyPolicy,
+    name: &str,
+) -> Result<Preflighted, VerificationFailure> {
+    use VerificationFailure::{BadFormat, ParamsRejected};
+    let container_bytes = bytes.len();
+    policy.check_size(container_bytes).map_err(ParamsRejected)?;
+    let data = decode_data(bytes, key, name)?;
+    if let Some(op) = Op::from_name(&data.circuit) {
+        check_result_shape(op, &data).map_err(BadFormat)?;
+        check_key_digest(op, data.evaluation_key_digest.as_deref()).map_err(BadFormat)?;
+    }
+    let evaluation_key_digest = data.evaluation_key_digest.clone();
+    let (op, pub_inputs, proof) = parse_data(data)?;
+    check_signature(op, &TraceShape::of(op), &proof).map_err(ParamsRejected)?;
+    policy.check(op, &proof).map_err(ParamsRejected)?;
+    Ok(Preflighted {
+        op,
+        pub_inputs,
+        proof,
+        evaluation_key_digest,
+        container_bytes,
+    })
+}
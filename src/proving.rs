@@ -0,0 +1,1409 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::path::Path;
+use std::time::Instant;
+
+use clap::{ArgEnum, Args};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use winter_air::{Air, FieldExtension, HashFunction, ProofOptions};
+use winter_math::{log2, ExtensibleField};
+use winter_prover::{Matrix, Prover, StarkProof, Trace};
+use x25519_dalek::PublicKey;
+
+use crate::aggregate_air::{get_aggregate_pub_inputs, AggregateAir};
+use crate::air::{
+    check_field_extension, BaseElement, FreshAir, InputArg, LoadConfig, Op, PublicInputs,
+    SchemeConfig, TraceType,
+};
+use crate::air::{fresh_width_accepted, get_pub_inputs, load_custom_data, to_data};
+use crate::archive::{write_archive, ProverArchive, RunRecord};
+use crate::base_ext_air::{get_base_ext_pub_inputs, BaseExtAir};
+use crate::blind_rotate_air::{get_blind_rotate_pub_inputs, BlindRotateAir};
+use crate::canonical::to_hex;
+use crate::ckks_encode_air::{get_ckks_encode_pub_inputs, CkksEncodeAir, CkksSlots};
+use crate::compare_air::{get_compare_pub_inputs, CompareAir};
+use crate::conjugate_air::{get_conjugate_pub_inputs, ConjugateAir};
+use crate::decrypt_air::{get_decrypt_pub_inputs, DecryptAir};
+use crate::decrypt_share_air::{get_decrypt_share_pub_inputs, DecryptShareAir};
+use crate::encrypt_air::{get_encrypt_pub_inputs, EncryptAir};
+use crate::error::StarkHeError;
+use crate::fma_air::{get_fma_pub_inputs, FmaAir};
+use crate::he_security::{check_he_security, HeSecurity};
+use crate::hoisted_rotate_air::{get_hoisted_rotate_pub_inputs, HoistedRotateAir};
+use crate::horner_air::{get_horner_pub_inputs, HornerAir};
+use crate::inner_product_air::{
+    get_inner_product_pub_inputs, result_key, result_text, InnerProductAir,
+};
+use crate::intt_air::{get_intt_pub_inputs, InttAir};
+use crate::key_conjugate_air::{get_key_conjugate_pub_inputs, KeyConjugateAir};
+use crate::key_rotate_air::{get_key_rotate_pub_inputs, KeyRotateAir};
+use crate::keyswitch_air::{get_keyswitch_pub_inputs, KeySwitchAir};
+use crate::lde_cache::{CachingProver, SharedLdeCache};
+#[cfg(feature = "goldilocks")]
+use crate::limb_mul_air::{get_limb_mul_pub_inputs, LimbMulAir};
+use crate::mat_vec_air::{get_mat_vec_pub_inputs, MatVecAir, MatVecTrace};
+use crate::modraise_air::{get_modraise_pub_inputs, ModRaiseAir};
+use crate::modswitch_air::{get_modswitch_pub_inputs, ModSwitchAir};
+use crate::mont_mul_air::{get_mont_mul_pub_inputs, MontMulAir};
+use crate::mul_air::{get_mul_pub_inputs, MulAir};
+use crate::multi_key_add_air::{get_multi_key_add_pub_inputs, MultiKeyAddAir};
+use crate::neg_air::{get_neg_pub_inputs, NegAir};
+use crate::ntt_air::{get_ntt_pub_inputs, NttAir, NttTrace};
+use crate::operation::{visit_operation, HeOperation, OperationVisitor};
+use crate::pack_air::{get_pack_pub_inputs, PackAir};
+use crate::params::{check_params, Profile, SecurityLevel};
+use crate::pipeline_air::{get_pipeline_pub_inputs, PipelineAir};
+use crate::plain_mul_air::{get_plain_mul_pub_inputs, PlainMulAir};
+use crate::re_encrypt_air::{get_re_encrypt_pub_inputs, ReEncryptAir};
+use crate::relin_air::{get_relin_pub_inputs, RelinAir};
+use crate::reproducibility::{ReproducibilityManifest, FIELD_NAME};
+use crate::rescale_air::{get_rescale_pub_inputs, RescaleAir};
+use crate::rotate_air::{get_rotate_pub_inputs, AutomorphismTrace, RotateAir};
+use crate::scalar_mul_air::{get_scalar_mul_pub_inputs, ScalarMulAir};
+use crate::scheme::SchemeProver;
+use crate::sealed::seal;
+use crate::status::{Phase, StatusReporter};
+use crate::storage::{FsStorage, Storage};
+use crate::sub_air::{get_sub_pub_inputs, SubAir};
+use crate::sum_air::{get_sum_pub_inputs, SumAir};
+use crate::unpack_air::{get_unpack_pub_inputs, UnpackAir};
+#[cfg(not(feature = "goldilocks"))]
+use crate::wide_mul_air::{get_wide_mul_pub_inputs, WideMulAir};
+use crate::window::{check_window, WindowedProver};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+pub enum EnumFieldExtension {
+    None,
+    Quadratic,
+    Cubic,
+}
+
+impl EnumFieldExtension {
+    pub fn field_extension(&self) -> FieldExtension {
+        match self {
+            EnumFieldExtension::None => FieldExtension::None,
+            EnumFieldExtension::Quadratic => FieldExtension::Quadratic,
+            EnumFieldExtension::Cubic => FieldExtension::Cubic,
+        }
+    }
+}
+
+// The --field-extension of the field of the build, MIN_FIELD_EXTENSION.
+#[cfg(not(feature = "goldilocks"))]
+const DEFAULT_FIELD_EXTENSION: &str = "none";
+#[cfg(feature = "goldilocks")]
+const DEFAULT_FIELD_EXTENSION: &str = "quadratic";
+
+// Named as on the command line in operation manifests too.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Serialize, Deserialize)]
+pub enum EnumHashFunction {
+    #[serde(rename = "blake3-192")]
+    BLAKE3_192,
+    #[serde(rename = "blake3-256")]
+    BLAKE3_256,
+    #[serde(rename = "sha3-256")]
+    SHA3_256,
+}
+
+impl EnumHashFunction {
+    pub fn hash_function(&self) -> HashFunction {
+        match self {
+            EnumHashFunction::BLAKE3_192 => HashFunction::Blake3_192,
+            EnumHashFunction::BLAKE3_256 => HashFunction::Blake3_256,
+            EnumHashFunction::SHA3_256 => HashFunction::Sha3_256,
+        }
+    }
+}
+
+// Proof options trade proof size against proving time at a given conjectured security, which
+// is about num_queries * log2(blowup_factor) + grinding_factor bits, capped by the field and the
+// hash function (see `stark-he estimate`). The bounds are those of Winterfell.
+#[derive(Args, Clone)]
+#[clap(next_help_heading = "PROOF OPTIONS")]
+pub struct ProofOptionsConfig {
+    /// Number of queries, 1 to 128: fewer queries make smaller proofs but need a larger blowup
+    /// factor or more grinding for the same security
+    #[clap(long, default_value_t = 42, value_parser = parse_num_queries)]
+    pub num_queries: usize,
+    /// Blowup factor of the low degree extension, a power of two from 2 to 128: every doubling
+    /// adds a bit of security per query but doubles the extended trace and the proving time;
+    /// it must be at least the degree of the circuit's constraints minus one, rounded up to a
+    /// power of two
+    #[clap(long, default_value_t = 4, value_parser = parse_blowup_factor)]
+    pub blowup_factor: usize,
+    /// Field of the constraint evaluations and of FRI, `none` for the base field or `quadratic`
+    /// (or `cubic`, where the base field has one) for its extension: the base field caps the
+    /// security at about its bits minus log2 of the LDE size, an extension multiplies them;
+    /// builds of the `goldilocks` feature take `quadratic` at least
+    #[clap(long, default_value = DEFAULT_FIELD_EXTENSION, value_parser = parse_field_extension)]
+    pub field_extension: EnumFieldExtension,
+    /// Hash function of the Merkle commitments and the random coin: BLAKE3 is the fastest to
+    /// prove and verify natively, SHA3-256 suits verifiers with a cheap SHA3; BLAKE3-192 makes
+    /// smaller proofs but caps the security at 96 bits
+    #[clap(long, arg_enum, default_value_t = EnumHashFunction::BLAKE3_256)]
+    pub hash_fn: EnumHashFunction,
+    /// Proof-of-work bits, 0 to 32, each adding a bit of security at twice the grinding time
+    #[clap(long, default_value_t = 16, value_parser = parse_grinding_factor)]
+    pub grinding_factor: u32,
+    /// FRI folding factor, 4, 8 or 16: larger factors make fewer FRI layers, each opening more
+    /// values per query
+    #[clap(long, default_value_t = 8, value_parser = parse_folding_factor)]
+    pub folding_factor: usize,
+    /// Largest FRI remainder, a power of two from 32 to 1024
+    #[clap(long, default_value_t = 256, value_parser = parse_fri_max_remainder_size)]
+    pub fri_max_remainder_size: usize,
+    /// Conjectured security in bits, from which the number of queries, the blowup factor, the
+    /// grinding bits and the field extension are derived instead
+    #[clap(
+        long,
+        arg_enum,
+        conflicts_with_all = &[
+            "num-queries",
+            "blowup-factor",
+            "field-extension",
+            "grinding-factor",
+        ]
+    )]
+    pub security_level: Option<SecurityLevel>,
+    /// Named proof options, setting the number of queries, the blowup factor, the grinding
+    /// bits, the hash function and the field extension together: `fast` for development,
+    /// `balanced` or `paranoid`; proofs record the profile of their options
+    #[clap(
+        long,
+        arg_enum,
+        conflicts_with_all = &[
+            "num-queries",
+            "blowup-factor",
+            "field-extension",
+            "hash-fn",
+            "grinding-factor",
+            "security-level",
+        ]
+    )]
+    pub profile: Option<Profile>,
+}
+
+fn parse_bounded(value: &str, min: usize, max: usize) -> Result<usize, String> {
+    let value: usize = value.parse().map_err(|err| format!("{}", err))?;
+    if !(min..=max).contains(&value) {
+        return Err(format!("{} is not in {}..={}", value, min, max));
+    }
+    Ok(value)
+}
+
+fn parse_power_of_two(value: &str, min: usize, max: usize) -> Result<usize, String> {
+    let value = parse_bounded(value, min, max)?;
+    if !value.is_power_of_two() {
+        return Err(format!("{} is not a power of two", value));
+    }
+    Ok(value)
+}
+
+fn parse_num_queries(value: &str) -> Result<usize, String> {
+    parse_bounded(value, 1, 128)
+}
+
+fn parse_blowup_factor(value: &str) -> Result<usize, String> {
+    parse_power_of_two(value, 2, 128)
+}
+
+fn parse_field_extension(value: &str) -> Result<EnumFieldExtension, String> {
+    let extension = EnumFieldExtension::from_str(value, true)
+        .map_err(|_| format!("{} is not none, quadratic or cubic", value))?;
+    let supported = match extension {
+        EnumFieldExtension::None => true,
+        EnumFieldExtension::Quadratic => <BaseElement as ExtensibleField<2>>::is_supported(),
+        EnumFieldExtension::Cubic => <BaseElement as ExtensibleField<3>>::is_supported(),
+    };
+    if !supported {
+        return Err(format!(
+            "the {} field has no {} extension",
+            FIELD_NAME, value
+        ));
+    }
+    check_field_extension(extension.field_extension())?;
+    Ok(extension)
+}
+
+fn parse_grinding_factor(value: &str) -> Result<u32, String> {
+    parse_bounded(value, 0, 32).map(|value| value as u32)
+}
+
+fn parse_folding_factor(value: &str) -> Result<usize, String> {
+    parse_power_of_two(value, 4, 16)
+}
+
+fn parse_fri_max_remainder_size(value: &str) -> Result<usize, String> {
+    parse_power_of_two(value, 32, 1024)
+}
+
+// The proof options of the command line; a --hash-fn weaker than the --security-level is an
+// InvalidInput.
+pub fn new_proof_options(opt: &ProofOptionsConfig) -> Result<ProofOptions, StarkHeError> {
+    if let Some(profile) = opt.profile {
+        return Ok(profile.proof_options(opt.folding_factor, opt.fri_max_remainder_size));
+    }
+    let hash_fn = opt.hash_fn.hash_function();
+    if let Some(level) = opt.security_level {
+        return level
+            .proof_options(hash_fn, opt.folding_factor, opt.fri_max_remainder_size)
+            .map_err(StarkHeError::InvalidInput);
+    }
+    Ok(ProofOptions::new(
+        opt.num_queries,
+        opt.blowup_factor,
+        opt.grinding_factor,
+        hash_fn,
+        opt.field_extension.field_extension(),
+        opt.folding_factor,
+        opt.fri_max_remainder_size,
+    ))
+}
+
+// The proof options of `input_args`: those of its parameter preset, which comes with its own,
+// or else `opt`.
+pub fn input_proof_options(
+    input_args: &InputArg,
+    opt: &ProofOptionsConfig,
+) -> Result<ProofOptions, StarkHeError> {
+    match input_args.params {
+        Some(params) => Ok(params.proof_options()),
+        None => new_proof_options(opt),
+    }
+}
+
+pub struct ProveOutput {
+    pub proof: StarkProof,
+    pub public_input: PublicInputs,
+    // main trace segment, kept for prover archives
+    pub trace: Option<Matrix<BaseElement>>,
+}
+
+// Proves `input_args`; with `lde_cache`, extended trace columns are shared with the other proofs
+// using the same cache, with `keep_trace` the output holds a copy of the main trace. Arguments
+// the circuit does not take are an InvalidInput, a failure of the prover a Proving error.
+pub fn prove(
+    input_args: &InputArg,
+    options: ProofOptions,
+    lde_cache: Option<&SharedLdeCache>,
+    keep_trace: bool,
+    status: &mut StatusReporter,
+) -> Result<ProveOutput, StarkHeError> {
+    // generate the execution trace
+    debug!(
+        "Generating proof for computing a test algorithm with input_args {:?} \n\
+        ---------------------",
+        input_args
+    );
+
+    check_arguments(input_args).map_err(|err| fail(status, err))?;
+    status.set_phase(Phase::BuildingTrace);
+    let output = visit_circuit(
+        input_args,
+        options,
+        TraceProver {
+            lde_cache,
+            keep_trace,
+            status: &mut *status,
+        },
+    );
+    output.map_err(|err| fail(status, err))
+}
+
+// Rejects arguments the circuit of `input_args` does not take.
+fn check_arguments(input_args: &InputArg) -> Result<(), StarkHeError> {
+    if let Some(window) = &input_args.expose {
+        check_window(input_args.op, window).map_err(StarkHeError::InvalidInput)?;
+    }
+    check_params(input_args.op, input_args.params).map_err(StarkHeError::InvalidInput)?;
+    // arguments of `add` proofs only, with what other proofs do instead
+    for (given, arg, instead) in [
+        (
+            input_args.coeffs_per_row.is_some(),
+            "--coeffs-per-row",
+            "hold one coefficient per row",
+        ),
+        (
+            input_args.he_security.is_some(),
+            "--he-security",
+            "are of the build's degree and moduli",
+        ),
+        (
+            !input_args.operands.is_empty(),
+            "--operand",
+            "read the data file",
+        ),
+    ] {
+        if given && input_args.op != Op::Add {
+            return Err(StarkHeError::InvalidInput(format!(
+                "{} applies to {} proofs, {} proofs {}",
+                arg,
+                Op::Add.name(),
+                input_args.op.name(),
+                instead
+            )));
+        }
+    }
+    input_args
+        .op
+        .check_field()
+        .map_err(StarkHeError::InvalidInput)?;
+    if input_args.op == Op::Add {
+        check_add_he_security(input_args)?;
+    }
+    if !input_args.scheme.supports(input_args.op) {
+        return Err(StarkHeError::InvalidInput(format!(
+            "{} proofs do not support {}",
+            input_args.op.name(),
+            input_args.scheme.name()
+        )));
+    }
+    Ok(())
+}
+
+// Reports `err` as the failure of the job of `status`.
+fn fail(status: &mut StatusReporter, err: StarkHeError) -> StarkHeError {
+    status.update(|s| s.message = Some(err.to_string()));
+    status.set_phase(Phase::Failed);
+    err
+}
+
+// Checks the ring degree of the `add` operands with their whole modulus chain, active or not,
+// against the HE standard: below `--he-security` the proof is refused, below 128 bits without
+// it only warned about.
+fn check_add_he_security(input_args: &InputArg) -> Result<(), StarkHeError> {
+    let data = load_custom_data(input_args)?;
+    let config = SchemeConfig::from_data(input_args, &data)?;
+    let level = input_args.he_security.unwrap_or(HeSecurity::Bits128);
+    if let Err(err) = check_he_security(config.degree, &data.modulus, level) {
+        match input_args.he_security {
+            Some(_) => return Err(StarkHeError::InvalidInput(err)),
+            None => eprintln!("warning: {}", err),
+        }
+    }
+    Ok(())
+}
+
+// Something done with the prover and the trace builder of a circuit, see `visit_circuit`. The
+// prover is an error when the parameters of the data file do not load, the trace when its
+// operands do not.
+pub trait CircuitVisitor {
+    type Output;
+
+    fn visit<P>(
+        self,
+        prover: Result<P, StarkHeError>,
+        build: fn(&InputArg) -> Result<P::Trace, StarkHeError>,
+        input_args: &InputArg,
+    ) -> Self::Output
+    where
+        P: Prover<BaseField = BaseElement>,
+        P::Air: Air<PublicInputs = PublicInputs>;
+}
+
+// Hands the prover and the trace builder of the circuit of `input_args` to `visitor`.
+pub fn visit_circuit<V: CircuitVisitor>(
+    input_args: &InputArg,
+    options: ProofOptions,
+    visitor: V,
+) -> V::Output {
+    visit_operation(
+        input_args.op,
+        CircuitOperation {
+            input_args,
+            options,
+            visitor,
+        },
+    )
+}
+
+struct CircuitOperation<'a, V> {
+    input_args: &'a InputArg,
+    options: ProofOptions,
+    visitor: V,
+}
+
+impl<V: CircuitVisitor> OperationVisitor for CircuitOperation<'_, V> {
+    type Output = V::Output;
+
+    fn visit<O: HeOperation>(self) -> V::Output {
+        let prover = O::prover(self.input_args, self.options);
+        self.visitor.visit(prover, O::build_trace, self.input_args)
+    }
+}
+
+struct TraceProver<'a> {
+    lde_cache: Option<&'a SharedLdeCache>,
+    keep_trace: bool,
+    status: &'a mut StatusReporter,
+}
+
+impl CircuitVisitor for TraceProver<'_> {
+    type Output = Result<ProveOutput, StarkHeError>;
+
+    fn visit<P>(
+        self,
+        prover: Result<P, StarkHeError>,
+        build: fn(&InputArg) -> Result<P::Trace, StarkHeError>,
+        input_args: &InputArg,
+    ) -> Result<ProveOutput, StarkHeError>
+    where
+        P: Prover<BaseField = BaseElement>,
+        P::Air: Air<PublicInputs = PublicInputs>,
+    {
+        let TraceProver {
+            lde_cache,
+            keep_trace,
+            status,
+        } = self;
+
+        // generate the execution trace
+        let prover = prover?;
+        let now = Instant::now();
+        let trace = build(input_args)?;
+        debug!(
+            "Generated execution trace of {} registers and 2^{} steps in {} ms",
+            trace.main_trace_width(),
+            log2(trace.length()),
+            now.elapsed().as_millis()
+        );
+        let prover = SchemeProver {
+            inner: WindowedProver {
+                inner: prover,
+                window: input_args.expose.clone(),
+            },
+            scheme: input_args.scheme,
+        };
+        prove_trace(input_args.op, prover, trace, lde_cache, keep_trace, status)
+    }
+}
+
+// Proves an already built `trace` of the circuit of `op` with `prover`, for callers which hold
+// the coefficients in their own structures and fill the trace columns directly instead of going
+// through a data file. The trace must have the layout of `op`, see `Op::layout`, or it is an
+// InvalidInput; nothing else of it is checked before proving, a trace which does not satisfy
+// the constraints is a Proving error. Wrap `prover` in a WindowedProver to window the results.
+pub fn prove_trace<P>(
+    op: Op,
+    prover: P,
+    trace: P::Trace,
+    lde_cache: Option<&SharedLdeCache>,
+    keep_trace: bool,
+    status: &mut StatusReporter,
+) -> Result<ProveOutput, StarkHeError>
+where
+    P: Prover<BaseField = BaseElement>,
+    P::Air: Air<PublicInputs = PublicInputs>,
+{
+    // additions widen with their operand count and packing
+    let width = trace.main_trace_width();
+    let state_width = match op {
+        Op::Add => fresh_width_accepted(width).then_some(width),
+        _ => op
+            .layout()
+            .into_iter()
+            .find(|(name, _)| *name == "state_width")
+            .map(|(_, width)| width),
+    };
+    if state_width != Some(width) {
+        return Err(StarkHeError::InvalidInput(format!(
+            "trace of {} columns does not have the {} layout",
+            width,
+            op.name()
+        )));
+    }
+
+    let prover = CachingProver {
+        inner: prover,
+        cache: lde_cache.cloned(),
+    };
+    let public_input = prover.get_pub_inputs(&trace);
+    let snapshot = keep_trace.then(|| trace.main_segment().clone());
+
+    // generate the proof
+    status.update(|s| {
+        s.trace_width = Some(trace.main_trace_width());
+        s.trace_length = Some(trace.length());
+    });
+    status.set_phase(Phase::Proving);
+    let proof = prover.prove(trace).map_err(|err| {
+        let err = StarkHeError::Proving(format!("failed to generate proof: {}", err));
+        fail(status, err)
+    })?;
+    Ok(ProveOutput {
+        proof,
+        public_input,
+        trace: snapshot,
+    })
+}
+
+pub struct FreshProver {
+    pub options: ProofOptions,
+    pub config: SchemeConfig,
+}
+
+// When implementing Prover trait we set the `Air` associated type to the AIR of the
+// computation we defined previously, and set the `Trace` associated type to `TraceTable`
+// struct as we don't need to define a custom trace for our computation.
+impl Prover for FreshProver {
+    type BaseField = BaseElement;
+    type Air = FreshAir;
+    type Trace = TraceType;
+
+    // Our public inputs consist of the first and last value in the execution trace.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_pub_inputs(trace, &self.config)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct SubProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for SubProver {
+    type BaseField = BaseElement;
+    type Air = SubAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_sub_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct NegProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for NegProver {
+    type BaseField = BaseElement;
+    type Air = NegAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_neg_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves with NttTrace, whose auxiliary segment holds the wiring of the butterfly stages.
+pub struct NttProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for NttProver {
+    type BaseField = BaseElement;
+    type Air = NttAir;
+    type Trace = NttTrace;
+
+    // The transformed ciphertext and the moduli the twiddles are derived from.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_ntt_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves the inverse NTT with the same trace type as NttProver.
+pub struct InttProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for InttProver {
+    type BaseField = BaseElement;
+    type Air = InttAir;
+    type Trace = NttTrace;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_intt_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves a decryption with the same trace type as NttProver; the plain modulus comes from the
+// data file.
+pub struct DecryptProver {
+    pub options: ProofOptions,
+    pub plain_modulus: u64,
+}
+
+impl Prover for DecryptProver {
+    type BaseField = BaseElement;
+    type Air = DecryptAir;
+    type Trace = NttTrace;
+
+    // The plaintext, the ciphertext and the public key, see DecryptAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_decrypt_pub_inputs(trace, self.plain_modulus)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct DecryptShareProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for DecryptShareProver {
+    type BaseField = BaseElement;
+    type Air = DecryptShareAir;
+    type Trace = NttTrace;
+
+    // The share, the ciphertext and the public key share, see DecryptShareAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_decrypt_share_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves an encryption with the same trace type as NttProver; the plain modulus comes from the
+// data file.
+pub struct EncryptProver {
+    pub options: ProofOptions,
+    pub plain_modulus: u64,
+}
+
+impl Prover for EncryptProver {
+    type BaseField = BaseElement;
+    type Air = EncryptAir;
+    type Trace = NttTrace;
+
+    // The ciphertext and the public key, see EncryptAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_encrypt_pub_inputs(trace, self.plain_modulus)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves an encoding with the same trace type as NttProver; the slot values come from the data
+// file.
+pub struct CkksEncodeProver {
+    pub options: ProofOptions,
+    pub slots: CkksSlots,
+}
+
+impl Prover for CkksEncodeProver {
+    type BaseField = BaseElement;
+    type Air = CkksEncodeAir;
+    type Trace = NttTrace;
+
+    // The plaintext, see CkksEncodeAir::get_assertions; the slot values fix the targets.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_ckks_encode_pub_inputs(trace, self.slots.clone())
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct MulProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for MulProver {
+    type BaseField = BaseElement;
+    type Air = MulAir;
+    type Trace = TraceType;
+
+    // Every result coefficient is a public input, see MulAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_mul_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct MontMulProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for MontMulProver {
+    type BaseField = BaseElement;
+    type Air = MontMulAir;
+    type Trace = TraceType;
+
+    // Every result coefficient is a public input, as for MulProver.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_mont_mul_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct WideMulProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for WideMulProver {
+    type BaseField = BaseElement;
+    #[cfg(not(feature = "goldilocks"))]
+    type Air = WideMulAir;
+    #[cfg(feature = "goldilocks")]
+    type Air = LimbMulAir;
+    type Trace = TraceType;
+
+    // Every result coefficient is a public input, composed from its limbs.
+    #[cfg(not(feature = "goldilocks"))]
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_wide_mul_pub_inputs(trace)
+    }
+
+    #[cfg(feature = "goldilocks")]
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_limb_mul_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct FmaProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for FmaProver {
+    type BaseField = BaseElement;
+    type Air = FmaAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_fma_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct SumProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for SumProver {
+    type BaseField = BaseElement;
+    type Air = SumAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_sum_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves an inner product; the weights come from the data file.
+pub struct InnerProductProver {
+    pub options: ProofOptions,
+    pub weights: Vec<u64>,
+}
+
+impl Prover for InnerProductProver {
+    type BaseField = BaseElement;
+    type Air = InnerProductAir;
+    type Trace = TraceType;
+
+    // The digest of the products and the weights, which are periodic columns.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_inner_product_pub_inputs(trace, self.weights.clone())
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct HornerProver {
+    pub options: ProofOptions,
+    pub coefficients: Vec<u64>,
+}
+
+impl Prover for HornerProver {
+    type BaseField = BaseElement;
+    type Air = HornerAir;
+    type Trace = TraceType;
+
+    // The results and the coefficients, which are periodic columns.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_horner_pub_inputs(trace, self.coefficients.clone())
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct PipelineProver {
+    pub options: ProofOptions,
+    pub steps: Vec<u64>,
+    pub scale: Option<f64>,
+}
+
+impl Prover for PipelineProver {
+    type BaseField = BaseElement;
+    type Air = PipelineAir;
+    type Trace = TraceType;
+
+    // The results, the step codes, which select the periodic selector columns, and the scales of
+    // a rescaling.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_pipeline_pub_inputs(trace, self.steps.clone(), self.scale)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct AggregateProver {
+    pub options: ProofOptions,
+    pub mean: Option<u64>,
+}
+
+impl Prover for AggregateProver {
+    type BaseField = BaseElement;
+    type Air = AggregateAir;
+    type Trace = TraceType;
+
+    // The sums, the scaled sums and the scalar if there is one.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_aggregate_pub_inputs(trace, self.mean)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct MatVecProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for MatVecProver {
+    type BaseField = BaseElement;
+    type Air = MatVecAir;
+    type Trace = MatVecTrace;
+
+    // The products and the matrix, whose weights are periodic columns.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_mat_vec_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves a base extension; the source and target moduli come from the data file.
+pub struct BaseExtProver {
+    pub options: ProofOptions,
+    pub modulus: Vec<u64>,
+}
+
+impl Prover for BaseExtProver {
+    type BaseField = BaseElement;
+    type Air = BaseExtAir;
+    type Trace = TraceType;
+
+    // The residues modulo the targets and the moduli, which the constraints use as constants.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_base_ext_pub_inputs(trace, &self.modulus)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct PlainMulProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for PlainMulProver {
+    type BaseField = BaseElement;
+    type Air = PlainMulAir;
+    type Trace = TraceType;
+
+    // The products; the plaintext is not a public input.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_plain_mul_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct RelinProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for RelinProver {
+    type BaseField = BaseElement;
+    type Air = RelinAir;
+    type Trace = TraceType;
+
+    // The results and the evaluation key, see RelinAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_relin_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct KeySwitchProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for KeySwitchProver {
+    type BaseField = BaseElement;
+    type Air = KeySwitchAir;
+    type Trace = TraceType;
+
+    // The results and the key-switching key, see KeySwitchAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_keyswitch_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct BlindRotateProver {
+    pub options: ProofOptions,
+    pub rotation: i32,
+}
+
+impl Prover for BlindRotateProver {
+    type BaseField = BaseElement;
+    type Air = BlindRotateAir;
+    type Trace = TraceType;
+
+    // The results, the rotation and the bootstrapping key, see BlindRotateAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_blind_rotate_pub_inputs(trace, self.rotation)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct ModSwitchProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for ModSwitchProver {
+    type BaseField = BaseElement;
+    type Air = ModSwitchAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_modswitch_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct ModRaiseProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for ModRaiseProver {
+    type BaseField = BaseElement;
+    type Air = ModRaiseAir;
+    type Trace = TraceType;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_modraise_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves a scalar multiplication; the scalar comes from the data file.
+pub struct ScalarMulProver {
+    pub options: ProofOptions,
+    pub scalar: u64,
+}
+
+impl Prover for ScalarMulProver {
+    type BaseField = BaseElement;
+    type Air = ScalarMulAir;
+    type Trace = TraceType;
+
+    // The products and the scalar, which the constraints use as a constant.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_scalar_mul_pub_inputs(trace, self.scalar)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct RescaleProver {
+    pub options: ProofOptions,
+    pub scale: f64,
+}
+
+impl Prover for RescaleProver {
+    type BaseField = BaseElement;
+    type Air = RescaleAir;
+    type Trace = TraceType;
+
+    // The results and the scales, the scale before is taken from the data file.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_rescale_pub_inputs(trace, self.scale)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct RotateProver {
+    pub options: ProofOptions,
+    pub rotation: i32,
+}
+
+impl Prover for RotateProver {
+    type BaseField = BaseElement;
+    type Air = RotateAir;
+    type Trace = AutomorphismTrace;
+
+    // The results, the input ciphertext, its moduli and the rotation, which is not stored in
+    // the trace.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_rotate_pub_inputs(trace, self.rotation)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct ConjugateProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for ConjugateProver {
+    type BaseField = BaseElement;
+    type Air = ConjugateAir;
+    type Trace = AutomorphismTrace;
+
+    // The results, the input ciphertext and its moduli; the Galois element of the conjugation is
+    // fixed.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_conjugate_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct PackProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for PackProver {
+    type BaseField = BaseElement;
+    type Air = PackAir;
+    type Trace = TraceType;
+
+    // The packed ciphertext and the moduli, from which the monomial factors are derived.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_pack_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct UnpackProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for UnpackProver {
+    type BaseField = BaseElement;
+    type Air = UnpackAir;
+    type Trace = TraceType;
+
+    // The unpacked ciphertexts and the moduli, as for PackProver.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_unpack_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct MultiKeyAddProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for MultiKeyAddProver {
+    type BaseField = BaseElement;
+    type Air = MultiKeyAddAir;
+    type Trace = TraceType;
+
+    // The sum, one component per party after c_0.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_multi_key_add_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct ReEncryptProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for ReEncryptProver {
+    type BaseField = BaseElement;
+    type Air = ReEncryptAir;
+    type Trace = TraceType;
+
+    // The results and the re-encryption key, see ReEncryptAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_re_encrypt_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct HoistedRotateProver {
+    pub options: ProofOptions,
+    pub rotation: i32,
+}
+
+impl Prover for HoistedRotateProver {
+    type BaseField = BaseElement;
+    type Air = HoistedRotateAir;
+    type Trace = TraceType;
+
+    // The rotated ciphertexts, the step and the Galois keys, see HoistedRotateAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_hoisted_rotate_pub_inputs(trace, self.rotation)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct CompareProver {
+    pub options: ProofOptions,
+    pub plain_modulus: u64,
+}
+
+impl Prover for CompareProver {
+    type BaseField = BaseElement;
+    type Air = CompareAir;
+    type Trace = TraceType;
+
+    // The sign of the difference and the plain modulus, see CompareAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_compare_pub_inputs(trace, self.plain_modulus)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct KeyRotateProver {
+    pub options: ProofOptions,
+    pub rotation: i32,
+}
+
+impl Prover for KeyRotateProver {
+    type BaseField = BaseElement;
+    type Air = KeyRotateAir;
+    type Trace = TraceType;
+
+    // The rotated ciphertext, the rotation and the automorphism key, see
+    // KeyRotateAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_key_rotate_pub_inputs(trace, self.rotation)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+pub struct KeyConjugateProver {
+    pub options: ProofOptions,
+}
+
+impl Prover for KeyConjugateProver {
+    type BaseField = BaseElement;
+    type Air = KeyConjugateAir;
+    type Trace = TraceType;
+
+    // The conjugated ciphertext and the automorphism key, see KeyRotateAir::get_assertions.
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        get_key_conjugate_pub_inputs(trace)
+    }
+
+    fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+}
+
+// Proves `input_args` and stores the proof container at `proof_file_path`, sealed to `recipient`
+// if given, reporting progress and proof statistics through `status`.
+pub fn prove_to_file(
+    input_args: &InputArg,
+    options: ProofOptions,
+    lde_cache: Option<&SharedLdeCache>,
+    recipient: Option<&PublicKey>,
+    archive: Option<&ProverArchive>,
+    proof_file_path: &Path,
+    status: &mut StatusReporter,
+) -> Result<(), StarkHeError> {
+    let (storage, key) = FsStorage::for_file(proof_file_path);
+    prove_to_storage(
+        input_args, options, lde_cache, recipient, archive, &storage, &key, status,
+    )
+}
+
+// Same as prove_to_file with the proof container, and the result of inner product proofs,
+// stored in `storage` under `key`.
+#[allow(clippy::too_many_arguments)]
+pub fn prove_to_storage(
+    input_args: &InputArg,
+    options: ProofOptions,
+    lde_cache: Option<&SharedLdeCache>,
+    recipient: Option<&PublicKey>,
+    archive: Option<&ProverArchive>,
+    storage: &dyn Storage,
+    key: &str,
+    status: &mut StatusReporter,
+) -> Result<(), StarkHeError> {
+    status.restart();
+
+    let mut manifest = ReproducibilityManifest::new(input_args.op, &options);
+    manifest.params = input_args.params.map(|params| String::from(params.name()));
+    let now = Instant::now();
+    let output = prove(input_args, options, lde_cache, archive.is_some(), status)?;
+    let proving_ms = now.elapsed().as_millis() as u64;
+    debug!(
+        "---------------------\nProof generated in {} ms",
+        proving_ms
+    );
+
+    let proof_bytes = output.proof.to_bytes();
+    status.update(|s| {
+        s.proving_ms = Some(proving_ms);
+        s.proof_size_bytes = Some(proof_bytes.len());
+        s.security_bits = Some(output.proof.security_level(true));
+    });
+    debug!("Proof size: {:.1} KB", proof_bytes.len() as f64 / 1024f64);
+    debug!("Proof security: {} bits", output.proof.security_level(true));
+    debug!(
+        "Public inputs commitment: {}",
+        to_hex(&output.public_input.commitment())
+    );
+
+    debug!(
+        "Circuit: {} {}, version {}",
+        manifest.circuit_id, manifest.circuit_semver, manifest.circuit_version
+    );
+
+    manifest.security_bits = Some(output.proof.security_level(true));
+    let public_inputs_commitment = output.public_input.commitment();
+    if output.public_input.result_digest.is_some() {
+        let result = result_key(key);
+        let text = result_text(&input_args.data_file_path, &output.public_input)
+            .map_err(|err| fail(status, err))?;
+        put(storage, &result, text.as_bytes()).map_err(|err| fail(status, err))?;
+        debug!("Result written to {}", result);
+    }
+    let mut data = to_data(input_args.op, proof_bytes.clone(), output.public_input);
+    data.reproducibility = Some(manifest.clone());
+    let text = toml::to_string(&data).expect("proof files serialize to TOML");
+    let container = match recipient {
+        Some(recipient) => seal(text.as_bytes(), recipient),
+        None => text.into_bytes(),
+    };
+    put(storage, key, &container).map_err(|err| fail(status, err))?;
+    debug!("Proof written to {}", key);
+
+    if let Some(archive) = archive {
+        let record = RunRecord {
+            input_args,
+            proof_file_path: Path::new(key),
+            proof_bytes: &proof_bytes,
+            public_inputs_commitment,
+            proving_ms,
+            trace: output
+                .trace
+                .as_ref()
+                .expect("the trace is kept for archives"),
+            reproducibility: manifest,
+        };
+        write_archive(archive, record).map_err(|err| {
+            let err = StarkHeError::Proving(format!("failed to write the prover archive: {}", err));
+            fail(status, err)
+        })?;
+        debug!("Prover archive written to {}", archive.dir.display());
+    }
+    status.set_phase(Phase::Proved);
+    Ok(())
+}
+
+fn put(storage: &dyn Storage, key: &str, bytes: &[u8]) -> Result<(), StarkHeError> {
+    storage
+        .put(key, bytes)
+        .map_err(|err| StarkHeError::Proving(format!("cannot write {}: {}", key, err)))
+}
@@ -0,0 +1,373 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Proxy re-encryption of a ciphertext (c0, c1) under the secret s_A of a delegator to the secret
+// s_B of a delegatee with a re-encryption key (rk0_i, rk1_i) = (-a_i * s_B + e_i + w^i * s_A, a_i)
+// for the gadget base w = 2^RE_ENCRYPT_DIGIT_BITS:
+//
+//     c1 = sum_i d_i * w^i,  0 <= d_i < w
+//     c0' = c0 + sum_i d_i * rk0_i,  c1' = sum_i d_i * rk1_i
+//
+// so that c0' + c1' * s_B = c0 + c1 * s_A + sum_i d_i * e_i. A proxy proves that it transformed
+// the ciphertext with the key it was given, without learning either secret. Unlike KeySwitchAir,
+// whose RNS digits are as large as the primes, c1 is decomposed with the gadget decomposition of
+// src/gadget.rs as in RelinAir, so the noise added by the proxy is bounded by the proof. As in
+// RelinAir every circuit row holds one coefficient of every level, and the sums are reduced by
+// the Barrett blocks of reduction.rs, one per row of a row group, against the moduli of the
+// public inputs.
+//
+// The key columns are asserted against the key, which has the evaluation key format and is
+// loaded by verifiers from a file; its digest is part of the public inputs. KeyRotateAir, which
+// shares the trace, also asserts the data columns against the public input ciphertext.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::gadget::{self, bit_elements, decomposition_degrees, evaluate_decomposition};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, read_group_result,
+    reduction_group, reduction_width, Reductions,
+};
+use crate::rotate_air::AutomorphismInput;
+
+// Modulus + Result + Data + Bits + Key + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11
+// B0_0..B0_49 B1_0..B1_49 K000 K001 K010 K011 K100 .. K411 K.. R.. S..
+pub const RE_ENCRYPT_DIGIT_BITS: usize = 10;
+#[cfg(not(feature = "micro"))]
+pub const RE_ENCRYPT_DIGIT_NUM: usize = 5;
+// moduli of 20 bits, so that the high bits of the decomposition are not constant
+#[cfg(feature = "micro")]
+pub const RE_ENCRYPT_DIGIT_NUM: usize = 2;
+const BIT_NUM: usize = RE_ENCRYPT_DIGIT_BITS * RE_ENCRYPT_DIGIT_NUM;
+// as in RelinAir, the decomposition is as wide as the moduli it is meant for
+pub const RE_ENCRYPT_MAX_MODULUS_BITS: u32 = BIT_NUM as u32;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const KEY_LEN: usize = RE_ENCRYPT_DIGIT_NUM * VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const BIT_START: usize = DATA_START + RESULT_LEN;
+const KEY_START: usize = BIT_START + BIT_NUM * COEFF_LEVEL;
+const KEY_END: usize = KEY_START + KEY_LEN;
+// a sum is below (1 + RE_ENCRYPT_DIGIT_NUM * (w - 1)) * m, so is its quotient
+pub const RE_ENCRYPT_REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(RE_ENCRYPT_DIGIT_NUM as u128 * ((1 << RE_ENCRYPT_DIGIT_BITS) - 1) + 1),
+    RE_ENCRYPT_MAX_MODULUS_BITS as usize,
+);
+// one row per result coefficient and level, then padding
+pub const RE_ENCRYPT_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = KEY_END + RE_ENCRYPT_REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+// binary bits and c1 recomposition, ahead of the reductions
+const DECOMPOSITION_CONSTRAINTS: usize = BIT_NUM * COEFF_LEVEL + COEFF_LEVEL;
+
+// Trace layout constants of ReEncryptAir in canonical order.
+pub fn re_encrypt_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("digit_bits", RE_ENCRYPT_DIGIT_BITS),
+        ("digit_num", RE_ENCRYPT_DIGIT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", RE_ENCRYPT_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn bit_column(l: usize, b: usize) -> usize {
+    BIT_START + l * BIT_NUM + b
+}
+
+fn key_column(i: usize, v: usize, l: usize) -> usize {
+    level_column(KEY_START + i * VALUE_NUM * COEFF_LEVEL, v, l)
+}
+
+// Re-encrypted components of one coefficient, before their reduction.
+pub fn re_encrypt(
+    c: [u64; VALUE_NUM],
+    key: [[u64; VALUE_NUM]; RE_ENCRYPT_DIGIT_NUM],
+) -> [u128; VALUE_NUM] {
+    let digits: [u64; RE_ENCRYPT_DIGIT_NUM] = gadget::decompose(c[1], RE_ENCRYPT_DIGIT_BITS);
+    std::array::from_fn(|v| {
+        let mut sum = if v == 0 { c[0] as u128 } else { 0 };
+        for i in 0..RE_ENCRYPT_DIGIT_NUM {
+            sum += digits[i] as u128 * key[i][v] as u128;
+        }
+        sum
+    })
+}
+
+// Bound of the re-encrypted components by the modulus `m`, which HoistedRotateAir shares.
+pub fn re_encrypt_bound(m: u64) -> u128 {
+    let digits = RE_ENCRYPT_DIGIT_NUM as u128 * ((1 << RE_ENCRYPT_DIGIT_BITS) - 1);
+    (m as u128 - 1) * (1 + digits)
+}
+
+// The reductions of a row by the public moduli `modulus`, component v at level l being value
+// v * COEFF_LEVEL + l of the row.
+pub fn re_encrypt_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::ReEncrypt, modulus, RE_ENCRYPT_MAX_MODULUS_BITS)?;
+    let mut copied: Vec<usize> = (RESULT_START..BIT_START).collect();
+    copied.extend(KEY_START..KEY_END);
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| re_encrypt_bound(modulus[l]),
+        copied,
+        KEY_END,
+        RE_ENCRYPT_REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_re_encrypt_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("re-encryption needs --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    re_encrypt_trace(&data, &key)
+}
+
+// Trace of the re-encryption of `data` with `key`, which KeyRotateAir shares.
+pub fn re_encrypt_trace(
+    data: &CiphertextData,
+    key: &EvaluationKey,
+) -> Result<TraceType, StarkHeError> {
+    if key.modulus != data.modulus {
+        return Err(StarkHeError::InvalidInput(String::from(
+            "key moduli differ from the data moduli",
+        )));
+    }
+    if key.values.len() != RE_ENCRYPT_DIGIT_NUM {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "key needs {} digits, found {}",
+            RE_ENCRYPT_DIGIT_NUM,
+            key.values.len()
+        )));
+    }
+    check_modulus_bits(&data.modulus, RE_ENCRYPT_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, STATE_LENGTH, &data.values)?;
+    for digit in key.values.iter() {
+        check_levels(&key.modulus, STATE_LENGTH, digit)?;
+    }
+    let reductions = re_encrypt_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; KEY_END];
+    let sums = |k: usize, l: usize| {
+        let c = [data.values[0][l][k], data.values[1][l][k]];
+        let row_key = std::array::from_fn(|i| std::array::from_fn(|v| key.values[i][v][l][k]));
+        re_encrypt(c, row_key)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for i in 0..RE_ENCRYPT_DIGIT_NUM {
+                for v in 0..VALUE_NUM {
+                    columns[key_column(i, v, l)][k] = element_from_u64(key.values[i][v][l][k]);
+                }
+            }
+            for (v, sum) in sums(k, l).into_iter().enumerate() {
+                columns[result_column(v, l)][k] = element_from_u64((sum % m as u128) as u64);
+                columns[data_column(v, l)][k] = element_from_u64(data.values[v][l][k]);
+            }
+            let bits = bit_elements(data.values[1][l][k], BIT_NUM);
+            for (b, bit) in bits.into_iter().enumerate() {
+                columns[bit_column(l, b)][k] = bit;
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        sums(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    }))
+}
+
+// Results and the re-encryption key, read back from the first row of every group.
+pub fn get_re_encrypt_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, RE_ENCRYPT_GROUP, result_column),
+        evaluation_key: Some(EvaluationKey::from_trace(
+            trace,
+            RE_ENCRYPT_DIGIT_NUM,
+            RE_ENCRYPT_GROUP,
+            key_column,
+        )),
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The ciphertext the re-encryption was applied to, read back from the first row of every group.
+pub fn read_re_encrypt_input(trace: &TraceType) -> AutomorphismInput {
+    AutomorphismInput::from_elements(read_group_result(
+        trace,
+        VALUE_NUM,
+        RE_ENCRYPT_GROUP,
+        data_column,
+    ))
+}
+
+// Decomposition constraints of one row into `result` and its re-encrypted sums, component v at
+// level l at v * COEFF_LEVEL + l.
+fn evaluate_row<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    result: &mut [E],
+) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    let mut idx = 0;
+    for l in 0..COEFF_LEVEL {
+        let mut digits = [E::ZERO; RE_ENCRYPT_DIGIT_NUM];
+        evaluate_decomposition(
+            &row[bit_column(l, 0)..bit_column(l, BIT_NUM)],
+            row[data_column(1, l)],
+            RE_ENCRYPT_DIGIT_BITS,
+            &mut digits,
+            &mut result[idx..idx + BIT_NUM + 1],
+        );
+        idx += BIT_NUM + 1;
+
+        for v in 0..VALUE_NUM {
+            let mut sum = if v == 0 {
+                row[data_column(0, l)]
+            } else {
+                E::ZERO
+            };
+            for i in 0..RE_ENCRYPT_DIGIT_NUM {
+                sum += digits[i] * row[key_column(i, v, l)];
+            }
+            sums[v * COEFF_LEVEL + l] = sum;
+        }
+    }
+    sums
+}
+
+pub struct ReEncryptAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    re_encryption_key: EvaluationKey,
+    input: Option<AutomorphismInput>,
+}
+
+impl Air for ReEncryptAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The decomposition holds on every row; the sums are quadratic and selected by a periodic
+    // column.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("re-encryption proofs record their moduli");
+        let reductions =
+            re_encrypt_reductions(&modulus).expect("re-encryption moduli were checked");
+        let mut degrees = Vec::with_capacity(DECOMPOSITION_CONSTRAINTS);
+        for _ in 0..COEFF_LEVEL {
+            degrees.extend(decomposition_degrees(
+                RE_ENCRYPT_DIGIT_BITS,
+                RE_ENCRYPT_DIGIT_NUM,
+            ));
+        }
+        degrees.extend(reductions.degrees(2, &[]));
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + KEY_LEN
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref())
+            + pub_inputs.input.as_ref().map_or(0, |_| RESULT_LEN);
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        ReEncryptAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            re_encryption_key: pub_inputs
+                .evaluation_key
+                .expect("re-encryption needs the re-encryption key"),
+            input: pub_inputs.input,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (decomposition, reduction) = result.split_at_mut(DECOMPOSITION_CONSTRAINTS);
+        let sums = evaluate_row(frame.current(), decomposition);
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, reduction);
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, key
+    // columns and the data columns of a public input on the first row of every group, the modulus
+    // columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_columns(self.re_encryption_key.columns(key_column)),
+        );
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        if let Some(input) = &self.input {
+            assertions.extend(self.reductions.bind_columns(input.columns(data_column)));
+        }
+        assertions
+    }
+}
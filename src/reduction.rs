@@ -0,0 +1,425 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Range-checked reductions of the circuits which reduce several values per row, like the three
+// terms of the tensor product of mul at every level. Every circuit row is repeated over a row
+// group of G rows, G being the number of reductions plus one rounded up to a power of two, and
+// row j of a group reduces value j of the circuit row to its result column with the Barrett
+// block of barrett.rs, shared by the rows of the group:
+//
+//     x_j = q * m_j + r_j,  0 <= r_j < m_j,  0 <= q < 2^quotient_bits
+//
+// Periodic columns select the value, the result and the modulus of every row of a group. The
+// moduli are public inputs, so no remainder is checked against a modulus the prover picks. The
+// last row of a group is padding, which reduces zero by the first modulus, so the last row of the
+// trace, which winterfell exempts from the transition constraints, holds no reduction. The
+// circuit columns are copied from row to row within a group, all but the constant ones, which
+// the circuits assert, so the circuit constraints hold on the current row alone; constraints
+// linking two circuit rows apply on the last row of a group. Results are read and bound on the
+// first row of every group.
+//
+// The trace reserves the columns of the widest block a circuit accepts. The block of a proof is
+// sized to the bits of its largest modulus and quotient, which follow from the public moduli;
+// the reserved columns it leaves stay zero and unconstrained, so the layout does not depend on
+// the moduli.
+
+use winter_air::{Assertion, EvaluationFrame, TransitionConstraintDegree};
+use winter_math::FieldElement;
+use winter_prover::TraceTable;
+
+use crate::air::{
+    element_from_u64, BaseElement, Boundary, Op, TraceType, COEFF_LEVEL, FIELD_BITS, MODULUS_NUM,
+};
+use crate::barrett::{Barrett, BARRETT_WIDE_MODULUS_BITS};
+use crate::ciphertext::check_modulus_bits;
+
+// Bits q * m + r may take: the field modulus is above 2^FIELD_BITS, so a reduction which fits holds
+// over the integers and r is the remainder of x. Over Goldilocks this leaves the products of two
+// residues to moduli of about 31 bits; wide-mul splits wider ones into limbs.
+pub const REDUCTION_MAX_BITS: usize = FIELD_BITS;
+
+// Rows per circuit row of a circuit reducing `count` values per row.
+pub const fn reduction_group(count: usize) -> usize {
+    (count + 1).next_power_of_two()
+}
+
+// Columns to reserve for blocks of quotients of `quotient_bits` bits and moduli of
+// `modulus_bits` bits.
+pub const fn reduction_width(quotient_bits: usize, modulus_bits: usize) -> usize {
+    quotient_bits + 2 * modulus_bits
+}
+
+pub const fn bit_length(value: u128) -> usize {
+    (u128::BITS - value.leading_zeros()) as usize
+}
+
+// The reductions of the rows of a circuit and the columns they copy along a group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reductions {
+    results: Vec<usize>,
+    moduli: Vec<u64>,
+    copied: Vec<usize>,
+    block: Barrett,
+}
+
+impl Reductions {
+    // Reductions of value j of a row to column `results[j]` modulo `moduli[j]`, for values of at
+    // most `bounds[j]`, with the block starting at column `start` and taking at most `reserve`
+    // columns; `copied` lists the circuit columns which are not constant.
+    pub fn new(
+        results: Vec<usize>,
+        moduli: Vec<u64>,
+        bounds: &[u128],
+        copied: Vec<usize>,
+        start: usize,
+        reserve: usize,
+    ) -> Result<Self, String> {
+        assert!(
+            !results.is_empty() && moduli.len() == results.len() && bounds.len() == results.len(),
+            "every reduction has a result column, a modulus and a bound"
+        );
+        let mut modulus_bits = 0;
+        let mut quotient = 0;
+        for (&m, &bound) in moduli.iter().zip(bounds) {
+            let bits = bit_length(m as u128);
+            if m < 2 || bits > BARRETT_WIDE_MODULUS_BITS {
+                return Err(format!(
+                    "modulus {} does not fit into {} bits",
+                    m, BARRETT_WIDE_MODULUS_BITS
+                ));
+            }
+            // remainders and slacks stay below m, a bit less than m for a power of two
+            modulus_bits = modulus_bits.max(bit_length(m as u128 - 1));
+            quotient = quotient.max(bound / m as u128);
+        }
+        let quotient_bits = bit_length(quotient);
+        if quotient_bits + modulus_bits > REDUCTION_MAX_BITS {
+            return Err(format!(
+                "reductions by moduli of {} bits with quotients of {} bits exceed {} bits",
+                modulus_bits, quotient_bits, REDUCTION_MAX_BITS
+            ));
+        }
+        let block = Barrett::with_modulus_bits(start, quotient_bits, modulus_bits);
+        if block.width() > reserve {
+            return Err(format!(
+                "reductions by moduli of {} bits with quotients of {} bits take {} columns, the \
+                 circuit reserves {}",
+                modulus_bits,
+                quotient_bits,
+                block.width(),
+                reserve
+            ));
+        }
+        Ok(Reductions {
+            results,
+            moduli,
+            copied,
+            block,
+        })
+    }
+
+    // Reductions of `components` result components at every level, component v at level l being
+    // value v * COEFF_LEVEL + l of a row, reduced to column `result(v, l)` modulo `modulus[l]`
+    // for values of at most `bound(v, l)`.
+    pub fn of_components(
+        modulus: &[u64],
+        components: usize,
+        result: impl Fn(usize, usize) -> usize,
+        bound: impl Fn(usize, usize) -> u128,
+        copied: Vec<usize>,
+        start: usize,
+        reserve: usize,
+    ) -> Result<Self, String> {
+        let mut results = Vec::with_capacity(components * COEFF_LEVEL);
+        let mut moduli = Vec::with_capacity(components * COEFF_LEVEL);
+        let mut bounds = Vec::with_capacity(components * COEFF_LEVEL);
+        for v in 0..components {
+            for l in 0..COEFF_LEVEL {
+                results.push(result(v, l));
+                moduli.push(modulus[l]);
+                bounds.push(bound(v, l));
+            }
+        }
+        Reductions::new(results, moduli, &bounds, copied, start, reserve)
+    }
+
+    pub fn count(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn group(&self) -> usize {
+        reduction_group(self.count())
+    }
+
+    pub fn block(&self) -> Barrett {
+        self.block
+    }
+
+    // The last row of the trace, the one winterfell exempts, is padding of the last group.
+    pub fn boundary(&self) -> Boundary {
+        Boundary::Padding(1)
+    }
+
+    // Modulus of row `i` of a group, the first one on the padding rows.
+    fn modulus(&self, i: usize) -> u64 {
+        self.moduli.get(i).copied().unwrap_or(self.moduli[0])
+    }
+
+    // A selector per value, the flag of the last row of a group and the modulus of every row.
+    pub fn num_periodic_columns(&self) -> usize {
+        self.count() + 2
+    }
+
+    pub fn periodic_columns(&self) -> Vec<Vec<BaseElement>> {
+        let group = self.group();
+        let flag = |row: usize| {
+            (0..group)
+                .map(|i| element_from_u64((i == row) as u64))
+                .collect::<Vec<_>>()
+        };
+        let mut columns: Vec<Vec<BaseElement>> = (0..self.count()).map(flag).collect();
+        columns.push(flag(group - 1));
+        columns.push(
+            (0..group)
+                .map(|i| element_from_u64(self.modulus(i)))
+                .collect(),
+        );
+        columns
+    }
+
+    // The flag of the last row of a group among the periodic values of the reductions, under
+    // which the constraints linking two circuit rows apply.
+    pub fn last<E: FieldElement>(&self, periodic_values: &[E]) -> E {
+        periodic_values[self.count()]
+    }
+
+    // A periodic column of the circuit stretched over the groups, every value repeated on the
+    // rows of its group.
+    pub fn stretch(&self, column: &[BaseElement]) -> Vec<BaseElement> {
+        column
+            .iter()
+            .flat_map(|&value| vec![value; self.group()])
+            .collect()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.copied.len() + self.block.num_constraints()
+    }
+
+    // Degrees of the constraints `evaluate` writes, for values of degree `value_degree` in the
+    // trace columns whose periodic factors, stretched, have the cycles `value_cycles`: copies
+    // and the remainder equations apply under periodic flags, the reduction of the selected
+    // value under its selector.
+    pub fn degrees(
+        &self,
+        value_degree: usize,
+        value_cycles: &[usize],
+    ) -> Vec<TransitionConstraintDegree> {
+        let grouped = TransitionConstraintDegree::with_cycles(1, vec![self.group()]);
+        let mut degrees = vec![grouped.clone(); self.copied.len()];
+        let mut cycles = vec![self.group()];
+        cycles.extend_from_slice(value_cycles);
+        degrees.push(TransitionConstraintDegree::with_cycles(
+            value_degree,
+            cycles,
+        ));
+        degrees.extend([grouped.clone(), grouped]);
+        degrees.extend(vec![TransitionConstraintDegree::new(2); self.block.width()]);
+        degrees
+    }
+
+    // Constraint values of the reductions on the current row of `frame`, with the periodic values
+    // of the reductions and the value of every reduction of the row in `values`, zero on a
+    // correct trace.
+    pub fn evaluate<E: FieldElement + From<BaseElement>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        values: &[E],
+        result: &mut [E],
+    ) {
+        let (current, next) = (frame.current(), frame.next());
+        let (selectors, flags) = periodic_values.split_at(self.count());
+        let (last, m) = (flags[0], flags[1]);
+        let (copies, reduction) = result.split_at_mut(self.copied.len());
+        for (constraint, &c) in copies.iter_mut().zip(self.copied.iter()) {
+            *constraint = (E::ONE - last) * (next[c] - current[c]);
+        }
+        let mut x = E::ZERO;
+        let mut r = E::ZERO;
+        for (j, &selector) in selectors.iter().enumerate() {
+            x += selector * values[j];
+            r += selector * current[self.results[j]];
+        }
+        self.block.evaluate(x, m, r, current, reduction);
+    }
+
+    // The trace of `width` columns of a circuit with `columns`, one value per circuit row: every
+    // row is repeated over its group, and row j of the group holds the block reducing
+    // `value(k, j)`, value j of circuit row k, to the result the circuit holds.
+    pub fn build_trace(
+        &self,
+        columns: Vec<Vec<BaseElement>>,
+        width: usize,
+        value: impl Fn(usize, usize) -> u128,
+    ) -> TraceType {
+        TraceTable::init(self.build_columns(columns, width, value))
+    }
+
+    // The columns of build_trace, for the circuits with an auxiliary segment which keep their
+    // main segment in a trace of their own.
+    pub fn build_columns(
+        &self,
+        columns: Vec<Vec<BaseElement>>,
+        width: usize,
+        value: impl Fn(usize, usize) -> u128,
+    ) -> Vec<Vec<BaseElement>> {
+        let length = columns[0].len();
+        let group = self.group();
+        let mut trace = vec![vec![BaseElement::ZERO; length * group]; width];
+        let mut block = vec![BaseElement::ZERO; self.block.end()];
+        for k in 0..length {
+            for i in 0..group {
+                let row = k * group + i;
+                for (c, column) in columns.iter().enumerate() {
+                    trace[c][row] = column[k];
+                }
+                if i < self.count() {
+                    let remainder = self.block.fill(value(k, i), self.moduli[i], &mut block);
+                    assert_eq!(
+                        element_from_u64(remainder),
+                        columns[self.results[i]][k],
+                        "reduction {} of row {} does not leave the result of the circuit",
+                        i,
+                        k
+                    );
+                } else {
+                    self.block.fill(0, self.moduli[0], &mut block);
+                }
+                for c in self.block.start..self.block.end() {
+                    trace[c][row] = block[c];
+                }
+            }
+        }
+        trace
+    }
+
+    // Binds the values of every column on the first row of every group, as bind_columns does for
+    // one row per coefficient; the copies carry them over the group.
+    pub fn bind_columns(
+        &self,
+        columns: Vec<(usize, Vec<BaseElement>)>,
+    ) -> Vec<Assertion<BaseElement>> {
+        columns
+            .into_iter()
+            .map(|(column, values)| Assertion::sequence(column, 0, self.group(), values))
+            .collect()
+    }
+
+    // Binds the values of every result column on the first row of every group or, with a
+    // window, those of the window, as bind_results does for one row per coefficient.
+    pub fn bind_results(
+        &self,
+        columns: Vec<(usize, Vec<BaseElement>)>,
+        window: Option<&[usize]>,
+    ) -> Vec<Assertion<BaseElement>> {
+        let group = self.group();
+        let mut assertions = Vec::new();
+        for (column, values) in columns {
+            match window {
+                Some(window) => {
+                    for (&k, &value) in window.iter().zip(values.iter()) {
+                        assertions.push(Assertion::single(column, k * group, value));
+                    }
+                }
+                None => assertions.extend(self.bind_columns(vec![(column, values)])),
+            }
+        }
+        assertions
+    }
+
+    // Binds the modulus columns to the public moduli on the rows bind_results binds the results
+    // on, as bind_moduli does for the rows of other assertions.
+    pub fn bind_result_moduli(
+        &self,
+        modulus: &[u64],
+        window: Option<&[usize]>,
+    ) -> Vec<Assertion<BaseElement>> {
+        match window {
+            Some(window) => window
+                .iter()
+                .flat_map(|&k| {
+                    modulus.iter().enumerate().map(move |(l, &m)| {
+                        Assertion::single(l, k * self.group(), element_from_u64(m))
+                    })
+                })
+                .collect(),
+            None => bind_moduli(modulus, 0, self.group()),
+        }
+    }
+}
+
+// Number of assertions Reductions::bind_results makes for `num_columns` result columns.
+pub fn grouped_result_assertion_num(num_columns: usize, window: Option<&[usize]>) -> usize {
+    num_columns * window.map_or(1, <[usize]>::len)
+}
+
+// Public moduli of `op`: one per level, of at most `bits` bits.
+pub fn check_public_moduli(op: Op, modulus: &[u64], bits: u32) -> Result<(), String> {
+    if modulus.len() != MODULUS_NUM {
+        return Err(format!(
+            "{} proofs reduce by {} moduli, {} are recorded",
+            op.name(),
+            MODULUS_NUM,
+            modulus.len()
+        ));
+    }
+    check_modulus_bits(modulus, bits)
+}
+
+// Binds the modulus columns of the circuits which start with them to the public moduli on row
+// `first_step` and every `stride` rows after it, rows the circuit binds other columns on: the
+// modulus columns are constant, so assertions on rows of their own would make boundary
+// constraints of degree zero. The moduli reach the constraints as periodic columns; the modulus
+// columns only carry them to the public inputs.
+pub fn bind_moduli(
+    modulus: &[u64],
+    first_step: usize,
+    stride: usize,
+) -> Vec<Assertion<BaseElement>> {
+    modulus
+        .iter()
+        .enumerate()
+        .map(|(l, &m)| Assertion::periodic(l, first_step, stride, element_from_u64(m)))
+        .collect()
+}
+
+pub const MODULUS_ASSERTION_NUM: usize = MODULUS_NUM;
+
+// Result components read back from the first row of every group of `group` rows, as read_result
+// reads them from every row.
+pub fn read_group_result(
+    trace: &TraceType,
+    num: usize,
+    group: usize,
+    column: impl Fn(usize, usize) -> usize,
+) -> Vec<Vec<Vec<BaseElement>>> {
+    (0..num)
+        .map(|r| {
+            (0..COEFF_LEVEL)
+                .map(|l| {
+                    trace
+                        .get_column(column(r, l))
+                        .iter()
+                        .step_by(group)
+                        .copied()
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,329 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Registry of the circuits this build can prove and verify. Every circuit has a stable
+// identifier and a semantic version recorded in proofs:
+//
+// - the major version changes whenever the constraints or the trace layout change in a way
+//   that makes old proofs unverifiable; it is part of the circuit version digest;
+// - the minor version changes for additions old verifiers can ignore, the patch version for
+//   changes which do not affect proofs at all.
+//
+// A build supports a circuit version if it has the same major version and a minor and patch
+// version which are not older, i.e. verifiers accept proofs of every older compatible release
+// but not proofs produced by a newer minor release.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::air::Op;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Version, String> {
+        let parts: Vec<&str> = text.trim().split('.').collect();
+        let numbers: Result<Vec<u32>, _> = parts.iter().map(|part| part.parse::<u32>()).collect();
+        match numbers {
+            Ok(numbers) if numbers.len() == 3 => {
+                Ok(Version::new(numbers[0], numbers[1], numbers[2]))
+            }
+            _ => Err(format!(
+                "invalid version {}, expected MAJOR.MINOR.PATCH",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+pub struct CircuitInfo {
+    pub op: Op,
+    pub id: &'static str,
+    pub version: Version,
+    pub summary: &'static str,
+}
+
+pub const CIRCUITS: [CircuitInfo; 38] = [
+    CircuitInfo {
+        op: Op::Add,
+        id: "stark-he/add",
+        version: Version::new(6, 0, 0),
+        summary: "sum of 2 to 16 ciphertexts minus the last, a + b - c by default",
+    },
+    CircuitInfo {
+        op: Op::Mul,
+        id: "stark-he/mul",
+        version: Version::new(3, 0, 0),
+        summary: "BFV tensor product of two ciphertexts in NTT form",
+    },
+    CircuitInfo {
+        op: Op::Relin,
+        id: "stark-he/relin",
+        version: Version::new(3, 0, 0),
+        summary: "relinearization of a tensor product with an evaluation key",
+    },
+    CircuitInfo {
+        op: Op::Rotate,
+        id: "stark-he/rotate",
+        version: Version::new(2, 0, 0),
+        summary: "slot rotation of a ciphertext by a Galois automorphism",
+    },
+    CircuitInfo {
+        op: Op::KeySwitch,
+        id: "stark-he/key-switch",
+        version: Version::new(4, 0, 0),
+        summary: "key switch of a ciphertext with an RNS gadget key-switching key",
+    },
+    CircuitInfo {
+        op: Op::ModSwitch,
+        id: "stark-he/mod-switch",
+        version: Version::new(3, 0, 0),
+        summary: "modulus switching of a ciphertext to the next lower RNS level",
+    },
+    CircuitInfo {
+        op: Op::Rescale,
+        id: "stark-he/rescale",
+        version: Version::new(3, 0, 0),
+        summary: "CKKS rescale of a ciphertext by its last RNS prime",
+    },
+    CircuitInfo {
+        op: Op::PlainMul,
+        id: "stark-he/plain-mul",
+        version: Version::new(3, 0, 0),
+        summary: "product of a ciphertext and a plaintext in NTT form",
+    },
+    CircuitInfo {
+        op: Op::Sub,
+        id: "stark-he/sub",
+        version: Version::new(2, 0, 0),
+        summary: "difference a - b of two ciphertexts",
+    },
+    CircuitInfo {
+        op: Op::Neg,
+        id: "stark-he/neg",
+        version: Version::new(2, 0, 0),
+        summary: "coefficient-wise negation of a ciphertext",
+    },
+    CircuitInfo {
+        op: Op::Ntt,
+        id: "stark-he/ntt",
+        version: Version::new(2, 0, 0),
+        summary: "forward negacyclic NTT of a ciphertext",
+    },
+    CircuitInfo {
+        op: Op::Intt,
+        id: "stark-he/intt",
+        version: Version::new(2, 0, 0),
+        summary: "inverse negacyclic NTT of a ciphertext, scaled by N^-1",
+    },
+    CircuitInfo {
+        op: Op::Decrypt,
+        id: "stark-he/decrypt",
+        version: Version::new(2, 0, 0),
+        summary: "BFV decryption under the secret key of a public key",
+    },
+    CircuitInfo {
+        op: Op::Encrypt,
+        id: "stark-he/encrypt",
+        version: Version::new(3, 0, 0),
+        summary: "well-formed BFV encryption under a public key",
+    },
+    CircuitInfo {
+        op: Op::CkksEncode,
+        id: "stark-he/ckks-encode",
+        version: Version::new(3, 0, 0),
+        summary: "CKKS encoding of fixed-point slot values at a power-of-two scale",
+    },
+    CircuitInfo {
+        op: Op::ModRaise,
+        id: "stark-he/mod-raise",
+        version: Version::new(3, 0, 0),
+        summary: "modulus raising of a ciphertext from the first RNS level to every level",
+    },
+    CircuitInfo {
+        op: Op::ScalarMul,
+        id: "stark-he/scalar-mul",
+        version: Version::new(3, 0, 0),
+        summary: "multiplication of a ciphertext by a public scalar",
+    },
+    CircuitInfo {
+        op: Op::Fma,
+        id: "stark-he/fma",
+        version: Version::new(3, 0, 0),
+        summary: "tensor product of two ciphertexts added to an accumulator",
+    },
+    CircuitInfo {
+        op: Op::Sum,
+        id: "stark-he/sum",
+        version: Version::new(3, 0, 0),
+        summary: "sum of up to 256 ciphertexts",
+    },
+    CircuitInfo {
+        op: Op::InnerProduct,
+        id: "stark-he/inner-product",
+        version: Version::new(3, 0, 0),
+        summary: "inner product of ciphertexts with public weights, published as a digest",
+    },
+    CircuitInfo {
+        op: Op::MatVec,
+        id: "stark-he/mat-vec",
+        version: Version::new(3, 0, 0),
+        summary: "product of a public matrix with a vector of ciphertexts",
+    },
+    CircuitInfo {
+        op: Op::BaseExt,
+        id: "stark-he/base-ext",
+        version: Version::new(3, 0, 0),
+        summary: "fast base conversion of a ciphertext to another RNS base",
+    },
+    CircuitInfo {
+        op: Op::MontMul,
+        id: "stark-he/mont-mul",
+        version: Version::new(3, 0, 0),
+        summary: "BFV tensor product of two ciphertexts in NTT and Montgomery form",
+    },
+    CircuitInfo {
+        op: Op::WideMul,
+        id: "stark-he/wide-mul",
+        version: Version::new(3, 0, 0),
+        summary: "BFV tensor product of two ciphertexts with moduli of up to 63 bits",
+    },
+    CircuitInfo {
+        op: Op::BlindRotate,
+        id: "stark-he/blind-rotate",
+        version: Version::new(3, 0, 0),
+        summary: "CMux step of a TFHE blind rotation with a GGSW bootstrapping key",
+    },
+    CircuitInfo {
+        op: Op::Horner,
+        id: "stark-he/horner",
+        version: Version::new(3, 0, 0),
+        summary: "evaluation of a public polynomial on a ciphertext by Horner's rule",
+    },
+    CircuitInfo {
+        op: Op::Aggregate,
+        id: "stark-he/aggregate",
+        version: Version::new(3, 0, 0),
+        summary: "sum and scaled mean of a directory of ciphertexts",
+    },
+    CircuitInfo {
+        op: Op::DecryptShare,
+        id: "stark-he/decrypt-share",
+        version: Version::new(2, 0, 0),
+        summary: "threshold decryption share under a committed secret key share",
+    },
+    CircuitInfo {
+        op: Op::Conjugate,
+        id: "stark-he/conjugate",
+        version: Version::new(2, 0, 0),
+        summary: "complex conjugation of the slots of a CKKS ciphertext",
+    },
+    CircuitInfo {
+        op: Op::Pack,
+        id: "stark-he/pack",
+        version: Version::new(3, 0, 0),
+        summary: "packing of scalar ciphertexts into the coefficients of one ciphertext",
+    },
+    CircuitInfo {
+        op: Op::Unpack,
+        id: "stark-he/unpack",
+        version: Version::new(3, 0, 0),
+        summary: "unpacking of a packed ciphertext into one ciphertext per value",
+    },
+    CircuitInfo {
+        op: Op::MultiKeyAdd,
+        id: "stark-he/multi-key-add",
+        version: Version::new(2, 0, 0),
+        summary: "addition of multi-key ciphertexts under the keys of different parties",
+    },
+    CircuitInfo {
+        op: Op::ReEncrypt,
+        id: "stark-he/re-encrypt",
+        version: Version::new(3, 0, 0),
+        summary: "proxy re-encryption of a ciphertext with a gadget re-encryption key",
+    },
+    CircuitInfo {
+        op: Op::HoistedRotate,
+        id: "stark-he/hoisted-rotate",
+        version: Version::new(4, 0, 0),
+        summary: "batch of key-switched rotations sharing one gadget decomposition",
+    },
+    CircuitInfo {
+        op: Op::Compare,
+        id: "stark-he/compare",
+        version: Version::new(3, 0, 0),
+        summary: "less-than comparison of two BGV ciphertexts by sign extraction",
+    },
+    CircuitInfo {
+        op: Op::KeyRotate,
+        id: "stark-he/key-rotate",
+        version: Version::new(4, 0, 0),
+        summary: "key-switched slot rotation with an automorphism key",
+    },
+    CircuitInfo {
+        op: Op::KeyConjugate,
+        id: "stark-he/key-conjugate",
+        version: Version::new(4, 0, 0),
+        summary: "key-switched conjugation of CKKS slots with an automorphism key",
+    },
+    CircuitInfo {
+        op: Op::Pipeline,
+        id: "stark-he/pipeline",
+        version: Version::new(3, 0, 0),
+        summary: "sequence of additions, a multiplication and a rescaling in one trace",
+    },
+];
+
+pub fn circuit_info(op: Op) -> &'static CircuitInfo {
+    CIRCUITS
+        .iter()
+        .find(|info| info.op == op)
+        .expect("every operation is registered")
+}
+
+pub fn lookup(id: &str) -> Option<&'static CircuitInfo> {
+    CIRCUITS.iter().find(|info| info.id == id)
+}
+
+// Whether this build verifies proofs of circuit `id` at `version`, with the reason if it doesn't.
+pub fn check_support(id: &str, version: &Version) -> Result<&'static CircuitInfo, String> {
+    let info = lookup(id).ok_or_else(|| format!("unknown circuit {}", id))?;
+    if version.major != info.version.major {
+        return Err(format!(
+            "{} {} is incompatible with the supported version {}",
+            id, version, info.version
+        ));
+    }
+    match version.cmp(&info.version) {
+        Ordering::Greater => Err(format!(
+            "{} {} is newer than the supported version {}, upgrade the verifier",
+            id, version, info.version
+        )),
+        _ => Ok(info),
+    }
+}
+
+pub fn supports(id: &str, version: &Version) -> bool {
+    check_support(id, version).is_ok()
+}
@@ -0,0 +1,345 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Relinearization of a multiplication result (c0, c1, c2) back to two components with an
+// evaluation key (evk0_i, evk1_i) for the gadget base w = 2^RELIN_DIGIT_BITS:
+//
+//     c2 = sum_i d_i * w^i,  0 <= d_i < w
+//     c0' = c0 + sum_i d_i * evk0_i,  c1' = c1 + sum_i d_i * evk1_i
+//
+// c2 is decomposed with the gadget decomposition of src/gadget.rs, whose digits are linear
+// combinations of binary bit columns and so range checked for free. As in MulAir every circuit
+// row holds one coefficient of every level, and the sums are reduced by the Barrett blocks of
+// reduction.rs, one per row of a row group, against the moduli of the public inputs. The bits
+// are not copied along a group: the recomposition of the copied c2 fixes them on every row.
+//
+// The evaluation key columns are asserted against the key, which verifiers load from a file;
+// its digest is part of the public inputs, so a proof only verifies against the key it was
+// generated with.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::gadget::{self, bit_elements, decomposition_degrees, evaluate_decomposition};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::mul_air::MUL_RESULT_NUM;
+use crate::reduction::{
+    bit_length, check_public_moduli, grouped_result_assertion_num, read_group_result,
+    reduction_group, reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Bits + Key + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 C20 C21
+// B0_0..B0_49 B1_0..B1_49 K000 K001 K010 K011 K100 .. K411 K.. R.. S..
+pub const RELIN_DIGIT_BITS: usize = 10;
+pub const RELIN_DIGIT_NUM: usize = 5;
+const BIT_NUM: usize = RELIN_DIGIT_BITS * RELIN_DIGIT_NUM;
+// Every bit of c2 must vary over the trace (Winterfell checks constraint degrees in debug
+// builds), so the decomposition is exactly as wide as the 50-bit primes it is meant for.
+pub const RELIN_MAX_MODULUS_BITS: u32 = BIT_NUM as u32;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const KEY_LEN: usize = RELIN_DIGIT_NUM * VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const BIT_START: usize = DATA_START + DATA_LEN;
+const KEY_START: usize = BIT_START + BIT_NUM * COEFF_LEVEL;
+const KEY_END: usize = KEY_START + KEY_LEN;
+// a sum is below (1 + RELIN_DIGIT_NUM * (w - 1)) * m, so is its quotient
+const REDUCTION_WIDTH: usize = reduction_width(
+    bit_length(RELIN_DIGIT_NUM as u128 * ((1 << RELIN_DIGIT_BITS) - 1) + 1),
+    RELIN_MAX_MODULUS_BITS as usize,
+);
+// one row per result coefficient and level, then padding
+pub const RELIN_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = KEY_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+// binary bits and c2 recomposition, ahead of the reductions
+const DECOMPOSITION_CONSTRAINTS: usize = BIT_NUM * COEFF_LEVEL + COEFF_LEVEL;
+
+// Trace layout constants of RelinAir in canonical order.
+pub fn relin_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_RESULT_NUM),
+        ("value_num", VALUE_NUM),
+        ("digit_bits", RELIN_DIGIT_BITS),
+        ("digit_num", RELIN_DIGIT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", RELIN_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// A multiplication result: Values holds c0, c1 and c2.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RelinData {
+    pub modulus: Vec<u64>,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; MUL_RESULT_NUM],
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn data_column(c: usize, l: usize) -> usize {
+    level_column(DATA_START, c, l)
+}
+
+fn bit_column(l: usize, b: usize) -> usize {
+    BIT_START + l * BIT_NUM + b
+}
+
+fn key_column(i: usize, v: usize, l: usize) -> usize {
+    level_column(KEY_START + i * VALUE_NUM * COEFF_LEVEL, v, l)
+}
+
+// Base-w digits of `value`, least significant first.
+pub fn decompose(value: u64) -> [u64; RELIN_DIGIT_NUM] {
+    gadget::decompose(value, RELIN_DIGIT_BITS)
+}
+
+// Key-switched components of one coefficient, before their reduction.
+pub fn key_switch(
+    c: [u64; MUL_RESULT_NUM],
+    key: [[u64; VALUE_NUM]; RELIN_DIGIT_NUM],
+) -> [u128; VALUE_NUM] {
+    let digits = decompose(c[2]);
+    std::array::from_fn(|v| {
+        let mut sum = c[v] as u128;
+        for i in 0..RELIN_DIGIT_NUM {
+            sum += digits[i] as u128 * key[i][v] as u128;
+        }
+        sum
+    })
+}
+
+// The reductions of a row by the public moduli `modulus`, component v at level l being value
+// v * COEFF_LEVEL + l of the row.
+pub fn relin_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Relin, modulus, RELIN_MAX_MODULUS_BITS)?;
+    let digits = RELIN_DIGIT_NUM as u128 * ((1 << RELIN_DIGIT_BITS) - 1);
+    let mut copied: Vec<usize> = (RESULT_START..BIT_START).collect();
+    copied.extend(KEY_START..KEY_END);
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| (modulus[l] as u128 - 1) * (1 + digits),
+        copied,
+        KEY_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_relin_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: RelinData = load_data_file(&arg.data_file_path)?;
+    let key_path = arg.evaluation_key.as_deref().ok_or_else(|| {
+        StarkHeError::InvalidInput(String::from("relinearization needs --evaluation-key"))
+    })?;
+    let key = load_evaluation_key(key_path)?;
+    assert_eq!(
+        key.modulus, data.modulus,
+        "evaluation key moduli differ from the data moduli"
+    );
+    assert_eq!(
+        key.values.len(),
+        RELIN_DIGIT_NUM,
+        "evaluation key needs {} digits",
+        RELIN_DIGIT_NUM
+    );
+    check_modulus_bits(&data.modulus, RELIN_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+
+    let ciphertext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.values)?;
+    let keys = key
+        .values
+        .into_iter()
+        .map(|digit| Ciphertext::from_levels(&data.modulus, STATE_LENGTH, digit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reductions = relin_reductions(ciphertext.modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; KEY_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    for (i, digit) in keys.iter().enumerate() {
+        digit.write_columns(&mut columns, |v, l| key_column(i, v, l));
+    }
+    let sums = |k: usize, l: usize| {
+        let c = std::array::from_fn(|j| ciphertext.coeff(j, l, k));
+        let row_key = std::array::from_fn(|i| std::array::from_fn(|v| keys[i].coeff(v, l, k)));
+        key_switch(c, row_key)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = ciphertext.modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for (v, sum) in sums(k, l).into_iter().enumerate() {
+                columns[result_column(v, l)][k] = element_from_u64((sum % m as u128) as u64);
+            }
+            let bits = bit_elements(ciphertext.coeff(2, l, k), BIT_NUM);
+            for (b, bit) in bits.into_iter().enumerate() {
+                columns[bit_column(l, b)][k] = bit;
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        sums(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    }))
+}
+
+// Results and the evaluation key, read back from the first row of every group.
+pub fn get_relin_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, RELIN_GROUP, result_column),
+        evaluation_key: Some(EvaluationKey::from_trace(
+            trace,
+            RELIN_DIGIT_NUM,
+            RELIN_GROUP,
+            key_column,
+        )),
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// Decomposition constraints of one row into `result` and its key-switched sums, component v at
+// level l at v * COEFF_LEVEL + l.
+fn evaluate_row<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    result: &mut [E],
+) -> [E; RESULT_LEN] {
+    let mut sums = [E::ZERO; RESULT_LEN];
+    let mut idx = 0;
+    for l in 0..COEFF_LEVEL {
+        let mut digits = [E::ZERO; RELIN_DIGIT_NUM];
+        evaluate_decomposition(
+            &row[bit_column(l, 0)..bit_column(l, BIT_NUM)],
+            row[data_column(2, l)],
+            RELIN_DIGIT_BITS,
+            &mut digits,
+            &mut result[idx..idx + BIT_NUM + 1],
+        );
+        idx += BIT_NUM + 1;
+
+        for v in 0..VALUE_NUM {
+            let mut sum = row[data_column(v, l)];
+            for i in 0..RELIN_DIGIT_NUM {
+                sum += digits[i] * row[key_column(i, v, l)];
+            }
+            sums[v * COEFF_LEVEL + l] = sum;
+        }
+    }
+    sums
+}
+
+pub struct RelinAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    evaluation_key: EvaluationKey,
+}
+
+impl Air for RelinAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The decomposition holds on every row; the sums are quadratic and selected by a periodic
+    // column.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("relin proofs record their moduli");
+        let reductions = relin_reductions(&modulus).expect("relin moduli were checked");
+        let mut degrees = Vec::with_capacity(DECOMPOSITION_CONSTRAINTS);
+        for _ in 0..COEFF_LEVEL {
+            degrees.extend(decomposition_degrees(RELIN_DIGIT_BITS, RELIN_DIGIT_NUM));
+        }
+        degrees.extend(reductions.degrees(2, &[]));
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + KEY_LEN
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        RelinAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            evaluation_key: pub_inputs
+                .evaluation_key
+                .expect("relinearization needs the evaluation key"),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (decomposition, reduction) = result.split_at_mut(DECOMPOSITION_CONSTRAINTS);
+        let sums = evaluate_row(frame.current(), decomposition);
+        self.reductions
+            .evaluate(frame, periodic_values, &sums, reduction);
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, key
+    // columns on the first row of every group, the modulus columns to the public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_columns(self.evaluation_key.columns(key_column)),
+        );
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
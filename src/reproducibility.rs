@@ -0,0 +1,426 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Reproducibility manifest stored in proof containers: the build that produced the proof, the
+// circuit identifier, semantic version and trace layout it was proven against, the field, the
+// proof options and the conjectured security they achieve.
+//
+// The circuit version is the commitment to the canonical encoding of the circuit name, major
+// version, field modulus and layout constants (tag CIRCUIT_TAG). A verifier first checks the
+// semantic version against its registry, then recomputes the circuit version from its own
+// build and rejects proofs of another version with a "circuit mismatch" error, instead of
+// failing somewhere inside constraint evaluation. The field, hash function, field extension and
+// parameter preset are checked before that, ahead of decoding the proof bytes.
+
+use std::collections::BTreeMap;
+
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use winter_air::{FieldExtension, HashFunction, ProofOptions};
+use winter_math::StarkField;
+
+use crate::air::{check_field_extension, BaseElement, Op};
+use crate::canonical::{commitment, to_hex, CanonicalWriter, CIRCUIT_TAG};
+use crate::params::{check_params, Params, Profile};
+use crate::registry::{check_support, Version};
+
+pub use crate::air::FIELD_NAME;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofOptionsRecord {
+    pub num_queries: usize,
+    pub blowup_factor: usize,
+    pub grinding_factor: u32,
+    pub hash_fn: String,
+    pub field_extension: String,
+    pub folding_factor: usize,
+    pub fri_max_remainder_size: usize,
+}
+
+impl From<&ProofOptions> for ProofOptionsRecord {
+    fn from(options: &ProofOptions) -> Self {
+        let fri_options = options.to_fri_options();
+        ProofOptionsRecord {
+            num_queries: options.num_queries(),
+            blowup_factor: options.blowup_factor(),
+            grinding_factor: options.grinding_factor(),
+            hash_fn: format!("{:?}", options.hash_fn()),
+            field_extension: format!("{:?}", options.field_extension()),
+            folding_factor: fri_options.folding_factor(),
+            fri_max_remainder_size: fri_options.max_remainder_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReproducibilityManifest {
+    pub crate_version: String,
+    pub circuit: String,
+    // stable identifier and semantic version from the registry, missing in proofs written before
+    // the registry existed
+    #[serde(default)]
+    pub circuit_id: String,
+    #[serde(default)]
+    pub circuit_semver: String,
+    // major version of the circuit
+    pub circuit_revision: u32,
+    pub circuit_version: String,
+    pub field: String,
+    // decimal, TOML integers are limited to 64 bits
+    pub field_modulus: String,
+    // conjectured security in bits, missing in proofs written before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_bits: Option<u32>,
+    // the `--profile` of the proof options, if they are those of one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    // the `--params` preset of the moduli and ring degree, if the proof was generated with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<String>,
+    pub layout: BTreeMap<String, usize>,
+    pub proof_options: ProofOptionsRecord,
+}
+
+pub fn circuit_version(op: Op) -> String {
+    let mut writer = CanonicalWriter::new(CIRCUIT_TAG);
+    writer.write_str(op.name());
+    writer.write_u32(op.info().version.major);
+    let modulus = BaseElement::get_modulus_le_bytes();
+    writer.write_u32(modulus.len() as u32);
+    for byte in modulus {
+        writer.write_u8(byte);
+    }
+    let layout = op.layout();
+    writer.write_u32(layout.len() as u32);
+    for (name, value) in layout {
+        writer.write_str(name);
+        writer.write_u64(value as u64);
+    }
+    to_hex(&commitment(&writer.into_bytes()))
+}
+
+impl ReproducibilityManifest {
+    pub fn new(op: Op, options: &ProofOptions) -> Self {
+        ReproducibilityManifest {
+            crate_version: String::from(env!("CARGO_PKG_VERSION")),
+            circuit: String::from(op.name()),
+            circuit_id: String::from(op.info().id),
+            circuit_semver: op.info().version.to_string(),
+            circuit_revision: op.info().version.major,
+            circuit_version: circuit_version(op),
+            field: String::from(FIELD_NAME),
+            field_modulus: BaseElement::MODULUS.to_string(),
+            security_bits: None,
+            profile: Profile::of(options).map(|profile| String::from(profile.name())),
+            params: None,
+            layout: op
+                .layout()
+                .into_iter()
+                .map(|(name, value)| (String::from(name), value))
+                .collect(),
+            proof_options: options.into(),
+        }
+    }
+
+    // Checks that the proof is over the field of this build, with a hash function, field
+    // extension and parameter preset it knows, before its bytes are decoded.
+    pub fn check_encoding(&self, op: Op) -> Result<(), String> {
+        if self.field != FIELD_NAME || self.field_modulus != BaseElement::MODULUS.to_string() {
+            return Err(format!(
+                "proof is over the field {} of modulus {}, this verifier is over {} of modulus {}",
+                self.field,
+                self.field_modulus,
+                FIELD_NAME,
+                BaseElement::MODULUS
+            ));
+        }
+        let hash_fns = [
+            HashFunction::Blake3_192,
+            HashFunction::Blake3_256,
+            HashFunction::Sha3_256,
+        ]
+        .map(|hash_fn| format!("{:?}", hash_fn));
+        if !hash_fns.contains(&self.proof_options.hash_fn) {
+            return Err(format!(
+                "proof uses the hash function {}, this verifier supports {}",
+                self.proof_options.hash_fn,
+                hash_fns.join(", ")
+            ));
+        }
+        let field_extensions: Vec<String> = [
+            FieldExtension::None,
+            FieldExtension::Quadratic,
+            FieldExtension::Cubic,
+        ]
+        .into_iter()
+        .filter(|&extension| check_field_extension(extension).is_ok())
+        .map(|extension| format!("{:?}", extension))
+        .collect();
+        if !field_extensions.contains(&self.proof_options.field_extension) {
+            return Err(format!(
+                "proof uses the field extension {}, this verifier supports {}",
+                self.proof_options.field_extension,
+                field_extensions.join(", ")
+            ));
+        }
+        if let Some(name) = &self.params {
+            let params = Params::from_str(name, false)
+                .map_err(|_| format!("proof records the unknown parameter set {}", name))?;
+            check_params(op, Some(params))?;
+        }
+        Ok(())
+    }
+
+    // Checks that this build implements the circuit the proof was generated for.
+    pub fn check_circuit(&self, op: Op) -> Result<(), String> {
+        if !self.circuit_id.is_empty() {
+            if self.circuit_id != op.info().id {
+                return Err(format!(
+                    "circuit mismatch: proof records circuit {}, but its container says {}",
+                    self.circuit_id,
+                    op.info().id
+                ));
+            }
+            let semver = Version::parse(&self.circuit_semver)?;
+            check_support(&self.circuit_id, &semver)
+                .map_err(|err| format!("unsupported circuit: {}", err))?;
+        }
+        let expected_version = circuit_version(op);
+        if self.circuit == op.name()
+            && self.circuit_revision == op.info().version.major
+            && self.circuit_version == expected_version
+        {
+            return Ok(());
+        }
+        let mut details = Vec::new();
+        if self.circuit != op.name() {
+            details.push(format!(
+                "recorded circuit {} differs from container circuit {}",
+                self.circuit,
+                op.name()
+            ));
+        }
+        if self.circuit_revision != op.info().version.major {
+            details.push(format!(
+                "revision {} != {}",
+                self.circuit_revision,
+                op.info().version.major
+            ));
+        }
+        if self.field_modulus != BaseElement::MODULUS.to_string() {
+            details.push(format!("field {} != {}", self.field, FIELD_NAME));
+        }
+        for (name, value) in op.layout() {
+            match self.layout.get(name) {
+                Some(&recorded) if recorded == value => {}
+                Some(recorded) => details.push(format!("{} {} != {}", name, recorded, value)),
+                None => details.push(format!("{} missing", name)),
+            }
+        }
+        Err(format!(
+            "circuit mismatch: proof was generated by stark {} for {} circuit version {}, \
+            this verifier (stark {}) implements version {}{}",
+            self.crate_version,
+            self.circuit,
+            self.circuit_version,
+            env!("CARGO_PKG_VERSION"),
+            expected_version,
+            if details.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", details.join(", "))
+            }
+        ))
+    }
+
+    // Checks that the recorded security is the conjectured security `bits` of the proof.
+    pub fn check_security(&self, bits: u32) -> Result<(), String> {
+        match self.security_bits {
+            Some(recorded) if recorded != bits => Err(format!(
+                "reproducibility manifest records {} bits of security, the proof has {}",
+                recorded, bits
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Checks that the recorded profile is the one of the proof options `options`.
+    pub fn check_profile(&self, options: &ProofOptions) -> Result<(), String> {
+        let actual = Profile::of(options).map(|profile| profile.name());
+        match &self.profile {
+            Some(recorded) if Some(recorded.as_str()) != actual => Err(format!(
+                "reproducibility manifest records the {} profile, the proof options are {}",
+                recorded,
+                actual.map_or(String::from("of none"), |name| format!("{} ones", name))
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Checks that the recorded proof options are the ones the proof was generated with.
+    pub fn check_options(&self, options: &ProofOptions) -> Result<(), String> {
+        let (recorded, actual) = (&self.proof_options, ProofOptionsRecord::from(options));
+        let fields = [
+            ("num_queries", recorded.num_queries, actual.num_queries),
+            (
+                "blowup_factor",
+                recorded.blowup_factor,
+                actual.blowup_factor,
+            ),
+            (
+                "grinding_factor",
+                recorded.grinding_factor as usize,
+                actual.grinding_factor as usize,
+            ),
+            (
+                "folding_factor",
+                recorded.folding_factor,
+                actual.folding_factor,
+            ),
+            (
+                "fri_max_remainder_size",
+                recorded.fri_max_remainder_size,
+                actual.fri_max_remainder_size,
+            ),
+        ];
+        let mut details: Vec<String> = fields
+            .iter()
+            .filter(|(_, recorded, actual)| recorded != actual)
+            .map(|(name, recorded, actual)| format!("{} {} != {}", name, recorded, actual))
+            .collect();
+        if recorded.hash_fn != actual.hash_fn {
+            details.push(format!(
+                "hash_fn {} != {}",
+                recorded.hash_fn, actual.hash_fn
+            ));
+        }
+        if recorded.field_extension != actual.field_extension {
+            details.push(format!(
+                "field_extension {} != {}",
+                recorded.field_extension, actual.field_extension
+            ));
+        }
+        if details.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "reproducibility manifest does not match the proof options: {}",
+                details.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::{CustomData, COEFF_DEGREE, MIN_FIELD_EXTENSION};
+    use crate::verifying::{parse_data, VerificationFailure};
+
+    fn options(num_queries: usize, hash_fn: HashFunction) -> ProofOptions {
+        ProofOptions::new(num_queries, 8, 0, hash_fn, MIN_FIELD_EXTENSION, 4, 256)
+    }
+
+    #[test]
+    fn changed_circuits_are_rejected() {
+        let manifest =
+            ReproducibilityManifest::new(Op::Add, &options(28, HashFunction::Blake3_256));
+        assert_eq!(manifest.check_circuit(Op::Add), Ok(()));
+        let major = Op::Add.info().version.major;
+
+        let mut revised = manifest.clone();
+        revised.circuit_revision = major + 1;
+        let err = revised.check_circuit(Op::Add).unwrap_err();
+        assert!(err.starts_with("circuit mismatch"));
+        assert!(err.ends_with(&format!("(revision {} != {})", major + 1, major)));
+
+        let mut relaid = manifest.clone();
+        relaid.circuit_version = circuit_version(Op::Sub);
+        *relaid.layout.values_mut().next().unwrap() += 1;
+        let err = relaid.check_circuit(Op::Add).unwrap_err();
+        assert!(err.contains(&format!("implements version {}", circuit_version(Op::Add))));
+        assert!(err.contains(" != "));
+
+        let mut other = manifest;
+        other.circuit_id = String::new();
+        other.circuit = String::from(Op::Sub.name());
+        assert!(other
+            .check_circuit(Op::Add)
+            .unwrap_err()
+            .contains("recorded circuit sub differs from container circuit add"));
+    }
+
+    #[test]
+    fn changed_options_are_rejected() {
+        let proven = options(28, HashFunction::Blake3_256);
+        let manifest = ReproducibilityManifest::new(Op::Add, &proven);
+        assert_eq!(manifest.check_options(&proven), Ok(()));
+        assert_eq!(
+            manifest.check_options(&options(32, HashFunction::Blake3_256)),
+            Err(String::from(
+                "reproducibility manifest does not match the proof options: num_queries 28 != 32"
+            ))
+        );
+        assert_eq!(
+            manifest.check_options(&options(28, HashFunction::Sha3_256)),
+            Err(String::from(
+                "reproducibility manifest does not match the proof options: \
+                 hash_fn Blake3_256 != Sha3_256"
+            ))
+        );
+        assert!(manifest.check_security(96).is_ok());
+        let mut secured = manifest;
+        secured.security_bits = Some(96);
+        assert!(secured.check_security(96).is_ok());
+        assert!(secured.check_security(100).is_err());
+    }
+
+    #[test]
+    fn containers_are_checked_against_their_manifest_if_any() {
+        // hashes of the coefficient indices, so that no column of the trace is constant
+        let operand = |seed: u64| {
+            std::array::from_fn(|v| {
+                let hash = |k: u64| {
+                    let x = (k + 1000 * seed + 100 * v as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                    (x ^ x >> 31).wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 47
+                };
+                vec![(0..COEFF_DEGREE as u64).map(|k| hash(k) % 114689).collect()]
+            })
+        };
+        let data = CustomData {
+            modulus: vec![114689],
+            values: vec![operand(0), operand(1)],
+            ..Default::default()
+        };
+        let container = crate::prove(&data, options(28, HashFunction::Blake3_256))
+            .unwrap()
+            .to_data();
+        assert!(container.reproducibility.is_some());
+        assert!(parse_data(container.clone()).is_ok());
+
+        let mut bare = container.clone();
+        bare.reproducibility = None;
+        assert!(parse_data(bare).is_ok());
+
+        let mut revised = container.clone();
+        revised.reproducibility.as_mut().unwrap().circuit_revision += 1;
+        assert!(matches!(
+            parse_data(revised),
+            Err(VerificationFailure::ParamsRejected(err)) if err.starts_with("circuit mismatch")
+        ));
+
+        let mut reoptioned = container;
+        reoptioned
+            .reproducibility
+            .as_mut()
+            .unwrap()
+            .proof_options
+            .num_queries = 32;
+        assert!(matches!(
+            parse_data(reoptioned),
+            Err(VerificationFailure::ParamsRejected(err)) if err.contains("num_queries 32 != 28")
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// CKKS rescaling. After a multiplication the scale of a CKKS ciphertext is the product of the
+// operand scales; rescaling divides every coefficient by the last RNS prime q_L with rounding,
+// c' = round(c / q_L), and the scale by q_L. The division is the one proven by ModSwitchAir, so
+// the trace and constraints are shared with it.
+//
+// The public inputs also hold q_L and the scales before and after, so downstream verifiers can
+// track precision; parse_data checks that q_L is the last of the public moduli the modulus
+// columns are bound to, so the recorded scales belong to the division that was proven.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    load_data_file, read_modulus, BaseElement, CiphertextData, InputArg, Op, PublicInputs,
+    TraceType, COEFF_LEVEL, VALUE_NUM,
+};
+use crate::error::StarkHeError;
+use crate::modswitch_air::{
+    modswitch_assertion_num, modswitch_trace, read_modswitch_result, ModSwitchConstraints,
+    DROPPED_LEVEL,
+};
+
+// Same format as CiphertextData, with the scale of the ciphertext.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RescaleData {
+    pub modulus: Vec<u64>,
+    pub scale: f64,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+// The prime divided by and the scales before and after the division.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Scale {
+    pub modulus: u64,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl Scale {
+    pub fn new(before: f64, modulus: u64) -> Self {
+        Scale {
+            modulus,
+            before,
+            after: before / modulus as f64,
+        }
+    }
+
+    // Recorded scales must be positive and related by the recorded prime.
+    pub fn check(&self) -> Result<(), String> {
+        if !(self.before.is_finite() && self.before > 0.0) {
+            return Err(format!("invalid scale {}", self.before));
+        }
+        let expected = Scale::new(self.before, self.modulus);
+        if self.after != expected.after {
+            return Err(format!(
+                "scale after rescaling is {}, expected {} / {} = {}",
+                self.after, self.before, self.modulus, expected.after
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Scale of the ciphertext in a data file, which the prover records in the public inputs.
+pub fn load_scale(data_file_path: &str) -> Result<f64, StarkHeError> {
+    let data: RescaleData = load_data_file(data_file_path)?;
+    if !(data.scale.is_finite() && data.scale > 0.0) {
+        return Err(StarkHeError::InvalidInput(format!(
+            "invalid scale {}",
+            data.scale
+        )));
+    }
+    Ok(data.scale)
+}
+
+pub fn build_rescale_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: RescaleData = load_data_file(&arg.data_file_path)?;
+    let ciphertext = CiphertextData {
+        modulus: data.modulus,
+        values: data.values,
+    };
+    modswitch_trace(Op::Rescale, &ciphertext)
+}
+
+// Results, moduli and scales; the scale before is not stored in the trace.
+pub fn get_rescale_pub_inputs(trace: &TraceType, scale: f64) -> PublicInputs {
+    let modulus = read_modulus(trace);
+    PublicInputs {
+        result: read_modswitch_result(trace),
+        evaluation_key: None,
+        rotation: None,
+        scale: Some(Scale::new(scale, modulus[DROPPED_LEVEL])),
+        modulus: Some(modulus),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+pub struct RescaleAir {
+    context: AirContext<BaseElement>,
+    constraints: ModSwitchConstraints,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for RescaleAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("rescale proofs record their moduli");
+        let constraints = ModSwitchConstraints::new(Op::Rescale, &modulus);
+        let num_assertions = modswitch_assertion_num(pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, constraints.degrees(), num_assertions, options);
+        RescaleAir {
+            context: constraints.reductions().boundary().apply(context),
+            constraints,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.constraints.reductions().periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        self.constraints.evaluate(frame, periodic_values, result);
+    }
+
+    // Results of the kept levels are bound as for ModSwitchAir, q_L with the other moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        self.constraints
+            .assertions(&self.result, &self.modulus, self.window.as_deref())
+    }
+}
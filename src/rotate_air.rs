@@ -0,0 +1,645 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Slot rotation. Rotating by k applies the Galois automorphism a(X) -> a(X^g) with
+// g = 5^k mod 2N to both components of a ciphertext given in coefficient form: coefficient i
+// moves to i * g mod 2N, and is negated when that exceeds N because X^N = -1.
+//
+// Row k of the trace holds result coefficient k, input coefficient k and the input coefficient
+// result k comes from. The source index is not recomputed by the verifier: the index column
+// walks u_k = k * h mod 2N for h = g^-1, enforced by the transition u_(k+1) = u_k + h - 2N * w_k
+// with a binary wrap flag. u = i + N * s splits it into the source coefficient i and the sign s,
+// and i is range checked by ternary digits of i and N - 1 - i. Binary digits would do, but the
+// low bits of k * h are periodic in k, which Winterfell's debug builds reject as a constraint
+// degree mismatch. Other automorphisms, e.g. the conjugation of ConjugateAir, use the same trace
+// with their own step.
+//
+// The input ciphertext and its moduli are public, the input columns asserted against them. The
+// source coefficients are wired to the input by a permutation argument in an auxiliary column
+// per polynomial, as in NttAir: with random alpha and gamma, row k contributes the factor
+// gamma - x_k - alpha * i_k of its source coefficient x_k at index i_k over the factor
+// gamma - c_k - alpha * k of input coefficient c_k, and the running product must return to 1,
+// so every x_k is the input coefficient at i_k. Row 0, whose source index is 0, is asserted
+// directly and left out of the product.
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, AuxTraceRandElements, EvaluationFrame, ProofOptions, TraceInfo,
+    TraceLayout, TransitionConstraintDegree,
+};
+use winter_math::{ExtensionOf, FieldElement};
+use winter_prover::{Matrix, Trace};
+use winter_utils::{ByteReader, DeserializationError};
+
+use crate::air::{
+    bind_columns, element_from_u64, element_to_u64, level_column, load_data_file, BaseElement,
+    CiphertextData, InputArg, PublicInputs, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::canonical::{read_u64s, CanonicalWriter};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::ntt_air::wiring_assertions;
+use crate::reduction::{bind_moduli, MODULUS_ASSERTION_NUM};
+use crate::window::{bind_results, result_assertion_num};
+
+// Modulus + Result + Input + Data + Inverse + Index + Source + Sign + Wrap + Digits
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 X00 X01 X10 X11 I00 I01 I10 I11 U S SG W D0..D7 E0..E7
+pub const GALOIS_GENERATOR: u64 = 5;
+// as for subtractions, m - x and the moduli stay far below the field modulus
+pub const ROTATE_MAX_MODULUS_BITS: u32 = 62;
+// 5 generates a cyclic subgroup of order N/2 modulo 2N, rotations are taken modulo it
+pub const ROTATION_NUM: usize = COEFF_DEGREE / 2;
+const TWO_N: u64 = 2 * COEFF_DEGREE as u64;
+// the fewest ternary digits with 3^d >= N, 8 for N = 4096: digits above would stay zero
+const DIGIT_NUM: usize = ternary_digits(COEFF_DEGREE);
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const INPUT_START: usize = RESULT_START + RESULT_LEN;
+const DATA_START: usize = INPUT_START + RESULT_LEN;
+const INVERSE_START: usize = DATA_START + RESULT_LEN;
+const INDEX_COLUMN: usize = INVERSE_START + RESULT_LEN;
+const SOURCE_COLUMN: usize = INDEX_COLUMN + 1;
+const SIGN_COLUMN: usize = SOURCE_COLUMN + 1;
+const WRAP_COLUMN: usize = SIGN_COLUMN + 1;
+const DIGIT_START: usize = WRAP_COLUMN + 1;
+const DIGIT_END: usize = DIGIT_START + 2 * DIGIT_NUM;
+
+const STATE_WIDTH: usize = DIGIT_END;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+// zero test and signed copy per result column, binary sign, index split, ternary digits and
+// the recomposition of i and N - 1 - i
+const ROW_CONSTRAINTS: usize = 2 * RESULT_LEN + 2 + 2 * (DIGIT_NUM + 1);
+// one running product per polynomial, driven by alpha and gamma
+pub const AUTOMORPHISM_AUX_WIDTH: usize = RESULT_LEN;
+const AUX_WIDTH: usize = AUTOMORPHISM_AUX_WIDTH;
+const AUX_RAND_NUM: usize = 2;
+// the only periodic column, the row index
+const POSITION: usize = 0;
+
+const fn ternary_digits(n: usize) -> usize {
+    let (mut digits, mut power) = (0, 1);
+    while power < n {
+        power *= 3;
+        digits += 1;
+    }
+    digits
+}
+
+// Trace layout constants of RotateAir in canonical order.
+pub fn rotate_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("digit_num", DIGIT_NUM),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+pub fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+fn input_column(v: usize, l: usize) -> usize {
+    level_column(INPUT_START, v, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn inverse_column(v: usize, l: usize) -> usize {
+    level_column(INVERSE_START, v, l)
+}
+
+// Digit `j` of i for `complement` false, of N - 1 - i otherwise.
+fn digit_column(complement: bool, j: usize) -> usize {
+    DIGIT_START + complement as usize * DIGIT_NUM + j
+}
+
+fn pow_mod(base: u64, exponent: u64, m: u64) -> u64 {
+    let (mut result, mut base, mut exponent) = (1, base % m, exponent);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % m;
+        }
+        base = base * base % m;
+        exponent >>= 1;
+    }
+    result
+}
+
+fn galois_power(exponent: i64) -> u64 {
+    let exponent = exponent.rem_euclid(ROTATION_NUM as i64) as u64;
+    pow_mod(GALOIS_GENERATOR, exponent, TWO_N)
+}
+
+// Galois element 5^k mod 2N of a rotation by `rotation` slots; negative rotations go the
+// other way.
+pub fn galois_element(rotation: i32) -> u64 {
+    galois_power(rotation as i64)
+}
+
+// g^-1 mod 2N, the Galois element of the opposite rotation.
+pub fn galois_inverse(rotation: i32) -> u64 {
+    galois_power(-(rotation as i64))
+}
+
+// Coefficient index and sign the result coefficient `k` is taken from, for h = g^-1.
+fn source(k: usize, h: u64) -> (u64, bool) {
+    let u = k as u64 * h % TWO_N;
+    (u, u >= COEFF_DEGREE as u64)
+}
+
+// Reference implementation of the rotation of one coefficient vector modulo `m`.
+pub fn rotate_coefficients(values: &[u64], rotation: i32, m: u64) -> Vec<u64> {
+    automorphism_coefficients(values, galois_inverse(rotation), m)
+}
+
+// Reference implementation of the automorphism with Galois element h^-1 of one coefficient
+// vector modulo `m`.
+pub fn automorphism_coefficients(values: &[u64], h: u64, m: u64) -> Vec<u64> {
+    (0..values.len())
+        .map(|k| {
+            let (u, negate) = source(k, h);
+            let x = values[(u % COEFF_DEGREE as u64) as usize];
+            if negate && x != 0 {
+                m - x
+            } else {
+                x
+            }
+        })
+        .collect()
+}
+
+// The ciphertext an automorphism is applied to, in the form its circuit takes: coefficient form
+// for rotations and conjugations, evaluation form for the key-switched automorphisms.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutomorphismInput {
+    pub ciphertext: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+impl AutomorphismInput {
+    // The input of the components `values`, one coefficient vector per level, as circuits read
+    // them back from their traces.
+    pub fn from_elements(values: Vec<Vec<Vec<BaseElement>>>) -> Self {
+        let mut ciphertext: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
+        for (component, levels) in ciphertext.iter_mut().zip(values) {
+            for (level, values) in component.iter_mut().zip(levels) {
+                *level = values.into_iter().map(element_to_u64).collect();
+            }
+        }
+        AutomorphismInput { ciphertext }
+    }
+
+    // Layout: every ciphertext vector as its length (u32) and u64 coefficients, ordered by
+    // component and level.
+    pub fn write_canonical(&self, writer: &mut CanonicalWriter) {
+        for component in self.ciphertext.iter() {
+            for level in component.iter() {
+                writer.write_u64s(level);
+            }
+        }
+    }
+
+    pub fn read_canonical<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let mut ciphertext: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] = Default::default();
+        for level in ciphertext.iter_mut().flatten() {
+            *level = read_u64s(source)?;
+        }
+        Ok(AutomorphismInput { ciphertext })
+    }
+
+    // The ciphertext must be reduced modulo `modulus`, one modulus per level.
+    pub fn check(&self, modulus: &[u64]) -> Result<(), String> {
+        if modulus.len() != COEFF_LEVEL {
+            return Err(format!(
+                "an input ciphertext of {} levels needs as many moduli, not {}",
+                COEFF_LEVEL,
+                modulus.len()
+            ));
+        }
+        for (v, component) in self.ciphertext.iter().enumerate() {
+            for (l, level) in component.iter().enumerate() {
+                if level.len() != COEFF_DEGREE || level.iter().any(|&c| c >= modulus[l]) {
+                    return Err(format!(
+                        "input ciphertext component {} level {} is not {} residues modulo {}",
+                        v, l, COEFF_DEGREE, modulus[l]
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The columns `column(v, l)` holding component v at level l of the input, with their values.
+    pub fn columns(
+        &self,
+        column: impl Fn(usize, usize) -> usize,
+    ) -> Vec<(usize, Vec<BaseElement>)> {
+        let mut columns = Vec::with_capacity(VALUE_NUM * COEFF_LEVEL);
+        for (v, component) in self.ciphertext.iter().enumerate() {
+            for (l, level) in component.iter().enumerate() {
+                let values = level.iter().copied().map(element_from_u64).collect();
+                columns.push((column(v, l), values));
+            }
+        }
+        columns
+    }
+}
+
+pub fn build_rotate_trace(arg: &InputArg) -> Result<AutomorphismTrace, StarkHeError> {
+    // a single ciphertext in coefficient form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    let rotation = arg
+        .rotation
+        .ok_or_else(|| StarkHeError::InvalidInput(String::from("rotation needs --rotation")))?;
+    assert!(
+        (rotation as i64).rem_euclid(ROTATION_NUM as i64) != 0,
+        "rotation by a multiple of {} slots is the identity",
+        ROTATION_NUM
+    );
+    build_automorphism_trace(&data, galois_inverse(rotation))
+}
+
+// Execution trace with the permutation argument as its auxiliary segment, and the moduli of its
+// input.
+pub struct AutomorphismTrace {
+    layout: TraceLayout,
+    main: Matrix<BaseElement>,
+    modulus: Vec<u64>,
+}
+
+// Trace of the automorphism with Galois element h^-1 of `data`, for any odd h.
+pub fn build_automorphism_trace(
+    data: &CiphertextData,
+    h: u64,
+) -> Result<AutomorphismTrace, StarkHeError> {
+    check_modulus_bits(&data.modulus, ROTATE_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, STATE_LENGTH, &data.values)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; STATE_WIDTH];
+    for k in 0..STATE_LENGTH {
+        let (u, sign) = source(k, h);
+        let i = u % COEFF_DEGREE as u64;
+        columns[INDEX_COLUMN][k] = element_from_u64(u);
+        columns[SOURCE_COLUMN][k] = element_from_u64(i);
+        columns[SIGN_COLUMN][k] = element_from_u64(sign as u64);
+        columns[WRAP_COLUMN][k] = element_from_u64(((u + h) >= TWO_N) as u64);
+        let (mut digits, mut complement) = (i, COEFF_DEGREE as u64 - 1 - i);
+        for j in 0..DIGIT_NUM {
+            columns[digit_column(false, j)][k] = element_from_u64(digits % 3);
+            columns[digit_column(true, j)][k] = element_from_u64(complement % 3);
+            digits /= 3;
+            complement /= 3;
+        }
+    }
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            let results = automorphism_coefficients(&data.values[v][l], h, m);
+            for k in 0..STATE_LENGTH {
+                let (u, _) = source(k, h);
+                let x = element_from_u64(data.values[v][l][(u % COEFF_DEGREE as u64) as usize]);
+                columns[result_column(v, l)][k] = element_from_u64(results[k]);
+                columns[input_column(v, l)][k] = element_from_u64(data.values[v][l][k]);
+                columns[data_column(v, l)][k] = x;
+                columns[inverse_column(v, l)][k] = if x == BaseElement::ZERO {
+                    BaseElement::ZERO
+                } else {
+                    x.inv()
+                };
+            }
+        }
+    }
+    Ok(AutomorphismTrace {
+        layout: TraceLayout::new(STATE_WIDTH, [AUX_WIDTH], [AUX_RAND_NUM]),
+        main: Matrix::new(columns),
+        modulus: data.modulus.clone(),
+    })
+}
+
+// Components of the columns `column(v, l)` of `main`, one vector per level.
+fn read_columns(
+    main: &Matrix<BaseElement>,
+    column: impl Fn(usize, usize) -> usize,
+) -> Vec<Vec<Vec<BaseElement>>> {
+    (0..VALUE_NUM)
+        .map(|v| {
+            (0..COEFF_LEVEL)
+                .map(|l| main.get_column(column(v, l)).to_vec())
+                .collect()
+        })
+        .collect()
+}
+
+// The results, the input ciphertext and its moduli, read back from the trace.
+pub fn get_automorphism_pub_inputs(trace: &AutomorphismTrace) -> PublicInputs {
+    PublicInputs {
+        result: read_columns(&trace.main, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(trace.modulus.clone()),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: Some(AutomorphismInput::from_elements(read_columns(
+            &trace.main,
+            input_column,
+        ))),
+    }
+}
+
+pub fn get_rotate_pub_inputs(trace: &AutomorphismTrace, rotation: i32) -> PublicInputs {
+    PublicInputs {
+        rotation: Some(rotation),
+        ..get_automorphism_pub_inputs(trace)
+    }
+}
+
+// Permutation factor of the coefficient `value` at index `position`:
+// gamma - value - alpha * position.
+fn factor<E: FieldElement>(value: E, position: E, alpha: E, gamma: E) -> E {
+    gamma - value - alpha * position
+}
+
+impl Trace for AutomorphismTrace {
+    type BaseField = BaseElement;
+
+    fn layout(&self) -> &TraceLayout {
+        &self.layout
+    }
+
+    fn length(&self) -> usize {
+        self.main.num_rows()
+    }
+
+    fn meta(&self) -> &[u8] {
+        &[]
+    }
+
+    fn main_segment(&self) -> &Matrix<BaseElement> {
+        &self.main
+    }
+
+    // The running products z_(k+1) = z_k * sources_(k+1) / inputs_(k+1), starting from 1.
+    fn build_aux_segment<E: FieldElement<BaseField = BaseElement>>(
+        &mut self,
+        aux_segments: &[Matrix<E>],
+        rand_elements: &[E],
+    ) -> Option<Matrix<E>> {
+        if !aux_segments.is_empty() {
+            return None;
+        }
+        let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+        let mut columns = vec![vec![E::ONE; self.length()]; AUX_WIDTH];
+        for k in 1..self.length() {
+            let index = E::from(self.main.get(SOURCE_COLUMN, k));
+            let position = E::from(BaseElement::from(k as u64));
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    let p = v * COEFF_LEVEL + l;
+                    let source = factor(
+                        E::from(self.main.get(data_column(v, l), k)),
+                        index,
+                        alpha,
+                        gamma,
+                    );
+                    let input = factor(
+                        E::from(self.main.get(input_column(v, l), k)),
+                        position,
+                        alpha,
+                        gamma,
+                    );
+                    columns[p][k] = columns[p][k - 1] * source * input.inv();
+                }
+            }
+        }
+        Some(Matrix::new(columns))
+    }
+
+    fn read_main_frame(&self, row_idx: usize, frame: &mut EvaluationFrame<BaseElement>) {
+        let next_row_idx = (row_idx + 1) % self.length();
+        self.main.read_row_into(row_idx, frame.current_mut());
+        self.main.read_row_into(next_row_idx, frame.next_mut());
+    }
+}
+
+// Constraint values of one row, zero when the row holds a correctly signed copy of its source
+// coefficient and a well-formed index. With x * inv = 1 for every non-zero x, the result is
+// y = x - 2sx + s * m * x * inv, i.e. x or m - x, and 0 for x = 0 whatever the sign; m is the
+// public modulus of the level.
+fn evaluate_row<E: FieldElement + From<BaseElement>>(
+    row: &[E],
+    modulus: &[E; COEFF_LEVEL],
+    result: &mut [E],
+) {
+    let two = E::from(BaseElement::new(2));
+    let sign = row[SIGN_COLUMN];
+    let mut idx = 0;
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            let m = modulus[l];
+            let x = row[data_column(v, l)];
+            let product = x * row[inverse_column(v, l)];
+            result[idx] = x * (E::ONE - product);
+            result[idx + 1] = row[result_column(v, l)] - x + two * sign * x - sign * m * product;
+            idx += 2;
+        }
+    }
+    let n = E::from(element_from_u64(COEFF_DEGREE as u64));
+    result[idx] = sign * sign - sign;
+    result[idx + 1] = row[INDEX_COLUMN] - row[SOURCE_COLUMN] - n * sign;
+    idx += 2;
+    let three = E::from(BaseElement::new(3));
+    for complement in [false, true] {
+        let mut value = E::ZERO;
+        for j in (0..DIGIT_NUM).rev() {
+            let digit = row[digit_column(complement, j)];
+            result[idx] = digit * (digit - E::ONE) * (digit - two);
+            idx += 1;
+            value = value * three + digit;
+        }
+        let expected = if complement {
+            n - E::ONE - row[SOURCE_COLUMN]
+        } else {
+            row[SOURCE_COLUMN]
+        };
+        result[idx] = expected - value;
+        idx += 1;
+    }
+}
+
+pub struct RotateAir {
+    context: AirContext<BaseElement>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    modulus: Vec<u64>,
+    input: AutomorphismInput,
+    // inverse of the Galois element, the step of the index column
+    step: BaseElement,
+}
+
+impl Air for RotateAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // Row constraints are checked on the current and the next row as in MulAir, the index walk
+    // only between them.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let rotation = pub_inputs
+            .rotation
+            .expect("rotation proofs need the rotation");
+        RotateAir::with_step(trace_info, pub_inputs, options, galois_inverse(rotation))
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        _periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let modulus = std::array::from_fn(|l| E::from(element_from_u64(self.modulus[l])));
+        let (current, rest) = result.split_at_mut(ROW_CONSTRAINTS);
+        let (next, walk) = rest.split_at_mut(ROW_CONSTRAINTS);
+        evaluate_row(frame.current(), &modulus, current);
+        evaluate_row(frame.next(), &modulus, next);
+
+        let wrap = frame.current()[WRAP_COLUMN];
+        walk[0] = wrap * wrap - wrap;
+        walk[1] = frame.next()[INDEX_COLUMN] - frame.current()[INDEX_COLUMN] - E::from(self.step)
+            + E::from(element_from_u64(TWO_N)) * wrap;
+    }
+
+    // The permutation constraints z_next * input_next - z * source_next of every polynomial,
+    // the position of the next row being one past the periodic row index.
+    fn evaluate_aux_transition<F, E>(
+        &self,
+        main_frame: &EvaluationFrame<F>,
+        aux_frame: &EvaluationFrame<E>,
+        periodic_values: &[F],
+        aux_rand_elements: &AuxTraceRandElements<E>,
+        result: &mut [E],
+    ) where
+        F: FieldElement<BaseField = Self::BaseField>,
+        E: FieldElement<BaseField = Self::BaseField> + ExtensionOf<F>,
+    {
+        let rand_elements = aux_rand_elements.get_segment_elements(0);
+        let (alpha, gamma) = (rand_elements[0], rand_elements[1]);
+        let next = main_frame.next();
+        let index = E::from(next[SOURCE_COLUMN]);
+        let position = E::from(periodic_values[POSITION]) + E::ONE;
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let p = v * COEFF_LEVEL + l;
+                let source = factor(E::from(next[data_column(v, l)]), index, alpha, gamma);
+                let input = factor(E::from(next[input_column(v, l)]), position, alpha, gamma);
+                result[p] = aux_frame.next()[p] * input - aux_frame.current()[p] * source;
+            }
+        }
+    }
+
+    // Results are bound on every row as in MulAir, the input columns and the modulus columns
+    // to the public input and moduli; the index walk starts at 0, so row 0 holds the first
+    // input coefficient.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = bind_results(columns, self.window.as_deref());
+        assertions.extend(bind_columns(self.input.columns(input_column)));
+        assertions.extend(bind_moduli(&self.modulus, 0, 2));
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let first = element_from_u64(self.input.ciphertext[v][l][0]);
+                assertions.push(Assertion::single(data_column(v, l), 0, first));
+            }
+        }
+        assertions.push(Assertion::single(INDEX_COLUMN, 0, BaseElement::ZERO));
+        assertions
+    }
+
+    fn get_aux_assertions<E: FieldElement<BaseField = Self::BaseField>>(
+        &self,
+        _aux_rand_elements: &AuxTraceRandElements<E>,
+    ) -> Vec<Assertion<E>> {
+        wiring_assertions(AUX_WIDTH, self.trace_length())
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        vec![(0..self.trace_length() as u64)
+            .map(BaseElement::from)
+            .collect()]
+    }
+}
+
+impl RotateAir {
+    // The AIR of the automorphism with Galois element `step`^-1, whose index column walks by
+    // `step`.
+    pub fn with_step(
+        trace_info: TraceInfo,
+        pub_inputs: PublicInputs,
+        options: ProofOptions,
+        step: u64,
+    ) -> Self {
+        let mut row_degrees = vec![TransitionConstraintDegree::new(3); 2 * RESULT_LEN];
+        row_degrees.push(TransitionConstraintDegree::new(2));
+        row_degrees.push(TransitionConstraintDegree::new(1));
+        for _ in 0..2 {
+            row_degrees.extend(vec![TransitionConstraintDegree::new(3); DIGIT_NUM]);
+            row_degrees.push(TransitionConstraintDegree::new(1));
+        }
+        let mut degrees = [row_degrees.clone(), row_degrees].concat();
+        degrees.push(TransitionConstraintDegree::new(2));
+        degrees.push(TransitionConstraintDegree::new(1));
+        // the next running product times an input coefficient or the periodic row index
+        let aux_degrees = vec![TransitionConstraintDegree::new(2); AUX_WIDTH];
+        let num_assertions = result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + 2 * RESULT_LEN
+            + MODULUS_ASSERTION_NUM
+            + RESULT_LEN
+            + 1;
+        let num_aux_assertions = 2 * AUX_WIDTH;
+
+        RotateAir {
+            context: AirContext::new_multi_segment(
+                trace_info,
+                degrees,
+                aux_degrees,
+                num_assertions,
+                num_aux_assertions,
+                options,
+            ),
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            modulus: pub_inputs
+                .modulus
+                .expect("automorphism proofs record their moduli"),
+            input: pub_inputs
+                .input
+                .expect("automorphism proofs record their input ciphertext"),
+            step: element_from_u64(step),
+        }
+    }
+}
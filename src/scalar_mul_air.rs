@@ -0,0 +1,250 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Multiplication of a ciphertext by a public scalar, e.g. a weight of an encrypted weighted
+// sum. Every coefficient of both components and every RNS level is multiplied by the same
+// integer s, so every circuit row proves
+//
+//     r_v = c_v * s mod q_l
+//
+// with the Barrett blocks of reduction.rs, one product per row of a row group, against the
+// moduli of the public inputs. The scalar is not stored in the trace: it is a public input and
+// enters the constraints as a constant. Scalars and moduli have at most 62 bits, so the quotient
+// of a product fits into 62 bits as well.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 C00 C01 C10 C11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + RESULT_LEN;
+// the quotient of a product is below the scalar
+const REDUCTION_WIDTH: usize =
+    reduction_width(SCALAR_MAX_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per product, then padding
+pub const SCALAR_MUL_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Largest scalar, as for the moduli.
+pub const SCALAR_MAX_BITS: u32 = MUL_MAX_MODULUS_BITS;
+
+// Trace layout constants of ScalarMulAir in canonical order.
+pub fn scalar_mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", SCALAR_MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as CiphertextData, with the scalar in `Scalar`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScalarMulData {
+    pub modulus: Vec<u64>,
+    pub scalar: u64,
+    pub values: [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM],
+}
+
+// A nonzero scalar of at most SCALAR_MAX_BITS bits.
+pub fn check_scalar(scalar: u64) -> Result<(), String> {
+    if scalar == 0 || 64 - scalar.leading_zeros() > SCALAR_MAX_BITS {
+        return Err(format!(
+            "scalar {} is not a nonzero integer of at most {} bits",
+            scalar, SCALAR_MAX_BITS
+        ));
+    }
+    Ok(())
+}
+
+// Scalar of a data file, which the prover records in the public inputs.
+pub fn load_scalar(data_file_path: &str) -> Result<u64, StarkHeError> {
+    let data: ScalarMulData = load_data_file(data_file_path)?;
+    check_scalar(data.scalar).map_err(StarkHeError::InvalidInput)?;
+    Ok(data.scalar)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, the product of component v at level l
+// being value v * COEFF_LEVEL + l of the row: residues times the scalar.
+pub fn scalar_mul_reductions(modulus: &[u64], scalar: u64) -> Result<Reductions, String> {
+    check_public_moduli(Op::ScalarMul, modulus, MUL_MAX_MODULUS_BITS)?;
+    check_scalar(scalar)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| (modulus[l] as u128 - 1) * scalar as u128,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_scalar_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: ScalarMulData = load_data_file(&arg.data_file_path)?;
+    check_scalar(data.scalar).map_err(StarkHeError::InvalidInput)?;
+    check_modulus_bits(&data.modulus, MUL_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+
+    let ciphertext = Ciphertext::from_levels(&data.modulus, STATE_LENGTH, data.values)?;
+    let reductions = scalar_mul_reductions(ciphertext.modulus(), data.scalar)
+        .map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    ciphertext.write_columns(&mut columns, data_column);
+    let product =
+        |k: usize, v: usize, l: usize| ciphertext.coeff(v, l, k) as u128 * data.scalar as u128;
+    for l in 0..COEFF_LEVEL {
+        let m = ciphertext.modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                columns[result_column(v, l)][k] =
+                    element_from_u64((product(k, v, l) % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        product(k, j / COEFF_LEVEL, j % COEFF_LEVEL)
+    }))
+}
+
+pub fn get_scalar_mul_pub_inputs(trace: &TraceType, scalar: u64) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, SCALAR_MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: Some(scalar),
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The products of one row, component v at level l at v * COEFF_LEVEL + l.
+fn row_products<E: FieldElement>(row: &[E], scalar: E) -> [E; RESULT_LEN] {
+    let mut products = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            products[v * COEFF_LEVEL + l] = row[data_column(v, l)] * scalar;
+        }
+    }
+    products
+}
+
+pub struct ScalarMulAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    scalar: BaseElement,
+}
+
+impl Air for ScalarMulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The scalar is constant, so the products are linear and selected by a periodic column; the
+    // moduli and the scalar were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("scalar-mul proofs record their moduli");
+        let scalar = pub_inputs
+            .scalar
+            .expect("scalar multiplication needs its scalar");
+        let reductions =
+            scalar_mul_reductions(&modulus, scalar).expect("scalar-mul moduli were checked");
+        let degrees = reductions.degrees(1, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        ScalarMulAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            scalar: element_from_u64(scalar),
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let products = row_products(frame.current(), E::from(self.scalar));
+        self.reductions
+            .evaluate(frame, periodic_values, &products, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Homomorphic encryption scheme a proof is about. BFV puts the plaintext into the upper bits of
+// the ciphertext, scaled by Delta = floor(Q / t), BGV into the lower bits, as m + t * e, and
+// scales ciphertexts down by modulus switching with a correction which keeps m mod t. The
+// ciphertext arithmetic of additions and tensor products is the same for both schemes, so their
+// circuits prove BGV operations unchanged; the scheme only decides how the plaintext is embedded,
+// which circuits like `decrypt`, `encrypt` or `mod-switch` depend on and which only support BFV so
+// far, and `compare`, whose sign polynomial adds its coefficients unscaled, only BGV. A BGV proof
+// records the scheme in its public inputs, so that it cannot be passed off as a BFV proof of the
+// same ciphertexts or the other way round; BFV proofs record nothing, as they did before schemes
+// were selectable.
+
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use winter_air::{Air, ProofOptions};
+use winter_prover::Prover;
+
+use crate::air::{BaseElement, Op, PublicInputs};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    Bfv,
+    Bgv,
+}
+
+impl Scheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scheme::Bfv => "bfv",
+            Scheme::Bgv => "bgv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scheme> {
+        Scheme::from_str(name, false).ok()
+    }
+
+    // Identifier in the canonical encoding of public inputs.
+    pub fn tag(&self) -> u8 {
+        match self {
+            Scheme::Bfv => 0,
+            Scheme::Bgv => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Scheme> {
+        Scheme::value_variants()
+            .iter()
+            .copied()
+            .find(|scheme| scheme.tag() == tag)
+    }
+
+    // Whether the circuit of `op` proves operations of this scheme: BGV shares the ciphertext
+    // arithmetic of additions and tensor products with BFV, and comparisons are BGV only.
+    pub fn supports(&self, op: Op) -> bool {
+        match self {
+            Scheme::Bfv => op != Op::Compare,
+            Scheme::Bgv => matches!(
+                op,
+                Op::Add
+                    | Op::Sub
+                    | Op::Neg
+                    | Op::Sum
+                    | Op::Mul
+                    | Op::MontMul
+                    | Op::WideMul
+                    | Op::Fma
+                    | Op::MultiKeyAdd
+                    | Op::Compare
+            ),
+        }
+    }
+}
+
+// A proof of `op` may record `scheme`, if it is one the circuit supports; proofs without one
+// are BFV proofs, which BGV only circuits reject.
+pub fn check_scheme(op: Op, scheme: Option<Scheme>) -> Result<(), String> {
+    match scheme {
+        Some(Scheme::Bfv) => Err(String::from(
+            "BFV proofs do not record their scheme, but one is recorded",
+        )),
+        Some(scheme) if !scheme.supports(op) => Err(format!(
+            "{} proofs are BFV only, not {}",
+            op.name(),
+            scheme.name()
+        )),
+        None if !Scheme::Bfv.supports(op) => Err(format!(
+            "{} proofs are BGV only, but record no scheme",
+            op.name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Records the scheme of `inner`'s proofs in their public inputs unless it is BFV.
+pub struct SchemeProver<P> {
+    pub inner: P,
+    pub scheme: Scheme,
+}
+
+impl<P> Prover for SchemeProver<P>
+where
+    P: Prover<BaseField = BaseElement>,
+    P::Air: Air<PublicInputs = PublicInputs>,
+{
+    type BaseField = BaseElement;
+    type Air = P::Air;
+    type Trace = P::Trace;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        let mut public_input = self.inner.get_pub_inputs(trace);
+        public_input.scheme = (self.scheme != Scheme::Bfv).then_some(self.scheme);
+        public_input
+    }
+
+    fn options(&self) -> &ProofOptions {
+        self.inner.options()
+    }
+}
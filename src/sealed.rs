@@ -0,0 +1,256 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Proof containers encrypted to a recipient's X25519 key, for settings where the result
+// coefficients in the public inputs are confidential between prover and verifier. A sealed
+// container is
+//
+//     magic "SHEX", version u8, ephemeral public key (32 bytes), ChaCha20-Poly1305 ciphertext
+//
+// of the TOML container. The key is the commitment to the canonical encoding (tag SEALED_TAG)
+// of the X25519 shared secret, the ephemeral and the recipient public key. Every container has
+// a fresh ephemeral key, so the nonce is fixed to zero; the header is authenticated as
+// associated data.
+//
+// Key files hold one line, "stark-he x25519 public <base64>" or "stark-he x25519 secret
+// <base64>", so that the two cannot be swapped by accident.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::canonical::{commitment, CanonicalWriter};
+
+pub const MAGIC: &[u8; 4] = b"SHEX";
+pub const VERSION: u8 = 1;
+pub const SEALED_TAG: &str = "STARK-HE/sealed-container/v1";
+
+const KEY_BYTES: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + KEY_BYTES;
+const PUBLIC_LABEL: &str = "stark-he x25519 public";
+const SECRET_LABEL: &str = "stark-he x25519 secret";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SealError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    // wrong key or tampered container, which AEAD cannot tell apart
+    Decryption,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::Truncated => write!(f, "sealed container is truncated"),
+            SealError::BadMagic => write!(f, "not a sealed container"),
+            SealError::UnsupportedVersion(version) => {
+                write!(f, "unsupported sealed container version {}", version)
+            }
+            SealError::Decryption => write!(
+                f,
+                "cannot decrypt the container: wrong key or tampered data"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn derive_key(shared: &[u8; KEY_BYTES], ephemeral: &PublicKey, recipient: &PublicKey) -> Key {
+    let mut writer = CanonicalWriter::new(SEALED_TAG);
+    for key in [shared, ephemeral.as_bytes(), recipient.as_bytes()] {
+        for &byte in key.iter() {
+            writer.write_u8(byte);
+        }
+    }
+    Key::from(commitment(&writer.into_bytes()))
+}
+
+pub fn seal(plaintext: &[u8], recipient: &PublicKey) -> Vec<u8> {
+    let secret = EphemeralSecret::random();
+    let ephemeral = PublicKey::from(&secret);
+    let shared = secret.diffie_hellman(recipient);
+    let cipher = ChaCha20Poly1305::new(&derive_key(shared.as_bytes(), &ephemeral, recipient));
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    sealed.extend_from_slice(MAGIC);
+    sealed.push(VERSION);
+    sealed.extend_from_slice(ephemeral.as_bytes());
+    let ciphertext = cipher
+        .encrypt(
+            &Nonce::default(),
+            Payload {
+                msg: plaintext,
+                aad: &sealed,
+            },
+        )
+        .expect("ChaCha20-Poly1305 encrypts messages of any proof size");
+    sealed.extend_from_slice(&ciphertext);
+    sealed
+}
+
+pub fn open(sealed: &[u8], secret: &StaticSecret) -> Result<Vec<u8>, SealError> {
+    if sealed.len() < HEADER_LEN {
+        return Err(if is_sealed(sealed) {
+            SealError::Truncated
+        } else {
+            SealError::BadMagic
+        });
+    }
+    if !is_sealed(sealed) {
+        return Err(SealError::BadMagic);
+    }
+    let version = sealed[MAGIC.len()];
+    if version != VERSION {
+        return Err(SealError::UnsupportedVersion(version));
+    }
+    let (header, ciphertext) = sealed.split_at(HEADER_LEN);
+    let ephemeral: [u8; KEY_BYTES] = header[MAGIC.len() + 1..].try_into().unwrap();
+    let ephemeral = PublicKey::from(ephemeral);
+    let recipient = PublicKey::from(secret);
+    let shared = secret.diffie_hellman(&ephemeral);
+    let cipher = ChaCha20Poly1305::new(&derive_key(shared.as_bytes(), &ephemeral, &recipient));
+    cipher
+        .decrypt(
+            &Nonce::default(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| SealError::Decryption)
+}
+
+pub fn generate_secret_key() -> StaticSecret {
+    StaticSecret::random()
+}
+
+pub fn public_key_text(key: &PublicKey) -> String {
+    format!("{} {}\n", PUBLIC_LABEL, STANDARD.encode(key.as_bytes()))
+}
+
+pub fn secret_key_text(key: &StaticSecret) -> String {
+    format!("{} {}\n", SECRET_LABEL, STANDARD.encode(key.as_bytes()))
+}
+
+fn read_key_file(path: &Path, label: &str, kind: &str) -> Result<[u8; KEY_BYTES], String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let encoded = text
+        .trim()
+        .strip_prefix(label)
+        .ok_or_else(|| format!("{} is not an X25519 {} key file", path.display(), kind))?;
+    STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{} does not hold a 32-byte key", path.display()))
+}
+
+pub fn load_public_key(path: &Path) -> Result<PublicKey, String> {
+    read_key_file(path, PUBLIC_LABEL, "public").map(PublicKey::from)
+}
+
+pub fn load_secret_key(path: &Path) -> Result<StaticSecret, String> {
+    read_key_file(path, SECRET_LABEL, "secret").map(StaticSecret::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTAINER: &[u8] = b"circuit = \"add\"\nproof = \"AAAA\"\n";
+
+    #[test]
+    fn sealed_containers_open_with_the_recipient_key_only() {
+        let secret = generate_secret_key();
+        let sealed = seal(CONTAINER, &PublicKey::from(&secret));
+        assert!(is_sealed(&sealed));
+        assert_eq!(sealed.len(), HEADER_LEN + CONTAINER.len() + 16);
+        assert_eq!(open(&sealed, &secret).unwrap(), CONTAINER);
+        // a fresh ephemeral key per container
+        assert_ne!(seal(CONTAINER, &PublicKey::from(&secret)), sealed);
+
+        let other = generate_secret_key();
+        assert_eq!(open(&sealed, &other), Err(SealError::Decryption));
+
+        let mut tampered = sealed.clone();
+        tampered[MAGIC.len() + 1] ^= 1;
+        assert_eq!(open(&tampered, &secret), Err(SealError::Decryption));
+        let mut tampered = sealed.clone();
+        tampered[HEADER_LEN + 3] ^= 1;
+        assert_eq!(open(&tampered, &secret), Err(SealError::Decryption));
+        let mut tampered = sealed.clone();
+        tampered[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            open(&tampered, &secret),
+            Err(SealError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn malformed_containers_are_rejected() {
+        let secret = generate_secret_key();
+        let sealed = seal(CONTAINER, &PublicKey::from(&secret));
+        assert_eq!(
+            open(&sealed[..HEADER_LEN - 1], &secret),
+            Err(SealError::Truncated)
+        );
+        // the tag of the AEAD is cut
+        assert_eq!(
+            open(&sealed[..sealed.len() - 1], &secret),
+            Err(SealError::Decryption)
+        );
+        assert_eq!(open(b"SH", &secret), Err(SealError::BadMagic));
+        let mut renamed = sealed.clone();
+        renamed[..MAGIC.len()].copy_from_slice(b"SHEY");
+        assert!(!is_sealed(&renamed));
+        assert_eq!(open(&renamed, &secret), Err(SealError::BadMagic));
+        assert_eq!(open(CONTAINER, &secret), Err(SealError::BadMagic));
+    }
+
+    #[test]
+    fn key_files_cannot_be_swapped() {
+        let dir = std::env::temp_dir().join(format!("stark-he-sealed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let secret = generate_secret_key();
+        let public = PublicKey::from(&secret);
+        let public_path = dir.join("key.pub");
+        let secret_path = dir.join("key.secret");
+        fs::write(&public_path, public_key_text(&public)).unwrap();
+        fs::write(&secret_path, secret_key_text(&secret)).unwrap();
+
+        assert_eq!(load_public_key(&public_path).unwrap(), public);
+        let loaded = load_secret_key(&secret_path).unwrap();
+        assert_eq!(loaded.to_bytes(), secret.to_bytes());
+        assert!(load_public_key(&secret_path)
+            .unwrap_err()
+            .contains("is not an X25519 public key file"));
+        assert!(load_secret_key(&public_path)
+            .err()
+            .unwrap()
+            .contains("is not an X25519 secret key file"));
+
+        fs::write(&public_path, format!("{} AAAA\n", PUBLIC_LABEL)).unwrap();
+        assert!(load_public_key(&public_path)
+            .unwrap_err()
+            .contains("does not hold a 32-byte key"));
+        assert!(load_public_key(&dir.join("missing.pub"))
+            .unwrap_err()
+            .starts_with("cannot read"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
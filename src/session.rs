@@ -0,0 +1,156 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Verification sessions for applications that verify a stream of proofs of one circuit under
+// one parameter set, e.g. every relinearization of a service with its evaluation key:
+//
+//     let session = Verifier::session(params, policy)?;
+//     for bytes in containers {
+//         session.verify(bytes, "request")?;
+//     }
+//
+// What depends only on the parameters is done once when the session is opened: the circuit is
+// checked against the policy, its trace shape is computed and the evaluation key is checked and
+// hashed, which for full keys costs more than decoding a proof.
+// Every proof then goes through the preflight checks of preflight.rs against the session and
+// the STARK verification. Proofs of another circuit, with other proof options or for another
+// evaluation key are ParamsRejected or PublicInputMismatch without being verified.
+
+use winter_air::ProofOptions;
+use x25519_dalek::StaticSecret;
+
+use crate::air::Op;
+use crate::canonical::to_hex;
+use crate::cost::TraceShape;
+use crate::keyswitch_air::EvaluationKey;
+use crate::preflight::{check_result_shape, check_signature, VerifyPolicy};
+use crate::verifying::{
+    check_evaluation_key, decode_data, evaluation_key_digits, parse_data, verify_proof,
+    VerificationFailure,
+};
+
+// Parameter set shared by every proof of a session.
+pub struct SessionParams {
+    pub op: Op,
+    pub options: ProofOptions,
+    // key of relinearization, key-switching, decryption and encryption proofs
+    pub evaluation_key: Option<EvaluationKey>,
+    // opens containers sealed to the application, see sealed.rs
+    pub decryption_key: Option<StaticSecret>,
+}
+
+pub struct Verifier;
+
+impl Verifier {
+    // Opens a session verifying proofs with `params` which `policy` accepts. Fails when the
+    // policy refuses the circuit or the evaluation key does not fit it.
+    pub fn session(
+        params: SessionParams,
+        policy: VerifyPolicy,
+    ) -> Result<VerifierSession, VerificationFailure> {
+        use VerificationFailure::ParamsRejected;
+        let op = params.op;
+        policy.check_circuit(op).map_err(ParamsRejected)?;
+        let evaluation_key = match (evaluation_key_digits(op), params.evaluation_key) {
+            (Some(_), Some(key)) => {
+                let digest = to_hex(&key.digest());
+                check_evaluation_key(op, &key, "of the session", Some(&digest))?;
+                Some((key, digest))
+            }
+            (Some(_), None) => {
+                return Err(ParamsRejected(format!(
+                    "{} proofs need an evaluation key",
+                    op.name()
+                )))
+            }
+            (None, Some(_)) => {
+                return Err(ParamsRejected(format!(
+                    "{} proofs take no evaluation key",
+                    op.name()
+                )))
+            }
+            (None, None) => None,
+        };
+        Ok(VerifierSession {
+            op,
+            options: params.options,
+            shape: TraceShape::of(op),
+            evaluation_key,
+            decryption_key: params.decryption_key,
+            policy,
+        })
+    }
+}
+
+pub struct VerifierSession {
+    op: Op,
+    options: ProofOptions,
+    shape: TraceShape,
+    // the key with its hex digest, the form proofs record it in
+    evaluation_key: Option<(EvaluationKey, String)>,
+    decryption_key: Option<StaticSecret>,
+    policy: VerifyPolicy,
+}
+
+impl VerifierSession {
+    pub fn op(&self) -> Op {
+        self.op
+    }
+
+    pub fn options(&self) -> &ProofOptions {
+        &self.options
+    }
+
+    // Verifies the proof container `bytes`, named `name` in error messages.
+    pub fn verify(&self, bytes: Vec<u8>, name: &str) -> Result<(), VerificationFailure> {
+        use VerificationFailure::{BadFormat, ParamsRejected, PublicInputMismatch};
+        self.policy
+            .check_size(bytes.len())
+            .map_err(ParamsRejected)?;
+        let data = decode_data(bytes, self.decryption_key.as_ref(), name)?;
+        if data.circuit != self.op.name() {
+            return Err(ParamsRejected(format!(
+                "{} is a {} proof, the session verifies {} proofs",
+                name,
+                data.circuit,
+                self.op.name()
+            )));
+        }
+        check_result_shape(self.op, &data).map_err(BadFormat)?;
+        let recorded = data.evaluation_key_digest.clone();
+        match (&self.evaluation_key, recorded) {
+            (Some((_, digest)), Some(recorded)) if recorded != *digest => {
+                return Err(PublicInputMismatch(format!(
+                    "{} was generated with evaluation key {}, the session has {}",
+                    name, recorded, digest
+                )))
+            }
+            (Some(_), None) => {
+                return Err(BadFormat(String::from(
+                    "proof does not record the digest of its evaluation key",
+                )))
+            }
+            (None, Some(_)) => {
+                return Err(BadFormat(format!(
+                    "{} proofs take no evaluation key, but one is recorded",
+                    self.op.name()
+                )))
+            }
+            _ => {}
+        }
+
+        let (op, mut pub_inputs, proof) = parse_data(data)?;
+        if *proof.options() != self.options {
+            return Err(ParamsRejected(format!(
+                "{} was generated with other proof options than the session's",
+                name
+            )));
+        }
+        check_signature(op, &self.shape, &proof).map_err(ParamsRejected)?;
+        self.policy.check_security(&proof).map_err(ParamsRejected)?;
+        pub_inputs.evaluation_key = self.evaluation_key.as_ref().map(|(key, _)| key.clone());
+        verify_proof(op, proof, pub_inputs)
+    }
+}
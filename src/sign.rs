@@ -0,0 +1,191 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Sign extraction by the polynomial trick. For an odd prime plaintext modulus t, a value x of Z_t
+// read as a centred residue in -(t-1)/2..(t-1)/2 is negative iff x is in (t+1)/2..t-1, and by
+// Fermat's little theorem 1 - (x - a)^(t-1) is 1 for x = a and 0 otherwise, so
+//
+//     S(x) = sum_{a=(t+1)/2}^{t-1} (1 - (x - a)^(t-1))
+//
+// is 1 for negative x and 0 otherwise, a polynomial of degree d = t - 1. Evaluating S on a
+// ciphertext extracts the sign, the most significant bit of the centred value, without
+// decrypting it, e.g. x < y as S(x - y) for |x - y| <= (t-1)/2.
+//
+// The gadget evaluates S on a ciphertext x within one row by Horner's rule, as HornerAir does
+// across rows: p_0 = s_d, p_j = p_(j-1) * x + s_(d-j), so that for every component c of p_j
+//
+//     r_jc = p_(j-1),c * x_0 + p_(j-1),(c-1) * x_1 + [c = 0] s_(d-j)  mod m
+//
+// with a partial column r_jc, and p_d, with d + 1 components, is the sign. The coefficients are
+// added unscaled, which is the plaintext embedding of BGV, and the products are tensor products
+// without relinearization, so the gadget is exact for BGV ciphertexts given in evaluation form.
+// A level takes d * (d + 3) / 2 partial columns; the gadget gives the right-hand sides, quadratic
+// but for those of p_1, and the circuit reduces them to the partials, e.g. with the Barrett
+// blocks of reduction.rs. Every right-hand side stays below 2 * m^2 + m, far below the field
+// modulus for moduli of up to 62 bits.
+
+use winter_math::FieldElement;
+
+use crate::air::{element_from_u64, BaseElement};
+use crate::ntt_air::pow_mod;
+
+// Coefficients s_0..s_(t-1) of the sign polynomial modulo the odd prime `t`.
+pub fn sign_coefficients(t: u64) -> Vec<u64> {
+    let mut coefficients = vec![0; t as usize];
+    for a in t.div_ceil(2)..t {
+        coefficients[0] = (coefficients[0] + 1) % t;
+        // (x - a)^(t-1) = sum_k C(t-1, k) x^k (t - a)^(t-1-k), and C(t-1, k) = (-1)^k mod t
+        for (k, coefficient) in coefficients.iter_mut().enumerate() {
+            let power = pow_mod(t - a, t - 1 - k as u64, t);
+            let term = if k % 2 == 0 { power } else { (t - power) % t };
+            *coefficient = (*coefficient + t - term) % t;
+        }
+    }
+    coefficients
+}
+
+// Number of partial columns of one level for a polynomial of degree `degree`.
+pub const fn sign_partial_num(degree: usize) -> usize {
+    degree * (degree + 3) / 2
+}
+
+// Index of component `c` of p_j among the partial columns of a level.
+pub fn partial_index(j: usize, c: usize) -> usize {
+    (j - 1) * (j + 2) / 2 + c
+}
+
+// Partial values of one level with modulus `m` for the ciphertext coefficient `x` and the
+// coefficients s_0..s_d, each below `m`, and the right-hand sides reduced to them.
+pub fn sign_partials(x: [u64; 2], coefficients: &[u64], m: u64) -> (Vec<u64>, Vec<u128>) {
+    let d = coefficients.len() - 1;
+    let mut partials = vec![0; sign_partial_num(d)];
+    let mut values = vec![0; sign_partial_num(d)];
+    let mut previous = vec![coefficients[d]];
+    for j in 1..=d {
+        let mut current = vec![0; j + 1];
+        for c in 0..=j {
+            let mut sum = if c == 0 {
+                coefficients[d - j] as u128
+            } else {
+                0
+            };
+            if c < j {
+                sum += previous[c] as u128 * x[0] as u128;
+            }
+            if c > 0 {
+                sum += previous[c - 1] as u128 * x[1] as u128;
+            }
+            current[c] = (sum % m as u128) as u64;
+            partials[partial_index(j, c)] = current[c];
+            values[partial_index(j, c)] = sum;
+        }
+        previous = current;
+    }
+    (partials, values)
+}
+
+// Right-hand sides of one level, in the order of the partials, for the Horner evaluation of the
+// polynomial with `coefficients` on the ciphertext coefficient `x` with `partials`; a correct row
+// reduces them to the partials.
+pub fn sign_values<E: FieldElement>(x: [E; 2], coefficients: &[E], partials: &[E]) -> Vec<E> {
+    let d = coefficients.len() - 1;
+    let mut values = vec![E::ZERO; sign_partial_num(d)];
+    for j in 1..=d {
+        let previous = |c: usize| {
+            if j == 1 {
+                coefficients[d]
+            } else {
+                partials[partial_index(j - 1, c)]
+            }
+        };
+        for c in 0..=j {
+            let mut sum = if c == 0 { coefficients[d - j] } else { E::ZERO };
+            if c < j {
+                sum += previous(c) * x[0];
+            }
+            if c > 0 {
+                sum += previous(c - 1) * x[1];
+            }
+            values[partial_index(j, c)] = sum;
+        }
+    }
+    values
+}
+
+// Coefficients or partials as field elements, for `sign_values`.
+pub fn sign_elements(values: &[u64]) -> Vec<BaseElement> {
+    values.iter().map(|&x| element_from_u64(x)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::element_from_u128;
+    use crate::ntt_air::mul_mod;
+
+    #[test]
+    fn polynomial_is_the_sign() {
+        for t in [3, 5, 7, 11, 13] {
+            let coefficients = sign_coefficients(t);
+            assert_eq!(coefficients.len() as u64, t);
+            for x in 0..t {
+                let value = coefficients
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, &s| (acc * x + s) % t);
+                assert_eq!(value, (x > (t - 1) / 2) as u64, "S({}) mod {}", x, t);
+            }
+        }
+    }
+
+    // A BGV encryption (v + t * e - a * s, a) of every value in a scalar slot, whose sign
+    // decrypts from the tensor powers of s.
+    #[test]
+    fn gadget_extracts_the_sign() {
+        let (t, m, s, a) = (7u64, (1u64 << 61) - 1, 123_456_789u64, 987_654_321u64);
+        let coefficients = sign_coefficients(t);
+        let elements = sign_elements(&coefficients);
+        let d = coefficients.len() - 1;
+        for v in 0..t {
+            let c0 = (v + t + m - mul_mod(a, s, m)) % m;
+            let (partials, values) = sign_partials([c0, a], &coefficients, m);
+
+            let computed = sign_values(
+                [element_from_u64(c0), element_from_u64(a)],
+                &elements,
+                &sign_elements(&partials),
+            );
+            for (i, &value) in values.iter().enumerate() {
+                assert_eq!(computed[i], element_from_u128(value));
+                assert_eq!(value % m as u128, partials[i] as u128);
+            }
+
+            let (mut decrypted, mut power) = (0, 1);
+            for c in 0..=d {
+                decrypted = (decrypted + mul_mod(partials[partial_index(d, c)], power, m)) % m;
+                power = mul_mod(power, s, m);
+            }
+            let centred = decrypted as i64 - if decrypted > m / 2 { m as i64 } else { 0 };
+            assert_eq!(centred.rem_euclid(t as i64), (v > (t - 1) / 2) as i64);
+        }
+    }
+
+    // a wrong partial changes the right-hand sides of the next step
+    #[test]
+    fn wrong_partials_are_rejected() {
+        let coefficients = sign_coefficients(5);
+        let (mut partials, values) = sign_partials([3, 4], &coefficients, 65537);
+        partials[partial_index(2, 1)] += 1;
+        let elements = sign_values(
+            [element_from_u64(3), element_from_u64(4)],
+            &sign_elements(&coefficients),
+            &sign_elements(&partials),
+        );
+        assert_ne!(
+            elements[partial_index(3, 1)],
+            element_from_u128(values[partial_index(3, 1)])
+        );
+    }
+}
@@ -0,0 +1,1105 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+
+use stark_he::air::{InputArg, Op};
+use stark_he::constraint_export::{ConstraintExporter, ExportFormat};
+use stark_he::cost::{CostEstimate, TraceShape};
+use stark_he::explain::RowExplainer;
+use stark_he::lde_cache::LdeCache;
+use stark_he::manifest::{build_dag, to_dot, Dag, Manifest};
+use stark_he::preflight::{preflight, VerifyPolicy};
+use stark_he::proving::{
+    input_proof_options, new_proof_options, prove_to_file, visit_circuit, ProofOptionsConfig,
+};
+use stark_he::scheme::Scheme;
+use stark_he::status::{Phase, StatusReporter};
+#[cfg(feature = "tui")]
+use stark_he::tui;
+#[cfg(not(feature = "offline"))]
+use stark_he::verify_server;
+use stark_he::verifying::{
+    cache_key, load_data, proof_hash, verify_data, VerificationCache, VerificationFailure,
+};
+use stark_he::{analysis, config, cost, diff, lint, oplog, packed, registry, sealed};
+
+#[derive(Parser)]
+#[clap(name = "stark-he", author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check a data file for structural, range and consistency problems without proving
+    LintData(LintDataArgs),
+    /// Convert an add data file to the compressed binary format
+    PackData(PackDataArgs),
+    /// Build a manifest and data files from an HE library operation log and ciphertext snapshots
+    ImportLog(ImportLogArgs),
+    /// Print the proving schedule of a manifest and export its dependency graph
+    Graph(GraphArgs),
+    /// Estimate proving time, memory and proof size of a manifest without proving
+    Estimate(EstimateArgs),
+    /// Prove every operation of a manifest in dependency order
+    ProveManifest(ProveManifestArgs),
+    /// Verify every proof of a manifest, skipping proofs already known to pass
+    VerifyManifest(VerifyManifestArgs),
+    /// Check proof containers for everything but the STARK verification itself
+    Preflight(PreflightArgs),
+    /// Compare headers, parameters, public inputs and commitments of two proofs
+    DiffProof(DiffProofArgs),
+    /// Print or export coefficient histograms and noise estimates for operands and results
+    Analyze(AnalyzeArgs),
+    /// Print a trace row with every transition constraint evaluated on it
+    Explain(ExplainArgs),
+    /// Export the transition constraints of a circuit as polynomials for external analysis
+    ExportConstraints(ExportConstraintsArgs),
+    /// List the supported circuits or check whether a circuit version is supported
+    Circuits(CircuitsArgs),
+    /// Inspect the layered configuration of the prover
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+    /// Generate an X25519 key pair for sealed proof containers
+    Keygen(KeygenArgs),
+    /// Encrypt a proof container to a recipient's X25519 public key
+    Seal(SealArgs),
+    /// Decrypt a sealed proof container with the recipient's secret key
+    Unseal(UnsealArgs),
+    /// Show queued and running jobs, recent proofs and verification results
+    #[cfg(feature = "tui")]
+    Tui(TuiArgs),
+    /// Serve stateless proof verification over HTTP (POST /verify)
+    #[cfg(not(feature = "offline"))]
+    VerifyServer(VerifyServerArgs),
+}
+
+#[derive(Args)]
+struct LintDataArgs {
+    data_file_path: String,
+    /// Also compare the results claimed by this proof file against the data file
+    #[clap(long)]
+    proof: Option<String>,
+}
+
+#[derive(Args)]
+struct PackDataArgs {
+    data_file_path: String,
+    /// Packed file to write
+    #[clap(long)]
+    out: String,
+    /// Overwrite the packed file if it already exists
+    #[clap(long)]
+    force: bool,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args)]
+struct TuiArgs {
+    /// Directory passed as --status-dir to the prover and verifier
+    #[clap(long, default_value_t = String::from("./status"))]
+    status_dir: String,
+    /// Refresh interval in milliseconds
+    #[clap(long, default_value_t = 1000)]
+    refresh_ms: u64,
+    /// Number of recent proofs and verifications to show
+    #[clap(long, default_value_t = 10)]
+    recent: usize,
+}
+
+#[cfg(not(feature = "offline"))]
+#[derive(Args)]
+struct VerifyServerArgs {
+    /// Address to listen on
+    #[clap(long, default_value_t = String::from("127.0.0.1:8650"))]
+    listen: String,
+    /// Number of verification workers; connections are read on threads of their own
+    #[clap(long, default_value_t = 8)]
+    threads: usize,
+    /// Per-request latency target in milliseconds, decoding included; slower requests are
+    /// reported
+    #[clap(long, default_value_t = 20.0)]
+    latency_target_ms: f64,
+    /// Largest accepted request body in KiB
+    #[clap(long, default_value_t = 16384)]
+    max_body_kb: usize,
+    /// Evaluation key file to preload for relinearization and key-switching proofs, repeatable
+    #[clap(long, multiple_occurrences = true)]
+    evaluation_key: Vec<String>,
+    /// TOML policy file with the accepted `circuits`, `min_security_bits` and `max_container_kb`
+    #[clap(long)]
+    policy: Option<String>,
+    /// X25519 secret key file opening proof containers sealed to the server
+    #[clap(long)]
+    decryption_key: Option<String>,
+}
+
+#[derive(Args)]
+struct PreflightArgs {
+    #[clap(required = true)]
+    proofs: Vec<String>,
+    /// TOML policy file with the accepted `circuits`, `min_security_bits` and `max_container_kb`
+    #[clap(long)]
+    policy: Option<String>,
+    /// X25519 secret key file opening sealed proof containers
+    #[clap(long)]
+    decryption_key: Option<String>,
+}
+
+#[derive(Args)]
+struct ImportLogArgs {
+    log: String,
+    /// Directory holding a <ciphertext id>.toml snapshot per input [default: the log's directory]
+    #[clap(long)]
+    snapshots: Option<String>,
+    /// Directory receiving manifest.toml and the data files
+    #[clap(long, default_value_t = String::from("."))]
+    out: String,
+    /// Overwrite files which already exist
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct GraphArgs {
+    manifest: String,
+    /// Write the dependency graph in Graphviz format to this file ("-" for stdout)
+    #[clap(long)]
+    dot: Option<String>,
+}
+
+#[derive(Args)]
+struct EstimateArgs {
+    manifest: String,
+    #[clap(flatten)]
+    proof_options: ProofOptionsConfig,
+}
+
+#[derive(Args)]
+struct ProveManifestArgs {
+    manifest: String,
+    #[clap(flatten)]
+    proof_options: ProofOptionsConfig,
+    /// Overwrite proof files which already exist
+    #[clap(long)]
+    force: bool,
+    /// Directory receiving job status files read by `stark-he tui`
+    #[clap(long)]
+    status_dir: Option<String>,
+    /// Memory in MiB for extended trace columns shared between the proofs, 0 disables the cache
+    #[clap(long, default_value_t = 0)]
+    lde_cache_mb: usize,
+}
+
+#[derive(Args)]
+struct VerifyManifestArgs {
+    manifest: String,
+    /// Only re-verify proofs whose cached result is a failure
+    #[clap(long)]
+    only_failed: bool,
+    /// Verification cache [default: .stark-he-verify-cache.toml next to the manifest]
+    #[clap(long)]
+    cache: Option<String>,
+    /// Ignore cached results and verify everything again
+    #[clap(long, conflicts_with = "only-failed")]
+    no_cache: bool,
+}
+
+#[derive(Args)]
+struct DiffProofArgs {
+    left: String,
+    right: String,
+}
+
+#[derive(Args)]
+struct CircuitsArgs {
+    /// Circuit identifier, e.g. stark-he/mul
+    id: Option<String>,
+    /// Circuit version MAJOR.MINOR.PATCH, defaults to the version of this build
+    #[clap(requires = "id")]
+    version: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print every prover option with a value and where it comes from, as a config file
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    Show(ConfigShowArgs),
+}
+
+#[derive(Args)]
+struct ConfigShowArgs {
+    /// Prover arguments, as they would be passed to the prover
+    #[clap(allow_hyphen_values = true, multiple_values = true)]
+    args: Vec<String>,
+}
+
+#[derive(Args)]
+struct KeygenArgs {
+    /// Path of the key pair without extension: writes <OUT>.pub and <OUT>.key
+    #[clap(long)]
+    out: String,
+    /// Overwrite key files which already exist
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct SealArgs {
+    proof: String,
+    /// Public key file of the recipient
+    #[clap(long)]
+    recipient: String,
+    /// Sealed container to write
+    #[clap(long)]
+    out: String,
+    /// Overwrite the sealed container if it already exists
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct UnsealArgs {
+    sealed: String,
+    /// Secret key file of the recipient
+    #[clap(long)]
+    key: String,
+    /// Proof container to write
+    #[clap(long)]
+    out: String,
+    /// Overwrite the proof container if it already exists
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct ExplainArgs {
+    #[clap(flatten)]
+    input_args: InputArg,
+    #[clap(flatten)]
+    proof_options: ProofOptionsConfig,
+    /// Trace row to explain, checked against the row after it
+    #[clap(long)]
+    row: usize,
+}
+
+#[derive(Args)]
+struct ExportConstraintsArgs {
+    #[clap(flatten)]
+    input_args: InputArg,
+    #[clap(flatten)]
+    proof_options: ProofOptionsConfig,
+    /// `json` for the constraints, periodic columns and assertions, `csv` for one line per term
+    #[clap(long, arg_enum, default_value_t = ExportFormat::Json)]
+    format: ExportFormat,
+    /// Write the export to this file instead of printing it
+    #[clap(long)]
+    out: Option<String>,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    data_file_path: String,
+    /// Take results from this proof file instead of recomputing them
+    #[clap(long)]
+    proof: Option<String>,
+    /// Number of bins of the magnitude histogram
+    #[clap(long, default_value_t = 16)]
+    bins: usize,
+    /// Write all histograms to this CSV file instead of printing them
+    #[clap(long)]
+    csv: Option<String>,
+}
+
+fn load_manifest(path: &str) -> Result<(Manifest, Dag), String> {
+    let manifest = Manifest::load(path)?;
+    let dag = build_dag(&manifest)?;
+    Ok((manifest, dag))
+}
+
+fn import_log(args: &ImportLogArgs) -> i32 {
+    let log = Path::new(&args.log);
+    let snapshots = match &args.snapshots {
+        Some(dir) => PathBuf::from(dir),
+        None => log.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let import = match oplog::import_log(log, &snapshots) {
+        Ok(import) => import,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let manifest = match toml::to_string(&import.manifest) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("error: cannot serialize manifest: {}", err);
+            return 1;
+        }
+    };
+    let out = Path::new(&args.out);
+    let files: Vec<(PathBuf, &str)> = import
+        .data_files
+        .iter()
+        .map(|(name, text)| (out.join(name), text.as_str()))
+        .chain(std::iter::once((
+            out.join("manifest.toml"),
+            manifest.as_str(),
+        )))
+        .collect();
+    if !args.force {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            eprintln!(
+                "error: {} already exists, pass --force to overwrite it",
+                path.display()
+            );
+            return 1;
+        }
+    }
+    if let Err(err) = std::fs::create_dir_all(out) {
+        eprintln!("error: cannot create {}: {}", out.display(), err);
+        return 1;
+    }
+    for (path, text) in files.iter() {
+        if let Err(err) = std::fs::write(path, text) {
+            eprintln!("error: cannot write {}: {}", path.display(), err);
+            return 1;
+        }
+    }
+    println!(
+        "imported {} operations into {}",
+        import.manifest.operations.len(),
+        out.join("manifest.toml").display()
+    );
+    0
+}
+
+fn graph(args: &GraphArgs) -> i32 {
+    let (manifest, dag) = match load_manifest(&args.manifest) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let dot = to_dot(&manifest);
+    match args.dot.as_deref() {
+        Some("-") => print!("{}", dot),
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, dot) {
+                eprintln!("error: cannot write {}: {}", path, err);
+                return 1;
+            }
+        }
+        None => {}
+    }
+    if args.dot.as_deref() != Some("-") {
+        for (step, &i) in dag.schedule.iter().enumerate() {
+            let op = &manifest.operations[i];
+            let after: Vec<&str> = dag.dependencies[i]
+                .iter()
+                .map(|&dep| manifest.operations[dep].id.as_str())
+                .collect();
+            println!(
+                "{:>4}. {} ({}: {} -> {}){}",
+                step + 1,
+                op.id,
+                op.circuit,
+                op.inputs.join(", "),
+                op.output,
+                if after.is_empty() {
+                    String::new()
+                } else {
+                    format!(" after {}", after.join(", "))
+                }
+            );
+        }
+    }
+    0
+}
+
+fn print_estimate(name: &str, circuit: &str, width: Option<usize>, estimate: &CostEstimate) {
+    println!(
+        "{:<24} {:<11} {:>6} {:>9} {:>9.2} s {:>11} {:>11}",
+        name,
+        circuit,
+        width.map(|width| width.to_string()).unwrap_or_default(),
+        estimate.lde_rows,
+        estimate.seconds,
+        cost::format_bytes(estimate.memory_bytes),
+        cost::format_bytes(estimate.proof_bytes)
+    );
+}
+
+fn estimate(args: &EstimateArgs) -> i32 {
+    let (manifest, dag) = match load_manifest(&args.manifest) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let options = match new_proof_options(&args.proof_options) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let mut shapes = Vec::with_capacity(dag.schedule.len());
+    let mut estimates = Vec::with_capacity(dag.schedule.len());
+    println!(
+        "{:<24} {:<11} {:>6} {:>9} {:>11} {:>11} {:>11}",
+        "operation", "circuit", "width", "lde rows", "time", "memory", "proof"
+    );
+    for &i in dag.schedule.iter() {
+        let op = &manifest.operations[i];
+        let shape = match Op::from_name(&op.circuit) {
+            Some(circuit @ (Op::Sum | Op::InnerProduct)) => {
+                TraceShape::sum(circuit, op.inputs.len())
+            }
+            Some(circuit) => TraceShape::of(circuit),
+            None => {
+                eprintln!(
+                    "error: operation {} uses unsupported circuit {}",
+                    op.id, op.circuit
+                );
+                return 1;
+            }
+        };
+        let estimate = cost::estimate(&shape, &options);
+        print_estimate(&op.id, &op.circuit, Some(shape.width), &estimate);
+        shapes.push(shape);
+        estimates.push(estimate);
+    }
+    if estimates.is_empty() {
+        return 0;
+    }
+    println!();
+    print_estimate(
+        &format!("chained ({} proofs)", estimates.len()),
+        "",
+        None,
+        &CostEstimate::chain(&estimates),
+    );
+    if let Some(composed) = TraceShape::compose(&shapes) {
+        print_estimate(
+            "composed (1 proof)",
+            "",
+            Some(composed.width),
+            &cost::estimate(&composed, &options),
+        );
+    }
+    0
+}
+
+fn prove_manifest(args: &ProveManifestArgs) -> i32 {
+    let (manifest, dag) = match load_manifest(&args.manifest) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    for op in manifest.operations.iter() {
+        if Op::from_name(&op.circuit).is_none() {
+            eprintln!(
+                "error: operation {} uses unsupported circuit {}",
+                op.id, op.circuit
+            );
+            return 1;
+        }
+        let proof_path = manifest.proof_path(op);
+        if proof_path.exists() && !args.force {
+            eprintln!(
+                "error: {} already exists, pass --force to overwrite it",
+                proof_path.display()
+            );
+            return 1;
+        }
+    }
+
+    let mut reporters: Vec<StatusReporter> = manifest
+        .operations
+        .iter()
+        .map(|op| StatusReporter::new(args.status_dir.as_deref(), &op.id))
+        .collect();
+    for status in reporters.iter_mut() {
+        status.restart();
+        status.set_phase(Phase::Queued);
+    }
+
+    let mut proof_options = args.proof_options.clone();
+    if let Some(hash_fn) = manifest.hash_fn {
+        proof_options.hash_fn = hash_fn;
+    }
+    let options = match new_proof_options(&proof_options) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let lde_cache = (args.lde_cache_mb > 0).then(|| LdeCache::shared(args.lde_cache_mb << 20));
+    for (step, &i) in dag.schedule.iter().enumerate() {
+        let op = &manifest.operations[i];
+        let proof_path = manifest.proof_path(op);
+        println!(
+            "[{}/{}] proving {} -> {}",
+            step + 1,
+            dag.schedule.len(),
+            op.id,
+            proof_path.display()
+        );
+        let input_args = InputArg {
+            data_file_path: manifest.data_path(op).to_string_lossy().into_owned(),
+            op: Op::from_name(&op.circuit).unwrap(),
+            evaluation_key: manifest
+                .evaluation_key_path(op)
+                .map(|path| path.to_string_lossy().into_owned()),
+            rotation: op.rotation,
+            expose: op.expose.clone(),
+            scheme: op.scheme.unwrap_or(Scheme::Bfv),
+            coeff_degree: None,
+            params: None,
+            coeffs_per_row: None,
+            he_security: None,
+            operands: Vec::new(),
+        };
+        if let Err(err) = prove_to_file(
+            &input_args,
+            options.clone(),
+            lde_cache.as_ref(),
+            None,
+            None,
+            &proof_path,
+            &mut reporters[i],
+        ) {
+            eprintln!("error: {}: {}", op.id, err);
+            return 1;
+        }
+    }
+    if let Some(lde_cache) = lde_cache {
+        let lde_cache = lde_cache.lock().unwrap();
+        println!(
+            "LDE cache: {} of {} columns reused, {} held",
+            lde_cache.hits,
+            lde_cache.hits + lde_cache.misses,
+            cost::format_bytes(lde_cache.used_bytes())
+        );
+    }
+    0
+}
+
+fn verify_manifest(args: &VerifyManifestArgs) -> i32 {
+    let (manifest, dag) = match load_manifest(&args.manifest) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let cache_path = match &args.cache {
+        Some(path) => std::path::PathBuf::from(path),
+        None => manifest.base_dir.join(".stark-he-verify-cache.toml"),
+    };
+    let mut cache = VerificationCache::load(&cache_path);
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for &i in dag.schedule.iter() {
+        let op = &manifest.operations[i];
+        let proof_path = manifest.proof_path(op);
+        let circuit = match Op::from_name(&op.circuit) {
+            Some(circuit) => circuit,
+            None => {
+                println!("FAIL {}: unsupported circuit {}", op.id, op.circuit);
+                failed += 1;
+                continue;
+            }
+        };
+        let key = match proof_hash(&proof_path) {
+            Ok(hash) => cache_key(&hash, circuit),
+            Err(err) => {
+                println!("FAIL {}: {}", op.id, err);
+                failed += 1;
+                continue;
+            }
+        };
+        let cached = if args.no_cache {
+            None
+        } else {
+            cache.get(&key).map(|entry| entry.passed)
+        };
+        let wanted = match cached {
+            Some(true) => false,
+            Some(false) => true,
+            None => !args.only_failed,
+        };
+        if !wanted {
+            skipped += 1;
+            continue;
+        }
+
+        let evaluation_key = manifest
+            .evaluation_key_path(op)
+            .map(|path| path.to_string_lossy().into_owned());
+        let result =
+            load_data(&proof_path).and_then(|data| verify_data(data, evaluation_key.as_deref()));
+        match &result {
+            Ok(_) => {
+                println!("ok   {}", op.id);
+                passed += 1;
+            }
+            Err(err) => {
+                println!("FAIL {} ({}): {}", op.id, err.kind(), err);
+                failed += 1;
+            }
+        }
+        cache.record(key, &op.id, &result);
+        if let Err(err) = cache.store(&cache_path) {
+            eprintln!("warning: {}", err);
+        }
+    }
+
+    println!(
+        "{} passed, {} failed, {} skipped (cached or not selected)",
+        passed, failed, skipped
+    );
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn preflight_proofs(args: &PreflightArgs) -> i32 {
+    let setup = args
+        .policy
+        .as_deref()
+        .map(VerifyPolicy::load)
+        .transpose()
+        .and_then(|policy| {
+            let key = args
+                .decryption_key
+                .as_ref()
+                .map(|path| sealed::load_secret_key(Path::new(path)))
+                .transpose()?;
+            Ok((policy.unwrap_or_default(), key))
+        });
+    let (policy, key) = match setup {
+        Ok(setup) => setup,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 2;
+        }
+    };
+    let mut rejected = 0;
+    for path in args.proofs.iter() {
+        let result = std::fs::read(path)
+            .map_err(|err| VerificationFailure::BadFormat(format!("cannot read {}: {}", path, err)))
+            .and_then(|bytes| preflight(bytes, key.as_ref(), &policy, path));
+        match result {
+            Ok(checked) => println!(
+                "{}: ok ({} {}, {} bits, {})",
+                path,
+                checked.op.info().id,
+                checked.op.info().version,
+                checked.proof.security_level(true),
+                cost::format_bytes(checked.container_bytes)
+            ),
+            Err(err) => {
+                rejected += 1;
+                println!("{}: rejected ({}): {}", path, err.kind(), err);
+            }
+        }
+    }
+    if rejected > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn diff_proof(args: &DiffProofArgs) -> i32 {
+    let result = load_data(Path::new(&args.left))
+        .map_err(String::from)
+        .and_then(|left| Ok((left, load_data(Path::new(&args.right))?)))
+        .and_then(|(left, right)| diff::diff_proofs(left, right));
+    match result {
+        Ok(differences) if differences.is_empty() => {
+            println!("proofs are identical");
+            0
+        }
+        Ok(differences) => {
+            println!(
+                "proofs diverge first in section [{}]",
+                differences[0].section
+            );
+            for difference in differences.iter() {
+                println!("{}", difference);
+            }
+            1
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            2
+        }
+    }
+}
+
+fn config_show(args: &ConfigShowArgs) -> i32 {
+    let command = <config::ProverCli as clap::CommandFactory>::command();
+    let prover_args = std::iter::once("prover")
+        .chain(args.args.iter().map(String::as_str))
+        .map(OsString::from);
+    let layered = match config::layer(&command, prover_args, |name| std::env::var(name).ok())
+        .and_then(|layered| layered.parse().map(|_| layered))
+    {
+        Ok(layered) => layered,
+        Err(err) => {
+            let _ = err.print();
+            return 1;
+        }
+    };
+    for setting in &layered.settings {
+        let values: Vec<String> = setting
+            .values
+            .iter()
+            .map(|value| toml::Value::String(value.clone()).to_string())
+            .collect();
+        let value = match values.as_slice() {
+            [value] => value.clone(),
+            values => format!("[{}]", values.join(", ")),
+        };
+        println!("{} = {}  # {}", setting.key, value, setting.source);
+    }
+    0
+}
+
+fn circuits(args: &CircuitsArgs) -> i32 {
+    let id = match &args.id {
+        Some(id) => id,
+        None => {
+            for info in registry::CIRCUITS.iter() {
+                println!(
+                    "{:<20} {:<8} --op {:<11} {}",
+                    info.id,
+                    info.version.to_string(),
+                    info.op.name(),
+                    info.summary
+                );
+            }
+            return 0;
+        }
+    };
+    let version = match &args.version {
+        Some(text) => registry::Version::parse(text),
+        None => registry::lookup(id)
+            .map(|info| info.version)
+            .ok_or_else(|| format!("unknown circuit {}", id)),
+    };
+    match version.and_then(|version| registry::check_support(id, &version).map(|_| version)) {
+        Ok(version) => {
+            println!("{} {}: supported", id, version);
+            0
+        }
+        Err(err) => {
+            println!("unsupported: {}", err);
+            1
+        }
+    }
+}
+
+fn explain(args: &ExplainArgs) -> i32 {
+    let explainer = RowExplainer { row: args.row };
+    let result = input_proof_options(&args.input_args, &args.proof_options)
+        .map_err(|err| err.to_string())
+        .and_then(|options| visit_circuit(&args.input_args, options, explainer));
+    match result {
+        Ok(text) => {
+            print!("{}", text);
+            0
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+fn export_constraints(args: &ExportConstraintsArgs) -> i32 {
+    let exporter = ConstraintExporter {
+        format: args.format,
+    };
+    let result = input_proof_options(&args.input_args, &args.proof_options)
+        .map_err(|err| err.to_string())
+        .and_then(|options| visit_circuit(&args.input_args, options, exporter));
+    let text = match result {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    match &args.out {
+        Some(path) => match std::fs::write(path, text) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("error: cannot write {}: {}", path, err);
+                1
+            }
+        },
+        None => {
+            print!("{}", text);
+            0
+        }
+    }
+}
+
+fn analyze(args: &AnalyzeArgs) -> i32 {
+    let series = match analysis::load_series(&args.data_file_path, args.proof.as_deref()) {
+        Ok(series) => series,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let stats: Vec<_> = series
+        .iter()
+        .map(|s| analysis::analyze(s, args.bins))
+        .collect();
+    match &args.csv {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, analysis::render_csv(&stats)) {
+                eprintln!("error: cannot write {}: {}", path, err);
+                return 1;
+            }
+        }
+        None => {
+            for s in stats.iter() {
+                println!("{}", analysis::render_text(s));
+            }
+        }
+    }
+    0
+}
+
+fn lint_data(args: &LintDataArgs) -> i32 {
+    let report = lint::lint_data(&args.data_file_path, args.proof.as_deref());
+    for finding in report.findings.iter() {
+        println!("{}", finding);
+    }
+    if report.findings.is_empty() {
+        println!("{}: ok", args.data_file_path);
+    }
+    if report.has_errors() {
+        1
+    } else {
+        0
+    }
+}
+
+fn pack_data(args: &PackDataArgs) -> i32 {
+    let data = match packed::read_custom_data(&args.data_file_path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    if data.levels.is_some() {
+        eprintln!(
+            "error: packed files hold every level of their moduli, drop the inactive moduli and \
+             Levels of {} first",
+            args.data_file_path
+        );
+        return 1;
+    }
+    let out = Path::new(&args.out);
+    if out.exists() && !args.force {
+        eprintln!(
+            "error: {} already exists, pass --force to overwrite it",
+            out.display()
+        );
+        return 1;
+    }
+    let bytes = packed::encode_custom_data(&data);
+    if let Err(err) = std::fs::write(out, &bytes) {
+        eprintln!("error: cannot write {}: {}", out.display(), err);
+        return 1;
+    }
+    let raw_bytes = 8 * data
+        .values
+        .iter()
+        .flatten()
+        .flatten()
+        .map(Vec::len)
+        .sum::<usize>();
+    println!(
+        "{}: {} ({:.1}x smaller than raw u64 coefficients)",
+        out.display(),
+        cost::format_bytes(bytes.len()),
+        raw_bytes as f64 / bytes.len() as f64
+    );
+    0
+}
+
+// Writes `bytes` to `path` unless it exists and `force` is not set; `private` files are only
+// readable by their owner.
+fn write_output(path: &Path, bytes: &[u8], force: bool, private: bool) -> Result<(), String> {
+    if path.exists() && !force {
+        return Err(format!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        ));
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options
+        .open(path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, bytes))
+        .map_err(|err| format!("cannot write {}: {}", path.display(), err))
+}
+
+fn keygen(args: &KeygenArgs) -> i32 {
+    let secret = sealed::generate_secret_key();
+    let public = x25519_dalek::PublicKey::from(&secret);
+    let public_path = PathBuf::from(format!("{}.pub", args.out));
+    let secret_path = PathBuf::from(format!("{}.key", args.out));
+    let result = write_output(
+        &secret_path,
+        sealed::secret_key_text(&secret).as_bytes(),
+        args.force,
+        true,
+    )
+    .and_then(|_| {
+        write_output(
+            &public_path,
+            sealed::public_key_text(&public).as_bytes(),
+            args.force,
+            false,
+        )
+    });
+    match result {
+        Ok(()) => {
+            println!(
+                "wrote {} and {}",
+                public_path.display(),
+                secret_path.display()
+            );
+            0
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+fn seal(args: &SealArgs) -> i32 {
+    let result = sealed::load_public_key(Path::new(&args.recipient)).and_then(|recipient| {
+        let bytes = std::fs::read(&args.proof)
+            .map_err(|err| format!("cannot read {}: {}", args.proof, err))?;
+        if sealed::is_sealed(&bytes) {
+            return Err(format!("{} is already sealed", args.proof));
+        }
+        // only containers the verifier can read are sealed
+        load_data(Path::new(&args.proof))?;
+        let sealed = sealed::seal(&bytes, &recipient);
+        write_output(Path::new(&args.out), &sealed, args.force, false)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+fn unseal(args: &UnsealArgs) -> i32 {
+    let result = sealed::load_secret_key(Path::new(&args.key)).and_then(|key| {
+        let bytes = std::fs::read(&args.sealed)
+            .map_err(|err| format!("cannot read {}: {}", args.sealed, err))?;
+        let opened =
+            sealed::open(&bytes, &key).map_err(|err| format!("{}: {}", args.sealed, err))?;
+        write_output(Path::new(&args.out), &opened, args.force, false)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+#[cfg(not(feature = "offline"))]
+fn verify_server(args: &VerifyServerArgs) -> i32 {
+    let options = verify_server::ServerOptions {
+        addr: args.listen.clone(),
+        threads: args.threads,
+        latency_target: std::time::Duration::from_secs_f64(args.latency_target_ms / 1000.0),
+        max_body_bytes: args.max_body_kb * 1024,
+        evaluation_keys: args.evaluation_key.clone(),
+        policy: args.policy.clone(),
+        decryption_key: args.decryption_key.clone(),
+    };
+    match verify_server::run(&options) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let code = match &cli.command {
+        Command::LintData(args) => lint_data(args),
+        Command::PackData(args) => pack_data(args),
+        Command::ImportLog(args) => import_log(args),
+        Command::Graph(args) => graph(args),
+        Command::Estimate(args) => estimate(args),
+        Command::ProveManifest(args) => prove_manifest(args),
+        Command::VerifyManifest(args) => verify_manifest(args),
+        Command::Preflight(args) => preflight_proofs(args),
+        Command::DiffProof(args) => diff_proof(args),
+        Command::Analyze(args) => analyze(args),
+        Command::Explain(args) => explain(args),
+        Command::ExportConstraints(args) => export_constraints(args),
+        Command::Circuits(args) => circuits(args),
+        Command::Config(ConfigCommand::Show(args)) => config_show(args),
+        Command::Keygen(args) => keygen(args),
+        Command::Seal(args) => seal(args),
+        Command::Unseal(args) => unseal(args),
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => {
+            tui::run(&tui::TuiOptions {
+                status_dir: args.status_dir.clone(),
+                refresh: std::time::Duration::from_millis(args.refresh_ms),
+                recent: args.recent,
+            });
+            0
+        }
+        #[cfg(not(feature = "offline"))]
+        Command::VerifyServer(args) => verify_server(args),
+    };
+    std::process::exit(code);
+}
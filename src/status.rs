@@ -0,0 +1,260 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Per-job status files. Provers and verifiers started with `--status-dir` record their progress
+// in `<status-dir>/<job>.status.toml`, which `stark-he tui` polls to render its dashboard. The
+// job store is a Storage (see storage.rs), the status directory being the file-based one.
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{FsStorage, Storage};
+
+const STATUS_SUFFIX: &str = ".status.toml";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    #[default]
+    Queued,
+    BuildingTrace,
+    Proving,
+    Proved,
+    Failed,
+}
+
+impl Phase {
+    pub fn is_running(&self) -> bool {
+        matches!(self, Phase::BuildingTrace | Phase::Proving)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Queued => "queued",
+            Phase::BuildingTrace => "building trace",
+            Phase::Proving => "proving",
+            Phase::Proved => "proved",
+            Phase::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub job: String,
+    pub phase: Phase,
+    pub queued_at: u64,
+    pub updated_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub trace_width: Option<usize>,
+    pub trace_length: Option<usize>,
+    pub proving_ms: Option<u64>,
+    pub proof_size_bytes: Option<usize>,
+    pub security_bits: Option<u32>,
+    pub verified: Option<bool>,
+    pub verified_at: Option<u64>,
+    pub verification_ms: Option<f64>,
+    pub message: Option<String>,
+    // kind of a verification failure, see VerificationFailure::kind
+    pub failure: Option<String>,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn job_name(proof_file_path: &Path) -> String {
+    proof_file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("job"))
+}
+
+// Writes status updates for one job; a reporter without a status directory does nothing, so
+// callers don't need to special-case runs without `--status-dir`.
+pub struct StatusReporter {
+    // job store and the key of this job's status
+    store: Option<(Arc<dyn Storage>, String)>,
+    status: JobStatus,
+}
+
+impl StatusReporter {
+    pub fn new(status_dir: Option<&str>, job: &str) -> Self {
+        match status_dir {
+            Some(dir) => StatusReporter::with_storage(Arc::new(FsStorage::new(dir)), job),
+            None => StatusReporter {
+                store: None,
+                status: new_status(job),
+            },
+        }
+    }
+
+    // Reports to the job store `storage` instead of a status directory.
+    pub fn with_storage(storage: Arc<dyn Storage>, job: &str) -> Self {
+        let key = format!("{}{}", job, STATUS_SUFFIX);
+        let status = load(storage.as_ref(), &key).unwrap_or_else(|| new_status(job));
+        StatusReporter {
+            store: Some((storage, key)),
+            status,
+        }
+    }
+
+    pub fn update<F: FnOnce(&mut JobStatus)>(&mut self, f: F) {
+        f(&mut self.status);
+        self.status.updated_at = unix_now();
+        if let Some((storage, key)) = &self.store {
+            if let Err(err) = store(storage.as_ref(), key, &self.status) {
+                log::debug!("Failed to write status {}: {}", key, err);
+            }
+        }
+    }
+
+    // Drops results of a previous run of the same job, keeping its queue position.
+    pub fn restart(&mut self) {
+        self.update(|status| {
+            *status = JobStatus {
+                job: status.job.clone(),
+                queued_at: status.queued_at,
+                ..Default::default()
+            }
+        });
+    }
+
+    pub fn set_phase(&mut self, phase: Phase) {
+        self.update(|status| {
+            status.phase = phase;
+            match phase {
+                Phase::Queued => status.queued_at = unix_now(),
+                Phase::BuildingTrace => status.started_at = Some(unix_now()),
+                Phase::Proved | Phase::Failed => status.finished_at = Some(unix_now()),
+                Phase::Proving => {}
+            }
+        });
+    }
+}
+
+fn new_status(job: &str) -> JobStatus {
+    JobStatus {
+        job: job.to_string(),
+        queued_at: unix_now(),
+        ..Default::default()
+    }
+}
+
+fn load(storage: &dyn Storage, key: &str) -> Option<JobStatus> {
+    let bytes = storage.get(key).ok()??;
+    toml::from_str(&String::from_utf8(bytes).ok()?).ok()
+}
+
+// Storage::put never exposes a partial status to readers.
+fn store(storage: &dyn Storage, key: &str, status: &JobStatus) -> io::Result<()> {
+    let text =
+        toml::to_string(status).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    storage.put(key, text.as_bytes())
+}
+
+pub fn load_all(status_dir: &str) -> Vec<JobStatus> {
+    load_all_from(&FsStorage::new(status_dir))
+}
+
+// Every job of the job store `storage`, ordered by name.
+pub fn load_all_from(storage: &dyn Storage) -> Vec<JobStatus> {
+    let mut statuses: Vec<JobStatus> = storage
+        .list("")
+        .unwrap_or_default()
+        .iter()
+        .filter(|key| key.ends_with(STATUS_SUFFIX))
+        .filter_map(|key| load(storage, key))
+        .collect();
+    statuses.sort_by(|a, b| a.job.cmp(&b.job));
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn phases_are_reported_to_the_job_store() {
+        let storage = Arc::new(MemoryStorage::new());
+        let mut reporter = StatusReporter::with_storage(storage.clone(), "sum-1");
+        reporter.set_phase(Phase::Queued);
+        let queued = load_all_from(storage.as_ref()).pop().unwrap();
+        assert_eq!(
+            (queued.job.as_str(), queued.phase),
+            ("sum-1", Phase::Queued)
+        );
+        assert!(!queued.phase.is_running());
+        assert_eq!(queued.started_at, None);
+
+        reporter.set_phase(Phase::BuildingTrace);
+        reporter.update(|s| {
+            s.trace_width = Some(12);
+            s.trace_length = Some(8192);
+        });
+        reporter.set_phase(Phase::Proving);
+        let proving = load_all_from(storage.as_ref()).pop().unwrap();
+        assert!(proving.phase.is_running());
+        assert!(proving.started_at.is_some());
+        assert_eq!(proving.trace_length, Some(8192));
+        assert_eq!(proving.finished_at, None);
+
+        reporter.set_phase(Phase::Proved);
+        let proved = load_all_from(storage.as_ref()).pop().unwrap();
+        assert_eq!(proved.phase, Phase::Proved);
+        assert!(!proved.phase.is_running());
+        assert!(proved.finished_at.unwrap() >= proved.started_at.unwrap());
+
+        // a reporter of the same job resumes from the stored status until it restarts
+        let mut rerun = StatusReporter::with_storage(storage.clone(), "sum-1");
+        assert_eq!(rerun.status.phase, Phase::Proved);
+        rerun.restart();
+        let restarted = load_all_from(storage.as_ref()).pop().unwrap();
+        assert_eq!(restarted.phase, Phase::Queued);
+        assert_eq!(restarted.queued_at, queued.queued_at);
+        assert_eq!((restarted.started_at, restarted.trace_width), (None, None));
+        rerun.set_phase(Phase::BuildingTrace);
+        rerun.set_phase(Phase::Failed);
+        assert_eq!(load_all_from(storage.as_ref())[0].phase, Phase::Failed);
+    }
+
+    #[test]
+    fn job_stores_list_status_files_by_job() {
+        let storage = Arc::new(MemoryStorage::new());
+        for job in ["mul-2", "add-1"] {
+            StatusReporter::with_storage(storage.clone(), job).set_phase(Phase::Queued);
+        }
+        storage.put("notes.toml", b"job = \"other\"").unwrap();
+        storage.put("broken.status.toml", b"phase = 3").unwrap();
+        let jobs: Vec<String> = load_all_from(storage.as_ref())
+            .into_iter()
+            .map(|status| status.job)
+            .collect();
+        assert_eq!(jobs, ["add-1", "mul-2"]);
+
+        // without a job store nothing is written
+        let mut reporter = StatusReporter::new(None, "add-1");
+        reporter.set_phase(Phase::Proving);
+        assert_eq!(reporter.status.phase, Phase::Proving);
+        assert_eq!(job_name(Path::new("out/add-1.proof")), "add-1");
+        assert_eq!(
+            toml::to_string(&reporter.status)
+                .unwrap()
+                .lines()
+                .find(|line| line.starts_with("phase")),
+            Some("phase = \"proving\"")
+        );
+        assert_eq!(Phase::BuildingTrace.name(), "building trace");
+    }
+}
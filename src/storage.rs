@@ -0,0 +1,144 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Storage of the artifacts the binaries write and read back: proof containers and the results
+// distributed next to them, the verification cache and job status files. Artifacts are byte
+// strings under `/`-separated keys like `jobs/mul.status.toml`; FsStorage maps them to files
+// below a root directory and MemoryStorage keeps them in memory, e.g. for tests or services that
+// forward proofs without touching the disk. Deployments with their own backend (an object store,
+// a database) implement Storage and pass it where the file-based entry points take a path.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub trait Storage: Send + Sync {
+    // Bytes stored under `key`, None if there are none.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    // Stores `bytes` under `key`, replacing what was there. Readers see either the old or the
+    // new bytes, never a partial write.
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+
+    // Keys starting with `prefix`, in lexicographic order.
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+}
+
+// Files below `root`, one per key.
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsStorage { root: root.into() }
+    }
+
+    // Storage holding the file at `path` under the key returned with it, its file name.
+    pub fn for_file(path: &Path) -> (FsStorage, String) {
+        let root = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let key = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (FsStorage::new(root), key)
+    }
+
+    pub fn path(&self, key: &str) -> PathBuf {
+        key.split('/')
+            .fold(self.root.clone(), |path, part| path.join(part))
+    }
+
+    fn collect(&self, dir: &Path, prefix: &str, keys: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let key = match dir.strip_prefix(&self.root) {
+                Ok(relative) if relative.as_os_str().is_empty() => name,
+                Ok(relative) => format!("{}/{}", relative.to_string_lossy(), name),
+                Err(_) => continue,
+            };
+            if entry.file_type()?.is_dir() {
+                self.collect(&entry.path(), prefix, keys)?;
+            } else if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for FsStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Written to a temporary file first and renamed into place.
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        match self.collect(&self.root, prefix, &mut keys) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(keys),
+            result => result?,
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Ciphertext subtraction a - b, coefficient-wise modulo every RNS prime. Residues are reduced,
+// so a - b + m lies in [1, 2m) and every circuit row proves, for both components and every
+// level,
+//
+//     r = (a - b + m) mod m
+//
+// with the Barrett blocks of reduction.rs, one difference per row of a row group as in MulAir,
+// against the moduli of the public inputs. The quotient is a single bit, so the circuit holds no
+// borrow flag the prover could choose. FreshAir proves a + b - c in one go; this circuit needs no
+// third operand.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_modulus_bits, Ciphertext};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 A00 A01 A10 A11 B00 B01 B10 B11 K.. R.. S..
+pub const SUB_DATA_NUM: usize = 2;
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = RESULT_LEN;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + SUB_DATA_NUM * DATA_LEN;
+pub const SUB_MAX_MODULUS_BITS: u32 = 62;
+// a difference plus the modulus is below 2m, so its quotient is a single bit
+const REDUCTION_WIDTH: usize = reduction_width(1, SUB_MAX_MODULUS_BITS as usize);
+// one row per difference, then padding
+pub const SUB_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of SubAir in canonical order.
+pub fn sub_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", SUB_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", SUB_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Same format as CustomData, with the minuend and the subtrahend.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SubData {
+    pub modulus: Vec<u64>,
+    pub values: [[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; SUB_DATA_NUM],
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+// a - b mod m for residues a, b < m.
+pub fn sub_mod(a: u64, b: u64, m: u64) -> u64 {
+    if a < b {
+        a + (m - b)
+    } else {
+        a - b
+    }
+}
+
+// The reductions of a row by the public moduli `modulus`, the difference of component v at
+// level l being value v * COEFF_LEVEL + l of the row.
+pub fn sub_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Sub, modulus, SUB_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| 2 * modulus[l] as u128 - 1,
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_sub_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: SubData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, SUB_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    let operands = Ciphertext::from_operands(&data.modulus, STATE_LENGTH, data.values)?;
+    let reductions = sub_reductions(operands[0].modulus()).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    for (d, operand) in operands.iter().enumerate() {
+        operand.write_columns(&mut columns, |v, l| data_column(d, v, l));
+    }
+    for l in 0..COEFF_LEVEL {
+        let m = operands[0].modulus()[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            for k in 0..STATE_LENGTH {
+                let (a, b) = (operands[0].coeff(v, l, k), operands[1].coeff(v, l, k));
+                columns[result_column(v, l)][k] = element_from_u64(sub_mod(a, b, m));
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        let (v, l) = (j / COEFF_LEVEL, j % COEFF_LEVEL);
+        let (a, b) = (operands[0].coeff(v, l, k), operands[1].coeff(v, l, k));
+        a as u128 + operands[0].modulus()[l] as u128 - b as u128
+    }))
+}
+
+pub fn get_sub_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, VALUE_NUM, SUB_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The differences plus the moduli `modulus` of one row, component v at level l at
+// v * COEFF_LEVEL + l.
+fn row_differences<E: FieldElement>(row: &[E], modulus: &[E]) -> [E; RESULT_LEN] {
+    let mut differences = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            differences[v * COEFF_LEVEL + l] =
+                row[data_column(0, v, l)] - row[data_column(1, v, l)] + modulus[l];
+        }
+    }
+    differences
+}
+
+pub struct SubAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+}
+
+impl Air for SubAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The moduli are public constants, so the differences are linear and selected by a periodic
+    // column; the moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs.modulus.expect("sub proofs record their moduli");
+        let reductions = sub_reductions(&modulus).expect("sub moduli were checked");
+        let degrees = reductions.degrees(1, &[]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        SubAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let modulus: [E; COEFF_LEVEL] =
+            std::array::from_fn(|l| E::from(element_from_u64(self.modulus[l])));
+        let differences = row_differences(frame.current(), &modulus);
+        self.reductions
+            .evaluate(frame, periodic_values, &differences, result);
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(v, l), self.result[v][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
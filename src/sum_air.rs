@@ -0,0 +1,393 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Sum of N ciphertexts d_0 + ... + d_(N-1), coefficient-wise modulo every RNS prime, e.g. an
+// aggregation of many clients' inputs in one proof. FreshAir adds a fixed number of operands
+// side by side; here the operands are added one per circuit row in a running sum, so the trace
+// width does not depend on N. Coefficient k of operand i is in circuit row k * P + i, where P is
+// N rounded up to a power of two and the rows of the missing operands hold zeros. Every circuit
+// row proves, for both components and every level,
+//
+//     r = (p + d) mod m
+//
+// with the partial sum p of the previous operands, reduced by the Barrett blocks of
+// reduction.rs against the moduli of the public inputs, one value per row of a row group as in
+// MulAir. The partial sum of the next circuit row is r unless the row is the last of its
+// coefficient: a periodic column marks the last rows of the groups where the sum carries on,
+// partial sums start at zero and the results are the sums of the last circuit rows.
+
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+use winter_prover::Trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::reduction::{
+    bind_moduli, check_public_moduli, reduction_group, reduction_width, Reductions,
+    MODULUS_ASSERTION_NUM,
+};
+
+// Modulus + Result + Partial + Data + Reduction
+// M0 M1 R00 R01 R10 R11 P00 P01 P10 P11 D00 D01 D10 D11 K.. R.. S..
+const RESULT_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const PARTIAL_START: usize = RESULT_START + RESULT_LEN;
+const DATA_START: usize = PARTIAL_START + RESULT_LEN;
+// columns of the running sum, which AggregateAir extends
+pub const SUM_DATA_END: usize = DATA_START + RESULT_LEN;
+pub const SUM_MAX_MODULUS_BITS: u32 = 62;
+// a partial sum plus an operand is below 2m, so its quotient is a single bit
+const REDUCTION_WIDTH: usize = reduction_width(1, SUM_MAX_MODULUS_BITS as usize);
+// one row per sum and level, then padding
+pub const SUM_GROUP: usize = reduction_group(RESULT_LEN);
+
+pub const SUM_STATE_WIDTH: usize = SUM_DATA_END + REDUCTION_WIDTH;
+
+// Most operands of one proof; the trace of the largest sum has COEFF_DEGREE * 256 circuit rows.
+pub const SUM_MAX_OPERANDS: usize = 256;
+
+// Trace layout constants of SumAir in canonical order. The trace length follows from the number
+// of operands, see sum_slots.
+pub fn sum_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("max_operands", SUM_MAX_OPERANDS),
+        ("group", SUM_GROUP),
+        ("state_width", SUM_STATE_WIDTH),
+    ]
+}
+
+// Same format as CustomData, with any number of operands between 2 and SUM_MAX_OPERANDS.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SumData {
+    pub modulus: Vec<u64>,
+    pub values: Vec<[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+pub fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+pub fn partial_column(v: usize, l: usize) -> usize {
+    level_column(PARTIAL_START, v, l)
+}
+
+pub fn result_column(v: usize, l: usize) -> usize {
+    level_column(RESULT_START, v, l)
+}
+
+// Rows per coefficient of a sum of `operands` ciphertexts.
+pub fn sum_slots(operands: usize) -> usize {
+    operands.next_power_of_two().max(2)
+}
+
+// Checks that a trace of `length` rows holds a sum of at most SUM_MAX_OPERANDS operands.
+pub fn check_sum_length(length: usize) -> Result<(), String> {
+    let rows = COEFF_DEGREE * SUM_GROUP;
+    let slots = length / rows;
+    if !length.is_multiple_of(rows)
+        || !slots.is_power_of_two()
+        || slots < 2
+        || slots > sum_slots(SUM_MAX_OPERANDS)
+    {
+        return Err(format!(
+            "sum traces have COEFF_DEGREE * {} times 2 to {} rows, not {}",
+            SUM_GROUP,
+            sum_slots(SUM_MAX_OPERANDS),
+            length
+        ));
+    }
+    Ok(())
+}
+
+// The reductions of a row by the public moduli `modulus`, component v at level l being value
+// v * COEFF_LEVEL + l of the row.
+pub fn sum_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Sum, modulus, SUM_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        VALUE_NUM,
+        result_column,
+        |_, l| 2 * (modulus[l] as u128 - 1),
+        (RESULT_START..SUM_DATA_END).collect(),
+        SUM_DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+// Circuit columns of the running sum of `operands` with moduli `modulus`, one circuit row per
+// operand slot, and the partial sums plus operands every row reduces.
+pub struct RunningSum {
+    pub columns: Vec<Vec<BaseElement>>,
+    pub values: Vec<[u128; RESULT_LEN]>,
+    pub sums: Vec<[[u64; COEFF_LEVEL]; VALUE_NUM]>,
+}
+
+impl RunningSum {
+    // Empty running sums of `slots` operand slots, `width` circuit columns wide.
+    pub fn new(modulus: &[u64], slots: usize, width: usize) -> Self {
+        let length = slots * COEFF_DEGREE;
+        let mut columns = vec![vec![BaseElement::ZERO; length]; width];
+        for l in 0..COEFF_LEVEL {
+            columns[l] = vec![element_from_u64(modulus[l]); length];
+        }
+        RunningSum {
+            columns,
+            values: vec![[0; RESULT_LEN]; length],
+            sums: vec![[[0; COEFF_LEVEL]; VALUE_NUM]; COEFF_DEGREE],
+        }
+    }
+
+    // Adds `operand`, or zeros for a missing one, in slot `i` of `slots`.
+    pub fn add(
+        &mut self,
+        modulus: &[u64],
+        slots: usize,
+        i: usize,
+        operand: Option<&[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+    ) {
+        self.add_weighted(modulus, slots, i, 1, operand);
+    }
+
+    // Adds `operand` times the weight `w`, as InnerProductAir does.
+    pub fn add_weighted(
+        &mut self,
+        modulus: &[u64],
+        slots: usize,
+        i: usize,
+        w: u64,
+        operand: Option<&[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]>,
+    ) {
+        for k in 0..COEFF_DEGREE {
+            let row = k * slots + i;
+            for v in 0..VALUE_NUM {
+                for l in 0..COEFF_LEVEL {
+                    let d = operand.map_or(0, |operand| operand[v][l][k]);
+                    let p = self.sums[k][v][l];
+                    let t = p as u128 + w as u128 * d as u128;
+                    let r = (t % modulus[l] as u128) as u64;
+                    self.columns[data_column(v, l)][row] = element_from_u64(d);
+                    self.columns[partial_column(v, l)][row] = element_from_u64(p);
+                    self.columns[result_column(v, l)][row] = element_from_u64(r);
+                    self.values[row][v * COEFF_LEVEL + l] = t;
+                    self.sums[k][v][l] = r;
+                }
+            }
+        }
+    }
+}
+
+pub fn build_sum_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: SumData = load_data_file(&arg.data_file_path)?;
+    let operands = data.values.len();
+    if !(2..=SUM_MAX_OPERANDS).contains(&operands) {
+        return Err(StarkHeError::DimensionMismatch(format!(
+            "a sum has 2 to {} operands, not {}",
+            SUM_MAX_OPERANDS, operands
+        )));
+    }
+    check_modulus_bits(&data.modulus, SUM_MAX_MODULUS_BITS).map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, COEFF_DEGREE, operand)?;
+    }
+    let reductions = sum_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let slots = sum_slots(operands);
+
+    let mut sum = RunningSum::new(&data.modulus, slots, SUM_DATA_END);
+    for i in 0..slots {
+        sum.add(&data.modulus, slots, i, data.values.get(i));
+    }
+    let values = sum.values;
+    Ok(reductions.build_trace(sum.columns, SUM_STATE_WIDTH, |k, j| values[k][j]))
+}
+
+// The result columns `column(v, l)` of the last circuit row of every coefficient of a trace of
+// `slots` operand slots of `group` rows.
+pub fn read_sums(
+    trace: &TraceType,
+    slots: usize,
+    group: usize,
+    column: impl Fn(usize, usize) -> usize,
+) -> Vec<Vec<Vec<BaseElement>>> {
+    (0..VALUE_NUM)
+        .map(|v| {
+            (0..COEFF_LEVEL)
+                .map(|l| {
+                    (0..COEFF_DEGREE)
+                        .map(|k| trace.get(column(v, l), (k * slots + slots - 1) * group))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The results are the running sums of the last circuit row of every coefficient.
+pub fn get_sum_pub_inputs(trace: &TraceType) -> PublicInputs {
+    let slots = trace.length() / (COEFF_DEGREE * SUM_GROUP);
+    PublicInputs {
+        result: read_sums(trace, slots, SUM_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The partial sums plus operands of one row, component v at level l at v * COEFF_LEVEL + l.
+pub fn sum_values<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    weighted_sum_values(row, E::ONE)
+}
+
+// The partial sums plus operands times the weight `w` of one row, as sum_values.
+pub fn weighted_sum_values<E: FieldElement>(row: &[E], w: E) -> [E; RESULT_LEN] {
+    let mut values = [E::ZERO; RESULT_LEN];
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            values[v * COEFF_LEVEL + l] = row[partial_column(v, l)] + w * row[data_column(v, l)];
+        }
+    }
+    values
+}
+
+// The flags of the rows linking a circuit row to the next one of its coefficient: the last row
+// of every group of `group` rows but the last group of a coefficient of `slots` operand slots.
+pub fn link_column(slots: usize, group: usize) -> Vec<BaseElement> {
+    (0..slots * group)
+        .map(|i| element_from_u64((i % group == group - 1 && i / group != slots - 1) as u64))
+        .collect()
+}
+
+// The links of the running sum under the flag `link`, zero when the partial sum of the next row
+// is the sum of the current one.
+pub fn evaluate_links<E: FieldElement>(frame: &EvaluationFrame<E>, link: E, result: &mut [E]) {
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            result[v * COEFF_LEVEL + l] =
+                link * (frame.next()[partial_column(v, l)] - frame.current()[result_column(v, l)]);
+        }
+    }
+}
+
+// The links of the running sum, whose flag has the cycle of a coefficient.
+pub fn link_degrees(slots: usize, group: usize) -> Vec<TransitionConstraintDegree> {
+    vec![TransitionConstraintDegree::with_cycles(1, vec![slots * group]); RESULT_LEN]
+}
+
+// Partial sums are zero on the first row, the results `result` of the result columns
+// `column(v, l)` on the last circuit row of every coefficient of `slots` groups of `group` rows.
+pub fn sum_assertions(
+    slots: usize,
+    group: usize,
+    result: &[Vec<Vec<BaseElement>>],
+    column: impl Fn(usize, usize) -> usize,
+) -> Vec<Assertion<BaseElement>> {
+    let cycle = slots * group;
+    let mut assertions = Vec::with_capacity(2 * RESULT_LEN);
+    for v in 0..VALUE_NUM {
+        for l in 0..COEFF_LEVEL {
+            assertions.push(Assertion::periodic(
+                partial_column(v, l),
+                0,
+                cycle,
+                BaseElement::ZERO,
+            ));
+            assertions.push(Assertion::sequence(
+                column(v, l),
+                (slots - 1) * group,
+                cycle,
+                result[v][l].clone(),
+            ));
+        }
+    }
+    assertions
+}
+
+pub struct SumAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    slots: usize,
+}
+
+impl Air for SumAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // The reductions of every row, then the links of the running sum.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let slots = trace_info.length() / (COEFF_DEGREE * SUM_GROUP);
+        let modulus = pub_inputs.modulus.expect("sum proofs record their moduli");
+        let reductions = sum_reductions(&modulus).expect("sum moduli were checked");
+        let mut degrees = reductions.degrees(1, &[]);
+        degrees.extend(link_degrees(slots, SUM_GROUP));
+        let num_assertions = 2 * RESULT_LEN + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        SumAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+            slots,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let (reduction, links) = result.split_at_mut(self.reductions.num_constraints());
+        let values = sum_values(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &values, reduction);
+        let link = periodic_values[self.reductions.num_periodic_columns()];
+        evaluate_links(frame, link, links);
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut columns = self.reductions.periodic_columns();
+        columns.push(link_column(self.slots, SUM_GROUP));
+        columns
+    }
+
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut assertions = sum_assertions(self.slots, SUM_GROUP, &self.result, result_column);
+        assertions.extend(bind_moduli(&self.modulus, 0, self.slots * SUM_GROUP));
+        assertions
+    }
+}
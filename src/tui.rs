@@ -0,0 +1,264 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Terminal dashboard over the status files written by `prover --status-dir` and
+// `verifier --status-dir`. It only needs ANSI escape codes, so it works over plain SSH sessions.
+
+use std::fmt::Write;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use crate::status::{load_all, unix_now, JobStatus, Phase};
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const BOLD: &str = "\x1b[1m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+pub struct TuiOptions {
+    pub status_dir: String,
+    pub refresh: Duration,
+    pub recent: usize,
+}
+
+pub fn run(options: &TuiOptions) {
+    loop {
+        let statuses = load_all(&options.status_dir);
+        print!("{}{}", CLEAR_SCREEN, render(&statuses, options, unix_now()));
+        let _ = io::Write::flush(&mut io::stdout());
+        thread::sleep(options.refresh);
+    }
+}
+
+pub fn render(statuses: &[JobStatus], options: &TuiOptions, now: u64) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}STARK-HE jobs{} in {} ({} jobs, refresh {:.1}s, Ctrl-C to quit)\n",
+        BOLD,
+        RESET,
+        options.status_dir,
+        statuses.len(),
+        options.refresh.as_secs_f64()
+    );
+
+    let queued: Vec<&JobStatus> = statuses
+        .iter()
+        .filter(|s| s.phase == Phase::Queued)
+        .collect();
+    section(&mut out, "QUEUED", queued.len());
+    for status in queued {
+        let _ = writeln!(
+            out,
+            "  {:<32} waiting {}",
+            status.job,
+            elapsed(now, status.queued_at)
+        );
+    }
+
+    let running: Vec<&JobStatus> = statuses.iter().filter(|s| s.phase.is_running()).collect();
+    section(&mut out, "RUNNING", running.len());
+    for status in running {
+        let trace = match (status.trace_width, status.trace_length) {
+            (Some(width), Some(length)) => format!("{} x {}", width, length),
+            _ => String::from("-"),
+        };
+        let _ = writeln!(
+            out,
+            "  {:<32} {:<16} {:>10} trace {}",
+            status.job,
+            status.phase.name(),
+            elapsed(now, status.started_at.unwrap_or(status.queued_at)),
+            trace
+        );
+    }
+
+    let mut finished: Vec<&JobStatus> = statuses
+        .iter()
+        .filter(|s| matches!(s.phase, Phase::Proved | Phase::Failed))
+        .collect();
+    finished.sort_by_key(|s| std::cmp::Reverse(s.finished_at));
+    finished.truncate(options.recent);
+    section(&mut out, "RECENT PROOFS", finished.len());
+    for status in finished {
+        if status.phase == Phase::Failed {
+            let _ = writeln!(
+                out,
+                "  {:<32} {}failed{} {}",
+                status.job,
+                RED,
+                RESET,
+                status.message.as_deref().unwrap_or("")
+            );
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "  {:<32} {:>8.1} KB {:>4} bits {:>8} ms  {} ago",
+            status.job,
+            status.proof_size_bytes.unwrap_or_default() as f64 / 1024f64,
+            status.security_bits.unwrap_or_default(),
+            status.proving_ms.unwrap_or_default(),
+            elapsed(now, status.finished_at.unwrap_or(now))
+        );
+    }
+
+    let mut verified: Vec<&JobStatus> = statuses.iter().filter(|s| s.verified.is_some()).collect();
+    verified.sort_by_key(|s| std::cmp::Reverse(s.verified_at));
+    verified.truncate(options.recent);
+    section(&mut out, "VERIFICATIONS", verified.len());
+    for status in verified {
+        let (color, label) = if status.verified == Some(true) {
+            (GREEN, "passed")
+        } else {
+            (RED, "failed")
+        };
+        let message = match (&status.failure, &status.message) {
+            (Some(failure), Some(message)) => format!("{}: {}", failure, message),
+            (_, message) => message.clone().unwrap_or_default(),
+        };
+        let _ = writeln!(
+            out,
+            "  {:<32} {}{}{} {:>8.1} ms {}",
+            status.job,
+            color,
+            label,
+            RESET,
+            status.verification_ms.unwrap_or_default(),
+            message
+        );
+    }
+    out
+}
+
+fn section(out: &mut String, title: &str, count: usize) {
+    let _ = writeln!(out, "\n{}{} ({}){}", BOLD, title, count, RESET);
+}
+
+fn elapsed(now: u64, since: u64) -> String {
+    let secs = now.saturating_sub(since);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000_000;
+
+    fn job(name: &str, phase: Phase) -> JobStatus {
+        JobStatus {
+            job: String::from(name),
+            phase,
+            queued_at: NOW - 90,
+            ..Default::default()
+        }
+    }
+
+    fn options() -> TuiOptions {
+        TuiOptions {
+            status_dir: String::from("status"),
+            refresh: Duration::from_secs(1),
+            recent: 2,
+        }
+    }
+
+    #[test]
+    fn jobs_are_listed_by_phase() {
+        let mut building = job("building", Phase::BuildingTrace);
+        building.started_at = Some(NOW - 5);
+        building.trace_width = Some(12);
+        building.trace_length = Some(8192);
+        let mut proved = job("proved", Phase::Proved);
+        proved.finished_at = Some(NOW - 7200);
+        proved.proof_size_bytes = Some(2048);
+        proved.security_bits = Some(96);
+        proved.proving_ms = Some(350);
+        proved.verified = Some(true);
+        proved.verified_at = Some(NOW - 60);
+        proved.verification_ms = Some(2.5);
+        let mut failed = job("failed", Phase::Failed);
+        failed.finished_at = Some(NOW - 10);
+        failed.message = Some(String::from("coefficient not reduced"));
+        let mut rejected = job("rejected", Phase::Proved);
+        rejected.finished_at = Some(NOW - 20);
+        rejected.verified = Some(false);
+        rejected.verified_at = Some(NOW - 1);
+        rejected.failure = Some(String::from("constraint-failure"));
+        rejected.message = Some(String::from("bad result"));
+        let statuses = [
+            job("queued", Phase::Queued),
+            building,
+            job("proving", Phase::Proving),
+            proved,
+            failed,
+            rejected,
+        ];
+        let screen = render(&statuses, &options(), NOW);
+
+        for section in [
+            "QUEUED (1)",
+            "RUNNING (2)",
+            "RECENT PROOFS (2)",
+            "VERIFICATIONS (2)",
+        ] {
+            assert!(screen.contains(section), "{}", section);
+        }
+        assert!(screen.contains(&format!("  {:<32} waiting 1m30s", "queued")));
+        assert!(screen.contains(&format!(
+            "  {:<32} {:<16} {:>10} trace 12 x 8192",
+            "building", "building trace", "5s"
+        )));
+        assert!(screen.contains(&format!(
+            "  {:<32} {:<16} {:>10} trace -",
+            "proving", "proving", "1m30s"
+        )));
+        // only the two most recent proofs, newest first
+        let failed_line = format!(
+            "  {:<32} {}failed{} coefficient not reduced",
+            "failed", RED, RESET
+        );
+        let rejected_line = format!(
+            "  {:<32} {:>8.1} KB {:>4} bits {:>8} ms  20s ago",
+            "rejected", 0.0, 0, 0
+        );
+        let failed_at = screen.find(&failed_line).unwrap();
+        assert!(failed_at < screen.find(&rejected_line).unwrap());
+        assert!(!screen.contains("2.0 KB"));
+        let verifications = &screen[screen.find("VERIFICATIONS").unwrap()..];
+        let rejected_at = verifications
+            .find(&format!(
+                "  {:<32} {}failed{} {:>8.1} ms constraint-failure: bad result",
+                "rejected", RED, RESET, 0.0
+            ))
+            .unwrap();
+        let passed_at = verifications
+            .find(&format!(
+                "  {:<32} {}passed{} {:>8.1} ms ",
+                "proved", GREEN, RESET, 2.5
+            ))
+            .unwrap();
+        assert!(rejected_at < passed_at);
+    }
+
+    #[test]
+    fn empty_dashboards_and_elapsed_times() {
+        let screen = render(&[], &options(), NOW);
+        assert!(screen.contains("in status (0 jobs, refresh 1.0s"));
+        assert!(screen.contains("RUNNING (0)"));
+        assert_eq!(elapsed(NOW, NOW + 5), "0s");
+        assert_eq!(elapsed(NOW, NOW - 59), "59s");
+        assert_eq!(elapsed(NOW, NOW - 61), "1m01s");
+        assert_eq!(elapsed(NOW, NOW - 7260), "2h01m");
+    }
+}
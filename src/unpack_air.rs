@@ -0,0 +1,235 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Unpacking of a batched ciphertext into PACK_NUM ciphertexts, the reverse of PackAir: result j
+// is the ciphertext multiplied by X^(-e_j), e_j = j * N / PACK_NUM, which moves coefficient e_j
+// of its plaintext into the constant coefficient,
+//
+//     r_jv = w_jl * c_v mod q_l
+//
+// with the factors w_jl of X^(-e_j) at the evaluation point of the row, as periodic columns
+// derived from the public moduli. As in PackAir, the products are reduced by the Barrett blocks
+// of reduction.rs, one per row of a row group. The other coefficients of result j hold the
+// other values rotated along; extracting the constant coefficient, e.g. as an LWE ciphertext,
+// is left to the application.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, CiphertextData,
+    InputArg, Op, PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::ciphertext::check_levels;
+use crate::error::StarkHeError;
+use crate::mul_air::MUL_MAX_MODULUS_BITS;
+use crate::ntt_air::check_ntt_modulus;
+use crate::pack_air::{factor_columns, pack_factors, PACK_NUM};
+use crate::reduction::{
+    check_public_moduli, grouped_result_assertion_num, read_group_result, reduction_group,
+    reduction_width, Reductions,
+};
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R000 R001 R010 R011 R100 .. R711 C00 C01 C10 C11 K.. R.. S..
+pub const UNPACK_RESULT_NUM: usize = PACK_NUM * VALUE_NUM;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_LEN: usize = PACK_NUM * DATA_LEN;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + DATA_LEN;
+// a product is below m^2, so its quotient below m
+const REDUCTION_WIDTH: usize =
+    reduction_width(MUL_MAX_MODULUS_BITS as usize, MUL_MAX_MODULUS_BITS as usize);
+// one row per result coefficient and level, then padding
+pub const UNPACK_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of UnpackAir in canonical order.
+pub fn unpack_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("pack_num", PACK_NUM),
+        ("value_num", VALUE_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", UNPACK_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+// Column of component `v` of result `j` at level `l`; the results of one ciphertext are
+// adjacent, so result component r = j * VALUE_NUM + v.
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+fn data_column(v: usize, l: usize) -> usize {
+    level_column(DATA_START, v, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, result component r at level l being
+// value r * COEFF_LEVEL + l of the row.
+pub fn unpack_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::Unpack, modulus, MUL_MAX_MODULUS_BITS)?;
+    check_ntt_modulus(modulus)?;
+    Reductions::of_components(
+        modulus,
+        UNPACK_RESULT_NUM,
+        result_column,
+        |_, l| (modulus[l] as u128 - 1).pow(2),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_unpack_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    // a single ciphertext in evaluation form
+    let data: CiphertextData = load_data_file(&arg.data_file_path)?;
+    check_ntt_modulus(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    check_levels(&data.modulus, STATE_LENGTH, &data.values)?;
+    let reductions = unpack_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+    let factors = pack_factors(&data.modulus, -1);
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    let product = |k: usize, r: usize, l: usize| {
+        let (j, v) = (r / VALUE_NUM, r % VALUE_NUM);
+        factors[j][l][k] as u128 * data.values[v][l][k] as u128
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for v in 0..VALUE_NUM {
+            columns[data_column(v, l)] = data.values[v][l]
+                .iter()
+                .map(|&c| element_from_u64(c))
+                .collect();
+        }
+        for r in 0..UNPACK_RESULT_NUM {
+            for k in 0..STATE_LENGTH {
+                columns[result_column(r, l)][k] =
+                    element_from_u64((product(k, r, l) % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        product(k, j / COEFF_LEVEL, j % COEFF_LEVEL)
+    }))
+}
+
+pub fn get_unpack_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, UNPACK_RESULT_NUM, UNPACK_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The products of one row with the factors `factors` of its evaluation point, result component
+// r at level l at r * COEFF_LEVEL + l.
+fn row_products<E: FieldElement>(row: &[E], factors: &[E]) -> [E; RESULT_LEN] {
+    let mut products = [E::ZERO; RESULT_LEN];
+    for j in 0..PACK_NUM {
+        for v in 0..VALUE_NUM {
+            for l in 0..COEFF_LEVEL {
+                let r = j * VALUE_NUM + v;
+                products[r * COEFF_LEVEL + l] =
+                    factors[j * COEFF_LEVEL + l] * row[data_column(v, l)];
+            }
+        }
+    }
+    products
+}
+
+pub struct UnpackAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: Option<Vec<usize>>,
+    modulus: Vec<u64>,
+}
+
+impl Air for UnpackAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in PackAir the products are linear in the ciphertext with stretched factor columns; the
+    // moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("unpacking proofs need the moduli");
+        let reductions = unpack_reductions(&modulus).expect("unpacking moduli were checked");
+        let degrees = reductions.degrees(1, &[STATE_LENGTH * reductions.group()]);
+        let num_assertions = grouped_result_assertion_num(RESULT_LEN, pub_inputs.window.as_deref())
+            + grouped_result_assertion_num(MODULUS_NUM, pub_inputs.window.as_deref());
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        UnpackAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            result: pub_inputs.result,
+            window: pub_inputs.window,
+            modulus,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    // Periodic values are those of the reductions, then the factors for every result and level.
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let factors = &periodic_values[self.reductions.num_periodic_columns()..];
+        let products = row_products(frame.current(), factors);
+        self.reductions
+            .evaluate(frame, periodic_values, &products, result);
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        factor_columns(&self.reductions, &pack_factors(&self.modulus, -1))
+    }
+
+    // Results are bound on the first row of every group or of the groups of the window, the
+    // modulus columns to the public moduli, from which the factors are derived.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..UNPACK_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(r, l), self.result[r][l].clone()));
+            }
+        }
+        let mut assertions = self
+            .reductions
+            .bind_results(columns, self.window.as_deref());
+        assertions.extend(
+            self.reductions
+                .bind_result_moduli(&self.modulus, self.window.as_deref()),
+        );
+        assertions
+    }
+}
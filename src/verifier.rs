@@ -3,15 +3,24 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+// With a single level, loops over the higher levels are empty and level indices constant, and
+// the small polynomials would fit into arrays.
+#![cfg_attr(
+    feature = "micro",
+    allow(clippy::modulo_one, clippy::reversed_empty_ranges, clippy::useless_vec)
+)]
+
 use std::io::Write;
+use std::path::Path;
 use std::time::Instant;
 
 use log::debug;
-use winter_prover::StarkProof;
-use winter_verifier::verify;
 
-pub mod air;
-use air::{from_data, Data, FreshAir};
+use stark_he::sealed::load_secret_key;
+use stark_he::status::{job_name, unix_now, StatusReporter};
+use stark_he::verifying::{
+    attach_evaluation_key, attach_result, open_data, parse_data, verify_proof,
+};
 
 use clap::Parser;
 
@@ -20,6 +29,19 @@ use clap::Parser;
 struct Cli {
     #[clap(long, short, display_order = 1, default_value_t = String::from("./stark.toml"))]
     proof_file_path: String,
+    /// Evaluation key file, required to verify relinearization and key-switching proofs
+    #[clap(long)]
+    evaluation_key: Option<String>,
+    /// Result ciphertext file, required to verify inner product proofs, which record only its
+    /// digest
+    #[clap(long)]
+    result: Option<String>,
+    /// Directory receiving the job status file read by `stark-he tui`
+    #[clap(long)]
+    status_dir: Option<String>,
+    /// X25519 secret key file opening a sealed proof container
+    #[clap(long)]
+    decryption_key: Option<String>,
 }
 
 fn main() {
@@ -30,15 +52,49 @@ fn main() {
 
     let cli = Cli::parse();
 
-    let data: Data = confy::load_path(cli.proof_file_path).unwrap();
-    let (pub_inputs, proof_bytes) = from_data(data);
-    let proof = StarkProof::from_bytes(&proof_bytes).unwrap();
+    let mut status = StatusReporter::new(
+        cli.status_dir.as_deref(),
+        &job_name(Path::new(&cli.proof_file_path)),
+    );
+
+    let key = cli
+        .decryption_key
+        .as_ref()
+        .map(|path| load_secret_key(Path::new(path)))
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        });
+    let (op, pub_inputs, proof) = open_data(Path::new(&cli.proof_file_path), key.as_ref())
+        .and_then(|data| {
+            let digest = data.evaluation_key_digest.clone();
+            let (op, mut pub_inputs, proof) = parse_data(data)?;
+            attach_evaluation_key(
+                op,
+                digest.as_deref(),
+                cli.evaluation_key.as_deref(),
+                &mut pub_inputs,
+            )?;
+            attach_result(cli.result.as_deref(), &mut pub_inputs)?;
+            Ok((op, pub_inputs, proof))
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("error: {} ({})", err, err.kind());
+            std::process::exit(1);
+        });
     let now = Instant::now();
-    match verify::<FreshAir>(proof, pub_inputs) {
-        Ok(_) => debug!(
-            "Proof verified in {:.1} ms",
-            now.elapsed().as_micros() as f64 / 1000f64
-        ),
-        Err(msg) => debug!("Failed to verify proof: {}", msg),
+    let result = verify_proof(op, proof, pub_inputs);
+    let elapsed_ms = now.elapsed().as_micros() as f64 / 1000f64;
+    status.update(|s| {
+        s.verified = Some(result.is_ok());
+        s.verified_at = Some(unix_now());
+        s.verification_ms = Some(elapsed_ms);
+        s.message = result.as_ref().err().map(|err| err.to_string());
+        s.failure = result.as_ref().err().map(|err| err.kind().to_string());
+    });
+    match result {
+        Ok(_) => debug!("Proof verified in {:.1} ms", elapsed_ms),
+        Err(err) => debug!("Failed to verify proof ({}): {}", err.kind(), err),
     }
 }
@@ -0,0 +1,675 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::disallowed_types)]
+
+// Stateless HTTP/1.1 verification service for many small requests, compiled out by the `offline`
+// feature like all network code. Every request is verified on its own: nothing is cached or
+// written to disk, so the service can be replicated freely behind a load balancer. What a
+// request would otherwise read from disk, the evaluation keys, the policy and the decryption
+// key, is loaded once at startup.
+//
+//     POST /verify    body: a proof container, plain or sealed to the server's key
+//     POST /preflight body: a proof container, checked without verification, see preflight.rs
+//     GET  /healthz   liveness
+//     GET  /stats     request counters and latencies since startup
+//
+// Responses are JSON objects. /verify answers 200 with "verified": true, or 422 with the kind
+// of failure (see VerificationFailure) and the reason the proof was rejected, /preflight likewise
+// with "passed"; verifications slower than the latency target are counted and reported on
+// stderr, rejections are counted by kind. A request whose handling panics answers 500.
+//
+// Every connection is read on a thread of its own, which hands each request to a fixed pool of
+// workers: idle keep-alive connections hold no worker, so they cannot stall the service.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x25519_dalek::StaticSecret;
+
+use crate::canonical::to_hex;
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey};
+use crate::preflight::{preflight, VerifyPolicy};
+use crate::sealed::load_secret_key;
+use crate::verifying::{
+    check_evaluation_key, verify_proof, VerificationFailure, FAILURE_KINDS, FAILURE_KIND_NUM,
+};
+
+// Idle keep-alive connections are closed after this long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+pub struct ServerOptions {
+    pub addr: String,
+    pub threads: usize,
+    pub latency_target: Duration,
+    pub max_body_bytes: usize,
+    pub evaluation_keys: Vec<String>,
+    pub policy: Option<String>,
+    pub decryption_key: Option<String>,
+}
+
+// Everything loaded at startup, shared read-only by the workers.
+struct Preloaded {
+    // evaluation keys by hex digest, the form proofs record them in
+    keys: BTreeMap<String, EvaluationKey>,
+    policy: VerifyPolicy,
+    decryption_key: Option<StaticSecret>,
+    latency_target: Duration,
+    max_body_bytes: usize,
+}
+
+#[derive(Default)]
+struct Stats {
+    requests: AtomicU64,
+    verified: AtomicU64,
+    rejected: AtomicU64,
+    over_target: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+    preflights: AtomicU64,
+    preflights_rejected: AtomicU64,
+    // rejections of both endpoints by VerificationFailure kind
+    failures: [AtomicU64; FAILURE_KIND_NUM],
+}
+
+impl Stats {
+    fn record(&self, verified: bool, elapsed: Duration, over_target: bool) {
+        let us = elapsed.as_micros() as u64;
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let outcome = if verified {
+            &self.verified
+        } else {
+            &self.rejected
+        };
+        outcome.fetch_add(1, Ordering::Relaxed);
+        if over_target {
+            self.over_target.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn record_preflight(&self, passed: bool) {
+        self.preflights.fetch_add(1, Ordering::Relaxed);
+        if !passed {
+            self.preflights_rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_failure(&self, failure: &VerificationFailure) {
+        self.failures[failure.kind_index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn to_json(&self, latency_target: Duration) -> String {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let mean_ms = match requests {
+            0 => 0.0,
+            n => self.total_us.load(Ordering::Relaxed) as f64 / n as f64 / 1000.0,
+        };
+        let failures: Vec<String> = FAILURE_KINDS
+            .iter()
+            .zip(self.failures.iter())
+            .map(|(kind, count)| format!("{}:{}", json_string(kind), count.load(Ordering::Relaxed)))
+            .collect();
+        format!(
+            "{{\"requests\":{},\"verified\":{},\"rejected\":{},\"over_target\":{},\
+             \"mean_ms\":{:.3},\"max_ms\":{:.3},\"latency_target_ms\":{:.3},\
+             \"preflights\":{},\"preflights_rejected\":{},\"failures\":{{{}}}}}",
+            requests,
+            self.verified.load(Ordering::Relaxed),
+            self.rejected.load(Ordering::Relaxed),
+            self.over_target.load(Ordering::Relaxed),
+            mean_ms,
+            self.max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            latency_target.as_secs_f64() * 1000.0,
+            self.preflights.load(Ordering::Relaxed),
+            self.preflights_rejected.load(Ordering::Relaxed),
+            failures.join(",")
+        )
+    }
+}
+
+fn preload(options: &ServerOptions) -> Result<Preloaded, String> {
+    let mut keys = BTreeMap::new();
+    for path in options.evaluation_keys.iter() {
+        let key = load_evaluation_key(path)?;
+        keys.insert(to_hex(&key.digest()), key);
+    }
+    let policy = match &options.policy {
+        Some(path) => VerifyPolicy::load(path)?,
+        None => VerifyPolicy::default(),
+    };
+    let decryption_key = options
+        .decryption_key
+        .as_ref()
+        .map(|path| load_secret_key(Path::new(path)))
+        .transpose()?;
+    Ok(Preloaded {
+        keys,
+        policy,
+        decryption_key,
+        latency_target: options.latency_target,
+        max_body_bytes: options.max_body_bytes,
+    })
+}
+
+// Binds `options.addr` and verifies on `options.threads` workers until the process is killed.
+pub fn run(options: &ServerOptions) -> Result<(), String> {
+    let preloaded = Arc::new(preload(options)?);
+    let listener = TcpListener::bind(&options.addr)
+        .map_err(|err| format!("cannot listen on {}: {}", options.addr, err))?;
+    let threads = options.threads.max(1);
+    eprintln!(
+        "verifying on {} with {} workers, {} evaluation keys preloaded",
+        options.addr,
+        threads,
+        preloaded.keys.len()
+    );
+    serve(listener, Dispatcher::spawn(threads, preloaded));
+    Ok(())
+}
+
+// Accepts connections until the listener fails, each served on a thread of its own.
+fn serve(listener: TcpListener, dispatcher: Dispatcher) {
+    for stream in listener.incoming().flatten() {
+        let dispatcher = dispatcher.clone();
+        thread::spawn(move || serve_connection(stream, &dispatcher));
+    }
+}
+
+// A request and where to send its response.
+struct Job {
+    request: Request,
+    response: Sender<(u16, String)>,
+}
+
+// Hands requests to the workers, which share the preloaded state and the counters.
+#[derive(Clone)]
+struct Dispatcher {
+    jobs: Sender<Job>,
+    max_body_bytes: usize,
+}
+
+impl Dispatcher {
+    fn spawn(threads: usize, preloaded: Arc<Preloaded>) -> Dispatcher {
+        let (jobs, queue) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let stats = Arc::new(Stats::default());
+        for _ in 0..threads {
+            let (queue, preloaded, stats) = (queue.clone(), preloaded.clone(), stats.clone());
+            thread::spawn(move || work(&queue, &preloaded, &stats));
+        }
+        Dispatcher {
+            jobs,
+            max_body_bytes: preloaded.max_body_bytes,
+        }
+    }
+
+    // The response to `request`, once a worker has handled it.
+    fn dispatch(&self, request: Request) -> (u16, String) {
+        let (response, received) = mpsc::channel();
+        let stopped = || (500, error_json("verification workers stopped"));
+        if self.jobs.send(Job { request, response }).is_err() {
+            return stopped();
+        }
+        received.recv().unwrap_or_else(|_| stopped())
+    }
+}
+
+fn work(queue: &Mutex<Receiver<Job>>, preloaded: &Preloaded, stats: &Stats) {
+    loop {
+        // the lock is only held while waiting for the next job
+        let job = match queue.lock().map(|queue| queue.recv()) {
+            Ok(Ok(job)) => job,
+            _ => return,
+        };
+        let response = contain_panics(|| route(&job.request, preloaded, stats));
+        let _ = job.response.send(response);
+    }
+}
+
+// The response of `handler`, or 500 if it panics, so that a worker outlives a failed request.
+fn contain_panics(handler: impl FnOnce() -> (u16, String)) -> (u16, String) {
+    panic::catch_unwind(AssertUnwindSafe(handler))
+        .unwrap_or_else(|_| (500, error_json("internal error while handling the request")))
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+// Reads the next request of a connection, None when the client closed it.
+fn read_request(
+    reader: &mut BufReader<TcpStream>,
+    max_body_bytes: usize,
+) -> Result<Option<Request>, (u16, String)> {
+    let bad_request = |message: &str| (400, String::from(message));
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        match reader
+            .by_ref()
+            .take((MAX_HEADER_BYTES + 1 - start) as u64)
+            .read_until(b'\n', &mut head)
+        {
+            Ok(0) if head.is_empty() => return Ok(None),
+            Ok(0) => return Err(bad_request("truncated request")),
+            Ok(_) if head.len() > MAX_HEADER_BYTES => {
+                return Err((431, String::from("request header is too large")))
+            }
+            Ok(_) if head[start..] == *b"\r\n" || head[start..] == *b"\n" => break,
+            Ok(_) => {}
+            Err(_) if head.is_empty() => return Ok(None),
+            Err(err) => return Err(bad_request(&err.to_string())),
+        }
+    }
+    let head = String::from_utf8(head).map_err(|_| bad_request("request header is not UTF-8"))?;
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (method, path, version) = match (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) {
+        (Some(method), Some(path), Some(version)) => (method, path, version),
+        _ => return Err(bad_request("malformed request line")),
+    };
+    let mut keep_alive = version == "HTTP/1.1";
+    let mut content_length = 0;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| bad_request("invalid Content-Length"))?
+            }
+            "connection" => keep_alive = !value.eq_ignore_ascii_case("close"),
+            "transfer-encoding" => {
+                return Err((411, String::from("chunked bodies are not supported")))
+            }
+            _ => {}
+        }
+    }
+    if content_length > max_body_bytes {
+        return Err((
+            413,
+            format!("request body exceeds {} bytes", max_body_bytes),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("truncated request body"))?;
+    Ok(Some(Request {
+        method: String::from(method),
+        path: String::from(path),
+        body,
+        keep_alive,
+    }))
+}
+
+fn serve_connection(stream: TcpStream, dispatcher: &Dispatcher) {
+    let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+    let _ = stream.set_nodelay(true);
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let (status, body, keep_alive) = match read_request(&mut reader, dispatcher.max_body_bytes)
+        {
+            Ok(None) => return,
+            Ok(Some(request)) => {
+                let keep_alive = request.keep_alive;
+                let (status, body) = dispatcher.dispatch(request);
+                (status, body, keep_alive)
+            }
+            Err((status, message)) => (status, error_json(&message), false),
+        };
+        if write_response(&mut writer, status, &body, keep_alive).is_err() || !keep_alive {
+            return;
+        }
+    }
+}
+
+fn route(request: &Request, preloaded: &Preloaded, stats: &Stats) -> (u16, String) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/verify") => verify_request(&request.body, preloaded, stats),
+        ("POST", "/preflight") => preflight_request(&request.body, preloaded, stats),
+        ("GET", "/healthz") => (200, String::from("{\"status\":\"ok\"}")),
+        ("GET", "/stats") => (200, stats.to_json(preloaded.latency_target)),
+        (_, "/verify") | (_, "/preflight") | (_, "/healthz") | (_, "/stats") => {
+            (405, error_json("method not allowed"))
+        }
+        _ => (404, error_json("not found")),
+    }
+}
+
+// Latency is measured from the first byte of the body to the verdict: decoding the container
+// is part of it and, for TOML containers of full ciphertexts, takes longer than verification.
+fn verify_request(body: &[u8], preloaded: &Preloaded, stats: &Stats) -> (u16, String) {
+    let start = Instant::now();
+    let mut circuit = None;
+    let mut decode_elapsed = None;
+    let result = preflight(
+        body.to_vec(),
+        preloaded.decryption_key.as_ref(),
+        &preloaded.policy,
+        "request",
+    )
+    .and_then(|checked| {
+        decode_elapsed = Some(start.elapsed());
+        let (op, mut pub_inputs) = (checked.op, checked.pub_inputs);
+        circuit = Some(op.info().id);
+        if let Some(recorded) = checked.evaluation_key_digest.as_deref() {
+            let key = preloaded.keys.get(recorded).ok_or_else(|| {
+                VerificationFailure::PublicInputMismatch(format!(
+                    "no preloaded evaluation key has digest {}",
+                    recorded
+                ))
+            })?;
+            check_evaluation_key(op, key, recorded, Some(recorded))?;
+            pub_inputs.evaluation_key = Some(key.clone());
+        }
+        verify_proof(op, checked.proof, pub_inputs)
+    });
+    let elapsed = start.elapsed();
+    let decode_elapsed = decode_elapsed.unwrap_or(elapsed);
+    let over_target = elapsed > preloaded.latency_target;
+    stats.record(result.is_ok(), elapsed, over_target);
+    if let Err(err) = &result {
+        stats.record_failure(err);
+    }
+    let (latency_ms, decode_ms) = (
+        elapsed.as_secs_f64() * 1000.0,
+        decode_elapsed.as_secs_f64() * 1000.0,
+    );
+    if over_target {
+        eprintln!(
+            "{} request took {:.1} ms ({:.1} ms decoding), over the {:.1} ms target",
+            circuit.unwrap_or("unknown"),
+            latency_ms,
+            decode_ms,
+            preloaded.latency_target.as_secs_f64() * 1000.0
+        );
+    }
+
+    let mut json = format!("{{\"verified\":{}", result.is_ok());
+    if let Some(circuit) = circuit {
+        let _ = write!(json, ",\"circuit\":{}", json_string(circuit));
+    }
+    let _ = write!(
+        json,
+        ",\"latency_ms\":{:.3},\"decode_ms\":{:.3},\"verification_ms\":{:.3},\"over_target\":{}",
+        latency_ms,
+        decode_ms,
+        latency_ms - decode_ms,
+        over_target
+    );
+    if let Err(err) = &result {
+        let _ = write!(
+            json,
+            ",\"failure\":{},\"error\":{}",
+            json_string(err.kind()),
+            json_string(err.message())
+        );
+    }
+    json.push('}');
+    (if result.is_ok() { 200 } else { 422 }, json)
+}
+
+fn preflight_request(body: &[u8], preloaded: &Preloaded, stats: &Stats) -> (u16, String) {
+    let start = Instant::now();
+    let result = preflight(
+        body.to_vec(),
+        preloaded.decryption_key.as_ref(),
+        &preloaded.policy,
+        "request",
+    );
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    stats.record_preflight(result.is_ok());
+    if let Err(err) = &result {
+        stats.record_failure(err);
+    }
+    let mut json = format!("{{\"passed\":{}", result.is_ok());
+    match &result {
+        Ok(checked) => {
+            let _ = write!(
+                json,
+                ",\"circuit\":{},\"security_bits\":{}",
+                json_string(checked.op.info().id),
+                checked.proof.security_level(true)
+            );
+        }
+        Err(err) => {
+            let _ = write!(
+                json,
+                ",\"failure\":{},\"error\":{}",
+                json_string(err.kind()),
+                json_string(err.message())
+            );
+        }
+    }
+    let _ = write!(json, ",\"latency_ms\":{:.3}}}", elapsed_ms);
+    (if result.is_ok() { 200 } else { 422 }, json)
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    body: &str,
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        _ => "Error",
+    };
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: {}\r\n\r\n",
+        status,
+        reason,
+        body.len(),
+        connection
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+fn error_json(message: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(message))
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::{CustomData, COEFF_DEGREE, MIN_FIELD_EXTENSION, VALUE_NUM};
+    use std::net::{Shutdown, SocketAddr};
+    use winter_air::{HashFunction, ProofOptions};
+
+    // A server of `threads` workers on a free loopback port, detached from the test.
+    fn listen(threads: usize, max_body_bytes: usize) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let preloaded = Preloaded {
+            keys: BTreeMap::new(),
+            policy: VerifyPolicy::default(),
+            decryption_key: None,
+            latency_target: Duration::from_secs(60),
+            max_body_bytes,
+        };
+        let dispatcher = Dispatcher::spawn(threads, Arc::new(preloaded));
+        thread::spawn(move || serve(listener, dispatcher));
+        addr
+    }
+
+    // Sends `request` on a connection of its own and returns the status and body of the response.
+    fn exchange(addr: SocketAddr, request: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, String::from(body))
+    }
+
+    #[test]
+    fn oversize_and_malformed_requests_are_refused() {
+        let addr = listen(2, 1024);
+        // exactly one byte over the limit, all of it read before the refusal
+        let mut request = b"GET /healthz HTTP/1.1\r\nX-Padding: ".to_vec();
+        request.resize(MAX_HEADER_BYTES + 1, b'a');
+        let (status, body) = exchange(addr, &request);
+        assert_eq!(status, 431);
+        assert_eq!(body, error_json("request header is too large"));
+
+        let (status, body) = exchange(
+            addr,
+            b"POST /verify HTTP/1.1\r\nContent-Length: 1025\r\n\r\n",
+        );
+        assert_eq!(status, 413);
+        assert_eq!(body, error_json("request body exceeds 1024 bytes"));
+
+        for length in ["-1", "12ab", ""] {
+            let request = format!(
+                "POST /verify HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                length
+            );
+            let (status, body) = exchange(addr, request.as_bytes());
+            assert_eq!(status, 400);
+            assert_eq!(body, error_json("invalid Content-Length"));
+        }
+
+        let (status, _) = exchange(addr, b"GET /healthz\r\n\r\n");
+        assert_eq!(status, 400);
+        let (status, body) = exchange(addr, b"GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!((status, body.as_str()), (200, "{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn idle_connections_hold_no_worker() {
+        let addr = listen(1, 1024);
+        let idle: Vec<_> = (0..4).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        let (status, body) = exchange(addr, b"GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!((status, body.as_str()), (200, "{\"status\":\"ok\"}"));
+        drop(idle);
+    }
+
+    #[test]
+    fn panics_answer_500() {
+        let (status, body) = contain_panics(|| panic!("verification panicked"));
+        assert_eq!(status, 500);
+        assert_eq!(
+            body,
+            error_json("internal error while handling the request")
+        );
+        assert_eq!(
+            contain_panics(|| (200, String::new())),
+            (200, String::new())
+        );
+    }
+
+    #[test]
+    fn proofs_verify_over_loopback() {
+        // hashes of the coefficient indices, so that no column of the trace is constant
+        let operand = |seed: u64| {
+            std::array::from_fn::<_, VALUE_NUM, _>(|v| {
+                let hash = |k: u64| {
+                    let x = (k + 1000 * seed + 100 * v as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                    (x ^ x >> 31).wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 47
+                };
+                vec![(0..COEFF_DEGREE as u64).map(|k| hash(k) % 114689).collect()]
+            })
+        };
+        let data = CustomData {
+            modulus: vec![114689],
+            values: vec![operand(0), operand(1)],
+            ..Default::default()
+        };
+        let options = ProofOptions::new(
+            28,
+            8,
+            0,
+            HashFunction::Blake3_256,
+            MIN_FIELD_EXTENSION,
+            4,
+            256,
+        );
+        let proof = crate::prove(&data, options).unwrap().to_data();
+        let container = toml::to_string(&proof).unwrap();
+        let addr = listen(2, container.len() + 16);
+
+        let request = |container: &str| {
+            let mut request = format!(
+                "POST /verify HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                container.len()
+            )
+            .into_bytes();
+            request.extend_from_slice(container.as_bytes());
+            request
+        };
+        let (status, body) = exchange(addr, &request(&container));
+        assert_eq!(status, 200, "{}", body);
+        assert!(body.starts_with("{\"verified\":true,\"circuit\":"));
+
+        let mut tampered = proof;
+        tampered.result[0][0][0] += 1;
+        let (status, body) = exchange(addr, &request(&toml::to_string(&tampered).unwrap()));
+        assert_eq!(status, 422);
+        assert!(body.contains("\"failure\":\"constraint-failure\""));
+
+        let (status, body) = exchange(addr, b"GET /stats HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"requests\":2,\"verified\":1,\"rejected\":1,"));
+    }
+}
@@ -0,0 +1,786 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use winter_prover::StarkProof;
+use winter_verifier::{verify, VerifierError};
+use x25519_dalek::StaticSecret;
+
+use crate::aggregate_air::check_aggregate_length;
+use crate::air::{
+    check_field_extension, check_fresh_trace, from_data, Data, Op, PublicInputs, COEFF_DEGREE,
+    COEFF_LEVEL, PROOF_FORMAT_VERSION, VALUE_NUM,
+};
+use crate::base_ext_air::check_base_ext;
+use crate::blind_rotate_air::BLIND_ROTATE_DIGIT_NUM;
+use crate::canonical::{commitment, from_hex, to_hex};
+use crate::compare_air::check_plain_modulus;
+use crate::decrypt_air::PUBLIC_KEY_DIGIT_NUM;
+use crate::decrypt_share_air::check_share;
+use crate::encrypt_air::check_encryption;
+use crate::hoisted_rotate_air::HOIST_KEY_DIGIT_NUM;
+use crate::horner_air::check_coefficients;
+use crate::inner_product_air::{check_weights, load_result, result_digest};
+use crate::keyswitch_air::{load_evaluation_key, EvaluationKey, KEYSWITCH_DIGIT_NUM};
+#[cfg(feature = "goldilocks")]
+use crate::limb_mul_air::check_limb_mul_trace;
+use crate::mat_vec_air::check_matrix;
+use crate::modswitch_air::{DROPPED_LEVEL, KEPT_LEVEL_NUM};
+use crate::ntt_air::{check_ntt_modulus, NTT_TRACE_LENGTH};
+use crate::operation::{visit_operation, HeOperation, OperationVisitor};
+use crate::pipeline_air::{check_pipeline_scale, check_program};
+use crate::re_encrypt_air::RE_ENCRYPT_DIGIT_NUM;
+use crate::reduction::check_public_moduli;
+use crate::relin_air::RELIN_DIGIT_NUM;
+use crate::reproducibility::circuit_version;
+use crate::rotate_air::ROTATE_MAX_MODULUS_BITS;
+use crate::scalar_mul_air::check_scalar;
+use crate::scheme::check_scheme;
+use crate::sealed::{is_sealed, open, SealError};
+use crate::status::unix_now;
+use crate::storage::{FsStorage, Storage};
+use crate::sum_air::check_sum_length;
+use crate::window::{check_window, check_window_shape};
+
+// Why a proof was rejected. Monitoring keys on the kind: malformed containers and rejected
+// parameters point at integration bugs, while mismatched public inputs, failed constraint or FRI
+// checks and containers that do not authenticate point at tampering. Public inputs that differ
+// from the proven ones change the Fiat-Shamir transcript, so the STARK reports them as a
+// ConstraintFailure; PublicInputMismatch covers what is checked before, like the evaluation key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationFailure {
+    // unreadable container, missing fields or undecodable proof bytes
+    BadFormat(String),
+    // unknown or unsupported circuit, options, parameters or trace shape, or a policy refusal
+    ParamsRejected(String),
+    // the evaluation key differs from the one the proof was generated with
+    PublicInputMismatch(String),
+    // trace or constraint queries do not match their commitments, or the out-of-domain
+    // constraint evaluations are inconsistent
+    ConstraintFailure(String),
+    // the low-degree test or the proof-of-work of the query seed failed
+    FriFailure(String),
+    // a sealed container does not authenticate under the recipient's key
+    SignatureInvalid(String),
+}
+
+pub const FAILURE_KIND_NUM: usize = 6;
+
+impl VerificationFailure {
+    // Stable name recorded in status files, verification caches and server responses.
+    pub fn kind(&self) -> &'static str {
+        FAILURE_KINDS[self.kind_index()]
+    }
+
+    pub fn kind_index(&self) -> usize {
+        match self {
+            VerificationFailure::BadFormat(_) => 0,
+            VerificationFailure::ParamsRejected(_) => 1,
+            VerificationFailure::PublicInputMismatch(_) => 2,
+            VerificationFailure::ConstraintFailure(_) => 3,
+            VerificationFailure::FriFailure(_) => 4,
+            VerificationFailure::SignatureInvalid(_) => 5,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            VerificationFailure::BadFormat(message)
+            | VerificationFailure::ParamsRejected(message)
+            | VerificationFailure::PublicInputMismatch(message)
+            | VerificationFailure::ConstraintFailure(message)
+            | VerificationFailure::FriFailure(message)
+            | VerificationFailure::SignatureInvalid(message) => message,
+        }
+    }
+}
+
+pub const FAILURE_KINDS: [&str; FAILURE_KIND_NUM] = [
+    "bad-format",
+    "params-rejected",
+    "public-input-mismatch",
+    "constraint-failure",
+    "fri-failure",
+    "signature-invalid",
+];
+
+impl fmt::Display for VerificationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for VerificationFailure {}
+
+impl From<VerifierError> for VerificationFailure {
+    fn from(err: VerifierError) -> Self {
+        let message = err.to_string();
+        match err {
+            VerifierError::ProofDeserializationError(_) => VerificationFailure::BadFormat(message),
+            VerifierError::InconsistentBaseField | VerifierError::UnsupportedFieldExtension(_) => {
+                VerificationFailure::ParamsRejected(message)
+            }
+            VerifierError::InconsistentOodConstraintEvaluations
+            | VerifierError::TraceQueryDoesNotMatchCommitment
+            | VerifierError::ConstraintQueryDoesNotMatchCommitment => {
+                VerificationFailure::ConstraintFailure(message)
+            }
+            VerifierError::RandomCoinError
+            | VerifierError::QuerySeedProofOfWorkVerificationFailed
+            | VerifierError::FriVerificationFailed(_) => VerificationFailure::FriFailure(message),
+        }
+    }
+}
+
+// Callers that only report the reason keep working with strings.
+impl From<VerificationFailure> for String {
+    fn from(failure: VerificationFailure) -> Self {
+        failure.to_string()
+    }
+}
+
+// Reads a proof file without creating it when missing (unlike `confy::load_path`).
+pub fn load_data(path: &Path) -> Result<Data, VerificationFailure> {
+    open_data(path, None)
+}
+
+// Reads a proof file which may be sealed to the owner of `key`, see sealed.rs.
+pub fn open_data(path: &Path, key: Option<&StaticSecret>) -> Result<Data, VerificationFailure> {
+    let bytes = fs::read(path).map_err(|err| {
+        VerificationFailure::BadFormat(format!("cannot read {}: {}", path.display(), err))
+    })?;
+    decode_data(bytes, key, &path.display().to_string())
+}
+
+// Decodes the bytes of a proof container named `name` in error messages.
+pub fn decode_data(
+    mut bytes: Vec<u8>,
+    key: Option<&StaticSecret>,
+    name: &str,
+) -> Result<Data, VerificationFailure> {
+    use VerificationFailure::{BadFormat, SignatureInvalid};
+    if is_sealed(&bytes) {
+        let key = key.ok_or_else(|| {
+            BadFormat(format!(
+                "{} is sealed, pass the recipient's secret key to open it",
+                name
+            ))
+        })?;
+        bytes = open(&bytes, key).map_err(|err| match err {
+            SealError::Decryption => SignatureInvalid(format!("{}: {}", name, err)),
+            _ => BadFormat(format!("{}: {}", name, err)),
+        })?;
+    }
+    let text = String::from_utf8(bytes)
+        .map_err(|err| BadFormat(format!("cannot parse {}: {}", name, err)))?;
+    toml::from_str(&text).map_err(|err| BadFormat(format!("cannot parse {}: {}", name, err)))
+}
+
+// Decodes a proof container. Containers of a newer format than PROOF_FORMAT_VERSION are
+// rejected first; containers with a reproducibility manifest are rejected unless this build
+// knows the recorded field, hash function and field extension, implements the recorded circuit
+// version and the recorded options match the proof.
+pub fn parse_data(mut data: Data) -> Result<(Op, PublicInputs, StarkProof), VerificationFailure> {
+    use VerificationFailure::{BadFormat, ParamsRejected};
+    if data.format_version > PROOF_FORMAT_VERSION {
+        return Err(BadFormat(format!(
+            "proof container has format version {}, this verifier (stark {}) reads versions up \
+            to {}",
+            data.format_version,
+            env!("CARGO_PKG_VERSION"),
+            PROOF_FORMAT_VERSION
+        )));
+    }
+    let op = Op::from_name(&data.circuit)
+        .ok_or_else(|| ParamsRejected(format!("unknown circuit {}", data.circuit)))?;
+    op.check_field().map_err(ParamsRejected)?;
+    let manifest = data.reproducibility.take();
+    if let Some(manifest) = &manifest {
+        manifest.check_encoding(op).map_err(ParamsRejected)?;
+        manifest.check_circuit(op).map_err(ParamsRejected)?;
+    }
+    // results with a digest are distributed separately, matrix-vector products have a result
+    // per row of the matrix, the rows are checked against the weights below, aggregations
+    // without a mean only the sum
+    let result_num = match data.result_digest {
+        Some(_) => 0,
+        None if op == Op::MatVec => data.result.len().max(1).next_multiple_of(VALUE_NUM),
+        None if op == Op::Horner => data.weights.as_ref().map_or(0, |weights| weights.len()),
+        None if op == Op::Aggregate && data.scalar.is_none() => VALUE_NUM,
+        None => op.result_num(),
+    };
+    if data.result.len() != result_num {
+        return Err(BadFormat(format!(
+            "{} proof has {} result components, expected {}",
+            op.name(),
+            data.result.len(),
+            result_num
+        )));
+    }
+    // additions may have any accepted level count, checked against the trace length below
+    match data
+        .result
+        .iter()
+        .find(|levels| levels.len() != COEFF_LEVEL)
+    {
+        Some(levels) if op != Op::Add => {
+            return Err(BadFormat(format!(
+                "{} proof has results of {} levels, expected {}",
+                op.name(),
+                levels.len(),
+                COEFF_LEVEL
+            )))
+        }
+        _ => {}
+    }
+    let missing =
+        |what: &str| BadFormat(format!("{} proof does not record its {}", op.name(), what));
+    if matches!(
+        op,
+        Op::Rotate | Op::HoistedRotate | Op::KeyRotate | Op::BlindRotate
+    ) && data.rotation.is_none()
+    {
+        return Err(missing("rotation"));
+    }
+    // automorphisms are proven against their public input ciphertext, rotations and
+    // conjugations also against their public moduli
+    let automorphism = matches!(
+        op,
+        Op::Rotate | Op::Conjugate | Op::HoistedRotate | Op::KeyRotate | Op::KeyConjugate
+    );
+    match (&data.input, automorphism) {
+        (Some(input), true) => {
+            let modulus = data.modulus.as_ref().ok_or_else(|| missing("moduli"))?;
+            if matches!(op, Op::Rotate | Op::Conjugate) {
+                check_public_moduli(op, modulus, ROTATE_MAX_MODULUS_BITS)
+                    .map_err(ParamsRejected)?;
+            }
+            input.check(modulus).map_err(ParamsRejected)?;
+        }
+        (None, true) => return Err(missing("input ciphertext")),
+        (Some(_), false) => {
+            return Err(ParamsRejected(format!(
+                "{} proofs take no input ciphertext, but one is recorded",
+                op.name()
+            )))
+        }
+        (None, false) => {}
+    }
+    if op == Op::Ntt || op == Op::Intt || op == Op::Pack || op == Op::Unpack {
+        check_ntt_modulus(data.modulus.as_ref().ok_or_else(|| missing("moduli"))?)
+            .map_err(ParamsRejected)?;
+    }
+    if op == Op::BaseExt {
+        check_base_ext(
+            data.modulus.as_ref().ok_or_else(|| missing("moduli"))?,
+            &data.result,
+        )
+        .map_err(ParamsRejected)?;
+    }
+    if op == Op::Decrypt {
+        let modulus = data.modulus.as_ref().ok_or_else(|| missing("moduli"))?;
+        let plain_modulus = data.plain_modulus.ok_or_else(|| missing("plain modulus"))?;
+        data.decryption
+            .as_ref()
+            .ok_or_else(|| missing("ciphertext"))?
+            .check(modulus, plain_modulus, &data.result)
+            .map_err(ParamsRejected)?;
+    }
+    if op == Op::DecryptShare {
+        check_share(
+            data.modulus.as_ref().ok_or_else(|| missing("moduli"))?,
+            data.decryption
+                .as_ref()
+                .ok_or_else(|| missing("ciphertext"))?,
+            &data.result,
+        )
+        .map_err(ParamsRejected)?;
+    }
+    if op == Op::Encrypt {
+        let modulus = data.modulus.as_ref().ok_or_else(|| missing("moduli"))?;
+        let plain_modulus = data.plain_modulus.ok_or_else(|| missing("plain modulus"))?;
+        check_encryption(modulus, plain_modulus, &data.result).map_err(ParamsRejected)?;
+    }
+    if op == Op::Compare {
+        check_plain_modulus(data.plain_modulus.ok_or_else(|| missing("plain modulus"))?)
+            .map_err(ParamsRejected)?;
+    }
+    if op == Op::CkksEncode {
+        data.slots
+            .as_ref()
+            .ok_or_else(|| missing("slot values"))?
+            .check(&data.result)
+            .map_err(ParamsRejected)?;
+    }
+    if matches!(op, Op::ModSwitch | Op::Rescale) && KEPT_LEVEL_NUM == 0 {
+        return Err(ParamsRejected(format!(
+            "{} needs a second RNS level",
+            op.name()
+        )));
+    }
+    if op == Op::Rescale {
+        let scale = data.scale.ok_or_else(|| missing("scales"))?;
+        scale.check().map_err(ParamsRejected)?;
+        // the division is proven by the last public modulus
+        let modulus = data.modulus.as_ref().ok_or_else(|| missing("moduli"))?;
+        if modulus.get(DROPPED_LEVEL) != Some(&scale.modulus) {
+            return Err(ParamsRejected(format!(
+                "rescale proof divides by {}, but its moduli are {:?}",
+                scale.modulus, modulus
+            )));
+        }
+    }
+    check_scheme(op, data.scheme).map_err(ParamsRejected)?;
+    if op != Op::Add && data.packing.is_some() {
+        return Err(ParamsRejected(format!(
+            "{} proofs hold one coefficient per row, but a packing is recorded",
+            op.name()
+        )));
+    }
+    if let Some(window) = &data.window {
+        check_window(op, window).map_err(ParamsRejected)?;
+        check_window_shape(op, window, &data.result).map_err(BadFormat)?;
+    }
+    if op == Op::ScalarMul {
+        check_scalar(data.scalar.ok_or_else(|| missing("scalar"))?).map_err(ParamsRejected)?;
+    }
+    if let (Op::Aggregate, Some(scalar)) = (op, data.scalar) {
+        check_scalar(scalar).map_err(ParamsRejected)?;
+    }
+    if matches!(
+        op,
+        Op::InnerProduct | Op::MatVec | Op::Horner | Op::Pipeline
+    ) {
+        data.weights.as_ref().ok_or_else(|| missing("weights"))?;
+    }
+    if op == Op::InnerProduct {
+        data.result_digest
+            .as_deref()
+            .ok_or_else(|| missing("result digest"))?;
+    }
+    match data.result_digest.as_deref() {
+        Some(digest) if op != Op::InnerProduct => {
+            return Err(BadFormat(format!(
+                "{} proofs record their result, not its digest {}",
+                op.name(),
+                digest
+            )))
+        }
+        Some(digest) if from_hex(digest).is_none() => {
+            return Err(BadFormat(format!("malformed result digest {}", digest)))
+        }
+        _ => {}
+    }
+    let (pub_inputs, proof_bytes) =
+        from_data(data).map_err(|err| BadFormat(format!("invalid proof encoding: {}", err)))?;
+    let proof = StarkProof::from_bytes(&proof_bytes)
+        .map_err(|err| BadFormat(format!("invalid proof: {}", err)))?;
+    check_field_extension(proof.options().field_extension()).map_err(ParamsRejected)?;
+    if op == Op::Add {
+        let width = proof.trace_layout().main_trace_width();
+        check_fresh_trace(
+            width,
+            proof.trace_length(),
+            &pub_inputs.result,
+            pub_inputs
+                .modulus
+                .as_deref()
+                .ok_or_else(|| missing("moduli"))?,
+            pub_inputs.packing,
+        )
+        .map_err(ParamsRejected)?;
+    }
+    #[cfg(feature = "goldilocks")]
+    if op == Op::WideMul {
+        check_limb_mul_trace(
+            pub_inputs
+                .modulus
+                .as_deref()
+                .ok_or_else(|| missing("moduli"))?,
+            proof.trace_length(),
+        )
+        .map_err(ParamsRejected)?;
+    }
+    // the circuits reducing with Barrett row groups size their blocks to the public moduli and
+    // repeat every row of a coefficient, or of a butterfly in the NTT networks, over a group; the
+    // running sums have a row per operand slot and check their length below
+    if let Some(reductions) = op.reductions(&pub_inputs) {
+        let group = reductions.map_err(ParamsRejected)?.group();
+        let variable = matches!(
+            op,
+            Op::Sum | Op::Aggregate | Op::InnerProduct | Op::MatVec | Op::Horner | Op::Pipeline
+        );
+        let (rows, kind) = match op {
+            Op::Ntt | Op::Intt | Op::Decrypt | Op::DecryptShare | Op::Encrypt | Op::CkksEncode => {
+                (NTT_TRACE_LENGTH, "butterfly")
+            }
+            _ => (COEFF_DEGREE, "coefficient"),
+        };
+        if !variable && proof.trace_length() != rows * group {
+            return Err(ParamsRejected(format!(
+                "{} proof has a trace of {} rows, expected {} {} rows of {} rows",
+                op.name(),
+                proof.trace_length(),
+                rows,
+                kind,
+                group
+            )));
+        }
+    }
+    if op == Op::Sum {
+        check_sum_length(proof.trace_length()).map_err(ParamsRejected)?;
+    }
+    if op == Op::Aggregate {
+        check_aggregate_length(proof.trace_length()).map_err(ParamsRejected)?;
+    }
+    match (op, &pub_inputs.weights) {
+        (Op::InnerProduct, Some(weights)) => {
+            check_weights(weights, Some(proof.trace_length())).map_err(ParamsRejected)?
+        }
+        (Op::MatVec, Some(weights)) => check_matrix(
+            weights,
+            pub_inputs.result.len() / VALUE_NUM,
+            Some(proof.trace_length()),
+        )
+        .map_err(ParamsRejected)?,
+        (Op::Horner, Some(coefficients)) => {
+            check_coefficients(coefficients, Some(proof.trace_length())).map_err(ParamsRejected)?
+        }
+        (Op::Pipeline, Some(steps)) => {
+            check_program(steps, Some(proof.trace_length())).map_err(ParamsRejected)?;
+            check_pipeline_scale(steps, pub_inputs.scale).map_err(ParamsRejected)?;
+            // as for rescale proofs, the rounding is proven by the last public modulus
+            if let (Some(scale), Some(modulus)) = (pub_inputs.scale, &pub_inputs.modulus) {
+                if modulus.get(DROPPED_LEVEL) != Some(&scale.modulus) {
+                    return Err(ParamsRejected(format!(
+                        "pipeline proof divides by {}, but its moduli are {:?}",
+                        scale.modulus, modulus
+                    )));
+                }
+            }
+        }
+        (_, Some(_)) => {
+            return Err(BadFormat(format!(
+                "{} proofs take no weights, but some are recorded",
+                op.name()
+            )))
+        }
+        (_, None) => {}
+    }
+    if let Some(manifest) = &manifest {
+        manifest
+            .check_options(proof.options())
+            .map_err(ParamsRejected)?;
+        manifest
+            .check_security(proof.security_level(true))
+            .map_err(ParamsRejected)?;
+        manifest
+            .check_profile(proof.options())
+            .map_err(ParamsRejected)?;
+    }
+    Ok((op, pub_inputs, proof))
+}
+
+// Number of digits of the evaluation key `op` is verified against, None if it takes no key.
+pub fn evaluation_key_digits(op: Op) -> Option<usize> {
+    match op {
+        Op::Relin => Some(RELIN_DIGIT_NUM),
+        Op::KeySwitch => Some(KEYSWITCH_DIGIT_NUM),
+        Op::BlindRotate => Some(BLIND_ROTATE_DIGIT_NUM),
+        Op::ReEncrypt | Op::KeyRotate | Op::KeyConjugate => Some(RE_ENCRYPT_DIGIT_NUM),
+        Op::HoistedRotate => Some(HOIST_KEY_DIGIT_NUM),
+        Op::Decrypt | Op::DecryptShare | Op::Encrypt => Some(PUBLIC_KEY_DIGIT_NUM),
+        _ => None,
+    }
+}
+
+// Relinearization, key-switching, decryption, decryption share, encryption, blind rotation,
+// re-encryption, hoisted rotation and automorphism key proofs are verified against a key loaded
+// from `path`, which must have the digest recorded in the proof container.
+pub fn attach_evaluation_key(
+    op: Op,
+    recorded_digest: Option<&str>,
+    path: Option<&str>,
+    pub_inputs: &mut PublicInputs,
+) -> Result<(), VerificationFailure> {
+    if evaluation_key_digits(op).is_none() {
+        return Ok(());
+    }
+    let path = path.ok_or_else(|| {
+        VerificationFailure::ParamsRejected(format!("{} proofs need an evaluation key", op.name()))
+    })?;
+    let key =
+        load_evaluation_key(path).map_err(|err| VerificationFailure::BadFormat(err.to_string()))?;
+    check_evaluation_key(op, &key, path, recorded_digest)?;
+    pub_inputs.evaluation_key = Some(key);
+    Ok(())
+}
+
+// Checks that `key`, named `name` in error messages, fits `op` and has the recorded digest.
+pub fn check_evaluation_key(
+    op: Op,
+    key: &EvaluationKey,
+    name: &str,
+    recorded_digest: Option<&str>,
+) -> Result<(), VerificationFailure> {
+    use VerificationFailure::{BadFormat, ParamsRejected, PublicInputMismatch};
+    let digits = evaluation_key_digits(op)
+        .ok_or_else(|| ParamsRejected(format!("{} proofs take no evaluation key", op.name())))?;
+    if key.values.len() != digits {
+        return Err(ParamsRejected(format!(
+            "evaluation key {} has {} digits, {} proofs need {}",
+            name,
+            key.values.len(),
+            op.name(),
+            digits
+        )));
+    }
+    // the monomial values of blind rotations are derived from the moduli, which the key shares
+    if op == Op::BlindRotate {
+        check_ntt_modulus(&key.modulus).map_err(ParamsRejected)?;
+    }
+    let digest = to_hex(&key.digest());
+    match recorded_digest {
+        Some(recorded) if recorded == digest => Ok(()),
+        Some(recorded) => Err(PublicInputMismatch(format!(
+            "evaluation key {} has digest {}, but the proof was generated with {}",
+            name, digest, recorded
+        ))),
+        None => Err(BadFormat(String::from(
+            "proof does not record the digest of its evaluation key",
+        ))),
+    }
+}
+
+// Inner product proofs record only the digest of their result, which is verified against the
+// ciphertext loaded from `path`.
+pub fn attach_result(
+    path: Option<&str>,
+    pub_inputs: &mut PublicInputs,
+) -> Result<(), VerificationFailure> {
+    let recorded = match pub_inputs.result_digest {
+        Some(digest) => digest,
+        None => return Ok(()),
+    };
+    let path = path.ok_or_else(|| {
+        VerificationFailure::ParamsRejected(String::from(
+            "the proof records only the digest of its result, pass the result ciphertext",
+        ))
+    })?;
+    let (modulus, result) = load_result(path).map_err(VerificationFailure::BadFormat)?;
+    if let Some(proven) = pub_inputs
+        .modulus
+        .as_ref()
+        .filter(|&proven| *proven != modulus)
+    {
+        return Err(VerificationFailure::PublicInputMismatch(format!(
+            "result {} has moduli {:?}, but the proof reduces by {:?}",
+            path, modulus, proven
+        )));
+    }
+    let digest = result_digest(&result);
+    if digest != recorded {
+        return Err(VerificationFailure::PublicInputMismatch(format!(
+            "result {} has digest {}, but the proof records {}",
+            path,
+            to_hex(&digest),
+            to_hex(&recorded)
+        )));
+    }
+    pub_inputs.result = result;
+    Ok(())
+}
+
+pub fn verify_proof(
+    op: Op,
+    proof: StarkProof,
+    pub_inputs: PublicInputs,
+) -> Result<(), VerificationFailure> {
+    if pub_inputs.result_digest.is_some() && pub_inputs.result.is_empty() {
+        return Err(VerificationFailure::ParamsRejected(format!(
+            "{} proofs are verified against their result ciphertext",
+            op.name()
+        )));
+    }
+    // key switches and blind rotations reduce by the public moduli, which the key must share
+    if let (
+        Op::Relin
+        | Op::KeySwitch
+        | Op::ReEncrypt
+        | Op::HoistedRotate
+        | Op::KeyRotate
+        | Op::KeyConjugate
+        | Op::BlindRotate,
+        Some(key),
+        Some(modulus),
+    ) = (op, &pub_inputs.evaluation_key, &pub_inputs.modulus)
+    {
+        if &key.modulus != modulus {
+            return Err(VerificationFailure::PublicInputMismatch(format!(
+                "evaluation key has moduli {:?}, but the proof reduces by {:?}",
+                key.modulus, modulus
+            )));
+        }
+    }
+    visit_operation(op, Verification { proof, pub_inputs }).map_err(VerificationFailure::from)
+}
+
+struct Verification {
+    proof: StarkProof,
+    pub_inputs: PublicInputs,
+}
+
+impl OperationVisitor for Verification {
+    type Output = Result<(), VerifierError>;
+
+    fn visit<O: HeOperation>(self) -> Self::Output {
+        verify::<O::Air>(self.proof, self.pub_inputs)
+    }
+}
+
+pub fn verify_data(data: Data, evaluation_key: Option<&str>) -> Result<(), VerificationFailure> {
+    let digest = data.evaluation_key_digest.clone();
+    let (op, mut pub_inputs, proof) = parse_data(data)?;
+    attach_evaluation_key(op, digest.as_deref(), evaluation_key, &mut pub_inputs)?;
+    verify_proof(op, proof, pub_inputs)
+}
+
+// Hex BLAKE3-256 digest of a proof file's bytes, used as the verification cache key.
+pub fn proof_hash(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    Ok(to_hex(&commitment(&bytes)))
+}
+
+fn cache_key_prefix() -> String {
+    format!("{}/", env!("CARGO_PKG_VERSION"))
+}
+
+// The VerificationCache key of the proof of hash `proof_hash` for the circuit `op`.
+pub fn cache_key(proof_hash: &str, op: Op) -> String {
+    format!(
+        "{}{}/{}",
+        cache_key_prefix(),
+        circuit_version(op),
+        proof_hash
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResult {
+    pub job: String,
+    pub passed: bool,
+    pub verified_at: u64,
+    pub message: Option<String>,
+    // kind of the failure, see VerificationFailure::kind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+// Verification outcomes keyed by proof hash, crate version and circuit version, see cache_key.
+// A changed proof file, another release of the verifier or another version of the circuit gets
+// a new key, so stale results are never reused; loading drops those of other releases.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VerificationCache {
+    #[serde(default)]
+    pub results: BTreeMap<String, CachedResult>,
+}
+
+impl VerificationCache {
+    pub fn load(path: &Path) -> VerificationCache {
+        let (storage, key) = FsStorage::for_file(path);
+        VerificationCache::load_from(&storage, &key)
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), String> {
+        let (storage, key) = FsStorage::for_file(path);
+        self.store_to(&storage, &key)
+    }
+
+    // Cache stored under `key`; missing or unreadable caches start empty.
+    pub fn load_from(storage: &dyn Storage, key: &str) -> VerificationCache {
+        let mut cache: VerificationCache = storage
+            .get(key)
+            .ok()
+            .flatten()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default();
+        let release = cache_key_prefix();
+        cache.results.retain(|key, _| key.starts_with(&release));
+        cache
+    }
+
+    pub fn store_to(&self, storage: &dyn Storage, key: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|err| err.to_string())?;
+        storage
+            .put(key, text.as_bytes())
+            .map_err(|err| format!("cannot write {}: {}", key, err))
+    }
+
+    // The outcome recorded under `key`, a cache_key.
+    pub fn get(&self, key: &str) -> Option<&CachedResult> {
+        self.results.get(key)
+    }
+
+    pub fn record(&mut self, key: String, job: &str, result: &Result<(), VerificationFailure>) {
+        self.results.insert(
+            key,
+            CachedResult {
+                job: job.to_string(),
+                passed: result.is_ok(),
+                verified_at: unix_now(),
+                message: result.as_ref().err().map(|err| err.to_string()),
+                failure: result.as_ref().err().map(|err| err.kind().to_string()),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn cache_keys_cover_the_proof_crate_and_circuit() {
+        let storage = MemoryStorage::new();
+        let mut cache = VerificationCache::default();
+        let key = cache_key("00ff", Op::Add);
+        cache.record(key.clone(), "job", &Ok(()));
+        let failure = VerificationFailure::ConstraintFailure(String::from("bad"));
+        cache.record(cache_key("01ff", Op::Add), "job", &Err(failure));
+        // an entry of another release of the crate
+        let stale = format!("0.0.0/{}/00ff", circuit_version(Op::Add));
+        cache.record(stale.clone(), "job", &Ok(()));
+        cache.store_to(&storage, "cache").unwrap();
+
+        let cache = VerificationCache::load_from(&storage, "cache");
+        assert!(cache.get(&key).unwrap().passed);
+        let failed = cache.get(&cache_key("01ff", Op::Add)).unwrap();
+        assert!(!failed.passed);
+        assert_eq!(failed.failure.as_deref(), Some("constraint-failure"));
+        assert!(cache.get(&cache_key("02ff", Op::Add)).is_none());
+        assert!(cache.get(&cache_key("00ff", Op::Sub)).is_none());
+        assert!(cache.get(&stale).is_none());
+        assert_eq!(cache.results.len(), 2);
+    }
+
+    #[test]
+    fn unreadable_caches_start_empty() {
+        let storage = MemoryStorage::new();
+        assert!(VerificationCache::load_from(&storage, "cache")
+            .results
+            .is_empty());
+        storage.put("cache", b"results = 42").unwrap();
+        assert!(VerificationCache::load_from(&storage, "cache")
+            .results
+            .is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![allow(clippy::needless_range_loop)]
+
+// Ciphertext-ciphertext multiplication for moduli of up to 63 bits, the largest integers of a
+// TOML data file. MulAir reduces the tensor product terms with Barrett blocks of 62-bit moduli;
+// here the blocks take the BARRETT_WIDE_MODULUS_BITS moduli of barrett.rs. The middle term
+// a0 * b1 + a1 * b0 reaches 2 * (m - 1)^2 < 2^127, so its quotient takes 64 bits and q * m + r
+// still stays below the field modulus. The layout is that of MulAir with the wider blocks: every
+// circuit row holds the tensor product of one evaluation point for every RNS level, repeated over
+// a row group, one term reduced per row against the moduli of the public inputs.
+
+use winter_air::{Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo};
+use winter_math::FieldElement;
+
+use crate::air::{
+    element_from_u64, level_column, load_data_file, read_modulus, BaseElement, InputArg, Op,
+    PublicInputs, TraceType, COEFF_DEGREE, COEFF_LEVEL, MODULUS_NUM, VALUE_NUM,
+};
+use crate::barrett::BARRETT_WIDE_MODULUS_BITS;
+use crate::ciphertext::{check_levels, check_modulus_bits};
+use crate::error::StarkHeError;
+use crate::mul_air::{tensor_terms, MulData, MUL_DATA_NUM, MUL_RESULT_NUM};
+use crate::reduction::{
+    bind_moduli, check_public_moduli, read_group_result, reduction_group, reduction_width,
+    Reductions, MODULUS_ASSERTION_NUM,
+};
+
+pub const WIDE_MUL_MAX_MODULUS_BITS: u32 = BARRETT_WIDE_MODULUS_BITS as u32;
+
+// Modulus + Result + Data + Reduction
+// M0 M1 R00 R01 R10 R11 R20 R21 A00 A01 A10 A11 B00 B01 B10 B11 K.. R.. S..
+const RESULT_LEN: usize = MUL_RESULT_NUM * COEFF_LEVEL;
+const DATA_LEN: usize = VALUE_NUM * COEFF_LEVEL;
+const RESULT_START: usize = MODULUS_NUM;
+const DATA_START: usize = RESULT_START + RESULT_LEN;
+const DATA_END: usize = DATA_START + MUL_DATA_NUM * DATA_LEN;
+// the middle term reaches 2 (m - 1)^2, so its quotient takes a bit more than the modulus
+const REDUCTION_WIDTH: usize = reduction_width(
+    WIDE_MUL_MAX_MODULUS_BITS as usize + 1,
+    WIDE_MUL_MAX_MODULUS_BITS as usize,
+);
+// one row per term and level, then padding
+pub const WIDE_MUL_GROUP: usize = reduction_group(RESULT_LEN);
+
+const STATE_WIDTH: usize = DATA_END + REDUCTION_WIDTH;
+const STATE_LENGTH: usize = COEFF_DEGREE;
+
+// Trace layout constants of WideMulAir in canonical order.
+pub fn wide_mul_layout() -> Vec<(&'static str, usize)> {
+    vec![
+        ("modulus_num", MODULUS_NUM),
+        ("data_num", MUL_DATA_NUM),
+        ("value_num", VALUE_NUM),
+        ("result_num", MUL_RESULT_NUM),
+        ("coeff_level", COEFF_LEVEL),
+        ("coeff_degree", COEFF_DEGREE),
+        ("group", WIDE_MUL_GROUP),
+        ("state_width", STATE_WIDTH),
+    ]
+}
+
+fn data_column(d: usize, v: usize, l: usize) -> usize {
+    level_column(DATA_START + d * DATA_LEN, v, l)
+}
+
+fn result_column(r: usize, l: usize) -> usize {
+    level_column(RESULT_START, r, l)
+}
+
+// The reductions of a row by the public moduli `modulus`, as for MulAir with blocks of up to
+// WIDE_MUL_MAX_MODULUS_BITS bits.
+pub fn wide_mul_reductions(modulus: &[u64]) -> Result<Reductions, String> {
+    check_public_moduli(Op::WideMul, modulus, WIDE_MUL_MAX_MODULUS_BITS)?;
+    Reductions::of_components(
+        modulus,
+        MUL_RESULT_NUM,
+        result_column,
+        |r, l| (1 + (r == 1) as u128) * (modulus[l] as u128 - 1).pow(2),
+        (RESULT_START..DATA_END).collect(),
+        DATA_END,
+        REDUCTION_WIDTH,
+    )
+}
+
+pub fn build_wide_mul_trace(arg: &InputArg) -> Result<TraceType, StarkHeError> {
+    let data: MulData = load_data_file(&arg.data_file_path)?;
+    check_modulus_bits(&data.modulus, WIDE_MUL_MAX_MODULUS_BITS)
+        .map_err(StarkHeError::InvalidInput)?;
+    for operand in data.values.iter() {
+        check_levels(&data.modulus, STATE_LENGTH, operand)?;
+    }
+    let reductions = wide_mul_reductions(&data.modulus).map_err(StarkHeError::InvalidInput)?;
+
+    let mut columns = vec![vec![BaseElement::ZERO; STATE_LENGTH]; DATA_END];
+    let terms = |k: usize, l: usize| {
+        let [a, b] = [0, 1].map(|d| [0, 1].map(|v| data.values[d][v][l][k]));
+        tensor_terms(a, b)
+    };
+    for l in 0..COEFF_LEVEL {
+        let m = data.modulus[l];
+        columns[l] = vec![element_from_u64(m); STATE_LENGTH];
+        for k in 0..STATE_LENGTH {
+            for d in 0..MUL_DATA_NUM {
+                for v in 0..VALUE_NUM {
+                    columns[data_column(d, v, l)][k] = element_from_u64(data.values[d][v][l][k]);
+                }
+            }
+            for (r, term) in terms(k, l).into_iter().enumerate() {
+                columns[result_column(r, l)][k] = element_from_u64((term % m as u128) as u64);
+            }
+        }
+    }
+    Ok(reductions.build_trace(columns, STATE_WIDTH, |k, j| {
+        terms(k, j % COEFF_LEVEL)[j / COEFF_LEVEL]
+    }))
+}
+
+pub fn get_wide_mul_pub_inputs(trace: &TraceType) -> PublicInputs {
+    PublicInputs {
+        result: read_group_result(trace, MUL_RESULT_NUM, WIDE_MUL_GROUP, result_column),
+        evaluation_key: None,
+        rotation: None,
+        scale: None,
+        modulus: Some(read_modulus(trace)),
+        plain_modulus: None,
+        decryption: None,
+        slots: None,
+        scalar: None,
+        weights: None,
+        result_digest: None,
+        window: None,
+        scheme: None,
+        packing: None,
+        input: None,
+    }
+}
+
+// The terms of one row, term r at level l at r * COEFF_LEVEL + l.
+fn row_terms<E: FieldElement>(row: &[E]) -> [E; RESULT_LEN] {
+    let mut terms = [E::ZERO; RESULT_LEN];
+    for l in 0..COEFF_LEVEL {
+        let a = [row[data_column(0, 0, l)], row[data_column(0, 1, l)]];
+        let b = [row[data_column(1, 0, l)], row[data_column(1, 1, l)]];
+        let products = [a[0] * b[0], a[0] * b[1] + a[1] * b[0], a[1] * b[1]];
+        for (r, product) in products.into_iter().enumerate() {
+            terms[r * COEFF_LEVEL + l] = product;
+        }
+    }
+    terms
+}
+
+pub struct WideMulAir {
+    context: AirContext<BaseElement>,
+    reductions: Reductions,
+    modulus: Vec<u64>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+}
+
+impl Air for WideMulAir {
+    type BaseField = BaseElement;
+    type PublicInputs = PublicInputs;
+
+    // As in MulAir the terms are quadratic in the operands and selected by a periodic column; the
+    // moduli were checked by parse_data.
+    fn new(trace_info: TraceInfo, pub_inputs: PublicInputs, options: ProofOptions) -> Self {
+        let modulus = pub_inputs
+            .modulus
+            .expect("wide-mul proofs record their moduli");
+        let reductions = wide_mul_reductions(&modulus).expect("wide-mul moduli were checked");
+        let degrees = reductions.degrees(2, &[]);
+        let num_assertions = RESULT_LEN + MODULUS_ASSERTION_NUM;
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        WideMulAir {
+            context: reductions.boundary().apply(context),
+            reductions,
+            modulus,
+            result: pub_inputs.result,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        self.reductions.periodic_columns()
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let terms = row_terms(frame.current());
+        self.reductions
+            .evaluate(frame, periodic_values, &terms, result);
+    }
+
+    // Every result coefficient is bound on the first row of its group, the modulus columns to the
+    // public moduli.
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let mut columns = Vec::with_capacity(RESULT_LEN);
+        for r in 0..MUL_RESULT_NUM {
+            for l in 0..COEFF_LEVEL {
+                columns.push((result_column(r, l), self.result[r][l].clone()));
+            }
+        }
+        let mut assertions = self.reductions.bind_columns(columns);
+        assertions.extend(bind_moduli(&self.modulus, 0, self.reductions.group()));
+        assertions
+    }
+}
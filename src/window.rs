@@ -0,0 +1,170 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Windowed results: with `--expose 3,17,42` the proof binds only the result coefficients at
+// those indices, at every level of every component, as public inputs. The other coefficients are
+// still columns of the committed trace and constrained like before, so they are fixed by the
+// proof, but they are neither recorded in the proof file nor asserted by the verifier. The
+// STARK is not zero-knowledge: queried rows of the extended trace still depend on them.
+//
+// Windows apply to the circuits whose result coefficient k sits in row k of its result column,
+// or in the first row of row group k for those reducing with reduction.rs, and is bound on every
+// such row; the others bind their results differently and reject windows.
+
+use winter_air::{Air, Assertion, ProofOptions};
+use winter_prover::Prover;
+
+use crate::air::{bind_columns, BaseElement, Op, PublicInputs, COEFF_DEGREE, COEFF_LEVEL};
+
+// Whether the results of `op` can be windowed.
+pub fn windowed(op: Op) -> bool {
+    matches!(
+        op,
+        Op::Sub
+            | Op::Mul
+            | Op::Relin
+            | Op::Rotate
+            | Op::Conjugate
+            | Op::KeySwitch
+            | Op::BlindRotate
+            | Op::Pack
+            | Op::Unpack
+            | Op::MultiKeyAdd
+            | Op::ReEncrypt
+            | Op::Compare
+            | Op::ModSwitch
+            | Op::Rescale
+            | Op::PlainMul
+            | Op::Neg
+            | Op::ModRaise
+            | Op::ScalarMul
+            | Op::Fma
+    )
+}
+
+// A window of `op` lists distinct coefficient indices in increasing order.
+pub fn check_window(op: Op, window: &[usize]) -> Result<(), String> {
+    if !windowed(op) {
+        return Err(format!("{} results cannot be windowed", op.name()));
+    }
+    if window.is_empty() {
+        return Err(String::from(
+            "a result window needs at least one coefficient",
+        ));
+    }
+    if window.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(String::from(
+            "result window indices must be distinct and in increasing order",
+        ));
+    }
+    match window.last() {
+        Some(&last) if last >= COEFF_DEGREE => Err(format!(
+            "result window index {} is out of range, there are {} coefficients",
+            last, COEFF_DEGREE
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Every level of a windowed result holds one coefficient per window index, except the dropped
+// level of a modulus switch or rescale, which is empty.
+pub fn check_window_shape(
+    op: Op,
+    window: &[usize],
+    result: &[Vec<Vec<u64>>],
+) -> Result<(), String> {
+    let dropped = matches!(op, Op::ModSwitch | Op::Rescale).then_some(COEFF_LEVEL - 1);
+    for (v, levels) in result.iter().enumerate() {
+        for (l, values) in levels.iter().enumerate() {
+            let expected = if Some(l) == dropped { 0 } else { window.len() };
+            if values.len() != expected {
+                return Err(format!(
+                    "windowed result component {} level {} has {} coefficients, expected {}",
+                    v,
+                    l,
+                    values.len(),
+                    expected
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The coefficients of `result` at the window indices; levels without coefficients, such as the
+// dropped level of a modulus switch, stay empty.
+pub fn apply_window(
+    result: Vec<Vec<Vec<BaseElement>>>,
+    window: &[usize],
+) -> Vec<Vec<Vec<BaseElement>>> {
+    result
+        .into_iter()
+        .map(|levels| {
+            levels
+                .into_iter()
+                .map(|values| {
+                    if values.is_empty() {
+                        values
+                    } else {
+                        window.iter().map(|&k| values[k]).collect()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Number of assertions binding `num_columns` result columns, see `bind_results`.
+pub fn result_assertion_num(num_columns: usize, window: Option<&[usize]>) -> usize {
+    num_columns * window.map_or(2, <[usize]>::len)
+}
+
+// Binds every row of each result column as `bind_columns` does or, with a window, the rows of
+// the window to the windowed values.
+pub fn bind_results(
+    columns: Vec<(usize, Vec<BaseElement>)>,
+    window: Option<&[usize]>,
+) -> Vec<Assertion<BaseElement>> {
+    let window = match window {
+        Some(window) => window,
+        None => return bind_columns(columns),
+    };
+    let mut assertions = Vec::with_capacity(columns.len() * window.len());
+    for (column, values) in columns {
+        for (&step, &value) in window.iter().zip(values.iter()) {
+            assertions.push(Assertion::single(column, step, value));
+        }
+    }
+    assertions
+}
+
+// A prover whose public inputs hold only the windowed results, otherwise identical to `inner`.
+pub struct WindowedProver<P> {
+    pub inner: P,
+    pub window: Option<Vec<usize>>,
+}
+
+impl<P> Prover for WindowedProver<P>
+where
+    P: Prover<BaseField = BaseElement>,
+    P::Air: Air<PublicInputs = PublicInputs>,
+{
+    type BaseField = BaseElement;
+    type Air = P::Air;
+    type Trace = P::Trace;
+
+    fn get_pub_inputs(&self, trace: &Self::Trace) -> PublicInputs {
+        let mut public_input = self.inner.get_pub_inputs(trace);
+        if let Some(window) = &self.window {
+            public_input.result = apply_window(public_input.result, window);
+            public_input.window = Some(window.clone());
+        }
+        public_input
+    }
+
+    fn options(&self) -> &ProofOptions {
+        self.inner.options()
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Helpers of the micro and goldilocks round trips, each of which uses a part of them.
+#![allow(dead_code)]
+
+use serde::Serialize;
+use stark_he::air::{InputArg, Op, COEFF_DEGREE};
+use stark_he::error::StarkHeError;
+use stark_he::proving::prove_to_file;
+use stark_he::status::StatusReporter;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use winter_air::ProofOptions;
+
+// xorshift64* residues, so that no constraint holds on the wrap-around row by accident
+pub fn residues(seed: u64, m: u64) -> Vec<u64> {
+    let mut state = 0x9e37_79b9_7f4a_7c15u64 ^ (seed + 1).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    (0..COEFF_DEGREE)
+        .map(|_| {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_f491_4f6c_dd1d) % m
+        })
+        .collect()
+}
+
+// Writes `value` as TOML to the file `name` of `dir` and returns its path.
+pub fn write_toml<T: Serialize>(dir: &Path, name: &str, value: &T) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, toml::to_string(value).unwrap()).unwrap();
+    path
+}
+
+// The arguments of `op` on the data file at `data_file_path`, the defaults for the rest.
+pub fn input_args(op: Op, data_file_path: &Path) -> InputArg {
+    InputArg {
+        data_file_path: data_file_path.to_string_lossy().into_owned(),
+        op,
+        ..InputArg::default()
+    }
+}
+
+// Proves `input_args` to the file at `proof_file_path`, reporting to no status file.
+pub fn prove(
+    input_args: &InputArg,
+    options: ProofOptions,
+    proof_file_path: &Path,
+) -> Result<(), StarkHeError> {
+    let mut status = StatusReporter::new(None, input_args.op.name());
+    prove_to_file(
+        input_args,
+        options,
+        None,
+        None,
+        None,
+        proof_file_path,
+        &mut status,
+    )
+}
+
+// The path of a proof file in a temporary directory of its own, so that tests running in
+// parallel never share a file; the directory is removed with it.
+#[derive(Debug)]
+pub struct ProofFile {
+    dir: TempDir,
+    path: PathBuf,
+}
+
+impl ProofFile {
+    pub fn new(name: &str) -> Self {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(format!("{}.proof", name));
+        ProofFile { dir, path }
+    }
+
+    // the directory of the proof file, for the files it is proved from
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Deref for ProofFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for ProofFile {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Round trips of the circuits a goldilocks build proves, over the 64-bit field, through the same
+// files and checks as the binaries; the micro round trips cover the 128-bit field.
+#![cfg(all(feature = "micro", feature = "goldilocks"))]
+
+mod common;
+
+use common::{input_args, prove, residues, write_toml, ProofFile};
+use serde::Serialize;
+use stark_he::air::{CustomData, Op, COEFF_DEGREE, COEFF_LEVEL, MIN_FIELD_EXTENSION, VALUE_NUM};
+use stark_he::error::StarkHeError;
+use stark_he::fma_air::FmaData;
+use stark_he::mul_air::MulData;
+use stark_he::plain_mul_air::PlainMulData;
+use stark_he::scalar_mul_air::ScalarMulData;
+use stark_he::sub_air::SubData;
+use stark_he::verifying::{load_data, parse_data, verify_data, VerificationFailure};
+use std::path::Path;
+use winter_air::{FieldExtension, HashFunction, ProofOptions};
+
+// prime with q = 1 mod 2N, whose products fit the reductions of the 63-bit field
+const MODULUS: u64 = 114689;
+// the largest moduli of the limbs of wide-mul
+const WIDE_MODULUS: u64 = (1 << 62) - 57;
+// the largest prime whose tensor products fit the reductions of the 63-bit field: the middle
+// term is below 2^63, a quotient of 32 bits times a modulus of 31 bits
+const PRODUCT_MODULUS: u64 = (1 << 31) - 1;
+// a prime of one bit more
+const WIDER_MODULUS: u64 = (1 << 32) - 5;
+
+fn ciphertext<const N: usize>(seed: u64, m: u64) -> [[Vec<u64>; COEFF_LEVEL]; N] {
+    std::array::from_fn(|v| {
+        std::array::from_fn(|l| residues(seed + (v * COEFF_LEVEL + l) as u64, m))
+    })
+}
+
+// no grinding, so that proofs take milliseconds
+fn options(extension: FieldExtension) -> ProofOptions {
+    ProofOptions::new(28, 8, 0, HashFunction::Blake3_256, extension, 4, 256)
+}
+
+// Residues of `ciphertext` whose first coefficients are m - 1, so that the quotients of their
+// products take all their bits.
+fn extremal<const N: usize>(
+    mut ciphertext: [[Vec<u64>; COEFF_LEVEL]; N],
+    m: u64,
+) -> [[Vec<u64>; COEFF_LEVEL]; N] {
+    for coeffs in ciphertext.iter_mut().flatten() {
+        coeffs[0] = m - 1;
+    }
+    ciphertext
+}
+
+// Proves `data` in a directory of its own and returns the proof file.
+fn prove_data<T: Serialize>(op: Op, data: T, extension: FieldExtension) -> ProofFile {
+    try_prove(op, data, extension).unwrap()
+}
+
+fn try_prove<T: Serialize>(
+    op: Op,
+    data: T,
+    extension: FieldExtension,
+) -> Result<ProofFile, StarkHeError> {
+    let proof_file = ProofFile::new("data");
+    let data_file_path = write_toml(proof_file.dir(), "data.toml", &data);
+    prove(
+        &input_args(op, &data_file_path),
+        options(extension),
+        &proof_file,
+    )?;
+    Ok(proof_file)
+}
+
+fn round_trip<T: Serialize>(op: Op, data: T) -> ProofFile {
+    let proof_file = prove_data(op, data, MIN_FIELD_EXTENSION);
+    let data = load_data(&proof_file).unwrap();
+    assert_eq!(verify_data(data, None), Ok(()), "{} round trip", op.name());
+    proof_file
+}
+
+#[test]
+fn add() {
+    round_trip(
+        Op::Add,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: (0..3)
+                .map(|d| ciphertext(10 * d, MODULUS).map(Vec::from))
+                .collect(),
+        },
+    );
+}
+
+#[test]
+fn sub() {
+    round_trip(
+        Op::Sub,
+        SubData {
+            modulus: vec![MODULUS],
+            values: std::array::from_fn(|d| ciphertext(10 * d as u64, MODULUS)),
+        },
+    );
+}
+
+#[test]
+fn mul() {
+    round_trip(
+        Op::Mul,
+        MulData {
+            modulus: vec![MODULUS],
+            values: std::array::from_fn(|d| ciphertext(10 * d as u64, MODULUS)),
+        },
+    );
+}
+
+// 62-bit residues, whose products only the limbs hold; the first coefficients are m - 1, so
+// that the quotient of their middle term takes all its bits
+#[test]
+fn wide_mul() {
+    let values: [_; 2] = std::array::from_fn(|d| {
+        ciphertext(10 * d as u64, WIDE_MODULUS).map(|levels| {
+            levels.map(|mut coeffs| {
+                coeffs[0] = WIDE_MODULUS - 1;
+                coeffs
+            })
+        })
+    });
+    let proof_file_path = round_trip(
+        Op::WideMul,
+        MulData {
+            modulus: vec![WIDE_MODULUS],
+            values: values.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    let m = WIDE_MODULUS as u128;
+    let product = |x: u64, y: u64| x as u128 * y as u128 % m;
+    for k in 0..COEFF_DEGREE {
+        let (a, b) = (
+            [values[0][0][0][k], values[0][1][0][k]],
+            [values[1][0][0][k], values[1][1][0][k]],
+        );
+        let expected = [
+            product(a[0], b[0]),
+            (product(a[0], b[1]) + product(a[1], b[0])) % m,
+            product(a[1], b[1]),
+        ];
+        for (r, result) in data.result.iter().enumerate() {
+            assert_eq!(result[0][k] as u128, expected[r]);
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[1][0][3] ^= 1 << 40;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+}
+
+// Checks the results of the proof at `proof_file_path` against `expected` of the level and
+// coefficient index, result component by result component.
+fn check_results(proof_file_path: &Path, expected: impl Fn(usize, usize) -> Vec<u128>) {
+    let data = load_data(proof_file_path).unwrap();
+    for l in 0..COEFF_LEVEL {
+        for k in 0..COEFF_DEGREE {
+            let expected = expected(l, k);
+            for (r, result) in data.result.iter().enumerate() {
+                assert_eq!(result[l][k] as u128, expected[r], "result {} at {}", r, k);
+            }
+        }
+    }
+}
+
+// the multiplications reducing with reduction.rs stay in the field up to their largest
+// moduli, also for residues of m - 1, and refuse moduli of one bit more
+#[test]
+fn maximal_moduli() {
+    let m = PRODUCT_MODULUS;
+    let product = |x: u64, y: u64| x as u128 * y as u128 % m as u128;
+    let operands: [_; 2] = std::array::from_fn(|d| extremal(ciphertext(10 * d as u64, m), m));
+    let tensor = |d: &[[[Vec<u64>; COEFF_LEVEL]; VALUE_NUM]; 2], l: usize, k: usize| {
+        let (a, b) = (
+            [d[0][0][l][k], d[0][1][l][k]],
+            [d[1][0][l][k], d[1][1][l][k]],
+        );
+        vec![
+            product(a[0], b[0]),
+            (product(a[0], b[1]) + product(a[1], b[0])) % m as u128,
+            product(a[1], b[1]),
+        ]
+    };
+
+    let proof_file_path = round_trip(
+        Op::Mul,
+        MulData {
+            modulus: vec![m],
+            values: operands.clone(),
+        },
+    );
+    check_results(&proof_file_path, |l, k| tensor(&operands, l, k));
+
+    let accumulator = extremal(ciphertext(30, m), m);
+    let proof_file_path = round_trip(
+        Op::Fma,
+        FmaData {
+            modulus: vec![m],
+            values: operands.clone(),
+            accumulator: accumulator.clone(),
+        },
+    );
+    check_results(&proof_file_path, |l, k| {
+        let terms = tensor(&operands, l, k);
+        (0..3)
+            .map(|r| (terms[r] + accumulator[r][l][k] as u128) % m as u128)
+            .collect()
+    });
+
+    let [plaintext] = extremal(ciphertext(40, m), m);
+    let proof_file_path = round_trip(
+        Op::PlainMul,
+        PlainMulData {
+            modulus: vec![m],
+            values: operands[0].clone(),
+            plaintext: plaintext.clone(),
+        },
+    );
+    check_results(&proof_file_path, |l, k| {
+        (0..VALUE_NUM)
+            .map(|v| product(operands[0][v][l][k], plaintext[l][k]))
+            .collect()
+    });
+
+    // a scalar of 32 bits, the widest the quotients of 31-bit residues leave
+    let scalar = 0xfedc_ba98;
+    let proof_file_path = round_trip(
+        Op::ScalarMul,
+        ScalarMulData {
+            modulus: vec![m],
+            scalar,
+            values: operands[0].clone(),
+        },
+    );
+    check_results(&proof_file_path, |l, k| {
+        (0..VALUE_NUM)
+            .map(|v| product(operands[0][v][l][k], scalar))
+            .collect()
+    });
+
+    let m = WIDER_MODULUS;
+    let operands: [_; 2] = std::array::from_fn(|d| extremal(ciphertext(10 * d as u64, m), m));
+    let refused = |op: Op, result: Result<ProofFile, StarkHeError>| {
+        assert!(
+            matches!(result, Err(StarkHeError::InvalidInput(_))),
+            "{} with moduli of one bit more: {:?}",
+            op.name(),
+            result
+        );
+    };
+    let extension = MIN_FIELD_EXTENSION;
+    refused(
+        Op::Mul,
+        try_prove(
+            Op::Mul,
+            MulData {
+                modulus: vec![m],
+                values: operands.clone(),
+            },
+            extension,
+        ),
+    );
+    refused(
+        Op::Fma,
+        try_prove(
+            Op::Fma,
+            FmaData {
+                modulus: vec![m],
+                values: operands.clone(),
+                accumulator: extremal(ciphertext(30, m), m),
+            },
+            extension,
+        ),
+    );
+    refused(
+        Op::PlainMul,
+        try_prove(
+            Op::PlainMul,
+            PlainMulData {
+                modulus: vec![m],
+                values: operands[0].clone(),
+                plaintext: ciphertext::<1>(40, m)[0].clone(),
+            },
+            extension,
+        ),
+    );
+    refused(
+        Op::ScalarMul,
+        try_prove(
+            Op::ScalarMul,
+            ScalarMulData {
+                modulus: vec![m],
+                scalar,
+                values: operands[0].clone(),
+            },
+            extension,
+        ),
+    );
+    // and wide-mul moduli of 63 bits, here the largest 63-bit prime
+    let m = (1 << 63) - 25;
+    refused(
+        Op::WideMul,
+        try_prove(
+            Op::WideMul,
+            MulData {
+                modulus: vec![m],
+                values: std::array::from_fn(|d| ciphertext(10 * d as u64, m)),
+            },
+            extension,
+        ),
+    );
+}
+
+// the circuits holding products of wide residues in single columns are refused by the prover
+// and the verifier, also for a proof relabeled as one of them
+#[test]
+fn wide_circuits() {
+    assert!(Op::Relin.check_field().is_err());
+    assert!(Op::Add.check_field().is_ok());
+    let data = SubData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(4 + d as u64, MODULUS)),
+    };
+    assert!(matches!(
+        try_prove(Op::Rotate, &data, MIN_FIELD_EXTENSION),
+        Err(StarkHeError::InvalidInput(_))
+    ));
+    let proof_file_path = round_trip(Op::Sub, data);
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.circuit = String::from(Op::Rotate.name());
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+// proofs over the base field alone are too weak and rejected before the STARK
+#[test]
+fn base_field() {
+    let proof_file_path = prove_data(
+        Op::Sub,
+        SubData {
+            modulus: vec![MODULUS],
+            values: std::array::from_fn(|d| ciphertext(4 + d as u64, MODULUS)),
+        },
+        FieldExtension::None,
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
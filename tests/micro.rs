@@ -0,0 +1,1927 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// End-to-end round trips of the circuits that fit a single level, through the same data files,
+// proof files and checks as the binaries.
+#![cfg(all(feature = "micro", not(feature = "goldilocks")))]
+
+mod common;
+
+use common::{input_args, prove, residues, write_toml, ProofFile};
+use serde::Serialize;
+use stark_he::aggregate_air::AggregateData;
+use stark_he::air::{
+    element_from_u64, element_to_u64, to_data, CiphertextData, CustomData, Data, InputArg, Op,
+    Packing, COEFF_DEGREE, COEFF_LEVEL, PROOF_FORMAT_VERSION, VALUE_NUM,
+};
+use stark_he::base_ext_air::BaseExtData;
+use stark_he::ckks_encode_air::CkksEncodeData;
+use stark_he::compare_air::{
+    build_compare_trace, get_compare_pub_inputs, CompareData, COMPARE_MAX_PLAIN_MODULUS,
+};
+use stark_he::conjugate_air::{conjugate_coefficients, CONJUGATION_ELEMENT};
+use stark_he::constraint_export::{ConstraintExporter, ExportFormat};
+use stark_he::decrypt_air::DecryptData;
+use stark_he::decrypt_share_air::{DecryptShareData, SMUDGING_OFFSET};
+use stark_he::encrypt_air::EncryptData;
+use stark_he::error::StarkHeError;
+use stark_he::explain::RowExplainer;
+use stark_he::ffi::{
+    stark_he_buffer_free, stark_he_prove, stark_he_verify, StarkHeBuffer,
+    STARK_HE_CONSTRAINT_FAILURE, STARK_HE_OK, STARK_HE_PROFILE_BALANCED,
+};
+use stark_he::fma_air::FmaData;
+use stark_he::he_security::HeSecurity;
+use stark_he::hoisted_rotate_air::{
+    build_hoisted_rotate_trace, get_hoisted_rotate_pub_inputs, hoisted_rotation,
+    HOIST_KEY_DIGIT_NUM, HOIST_NUM,
+};
+use stark_he::horner_air::HornerData;
+use stark_he::inner_product_air::{result_key, InnerProductData};
+use stark_he::key_rotate_air::{
+    automorphism_rows, build_automorphism_key_trace, get_automorphism_key_pub_inputs,
+};
+use stark_he::keyswitch_air::EvaluationKey;
+use stark_he::mat_vec_air::MatVecData;
+use stark_he::modraise_air::modraise_trace;
+use stark_he::montgomery::{from_montgomery, to_montgomery};
+use stark_he::mul_air::{mul_reductions, tensor_product, MulData};
+use stark_he::multi_key_add_air::{MultiKeyAddData, MultiKeyCiphertext, MULTI_KEY_PARTY_NUM};
+use stark_he::ntt_air::forward_transform;
+use stark_he::operand_files::expand_operand_files;
+use stark_he::pack_air::{pack_exponent, pack_factors, PackData, PACK_NUM};
+use stark_he::params::{Profile, SecurityLevel};
+use stark_he::pipeline_air::{PipelineData, PipelineStep};
+use stark_he::plain_mul_air::PlainMulData;
+use stark_he::preflight::VerifyPolicy;
+use stark_he::proving::{prove_to_storage, prove_trace, visit_circuit, ModRaiseProver};
+use stark_he::re_encrypt_air::{self, RE_ENCRYPT_DIGIT_BITS, RE_ENCRYPT_DIGIT_NUM};
+use stark_he::rotate_air::galois_element;
+use stark_he::scalar_mul_air::ScalarMulData;
+use stark_he::scheme::Scheme;
+use stark_he::session::{SessionParams, Verifier};
+use stark_he::status::{load_all_from, StatusReporter};
+use stark_he::storage::{MemoryStorage, Storage};
+use stark_he::sub_air::SubData;
+use stark_he::sum_air::SumData;
+use stark_he::verifying::{
+    attach_result, decode_data, load_data, parse_data, verify_data, verify_proof,
+    VerificationFailure,
+};
+use stark_he_verifier::fresh::FreshAir;
+use stark_he_verifier::json::verify_json;
+use stark_he_verifier::{verify_add, AddPublicInputs, VerifyError};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use winter_air::proof::StarkProof;
+use winter_air::{FieldExtension, HashFunction, ProofOptions};
+
+// In debug builds winterfell checks that every constraint reaches its declared degree, so the
+// data of the round trips sets every bit of the Barrett blocks they declare: random residues,
+// with the coefficients which carry the largest values where a circuit needs them, e.g. the
+// middle term 2 (m - 1)^2 of a multiplication.
+//
+// prime with q = 1 mod 2N
+const MODULUS: u64 = 114689;
+// prime of the 20 bits of the two gadget digits of ReEncryptAir in micro mode
+const KEY_MODULUS: u64 = 1048573;
+
+fn poly(seed: u64) -> Vec<u64> {
+    residues(seed, MODULUS)
+}
+
+fn ciphertext(seed: u64) -> [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] {
+    std::array::from_fn(|v| std::array::from_fn(|l| poly(seed + (v * COEFF_LEVEL + l) as u64)))
+}
+
+// A neg input whose first coefficients are zero, the only ones NegAir reduces with a quotient
+// of one.
+fn neg_data(seed: u64) -> CiphertextData {
+    let mut values = ciphertext(seed);
+    for level in values.iter_mut().flatten() {
+        level[0] = 0;
+    }
+    CiphertextData {
+        modulus: vec![MODULUS],
+        values,
+    }
+}
+
+// A ciphertext modulo KEY_MODULUS whose c1 is m - 1 on its first coefficients, and the key
+// digits of the circuits of the gadget decomposition, skewed towards the modulus: their key
+// switches of those coefficients take every bit of their quotients.
+fn key_ciphertext(seed: u64) -> [[Vec<u64>; COEFF_LEVEL]; VALUE_NUM] {
+    let mut values = std::array::from_fn(|v| {
+        std::array::from_fn(|l| residues(seed + (v * COEFF_LEVEL + l) as u64, KEY_MODULUS))
+    });
+    values[1][0][..2].fill(KEY_MODULUS - 1);
+    values
+}
+
+fn key_digit(seed: u64) -> Vec<u64> {
+    residues(seed, KEY_MODULUS)
+        .iter()
+        .map(|&c| KEY_MODULUS - 1 - c % (KEY_MODULUS / 8))
+        .collect()
+}
+
+// no grinding, so that proofs take milliseconds
+fn options() -> ProofOptions {
+    ProofOptions::new(
+        28,
+        8,
+        0,
+        HashFunction::Blake3_256,
+        FieldExtension::None,
+        4,
+        256,
+    )
+}
+
+// The round trips return the proof file, whose directory lives as long as it.
+fn round_trip<T: Serialize>(op: Op, rotation: Option<i32>, data: T) -> ProofFile {
+    keyed_round_trip(op, rotation, data, None)
+}
+
+fn keyed_round_trip<T: Serialize>(
+    op: Op,
+    rotation: Option<i32>,
+    data: T,
+    key: Option<EvaluationKey>,
+) -> ProofFile {
+    options_round_trip(op, rotation, data, key, options())
+}
+
+fn options_round_trip<T: Serialize>(
+    op: Op,
+    rotation: Option<i32>,
+    data: T,
+    key: Option<EvaluationKey>,
+    options: ProofOptions,
+) -> ProofFile {
+    let proof_file = ProofFile::new(op.name());
+    let name = |extension| format!("{}.{}", op.name(), extension);
+    let data_file_path = write_toml(proof_file.dir(), &name("toml"), &data);
+    let evaluation_key = key.map(|key| {
+        let key_path = write_toml(proof_file.dir(), &name("key"), &key);
+        key_path.to_string_lossy().into_owned()
+    });
+
+    let input_args = InputArg {
+        evaluation_key: evaluation_key.clone(),
+        rotation,
+        ..input_args(op, &data_file_path)
+    };
+    prove(&input_args, options, &proof_file).unwrap();
+    let data = load_data(&proof_file).unwrap();
+    assert_eq!(
+        verify_data(data, evaluation_key.as_deref()),
+        Ok(()),
+        "{} round trip",
+        op.name()
+    );
+    proof_file
+}
+
+#[test]
+fn memory_storage() {
+    let dir = TempDir::new().unwrap();
+    let data = SubData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(4 + d as u64)),
+    };
+    let data_file_path = write_toml(dir.path(), "storage.toml", &data);
+    let input_args = input_args(Op::Sub, &data_file_path);
+    let storage = Arc::new(MemoryStorage::new());
+    let mut status = StatusReporter::with_storage(storage.clone(), "jobs/sub");
+    prove_to_storage(
+        &input_args,
+        options(),
+        None,
+        None,
+        None,
+        storage.as_ref(),
+        "proofs/sub.proof",
+        &mut status,
+    )
+    .unwrap();
+
+    assert_eq!(
+        storage.list("").unwrap(),
+        vec!["jobs/sub.status.toml", "proofs/sub.proof"]
+    );
+    let bytes = storage.get("proofs/sub.proof").unwrap().unwrap();
+    let data = decode_data(bytes, None, "proofs/sub.proof").unwrap();
+    assert_eq!(verify_data(data, None), Ok(()));
+    let statuses = load_all_from(storage.as_ref());
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].job, "jobs/sub");
+    assert!(statuses[0].proof_size_bytes.is_some());
+}
+
+#[test]
+fn windowed_neg() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("window.proof");
+    let data = neg_data(3);
+    let data_file_path = write_toml(dir.path(), "window.toml", &data);
+    let input_args = InputArg {
+        expose: Some(vec![1, 5, 9]),
+        ..input_args(Op::Neg, &data_file_path)
+    };
+    prove(&input_args, options(), &proof_file_path).unwrap();
+
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.window, Some(vec![1, 5, 9]));
+    assert!(data.result.iter().flatten().all(|level| level.len() == 3));
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[1][0][2] = (data.result[1][0][2] + 1) % MODULUS;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+}
+
+#[test]
+fn session() {
+    let dir = TempDir::new().unwrap();
+    let prove_bytes = |name: &str, op: Op, data: String, options: ProofOptions| {
+        let (data_file_path, proof_file_path) = (
+            dir.path().join(format!("session-{}.toml", name)),
+            dir.path().join(format!("session-{}.proof", name)),
+        );
+        fs::write(&data_file_path, data).unwrap();
+        let input_args = input_args(op, &data_file_path);
+        prove(&input_args, options, &proof_file_path).unwrap();
+        fs::read(&proof_file_path).unwrap()
+    };
+    let neg = |seed| toml::to_string(&neg_data(seed)).unwrap();
+    let params = || SessionParams {
+        op: Op::Neg,
+        options: options(),
+        evaluation_key: None,
+        decryption_key: None,
+    };
+    let session = Verifier::session(params(), VerifyPolicy::default()).unwrap();
+
+    for seed in [4, 5] {
+        let bytes = prove_bytes(&seed.to_string(), Op::Neg, neg(seed), options());
+        assert_eq!(session.verify(bytes, "neg"), Ok(()));
+    }
+    let sub = SubData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(10 * d as u64)),
+    };
+    let bytes = prove_bytes("sub", Op::Sub, toml::to_string(&sub).unwrap(), options());
+    assert!(matches!(
+        session.verify(bytes, "sub"),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+    let other_options = ProofOptions::new(
+        30,
+        8,
+        0,
+        HashFunction::Blake3_256,
+        FieldExtension::None,
+        4,
+        256,
+    );
+    let bytes = prove_bytes("options", Op::Neg, neg(6), other_options);
+    assert!(matches!(
+        session.verify(bytes, "options"),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+
+    let policy = VerifyPolicy {
+        circuits: Some(vec![String::from("sub")]),
+        ..VerifyPolicy::default()
+    };
+    assert!(Verifier::session(params(), policy).is_err());
+}
+
+#[test]
+fn explain_add() {
+    let dir = TempDir::new().unwrap();
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: (0..3).map(|d| ciphertext(10 * d).map(Vec::from)).collect(),
+    };
+    let data_file_path = write_toml(dir.path(), "explain.toml", &data);
+    let input_args = input_args(Op::Add, &data_file_path);
+    let text = visit_circuit(&input_args, options(), RowExplainer { row: 3 }).unwrap();
+    assert!(text.contains("0 of 78 main transition constraints violated"));
+    assert!(text.contains("result[1][0] coefficient 1 = a + b"));
+    assert!(visit_circuit(&input_args, options(), RowExplainer { row: 32 }).is_err());
+}
+
+#[test]
+fn export_mul_constraints() {
+    let dir = TempDir::new().unwrap();
+    let data = MulData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(10 * d as u64)),
+    };
+    let data_file_path = write_toml(dir.path(), "export.toml", &data);
+    let input_args = input_args(Op::Mul, &data_file_path);
+    let export =
+        |format| visit_circuit(&input_args, options(), ConstraintExporter { format }).unwrap();
+    // modulus, results, the components of both operands, then the Barrett block; periodic
+    // columns select the term of a row, flag the last row of a group and hold the modulus
+    let csv = export(ExportFormat::Csv);
+    for line in [
+        "0,2,-1,current[1]",
+        "0,2,1,current[1]*periodic[3]",
+        "0,2,-1,next[1]*periodic[3]",
+        "7,3,1,current[4]*current[6]*periodic[0]",
+        "7,3,1,current[5]*current[6]*periodic[1]",
+        "7,3,-1,current[8]*periodic[4]",
+        "7,3,-1,current[3]*periodic[2]",
+        "8,2,1,current[2]*periodic[1]",
+        "10,2,1,current[8]^2",
+    ] {
+        assert!(csv.lines().any(|l| l == line), "missing {}", line);
+    }
+    // copies of the results and operands, the reduction of the selected term, the remainder
+    // and slack equations, then the bits
+    let block = mul_reductions(&data.modulus).unwrap().block();
+    assert_eq!(
+        csv.lines().count(),
+        1 + 7 * 4
+            + (4 + block.quotient_bits + 3)
+            + (3 + block.modulus_bits)
+            + (5 + block.modulus_bits)
+            + 2 * block.width()
+    );
+    let json = export(ExportFormat::Json);
+    assert!(json.starts_with("{\"circuit\":\"stark-he/mul\""));
+    assert!(json.contains("\"assertions\":[{\"column\":1,"));
+}
+
+#[test]
+fn add() {
+    let values: Vec<_> = (0..3).map(|d| ciphertext(10 * d).map(Vec::from)).collect();
+    // a ring of twice the build's degree, as of another parameter set; the halves differ,
+    // so that the trace columns are not periodic
+    let wide = (0..3)
+        .map(|d| {
+            let (low, high) = (ciphertext(10 * d), ciphertext(10 * d + 5));
+            std::array::from_fn(|v| {
+                (0..COEFF_LEVEL)
+                    .map(|l| [&low[v][l][..], &high[v][l]].concat())
+                    .collect()
+            })
+        })
+        .collect();
+    // a degree of one and a half times the build's, padded to twice it
+    let padded: Vec<_> = (0..3)
+        .map(|d| {
+            let (low, high) = (ciphertext(10 * d), ciphertext(10 * d + 5));
+            std::array::from_fn(|v| {
+                (0..COEFF_LEVEL)
+                    .map(|l| [&low[v][l][..], &high[v][l][..COEFF_DEGREE / 2]].concat())
+                    .collect()
+            })
+        })
+        .collect();
+    // three levels, whose six rows per coefficient are padded to eight
+    let deep = (0..3)
+        .map(|d| {
+            std::array::from_fn(|v| (0..3).map(|l| poly((10 * d + 3 * v + l) as u64)).collect())
+        })
+        .collect();
+    // a chain of three additions and a subtraction, whose reduction has a third quotient
+    // bit, which its first coefficients set, and a lone subtraction, whose reduction has one
+    let long = (0..5)
+        .map(|d| {
+            ciphertext(10 * d).map(|mut levels| {
+                let c = if d < 4 { MODULUS - 1 } else { 0 };
+                levels[0][..3].fill(c);
+                Vec::from(levels)
+            })
+        })
+        .collect();
+    let proof_file_path = round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: values.clone(),
+        },
+    );
+    // every result coefficient is bound, not only the first and the last
+    let mut data = load_data(&proof_file_path).unwrap();
+    let k = COEFF_DEGREE / 2;
+    data.result[1][0][k] = (data.result[1][0][k] + 1) % MODULUS;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: long,
+        },
+    );
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: values[1..].to_vec(),
+        },
+    );
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: Some(2 * COEFF_DEGREE),
+            levels: None,
+            values: wide,
+        },
+    );
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS, 147457, 163841],
+            coeff_degree: None,
+            levels: None,
+            values: deep,
+        },
+    );
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: Some(3 * COEFF_DEGREE / 2),
+            levels: None,
+            values: padded,
+        },
+    );
+    // operands switched down to the first level of a three level chain
+    round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS, 147457, 163841],
+            coeff_degree: None,
+            levels: Some(vec![1; 3]),
+            values,
+        },
+    );
+}
+
+// constraints evaluated in the quadratic extension, out of the domain and by FRI
+#[test]
+fn quadratic_extension() {
+    let options = ProofOptions::new(
+        28,
+        8,
+        0,
+        HashFunction::Blake3_256,
+        FieldExtension::Quadratic,
+        4,
+        256,
+    );
+    let values = (0..3).map(|d| ciphertext(10 * d).map(Vec::from)).collect();
+    options_round_trip(
+        Op::Add,
+        None,
+        CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values,
+        },
+        None,
+        options.clone(),
+    );
+    let data = MulData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(10 * d as u64)),
+    };
+    options_round_trip(Op::Mul, None, data, None, options);
+}
+
+// several coefficients per row, in lanes sized to the 17-bit modulus, of a ring large enough
+// that the bits of four lanes still vary over the rows
+#[test]
+fn packed_rows() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("packed.proof");
+    let component = |seed: u64| (0..4).flat_map(|i| poly(seed + 100 * i)).collect();
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: Some(4 * COEFF_DEGREE),
+        levels: None,
+        values: (0..3)
+            .map(|d| std::array::from_fn(|v| vec![component(10 * d + v as u64)]))
+            .collect(),
+    };
+    let data_file_path = write_toml(dir.path(), "packed.toml", &data);
+    for coeffs_per_row in [2, 4] {
+        let input_args = InputArg {
+            coeffs_per_row: Some(coeffs_per_row),
+            ..input_args(Op::Add, &data_file_path)
+        };
+        prove(&input_args, options(), &proof_file_path).unwrap();
+
+        let data = load_data(&proof_file_path).unwrap();
+        let packing = Packing {
+            coeffs_per_row,
+            modulus_bits: 17,
+        };
+        assert_eq!(data.packing, Some(packing));
+        assert_eq!(verify_data(data, None), Ok(()));
+
+        // the unpacked layout has no operand count of this width
+        let mut data = load_data(&proof_file_path).unwrap();
+        data.packing = None;
+        assert!(matches!(
+            parse_data(data),
+            Err(VerificationFailure::ParamsRejected(_))
+        ));
+    }
+}
+
+// operands from a file of their own each, two of them through a glob
+#[test]
+fn operand_files() {
+    let dir = TempDir::new().unwrap();
+    let mut operands = Vec::new();
+    for d in 0..3 {
+        let data = CustomData {
+            modulus: vec![MODULUS],
+            coeff_degree: None,
+            levels: None,
+            values: vec![ciphertext(10 * d).map(Vec::from)],
+        };
+        let path = write_toml(dir.path(), &format!("operand-{}.toml", d), &data);
+        operands.push(path.to_string_lossy().into_owned());
+    }
+    let glob = dir.path().join("operand-9*");
+    assert!(expand_operand_files(&[glob.to_string_lossy().into_owned()]).is_err());
+    operands.truncate(1);
+    operands.push(
+        dir.path()
+            .join("operand-?.toml")
+            .to_string_lossy()
+            .into_owned(),
+    );
+    let input_args = InputArg {
+        data_file_path: String::new(),
+        operands,
+        ..InputArg::default()
+    };
+    let proof_file_path = dir.path().join("operands.proof");
+    prove(&input_args, options(), &proof_file_path).unwrap();
+    let split = load_data(&proof_file_path).unwrap();
+
+    // the same operands in a single data file
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: [0, 0, 10, 20]
+            .map(|seed| ciphertext(seed).map(Vec::from))
+            .into(),
+    };
+    let data_file_path = write_toml(dir.path(), "operands.toml", &data);
+    let input_args = InputArg {
+        data_file_path: data_file_path.to_string_lossy().into_owned(),
+        operands: Vec::new(),
+        ..input_args
+    };
+    prove(&input_args, options(), &proof_file_path).unwrap();
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(split.result, data.result);
+    assert_eq!(verify_data(split, None), Ok(()));
+}
+
+#[test]
+fn library_entry_points() {
+    let (a, b, c) = (ciphertext(0), ciphertext(10), ciphertext(20));
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: [&a, &b, &c].map(|ct| ct.clone().map(Vec::from)).into(),
+    };
+    let proof = stark_he::prove(&data, options()).unwrap();
+    let result = &proof.public_inputs.result;
+    assert_eq!(
+        element_to_u64(result[1][0][3]),
+        (a[1][0][3] + b[1][0][3] + MODULUS - c[1][0][3]) % MODULUS
+    );
+    // the no_std verifier crate checks the same proof
+    let add_inputs = AddPublicInputs::from(&proof.public_inputs);
+    assert!(verify_add(&proof.proof.to_bytes(), add_inputs.clone()).is_ok());
+    let mut tampered = add_inputs.clone();
+    tampered.result[0][0][0] =
+        element_from_u64((element_to_u64(tampered.result[0][0][0]) + 1) % MODULUS);
+    assert!(matches!(
+        verify_add(&proof.proof.to_bytes(), tampered),
+        Err(VerifyError::Verifier(_))
+    ));
+    // public inputs of another configuration than the trace fail the STARK without the checks
+    // of the verifiers too, instead of panicking
+    let mut two_levels = add_inputs.clone();
+    two_levels.modulus.push(MODULUS);
+    let mut empty = add_inputs;
+    empty.result.clear();
+    for inputs in [two_levels, empty] {
+        let proof = StarkProof::from_bytes(&proof.proof.to_bytes()).unwrap();
+        assert!(winter_verifier::verify::<FreshAir<AddPublicInputs>>(proof, inputs).is_err());
+    }
+    assert!(stark_he::verify(proof.proof, proof.public_inputs).is_ok());
+
+    // the container of a proof is that of a proof file, tampered results fail
+    let mut data_file = stark_he::prove(&data, options()).unwrap().to_data();
+    assert_eq!(data_file.circuit, Op::Add.name());
+    // as are its text fields, which the WASM bindings take
+    let result_json = format!(
+        "{{\"result\": {:?}, \"modulus\": {:?}}}",
+        data_file.result,
+        data_file.modulus.as_ref().unwrap()
+    );
+    assert!(verify_json(&data_file.proof, &result_json).is_ok());
+    // the moduli are public inputs, another modulus fails
+    let other_json = format!(
+        "{{\"result\": {:?}, \"modulus\": [{}]}}",
+        data_file.result,
+        MODULUS + 2
+    );
+    assert!(verify_json(&data_file.proof, &other_json).is_err());
+    data_file.result[0][0][0] = (data_file.result[0][0][0] + 1) % MODULUS;
+    assert!(matches!(
+        verify_data(data_file, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+
+    // streamed rows are checked as data files are, an unreduced coefficient fails
+    let header = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: Vec::new(),
+    };
+    let rows = (0..COEFF_DEGREE).map(|k| {
+        Ok(data
+            .values
+            .iter()
+            .map(|operand| {
+                std::array::from_fn(|v| {
+                    let unreduced = (k == 5 && v == 1) as u64 * MODULUS;
+                    vec![operand[v][0][k] + unreduced]
+                })
+            })
+            .collect())
+    });
+    assert!(matches!(
+        stark_he::prove_rows(&header, 3, rows, options()),
+        Err(StarkHeError::InvalidInput(_))
+    ));
+
+    let data = CustomData {
+        values: data.values[..1].to_vec(),
+        ..data
+    };
+    assert!(stark_he::prove(&data, options()).is_err());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn prove_async_reports_status() {
+    use stark_he::async_prover::prove_async;
+    use stark_he::status::Phase;
+
+    let (a, b) = (ciphertext(0), ciphertext(10));
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: [&a, &b].map(|ct| ct.clone().map(Vec::from)).into(),
+    };
+    let job = prove_async(data, options());
+    let status = job.status();
+    let proof = job.await.unwrap();
+    let reported = status.get();
+    assert_eq!(reported.phase, Phase::Proved);
+    assert_eq!(reported.job, Op::Add.name());
+    assert_eq!(
+        reported.security_bits,
+        Some(proof.proof.security_level(true))
+    );
+    assert!(reported.trace_width.is_some() && reported.proving_ms.is_some());
+    assert!(stark_he::verify(proof.proof, proof.public_inputs).is_ok());
+
+    // a data file of a single operand fails before proving
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        values: vec![a.map(Vec::from)],
+        ..Default::default()
+    };
+    let job = prove_async(data, options());
+    let status = job.status();
+    assert!(job.await.is_err());
+    assert_eq!(status.phase(), Phase::Failed);
+    assert!(status.get().message.is_some());
+}
+
+#[test]
+fn c_api() {
+    let (a, b) = (ciphertext(0), ciphertext(10));
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: [&a, &b].map(|ct| ct.clone().map(Vec::from)).into(),
+    };
+    let text = toml::to_string(&data).unwrap();
+    let mut proof = StarkHeBuffer {
+        data: std::ptr::null_mut(),
+        len: 0,
+    };
+    let code = unsafe {
+        stark_he_prove(
+            text.as_ptr(),
+            text.len(),
+            STARK_HE_PROFILE_BALANCED,
+            &mut proof,
+            std::ptr::null_mut(),
+        )
+    };
+    assert_eq!(code, STARK_HE_OK);
+    let mut bytes = unsafe { std::slice::from_raw_parts(proof.data, proof.len) }.to_vec();
+    unsafe { stark_he_buffer_free(proof) };
+    let verify = |bytes: &[u8]| unsafe {
+        stark_he_verify(bytes.as_ptr(), bytes.len(), std::ptr::null_mut())
+    };
+    assert_eq!(verify(&bytes), STARK_HE_OK);
+
+    // the proof file is that of `prover`, so a tampered result fails as in `verifier`
+    let mut data_file: Data = toml::from_str(std::str::from_utf8(&bytes).unwrap()).unwrap();
+    data_file.result[0][0][0] = (data_file.result[0][0][0] + 1) % MODULUS;
+    bytes = toml::to_string(&data_file).unwrap().into_bytes();
+    assert_eq!(verify(&bytes), STARK_HE_CONSTRAINT_FAILURE);
+}
+
+// degrees below 1024 are below every level of the HE standard
+#[test]
+fn he_security_is_enforced() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("insecure.proof");
+    let data = CustomData {
+        modulus: vec![MODULUS],
+        coeff_degree: None,
+        levels: None,
+        values: (0..3).map(|d| ciphertext(10 * d).map(Vec::from)).collect(),
+    };
+    let data_file_path = write_toml(dir.path(), "insecure.toml", &data);
+    let input_args = InputArg {
+        he_security: Some(HeSecurity::Bits128),
+        ..input_args(Op::Add, &data_file_path)
+    };
+    let result = prove(&input_args, options(), &proof_file_path);
+    assert!(matches!(
+        result,
+        Err(StarkHeError::InvalidInput(message))
+            if message.contains("has no 128-bit parameters in the HE standard")
+    ));
+    assert!(!proof_file_path.exists());
+}
+
+#[test]
+fn sub() {
+    let values = std::array::from_fn(|d| ciphertext(10 * d as u64));
+    round_trip(
+        Op::Sub,
+        None,
+        SubData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+#[test]
+fn neg() {
+    round_trip(Op::Neg, None, neg_data(1));
+}
+
+// the achieved security is recorded, and a manifest recording another one is rejected
+#[test]
+fn security_level() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("security.proof");
+    let data = neg_data(2);
+    let data_file_path = write_toml(dir.path(), "security.toml", &data);
+    let input_args = input_args(Op::Neg, &data_file_path);
+    let options = SecurityLevel::Bits96
+        .proof_options(HashFunction::Blake3_256, 4, 256)
+        .unwrap();
+    prove(&input_args, options, &proof_file_path).unwrap();
+
+    let data = load_data(&proof_file_path).unwrap();
+    let bits = data
+        .reproducibility
+        .as_ref()
+        .unwrap()
+        .security_bits
+        .unwrap();
+    assert!(bits >= SecurityLevel::Bits96.bits(), "{} bits", bits);
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.reproducibility.as_mut().unwrap().security_bits = Some(128);
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+// containers record their format version; newer formats, other fields and unknown hash
+// functions are rejected before the proof is decoded
+#[test]
+fn envelope() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("envelope.proof");
+    let data = neg_data(2);
+    let data_file_path = write_toml(dir.path(), "envelope.toml", &data);
+    let input_args = input_args(Op::Neg, &data_file_path);
+    prove(&input_args, options(), &proof_file_path).unwrap();
+
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.format_version, PROOF_FORMAT_VERSION);
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    // containers written before the version was recorded are version 1
+    let text = fs::read_to_string(&proof_file_path).unwrap();
+    let legacy = text.replace(&format!("format_version = {}\n", PROOF_FORMAT_VERSION), "");
+    let data: Data = toml::from_str(&legacy).unwrap();
+    assert_eq!(data.format_version, 1);
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.format_version = PROOF_FORMAT_VERSION + 1;
+    data.proof = String::from("not a proof");
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::BadFormat(message)) if message.contains("format version")
+    ));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.reproducibility.as_mut().unwrap().field = String::from("f64");
+    data.proof = String::from("not a proof");
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(message)) if message.contains("field f64")
+    ));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.reproducibility.as_mut().unwrap().proof_options.hash_fn = String::from("Rp64_256");
+    data.proof = String::from("not a proof");
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(message)) if message.contains("Rp64_256")
+    ));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.reproducibility.as_mut().unwrap().params = Some(String::from("bfv_4096_128bit"));
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+#[test]
+fn profile() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("profile.proof");
+    let data = neg_data(3);
+    let data_file_path = write_toml(dir.path(), "profile.toml", &data);
+    let input_args = input_args(Op::Neg, &data_file_path);
+    prove(
+        &input_args,
+        Profile::Fast.proof_options(4, 256),
+        &proof_file_path,
+    )
+    .unwrap();
+
+    let data = load_data(&proof_file_path).unwrap();
+    let manifest = data.reproducibility.as_ref().unwrap();
+    assert_eq!(manifest.profile.as_deref(), Some("fast"));
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.reproducibility.as_mut().unwrap().profile = Some(String::from("paranoid"));
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+#[test]
+fn scalar_mul() {
+    round_trip(
+        Op::ScalarMul,
+        None,
+        ScalarMulData {
+            modulus: vec![MODULUS],
+            scalar: 3 * MODULUS + 5,
+            values: ciphertext(1),
+        },
+    );
+}
+
+#[test]
+fn mul() {
+    let values = std::array::from_fn(|d| ciphertext(10 * d as u64));
+    round_trip(
+        Op::Mul,
+        None,
+        MulData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+// the tensor product of mul under BGV, which a BFV relabeling or a BGV decryption rejects
+#[test]
+fn bgv_mul() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("bgv.proof");
+    let data = MulData {
+        modulus: vec![MODULUS],
+        values: std::array::from_fn(|d| ciphertext(10 * d as u64)),
+    };
+    let data_file_path = write_toml(dir.path(), "bgv.toml", &data);
+    let input_args = InputArg {
+        scheme: Scheme::Bgv,
+        ..input_args(Op::Mul, &data_file_path)
+    };
+    prove(&input_args, options(), &proof_file_path).unwrap();
+
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.scheme, Some(Scheme::Bgv));
+    assert_eq!(verify_data(data, None), Ok(()));
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.scheme = None;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.circuit = String::from(Op::Decrypt.name());
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+// the operands are the Montgomery forms of mul operands, so the results are too; the modulus
+// takes 62 bits, as the REDC multiples of a smaller one all reach R
+#[test]
+fn mont_mul() {
+    const WIDE_MODULUS: u64 = (1 << 62) - 57;
+    let plain: [_; 2] = std::array::from_fn(|d| {
+        ciphertext(10 * d as u64).map(|levels| {
+            levels.map(|coeffs| {
+                coeffs
+                    .iter()
+                    .map(|&x| x.wrapping_mul(0x9e37_79b9_7f4a_7c15) % WIDE_MODULUS)
+                    .collect::<Vec<_>>()
+            })
+        })
+    });
+    let values = plain.clone().map(|operand| {
+        operand.map(|levels| {
+            levels.map(|coeffs| {
+                coeffs
+                    .iter()
+                    .map(|&x| to_montgomery(x, WIDE_MODULUS))
+                    .collect()
+            })
+        })
+    });
+    let proof_file_path = round_trip(
+        Op::MontMul,
+        None,
+        MulData {
+            modulus: vec![WIDE_MODULUS],
+            values,
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    for k in 0..COEFF_DEGREE {
+        let a = [plain[0][0][0][k], plain[0][1][0][k]];
+        let b = [plain[1][0][0][k], plain[1][1][0][k]];
+        let (expected, _) = tensor_product(a, b, WIDE_MODULUS);
+        for (r, result) in data.result.iter().enumerate() {
+            assert_eq!(from_montgomery(result[0][k], WIDE_MODULUS), expected[r]);
+        }
+    }
+}
+
+// a 63-bit prime, above the bound of mul, and coefficients skewed towards it, so that the
+// quotients of some middle terms take all 64 bits
+#[test]
+fn wide_mul() {
+    const WIDE_MODULUS: u64 = (1 << 63) - 25;
+    let values: [_; 2] = std::array::from_fn(|d| {
+        ciphertext(10 * d as u64).map(|levels| {
+            levels.map(|coeffs| {
+                coeffs
+                    .iter()
+                    .map(|&x| {
+                        let h = (x.wrapping_mul(0x9e37_79b9_7f4a_7c15) % WIDE_MODULUS) as u128;
+                        WIDE_MODULUS - 1 - (h * h / WIDE_MODULUS as u128) as u64
+                    })
+                    .collect()
+            })
+        })
+    });
+    let proof_file_path = round_trip(
+        Op::WideMul,
+        None,
+        MulData {
+            modulus: vec![WIDE_MODULUS],
+            values: values.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    let m = WIDE_MODULUS as u128;
+    let product = |x: u64, y: u64| x as u128 * y as u128 % m;
+    for k in 0..COEFF_DEGREE {
+        let (a, b) = (
+            [values[0][0][0][k], values[0][1][0][k]],
+            [values[1][0][0][k], values[1][1][0][k]],
+        );
+        let expected = [
+            product(a[0], b[0]),
+            (product(a[0], b[1]) + product(a[1], b[0])) % m,
+            product(a[1], b[1]),
+        ];
+        for (r, result) in data.result.iter().enumerate() {
+            assert_eq!(result[0][k] as u128, expected[r]);
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[1][0][3] ^= 1 << 40;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+}
+
+// Evaluates the polynomial with `coefficients` on a ciphertext and checks the result
+// components against the expansion of p(x0 + x1 s) in powers of s.
+fn horner_round_trip(coefficients: Vec<u64>) {
+    let values = ciphertext(1);
+    let proof_file_path = round_trip(
+        Op::Horner,
+        None,
+        HornerData {
+            modulus: vec![MODULUS],
+            coefficients: coefficients.clone(),
+            values: values.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.result.len(), coefficients.len());
+    for k in 0..COEFF_DEGREE {
+        let x = [values[0][0][k], values[1][0][k]];
+        let mut p: Vec<u64> = vec![];
+        for &a in coefficients.iter().rev() {
+            let mut next = vec![0; p.len() + 1];
+            for (t, &c) in p.iter().enumerate() {
+                next[t] = (next[t] + c * x[0]) % MODULUS;
+                next[t + 1] = (next[t + 1] + c * x[1]) % MODULUS;
+            }
+            next[0] = (next[0] + a) % MODULUS;
+            p = next;
+        }
+        for (t, result) in data.result.iter().enumerate() {
+            assert_eq!(result[0][k], p[t]);
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.weights.as_mut().unwrap()[0] += 1;
+    assert!(verify_data(data, None).is_err());
+}
+
+#[test]
+fn horner() {
+    horner_round_trip(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+}
+
+#[test]
+fn horner_padded() {
+    horner_round_trip(vec![7, 0, 65536]);
+}
+
+// add -> mul -> sub in one trace, checked against the operations one after another; the
+// proof is rejected for another program
+#[test]
+fn pipeline() {
+    let mut values = ciphertext(1);
+    let mut operands = vec![ciphertext(3), ciphertext(5), ciphertext(7)];
+    // the middle term of the multiplication reaches 2 (m - 1)^2 on the first coefficient
+    for v in 0..VALUE_NUM {
+        values[v][0][0] = MODULUS - 1;
+        operands[0][v][0][0] = 0;
+        operands[1][v][0][0] = MODULUS - 1;
+    }
+    let proof_file_path = round_trip(
+        Op::Pipeline,
+        None,
+        PipelineData {
+            modulus: vec![MODULUS],
+            steps: vec![PipelineStep::Add, PipelineStep::Mul, PipelineStep::Sub],
+            scale: None,
+            values: values.clone(),
+            operands: operands.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    for k in 0..COEFF_DEGREE {
+        let [a, b, c] = [0, 1, 2].map(|d| [operands[d][0][0][k], operands[d][1][0][k]]);
+        let x = [0, 1].map(|v| (values[v][0][k] + a[v]) % MODULUS);
+        let product = [
+            x[0] * b[0] % MODULUS,
+            (x[0] * b[1] + x[1] * b[0]) % MODULUS,
+            x[1] * b[1] % MODULUS,
+        ];
+        let expected = [
+            (product[0] + MODULUS - c[0]) % MODULUS,
+            (product[1] + MODULUS - c[1]) % MODULUS,
+            product[2],
+        ];
+        for (t, result) in data.result.iter().enumerate() {
+            assert_eq!(result[0][k], expected[t]);
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.weights.as_mut().unwrap()[2] = PipelineStep::Add.code();
+    assert!(verify_data(data, None).is_err());
+}
+
+#[test]
+fn fma() {
+    let values = std::array::from_fn(|d| ciphertext(10 * d as u64));
+    let accumulator =
+        std::array::from_fn(|r| std::array::from_fn(|l| poly(200 + (r * COEFF_LEVEL + l) as u64)));
+    round_trip(
+        Op::Fma,
+        None,
+        FmaData {
+            modulus: vec![MODULUS],
+            values,
+            accumulator,
+        },
+    );
+}
+
+// five operands, so that the running sum is padded with three zero operands
+#[test]
+fn sum() {
+    round_trip(
+        Op::Sum,
+        None,
+        SumData {
+            modulus: vec![MODULUS],
+            values: (0..5).map(|i| ciphertext(10 * i)).collect(),
+        },
+    );
+}
+
+// five records in their own directory, so that the running sum is padded as in `sum`
+fn aggregate_round_trip(mean: Option<u64>) {
+    let dir = TempDir::new().unwrap();
+    let records = dir.path().join("aggregate-records");
+    fs::create_dir_all(&records).unwrap();
+    let values: Vec<_> = (0..5).map(|i| ciphertext(10 * i)).collect();
+    for (i, values) in values.iter().enumerate() {
+        let record = CiphertextData {
+            modulus: vec![MODULUS],
+            values: values.clone(),
+        };
+        write_toml(&records, &format!("{:02}.toml", i), &record);
+    }
+    let proof_file_path = round_trip(
+        Op::Aggregate,
+        None,
+        AggregateData {
+            modulus: vec![MODULUS],
+            records: records.to_string_lossy().into_owned(),
+            mean,
+        },
+    );
+
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.result.len(), VALUE_NUM * (1 + mean.iter().len()));
+    for v in 0..VALUE_NUM {
+        for k in 0..COEFF_DEGREE {
+            let sum = values.iter().map(|value| value[v][0][k]).sum::<u64>() % MODULUS;
+            assert_eq!(data.result[v][0][k], sum);
+            if let Some(mean) = mean {
+                let scaled = (sum as u128 * mean as u128 % MODULUS as u128) as u64;
+                assert_eq!(data.result[VALUE_NUM + v][0][k], scaled);
+            }
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[0][0][0] = (data.result[0][0][0] + 1) % MODULUS;
+    assert!(verify_data(data, None).is_err());
+}
+
+// the scalar is larger than the modulus, so that the scaled sums have quotients
+#[test]
+fn aggregate() {
+    aggregate_round_trip(None);
+    aggregate_round_trip(Some((1 << 40) + 3));
+}
+
+// three rows and columns, so that the trace has a padding row of the matrix as well as
+// padding slots
+#[test]
+fn mat_vec() {
+    let weights = vec![vec![3, 1 << 40, 0], vec![1, 1, 1], vec![MODULUS + 7, 5, 2]];
+    let values: Vec<_> = (0..3).map(|i| ciphertext(10 * i)).collect();
+    let proof_file_path = round_trip(
+        Op::MatVec,
+        None,
+        MatVecData {
+            modulus: vec![MODULUS],
+            weights: weights.clone(),
+            values: values.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.result.len(), 3 * VALUE_NUM);
+    for (c, result) in data.result.iter().enumerate() {
+        let (row, v) = (&weights[c / VALUE_NUM], c % VALUE_NUM);
+        for (k, &x) in result[0].iter().enumerate() {
+            let expected = (0..3).fold(0, |acc, i| {
+                (acc + row[i] as u128 * values[i][v][0][k] as u128) % MODULUS as u128
+            });
+            assert_eq!(x as u128, expected);
+        }
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[VALUE_NUM][0][3] = (data.result[VALUE_NUM][0][3] + 1) % MODULUS;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result.truncate(2 * VALUE_NUM);
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+// three operands, so that the last weight is the zero of a padding row
+#[test]
+fn inner_product() {
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("inner-product.proof");
+    let weights = vec![3, 1 << 40, MODULUS + 7];
+    let values: Vec<_> = (0..3).map(|i| ciphertext(10 * i)).collect();
+    let data = InnerProductData {
+        modulus: vec![MODULUS],
+        weights: weights.clone(),
+        values: values.clone(),
+    };
+    let data_file_path = write_toml(dir.path(), "inner-product.toml", &data);
+    let input_args = input_args(Op::InnerProduct, &data_file_path);
+    prove(&input_args, options(), &proof_file_path).unwrap();
+
+    let result_path = dir.path().join(result_key("inner-product.proof"));
+    let result: CiphertextData = confy::load_path(&result_path).unwrap();
+    for (v, levels) in result.values.iter().enumerate() {
+        for (k, &x) in levels[0].iter().enumerate() {
+            let expected = (0..3).fold(0, |acc, i| {
+                (acc + weights[i] as u128 * values[i][v][0][k] as u128) % MODULUS as u128
+            });
+            assert_eq!(x as u128, expected);
+        }
+    }
+
+    let data = load_data(&proof_file_path).unwrap();
+    assert!(data.result.is_empty());
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+    let verify_with_result = |path: &std::path::Path| {
+        let (op, mut pub_inputs, proof) = parse_data(load_data(&proof_file_path)?)?;
+        attach_result(Some(&path.to_string_lossy()), &mut pub_inputs)?;
+        verify_proof(op, proof, pub_inputs)
+    };
+    assert_eq!(verify_with_result(&result_path), Ok(()));
+
+    let mut forged = result;
+    forged.values[0][0][4] = (forged.values[0][0][4] + 1) % MODULUS;
+    let forged_path = write_toml(dir.path(), "inner-product.forged.toml", &forged);
+    assert!(matches!(
+        verify_with_result(&forged_path),
+        Err(VerificationFailure::PublicInputMismatch(_))
+    ));
+
+    // the coefficients of the proof under other moduli
+    let mut forged: CiphertextData = confy::load_path(&result_path).unwrap();
+    forged.modulus[0] += 2;
+    let forged_path = write_toml(dir.path(), "inner-product.forged.toml", &forged);
+    assert!(matches!(
+        verify_with_result(&forged_path),
+        Err(VerificationFailure::PublicInputMismatch(_))
+    ));
+}
+
+#[test]
+fn plain_mul() {
+    let data = PlainMulData {
+        modulus: vec![MODULUS],
+        values: ciphertext(1),
+        plaintext: std::array::from_fn(|l| poly(100 + l as u64)),
+    };
+    round_trip(Op::PlainMul, None, data);
+}
+
+#[test]
+fn rotate() {
+    let values = ciphertext(1);
+    let proof_file_path = round_trip(
+        Op::Rotate,
+        Some(3),
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+    // the results are proven against the recorded input, which must be there
+    let mut data = load_data(&proof_file_path).unwrap();
+    let input = data.input.as_mut().unwrap();
+    let k = COEFF_DEGREE / 2;
+    input.ciphertext[1][0][k] = (input.ciphertext[1][0][k] + 1) % MODULUS;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.input = None;
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::BadFormat(_))
+    ));
+}
+
+// the negacyclic a(X^-1) of every component, which is its own inverse
+#[test]
+fn conjugate() {
+    let values = ciphertext(1);
+    for x in values.iter().flatten() {
+        let y = conjugate_coefficients(x, MODULUS);
+        assert_eq!(y[0], x[0]);
+        assert_eq!(y[1], (MODULUS - x[COEFF_DEGREE - 1]) % MODULUS);
+        assert_eq!(&conjugate_coefficients(&y, MODULUS), x);
+    }
+    round_trip(
+        Op::Conjugate,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+// the factors of operand j are the evaluations of the monomial X^(j * N / PACK_NUM)
+#[test]
+fn pack() {
+    for j in 0..PACK_NUM {
+        let mut monomial = vec![0; COEFF_DEGREE];
+        monomial[pack_exponent(j) as usize] = 1;
+        assert_eq!(
+            pack_factors(&[MODULUS], 1)[j][0],
+            forward_transform(monomial, MODULUS)
+        );
+    }
+    // every operand, as the copies of a missing one vanish, and the first half of the
+    // coefficients at m - 1, so that the sums of some rows take every bit of their quotients
+    let operand = |j: u64| {
+        ciphertext(10 * j).map(|levels| {
+            levels.map(|mut coeffs| {
+                coeffs[..COEFF_DEGREE / 2].fill(MODULUS - 1);
+                coeffs
+            })
+        })
+    };
+    let data = PackData {
+        modulus: vec![MODULUS],
+        values: (0..PACK_NUM as u64).map(operand).collect(),
+    };
+    round_trip(Op::Pack, None, data);
+}
+
+// X^(-e_j) undoes the factor of packing
+#[test]
+fn unpack() {
+    let (packing, unpacking) = (pack_factors(&[MODULUS], 1), pack_factors(&[MODULUS], -1));
+    for j in 0..PACK_NUM {
+        for k in 0..COEFF_DEGREE {
+            assert_eq!(packing[j][0][k] * unpacking[j][0][k] % MODULUS, 1);
+        }
+    }
+    round_trip(
+        Op::Unpack,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values: ciphertext(1),
+        },
+    );
+}
+
+// a ciphertext of parties 0 and 2 plus one of parties 1, 2 and 3
+#[test]
+fn multi_key_add() {
+    let operand = |parties: Vec<usize>, seed: u64| MultiKeyCiphertext {
+        values: (0..=parties.len())
+            .map(|u| [poly(seed + u as u64)])
+            .collect(),
+        parties,
+    };
+    let partial = operand(vec![0, 2], 1);
+    let extended = partial.extend().unwrap();
+    assert_eq!(extended[3], partial.values[2]);
+    assert!(extended[2][0].iter().all(|&c| c == 0));
+    assert!(operand(vec![1, 1], 20).extend().is_err());
+    assert!(operand(vec![MULTI_KEY_PARTY_NUM], 20).extend().is_err());
+    // both operands under every party, listed in other orders: the quotients of a component
+    // only one operand has would stay zero
+    let data = MultiKeyAddData {
+        modulus: vec![MODULUS],
+        operands: [operand(vec![0, 2, 1, 3], 1), operand(vec![3, 1, 2, 0], 10)],
+    };
+    round_trip(Op::MultiKeyAdd, None, data);
+}
+
+// a re-encryption key without noise from s_A to s_B, under which the re-encrypted
+// ciphertext decrypts exactly as the original one
+#[test]
+fn re_encrypt() {
+    let m = KEY_MODULUS;
+    let (s_a, s_b) = (residues(400, m), residues(401, m));
+    let values = (0..RE_ENCRYPT_DIGIT_NUM)
+        .map(|i| {
+            let a = key_digit(410 + i as u64);
+            let w = (1u64 << (i * RE_ENCRYPT_DIGIT_BITS)) % m;
+            let b = (0..COEFF_DEGREE)
+                .map(|k| (w * s_a[k] % m + m - a[k] * s_b[k] % m) % m)
+                .collect();
+            [[b], [a]]
+        })
+        .collect::<Vec<_>>();
+    let data = CiphertextData {
+        modulus: vec![m],
+        values: key_ciphertext(1),
+    };
+    for k in 0..COEFF_DEGREE {
+        let c = [data.values[0][0][k], data.values[1][0][k]];
+        let key = std::array::from_fn(|i| [values[i][0][0][k], values[i][1][0][k]]);
+        let r = re_encrypt_air::re_encrypt(c, key).map(|sum| (sum % m as u128) as u64);
+        assert_eq!((r[0] + r[1] * s_b[k]) % m, (c[0] + c[1] * s_a[k]) % m);
+    }
+    let key = EvaluationKey {
+        modulus: vec![m],
+        values,
+    };
+    keyed_round_trip(Op::ReEncrypt, None, data, Some(key));
+}
+
+// Galois keys without noise, under which every rotated ciphertext decrypts to the rotated
+// plaintext
+#[test]
+fn hoisted_rotate() {
+    let m = KEY_MODULUS;
+    let s = residues(500, m);
+    let step = 3;
+    let values = (0..HOIST_KEY_DIGIT_NUM)
+        .map(|d| {
+            let (j, i) = (d / RE_ENCRYPT_DIGIT_NUM, d % RE_ENCRYPT_DIGIT_NUM);
+            let rows = automorphism_rows(galois_element(hoisted_rotation(step, j)));
+            let a = key_digit(510 + d as u64);
+            let w = (1u64 << (i * RE_ENCRYPT_DIGIT_BITS)) % m;
+            let b = (0..COEFF_DEGREE)
+                .map(|k| (w * s[rows[k]] % m + m - a[k] * s[k] % m) % m)
+                .collect();
+            [[b], [a]]
+        })
+        .collect();
+    let key = EvaluationKey {
+        modulus: vec![m],
+        values,
+    };
+    let data = CiphertextData {
+        modulus: vec![m],
+        values: key_ciphertext(1),
+    };
+
+    let dir = TempDir::new().unwrap();
+    let data_file_path = write_toml(dir.path(), "hoisted.toml", &data);
+    let key_path = write_toml(dir.path(), "hoisted.key", &key);
+    let trace = build_hoisted_rotate_trace(&InputArg {
+        evaluation_key: Some(key_path.to_string_lossy().into_owned()),
+        rotation: Some(step),
+        ..input_args(Op::HoistedRotate, &data_file_path)
+    })
+    .unwrap();
+    let pub_inputs = get_hoisted_rotate_pub_inputs(&trace, step);
+    assert_eq!(pub_inputs.evaluation_key.as_ref(), Some(&key));
+    let (c0, c1) = (&data.values[0][0], &data.values[1][0]);
+    for j in 0..HOIST_NUM {
+        let rows = automorphism_rows(galois_element(hoisted_rotation(step, j)));
+        let (r0, r1) = (
+            &pub_inputs.result[j * VALUE_NUM][0],
+            &pub_inputs.result[j * VALUE_NUM + 1][0],
+        );
+        for k in 0..COEFF_DEGREE {
+            let (r0, r1) = (element_to_u64(r0[k]), element_to_u64(r1[k]));
+            let u = rows[k];
+            assert_eq!((r0 + r1 * s[k]) % m, (c0[u] + c1[u] * s[u]) % m);
+        }
+    }
+    keyed_round_trip(Op::HoistedRotate, Some(step), data, Some(key));
+}
+
+// automorphism keys without noise for a rotation and the conjugation, under which the
+// key-switched ciphertexts decrypt to the permuted plaintext
+#[test]
+fn key_rotate() {
+    let m = KEY_MODULUS;
+    let s = residues(700, m);
+    let data = CiphertextData {
+        modulus: vec![m],
+        values: key_ciphertext(1),
+    };
+    let (c0, c1) = (&data.values[0][0], &data.values[1][0]);
+    let rotation = 5;
+    for (op, g) in [
+        (Op::KeyRotate, galois_element(rotation)),
+        (Op::KeyConjugate, CONJUGATION_ELEMENT),
+    ] {
+        let rows = automorphism_rows(g);
+        let values = (0..RE_ENCRYPT_DIGIT_NUM)
+            .map(|i| {
+                let a = key_digit(710 + i as u64);
+                let w = (1u64 << (i * RE_ENCRYPT_DIGIT_BITS)) % m;
+                let b = (0..COEFF_DEGREE)
+                    .map(|k| (w * s[rows[k]] % m + m - a[k] * s[k] % m) % m)
+                    .collect();
+                [[b], [a]]
+            })
+            .collect();
+        let key = EvaluationKey {
+            modulus: vec![m],
+            values,
+        };
+        let trace = build_automorphism_key_trace(&data, key.clone(), g).unwrap();
+        let pub_inputs = get_automorphism_key_pub_inputs(&trace, g);
+        assert_eq!(pub_inputs.evaluation_key.as_ref(), Some(&key));
+        let (r0, r1) = (&pub_inputs.result[0][0], &pub_inputs.result[1][0]);
+        for k in 0..COEFF_DEGREE {
+            let (r0, r1) = (element_to_u64(r0[k]), element_to_u64(r1[k]));
+            let u = rows[k];
+            assert_eq!((r0 + r1 * s[k]) % m, (c0[u] + c1[u] * s[u]) % m);
+        }
+        let rotation = (op == Op::KeyRotate).then_some(rotation);
+        keyed_round_trip(op, rotation, &data, Some(key));
+    }
+}
+
+// BGV encryptions without noise of scalar plaintexts, whose evaluations all equal the
+// plaintext, so every row compares two small values; the tensor powers of s decrypt the
+// result, and the proof is rejected as a BFV proof. The masks are picked so that some
+// right-hand sides take every bit of their quotients.
+#[test]
+fn compare() {
+    let t = COMPARE_MAX_PLAIN_MODULUS;
+    let s = poly(600);
+    let plaintexts = [
+        (0..COEFF_DEGREE).map(|k| k as u64 % 4).collect::<Vec<_>>(),
+        (0..COEFF_DEGREE).map(|k| k as u64 / 4 % 4).collect(),
+    ];
+    let data = CompareData {
+        modulus: vec![MODULUS],
+        plain_modulus: t,
+        values: std::array::from_fn(|d| {
+            let a = poly(620 + d as u64);
+            let c0 = (0..COEFF_DEGREE)
+                .map(|k| (plaintexts[d][k] + MODULUS - a[k] * s[k] % MODULUS) % MODULUS)
+                .collect();
+            [[c0], [a]]
+        }),
+    };
+
+    let dir = TempDir::new().unwrap();
+    let proof_file_path = dir.path().join("compare.proof");
+    let data_file_path = write_toml(dir.path(), "compare.toml", &data);
+    let input_args = InputArg {
+        scheme: Scheme::Bgv,
+        ..input_args(Op::Compare, &data_file_path)
+    };
+    let result = get_compare_pub_inputs(&build_compare_trace(&input_args).unwrap(), t).result;
+    for k in 0..COEFF_DEGREE {
+        let (mut decrypted, mut power) = (0, 1);
+        for component in result.iter() {
+            decrypted = (decrypted + element_to_u64(component[0][k]) * power) % MODULUS;
+            power = power * s[k] % MODULUS;
+        }
+        let centred = (decrypted as i64 + MODULUS as i64 / 2) % MODULUS as i64 - MODULUS as i64 / 2;
+        assert_eq!(
+            centred.rem_euclid(t as i64),
+            (plaintexts[0][k] < plaintexts[1][k]) as i64
+        );
+    }
+
+    prove(&input_args, options(), &proof_file_path).unwrap();
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(verify_data(data, None), Ok(()));
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.scheme = None;
+    assert!(matches!(
+        parse_data(data),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+#[test]
+fn ntt() {
+    let values = ciphertext(1);
+    round_trip(
+        Op::Ntt,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+#[test]
+fn intt() {
+    let values = ciphertext(1);
+    round_trip(
+        Op::Intt,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+#[test]
+fn ckks_encode() {
+    let values = (0..COEFF_DEGREE / 2)
+        .map(|i| [i as f64 / 4.0 - 1.0, 0.5 - i as f64 / 8.0])
+        .collect();
+    round_trip(
+        Op::CkksEncode,
+        None,
+        CkksEncodeData {
+            scale: 2f64.powi(40),
+            values,
+        },
+    );
+}
+
+#[test]
+fn mod_raise() {
+    let mut values = ciphertext(1);
+    for component in values.iter_mut() {
+        for level in component[1..].iter_mut() {
+            level.clear();
+        }
+    }
+    round_trip(
+        Op::ModRaise,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
+
+// with a single source modulus, the conversion reduces every coefficient modulo the targets
+#[test]
+fn base_ext() {
+    let target = vec![12289, 40961];
+    let values = ciphertext(1);
+    let proof_file_path = round_trip(
+        Op::BaseExt,
+        None,
+        BaseExtData {
+            modulus: vec![MODULUS],
+            target: target.clone(),
+            values: values.clone(),
+        },
+    );
+    let data = load_data(&proof_file_path).unwrap();
+    assert_eq!(data.modulus, Some(vec![MODULUS, 12289, 40961]));
+    for (r, result) in data.result.iter().enumerate() {
+        let (v, p) = (r / target.len(), target[r % target.len()]);
+        let expected: Vec<_> = values[v][0].iter().map(|&x| x % p).collect();
+        assert_eq!(result[0], expected);
+    }
+
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.result[1][0][5] = (data.result[1][0][5] + 1) % target[1];
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ConstraintFailure(_))
+    ));
+    let mut data = load_data(&proof_file_path).unwrap();
+    data.modulus.as_mut().unwrap().pop();
+    assert!(matches!(
+        verify_data(data, None),
+        Err(VerificationFailure::ParamsRejected(_))
+    ));
+}
+
+#[test]
+fn precomputed_trace() {
+    let mut values = ciphertext(2);
+    for component in values.iter_mut() {
+        for level in component[1..].iter_mut() {
+            level.clear();
+        }
+    }
+    let data = CiphertextData {
+        modulus: vec![MODULUS],
+        values,
+    };
+    let trace = modraise_trace(&data).unwrap();
+    let mut status = StatusReporter::new(None, Op::ModRaise.name());
+    let prover = ModRaiseProver { options: options() };
+    let output = prove_trace(Op::ModRaise, prover, trace, None, false, &mut status).unwrap();
+    let data = to_data(Op::ModRaise, output.proof.to_bytes(), output.public_input);
+    assert_eq!(verify_data(data, None), Ok(()));
+}
+
+const PLAIN_MODULUS: u64 = 16;
+
+// A ternary secret key in NTT form, its public key and ternary coefficients for the
+// randomness of an encryption or the noise of a ciphertext
+fn keys() -> (Vec<u64>, EvaluationKey, Vec<i64>) {
+    let ternary = |seed| {
+        poly(seed)
+            .iter()
+            .map(|&c| (c % 3) as i64 - 1)
+            .collect::<Vec<_>>()
+    };
+    let residues = |coefficients: &[i64]| -> Vec<u64> {
+        let residues = coefficients
+            .iter()
+            .map(|&c| c.rem_euclid(MODULUS as i64) as u64)
+            .collect();
+        forward_transform(residues, MODULUS)
+    };
+    // the errors of -1 of neither half sit on antipodal rows only, where the copies of their
+    // bits would lose a degree
+    let (s, e) = (residues(&ternary(200)), residues(&ternary(213)));
+    let a = poly(202);
+    let b = (0..COEFF_DEGREE)
+        .map(|k| (2 * MODULUS - (a[k] * s[k] % MODULUS) - e[k]) % MODULUS)
+        .collect();
+    let key = EvaluationKey {
+        modulus: vec![MODULUS],
+        values: vec![[[b], [a]]],
+    };
+    (s, key, ternary(203))
+}
+
+#[test]
+fn decrypt() {
+    let (s, key, noise) = keys();
+    let plaintext = poly(204);
+    let delta = MODULUS / PLAIN_MODULUS;
+    let scaled = (0..COEFF_DEGREE)
+        .map(|k| {
+            let m = (plaintext[k] % PLAIN_MODULUS * delta) as i64;
+            (m + noise[k]).rem_euclid(MODULUS as i64) as u64
+        })
+        .collect();
+    let scaled = forward_transform(scaled, MODULUS);
+    let c1 = poly(205);
+    let c0 = (0..COEFF_DEGREE)
+        .map(|k| (scaled[k] + MODULUS - c1[k] * s[k] % MODULUS) % MODULUS)
+        .collect();
+    let data = DecryptData {
+        modulus: vec![MODULUS],
+        plain_modulus: PLAIN_MODULUS,
+        values: [[c0], [c1]],
+        secret_key: [s],
+    };
+    keyed_round_trip(Op::Decrypt, None, data, Some(key));
+}
+
+// smudging noise across its whole range, so that every noise bit changes
+#[test]
+fn decrypt_share() {
+    let (s, key, _) = keys();
+    let offset = SMUDGING_OFFSET as i64;
+    let noise: Vec<i64> = poly(210)
+        .iter()
+        .map(|&c| (c % (2 * offset as u64)) as i64 - offset)
+        .collect();
+    let c1 = poly(211);
+    let data = DecryptShareData {
+        modulus: vec![MODULUS],
+        values: [[poly(212)], [c1.clone()]],
+        secret_key: [s.clone()],
+        noise: noise.clone(),
+    };
+    let proof_file_path = keyed_round_trip(Op::DecryptShare, None, data, Some(key));
+
+    let residues = noise
+        .iter()
+        .map(|&c| c.rem_euclid(MODULUS as i64) as u64)
+        .collect();
+    let noise = forward_transform(residues, MODULUS);
+    let data = load_data(&proof_file_path).unwrap();
+    for k in 0..COEFF_DEGREE {
+        assert_eq!(data.result[0][0][k], (c1[k] * s[k] + noise[k]) % MODULUS);
+    }
+}
+
+#[test]
+fn encrypt() {
+    let (_, key, randomness) = keys();
+    let data = EncryptData {
+        modulus: vec![MODULUS],
+        plain_modulus: PLAIN_MODULUS,
+        plaintext: poly(206).iter().map(|c| c % PLAIN_MODULUS).collect(),
+        randomness,
+        errors: [poly(207), poly(208)].map(|e| e.iter().map(|&c| (c % 9) as i64 - 4).collect()),
+    };
+    keyed_round_trip(Op::Encrypt, None, data, Some(key));
+}
+
+// a bootstrapping key of random rows: the circuit proves the CMux for any GGSW ciphertext
+#[test]
+fn blind_rotate() {
+    let key = EvaluationKey {
+        modulus: vec![MODULUS],
+        values: (0..VALUE_NUM * COEFF_LEVEL)
+            .map(|i| ciphertext(300 + 2 * i as u64))
+            .collect(),
+    };
+    let data = CiphertextData {
+        modulus: vec![MODULUS],
+        values: ciphertext(1),
+    };
+    keyed_round_trip(Op::BlindRotate, Some(-5), data, Some(key));
+}
+
+#[test]
+#[should_panic(expected = "needs a second RNS level")]
+fn mod_switch_is_rejected() {
+    let values = ciphertext(1);
+    round_trip(
+        Op::ModSwitch,
+        None,
+        CiphertextData {
+            modulus: vec![MODULUS],
+            values,
+        },
+    );
+}
@@ -0,0 +1,312 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Barrett reduction gadget: constraints proving r = x mod m for a value x of an AIR, a modulus
+// m below 2^BARRETT_MODULUS_BITS and a remainder r held by the AIR, e.g. in its result columns.
+// The gadget adds a block of binary columns to the trace of the AIR:
+//
+//     x = k * m + r,  k = sum_i k_i * 2^i,  r = sum_i r_i * 2^i,  m - 1 - r = sum_i s_i * 2^i
+//
+// The quotient k has `quotient_bits` bits, the remainder r and the slack m - 1 - r have
+// BARRETT_MODULUS_BITS bits each, or `modulus_bits` for blocks of narrower moduli or of the
+// BARRETT_WIDE_MODULUS_BITS moduli of wide-mul, so 0 <= r < m and x < 2^quotient_bits * m.
+// Both sides stay far below the field modulus, so the first equation holds over the integers
+// and r is the unique remainder; unlike with carry or borrow flags, no comparison is left to the
+// prover. The prover finds k by Barrett's method, see reduce; the verifier only checks the
+// equations.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use winter_air::TransitionConstraintDegree;
+use winter_math::FieldElement;
+
+use crate::{element_from_u64, BaseElement};
+
+pub const BARRETT_MODULUS_BITS: usize = 62;
+// Widest moduli of a block, for circuits whose values with 64-bit quotients stay below the field
+// modulus.
+pub const BARRETT_WIDE_MODULUS_BITS: usize = 63;
+
+// Block of trace columns reducing one value, starting at column `start`: the quotient bits, then
+// the remainder bits, then the slack bits, least significant first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Barrett {
+    pub start: usize,
+    pub quotient_bits: usize,
+    pub modulus_bits: usize,
+}
+
+impl Barrett {
+    pub const fn new(start: usize, quotient_bits: usize) -> Self {
+        Barrett::with_modulus_bits(start, quotient_bits, BARRETT_MODULUS_BITS)
+    }
+
+    // Block for moduli of at most 2^modulus_bits, modulus_bits being at most
+    // BARRETT_WIDE_MODULUS_BITS, whose remainder and slack take fewer columns, or one more.
+    pub const fn with_modulus_bits(
+        start: usize,
+        quotient_bits: usize,
+        modulus_bits: usize,
+    ) -> Self {
+        assert!(modulus_bits <= BARRETT_WIDE_MODULUS_BITS);
+        Barrett {
+            start,
+            quotient_bits,
+            modulus_bits,
+        }
+    }
+
+    // Number of trace columns of the block.
+    pub const fn width(&self) -> usize {
+        self.quotient_bits + 2 * self.modulus_bits
+    }
+
+    // Column after the block, where the next block can start.
+    pub const fn end(&self) -> usize {
+        self.start + self.width()
+    }
+
+    // Number of constraints evaluate writes: the three equations, then one per bit.
+    pub const fn num_constraints(&self) -> usize {
+        3 + self.width()
+    }
+
+    fn remainder_start(&self) -> usize {
+        self.start + self.quotient_bits
+    }
+
+    fn slack_start(&self) -> usize {
+        self.remainder_start() + self.modulus_bits
+    }
+
+    // Degrees of the constraints in the order evaluate writes them, for a value x of degree
+    // `value_degree` and a modulus of degree `modulus_degree` in the trace, 0 for a constant.
+    pub fn degrees(
+        &self,
+        value_degree: usize,
+        modulus_degree: usize,
+    ) -> Vec<TransitionConstraintDegree> {
+        let mut degrees = vec![
+            TransitionConstraintDegree::new(value_degree.max(modulus_degree + 1)),
+            TransitionConstraintDegree::new(1),
+            TransitionConstraintDegree::new(1),
+        ];
+        degrees.extend(vec![TransitionConstraintDegree::new(2); self.width()]);
+        degrees
+    }
+
+    // Fills the block of `row` with the bits of the reduction of `x` modulo `m` and returns the
+    // remainder.
+    pub fn fill(&self, x: u128, m: u64, row: &mut [BaseElement]) -> u64 {
+        assert!(
+            m > 0 && 64 - (m - 1).leading_zeros() as usize <= self.modulus_bits,
+            "remainders modulo {} do not fit into {} bits",
+            m,
+            self.modulus_bits
+        );
+        let (quotient, remainder) = reduce(x, m);
+        assert!(
+            quotient >> self.quotient_bits == 0,
+            "quotient {} of {} by {} does not fit into {} bits",
+            quotient,
+            x,
+            m,
+            self.quotient_bits
+        );
+        write_bits(quotient, &mut row[self.start..self.remainder_start()]);
+        write_bits(
+            remainder as u128,
+            &mut row[self.remainder_start()..self.slack_start()],
+        );
+        write_bits(
+            (m - 1 - remainder) as u128,
+            &mut row[self.slack_start()..self.end()],
+        );
+        remainder
+    }
+
+    // Quotient recomposed from the bits of the block in `row`.
+    pub fn quotient<E: FieldElement + From<BaseElement>>(&self, row: &[E]) -> E {
+        compose(&row[self.start..self.remainder_start()])
+    }
+
+    // Constraint values of the reduction of `x` modulo `m` to `r` with the block in `row`, zero
+    // when r = x mod m.
+    pub fn evaluate<E: FieldElement + From<BaseElement>>(
+        &self,
+        x: E,
+        m: E,
+        r: E,
+        row: &[E],
+        result: &mut [E],
+    ) {
+        result[0] = x - self.quotient(row) * m - r;
+        result[1] = r - compose(&row[self.remainder_start()..self.slack_start()]);
+        result[2] = m - E::ONE - r - compose(&row[self.slack_start()..self.end()]);
+        for (i, &bit) in row[self.start..self.end()].iter().enumerate() {
+            result[3 + i] = bit * (bit - E::ONE);
+        }
+    }
+}
+
+// Writes the bits of `value` to `columns`, least significant first, as the blocks hold them.
+pub fn write_bits(value: u128, columns: &mut [BaseElement]) {
+    for (i, column) in columns.iter_mut().enumerate() {
+        *column = element_from_u64((value >> i) as u64 & 1);
+    }
+}
+
+// Value of the bits written by write_bits.
+pub fn compose<E: FieldElement + From<BaseElement>>(bits: &[E]) -> E {
+    bits.iter()
+        .rev()
+        .fold(E::ZERO, |value, &bit| value.double() + bit)
+}
+
+// Quotient and remainder of `x` by `m` with Barrett's method: with k the bit length of m and
+// mu = floor(4^k / m), the estimate floor(floor(x / 2^(k-1)) * mu / 2^(k+1)) is at most two
+// below the quotient for x < 4^k, and the remainder is corrected by at most two subtractions.
+pub fn barrett_reduce(x: u128, m: u64) -> (u128, u64) {
+    assert!(
+        m > 1 && 64 - m.leading_zeros() as usize <= BARRETT_WIDE_MODULUS_BITS,
+        "modulus {} does not fit into {} bits",
+        m,
+        BARRETT_WIDE_MODULUS_BITS
+    );
+    let k = 64 - m.leading_zeros();
+    assert!(
+        x >> (2 * k) == 0,
+        "{} is too large for a Barrett reduction by {}",
+        x,
+        m
+    );
+    let mu = (1u128 << (2 * k)) / m as u128;
+    let mut quotient = ((x >> (k - 1)) * mu) >> (k + 1);
+    let mut remainder = x - quotient * m as u128;
+    while remainder >= m as u128 {
+        remainder -= m as u128;
+        quotient += 1;
+    }
+    (quotient, remainder as u64)
+}
+
+// Quotient and remainder of `x` by `m`: Barrett's method below 4^k, a division for the wider
+// values of the circuits reducing products, like the tensor products of mul.
+pub fn reduce(x: u128, m: u64) -> (u128, u64) {
+    let k = 64 - m.leading_zeros();
+    match x >> (2 * k) {
+        0 => barrett_reduce(x, m),
+        _ => (x / m as u128, (x % m as u128) as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::element_from_u128;
+
+    const MODULI: [u64; 3] = [65537, 1125899906826241, (1 << 62) - 57];
+
+    // xorshift64*, as in the tests of air.rs of the stark crate
+    fn samples(count: usize) -> Vec<u64> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..count)
+            .map(|_| {
+                state ^= state >> 12;
+                state ^= state << 25;
+                state ^= state >> 27;
+                state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reduction_matches_remainder() {
+        for m in MODULI {
+            let edges = [0, 1, m as u128 - 1, m as u128, 3 * m as u128 - 1];
+            let xs = samples(10_000)
+                .into_iter()
+                .map(|x| x as u128 % (3 * m as u128));
+            for x in edges.into_iter().chain(xs) {
+                let (quotient, remainder) = barrett_reduce(x, m);
+                assert_eq!(quotient, x / m as u128);
+                assert_eq!(remainder as u128, x % m as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn constraints_bind_the_remainder() {
+        let block = Barrett::new(1, 2);
+        for m in MODULI {
+            let x = 2 * m as u128 + 5;
+            let mut row = vec![BaseElement::ZERO; block.end()];
+            assert_eq!(block.fill(x, m, &mut row), 5);
+            let mut result = vec![BaseElement::ZERO; block.num_constraints()];
+            let (x, m) = (element_from_u128(x), element_from_u64(m));
+            block.evaluate(x, m, BaseElement::new(5), &row, &mut result);
+            assert!(result.iter().all(|&value| value == BaseElement::ZERO));
+
+            // 5 + m is congruent to x but does not match the remainder bits
+            block.evaluate(x, m, BaseElement::new(5) + m, &row, &mut result);
+            assert!(result.iter().any(|&value| value != BaseElement::ZERO));
+        }
+    }
+
+    #[test]
+    fn narrow_blocks_bind_the_remainder() {
+        let block = Barrett::with_modulus_bits(0, 2, 17);
+        assert_eq!(block.width(), 2 + 2 * 17);
+        let mut row = vec![BaseElement::ZERO; block.end()];
+        assert_eq!(block.fill(2 * 65537 + 5, 65537, &mut row), 5);
+        let mut result = vec![BaseElement::ZERO; block.num_constraints()];
+        let (x, m) = (element_from_u128(2 * 65537 + 5), element_from_u64(65537));
+        block.evaluate(x, m, BaseElement::new(5), &row, &mut result);
+        assert!(result.iter().all(|&value| value == BaseElement::ZERO));
+    }
+
+    #[test]
+    fn wide_values_are_divided() {
+        for m in MODULI {
+            let x = 2 * (m as u128 - 1) * (m as u128 - 1);
+            assert_eq!(reduce(x, m), (x / m as u128, (x % m as u128) as u64));
+            let block = Barrett::new(0, 64);
+            let mut row = vec![BaseElement::ZERO; block.width()];
+            assert_eq!(block.fill(x, m, &mut row), (x % m as u128) as u64);
+            assert_eq!(block.quotient(&row), element_from_u128(x / m as u128));
+        }
+    }
+
+    // the middle tensor product term of 63-bit residues and its 64-bit quotient fit the field
+    #[test]
+    fn wide_moduli_bind_the_remainder() {
+        let m = (1 << 63) - 25;
+        let x = 2 * (m as u128 - 1) * (m as u128 - 1);
+        let block = Barrett::with_modulus_bits(0, 64, BARRETT_WIDE_MODULUS_BITS);
+        let mut row = vec![BaseElement::ZERO; block.width()];
+        let remainder = block.fill(x, m, &mut row);
+        assert_eq!(remainder as u128, x % m as u128);
+        let mut result = vec![BaseElement::ZERO; block.num_constraints()];
+        let (x, m) = (element_from_u128(x), element_from_u64(m));
+        block.evaluate(x, m, element_from_u64(remainder), &row, &mut result);
+        assert!(result.iter().all(|&value| value == BaseElement::ZERO));
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit into 16 bits")]
+    fn wide_modulus_is_rejected() {
+        let block = Barrett::with_modulus_bits(0, 2, 16);
+        let mut row = vec![BaseElement::ZERO; block.width()];
+        block.fill(5, 65537, &mut row);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit into 2 bits")]
+    fn wide_quotient_is_rejected() {
+        let mut row = vec![BaseElement::ZERO; Barrett::new(0, 2).width()];
+        Barrett::new(0, 2).fill(4 * 65537, 65537, &mut row);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Canonical byte encoding of public inputs, the part of canonical.rs of the stark crate, which
+// lists the rules, that a verifier needs: a length-prefixed ASCII domain tag, little-endian
+// integers of a fixed width, field elements as their canonical integer value in 16 bytes, and
+// sequences prefixed with their length as u32, optional values prefixed with a presence flag.
+
+use alloc::vec::Vec;
+
+use crate::{element_to_u128, BaseElement};
+
+pub const PUBLIC_INPUTS_TAG: &str = "STARK-HE/public-inputs/v1";
+pub const PUBLIC_INPUTS_EXCHANGE_TAG: &str = "STARK-HE/public-inputs-exchange/v1";
+
+pub struct CanonicalWriter {
+    bytes: Vec<u8>,
+}
+
+impl CanonicalWriter {
+    pub fn new(tag: &str) -> Self {
+        let mut writer = CanonicalWriter { bytes: Vec::new() };
+        writer.write_str(tag);
+        writer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // IEEE 754 bits of `value`.
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    pub fn write_u64s(&mut self, values: &[u64]) {
+        self.write_u32(values.len() as u32);
+        for &value in values {
+            self.write_u64(value);
+        }
+    }
+
+    pub fn write_option<T>(&mut self, value: Option<&T>, write: impl FnOnce(&mut Self, &T)) {
+        self.write_u8(value.is_some() as u8);
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn write_element(&mut self, value: BaseElement) {
+        self.bytes
+            .extend_from_slice(&element_to_u128(value).to_le_bytes());
+    }
+
+    pub fn write_elements(&mut self, values: &[BaseElement]) {
+        self.write_u32(values.len() as u32);
+        for value in values {
+            self.write_element(*value);
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
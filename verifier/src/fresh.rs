@@ -0,0 +1,681 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// FreshAir, the circuit of `add`, and the layout of its trace.
+//
+// Modulus + C lanes of Result + Reduction + Data, C coefficients per row and result component:
+// with L levels, lane j of row (k / C) * G + v * L + l, k = j mod C, reduces
+// d_0 + ... + d_{n-2} + m - d_{n-1} for coefficient k of component v at level l, m being the
+// modulus of the level, with a Barrett block of quotient, remainder and slack bits. G is
+// VALUE_NUM * L rounded up to a power of two (see fresh_group), the padding rows reduce zero
+// operands plus the first modulus. The remainder and slack have the bits of the largest modulus.
+// With one coefficient per row, the default, a lane reserves BARRETT_MODULUS_BITS columns for
+// each, the ones the block leaves staying zero and unconstrained, so that the layout does not
+// depend on the moduli; packed rows (see Packing) size the lanes to the moduli, so that several
+// of them fit into a trace row. The moduli reach the constraints as a periodic column of the
+// public ones; the modulus column only carries them to the public inputs. With the default
+// n = 3 operands, a + b - c, and C = 1:
+// M R K0 K1 R0..R61 S0..S61 D0 D1 D2
+
+#![allow(clippy::needless_range_loop)]
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+use winter_air::{
+    Air, AirContext, Assertion, EvaluationFrame, ProofOptions, TraceInfo,
+    TransitionConstraintDegree,
+};
+use winter_math::FieldElement;
+use winter_utils::Serializable;
+
+use crate::barrett::{Barrett, BARRETT_MODULUS_BITS};
+use crate::{element_from_u64, BaseElement, FIELD_BITS, FIELD_NAME, VALUE_NUM};
+
+pub const MODULUS_COLUMN: usize = 0;
+pub const RESULT_COLUMN: usize = MODULUS_COLUMN + 1;
+
+// Ring degrees `add` accepts at runtime, so that operands of other SEAL parameter sets need no
+// rebuild; the other circuits are fixed to COEFF_DEGREE.
+#[cfg(not(feature = "micro"))]
+pub const MIN_COEFF_DEGREE: usize = 1024;
+#[cfg(feature = "micro")]
+pub const MIN_COEFF_DEGREE: usize = crate::COEFF_DEGREE;
+pub const MAX_COEFF_DEGREE: usize = 32768;
+
+// Operand counts `add` accepts, given by the data file.
+pub const FRESH_MAX_OPERANDS: usize = 16;
+
+pub fn check_operand_num(operands: usize) -> Result<(), String> {
+    if !(2..=FRESH_MAX_OPERANDS).contains(&operands) {
+        return Err(format!(
+            "{} operands, expected 2 to {}",
+            operands, FRESH_MAX_OPERANDS
+        ));
+    }
+    Ok(())
+}
+
+// Moduli the Barrett blocks of `operands` operands reduce by: above 1, of at most
+// `modulus_bits` bits, small enough for q * m + r to fit into the FIELD_BITS of the field, and
+// large enough for the reduced value, below operands * m, to stay below 4^k for m of k bits.
+pub fn check_fresh_modulus(
+    modulus: &[u64],
+    operands: usize,
+    modulus_bits: usize,
+) -> Result<(), String> {
+    let quotient_bits = fresh_reduction(0, operands, modulus_bits).quotient_bits;
+    for &m in modulus.iter() {
+        let k = 64 - m.leading_zeros() as usize;
+        if m < 2 || k > modulus_bits {
+            return Err(format!(
+                "modulus {} does not fit into {} bits",
+                m, modulus_bits
+            ));
+        }
+        if quotient_bits + k > FIELD_BITS {
+            return Err(format!(
+                "modulus {} of {} operands exceeds the {} bits of the {} field",
+                m, operands, FIELD_BITS, FIELD_NAME
+            ));
+        }
+        if operands as u128 * m as u128 > 1u128 << (2 * k) {
+            return Err(format!(
+                "modulus {} is too small for {} operands",
+                m, operands
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Reduction block of the FreshAir lane whose result column is `start`, for `operands` operands
+// and moduli of `modulus_bits` bits: for reduced operands the reduced value is below
+// operands * m, so the quotient has the bit length of operands - 1, two bits for a + b - c.
+// The lanes reserve the block of their width, see SchemeConfig::reduction.
+pub const fn fresh_reduction(start: usize, operands: usize, modulus_bits: usize) -> Barrett {
+    let quotient_bits = usize::BITS - (operands - 1).leading_zeros();
+    Barrett::with_modulus_bits(start + 1, quotient_bits as usize, modulus_bits)
+}
+
+// Columns of a lane: the result, the reduction block and the operands.
+pub const fn fresh_lane_width(operands: usize, modulus_bits: usize) -> usize {
+    fresh_reduction(0, operands, modulus_bits).end() + operands
+}
+
+pub const fn fresh_width(operands: usize) -> usize {
+    RESULT_COLUMN + fresh_lane_width(operands, BARRETT_MODULUS_BITS)
+}
+
+// The number of operands of a FreshAir trace of `width` columns with `packing`, if it is an
+// accepted one.
+pub fn fresh_operand_num(width: usize, packing: Option<Packing>) -> Option<usize> {
+    let (coeffs_per_row, modulus_bits) = lanes(packing);
+    (2..=FRESH_MAX_OPERANDS).find(|&operands| {
+        RESULT_COLUMN + coeffs_per_row * fresh_lane_width(operands, modulus_bits) == width
+    })
+}
+
+// Whether some packing gives a FreshAir trace `width` columns, for callers which check the
+// shape of a trace before its public inputs are known.
+pub fn fresh_width_accepted(width: usize) -> bool {
+    fresh_operand_num(width, None).is_some()
+        || (1..=MAX_COEFFS_PER_ROW.trailing_zeros()).any(|log_coeffs| {
+            (2..=BARRETT_MODULUS_BITS).any(|modulus_bits| {
+                let packing = Packing {
+                    coeffs_per_row: 1 << log_coeffs,
+                    modulus_bits,
+                };
+                fresh_operand_num(width, Some(packing)).is_some()
+            })
+        })
+}
+
+// Coefficients per row and modulus bits of the lanes of `packing`, one of BARRETT_MODULUS_BITS
+// without.
+pub fn lanes(packing: Option<Packing>) -> (usize, usize) {
+    packing.map_or((1, BARRETT_MODULUS_BITS), |packing| {
+        (packing.coeffs_per_row, packing.modulus_bits)
+    })
+}
+
+// Coefficients per FreshAir row `add` accepts, given by `--coeffs-per-row`: packing C
+// coefficients into a row divides the trace length by C, which the width of the lanes bounds,
+// as a trace has at most TraceInfo::MAX_TRACE_WIDTH columns.
+pub const MAX_COEFFS_PER_ROW: usize = 16;
+
+// The packing of a FreshAir trace of more than one coefficient per row, which its public inputs
+// record, as the verifier cannot tell it from the trace width: the coefficients per row and the
+// bits of the remainder and slack of every lane, the bit length of the largest active modulus.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packing {
+    pub coeffs_per_row: usize,
+    pub modulus_bits: usize,
+}
+
+impl Packing {
+    pub fn check(&self) -> Result<(), String> {
+        if !self.coeffs_per_row.is_power_of_two()
+            || !(2..=MAX_COEFFS_PER_ROW).contains(&self.coeffs_per_row)
+        {
+            return Err(format!(
+                "{} coefficients per packed row, expected a power of two from 2 to {}",
+                self.coeffs_per_row, MAX_COEFFS_PER_ROW
+            ));
+        }
+        if !(2..=BARRETT_MODULUS_BITS).contains(&self.modulus_bits) {
+            return Err(format!(
+                "packed moduli of {} bits, expected 2 to {}",
+                self.modulus_bits, BARRETT_MODULUS_BITS
+            ));
+        }
+        Ok(())
+    }
+}
+
+// RNS level counts `add` accepts at runtime, one per modulus of its data file; the other
+// circuits are fixed to COEFF_LEVEL.
+pub const MAX_COEFF_LEVEL: usize = 20;
+
+pub fn check_coeff_level(levels: usize) -> Result<(), String> {
+    if !(1..=MAX_COEFF_LEVEL).contains(&levels) {
+        return Err(format!(
+            "{} RNS levels, expected 1 to {}",
+            levels, MAX_COEFF_LEVEL
+        ));
+    }
+    Ok(())
+}
+
+// Rows per coefficient of a FreshAir trace over `levels` levels.
+pub fn fresh_group(levels: usize) -> usize {
+    (VALUE_NUM * levels).next_power_of_two()
+}
+
+// Degrees which are not a power of two are padded to the next one, see SchemeConfig::padding_flags.
+pub fn check_coeff_degree(degree: usize) -> Result<(), String> {
+    if !(MIN_COEFF_DEGREE..=MAX_COEFF_DEGREE).contains(&degree) {
+        return Err(format!(
+            "coefficient degree {} is not from {} to {}",
+            degree, MIN_COEFF_DEGREE, MAX_COEFF_DEGREE
+        ));
+    }
+    Ok(())
+}
+
+// Runtime configuration of `add`, from which the layout of its trace follows: the ring degree,
+// the number of RNS levels, the number of operands and the coefficients per row with the bits
+// of their moduli, and the moduli of the levels. Provers load it from the data file and the
+// command line (see LoadConfig in the stark crate); verifiers derive it from the shape of the
+// proof, its packing and the moduli of its public inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemeConfig {
+    pub degree: usize,
+    pub levels: usize,
+    pub operands: usize,
+    pub coeffs_per_row: usize,
+    pub modulus_bits: usize,
+    pub modulus: Vec<u64>,
+}
+
+impl SchemeConfig {
+    // The configuration of a trace of `width` columns and `length` rows over `levels` levels
+    // and `degree` coefficients with `packing`, if it is an accepted one; row groups are powers
+    // of two, like the trace length and the coefficients per row.
+    pub fn from_trace(
+        width: usize,
+        length: usize,
+        levels: usize,
+        degree: usize,
+        modulus: &[u64],
+        packing: Option<Packing>,
+    ) -> Result<Self, String> {
+        if let Some(packing) = packing {
+            packing.check()?;
+        }
+        let operands = fresh_operand_num(width, packing).ok_or_else(|| match packing {
+            None => format!(
+                "add traces of 2 to {} operands have {} to {} columns, not {}",
+                FRESH_MAX_OPERANDS,
+                fresh_width(2),
+                fresh_width(FRESH_MAX_OPERANDS),
+                width
+            ),
+            Some(packing) => format!(
+                "add traces of {} coefficients per row of {} bits have no operand count of {} \
+                 columns",
+                packing.coeffs_per_row, packing.modulus_bits, width
+            ),
+        })?;
+        check_coeff_level(levels).map_err(|err| format!("add result of {}", err))?;
+        check_coeff_degree(degree).map_err(|err| format!("add result of {}", err))?;
+        if modulus.len() != levels {
+            return Err(format!(
+                "add proof records {} moduli for results of {} levels",
+                modulus.len(),
+                levels
+            ));
+        }
+        let (coeffs_per_row, modulus_bits) = lanes(packing);
+        check_fresh_modulus(modulus, operands, modulus_bits)?;
+        let config = SchemeConfig {
+            degree,
+            levels,
+            operands,
+            coeffs_per_row,
+            modulus_bits,
+            modulus: modulus.to_vec(),
+        };
+        config.check_rows()?;
+        if config.trace_length() != length {
+            return Err(format!(
+                "add trace of {} rows, {} coefficients over {} levels take {}",
+                length,
+                degree,
+                levels,
+                config.trace_length()
+            ));
+        }
+        Ok(config)
+    }
+
+    // Packed rows hold coefficients of one kind, none or every one of them padding.
+    pub fn check_rows(&self) -> Result<(), String> {
+        if !self.degree.is_multiple_of(self.coeffs_per_row) {
+            return Err(format!(
+                "{} coefficients do not fill rows of {}",
+                self.degree, self.coeffs_per_row
+            ));
+        }
+        Ok(())
+    }
+
+    // The degree rounded up to a power of two, with padding coefficients above the degree.
+    pub fn padded_degree(&self) -> usize {
+        self.degree.next_power_of_two()
+    }
+
+    // Periodic column flagging the rows of padding coefficients, which must hold zero operands
+    // and results, if the degree is not a power of two. A row of packed lanes holds no or only
+    // padding coefficients, see check_rows.
+    pub fn padding_flags(&self) -> Option<Vec<BaseElement>> {
+        if self.padded_degree() == self.degree {
+            return None;
+        }
+        let first = self.degree / self.coeffs_per_row * fresh_group(self.levels);
+        Some(
+            (0..self.trace_length())
+                .map(|row| element_from_u64((row >= first) as u64))
+                .collect(),
+        )
+    }
+
+    // The packing public inputs record, none for one coefficient per row.
+    pub fn packing(&self) -> Option<Packing> {
+        (self.coeffs_per_row > 1).then_some(Packing {
+            coeffs_per_row: self.coeffs_per_row,
+            modulus_bits: self.modulus_bits,
+        })
+    }
+
+    // Result column of lane `j`, the first of the lane.
+    pub fn lane(&self, j: usize) -> usize {
+        RESULT_COLUMN + j * fresh_lane_width(self.operands, self.modulus_bits)
+    }
+
+    // The block of lane `j`, sized to the largest modulus: a bit above it would be constant.
+    pub fn reduction(&self, j: usize) -> Barrett {
+        let modulus_bits = self
+            .modulus
+            .iter()
+            .map(|m| 64 - m.leading_zeros() as usize)
+            .max()
+            .unwrap_or(self.modulus_bits);
+        fresh_reduction(self.lane(j), self.operands, modulus_bits)
+    }
+
+    // First operand column of lane `j`, after the block the lane reserves.
+    pub fn operand_column(&self, j: usize) -> usize {
+        self.lane(j + 1) - self.operands
+    }
+
+    // The moduli of the rows of a row group as a periodic column, if they differ: with a single
+    // level every row reduces by the same modulus.
+    pub fn modulus_column(&self) -> Option<Vec<BaseElement>> {
+        (self.levels > 1).then(|| {
+            (0..fresh_group(self.levels))
+                .map(|i| element_from_u64(self.row_modulus(i)))
+                .collect()
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.lane(self.coeffs_per_row)
+    }
+
+    pub fn trace_length(&self) -> usize {
+        self.padded_degree() / self.coeffs_per_row * fresh_group(self.levels)
+    }
+
+    // Row of coefficient `k` of result component `v` at level `l`.
+    pub fn row(&self, k: usize, v: usize, l: usize) -> usize {
+        k / self.coeffs_per_row * fresh_group(self.levels) + v * self.levels + l
+    }
+
+    // Result column of coefficient `k`.
+    pub fn column(&self, k: usize) -> usize {
+        self.lane(k % self.coeffs_per_row)
+    }
+
+    // Modulus of row `i` of a row group: that of its level, the first one on padding rows.
+    pub fn row_modulus(&self, i: usize) -> u64 {
+        match i < VALUE_NUM * self.levels {
+            true => self.modulus[i % self.levels],
+            false => self.modulus[0],
+        }
+    }
+
+    // Rows of a row group which pad it to a power of two, the last of the group.
+    pub fn group_padding(&self) -> usize {
+        fresh_group(self.levels) - VALUE_NUM * self.levels
+    }
+
+    // The last row group ends in padding unless the components and levels fill it, otherwise
+    // every row is checked as the next row as well. Padding rows hold reductions of zero, so only
+    // the last one is exempt: the linear constraints of a lane would vanish on every other row.
+    pub fn boundary(&self) -> Boundary {
+        match self.group_padding() {
+            0 => Boundary::NextRow,
+            _ => Boundary::Padding(1),
+        }
+    }
+}
+
+// Checks that a FreshAir trace of `width` columns and `length` rows has an accepted
+// configuration over the levels of the components of `result` with `packing` and the moduli
+// `modulus`, one per level, and that every level holds one coefficient per lane and row group;
+// returns the configuration.
+pub fn check_fresh_trace(
+    width: usize,
+    length: usize,
+    result: &[Vec<Vec<BaseElement>>],
+    modulus: &[u64],
+    packing: Option<Packing>,
+) -> Result<SchemeConfig, String> {
+    let levels = result.first().map_or(0, Vec::len);
+    if let Some(v) = result
+        .iter()
+        .position(|component| component.len() != levels)
+    {
+        return Err(format!(
+            "add result component {} has {} levels, component 0 has {}",
+            v,
+            result[v].len(),
+            levels
+        ));
+    }
+    let degree = result
+        .first()
+        .and_then(|levels| levels.first())
+        .map_or(0, Vec::len);
+    let config = SchemeConfig::from_trace(width, length, levels, degree, modulus, packing)?;
+    match result
+        .iter()
+        .flatten()
+        .find(|level| level.len() != config.degree)
+    {
+        Some(level) => Err(format!(
+            "add trace of {} rows expects {} result coefficients per level, found {}",
+            length,
+            config.degree,
+            level.len()
+        )),
+        None => Ok(config),
+    }
+}
+
+// How the transition constraints of an AIR reach the last rows of its trace, which winterfell
+// exempts from them. Trace builders fill every row from their own data, the last one included,
+// and never wrap around to the first, so an exempt row is unchecked unless the AIR checks it
+// otherwise: with NextRow, every row is constrained both as the current and as the next row of a
+// frame, so that the one exempt last row is checked as the next row of the row before; with
+// Padding(n), rows are constrained as the current row only and the last n rows are exempt, which
+// is sound only when no result or assertion depends on them. AIRs set their exemptions with
+// `apply` rather than relying on winterfell's default of one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    NextRow,
+    Padding(usize),
+}
+
+impl Boundary {
+    pub fn exemptions(&self) -> usize {
+        match self {
+            Boundary::NextRow => 1,
+            Boundary::Padding(rows) => *rows,
+        }
+    }
+
+    // Frames whose constraints are evaluated on every transition: the current row, and with
+    // NextRow also the next one.
+    pub fn frame_rows(&self) -> usize {
+        match self {
+            Boundary::NextRow => 2,
+            Boundary::Padding(_) => 1,
+        }
+    }
+
+    pub fn apply(&self, context: AirContext<BaseElement>) -> AirContext<BaseElement> {
+        context.set_num_transition_exemptions(self.exemptions())
+    }
+}
+
+// Public inputs of FreshAir: the VALUE_NUM result components, each with one coefficient vector
+// per level, the moduli of the levels and the packing of the rows. The stark crate passes its
+// PublicInputs of every circuit, the verifier of this crate AddPublicInputs; either writes the
+// exchange encoding the prover seeded the transcript with.
+pub trait FreshInputs: Serializable + Send + Sync {
+    fn into_fresh(self) -> (Vec<Vec<Vec<BaseElement>>>, Vec<u64>, Option<Packing>);
+}
+
+pub struct FreshAir<P> {
+    context: AirContext<BaseElement>,
+    // None for public inputs of another configuration than the trace
+    config: Option<SchemeConfig>,
+    result: Vec<Vec<Vec<BaseElement>>>,
+    inputs: PhantomData<P>,
+}
+
+impl<P: FreshInputs> Air for FreshAir<P> {
+    type BaseField = BaseElement;
+    type PublicInputs = P;
+
+    fn new(trace_info: TraceInfo, pub_inputs: P, options: ProofOptions) -> Self {
+        // the operand count is that of the trace width and the packing, the level count and the
+        // degree those of the results, the moduli public; the verifiers refuse other public
+        // inputs with the reason check_fresh_trace gives before they get here, but should they
+        // not, a single constraint no trace satisfies fails the verification
+        let (result, modulus, packing) = pub_inputs.into_fresh();
+        let config = match check_fresh_trace(
+            trace_info.width(),
+            trace_info.length(),
+            &result,
+            &modulus,
+            packing,
+        ) {
+            Ok(config) => config,
+            Err(_) => {
+                let degrees = vec![TransitionConstraintDegree::new(1)];
+                return FreshAir {
+                    context: AirContext::new(trace_info, degrees, 1, options),
+                    config: None,
+                    result,
+                    inputs: PhantomData,
+                };
+            }
+        };
+        // the reduced value is linear, the periodic modulus changes with the level unless there
+        // is a single one; the reduction of every lane is checked on the rows of the boundary,
+        // the current and the next row unless the trace ends in padding rows, the last exempt;
+        // rows of padding coefficients also zero the result and the operands of every lane under
+        // their flag
+        let boundary = config.boundary();
+        let padded = config.padding_flags().is_some();
+        let flagged = TransitionConstraintDegree::with_cycles(1, vec![config.trace_length()]);
+        let cycle = config.modulus_column().map(|column| column.len());
+        let mut degrees = Vec::new();
+        for _ in 0..boundary.frame_rows() {
+            for j in 0..config.coeffs_per_row {
+                let mut lane = config.reduction(j).degrees(1, 0);
+                if let Some(cycle) = cycle {
+                    lane[0] = TransitionConstraintDegree::with_cycles(1, vec![cycle]);
+                }
+                degrees.extend(lane);
+                if padded {
+                    degrees.extend(vec![flagged.clone(); 1 + config.operands]);
+                }
+            }
+        }
+        let num_assertions = VALUE_NUM * config.levels * (config.coeffs_per_row + 1);
+        let context = AirContext::new(trace_info, degrees, num_assertions, options);
+
+        FreshAir {
+            config: Some(config),
+            context: boundary.apply(context),
+            result,
+            inputs: PhantomData,
+        }
+    }
+
+    fn context(&self) -> &AirContext<Self::BaseField> {
+        &self.context
+    }
+
+    // The flags of the current and the next row, if the degree is padded, then their moduli, if
+    // the levels have several.
+    fn get_periodic_column_values(&self) -> Vec<Vec<Self::BaseField>> {
+        let mut columns = Vec::new();
+        let config = match &self.config {
+            Some(config) => config,
+            None => return columns,
+        };
+        for column in [config.padding_flags(), config.modulus_column()]
+            .into_iter()
+            .flatten()
+        {
+            let mut next = column.clone();
+            next.rotate_left(1);
+            columns.extend([column, next]);
+        }
+        columns
+    }
+
+    fn evaluate_transition<E: FieldElement + From<Self::BaseField>>(
+        &self,
+        frame: &EvaluationFrame<E>,
+        periodic_values: &[E],
+        result: &mut [E],
+    ) {
+        let config = match &self.config {
+            Some(config) => config,
+            None => {
+                result[0] = E::ONE;
+                return;
+            }
+        };
+        let padded = config.padded_degree() != config.degree;
+        let (flags, moduli) = periodic_values.split_at(2 * padded as usize);
+        let rows = [frame.current(), frame.next()]
+            .into_iter()
+            .enumerate()
+            .take(config.boundary().frame_rows())
+            .flat_map(|(f, row)| (0..config.coeffs_per_row).map(move |j| (f, j, row)));
+        let mut result = result;
+        for (f, j, row) in rows {
+            let m = match moduli.get(f) {
+                Some(&m) => m,
+                None => E::from(element_from_u64(config.modulus[0])),
+            };
+            let (lane, rest) = result.split_at_mut(config.reduction(j).num_constraints());
+            evaluate_fresh_lane(config, j, row, m, lane);
+            result = rest;
+            if let Some(&flag) = flags.get(f) {
+                let (padding, rest) = result.split_at_mut(1 + config.operands);
+                let operands = config.operand_column(j);
+                padding[0] = flag * row[config.lane(j)];
+                for (d, constraint) in padding[1..].iter_mut().enumerate() {
+                    *constraint = flag * row[operands + d];
+                }
+                result = rest;
+            }
+        }
+    }
+
+    // Every result coefficient, one sequence per lane, component and level over the row groups,
+    // the padding coefficients as zero, then the modulus column on the same rows: the column is
+    // constant over a single level, so assertions on rows of their own would form a boundary
+    // constraint group of degree zero
+    fn get_assertions(&self) -> Vec<Assertion<Self::BaseField>> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return vec![Assertion::single(MODULUS_COLUMN, 0, BaseElement::ZERO)],
+        };
+        let group = fresh_group(config.levels);
+        let mut assertions =
+            Vec::with_capacity(VALUE_NUM * config.levels * (config.coeffs_per_row + 1));
+        for j in 0..config.coeffs_per_row {
+            for v in 0..VALUE_NUM {
+                for l in 0..config.levels {
+                    let values = (j..config.padded_degree())
+                        .step_by(config.coeffs_per_row)
+                        .map(|k| {
+                            self.result[v][l]
+                                .get(k)
+                                .copied()
+                                .unwrap_or(BaseElement::ZERO)
+                        })
+                        .collect();
+                    assertions.push(Assertion::sequence(
+                        config.column(j),
+                        config.row(j, v, l),
+                        group,
+                        values,
+                    ));
+                }
+            }
+        }
+        for v in 0..VALUE_NUM {
+            for l in 0..config.levels {
+                assertions.push(Assertion::periodic(
+                    MODULUS_COLUMN,
+                    config.row(0, v, l),
+                    group,
+                    element_from_u64(config.modulus[l]),
+                ));
+            }
+        }
+        assertions
+    }
+}
+
+// The reduction of lane `j` of `row` by the modulus `m` of the row, the sum of all but the last of
+// its operands plus m minus the last, to the result of the lane, zero on a correct row.
+pub fn evaluate_fresh_lane<E: FieldElement + From<BaseElement>>(
+    config: &SchemeConfig,
+    j: usize,
+    row: &[E],
+    m: E,
+    result: &mut [E],
+) {
+    let reduction = config.reduction(j);
+    let operands = &row[config.operand_column(j)..config.lane(j + 1)];
+    let (last, rest) = operands.split_last().expect("additions have operands");
+    let x = rest.iter().fold(m - *last, |x, &d| x + d);
+    reduction.evaluate(x, m, row[config.lane(j)], row, result);
+}
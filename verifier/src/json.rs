@@ -0,0 +1,160 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Text inputs of verify_add, for callers which pass strings, like the WASM bindings: the proof as
+// the base64 `proof` field of a proof file, and its public inputs as JSON of the `result`,
+// `modulus`, `scheme` and `packing` fields of the file, e.g.
+//
+//     {"result": [[[3, 5, ...], [7, 11, ...]], [[...], [...]]], "modulus": [65537, 114689],
+//      "scheme": "bgv"}
+//
+// with every result component, level and coefficient as in the proof file; `scheme` is absent
+// for BFV, `packing` for one coefficient per row.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::fresh::Packing;
+use crate::{element_from_u64, verify_add, AddPublicInputs, VerifyError};
+
+// Proofs of the current circuits are well below 1 MiB, as in encoding.rs of the stark crate.
+pub const MAX_PROOF_BYTES: usize = 16 << 20;
+
+// Standard alphabet, padded or not, like ProofEncoding of the stark crate.
+const ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Schemes `add` proofs record, with their tags; BFV proofs record none.
+const SCHEMES: [(&str, u8); 1] = [("bgv", 1)];
+
+#[derive(Deserialize)]
+struct AddResult {
+    result: Vec<Vec<Vec<u64>>>,
+    #[serde(default)]
+    modulus: Vec<u64>,
+    #[serde(default)]
+    scheme: Option<String>,
+    #[serde(default)]
+    packing: Option<Packing>,
+}
+
+pub fn decode_proof(proof_b64: &str) -> Result<Vec<u8>, VerifyError> {
+    if proof_b64.is_empty() {
+        return Err(VerifyError::Encoding(String::from("proof is empty")));
+    }
+    if proof_b64.len() / 4 * 3 > MAX_PROOF_BYTES {
+        return Err(VerifyError::Encoding(format!(
+            "proof is larger than the limit of {} bytes",
+            MAX_PROOF_BYTES
+        )));
+    }
+    ENGINE
+        .decode(proof_b64)
+        .map_err(|err| VerifyError::Encoding(format!("invalid base64: {}", err)))
+}
+
+pub fn parse_public_inputs(result_json: &str) -> Result<AddPublicInputs, VerifyError> {
+    let parsed: AddResult = serde_json::from_str(result_json)
+        .map_err(|err| VerifyError::Encoding(format!("invalid result JSON: {}", err)))?;
+    let scheme = match parsed.scheme {
+        Some(name) => Some(
+            SCHEMES
+                .iter()
+                .find(|(scheme, _)| *scheme == name)
+                .map(|&(_, tag)| tag)
+                .ok_or_else(|| {
+                    VerifyError::Encoding(format!("add proofs do not record the scheme {}", name))
+                })?,
+        ),
+        None => None,
+    };
+    let result = parsed
+        .result
+        .into_iter()
+        .map(|levels| {
+            levels
+                .into_iter()
+                .map(|level| level.into_iter().map(element_from_u64).collect())
+                .collect()
+        })
+        .collect();
+    Ok(AddPublicInputs {
+        result,
+        modulus: parsed.modulus,
+        scheme,
+        packing: parsed.packing,
+    })
+}
+
+// Verifies the base64 `add` proof `proof_b64` against the results of `result_json`.
+pub fn verify_json(proof_b64: &str, result_json: &str) -> Result<(), VerifyError> {
+    let proof = decode_proof(proof_b64)?;
+    verify_add(&proof, parse_public_inputs(result_json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BaseElement;
+
+    #[test]
+    fn text_inputs_are_decoded() {
+        let inputs = parse_public_inputs(
+            r#"{"result": [[[1, 2], [3, 4]], [[5, 6], [7, 8]]], "modulus": [65537, 114689],
+                "scheme": "bgv", "packing": {"coeffs_per_row": 2, "modulus_bits": 17}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inputs.result[1][0],
+            [BaseElement::new(5), BaseElement::new(6)]
+        );
+        assert_eq!(inputs.modulus, [65537, 114689]);
+        assert_eq!(inputs.scheme, Some(1));
+        assert_eq!(
+            inputs.packing,
+            Some(Packing {
+                coeffs_per_row: 2,
+                modulus_bits: 17
+            })
+        );
+        let inputs = parse_public_inputs(r#"{"result": []}"#).unwrap();
+        assert_eq!((inputs.scheme, inputs.packing), (None, None));
+
+        assert_eq!(decode_proof("AQID").unwrap(), [1, 2, 3]);
+        assert_eq!(decode_proof("AQIDBA").unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn malformed_text_inputs_are_rejected() {
+        for proof in ["", "not base64!"] {
+            assert!(matches!(
+                verify_json(proof, r#"{"result": []}"#),
+                Err(VerifyError::Encoding(_))
+            ));
+        }
+        for result in [
+            "",
+            r#"{"result": [[["1"]]]}"#,
+            r#"{"result": [], "scheme": "ckks"}"#,
+        ] {
+            assert!(matches!(
+                verify_json("AQID", result),
+                Err(VerifyError::Encoding(_))
+            ));
+        }
+        assert!(matches!(
+            verify_json("AQID", r#"{"result": []}"#),
+            Err(VerifyError::Proof(_))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Verifier of STARK-HE `add` proofs for targets without the standard library, like embedded
+// devices, kernels or WASM: FreshAir, the Barrett gadget it reduces with and the canonical
+// encoding of its public inputs, over `core` and `alloc` only. The stark crate builds its prover
+// and its verifier of every circuit on these modules, so both sides of an `add` proof share one
+// AIR; this crate adds `verify_add`, which checks a proof against AddPublicInputs without the
+// data files, proof containers and command lines of the stark crate.
+//
+// Build with `default-features = false` to also drop the standard library from winterfell. The
+// `json` feature adds verify_json, which takes the proof and its results as text.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod barrett;
+pub mod canonical;
+pub mod fresh;
+#[cfg(feature = "json")]
+pub mod json;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use winter_air::FieldExtension;
+use winter_math::StarkField;
+use winter_utils::{ByteWriter, DeserializationError, Serializable};
+use winter_verifier::{StarkProof, VerifierError};
+
+use crate::barrett::BARRETT_MODULUS_BITS;
+use crate::canonical::{CanonicalWriter, PUBLIC_INPUTS_EXCHANGE_TAG, PUBLIC_INPUTS_TAG};
+use crate::fresh::{check_fresh_trace, FreshAir, FreshInputs, Packing};
+
+// The circuits prove integer relations between residues, such as x = k * m + r or the tensor
+// products of mul, and rely on both sides staying below the field modulus, so that no relation
+// holds only modulo the field: FIELD_BITS bounds the integers a relation may reach. The default
+// field is Winterfell's 128-bit one. The `goldilocks` feature builds over the 64-bit Goldilocks
+// field instead, whose arithmetic is several times faster; its integers stop at 63 bits, so the
+// circuits which multiply wide residues either split them into the limbs of limbs.rs of the
+// stark crate or reject the build, and its proofs take a field extension for their soundness.
+#[cfg(not(feature = "goldilocks"))]
+pub type BaseElement = winter_math::fields::f128::BaseElement;
+#[cfg(feature = "goldilocks")]
+pub type BaseElement = winter_math::fields::f64::BaseElement;
+
+#[cfg(not(feature = "goldilocks"))]
+pub const FIELD_NAME: &str = "f128";
+#[cfg(feature = "goldilocks")]
+pub const FIELD_NAME: &str = "f64";
+
+#[cfg(not(feature = "goldilocks"))]
+pub const FIELD_MODULUS: u128 = BaseElement::MODULUS;
+#[cfg(feature = "goldilocks")]
+pub const FIELD_MODULUS: u128 = BaseElement::MODULUS as u128;
+
+// Bits of the largest integers below the field modulus.
+pub const FIELD_BITS: usize = BaseElement::MODULUS_BITS as usize - 1;
+
+const _: () = assert!(FIELD_BITS > BARRETT_MODULUS_BITS);
+
+// The smallest field extension of the proofs of this build: a 64-bit field alone leaves the
+// out-of-domain and FRI challenges too few values for the security levels of the presets.
+#[cfg(not(feature = "goldilocks"))]
+pub const MIN_FIELD_EXTENSION: FieldExtension = FieldExtension::None;
+#[cfg(feature = "goldilocks")]
+pub const MIN_FIELD_EXTENSION: FieldExtension = FieldExtension::Quadratic;
+
+// Proofs of this build take at least MIN_FIELD_EXTENSION.
+pub fn check_field_extension(extension: FieldExtension) -> Result<(), String> {
+    if extension.degree() < MIN_FIELD_EXTENSION.degree() {
+        return Err(format!(
+            "proofs over {} take the field extension {:?} or higher, not {:?}",
+            FIELD_NAME, MIN_FIELD_EXTENSION, extension
+        ));
+    }
+    Ok(())
+}
+
+pub const VALUE_NUM: usize = 2;
+#[cfg(not(feature = "micro"))]
+pub const COEFF_LEVEL: usize = 2;
+#[cfg(not(feature = "micro"))]
+pub const COEFF_DEGREE: usize = 4096;
+#[cfg(feature = "micro")]
+pub const COEFF_LEVEL: usize = 1;
+#[cfg(feature = "micro")]
+pub const COEFF_DEGREE: usize = 16;
+
+// Explicit integer conversions between coefficients and field elements; coefficients are
+// always reduced modulo a u64 RNS prime, so anything wider indicates a corrupted trace.
+pub fn element_from_u64(value: u64) -> BaseElement {
+    BaseElement::from(value)
+}
+
+pub fn element_to_u64(value: BaseElement) -> u64 {
+    u64::try_from(element_to_u128(value)).expect("field element does not fit into u64")
+}
+
+// The same for the integers of the circuits, reduced modulo the field modulus.
+pub fn element_from_u128(value: u128) -> BaseElement {
+    BaseElement::from(value)
+}
+
+#[cfg(not(feature = "goldilocks"))]
+pub fn element_to_u128(value: BaseElement) -> u128 {
+    value.as_int()
+}
+
+#[cfg(feature = "goldilocks")]
+pub fn element_to_u128(value: BaseElement) -> u128 {
+    u128::from(value.as_int())
+}
+
+// The public inputs of an `add` proof: its VALUE_NUM result components, each with one
+// coefficient vector per level, the moduli of the levels, the tag of its scheme unless it is BFV
+// (see Scheme::tag of the stark crate) and the packing of rows of several coefficients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddPublicInputs {
+    pub result: Vec<Vec<Vec<BaseElement>>>,
+    pub modulus: Vec<u64>,
+    pub scheme: Option<u8>,
+    pub packing: Option<Packing>,
+}
+
+impl AddPublicInputs {
+    // The canonical encoding the PublicInputs of the stark crate write for an `add` proof, whose
+    // other fields are absent: the number of result components and of their levels as u32, every
+    // result vector ordered by component index, then level index, then the moduli as u64, then
+    // the scheme tag as u8 if there is one, then the coefficients per row and the modulus bits of
+    // a packing as u32.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut writer = CanonicalWriter::new(PUBLIC_INPUTS_TAG);
+        writer.write_u32(self.result.len() as u32);
+        writer.write_u32(self.result.first().map_or(COEFF_LEVEL, Vec::len) as u32);
+        for value in self.result.iter() {
+            for level in value.iter() {
+                writer.write_elements(level);
+            }
+        }
+        for &m in self.modulus.iter() {
+            writer.write_u64(m);
+        }
+        if let Some(scheme) = self.scheme {
+            writer.write_u8(scheme);
+        }
+        if let Some(packing) = self.packing {
+            writer.write_u32(packing.coeffs_per_row as u32);
+            writer.write_u32(packing.modulus_bits as u32);
+        }
+        writer.into_bytes()
+    }
+}
+
+// The exchange encoding the PublicInputs of the stark crate write for an `add` proof, which
+// seeds its transcript: the result components as sequences of levels, then the presence flags of
+// the other fields in declaration order, with the moduli as a sequence of u64, the scheme tag as
+// u8 and the packing as two u32 if they are present.
+impl Serializable for AddPublicInputs {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        let mut writer = CanonicalWriter::new(PUBLIC_INPUTS_EXCHANGE_TAG);
+        writer.write_u32(self.result.len() as u32);
+        for value in self.result.iter() {
+            writer.write_u32(value.len() as u32);
+            for level in value.iter() {
+                writer.write_elements(level);
+            }
+        }
+        // the evaluation key, rotation, scale
+        for _ in 0..3 {
+            writer.write_u8(0);
+        }
+        writer.write_option(Some(&self.modulus), |writer, modulus| {
+            writer.write_u64s(modulus)
+        });
+        // the plain modulus, decryption, slots, scalar, weights, result digest and window
+        for _ in 0..7 {
+            writer.write_u8(0);
+        }
+        writer.write_option(self.scheme.as_ref(), |writer, &scheme| {
+            writer.write_u8(scheme)
+        });
+        writer.write_option(self.packing.as_ref(), |writer, packing| {
+            writer.write_u32(packing.coeffs_per_row as u32);
+            writer.write_u32(packing.modulus_bits as u32);
+        });
+        // the input
+        writer.write_u8(0);
+        target.write_u8_slice(&writer.into_bytes());
+    }
+}
+
+impl FreshInputs for AddPublicInputs {
+    fn into_fresh(self) -> (Vec<Vec<Vec<BaseElement>>>, Vec<u64>, Option<Packing>) {
+        (self.result, self.modulus, self.packing)
+    }
+}
+
+// Why verify_add rejected a proof.
+#[derive(Debug)]
+pub enum VerifyError {
+    // base64 or JSON text which cannot be decoded, see json.rs
+    Encoding(String),
+    // the proof bytes cannot be decoded
+    Proof(DeserializationError),
+    // the trace shape, results or packing are not those of an accepted `add` trace
+    Shape(String),
+    // the proof options are below those of the field, see check_field_extension
+    Options(String),
+    // the STARK does not verify
+    Verifier(VerifierError),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Encoding(message) => write!(f, "{}", message),
+            VerifyError::Proof(err) => write!(f, "invalid proof: {}", err),
+            VerifyError::Shape(message) => write!(f, "{}", message),
+            VerifyError::Options(message) => write!(f, "{}", message),
+            VerifyError::Verifier(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// Verifies the `add` proof `proof`, as StarkProof::to_bytes writes it, against `public_inputs`,
+// with the trace checks the stark crate's verifier makes before the STARK.
+pub fn verify_add(proof: &[u8], public_inputs: AddPublicInputs) -> Result<(), VerifyError> {
+    let proof = StarkProof::from_bytes(proof).map_err(VerifyError::Proof)?;
+    check_field_extension(proof.options().field_extension()).map_err(VerifyError::Options)?;
+    check_fresh_trace(
+        proof.trace_layout().main_trace_width(),
+        proof.trace_length(),
+        &public_inputs.result,
+        &public_inputs.modulus,
+        public_inputs.packing,
+    )
+    .map_err(VerifyError::Shape)?;
+    winter_verifier::verify::<FreshAir<AddPublicInputs>>(proof, public_inputs)
+        .map_err(VerifyError::Verifier)
+}
@@ -0,0 +1,19 @@
+// Copyright Vesnica
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// wasm-bindgen bindings of the no_std verifier, for browsers and Node services which verify
+// `add` proofs client-side without the prover. Build with `wasm-pack build wasm`.
+
+use wasm_bindgen::prelude::*;
+
+// Verifies the `proof` field of an `add` proof file against its results, given as JSON of the
+// `result`, `modulus`, `scheme` and `packing` fields of the file (see json.rs of
+// stark-he-verifier).
+// Throws an Error with the reason of a rejected proof.
+#[wasm_bindgen]
+pub fn verify(proof_b64: &str, result_json: &str) -> Result<(), JsError> {
+    stark_he_verifier::json::verify_json(proof_b64, result_json)
+        .map_err(|err| JsError::new(&err.to_string()))
+}